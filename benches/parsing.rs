@@ -0,0 +1,68 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use rust_sniffer::protocols::analyze_frame_control;
+use rust_sniffer::protocols::ethernet::EthernetFrame;
+use rust_sniffer::protocols::ipv4::IPv4Packet;
+use rust_sniffer::protocols::ipv6::IPv6Packet;
+use rust_sniffer::testutil;
+
+/// A rough mix of what a busy LAN segment looks like
+fn mixed_corpus() -> Vec<Vec<u8>> {
+    let mut corpus = Vec::new();
+    for i in 0..100 {
+        match i % 10 {
+            0..=5 => {
+                let payload = vec![0xab; if i % 2 == 0 { 0 } else { 1400 }];
+                corpus.push(testutil::ipv4_tcp_frame(50000, 443, testutil::TCP_ACK, &payload))
+            }
+            6..=7 => corpus.push(testutil::ipv6_udp_frame(54321, 443, &[0xcd; 64])),
+            8 => corpus.push(testutil::dns_query_frame(i as u16, "example.com")),
+            _ => corpus.push(testutil::arp_request(testutil::client_v4(), testutil::server_v4())),
+        }
+    }
+    corpus
+}
+
+fn bench_layers(c: &mut Criterion) {
+    let ipv4 = testutil::ipv4_tcp_frame(50000, 443, testutil::TCP_SYN, &[0; 64]);
+    let ipv6 = testutil::ipv6_udp_frame(54321, 53, &[0; 64]);
+
+    let mut group = c.benchmark_group("layers");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("ethernet", |b| {
+        b.iter(|| EthernetFrame::parse(black_box(&ipv4)).map(|f| f.ether_type()))
+    });
+    group.bench_function("ipv4_fields", |b| {
+        b.iter(|| IPv4Packet::parse(black_box(&ipv4[14..])).map(|p| p.get_control_fields()))
+    });
+    group.bench_function("ipv6_fields", |b| {
+        b.iter(|| IPv6Packet::parse(black_box(&ipv6[14..])).map(|p| p.get_control_fields()))
+    });
+    group.finish();
+}
+
+fn bench_pipeline(c: &mut Criterion) {
+    let corpus = mixed_corpus();
+    let bytes: usize = corpus.iter().map(Vec::len).sum();
+
+    let mut group = c.benchmark_group("pipeline");
+    group.throughput(Throughput::Elements(corpus.len() as u64));
+    group.bench_function("mixed_packets", |b| {
+        b.iter(|| {
+            for frame in &corpus {
+                black_box(analyze_frame_control(black_box(frame)));
+            }
+        })
+    });
+    group.throughput(Throughput::Bytes(bytes as u64));
+    group.bench_function("mixed_bytes", |b| {
+        b.iter(|| {
+            for frame in &corpus {
+                black_box(analyze_frame_control(black_box(frame)));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_layers, bench_pipeline);
+criterion_main!(benches);
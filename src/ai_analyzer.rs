@@ -1,11 +1,20 @@
 use std::error::Error;
 use pcap::Packet;
 use serde::{Deserialize, Serialize};
-use reqwest;
+use crate::ask::{QueryResult, TrafficQuery};
+use crate::config::AiConfig;
+use crate::error::CaptureError;
+use crate::filter::{validate_bpf, DisplayFilter, FIELD_NAMES};
+use crate::flow_review::FlowReviewRequest;
+use crate::localnet::LocalNetwork;
+use std::fmt;
 
+#[derive(Clone)]
 pub struct AIAnalyzer {
     api_key: String,
     client: reqwest::Client,
+    model: String,
+    max_tokens: u32,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -13,8 +22,59 @@ pub struct SecurityAnalysis {
     pub security_score: f32,
     pub potential_threats: Vec<String>,
     pub recommendations: Vec<String>,
+    /// MITRE ATT&CK technique IDs the threats match; older responses may leave it out
+    #[serde(default)]
+    pub attack_techniques: Vec<String>,
+}
+
+/// Filters the model proposed for a question asked in plain words (`--ask-filter`)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FilterSuggestion {
+    /// libpcap expression, applied in the kernel
+    #[serde(default)]
+    pub bpf: Option<String>,
+    /// Display filter, for what BPF can't express
+    #[serde(default)]
+    pub display_filter: Option<String>,
+    #[serde(default)]
+    pub explanation: String,
+}
+
+impl FilterSuggestion {
+    /// Checks that libpcap compiles the BPF and the display filter parses,
+    /// and that at least one of them was given
+    pub fn validate(&self) -> Result<(), CaptureError> {
+        if self.bpf.is_none() && self.display_filter.is_none() {
+            return Err(CaptureError::InputError("The AI did not suggest any filter".to_string()));
+        }
+        if let Some(bpf) = &self.bpf {
+            validate_bpf(bpf)?;
+        }
+        if let Some(display) = &self.display_filter {
+            DisplayFilter::parse(display)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for FilterSuggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "BPF filter:     {}", self.bpf.as_deref().unwrap_or("-"))?;
+        writeln!(f, "Display filter: {}", self.display_filter.as_deref().unwrap_or("-"))?;
+        write!(f, "{}", self.explanation)
+    }
 }
 
+// How the model is asked to answer
+const RESPONSE_FORMAT: &str = "Provide your analysis in the following JSON format:\n\
+{\n\
+  \"security_score\": <float between 0.0 (insecure) to 1.0 (secure)>,\n\
+  \"potential_threats\": [<list of potential threat strings>],\n\
+  \"attack_techniques\": [<MITRE ATT&CK technique IDs the threats match, e.g. \"T1046\" or \"T1071.001\">],\n\
+  \"recommendations\": [<list of recommendation strings>]\n\
+}\n\n\
+Return only valid JSON without any additional text.";
+
 // Request structure for the deepseek API
 #[derive(Serialize)]
 struct DeepseekRequest {
@@ -39,10 +99,24 @@ impl AIAnalyzer {
         Self {
             api_key: api_key.to_string(),
             client: reqwest::Client::new(),
+            model: "deepseek-coder".to_string(),
+            max_tokens: 1000,
+        }
+    }
+
+    /// Applies the `[ai]` settings from the configuration file; unset ones are kept
+    pub fn configure(&mut self, config: &AiConfig) {
+        if let Some(model) = &config.model {
+            self.model = model.clone();
+        }
+        if let Some(max_tokens) = config.max_tokens {
+            self.max_tokens = max_tokens;
         }
     }
 
-    pub async fn analyze_packet_security(&self, packet: &Packet<'_>) -> Result<SecurityAnalysis, Box<dyn Error>> {
+    /// Asks the model about one packet, telling it what is known of the local
+    /// network so gateway and resolver traffic is judged in context
+    pub async fn analyze_packet_security(&self, packet: &Packet<'_>, network: &LocalNetwork) -> Result<SecurityAnalysis, Box<dyn Error + Send + Sync>> {
         // Extract relevant packet data for analysis
         let packet_info = format!(
             "Packet length: {}, Timestamp: {}.{}, Data (first 50 bytes, hex): {:?}",
@@ -55,21 +129,121 @@ impl AIAnalyzer {
         // Create a prompt for the AI model
         let prompt = format!(
             "You are a network security expert. Analyze the security of this network packet:\n\n{}\n\n\
-            Provide your analysis in the following JSON format:\n\
+            About the network it was captured on: {}\n\n{}",
+            packet_info,
+            network,
+            RESPONSE_FORMAT
+        );
+        self.complete(prompt).await
+    }
+
+    /// Asks the model about a whole conversation: its totals, how it ended and
+    /// the names, URLs and TLS fingerprints seen in it
+    pub async fn analyze_flow(&self, request: &FlowReviewRequest, network: &LocalNetwork) -> Result<SecurityAnalysis, Box<dyn Error + Send + Sync>> {
+        let flow = &request.flow;
+        let flow_info = format!(
+            "{}, started by {}:{}, {} packets/{} bytes ({} packets/{} bytes from the initiator) over {:.3}s, ended: {}",
+            flow.key,
+            flow.initiator.0,
+            flow.initiator.1,
+            flow.packets,
+            flow.bytes,
+            flow.packets_out,
+            flow.bytes_out,
+            flow.last - flow.first,
+            flow.end.name()
+        );
+        let flow_info = match &request.similarity {
+            Some(similarity) => format!("{}\nCompared with earlier flows on this network, it {}", flow_info, similarity),
+            None => flow_info,
+        };
+        let prompt = format!(
+            "You are a network security expert. Analyze the security of this network conversation:\n\n{}\n\
+            Application layer: {}\n\n\
+            About the network it was captured on: {}\n\n{}",
+            flow_info,
+            request.extractions,
+            network,
+            RESPONSE_FORMAT
+        );
+        self.complete(prompt).await
+    }
+
+    /// Asks the model to turn a question in plain words, e.g. "dns queries from
+    /// the printer subnet", into a BPF and/or display filter
+    pub async fn suggest_filter(&self, question: &str, network: &LocalNetwork) -> Result<FilterSuggestion, Box<dyn Error + Send + Sync>> {
+        let prompt = format!(
+            "You translate questions about network traffic into capture filters.\n\n\
+            Question: {}\n\n\
+            About the network: {}\n\n\
+            Use a libpcap BPF expression (as for tcpdump) where possible. For what BPF can't express, add a display filter: \
+            fields compared with ==, !=, <, <=, >, >= to numbers, IP addresses, CIDR subnets or MAC addresses, \
+            combined with and, or, not and parentheses, e.g. `udp.port == 53 and ip.src == 10.0.0.0/8`. \
+            Display filter fields: {}.\n\n\
+            Provide your answer in the following JSON format:\n\
+            {{\"bpf\": <BPF expression or null>, \"display_filter\": <display filter or null>, \"explanation\": <one sentence>}}\n\n\
+            Return only valid JSON without any additional text.",
+            question,
+            network,
+            FIELD_NAMES.join(", ")
+        );
+        let text = self.request(prompt).await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Turns a chat question into a query over the stored traffic. The model
+    /// only sees what the store holds in general, not the packets.
+    pub async fn plan_query(&self, question: &str, store: &str) -> Result<TrafficQuery, Box<dyn Error + Send + Sync>> {
+        let prompt = format!(
+            "You answer questions about captured network traffic by writing a query that runs locally.\n\n\
+            Question: {}\n\n\
+            The traffic store holds: {}\n\n\
+            Provide the query in the following JSON format:\n\
             {{\n\
-              \"security_score\": <float between 0.0 (insecure) to 1.0 (secure)>,\n\
-              \"potential_threats\": [<list of potential threat strings>],\n\
-              \"recommendations\": [<list of recommendation strings>]\n\
+              \"last_secs\": <only the last N seconds before the newest packet, or null for everything>,\n\
+              \"ip\": <address or CIDR network the packets must be to or from, or null>,\n\
+              \"port\": <port the packets must use, or null>,\n\
+              \"proto\": <\"tcp\", \"udp\", \"icmp\", \"icmpv6\" or null>,\n\
+              \"name\": <substring of a DNS query or TLS server name, or null>,\n\
+              \"group_by\": <\"host\", \"pair\", \"port\" or \"name\">,\n\
+              \"metric\": <\"packets\", \"bytes\", \"flows\", \"peers\" or \"external_peers\">,\n\
+              \"limit\": <number of rows, at most 50>\n\
             }}\n\n\
-            Return only valid JSON without any additional text.", 
-            packet_info
+            Return only valid JSON without any additional text.",
+            question,
+            store
         );
-        
+        let text = self.request(prompt).await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Answers a chat question in a few sentences from the result of the query planned for it
+    pub async fn summarize_query(&self, question: &str, query: &TrafficQuery, result: &QueryResult) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let prompt = format!(
+            "You are a network analyst. Answer the question in a few sentences, using only the query result.\n\n\
+            Question: {}\n\n\
+            Query: {}\n\n\
+            Result: {}",
+            question,
+            serde_json::to_string(query)?,
+            serde_json::to_string(result)?
+        );
+        Ok(self.request(prompt).await?.trim().to_string())
+    }
+
+    /// Sends a prompt and parses the JSON analysis the model answers with
+    async fn complete(&self, prompt: String) -> Result<SecurityAnalysis, Box<dyn Error + Send + Sync>> {
+        let text = self.request(prompt).await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Sends a prompt and returns the model's answer
+    async fn request(&self, prompt: String) -> Result<String, Box<dyn Error + Send + Sync>> {
         // Create request payload
         let request_payload = DeepseekRequest {
-            model: "deepseek-coder".to_string(),
+            model: self.model.clone(),
             prompt,
-            max_tokens: 1000,
+            max_tokens: self.max_tokens,
         };
 
         // Make the API request
@@ -81,10 +255,25 @@ impl AIAnalyzer {
             .json::<DeepseekResponse>()
             .await?;
         
-        // Parse the AI response
-        let response_text = response.to_string().await?;
-        let security_analysis: SecurityAnalysis = serde_json::from_str(response_text)?;
-        
-        Ok(security_analysis)
+        let choice = response.choices.into_iter().next().ok_or("The API returned no answer")?;
+        Ok(choice.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_suggested_filters() {
+        let suggestion: FilterSuggestion =
+            serde_json::from_str(r#"{"bpf": null, "display_filter": "udp.dstport == 53 and ip.src == 10.20.0.0/24", "explanation": "DNS queries from the printers"}"#).unwrap();
+        assert!(suggestion.validate().is_ok());
+        assert!(suggestion.to_string().starts_with("BPF filter:     -\nDisplay filter: udp.dstport == 53"));
+
+        let invalid = FilterSuggestion { display_filter: Some("dns.qry.name == printer".to_string()), ..suggestion.clone() };
+        assert!(invalid.validate().is_err());
+        let empty = FilterSuggestion { display_filter: None, ..suggestion };
+        assert!(empty.validate().is_err());
     }
 }
@@ -0,0 +1,124 @@
+use crate::flow::FlowRecord;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+/// How serious an alert is
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Low,
+    #[default]
+    Medium,
+    High,
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Low => write!(f, "LOW"),
+            Severity::Medium => write!(f, "MEDIUM"),
+            Severity::High => write!(f, "HIGH"),
+            Severity::Critical => write!(f, "CRITICAL"),
+        }
+    }
+}
+
+/// An alert raised during a capture, as handed to the notification sinks
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alert {
+    /// Capture time of the packet that raised it, in seconds since the epoch
+    pub time: f64,
+    pub severity: Severity,
+    /// What raised it: `builtin`, `rule` or `threshold`
+    pub kind: &'static str,
+    pub message: String,
+    /// Namespace the packet was captured in, when capturing in several
+    pub source: Option<String>,
+    /// Hosts the alert points at, exported as indicators
+    pub hosts: Vec<IpAddr>,
+    /// MITRE ATT&CK technique IDs
+    pub techniques: Vec<String>,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(source) = &self.source {
+            write!(f, "[{}] ", source)?;
+        }
+        write!(f, "{}", self.message)?;
+        if !self.techniques.is_empty() {
+            write!(f, " [ATT&CK {}]", self.techniques.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A destination for alerts besides the log. Sinks are shared by every
+/// capture thread; delivery problems are the sink's to log, never the
+/// capture's to handle.
+pub trait AlertSink: Send {
+    fn name(&self) -> &str;
+
+    fn send(&mut self, alert: &Alert);
+
+    /// Called when the capture stops, for sinks that batch alerts
+    fn flush(&mut self) {}
+
+    /// Whether the sink also takes a summary of every finished flow
+    fn wants_flows(&self) -> bool {
+        false
+    }
+
+    fn send_flow(&mut self, _flow: &FlowRecord) {}
+}
+
+/// The configured sinks, each behind its own lock
+#[derive(Default)]
+pub struct AlertSinks {
+    sinks: Vec<Mutex<Box<dyn AlertSink>>>,
+}
+
+impl AlertSinks {
+    pub fn add(&mut self, sink: Box<dyn AlertSink>) {
+        self.sinks.push(Mutex::new(sink));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    pub fn send(&self, alert: &Alert) {
+        for sink in &self.sinks {
+            sink.lock().unwrap_or_else(|e| e.into_inner()).send(alert);
+        }
+    }
+
+    pub fn wants_flows(&self) -> bool {
+        self.sinks.iter().any(|sink| sink.lock().unwrap_or_else(|e| e.into_inner()).wants_flows())
+    }
+
+    pub fn send_flow(&self, flow: &FlowRecord) {
+        for sink in &self.sinks {
+            let mut sink = sink.lock().unwrap_or_else(|e| e.into_inner());
+            if sink.wants_flows() {
+                sink.send_flow(flow);
+            }
+        }
+    }
+
+    pub fn flush(&self) {
+        for sink in &self.sinks {
+            sink.lock().unwrap_or_else(|e| e.into_inner()).flush();
+        }
+    }
+}
+
+impl fmt::Debug for AlertSinks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = self.sinks.iter().map(|s| s.lock().unwrap_or_else(|e| e.into_inner()).name().to_string()).collect();
+        f.debug_tuple("AlertSinks").field(&names).finish()
+    }
+}
@@ -0,0 +1,330 @@
+use crate::capfile::{create_writer, CaptureReader};
+use crate::error::CaptureError;
+use crate::protocols::ethernet::MacAddress;
+use crate::protocols::null::{NullHeader, DLT_LOOP, DLT_NULL};
+use crate::protocols::sll::{SllHeader, DLT_LINUX_SLL, DLT_LINUX_SLL2};
+use crate::protocols::summary::PacketSummary;
+use crate::replay::{ethernet_payload, rewrite_ip_packet};
+use aes::cipher::{BlockEncrypt, KeyInit};
+use aes::Aes128;
+use log::info;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+/// What happens to application payloads (bytes after the transport header)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PayloadPolicy {
+    #[default]
+    Keep,
+    /// Overwrite with zeros, keeping lengths and fixing checksums
+    Zero,
+    /// Cut the captured packet after this many payload bytes
+    Truncate(usize),
+}
+
+/// Rewrites packets so captures can be shared: IP addresses are mapped with
+/// the prefix-preserving Crypto-PAn scheme (hosts in the same subnet stay in
+/// the same subnet), unicast MACs are replaced with random locally
+/// administered addresses, and payloads can be zeroed or truncated.
+/// The same key always produces the same mapping.
+pub struct Anonymizer {
+    cipher: Aes128,
+    pad: u128,
+    payload: PayloadPolicy,
+    ips: HashMap<IpAddr, IpAddr>,
+    macs: HashMap<MacAddress, MacAddress>,
+}
+
+impl Anonymizer {
+    pub fn new(key: &[u8; 32], payload: PayloadPolicy) -> Self {
+        let cipher = Aes128::new(key[..16].into());
+        let mut pad = [0u8; 16];
+        pad.copy_from_slice(&key[16..]);
+        let mut anonymizer = Anonymizer {
+            cipher,
+            pad: 0,
+            payload,
+            ips: HashMap::new(),
+            macs: HashMap::new(),
+        };
+        anonymizer.pad = anonymizer.encrypt(u128::from_be_bytes(pad));
+        anonymizer
+    }
+
+    fn encrypt(&self, block: u128) -> u128 {
+        let mut block = block.to_be_bytes().into();
+        self.cipher.encrypt_block(&mut block);
+        u128::from_be_bytes(block.into())
+    }
+
+    /// Crypto-PAn over the top `bits` bits of `addr`: each output bit is the
+    /// input bit flipped by a pseudorandom function of the preceding prefix
+    fn prefix_preserving(&self, addr: u128, bits: u32) -> u128 {
+        let mut flips = 0u128;
+        for i in 0..bits {
+            let mask = if i == 0 { 0 } else { !0u128 << (128 - i) };
+            let input = (addr & mask) | (self.pad & !mask);
+            flips |= (self.encrypt(input) >> 127) << (127 - i);
+        }
+        addr ^ flips
+    }
+
+    /// Maps an address; unspecified, broadcast and multicast addresses are kept
+    pub fn anonymize_ip(&mut self, addr: IpAddr) -> IpAddr {
+        if addr.is_unspecified() || addr.is_multicast() || addr == IpAddr::V4(Ipv4Addr::BROADCAST) {
+            return addr;
+        }
+        if let Some(mapped) = self.ips.get(&addr) {
+            return *mapped;
+        }
+        let mapped = match addr {
+            IpAddr::V4(v4) => {
+                let bits = (u32::from(v4) as u128) << 96;
+                IpAddr::V4(Ipv4Addr::from((self.prefix_preserving(bits, 32) >> 96) as u32))
+            }
+            IpAddr::V6(v6) => IpAddr::V6(Ipv6Addr::from(self.prefix_preserving(u128::from(v6), 128))),
+        };
+        self.ips.insert(addr, mapped);
+        mapped
+    }
+
+    /// Maps unicast MACs to locally administered addresses; group addresses are kept
+    pub fn anonymize_mac(&mut self, mac: MacAddress) -> MacAddress {
+        let octets = mac.octets();
+        if octets[0] & 0x01 != 0 {
+            return mac;
+        }
+        if let Some(mapped) = self.macs.get(&mac) {
+            return *mapped;
+        }
+        let mut block = [0u8; 16];
+        block[0] = 0x4d; // domain-separate MAC blocks from Crypto-PAn inputs
+        block[10..].copy_from_slice(&octets);
+        let random = self.encrypt(u128::from_be_bytes(block)).to_be_bytes();
+        let mut new = [0u8; 6];
+        new.copy_from_slice(&random[..6]);
+        new[0] = (new[0] & 0xFC) | 0x02;
+        let mapped = MacAddress::new(new);
+        self.macs.insert(mac, mapped);
+        mapped
+    }
+
+    /// Anonymizes a packet of the given datalink in place; truncation shortens the buffer
+    pub fn anonymize_frame(&mut self, datalink: i32, frame: &mut Vec<u8>) {
+        let Some((ether_type, ip_offset)) = self.anonymize_link_header(datalink, frame) else { return };
+        if ether_type == 0x0806 {
+            self.anonymize_arp(&mut frame[ip_offset..]);
+            return;
+        }
+
+        let payload_offset = PacketSummary::from_link(datalink, frame)
+            .filter(|s| s.src_port.is_some())
+            .map(|s| s.payload_offset);
+        let zero = self.payload == PayloadPolicy::Zero && payload_offset.is_some();
+        if let Some(offset) = payload_offset.filter(|_| zero) {
+            frame[offset..].fill(0);
+        }
+        rewrite_ip_packet(ether_type, &mut frame[ip_offset..], |addr| self.anonymize_ip(addr), zero);
+
+        if let (PayloadPolicy::Truncate(keep), Some(offset)) = (self.payload, payload_offset) {
+            frame.truncate(offset + keep);
+        }
+    }
+
+    /// Maps the MACs of the link-layer header, returning the EtherType and
+    /// offset of the network header
+    fn anonymize_link_header(&mut self, datalink: i32, frame: &mut [u8]) -> Option<(u16, usize)> {
+        let mac_offsets = match datalink {
+            1 if frame.len() >= 14 => vec![0, 6],
+            DLT_LINUX_SLL | DLT_LINUX_SLL2 => {
+                let sll = SllHeader::parse(datalink, frame).ok()?;
+                let at = if datalink == DLT_LINUX_SLL { 6 } else { 12 };
+                if sll.address().len() == 6 { vec![at] } else { Vec::new() }
+            }
+            _ => Vec::new(),
+        };
+        for at in mac_offsets {
+            let mut mac = [0u8; 6];
+            mac.copy_from_slice(&frame[at..at + 6]);
+            frame[at..at + 6].copy_from_slice(&self.anonymize_mac(MacAddress::new(mac)).octets());
+        }
+        match datalink {
+            1 => ethernet_payload(frame),
+            DLT_LINUX_SLL | DLT_LINUX_SLL2 => SllHeader::parse(datalink, frame).ok().map(|sll| (sll.protocol(), sll.header_len())),
+            DLT_NULL | DLT_LOOP => NullHeader::parse(datalink, frame).ok().map(|null| (null.ether_type(), 4)),
+            _ => None,
+        }
+    }
+
+    /// Sender and target addresses of Ethernet/IPv4 ARP
+    fn anonymize_arp(&mut self, arp: &mut [u8]) {
+        if arp.len() < 28 || arp[4] != 6 || arp[5] != 4 {
+            return;
+        }
+        for (mac_at, ip_at) in [(8, 14), (18, 24)] {
+            let mut mac = [0u8; 6];
+            mac.copy_from_slice(&arp[mac_at..mac_at + 6]);
+            arp[mac_at..mac_at + 6].copy_from_slice(&self.anonymize_mac(MacAddress::new(mac)).octets());
+            let ip = IpAddr::V4(Ipv4Addr::new(arp[ip_at], arp[ip_at + 1], arp[ip_at + 2], arp[ip_at + 3]));
+            if let IpAddr::V4(mapped) = self.anonymize_ip(ip) {
+                arp[ip_at..ip_at + 4].copy_from_slice(&mapped.octets());
+            }
+        }
+    }
+}
+
+/// Parses a 32-byte key given as 64 hex digits
+pub fn parse_key(hex: &str) -> Result<[u8; 32], CaptureError> {
+    let hex = hex.trim();
+    let invalid = || CaptureError::InputError("Anonymization key must be 64 hex digits".to_string());
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(key)
+}
+
+/// A fresh random key from the operating system
+pub fn random_key() -> Result<[u8; 32], CaptureError> {
+    use std::io::Read;
+    let mut key = [0u8; 32];
+    std::fs::File::open("/dev/urandom")
+        .and_then(|mut f| f.read_exact(&mut key))
+        .map_err(|e| CaptureError::Other(format!("Cannot generate key: {}", e)))?;
+    Ok(key)
+}
+
+/// Writes an anonymized copy of `input` to `output`, returning the packet count
+pub fn anonymize_file(input: &Path, output: &Path, anonymizer: &mut Anonymizer) -> Result<u64, CaptureError> {
+    let reader = CaptureReader::open(input)?;
+    let datalink = reader.linktype().0;
+    if !matches!(datalink, 1 | DLT_LINUX_SLL | DLT_LINUX_SLL2 | DLT_NULL | DLT_LOOP) {
+        return Err(CaptureError::InputError(format!(
+            "{} has datalink {}; only Ethernet, Linux cooked and loopback captures can be anonymized",
+            input.display(),
+            datalink
+        )));
+    }
+    let mut writer = create_writer(reader.linktype(), output)?;
+    let mut count = 0;
+    for mut packet in reader {
+        anonymizer.anonymize_frame(datalink, &mut packet.data);
+        packet.header.caplen = packet.data.len() as u32;
+        writer.write(&packet.as_packet());
+        count += 1;
+    }
+    writer.flush().map_err(|e| CaptureError::PcapError(e.to_string()))?;
+    info!("Anonymized {} packets from {} into {}", count, input.display(), output.display());
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::internet_checksum;
+    use crate::testutil;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    #[test]
+    fn preserves_prefixes_consistently() {
+        let mut anon = Anonymizer::new(&KEY, PayloadPolicy::Keep);
+        let a = anon.anonymize_ip("10.1.2.3".parse().unwrap());
+        let b = anon.anonymize_ip("10.1.2.200".parse().unwrap());
+        let c = anon.anonymize_ip("10.1.9.1".parse().unwrap());
+        let (IpAddr::V4(a), IpAddr::V4(b), IpAddr::V4(c)) = (a, b, c) else { panic!("family changed") };
+        assert_ne!(a, Ipv4Addr::new(10, 1, 2, 3));
+        assert_eq!(a.octets()[..3], b.octets()[..3]);
+        assert_eq!(a.octets()[..2], c.octets()[..2]);
+        assert_ne!(a.octets()[2], c.octets()[2]);
+
+        let mut again = Anonymizer::new(&KEY, PayloadPolicy::Keep);
+        assert_eq!(again.anonymize_ip("10.1.2.3".parse().unwrap()), IpAddr::V4(a));
+        let broadcast = IpAddr::V4(Ipv4Addr::BROADCAST);
+        assert_eq!(again.anonymize_ip(broadcast), broadcast);
+    }
+
+    #[test]
+    fn randomizes_unicast_macs_only() {
+        let mut anon = Anonymizer::new(&KEY, PayloadPolicy::Keep);
+        let mac = MacAddress::new(testutil::SRC_MAC);
+        let mapped = anon.anonymize_mac(mac);
+        assert_ne!(mapped, mac);
+        assert_eq!(mapped.octets()[0] & 0x03, 0x02);
+        assert_eq!(anon.anonymize_mac(mac), mapped);
+        let broadcast = MacAddress::new([0xff; 6]);
+        assert_eq!(anon.anonymize_mac(broadcast), broadcast);
+    }
+
+    #[test]
+    fn zeroes_payload_and_fixes_checksums() {
+        let mut frame = testutil::ipv4_udp_frame(5000, 53, b"secret");
+        let mut anon = Anonymizer::new(&KEY, PayloadPolicy::Zero);
+        anon.anonymize_frame(1, &mut frame);
+
+        let summary = PacketSummary::from_ethernet(&frame).unwrap();
+        assert_eq!(summary.payload(&frame), &[0u8; 6]);
+        assert_ne!(summary.src_ip, Some(IpAddr::V4(testutil::client_v4())));
+        assert_eq!(internet_checksum(&frame[14..34]), 0);
+        let (IpAddr::V4(src), IpAddr::V4(dst)) = (summary.src_ip.unwrap(), summary.dst_ip.unwrap()) else {
+            panic!("family changed")
+        };
+        let mut pseudo = Vec::new();
+        pseudo.extend_from_slice(&src.octets());
+        pseudo.extend_from_slice(&dst.octets());
+        pseudo.extend_from_slice(&[0, 17, 0, 14]);
+        pseudo.extend_from_slice(&frame[34..]);
+        assert_eq!(internet_checksum(&pseudo), 0);
+    }
+
+    #[test]
+    fn rewrites_vlan_tagged_addresses() {
+        let inner = testutil::ipv4_udp_frame(1, 2, b"");
+        let mut frame = inner[..12].to_vec();
+        frame.extend_from_slice(&[0x81, 0x00, 0x00, 0x64]);
+        frame.extend_from_slice(&inner[12..]);
+        Anonymizer::new(&KEY, PayloadPolicy::Keep).anonymize_frame(1, &mut frame);
+        let summary = PacketSummary::from_ethernet(&frame).unwrap();
+        assert_ne!(summary.src_ip, Some(IpAddr::V4(testutil::client_v4())));
+        assert_eq!(internet_checksum(&frame[18..38]), 0);
+    }
+
+    #[test]
+    fn anonymizes_cooked_captures() {
+        let frame = testutil::ipv4_udp_frame(5000, 53, b"q");
+        let mut sll2 = vec![0x08, 0x00, 0, 0, 0, 0, 0, 2, 0, 1, 0, 6];
+        sll2.extend_from_slice(&frame[6..12]);
+        sll2.extend_from_slice(&[0, 0]);
+        sll2.extend_from_slice(&frame[14..]);
+        Anonymizer::new(&KEY, PayloadPolicy::Zero).anonymize_frame(DLT_LINUX_SLL2, &mut sll2);
+        let summary = PacketSummary::from_link(DLT_LINUX_SLL2, &sll2).unwrap();
+        assert_ne!(summary.src_mac, MacAddress::new(testutil::SRC_MAC));
+        assert_ne!(summary.src_ip, Some(IpAddr::V4(testutil::client_v4())));
+        assert_eq!(summary.payload(&sll2), &[0]);
+        assert_eq!(internet_checksum(&sll2[20..40]), 0);
+    }
+
+    #[test]
+    fn truncates_payload() {
+        let mut frame = testutil::ipv4_tcp_frame(40000, 80, testutil::TCP_ACK, b"GET / HTTP/1.1\r\n");
+        let header_len = frame.len() - 16;
+        Anonymizer::new(&KEY, PayloadPolicy::Truncate(4)).anonymize_frame(1, &mut frame);
+        assert_eq!(frame.len(), header_len + 4);
+        assert_eq!(&frame[header_len..], b"GET ");
+    }
+
+    #[test]
+    fn anonymizes_arp_and_parses_keys() {
+        let mut frame = testutil::arp_request(testutil::client_v4(), testutil::server_v4());
+        Anonymizer::new(&KEY, PayloadPolicy::Keep).anonymize_frame(1, &mut frame);
+        assert_ne!(&frame[28..32], &testutil::client_v4().octets());
+
+        assert_eq!(parse_key(&"07".repeat(32)).unwrap(), KEY);
+        assert!(parse_key("abcd").is_err());
+        assert!(parse_key(&"zz".repeat(32)).is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use crate::error::CaptureError;
+use crate::index::{parse_protocol, CaptureIndex, IndexEntry};
+use crate::localnet::LocalNetwork;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+/// Rows returned when a query doesn't say
+const DEFAULT_LIMIT: usize = 10;
+
+/// Rows any query may return, so answers stay small enough for a prompt
+const MAX_LIMIT: usize = 50;
+
+/// What the rows of a query are keyed by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    /// Each address, counting packets it sent and received
+    Host,
+    /// Both addresses of a conversation
+    Pair,
+    /// The lower port of each packet, usually the service
+    Port,
+    /// DNS question and TLS server names
+    Name,
+}
+
+/// What is counted per row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Packets,
+    Bytes,
+    /// Distinct conversations
+    Flows,
+    /// Distinct addresses talked to
+    Peers,
+    /// Distinct addresses outside the local network talked to
+    ExternalPeers,
+}
+
+/// A question about stored traffic, as the AI plans it; it runs locally over
+/// the capture indexes, so no packet leaves the machine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrafficQuery {
+    /// Only the last this many seconds before the newest packet
+    #[serde(default)]
+    pub last_secs: Option<f64>,
+    /// Packets to or from this address or network
+    #[serde(default)]
+    pub ip: Option<IpNet>,
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// `tcp`, `udp`, `icmp`, `icmpv6` or a protocol number
+    #[serde(default)]
+    pub proto: Option<String>,
+    /// Substring of a DNS question or TLS server name
+    #[serde(default)]
+    pub name: Option<String>,
+    pub group_by: GroupBy,
+    pub metric: Metric,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// One row of a query result
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueryRow {
+    pub key: String,
+    pub value: u64,
+}
+
+/// The rows of a query, largest first, with the time span they cover
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueryResult {
+    /// Seconds since the epoch of the first and last packet considered
+    pub start: f64,
+    pub end: f64,
+    pub packets: u64,
+    pub rows: Vec<QueryRow>,
+}
+
+/// Index entries of the stored captures, queried to answer chat questions
+pub struct TrafficStore {
+    entries: Vec<IndexEntry>,
+    network: LocalNetwork,
+}
+
+impl TrafficStore {
+    /// Loads the sidecar indexes of `files`, building those that are missing
+    pub fn load(files: &[PathBuf], network: LocalNetwork) -> Result<Self, CaptureError> {
+        let mut entries = Vec::new();
+        for file in files {
+            entries.extend(CaptureIndex::load_or_build(file)?.entries);
+        }
+        Ok(TrafficStore::new(entries, network))
+    }
+
+    pub fn new(mut entries: Vec<IndexEntry>, network: LocalNetwork) -> Self {
+        entries.sort_by(|a, b| a.ts.total_cmp(&b.ts));
+        TrafficStore { entries, network }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Outside the configured local prefixes, or outside private, link-local
+    /// and loopback ranges when none are configured; never multicast
+    pub fn is_external(&self, ip: IpAddr) -> bool {
+        if ip.is_multicast() || ip.is_loopback() || ip.is_unspecified() {
+            return false;
+        }
+        if !self.network.prefixes().is_empty() {
+            return !self.network.is_local(ip);
+        }
+        match ip {
+            IpAddr::V4(ip) => !(ip.is_private() || ip.is_link_local() || ip.is_broadcast()),
+            IpAddr::V6(ip) => !(ip.segments()[0] & 0xfe00 == 0xfc00 || ip.segments()[0] & 0xffc0 == 0xfe80),
+        }
+    }
+
+    /// Runs a query over the entries it selects
+    pub fn run(&self, query: &TrafficQuery) -> Result<QueryResult, CaptureError> {
+        let proto = query.proto.as_deref().map(parse_protocol).transpose()?;
+        let newest = self.entries.last().map_or(0.0, |e| e.ts);
+        let since = query.last_secs.map_or(f64::MIN, |secs| newest - secs);
+        let name = query.name.as_ref().map(|n| n.to_lowercase());
+        let selected = self.entries.iter().filter(|e| {
+            e.ts >= since
+                && query.ip.is_none_or(|net| [e.src, e.dst].iter().flatten().any(|ip| net.contains(ip)))
+                && query.port.is_none_or(|p| e.sport == Some(p) || e.dport == Some(p))
+                && proto.is_none_or(|p| e.proto == Some(p))
+                && name.as_ref().is_none_or(|n| [&e.dns, &e.sni].iter().filter_map(|v| v.as_ref()).any(|v| v.to_lowercase().contains(n)))
+        });
+
+        let mut counts: BTreeMap<String, u64> = BTreeMap::new();
+        let mut sets: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let (mut packets, mut start, mut end) = (0, f64::MAX, f64::MIN);
+        for entry in selected {
+            packets += 1;
+            start = start.min(entry.ts);
+            end = end.max(entry.ts);
+            for (key, peer) in self.keys(query.group_by, entry) {
+                match query.metric {
+                    Metric::Packets => *counts.entry(key).or_default() += 1,
+                    Metric::Bytes => *counts.entry(key).or_default() += entry.len as u64,
+                    Metric::Flows => {
+                        if let Some(flow) = entry.flow_key() {
+                            sets.entry(key).or_default().insert(flow.to_string());
+                        }
+                    }
+                    Metric::Peers | Metric::ExternalPeers => {
+                        if let Some(peer) = peer.filter(|&ip| query.metric == Metric::Peers || self.is_external(ip)) {
+                            sets.entry(key).or_default().insert(peer.to_string());
+                        }
+                    }
+                }
+            }
+        }
+        counts.extend(sets.into_iter().map(|(key, set)| (key, set.len() as u64)));
+        let mut rows: Vec<QueryRow> = counts.into_iter().map(|(key, value)| QueryRow { key, value }).collect();
+        rows.sort_by(|a, b| b.value.cmp(&a.value).then_with(|| a.key.cmp(&b.key)));
+        rows.truncate(query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT));
+        if packets == 0 {
+            (start, end) = (0.0, 0.0);
+        }
+        Ok(QueryResult { start, end, packets, rows })
+    }
+
+    /// Row keys a packet counts towards, each with the address on the other side
+    fn keys(&self, group_by: GroupBy, entry: &IndexEntry) -> Vec<(String, Option<IpAddr>)> {
+        match group_by {
+            GroupBy::Host => [(entry.src, entry.dst), (entry.dst, entry.src)]
+                .into_iter()
+                .filter_map(|(host, peer)| Some((host?.to_string(), peer)))
+                .collect(),
+            GroupBy::Pair => match (entry.src, entry.dst) {
+                (Some(src), Some(dst)) => {
+                    let (a, b) = if src <= dst { (src, dst) } else { (dst, src) };
+                    vec![(format!("{} <-> {}", a, b), None)]
+                }
+                _ => Vec::new(),
+            },
+            GroupBy::Port => match (entry.sport, entry.dport) {
+                (Some(sport), Some(dport)) => vec![(sport.min(dport).to_string(), None)],
+                _ => Vec::new(),
+            },
+            GroupBy::Name => [&entry.dns, &entry.sni].into_iter().flatten().map(|name| (name.to_lowercase(), None)).collect(),
+        }
+    }
+}
+
+/// What the store holds, for the AI to plan queries: packets, time span and busiest hosts
+impl fmt::Display for TrafficStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (Some(first), Some(last)) = (self.entries.first(), self.entries.last()) else {
+            return write!(f, "no packets");
+        };
+        let hosts: BTreeSet<IpAddr> = self.entries.iter().flat_map(|e| [e.src, e.dst]).flatten().collect();
+        write!(
+            f,
+            "{} packets between {:.0} and {:.0} (seconds since the epoch, {:.0}s), {} addresses, local prefixes: {}",
+            self.entries.len(),
+            first.ts,
+            last.ts,
+            last.ts - first.ts,
+            hosts.len(),
+            match self.network.prefixes() {
+                [] => "private ranges".to_string(),
+                prefixes => prefixes.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(", "),
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(ts: f64, src: &str, dst: &str, dport: u16, len: u32) -> IndexEntry {
+        IndexEntry {
+            number: 0,
+            ts,
+            len,
+            src: Some(src.parse().unwrap()),
+            dst: Some(dst.parse().unwrap()),
+            proto: Some(6),
+            sport: Some(40000),
+            dport: Some(dport),
+            dns: None,
+            sni: None,
+        }
+    }
+
+    fn store() -> TrafficStore {
+        TrafficStore::new(
+            vec![
+                entry(0.0, "192.168.1.10", "93.184.216.34", 443, 1000),
+                entry(4000.0, "192.168.1.10", "1.1.1.1", 443, 100),
+                entry(4000.0, "192.168.1.20", "8.8.8.8", 443, 100),
+                entry(4100.0, "192.168.1.20", "9.9.9.9", 443, 100),
+                entry(4200.0, "192.168.1.20", "192.168.1.1", 53, 80),
+            ],
+            LocalNetwork::default(),
+        )
+    }
+
+    #[test]
+    fn counts_external_peers_in_a_window() {
+        let query: TrafficQuery = serde_json::from_str(r#"{"last_secs": 3600, "group_by": "host", "metric": "external_peers", "limit": 2}"#).unwrap();
+        let result = store().run(&query).unwrap();
+        assert_eq!((result.packets, result.start, result.end), (4, 4000.0, 4200.0));
+        let rows: Vec<(&str, u64)> = result.rows.iter().map(|r| (r.key.as_str(), r.value)).collect();
+        assert_eq!(rows, [("192.168.1.20", 2), ("192.168.1.10", 1)]);
+    }
+
+    #[test]
+    fn groups_by_port_and_pair() {
+        let store = store();
+        let query = TrafficQuery { last_secs: None, ip: None, port: None, proto: None, name: None, group_by: GroupBy::Port, metric: Metric::Bytes, limit: None };
+        assert_eq!(store.run(&query).unwrap().rows[0], QueryRow { key: "443".to_string(), value: 1300 });
+        let query = TrafficQuery { group_by: GroupBy::Pair, metric: Metric::Flows, ip: Some("192.168.1.1/32".parse().unwrap()), ..query };
+        assert_eq!(store.run(&query).unwrap().rows, [QueryRow { key: "192.168.1.1 <-> 192.168.1.20".to_string(), value: 1 }]);
+        assert!(store.is_external("9.9.9.9".parse().unwrap()) && !store.is_external("fe80::1".parse().unwrap()));
+    }
+}
@@ -0,0 +1,74 @@
+use crate::error::CaptureError;
+
+// MITRE ATT&CK techniques the built-in detectors map to
+pub const NETWORK_SERVICE_DISCOVERY: &str = "T1046";
+pub const APPLICATION_LAYER_PROTOCOL: &str = "T1071";
+pub const DNS: &str = "T1071.004";
+pub const EXFILTRATION_OVER_ALTERNATIVE_PROTOCOL: &str = "T1048";
+pub const UNSECURED_CREDENTIALS: &str = "T1552";
+pub const ADVERSARY_IN_THE_MIDDLE: &str = "T1557";
+pub const LLMNR_NBT_NS_POISONING: &str = "T1557.001";
+pub const ARP_CACHE_POISONING: &str = "T1557.002";
+pub const DOMAIN_GENERATION_ALGORITHMS: &str = "T1568.002";
+pub const PROTOCOL_TUNNELING: &str = "T1572";
+// ATT&CK for ICS
+pub const MODIFY_PARAMETER: &str = "T0836";
+pub const UNAUTHORIZED_COMMAND_MESSAGE: &str = "T0855";
+
+const NAMES: [(&str, &str); 12] = [
+    (NETWORK_SERVICE_DISCOVERY, "Network Service Discovery"),
+    (APPLICATION_LAYER_PROTOCOL, "Application Layer Protocol"),
+    (DNS, "Application Layer Protocol: DNS"),
+    (EXFILTRATION_OVER_ALTERNATIVE_PROTOCOL, "Exfiltration Over Alternative Protocol"),
+    (UNSECURED_CREDENTIALS, "Unsecured Credentials"),
+    (ADVERSARY_IN_THE_MIDDLE, "Adversary-in-the-Middle"),
+    (LLMNR_NBT_NS_POISONING, "LLMNR/NBT-NS Poisoning and SMB Relay"),
+    (ARP_CACHE_POISONING, "ARP Cache Poisoning"),
+    (DOMAIN_GENERATION_ALGORITHMS, "Dynamic Resolution: Domain Generation Algorithms"),
+    (PROTOCOL_TUNNELING, "Protocol Tunneling"),
+    (MODIFY_PARAMETER, "Modify Parameter"),
+    (UNAUTHORIZED_COMMAND_MESSAGE, "Unauthorized Command Message"),
+];
+
+/// Name of a technique the built-in detectors use
+pub fn technique_name(id: &str) -> Option<&'static str> {
+    NAMES.iter().find(|(known, _)| *known == id).map(|(_, name)| *name)
+}
+
+/// Whether `id` looks like a technique or sub-technique ID: `T1046`, `T1071.001`
+pub fn is_technique_id(id: &str) -> bool {
+    let Some(number) = id.strip_prefix('T') else { return false };
+    let (technique, sub) = match number.split_once('.') {
+        Some((technique, sub)) => (technique, Some(sub)),
+        None => (number, None),
+    };
+    let digits = |text: &str, len: usize| text.len() == len && text.bytes().all(|b| b.is_ascii_digit());
+    digits(technique, 4) && sub.is_none_or(|sub| digits(sub, 3))
+}
+
+/// Checks technique IDs from the configuration file
+pub fn validate(owner: &str, ids: &[String]) -> Result<(), CaptureError> {
+    match ids.iter().find(|id| !is_technique_id(id)) {
+        Some(id) => Err(CaptureError::InputError(format!("{}: '{}' is not an ATT&CK technique ID such as T1046 or T1071.001", owner, id))),
+        None => Ok(()),
+    }
+}
+
+/// ATT&CK page of a technique
+pub fn technique_url(id: &str) -> String {
+    format!("https://attack.mitre.org/techniques/{}/", id.replace('.', "/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_technique_ids() {
+        assert!(is_technique_id("T1046") && is_technique_id("T1071.001") && is_technique_id(UNAUTHORIZED_COMMAND_MESSAGE));
+        assert!(!is_technique_id("T104") && !is_technique_id("t1046") && !is_technique_id("T1071.1") && !is_technique_id("TA0011"));
+        assert!(validate("Rule 'x'", &["T1046".to_string(), "scan".to_string()]).is_err());
+        assert_eq!(technique_name(LLMNR_NBT_NS_POISONING), Some("LLMNR/NBT-NS Poisoning and SMB Relay"));
+        assert_eq!(technique_url("T1071.004"), "https://attack.mitre.org/techniques/T1071/004/");
+    }
+}
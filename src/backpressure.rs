@@ -0,0 +1,359 @@
+use crate::error::CaptureError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// How long deep parsing stays off after libpcap reports new drops
+const DEGRADE_HOLD: Duration = Duration::from_secs(1);
+
+/// What a pipeline stage does when its consumer can't keep up
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DropPolicy {
+    /// Wait for room, slowing the producer down
+    #[default]
+    Block,
+    /// Discard what arrives while the queue is full
+    DropNewest,
+    /// Discard the oldest queued item to make room
+    DropOldest,
+    /// Keep every packet but skip deep parsing while the queue is half full
+    /// (`packets` stage only)
+    Degrade,
+}
+
+/// Queue settings of one stage (`[pipeline.<stage>]`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StageConfig {
+    pub policy: DropPolicy,
+    /// Items queued before the policy applies
+    pub capacity: usize,
+}
+
+impl Default for StageConfig {
+    fn default() -> Self {
+        StageConfig { policy: DropPolicy::Block, capacity: 10_000 }
+    }
+}
+
+/// Backpressure settings from the configuration file (`[pipeline]`)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PipelineConfig {
+    /// Packets waiting for analysis: the queue from namespace captures, or
+    /// libpcap's buffer for a single capture
+    pub packets: StageConfig,
+    /// Packets waiting to be written to `--pipe`
+    pub pipe: StageConfig,
+    /// Alerts waiting for the email and chat senders
+    pub exporters: StageConfig,
+}
+
+impl PipelineConfig {
+    pub fn validate(&self) -> Result<(), CaptureError> {
+        for (name, stage) in [("packets", self.packets), ("pipe", self.pipe), ("exporters", self.exporters)] {
+            if stage.capacity == 0 {
+                return Err(CaptureError::InputError(format!("pipeline.{} needs a capacity of at least 1", name)));
+            }
+            if stage.policy == DropPolicy::Degrade && name != "packets" {
+                return Err(CaptureError::InputError(format!("pipeline.{} can't degrade; only packets have parsing to skip", name)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How much a stage has shed so far
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Shed {
+    /// Items that went through
+    pub passed: u64,
+    /// Items discarded by `drop-newest` or `drop-oldest`
+    pub dropped: u64,
+    /// Packets that skipped deep parsing
+    pub degraded: u64,
+    /// Times a producer had to wait for room
+    pub blocked: u64,
+}
+
+impl Shed {
+    pub fn nothing_shed(&self) -> bool {
+        self.dropped == 0 && self.degraded == 0 && self.blocked == 0
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    passed: AtomicU64,
+    dropped: AtomicU64,
+    degraded: AtomicU64,
+    blocked: AtomicU64,
+}
+
+impl Counters {
+    fn add(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn shed(&self) -> Shed {
+        Shed {
+            passed: self.passed.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            degraded: self.degraded.load(Ordering::Relaxed),
+            blocked: self.blocked.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counters of every stage created in this process, including the queues of
+/// sinks replaced by a configuration reload
+static STAGES: Mutex<Vec<(String, Arc<Counters>)>> = Mutex::new(Vec::new());
+
+fn register(stage: &str) -> Arc<Counters> {
+    let counters = Arc::new(Counters::default());
+    STAGES.lock().unwrap_or_else(|e| e.into_inner()).push((stage.to_string(), counters.clone()));
+    counters
+}
+
+/// What each stage has shed, keyed by stage name
+pub fn shed_report() -> BTreeMap<String, Shed> {
+    let mut report: BTreeMap<String, Shed> = BTreeMap::new();
+    for (name, counters) in STAGES.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        let shed = counters.shed();
+        let total = report.entry(name.clone()).or_default();
+        total.passed += shed.passed;
+        total.dropped += shed.dropped;
+        total.degraded += shed.degraded;
+        total.blocked += shed.blocked;
+    }
+    report
+}
+
+struct Queue<T> {
+    items: VecDeque<T>,
+    senders: usize,
+    receiving: bool,
+}
+
+struct Shared<T> {
+    queue: Mutex<Queue<T>>,
+    changed: Condvar,
+    config: StageConfig,
+    counters: Arc<Counters>,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, Queue<T>> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A bounded queue between two threads that applies the stage's policy when
+/// full, counting what it sheds under `stage` in [`shed_report`]
+pub fn channel<T>(stage: &str, config: StageConfig) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(Queue { items: VecDeque::new(), senders: 1, receiving: true }),
+        changed: Condvar::new(),
+        config,
+        counters: register(stage),
+    });
+    (Sender { shared: shared.clone() }, Receiver { shared })
+}
+
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Queues an item, or hands it back once the receiver is gone
+    pub fn send(&self, item: T) -> Result<(), T> {
+        let shared = &*self.shared;
+        let mut queue = shared.lock();
+        if queue.items.len() >= shared.config.capacity {
+            match shared.config.policy {
+                DropPolicy::DropNewest => {
+                    Counters::add(&shared.counters.dropped);
+                    return if queue.receiving { Ok(()) } else { Err(item) };
+                }
+                DropPolicy::DropOldest => {
+                    queue.items.pop_front();
+                    Counters::add(&shared.counters.dropped);
+                }
+                DropPolicy::Block | DropPolicy::Degrade => {
+                    Counters::add(&shared.counters.blocked);
+                    while queue.receiving && queue.items.len() >= shared.config.capacity {
+                        queue = shared.changed.wait(queue).unwrap_or_else(|e| e.into_inner());
+                    }
+                }
+            }
+        }
+        if !queue.receiving {
+            return Err(item);
+        }
+        queue.items.push_back(item);
+        Counters::add(&shared.counters.passed);
+        shared.changed.notify_all();
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Sender { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.shared.lock().senders -= 1;
+        self.shared.changed.notify_all();
+    }
+}
+
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// The next item, or `None` once every sender is gone and the queue is empty
+    pub fn recv(&self) -> Option<T> {
+        let mut queue = self.shared.lock();
+        loop {
+            if let Some(item) = queue.items.pop_front() {
+                self.shared.changed.notify_all();
+                return Some(item);
+            }
+            if queue.senders == 0 {
+                return None;
+            }
+            queue = self.shared.changed.wait(queue).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut queue = self.shared.lock();
+        loop {
+            if let Some(item) = queue.items.pop_front() {
+                self.shared.changed.notify_all();
+                return Ok(item);
+            }
+            if queue.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            queue = self.shared.changed.wait_timeout(queue, left).unwrap_or_else(|e| e.into_inner()).0;
+        }
+    }
+
+    /// Whether the packet about to be handled should skip deep parsing: the
+    /// stage degrades and its queue is at least half full
+    pub fn shed_load(&self) -> bool {
+        let shared = &*self.shared;
+        let congested = shared.config.policy == DropPolicy::Degrade && shared.lock().items.len() * 2 >= shared.config.capacity;
+        if congested {
+            Counters::add(&shared.counters.degraded);
+        }
+        congested
+    }
+}
+
+impl<T> Iterator for Receiver<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.recv()
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut queue = self.shared.lock();
+        queue.receiving = false;
+        queue.items.clear();
+        self.shared.changed.notify_all();
+    }
+}
+
+/// Degrades a single live capture, which has no queue of its own: libpcap's
+/// buffer fills instead, so deep parsing is skipped for a moment whenever
+/// its drop counter rises
+pub struct DropWatch {
+    degrade: bool,
+    dropped: Option<u32>,
+    until: Option<Instant>,
+    counters: Arc<Counters>,
+}
+
+impl DropWatch {
+    pub fn new(config: StageConfig) -> Self {
+        DropWatch { degrade: config.policy == DropPolicy::Degrade, dropped: None, until: None, counters: register("packets") }
+    }
+
+    /// Takes libpcap's latest drop counter
+    pub fn observe(&mut self, dropped: u32) {
+        if self.dropped.is_some_and(|last| dropped > last) && self.degrade {
+            self.until = Some(Instant::now() + DEGRADE_HOLD);
+        }
+        self.dropped = Some(dropped);
+    }
+
+    /// Whether the packet about to be handled should skip deep parsing
+    pub fn shed_load(&mut self) -> bool {
+        let degraded = self.until.is_some_and(|until| Instant::now() < until);
+        Counters::add(if degraded { &self.counters.degraded } else { &self.counters.passed });
+        degraded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stage(policy: DropPolicy) -> StageConfig {
+        StageConfig { policy, capacity: 2 }
+    }
+
+    #[test]
+    fn applies_the_drop_policy_when_full() {
+        let (sender, receiver) = channel("test-newest", stage(DropPolicy::DropNewest));
+        (1..=4).for_each(|n| sender.send(n).unwrap());
+        drop(sender);
+        assert_eq!(receiver.collect::<Vec<_>>(), [1, 2]);
+
+        let (sender, receiver) = channel("test-oldest", stage(DropPolicy::DropOldest));
+        (1..=4).for_each(|n| sender.send(n).unwrap());
+        drop(sender);
+        assert_eq!(receiver.collect::<Vec<_>>(), [3, 4]);
+
+        let report = shed_report();
+        assert_eq!(report["test-newest"], Shed { passed: 2, dropped: 2, degraded: 0, blocked: 0 });
+        assert_eq!(report["test-oldest"].dropped, 2);
+    }
+
+    #[test]
+    fn blocks_until_there_is_room() {
+        let (sender, receiver) = channel("test-block", stage(DropPolicy::Block));
+        let producer = std::thread::spawn(move || (1..=5).for_each(|n| sender.send(n).unwrap()));
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!receiver.shed_load());
+        assert_eq!(receiver.collect::<Vec<_>>(), [1, 2, 3, 4, 5]);
+        producer.join().unwrap();
+        assert!(shed_report()["test-block"].blocked > 0);
+
+        let (sender, receiver) = channel("test-degrade", stage(DropPolicy::Degrade));
+        sender.send(1).unwrap();
+        assert!(receiver.shed_load());
+        assert_eq!(receiver.recv(), Some(1));
+        assert!(!receiver.shed_load());
+        assert!(PipelineConfig { pipe: stage(DropPolicy::Degrade), ..Default::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,72 @@
+use crate::error::CaptureError;
+use crate::protocols::analyze_frame_control;
+use log::info;
+use pcap::Capture;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Throughput figures collected while replaying a capture file through the parser
+#[derive(Debug)]
+pub struct BenchmarkReport {
+    pub packets: u64,
+    pub bytes: u64,
+    pub parsed: u64,
+    pub elapsed: Duration,
+}
+
+impl BenchmarkReport {
+    pub fn packets_per_sec(&self) -> f64 {
+        self.packets as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    pub fn megabytes_per_sec(&self) -> f64 {
+        self.bytes as f64 / 1_000_000.0 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Packets: {} ({} parsed)", self.packets, self.parsed)?;
+        writeln!(f, "Bytes: {}", self.bytes)?;
+        writeln!(f, "Elapsed: {:.3}s", self.elapsed.as_secs_f64())?;
+        writeln!(f, "Throughput: {:.0} packets/sec", self.packets_per_sec())?;
+        write!(f, "Throughput: {:.2} MB/sec", self.megabytes_per_sec())
+    }
+}
+
+/// Replays a pcap file through the parsing pipeline as fast as possible.
+///
+/// Packets are loaded into memory first so the measurement covers parsing only,
+/// not disk reads.
+pub fn run_benchmark(path: &str) -> Result<BenchmarkReport, CaptureError> {
+    info!("Loading '{}' for benchmarking", path);
+
+    let mut cap = Capture::from_file(path).map_err(|e| CaptureError::PcapError(e.to_string()))?;
+    let mut packets = Vec::new();
+    loop {
+        match cap.next_packet() {
+            Ok(packet) => packets.push(packet.data.to_vec()),
+            Err(pcap::Error::NoMorePackets) => break,
+            Err(e) => return Err(CaptureError::PcapError(e.to_string())),
+        }
+    }
+    info!("Loaded {} packets, starting benchmark", packets.len());
+
+    let mut report = BenchmarkReport {
+        packets: 0,
+        bytes: 0,
+        parsed: 0,
+        elapsed: Duration::ZERO,
+    };
+    let start = Instant::now();
+    for data in &packets {
+        if analyze_frame_control(data).is_some() {
+            report.parsed += 1;
+        }
+        report.packets += 1;
+        report.bytes += data.len() as u64;
+    }
+    report.elapsed = start.elapsed();
+
+    Ok(report)
+}
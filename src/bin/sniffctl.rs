@@ -0,0 +1,143 @@
+use clap::{Parser, Subcommand};
+use rust_sniffer::control::{ControlClient, ControlRequest};
+use rust_sniffer::error::CaptureError;
+use rust_sniffer::flow::FlowRecord;
+use rust_sniffer::timestamp::format_rfc3339;
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// Controls a rust-sniffer capture started with --control
+#[derive(Parser, Debug)]
+#[command(name = "sniffctl", version, about)]
+struct Cli {
+    /// Control socket of the running capture
+    #[arg(short, long, value_name = "SOCKET")]
+    socket: PathBuf,
+
+    /// Print the raw JSON replies
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Packet counters, libpcap statistics and the active filters
+    Stats,
+    /// Open flows, largest first
+    Flows {
+        /// Flows listed
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Recent alerts
+    Alerts {
+        /// Keep printing new alerts until interrupted
+        #[arg(short, long)]
+        follow: bool,
+    },
+    /// Replace the capture (BPF) and/or display filter; an empty expression clears it
+    Filter {
+        #[arg(long, value_name = "EXPRESSION")]
+        bpf: Option<String>,
+        #[arg(long, value_name = "EXPRESSION")]
+        display: Option<String>,
+    },
+    /// Write the capture's buffer of recent packets to a pcap file
+    Dump {
+        /// Written by the capture, so relative to its working directory
+        path: PathBuf,
+    },
+    /// Run the AI analysis on the most recent packet (AI mode only)
+    Analyze,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        eprintln!("sniffctl: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<(), CaptureError> {
+    let mut client = ControlClient::connect(&cli.socket)?;
+    let request = match &cli.command {
+        Command::Stats => ControlRequest::Stats,
+        Command::Flows { limit } => ControlRequest::Flows { limit: Some(*limit) },
+        Command::Alerts { .. } => ControlRequest::Alerts,
+        Command::Filter { bpf: None, display: None } => {
+            return Err(CaptureError::InputError("Give --bpf, --display or both".to_string()));
+        }
+        Command::Filter { bpf, display } => ControlRequest::Filter { bpf: bpf.clone(), display: display.clone() },
+        Command::Dump { path } => ControlRequest::Dump { path: path.clone() },
+        Command::Analyze => ControlRequest::Analyze,
+    };
+    let reply = client.request(&request)?;
+    if cli.json {
+        println!("{}", reply);
+    } else {
+        print_reply(&cli.command, &reply)?;
+    }
+    if let Command::Alerts { follow: true } = cli.command {
+        for event in client {
+            let event = event?;
+            if cli.json {
+                println!("{}", event);
+            } else if let Some(alert) = event.get("alert") {
+                print_alert(alert);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_reply(command: &Command, reply: &Value) -> Result<(), CaptureError> {
+    let text = |key: &str| reply[key].as_str().unwrap_or("none").to_string();
+    match command {
+        Command::Stats => {
+            println!("Packets processed: {}", reply["packets"]);
+            if reply.get("received").is_some() {
+                println!("libpcap: {} received, {} dropped, {} dropped by the interface", reply["received"], reply["dropped"], reply["if_dropped"]);
+            }
+            println!("Buffered packets: {}", reply["buffered"]);
+            for (direction, volume) in reply["directions"].as_object().into_iter().flatten() {
+                println!("Traffic {}: {} packets, {} bytes", direction, volume["packets"], volume["bytes"]);
+            }
+            println!("Capture filter: {}", text("bpf"));
+            println!("Display filter: {}", text("display"));
+            for (stage, shed) in reply["pipeline"].as_object().into_iter().flatten() {
+                println!("Stage {}: {} passed, {} dropped, {} degraded, {} waits", stage, shed["passed"], shed["dropped"], shed["degraded"], shed["blocked"]);
+            }
+        }
+        Command::Flows { .. } => {
+            let flows: Vec<FlowRecord> =
+                serde_json::from_value(reply["flows"].clone()).map_err(|e| CaptureError::Other(format!("Invalid reply: {}", e)))?;
+            println!("{:<56} {:>10} {:>12} {:>10}", "FLOW", "PACKETS", "BYTES", "DURATION");
+            for flow in &flows {
+                println!("{:<56} {:>10} {:>12} {:>9.1}s", flow.key.to_string(), flow.packets, flow.bytes, flow.last - flow.first);
+            }
+            println!("{} of {} open flow(s)", flows.len(), reply["open"]);
+        }
+        Command::Alerts { .. } => {
+            for alert in reply["recent"].as_array().into_iter().flatten() {
+                print_alert(alert);
+            }
+        }
+        Command::Filter { .. } => println!("Capture filter: {}\nDisplay filter: {}", text("bpf"), text("display")),
+        Command::Dump { .. } => println!("Wrote {} packets to {}", reply["packets"], text("path")),
+        Command::Analyze => println!("{}", serde_json::to_string_pretty(&reply["analysis"]).unwrap_or_default()),
+    }
+    Ok(())
+}
+
+/// One line per alert, like the capture's own log
+fn print_alert(alert: &Value) {
+    let time = alert["time"].as_f64().map(format_rfc3339).unwrap_or_default();
+    let source = alert["source"].as_str().map(|s| format!("[{}] ", s)).unwrap_or_default();
+    let techniques: Vec<&str> = alert["techniques"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+    let techniques = if techniques.is_empty() { String::new() } else { format!(" [ATT&CK {}]", techniques.join(", ")) };
+    println!("{} {:<8} {}{}{}", time, alert["severity"].as_str().unwrap_or("?").to_uppercase(), source, alert["message"].as_str().unwrap_or(""), techniques);
+}
@@ -0,0 +1,437 @@
+//! Packet crafting API.
+//!
+//! ```
+//! # use rust_sniffer::builder::PacketBuilder;
+//! # use std::net::Ipv4Addr;
+//! let frame = PacketBuilder::ethernet()
+//!     .ipv4(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2))
+//!     .udp(5353, 53)
+//!     .payload(b"hello")
+//!     .build();
+//! assert_eq!(frame.len(), 14 + 20 + 8 + 5);
+//! ```
+//!
+//! Length fields and checksums (IPv4 header, TCP/UDP with pseudo-header,
+//! ICMP/ICMPv6) are filled in by `build()`.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Builder producing wire-format frames layer by layer
+#[derive(Debug, Clone)]
+pub struct PacketBuilder {
+    link: Option<LinkLayer>,
+    network: Option<NetworkLayer>,
+    transport: Option<TransportLayer>,
+    payload: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+struct LinkLayer {
+    src: [u8; 6],
+    dst: [u8; 6],
+    ether_type: Option<u16>,
+}
+
+#[derive(Debug, Clone)]
+enum NetworkLayer {
+    V4 { src: Ipv4Addr, dst: Ipv4Addr, ttl: u8, id: u16, dont_fragment: bool },
+    V6 { src: Ipv6Addr, dst: Ipv6Addr, hop_limit: u8 },
+}
+
+#[derive(Debug, Clone)]
+enum TransportLayer {
+    Tcp { src_port: u16, dst_port: u16, seq: u32, ack: u32, flags: u8, window: u16 },
+    Udp { src_port: u16, dst_port: u16 },
+    Icmp { icmp_type: u8, code: u8, rest: [u8; 4] },
+}
+
+pub const TCP_FIN: u8 = 0x01;
+pub const TCP_SYN: u8 = 0x02;
+pub const TCP_RST: u8 = 0x04;
+pub const TCP_PSH: u8 = 0x08;
+pub const TCP_ACK: u8 = 0x10;
+
+impl PacketBuilder {
+    /// Starts a frame with an Ethernet II header (zeroed MAC addresses)
+    pub fn ethernet() -> Self {
+        PacketBuilder {
+            link: Some(LinkLayer { src: [0; 6], dst: [0; 6], ether_type: None }),
+            network: None,
+            transport: None,
+            payload: Vec::new(),
+        }
+    }
+
+    /// Starts a bare IP packet with no link-layer header
+    pub fn raw() -> Self {
+        PacketBuilder { link: None, network: None, transport: None, payload: Vec::new() }
+    }
+
+    pub fn src_mac(mut self, mac: [u8; 6]) -> Self {
+        if let Some(link) = self.link.as_mut() {
+            link.src = mac;
+        }
+        self
+    }
+
+    pub fn dst_mac(mut self, mac: [u8; 6]) -> Self {
+        if let Some(link) = self.link.as_mut() {
+            link.dst = mac;
+        }
+        self
+    }
+
+    /// Overrides the EtherType, for frames without an IP layer (ARP, LLDP...)
+    pub fn ether_type(mut self, ether_type: u16) -> Self {
+        if let Some(link) = self.link.as_mut() {
+            link.ether_type = Some(ether_type);
+        }
+        self
+    }
+
+    pub fn ipv4(mut self, src: Ipv4Addr, dst: Ipv4Addr) -> Self {
+        self.network = Some(NetworkLayer::V4 { src, dst, ttl: 64, id: 0, dont_fragment: true });
+        self
+    }
+
+    pub fn ipv6(mut self, src: Ipv6Addr, dst: Ipv6Addr) -> Self {
+        self.network = Some(NetworkLayer::V6 { src, dst, hop_limit: 64 });
+        self
+    }
+
+    /// Adds an IPv4 or IPv6 header depending on the address family.
+    ///
+    /// # Panics
+    ///
+    /// When `src` and `dst` are of different families.
+    pub fn ip(self, src: IpAddr, dst: IpAddr) -> Self {
+        match (src, dst) {
+            (IpAddr::V6(s), IpAddr::V6(d)) => self.ipv6(s, d),
+            (IpAddr::V4(s), IpAddr::V4(d)) => self.ipv4(s, d),
+            _ => panic!("Mixed address families: {} -> {}", src, dst),
+        }
+    }
+
+    /// Sets the IPv4 TTL or IPv6 hop limit
+    pub fn ttl(mut self, value: u8) -> Self {
+        match self.network.as_mut() {
+            Some(NetworkLayer::V4 { ttl, .. }) => *ttl = value,
+            Some(NetworkLayer::V6 { hop_limit, .. }) => *hop_limit = value,
+            None => {}
+        }
+        self
+    }
+
+    /// Sets the IPv4 identification field
+    pub fn ip_id(mut self, value: u16) -> Self {
+        if let Some(NetworkLayer::V4 { id, .. }) = self.network.as_mut() {
+            *id = value;
+        }
+        self
+    }
+
+    pub fn tcp(mut self, src_port: u16, dst_port: u16) -> Self {
+        self.transport = Some(TransportLayer::Tcp {
+            src_port,
+            dst_port,
+            seq: 0,
+            ack: 0,
+            flags: TCP_SYN,
+            window: 64240,
+        });
+        self
+    }
+
+    pub fn udp(mut self, src_port: u16, dst_port: u16) -> Self {
+        self.transport = Some(TransportLayer::Udp { src_port, dst_port });
+        self
+    }
+
+    /// Adds an ICMP (or ICMPv6 under an IPv6 header) message header
+    pub fn icmp(mut self, icmp_type: u8, code: u8) -> Self {
+        self.transport = Some(TransportLayer::Icmp { icmp_type, code, rest: [0; 4] });
+        self
+    }
+
+    /// Adds an ICMP echo request with the given identifier and sequence number
+    pub fn icmp_echo_request(self, id: u16, seq: u16) -> Self {
+        let is_v6 = matches!(self.network, Some(NetworkLayer::V6 { .. }));
+        let mut builder = self.icmp(if is_v6 { 128 } else { 8 }, 0);
+        if let Some(TransportLayer::Icmp { rest, .. }) = builder.transport.as_mut() {
+            rest[..2].copy_from_slice(&id.to_be_bytes());
+            rest[2..].copy_from_slice(&seq.to_be_bytes());
+        }
+        builder
+    }
+
+    pub fn tcp_flags(mut self, value: u8) -> Self {
+        if let Some(TransportLayer::Tcp { flags, .. }) = self.transport.as_mut() {
+            *flags = value;
+        }
+        self
+    }
+
+    pub fn seq(mut self, value: u32) -> Self {
+        if let Some(TransportLayer::Tcp { seq, .. }) = self.transport.as_mut() {
+            *seq = value;
+        }
+        self
+    }
+
+    pub fn ack(mut self, value: u32) -> Self {
+        if let Some(TransportLayer::Tcp { ack, .. }) = self.transport.as_mut() {
+            *ack = value;
+        }
+        self
+    }
+
+    pub fn window(mut self, value: u16) -> Self {
+        if let Some(TransportLayer::Tcp { window, .. }) = self.transport.as_mut() {
+            *window = value;
+        }
+        self
+    }
+
+    pub fn payload(mut self, data: &[u8]) -> Self {
+        self.payload = data.to_vec();
+        self
+    }
+
+    /// Serializes all layers, filling in lengths and checksums
+    pub fn build(&self) -> Vec<u8> {
+        let transport = self.build_transport();
+        let (network, ether_type) = match &self.network {
+            Some(NetworkLayer::V4 { src, dst, ttl, id, dont_fragment }) => {
+                let protocol = self.ip_protocol();
+                let total_len = (20 + transport.len()) as u16;
+                let mut header = Vec::with_capacity(total_len as usize);
+                header.extend_from_slice(&[0x45, 0x00]);
+                header.extend_from_slice(&total_len.to_be_bytes());
+                header.extend_from_slice(&id.to_be_bytes());
+                header.extend_from_slice(&[if *dont_fragment { 0x40 } else { 0x00 }, 0x00]);
+                header.extend_from_slice(&[*ttl, protocol, 0x00, 0x00]);
+                header.extend_from_slice(&src.octets());
+                header.extend_from_slice(&dst.octets());
+                let checksum = internet_checksum(&header);
+                header[10..12].copy_from_slice(&checksum.to_be_bytes());
+                header.extend_from_slice(&transport);
+                (header, 0x0800)
+            }
+            Some(NetworkLayer::V6 { src, dst, hop_limit }) => {
+                let mut header = Vec::with_capacity(40 + transport.len());
+                header.extend_from_slice(&[0x60, 0x00, 0x00, 0x00]);
+                header.extend_from_slice(&(transport.len() as u16).to_be_bytes());
+                header.extend_from_slice(&[self.ip_protocol(), *hop_limit]);
+                header.extend_from_slice(&src.octets());
+                header.extend_from_slice(&dst.octets());
+                header.extend_from_slice(&transport);
+                (header, 0x86DD)
+            }
+            None => (transport, 0x0000),
+        };
+
+        match &self.link {
+            Some(link) => {
+                let mut frame = Vec::with_capacity(14 + network.len());
+                frame.extend_from_slice(&link.dst);
+                frame.extend_from_slice(&link.src);
+                frame.extend_from_slice(&link.ether_type.unwrap_or(ether_type).to_be_bytes());
+                frame.extend_from_slice(&network);
+                frame
+            }
+            None => network,
+        }
+    }
+
+    fn ip_protocol(&self) -> u8 {
+        let is_v6 = matches!(self.network, Some(NetworkLayer::V6 { .. }));
+        match self.transport {
+            Some(TransportLayer::Tcp { .. }) => 6,
+            Some(TransportLayer::Udp { .. }) => 17,
+            Some(TransportLayer::Icmp { .. }) if is_v6 => 58,
+            Some(TransportLayer::Icmp { .. }) => 1,
+            None if is_v6 => 59, // No Next Header
+            None => 253, // Reserved for experimentation
+        }
+    }
+
+    fn build_transport(&self) -> Vec<u8> {
+        let mut segment = match &self.transport {
+            Some(TransportLayer::Tcp { src_port, dst_port, seq, ack, flags, window }) => {
+                let mut header = Vec::with_capacity(20 + self.payload.len());
+                header.extend_from_slice(&src_port.to_be_bytes());
+                header.extend_from_slice(&dst_port.to_be_bytes());
+                header.extend_from_slice(&seq.to_be_bytes());
+                header.extend_from_slice(&ack.to_be_bytes());
+                header.extend_from_slice(&[0x50, *flags]);
+                header.extend_from_slice(&window.to_be_bytes());
+                header.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+                header
+            }
+            Some(TransportLayer::Udp { src_port, dst_port }) => {
+                let mut header = Vec::with_capacity(8 + self.payload.len());
+                header.extend_from_slice(&src_port.to_be_bytes());
+                header.extend_from_slice(&dst_port.to_be_bytes());
+                header.extend_from_slice(&((8 + self.payload.len()) as u16).to_be_bytes());
+                header.extend_from_slice(&[0x00, 0x00]);
+                header
+            }
+            Some(TransportLayer::Icmp { icmp_type, code, rest }) => {
+                let mut header = vec![*icmp_type, *code, 0x00, 0x00];
+                header.extend_from_slice(rest);
+                header
+            }
+            None => Vec::new(),
+        };
+        segment.extend_from_slice(&self.payload);
+
+        let checksum_offset = match self.transport {
+            Some(TransportLayer::Tcp { .. }) => 16,
+            Some(TransportLayer::Udp { .. }) => 6,
+            Some(TransportLayer::Icmp { .. }) => 2,
+            None => return segment,
+        };
+        let checksum = match (&self.network, &self.transport) {
+            // ICMPv4 has no pseudo-header
+            (Some(NetworkLayer::V4 { .. }), Some(TransportLayer::Icmp { .. })) | (None, _) => {
+                internet_checksum(&segment)
+            }
+            (Some(network), _) => {
+                let (src, dst) = network.addresses();
+                transport_checksum(src, dst, self.ip_protocol(), &segment)
+            }
+        };
+        segment[checksum_offset..checksum_offset + 2].copy_from_slice(&checksum.to_be_bytes());
+        segment
+    }
+}
+
+impl NetworkLayer {
+    fn addresses(&self) -> (IpAddr, IpAddr) {
+        match self {
+            NetworkLayer::V4 { src, dst, .. } => (IpAddr::V4(*src), IpAddr::V4(*dst)),
+            NetworkLayer::V6 { src, dst, .. } => (IpAddr::V6(*src), IpAddr::V6(*dst)),
+        }
+    }
+}
+
+/// Checksum of a TCP/UDP/ICMPv6 segment including the IP pseudo-header.
+/// The segment's own checksum field must be zeroed beforehand.
+pub fn transport_checksum(src: IpAddr, dst: IpAddr, protocol: u8, segment: &[u8]) -> u16 {
+    let mut data = Vec::with_capacity(40 + segment.len());
+    match (src, dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            data.extend_from_slice(&src.octets());
+            data.extend_from_slice(&dst.octets());
+            data.extend_from_slice(&[0, protocol]);
+            data.extend_from_slice(&(segment.len() as u16).to_be_bytes());
+        }
+        (src, dst) => {
+            data.extend_from_slice(&to_v6(src).octets());
+            data.extend_from_slice(&to_v6(dst).octets());
+            data.extend_from_slice(&(segment.len() as u32).to_be_bytes());
+            data.extend_from_slice(&[0, 0, 0, protocol]);
+        }
+    }
+    data.extend_from_slice(segment);
+    match internet_checksum(&data) {
+        // A computed zero is sent as all ones for UDP
+        0 if protocol == 17 => 0xffff,
+        sum => sum,
+    }
+}
+
+fn to_v6(addr: IpAddr) -> Ipv6Addr {
+    match addr {
+        IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+        IpAddr::V6(v6) => v6,
+    }
+}
+
+/// RFC 1071 ones-complement checksum
+pub fn internet_checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    for chunk in data.chunks(2) {
+        let word = if chunk.len() == 2 {
+            u16::from_be_bytes([chunk[0], chunk[1]])
+        } else {
+            u16::from_be_bytes([chunk[0], 0])
+        };
+        sum += word as u32;
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::ethernet::EthernetFrame;
+    use crate::protocols::ipv4::IPv4Packet;
+    use crate::protocols::ipv6::IPv6Packet;
+
+    #[test]
+    fn builds_ipv4_udp_with_valid_checksums() {
+        let src = Ipv4Addr::new(10, 0, 0, 1);
+        let dst = Ipv4Addr::new(10, 0, 0, 2);
+        let frame = PacketBuilder::ethernet().ipv4(src, dst).udp(5353, 53).payload(b"hello").build();
+        assert_eq!(frame.len(), 14 + 20 + 8 + 5);
+
+        let eth = EthernetFrame::parse(&frame).unwrap();
+        let ip = IPv4Packet::parse(eth.payload()).unwrap();
+        assert_eq!(ip.total_length(), 33);
+        assert_eq!(ip.get_protocol_name(), "UDP");
+        assert_eq!(internet_checksum(&eth.payload()[..20]), 0);
+
+        let mut segment = eth.payload()[20..].to_vec();
+        let sent = u16::from_be_bytes([segment[6], segment[7]]);
+        segment[6..8].copy_from_slice(&[0, 0]);
+        assert_eq!(transport_checksum(src.into(), dst.into(), 17, &segment), sent);
+    }
+
+    #[test]
+    fn builds_ipv6_tcp_with_valid_checksum() {
+        let src: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let dst: Ipv6Addr = "2001:db8::2".parse().unwrap();
+        let frame = PacketBuilder::ethernet()
+            .ipv6(src, dst)
+            .tcp(40000, 443)
+            .tcp_flags(TCP_PSH | TCP_ACK)
+            .seq(7)
+            .payload(&[1, 2, 3])
+            .build();
+
+        let eth = EthernetFrame::parse(&frame).unwrap();
+        let ip = IPv6Packet::parse(eth.payload()).unwrap();
+        assert_eq!(ip.payload_length(), 23);
+        assert_eq!(ip.get_next_header_name(), "TCP");
+
+        let mut segment = eth.payload()[40..].to_vec();
+        let sent = u16::from_be_bytes([segment[16], segment[17]]);
+        segment[16..18].copy_from_slice(&[0, 0]);
+        assert_eq!(transport_checksum(src.into(), dst.into(), 6, &segment), sent);
+    }
+
+    #[test]
+    fn builds_icmp_echo_and_raw_frames() {
+        let packet = PacketBuilder::raw()
+            .ipv4(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2))
+            .icmp_echo_request(0x1234, 1)
+            .build();
+        assert_eq!(packet[9], 1);
+        assert_eq!(packet[20], 8);
+        assert_eq!(internet_checksum(&packet[20..]), 0);
+
+        let arp = PacketBuilder::ethernet().dst_mac([0xff; 6]).ether_type(0x0806).payload(&[0; 28]).build();
+        assert_eq!(&arp[12..14], &[0x08, 0x06]);
+        assert_eq!(arp.len(), 42);
+    }
+
+    #[test]
+    #[should_panic(expected = "Mixed address families")]
+    fn rejects_mixed_address_families() {
+        PacketBuilder::ethernet().ip(Ipv4Addr::new(10, 0, 0, 1).into(), Ipv6Addr::LOCALHOST.into());
+    }
+}
@@ -0,0 +1,365 @@
+use crate::error::CaptureError;
+use crate::filter::DisplayFilter;
+use crate::flow::FlowKey;
+use crate::protocols::summary::PacketSummary;
+use log::{info, warn};
+use pcap::{Capture, Linktype, Offline, Packet, PacketHeader, Savefile};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// pcap global header and per-record header sizes, used to estimate output file sizes
+const FILE_HEADER_LEN: u64 = 24;
+const RECORD_HEADER_LEN: u64 = 16;
+
+/// A packet copied out of a capture handle so it can outlive the next read
+#[derive(Debug, Clone)]
+pub struct StoredPacket {
+    pub header: PacketHeader,
+    pub data: Vec<u8>,
+}
+
+impl StoredPacket {
+    pub fn new(header: PacketHeader, data: Vec<u8>) -> Self {
+        StoredPacket { header, data }
+    }
+
+    /// Time since the Unix epoch
+    pub fn timestamp(&self) -> Duration {
+        Duration::new(self.header.ts.tv_sec.max(0) as u64, (self.header.ts.tv_usec.max(0) as u32) * 1000)
+    }
+
+    pub fn as_packet(&self) -> Packet<'_> {
+        Packet::new(&self.header, &self.data)
+    }
+
+    /// Bytes this packet occupies in a pcap file
+    pub fn record_len(&self) -> u64 {
+        RECORD_HEADER_LEN + self.data.len() as u64
+    }
+}
+
+impl From<Packet<'_>> for StoredPacket {
+    fn from(packet: Packet<'_>) -> Self {
+        StoredPacket::new(*packet.header, packet.data.to_vec())
+    }
+}
+
+/// Streams packets from a pcap or pcapng file
+pub struct CaptureReader {
+    path: PathBuf,
+    cap: Capture<Offline>,
+}
+
+impl CaptureReader {
+    pub fn open(path: &Path) -> Result<Self, CaptureError> {
+        let cap = Capture::from_file(path)
+            .map_err(|e| CaptureError::PcapError(format!("Cannot read {}: {}", path.display(), e)))?;
+        Ok(CaptureReader { path: path.to_path_buf(), cap })
+    }
+
+    pub fn linktype(&self) -> Linktype {
+        self.cap.get_datalink()
+    }
+
+    /// Applies a BPF filter to the packets read from the file
+    pub fn set_filter(&mut self, bpf: &str) -> Result<(), CaptureError> {
+        self.cap
+            .filter(bpf, true)
+            .map_err(|e| CaptureError::PcapError(format!("Invalid BPF '{}': {}", bpf, e)))
+    }
+}
+
+impl Iterator for CaptureReader {
+    type Item = StoredPacket;
+
+    /// Yields packets until the end of the file; a read error ends the stream with a warning
+    fn next(&mut self) -> Option<StoredPacket> {
+        match self.cap.next_packet() {
+            Ok(packet) => Some(packet.into()),
+            Err(pcap::Error::NoMorePackets) => None,
+            Err(e) => {
+                warn!("Stopped reading {}: {}", self.path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+/// Creates a pcap file for packets of the given link type
+pub fn create_writer(linktype: Linktype, path: &Path) -> Result<Savefile, CaptureError> {
+    Capture::dead(linktype)
+        .and_then(|cap| cap.savefile(path))
+        .map_err(|e| CaptureError::PcapError(format!("Cannot create {}: {}", path.display(), e)))
+}
+
+/// Merges already time-ordered packet sources into a single time-ordered stream.
+/// Ties keep the order of the sources.
+pub struct Merge<I: Iterator<Item = StoredPacket>> {
+    sources: Vec<I>,
+    heads: Vec<Option<StoredPacket>>,
+    queue: BinaryHeap<Reverse<(Duration, usize)>>,
+}
+
+impl<I: Iterator<Item = StoredPacket>> Merge<I> {
+    pub fn new(mut sources: Vec<I>) -> Self {
+        let mut heads = Vec::with_capacity(sources.len());
+        let mut queue = BinaryHeap::new();
+        for (index, source) in sources.iter_mut().enumerate() {
+            let head = source.next();
+            if let Some(packet) = &head {
+                queue.push(Reverse((packet.timestamp(), index)));
+            }
+            heads.push(head);
+        }
+        Merge { sources, heads, queue }
+    }
+}
+
+impl<I: Iterator<Item = StoredPacket>> Iterator for Merge<I> {
+    type Item = StoredPacket;
+
+    fn next(&mut self) -> Option<StoredPacket> {
+        let Reverse((_, index)) = self.queue.pop()?;
+        let packet = self.heads[index].take()?;
+        if let Some(next) = self.sources[index].next() {
+            self.queue.push(Reverse((next.timestamp(), index)));
+            self.heads[index] = Some(next);
+        }
+        Some(packet)
+    }
+}
+
+/// Merges capture files into `output` ordered by timestamp, returning the packet count
+pub fn merge_files(inputs: &[PathBuf], output: &Path) -> Result<u64, CaptureError> {
+    let readers = inputs
+        .iter()
+        .map(|path| CaptureReader::open(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let linktype = match readers.first() {
+        Some(reader) => reader.linktype(),
+        None => return Err(CaptureError::InputError("no input files to merge".to_string())),
+    };
+    if let Some((path, reader)) = inputs.iter().zip(&readers).find(|(_, r)| r.linktype() != linktype) {
+        return Err(CaptureError::InputError(format!(
+            "{} has link type {:?}, expected {:?}; files with different link types can't be merged into pcap",
+            path.display(),
+            reader.linktype(),
+            linktype
+        )));
+    }
+
+    let mut writer = create_writer(linktype, output)?;
+    let mut count = 0;
+    for packet in Merge::new(readers) {
+        writer.write(&packet.as_packet());
+        count += 1;
+    }
+    writer.flush().map_err(|e| CaptureError::PcapError(e.to_string()))?;
+    info!("Merged {} packets from {} files into {}", count, inputs.len(), output.display());
+    Ok(count)
+}
+
+/// How `split_file` divides a capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitMode {
+    /// At most this many packets per file
+    Packets(u64),
+    /// At most this many bytes per file (a single larger packet still gets its own file)
+    Bytes(u64),
+    /// One file per conversation (see `FlowKey`); non-IP packets go to `<prefix>_other.pcap`
+    Flow,
+}
+
+/// Decides which numbered chunk each packet goes to for count and size splits
+#[derive(Debug)]
+pub struct Chunker {
+    mode: SplitMode,
+    chunk: usize,
+    packets: u64,
+    bytes: u64,
+}
+
+impl Chunker {
+    pub fn new(mode: SplitMode) -> Self {
+        Chunker { mode, chunk: 0, packets: 0, bytes: FILE_HEADER_LEN }
+    }
+
+    /// Chunk index for a packet occupying `record_len` bytes in the file
+    pub fn assign(&mut self, record_len: u64) -> usize {
+        let full = match self.mode {
+            SplitMode::Packets(limit) => self.packets >= limit.max(1),
+            SplitMode::Bytes(limit) => self.packets > 0 && self.bytes + record_len > limit,
+            SplitMode::Flow => false,
+        };
+        if full {
+            self.chunk += 1;
+            self.packets = 0;
+            self.bytes = FILE_HEADER_LEN;
+        }
+        self.packets += 1;
+        self.bytes += record_len;
+        self.chunk
+    }
+}
+
+/// Splits a capture file into several pcap files named `<prefix>_<part>.pcap`,
+/// returning the paths written
+pub fn split_file(input: &Path, prefix: &str, mode: SplitMode) -> Result<Vec<PathBuf>, CaptureError> {
+    let reader = CaptureReader::open(input)?;
+    let linktype = reader.linktype();
+    let mut writers: HashMap<String, Savefile> = HashMap::new();
+    let mut written = Vec::new();
+    let mut chunker = Chunker::new(mode);
+
+    for packet in reader {
+        let part = match mode {
+            SplitMode::Flow => PacketSummary::from_link(linktype.0, &packet.data)
+                .and_then(|s| FlowKey::from_summary(&s))
+                .map(|key| key.file_stem())
+                .unwrap_or_else(|| "other".to_string()),
+            _ => format!("{:05}", chunker.assign(packet.record_len())),
+        };
+        if !writers.contains_key(&part) {
+            let path = PathBuf::from(format!("{}_{}.pcap", prefix, part));
+            if mode != SplitMode::Flow {
+                // Chunks are written one after another; close the previous file
+                for (_, mut done) in writers.drain() {
+                    done.flush().map_err(|e| CaptureError::PcapError(e.to_string()))?;
+                }
+            }
+            writers.insert(part.clone(), create_writer(linktype, &path)?);
+            written.push(path);
+        }
+        if let Some(writer) = writers.get_mut(&part) {
+            writer.write(&packet.as_packet());
+        }
+    }
+    for (_, mut writer) in writers {
+        writer.flush().map_err(|e| CaptureError::PcapError(e.to_string()))?;
+    }
+    info!("Split {} into {} files", input.display(), written.len());
+    Ok(written)
+}
+
+/// Selection criteria for carving packets out of a capture
+#[derive(Debug, Default)]
+pub struct Slice {
+    /// Keep packets at or after this time since the Unix epoch
+    pub start: Option<Duration>,
+    /// Keep packets before this time since the Unix epoch
+    pub end: Option<Duration>,
+    pub display: Option<DisplayFilter>,
+}
+
+impl Slice {
+    pub fn matches(&self, packet: &StoredPacket) -> bool {
+        let ts = packet.timestamp();
+        self.start.is_none_or(|start| ts >= start)
+            && self.end.is_none_or(|end| ts < end)
+            && self.display.as_ref().is_none_or(|f| f.matches_frame(&packet.data))
+    }
+}
+
+/// Copies the packets of `input` selected by `slice` (and an optional BPF
+/// filter) to `output`, returning how many were kept
+pub fn slice_file(input: &Path, output: &Path, slice: &Slice, bpf: Option<&str>) -> Result<u64, CaptureError> {
+    let mut reader = CaptureReader::open(input)?;
+    if let Some(bpf) = bpf {
+        reader.set_filter(bpf)?;
+    }
+    let mut writer = create_writer(reader.linktype(), output)?;
+    let (mut read, mut kept) = (0u64, 0u64);
+    for packet in reader {
+        read += 1;
+        if slice.matches(&packet) {
+            writer.write(&packet.as_packet());
+            kept += 1;
+        }
+    }
+    writer.flush().map_err(|e| CaptureError::PcapError(e.to_string()))?;
+    info!("Kept {} of {} packets from {}", kept, read, input.display());
+    Ok(kept)
+}
+
+/// Parses sizes such as `1500`, `64K`, `100M` or `2G` (binary multiples)
+pub fn parse_size(text: &str) -> Result<u64, CaptureError> {
+    let text = text.trim();
+    let (digits, multiplier) = match text.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&text[..i], 1u64 << 10),
+        Some((i, 'm' | 'M')) => (&text[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&text[..i], 1 << 30),
+        _ => (text, 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .filter(|n| *n > 0)
+        .ok_or_else(|| CaptureError::InputError(format!("Invalid size '{}'", text)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(secs: i64, marker: u8) -> StoredPacket {
+        StoredPacket::new(
+            PacketHeader { ts: libc::timeval { tv_sec: secs, tv_usec: 0 }, caplen: 1, len: 1 },
+            vec![marker],
+        )
+    }
+
+    #[test]
+    fn merges_by_timestamp() {
+        let a = vec![packet(1, 1), packet(4, 2), packet(5, 3)];
+        let b = vec![packet(2, 4), packet(4, 5)];
+        let c: Vec<StoredPacket> = Vec::new();
+        let merged: Vec<u8> = Merge::new(vec![a.into_iter(), b.into_iter(), c.into_iter()])
+            .map(|p| p.data[0])
+            .collect();
+        assert_eq!(merged, vec![1, 4, 2, 5, 3]);
+    }
+
+    #[test]
+    fn chunks_by_count_and_size() {
+        let mut by_count = Chunker::new(SplitMode::Packets(2));
+        let chunks: Vec<usize> = (0..5).map(|_| by_count.assign(100)).collect();
+        assert_eq!(chunks, vec![0, 0, 1, 1, 2]);
+
+        // 24-byte file header + 116 per record: two records fit in 256 bytes
+        let mut by_size = Chunker::new(SplitMode::Bytes(256));
+        let chunks: Vec<usize> = [116, 116, 116, 1000, 10].iter().map(|&len| by_size.assign(len)).collect();
+        assert_eq!(chunks, vec![0, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn slices_by_time_and_display_filter() {
+        let slice = Slice {
+            start: Some(Duration::from_secs(2)),
+            end: Some(Duration::from_secs(4)),
+            display: None,
+        };
+        let kept: Vec<i64> = (1..=5).filter(|&s| slice.matches(&packet(s, 0))).collect();
+        assert_eq!(kept, vec![2, 3]);
+
+        let slice = Slice {
+            display: Some(DisplayFilter::parse("udp.port == 53").unwrap()),
+            ..Slice::default()
+        };
+        let dns = StoredPacket::new(packet(1, 0).header, crate::testutil::dns_query_frame(1, "example.com"));
+        let web = StoredPacket::new(packet(1, 0).header, crate::testutil::ipv4_tcp_frame(1, 443, 0, b""));
+        assert!(slice.matches(&dns));
+        assert!(!slice.matches(&web));
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("1500").unwrap(), 1500);
+        assert_eq!(parse_size("64K").unwrap(), 65536);
+        assert_eq!(parse_size("10m").unwrap(), 10 << 20);
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("ten").is_err());
+    }
+}
@@ -0,0 +1,251 @@
+use crate::error::CaptureError;
+use crate::privileges;
+use crate::protocols::sll;
+use ipnet::IpNet;
+use log::{info, warn};
+use pcap::{Active, Capture, Device, Direction, Inactive, Precision, State, TimestampType};
+
+/// Snapshot length when none is configured, set explicitly because the default
+/// differs between libpcap releases shipped on macOS and the BSDs
+pub const DEFAULT_SNAPLEN: i32 = 262144;
+/// Kernel buffer requested on BPF platforms
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+const BPF_BUFFER_SIZE: i32 = 4 * 1024 * 1024;
+
+/// How to open a live capture handle, shared by every capture mode and by
+/// [`crate::sniffer::Sniffer`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureConfig {
+    pub interface: String,
+    /// Requested only where the device accepts it
+    pub promisc: bool,
+    pub monitor: bool,
+    pub snaplen: i32,
+    pub direction: Option<Direction>,
+    /// Kernel-side filter installed before the first packet is read
+    pub bpf: Option<String>,
+    /// Microsecond or nanosecond timestamps; falls back to microseconds where
+    /// the platform only has those
+    pub precision: Precision,
+    /// Clock that stamps packets, e.g. the adapter's own; only requested where
+    /// the device lists it
+    pub tstamp_type: Option<TimestampType>,
+}
+
+impl CaptureConfig {
+    /// Promiscuous capture of whole packets in both directions, without a filter
+    pub fn new(interface: &str) -> Self {
+        CaptureConfig {
+            interface: interface.to_string(),
+            promisc: true,
+            monitor: false,
+            snaplen: DEFAULT_SNAPLEN,
+            direction: None,
+            bpf: None,
+            precision: Precision::Micro,
+            tstamp_type: None,
+        }
+    }
+
+    pub fn promisc(mut self, enabled: bool) -> Self {
+        self.promisc = enabled;
+        self
+    }
+
+    /// 802.11 monitor mode (rfmon), for wireless interfaces
+    pub fn monitor(mut self, enabled: bool) -> Self {
+        self.monitor = enabled;
+        self
+    }
+
+    pub fn snaplen(mut self, bytes: i32) -> Self {
+        self.snaplen = bytes;
+        self
+    }
+
+    pub fn direction(mut self, direction: Option<Direction>) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn bpf(mut self, expression: Option<&str>) -> Self {
+        self.bpf = expression.map(str::to_string);
+        self
+    }
+
+    pub fn precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    pub fn tstamp_type(mut self, tstamp_type: Option<TimestampType>) -> Self {
+        self.tstamp_type = tstamp_type;
+        self
+    }
+
+    /// Opens the interface in immediate, non-blocking mode. The "any" device
+    /// is switched to SLL2 so each packet records its ingress interface.
+    pub fn open(&self) -> Result<Capture<Active>, CaptureError> {
+        if let Some(problem) = privileges::preflight(self.monitor) {
+            warn!("{}", problem);
+        }
+        let device = Device::list()
+            .map_err(|e| CaptureError::PcapError(e.to_string()))?
+            .into_iter()
+            .find(|d| d.name == self.interface)
+            .ok_or_else(|| CaptureError::InterfaceNotFound(self.interface.clone()))?;
+        info!("Interface found: {}", device.name);
+
+        check_monitor_support(&device, self.monitor);
+        let promisc = self.promisc && promisc_supported(&device);
+
+        let inactive = Capture::from_device(device).map_err(|e| CaptureError::PcapError(e.to_string()))?
+            .promisc(promisc)
+            .rfmon(self.monitor)
+            .snaplen(self.snaplen)
+            .immediate_mode(true)
+            .precision(self.precision);
+        let inactive = match self.tstamp_type {
+            Some(tstamp_type) if tstamp_types(&inactive).contains(&(tstamp_type as i32)) => inactive.tstamp_type(tstamp_type),
+            Some(tstamp_type) => {
+                warn!("'{}' does not offer {:?} timestamps; using the default clock", self.interface, tstamp_type);
+                inactive
+            }
+            None => inactive,
+        };
+        // BPF devices default to a small store buffer that drops packets under load
+        #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+        let inactive = inactive.buffer_size(BPF_BUFFER_SIZE);
+        let mut cap = inactive
+            .open().map_err(|e| open_error(&self.interface, self.monitor, e))?
+            .setnonblock().map_err(|e| CaptureError::PcapError(e.to_string()))?;
+        if let Some(direction) = self.direction {
+            cap.direction(direction).map_err(|e| CaptureError::PcapError(format!("Cannot set capture direction: {}", e)))?;
+        }
+        if self.precision == Precision::Nano && timestamp_precision(&cap) != Precision::Nano {
+            warn!("Nanosecond timestamps are not available on '{}'; using microseconds", self.interface);
+        }
+        prefer_sll2(&mut cap, &self.interface);
+        if let Some(bpf) = &self.bpf {
+            cap.filter(bpf, true).map_err(|e| CaptureError::PcapError(format!("Invalid BPF '{}': {}", bpf, e)))?;
+            info!("Capture filter: {}", bpf);
+        }
+        Ok(cap)
+    }
+}
+
+unsafe extern "C" {
+    fn pcap_get_tstamp_precision(p: *mut libc::c_void) -> libc::c_int;
+    fn pcap_list_tstamp_types(p: *mut libc::c_void, types: *mut *mut libc::c_int) -> libc::c_int;
+    fn pcap_free_tstamp_types(types: *mut libc::c_int);
+}
+
+/// The precision libpcap actually delivers timestamps in, which can be
+/// coarser than requested
+pub fn timestamp_precision<T: State + ?Sized>(cap: &Capture<T>) -> Precision {
+    // SAFETY: the handle stays open for as long as `cap` is borrowed
+    match unsafe { pcap_get_tstamp_precision(cap.as_ptr().cast()) } {
+        1 => Precision::Nano,
+        _ => Precision::Micro,
+    }
+}
+
+/// Timestamp types the device offers, as libpcap's `PCAP_TSTAMP_*` values;
+/// empty when it only has the default host clock
+fn tstamp_types(cap: &Capture<Inactive>) -> Vec<i32> {
+    let mut types = std::ptr::null_mut();
+    // SAFETY: libpcap allocates the list, which is copied and then freed
+    let count = unsafe { pcap_list_tstamp_types(cap.as_ptr().cast(), &mut types) };
+    if count <= 0 || types.is_null() {
+        return Vec::new();
+    }
+    let list = unsafe { std::slice::from_raw_parts(types, count as usize) }.to_vec();
+    unsafe { pcap_free_tstamp_types(types) };
+    list
+}
+
+/// The networks of the interface's own addresses (of every interface for
+/// "any"), which count as local when classifying traffic direction
+pub fn interface_networks(interface: &str) -> Vec<IpNet> {
+    let devices = match Device::list() {
+        Ok(devices) => devices,
+        Err(e) => {
+            warn!("Cannot list the addresses of '{}': {}", interface, e);
+            return Vec::new();
+        }
+    };
+    devices
+        .iter()
+        .filter(|device| interface == "any" || device.name == interface)
+        .flat_map(|device| &device.addresses)
+        .map(|address| match address.netmask {
+            Some(netmask) => IpNet::with_netmask(address.addr, netmask).map(|network| network.trunc()).unwrap_or_else(|_| IpNet::from(address.addr)),
+            None => IpNet::from(address.addr),
+        })
+        .collect()
+}
+
+/// Warns when monitor mode is requested on a device libpcap doesn't report as wireless
+fn check_monitor_support(device: &Device, monitor: bool) {
+    if monitor && !device.flags.is_wireless() {
+        warn!("'{}' is not reported as a wireless interface; monitor mode will likely fail", device.name);
+    }
+}
+
+/// The "any" pseudo-device and loopback interfaces reject promiscuous mode, so
+/// it is only requested where the kernel accepts it
+fn promisc_supported(device: &Device) -> bool {
+    let supported = device.name != "any" && !device.flags.is_loopback();
+    if !supported {
+        info!("'{}' does not support promiscuous mode; capturing without it", device.name);
+    }
+    supported
+}
+
+/// Switches the "any" device to SLL2 so each packet records its ingress interface
+fn prefer_sll2(cap: &mut Capture<Active>, interface_name: &str) {
+    if interface_name != "any" {
+        return;
+    }
+    if let Err(e) = cap.set_datalink(pcap::Linktype(sll::DLT_LINUX_SLL2)) {
+        warn!("SLL2 not available on 'any' ({}); ingress interfaces will not be shown", e);
+    }
+}
+
+/// Maps a failed open to a clear error, calling out drivers that refuse rfmon
+/// and missing privileges
+fn open_error(interface_name: &str, monitor: bool, e: pcap::Error) -> CaptureError {
+    match e {
+        pcap::Error::PcapError(msg) if msg.contains("Operation not permitted") || msg.contains("Permission denied") => {
+            CaptureError::PcapError(format!("Missing privileges to capture on '{}': {}. Try: {}", interface_name, msg, privileges::privilege_hint()))
+        }
+        pcap::Error::PcapError(msg) if monitor => CaptureError::PcapError(format!(
+            "Driver refused monitor mode (rfmon) on '{}': {}. Check that the adapter supports monitor mode and is not managed by another process",
+            interface_name, msg
+        )),
+        e => CaptureError::PcapError(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_on_the_defaults() {
+        let config = CaptureConfig::new("eth0").promisc(false).snaplen(96).bpf(Some("tcp port 443"));
+        assert_eq!(config.interface, "eth0");
+        assert!(!config.promisc && !config.monitor);
+        assert_eq!((config.snaplen, config.direction), (96, None));
+        assert_eq!(config.bpf.as_deref(), Some("tcp port 443"));
+        assert_eq!(CaptureConfig::new("eth0").snaplen, DEFAULT_SNAPLEN);
+    }
+
+    #[test]
+    fn explains_refused_opens() {
+        let denied = open_error("wlan0", false, pcap::Error::PcapError("socket: Operation not permitted".to_string()));
+        assert!(denied.to_string().contains("Missing privileges to capture on 'wlan0'"));
+        let rfmon = open_error("wlan0", true, pcap::Error::PcapError("That device doesn't support monitor mode".to_string()));
+        assert!(rfmon.to_string().contains("Driver refused monitor mode"));
+    }
+}
@@ -0,0 +1,314 @@
+use crate::alert::{Alert, AlertSink, Severity};
+use crate::attack::technique_name;
+use crate::backpressure::{self, Receiver, Sender, StageConfig};
+use crate::error::CaptureError;
+use crate::timestamp::format_rfc3339;
+use log::warn;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Longest wait honoured when a service asks to slow down
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Where chat notifications are posted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatService {
+    /// Incoming webhook
+    Slack,
+    /// Channel webhook
+    Discord,
+    /// Bot API `sendMessage`
+    Telegram,
+}
+
+/// A chat channel from the configuration file (`[[alerts.chat]]`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChatConfig {
+    pub service: ChatService,
+    /// Webhook URL for Slack and Discord
+    pub webhook: Option<String>,
+    /// Environment variable holding the Telegram bot token
+    pub token_env: Option<String>,
+    /// Telegram chat to post in
+    pub chat_id: Option<String>,
+    /// Alerts below this severity are not posted to the channel
+    #[serde(default = "default_min_severity")]
+    pub min_severity: Severity,
+    /// Only post these kinds of alert (`builtin`, `rule`, `threshold`); all when empty
+    #[serde(default)]
+    pub kinds: Vec<String>,
+}
+
+fn default_min_severity() -> Severity {
+    Severity::Medium
+}
+
+/// Slack attachment and Discord embed colour for a severity
+fn color(severity: Severity) -> u32 {
+    match severity {
+        Severity::Critical => 0x8b0000,
+        Severity::High => 0xe01e5a,
+        Severity::Medium => 0xecb22e,
+        Severity::Low => 0x2eb67d,
+    }
+}
+
+fn emoji(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical => "\u{1f6a8}",
+        Severity::High => "\u{1f534}",
+        Severity::Medium => "\u{1f7e0}",
+        Severity::Low => "\u{1f535}",
+    }
+}
+
+/// The JSON body posted for an alert
+pub fn payload(service: ChatService, chat_id: Option<&str>, alert: &Alert) -> Value {
+    let title = format!("{} {} alert", emoji(alert.severity), alert.severity);
+    let source = alert.source.as_deref().unwrap_or("-");
+    let techniques = match alert.techniques.as_slice() {
+        [] => "-".to_string(),
+        ids => ids.iter().map(|id| technique_label(id)).collect::<Vec<_>>().join(", "),
+    };
+    match service {
+        ChatService::Slack => json!({
+            "text": format!("{}: {}", title, alert),
+            "attachments": [{
+                "color": format!("#{:06x}", color(alert.severity)),
+                "title": title,
+                "text": alert.message,
+                "fields": [
+                    { "title": "Kind", "value": alert.kind, "short": true },
+                    { "title": "Namespace", "value": source, "short": true },
+                    { "title": "ATT&CK", "value": techniques, "short": false },
+                ],
+                "ts": alert.time as u64,
+            }],
+        }),
+        ChatService::Discord => json!({
+            "embeds": [{
+                "title": title,
+                "description": alert.message,
+                "color": color(alert.severity),
+                "timestamp": format_rfc3339(alert.time),
+                "fields": [
+                    { "name": "Kind", "value": alert.kind, "inline": true },
+                    { "name": "Namespace", "value": source, "inline": true },
+                    { "name": "ATT&CK", "value": techniques, "inline": false },
+                ],
+            }],
+        }),
+        ChatService::Telegram => json!({
+            "chat_id": chat_id,
+            "parse_mode": "HTML",
+            "text": format!(
+                "<b>{}</b>\n{}\n<i>{} · {} · {} · {}</i>",
+                escape_html(&title),
+                escape_html(&alert.message),
+                alert.kind,
+                escape_html(&techniques),
+                escape_html(source),
+                format_rfc3339(alert.time)
+            ),
+        }),
+    }
+}
+
+/// `T1046 Network Service Discovery` for techniques the detectors know, else the ID
+fn technique_label(id: &str) -> String {
+    technique_name(id).map_or_else(|| id.to_string(), |name| format!("{} {}", id, name))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+enum Request {
+    Alert(Alert),
+    Flush(mpsc::Sender<()>),
+}
+
+/// Posts alerts to one chat channel. Requests go out from a background thread
+/// so a slow service never holds up the capture.
+pub struct ChatSink {
+    name: String,
+    requests: Sender<Request>,
+    min_severity: Severity,
+    kinds: Vec<String>,
+}
+
+impl ChatSink {
+    /// Checks the channel settings and starts its sender thread, fed through
+    /// a queue with the `exporters` stage settings
+    pub fn start(config: &ChatConfig, queue: StageConfig) -> Result<Self, CaptureError> {
+        let missing = |what: &str| CaptureError::InputError(format!("{:?} chat notifications need {}", config.service, what));
+        let url = match config.service {
+            ChatService::Slack | ChatService::Discord => config.webhook.clone().ok_or_else(|| missing("a webhook"))?,
+            ChatService::Telegram => {
+                let var = config.token_env.as_deref().ok_or_else(|| missing("token_env"))?;
+                config.chat_id.as_ref().ok_or_else(|| missing("a chat_id"))?;
+                let token = std::env::var(var)
+                    .map_err(|_| CaptureError::InputError(format!("Environment variable {} with the Telegram token is not set", var)))?;
+                format!("https://api.telegram.org/bot{}/sendMessage", token)
+            }
+        };
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err(CaptureError::InputError(format!("Invalid {:?} webhook URL", config.service)));
+        }
+        for kind in &config.kinds {
+            if !["builtin", "rule", "threshold"].contains(&kind.as_str()) {
+                return Err(CaptureError::InputError(format!("Unknown alert kind '{}' in chat settings", kind)));
+            }
+        }
+        let name = format!("{:?}", config.service).to_lowercase();
+        let poster = Poster { service: config.service, chat_id: config.chat_id.clone(), url, errors: 0 };
+        let (requests, receiver) = backpressure::channel(&format!("exporters/{}", name), queue);
+        thread::Builder::new()
+            .name(name.clone())
+            .spawn(move || poster.run(receiver))
+            .map_err(|e| CaptureError::Other(format!("Cannot start the {} notifier: {}", name, e)))?;
+        Ok(ChatSink { name, requests, min_severity: config.min_severity, kinds: config.kinds.clone() })
+    }
+
+    /// Whether an alert is routed to this channel
+    fn accepts(&self, alert: &Alert) -> bool {
+        alert.severity >= self.min_severity && (self.kinds.is_empty() || self.kinds.iter().any(|kind| kind == alert.kind))
+    }
+}
+
+impl AlertSink for ChatSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send(&mut self, alert: &Alert) {
+        if self.accepts(alert) {
+            let _ = self.requests.send(Request::Alert(alert.clone()));
+        }
+    }
+
+    /// Waits for the queued alerts to be posted
+    fn flush(&mut self) {
+        let (done, wait) = mpsc::channel();
+        if self.requests.send(Request::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+}
+
+struct Poster {
+    service: ChatService,
+    chat_id: Option<String>,
+    /// Holds the Telegram token, so it is never logged
+    url: String,
+    errors: u32,
+}
+
+impl Poster {
+    fn run(mut self, requests: Receiver<Request>) {
+        let client = match reqwest::blocking::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("Cannot start {:?} notifications: {}", self.service, e);
+                return;
+            }
+        };
+        for request in requests {
+            match request {
+                Request::Alert(alert) => {
+                    let body = payload(self.service, self.chat_id.as_deref(), &alert);
+                    if let Err(e) = self.post(&client, &body) {
+                        self.errors += 1;
+                        // A dead webhook would otherwise log on every alert
+                        if self.errors <= 5 {
+                            warn!("Could not post alert to {:?}: {}", self.service, e);
+                        }
+                    }
+                }
+                Request::Flush(done) => {
+                    let _ = done.send(());
+                }
+            }
+        }
+    }
+
+    /// Posts once, and once more after the wait a rate-limited service asks for
+    fn post(&self, client: &reqwest::blocking::Client, body: &Value) -> Result<(), String> {
+        let send = || client.post(&self.url).json(body).send().map_err(|e| e.without_url().to_string());
+        let mut response = send()?;
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok()?.parse::<f64>().ok())
+                .unwrap_or(1.0);
+            thread::sleep(Duration::from_secs_f64(retry_after.max(0.0)).min(MAX_RETRY_AFTER));
+            response = send()?;
+        }
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alert() -> Alert {
+        Alert {
+            time: 1_700_000_000.0,
+            severity: Severity::High,
+            kind: "rule",
+            message: "Rule 'smb' matched 10.0.0.5 -> <unknown>".to_string(),
+            source: Some("web".to_string()),
+            hosts: Vec::new(),
+            techniques: vec!["T1557.001".to_string()],
+        }
+    }
+
+    #[test]
+    fn formats_rich_messages() {
+        let slack = payload(ChatService::Slack, None, &alert());
+        assert_eq!(slack["attachments"][0]["color"], "#e01e5a");
+        assert_eq!(slack["attachments"][0]["fields"][1]["value"], "web");
+        assert_eq!(slack["text"], "\u{1f534} HIGH alert: [web] Rule 'smb' matched 10.0.0.5 -> <unknown> [ATT&CK T1557.001]");
+
+        let discord = payload(ChatService::Discord, None, &alert());
+        assert_eq!(discord["embeds"][0]["color"], 0xe01e5a);
+        assert_eq!(discord["embeds"][0]["timestamp"], "2023-11-14T22:13:20.000000Z");
+
+        let telegram = payload(ChatService::Telegram, Some("-1001"), &alert());
+        assert_eq!(telegram["chat_id"], "-1001");
+        assert_eq!(
+            telegram["text"],
+            "<b>\u{1f534} HIGH alert</b>\nRule 'smb' matched 10.0.0.5 -&gt; &lt;unknown&gt;\n<i>rule · T1557.001 LLMNR/NBT-NS Poisoning and SMB Relay · web · 2023-11-14T22:13:20.000000Z</i>"
+        );
+    }
+
+    #[test]
+    fn routes_by_severity_and_kind() {
+        let config = ChatConfig {
+            service: ChatService::Discord,
+            webhook: Some("https://discord.com/api/webhooks/1/x".to_string()),
+            token_env: None,
+            chat_id: None,
+            min_severity: Severity::High,
+            kinds: vec!["rule".to_string()],
+        };
+        let sink = ChatSink::start(&config, StageConfig::default()).unwrap();
+        assert!(sink.accepts(&alert()));
+        assert!(!sink.accepts(&Alert { severity: Severity::Medium, ..alert() }));
+        assert!(!sink.accepts(&Alert { kind: "threshold", ..alert() }));
+        assert!(ChatSink::start(&ChatConfig { webhook: None, ..config.clone() }, StageConfig::default()).is_err());
+        assert!(ChatSink::start(&ChatConfig { kinds: vec!["rules".to_string()], ..config }, StageConfig::default()).is_err());
+    }
+}
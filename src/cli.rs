@@ -0,0 +1,672 @@
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use ipnet::IpNet;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+
+/// A sniffer made with rust and using some AI calls to evaluate security
+#[derive(Parser, Debug)]
+#[command(name = "rust-sniffer", version, about)]
+pub struct Cli {
+    /// Replay a pcap file through the parser as fast as possible and report throughput
+    #[arg(long, value_name = "PCAP")]
+    pub benchmark: Option<String>,
+
+    /// Interface to capture on; without it an interactive picker is shown
+    #[arg(short, long)]
+    pub interface: Option<String>,
+
+    /// `basic` prints packets and alerts; `ai` also has the AI analyze packets and flows (needs DEEPSEEK_API_KEY)
+    #[arg(long, value_enum, default_value_t = CaptureMode::Basic)]
+    pub mode: CaptureMode,
+
+    /// Analyze a capture file instead of a live interface; `-` reads a pcap stream from stdin
+    #[arg(short, long, value_name = "PCAP", conflicts_with = "interface")]
+    pub read_file: Option<String>,
+
+    /// Packet output format (default: text, or `output` from the config file)
+    #[arg(short = 'T', long, value_enum)]
+    pub output: Option<OutputFormat>,
+
+    /// Print no packets, only alerts and summaries
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Print what each decoded field means (-v), and a hexdump of each packet (-vv)
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// How packet times are printed (default: epoch, or `time_format` from the config file)
+    #[arg(long, value_enum)]
+    pub time_format: Option<TimeFormat>,
+
+    /// Color packets by protocol and alerts by severity; `auto` colors only on a terminal
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorMode::Auto)]
+    pub color: ColorMode,
+
+    /// Save the packets that pass the capture and display filters to this pcap file (overwritten if it exists)
+    #[arg(short, long, value_name = "PCAP")]
+    pub write_file: Option<PathBuf>,
+
+    /// Stream the filtered packets in pcap format to a named pipe (created if missing)
+    #[arg(long, value_name = "FIFO")]
+    pub pipe: Option<PathBuf>,
+
+    /// Put a wireless interface into monitor mode (rfmon) and decode 802.11/radiotap frames
+    #[arg(long)]
+    pub monitor: bool,
+
+    /// Append WPA 4-way handshakes and PMKIDs from 802.11 captures to this file in hashcat 22000 format
+    #[arg(long, value_name = "FILE")]
+    pub wpa_export: Option<PathBuf>,
+
+    /// Write a JSON session report (capture settings, libpcap stats, protocols, top talkers, alerts, AI analyses) here on exit
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<PathBuf>,
+
+    /// Save flows, routers, gateways, name owners and frame size baselines to this file
+    /// periodically and on exit, and restore them from it on startup
+    #[arg(long, value_name = "FILE")]
+    pub state: Option<PathBuf>,
+
+    /// Seconds between --state checkpoints
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    pub checkpoint_interval: u64,
+
+    /// Group finished flows by similarity and raise an alert for flows unlike any seen before
+    /// (kept across restarts with --state)
+    #[arg(long)]
+    pub cluster_flows: bool,
+
+    /// Accept stats, flows, alerts, filter, dump and analyze requests on this Unix socket while capturing live
+    #[arg(long, value_name = "SOCKET")]
+    pub control: Option<PathBuf>,
+
+    /// Recent packets kept in memory for the control socket's dump request
+    #[arg(long, value_name = "PACKETS", default_value_t = 10000)]
+    pub ring_buffer: usize,
+
+    /// IPv6 transition tunnel allowed on this network; other Teredo/6to4/6in4 traffic raises a policy alert (repeatable)
+    #[arg(long, value_enum, value_name = "KIND")]
+    pub allow_tunnel: Vec<TunnelKind>,
+
+    /// Router ID expected to speak OSPF on the segment (repeatable); Hellos from other routers raise an alert
+    #[arg(long, value_name = "ROUTER_ID")]
+    pub ospf_router: Vec<Ipv4Addr>,
+
+    /// Subnet that counts as local when classifying traffic direction (repeatable), besides the interface's own
+    #[arg(long, value_name = "CIDR")]
+    pub local_net: Vec<IpNet>,
+
+    /// Don't put the interface into promiscuous mode
+    #[arg(long)]
+    pub no_promisc: bool,
+
+    /// Bytes to capture per packet (default 262144); longer packets are truncated
+    #[arg(short = 's', long, value_name = "BYTES", value_parser = clap::value_parser!(i32).range(64..=262144))]
+    pub snaplen: Option<i32>,
+
+    /// Only capture packets travelling in this direction (not supported on every platform)
+    #[arg(long, value_enum)]
+    pub direction: Option<Direction>,
+
+    /// Timestamp precision of live captures; nanoseconds where the platform has them
+    #[arg(long, value_enum, value_name = "PRECISION", default_value_t = TstampPrecision::Micro)]
+    pub tstamp_precision: TstampPrecision,
+
+    /// Clock that timestamps packets, e.g. `adapter` for hardware timestamps on NICs that offer them
+    #[arg(long, value_enum, value_name = "TYPE")]
+    pub tstamp_type: Option<TstampType>,
+
+    /// Seconds between samples of libpcap's counters, logged with packet and drop rates
+    #[arg(long, value_name = "SECS", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub stats_interval: u64,
+
+    /// Capture inside a Linux network namespace: an `ip netns` name, a PID, `docker:<container>`
+    /// or a namespace file path. Repeat to capture in several namespaces at once
+    #[arg(long, value_name = "NAME|PID", conflicts_with = "read_file")]
+    pub netns: Vec<String>,
+
+    /// Switch to this unprivileged user once the capture is open, before any packet is parsed
+    #[arg(long, value_name = "USER")]
+    pub user: Option<String>,
+
+    /// Group to switch to with `--user` (defaults to the user's primary group)
+    #[arg(long, value_name = "GROUP", requires = "user")]
+    pub group: Option<String>,
+
+    /// Restrict the capture to the system calls it needs with seccomp once it is running (Linux)
+    #[arg(long)]
+    pub sandbox: bool,
+
+    /// Run a Lua script's on_packet/on_flow_end/on_alert hooks during the capture (needs the lua feature)
+    #[arg(long, value_name = "FILE")]
+    pub script: Option<PathBuf>,
+
+    /// Send alerts to syslog: udp://host[:port], tcp://host[:port], tls://host[:port] or a socket such as /dev/log
+    #[arg(long, value_name = "ADDRESS")]
+    pub syslog: Option<String>,
+
+    /// Load a dissector plugin (a shared library built with `export_dissector!`); repeatable
+    #[arg(long, value_name = "LIB", global = true)]
+    pub plugin: Vec<PathBuf>,
+
+    /// Decode a custom binary protocol described in a TOML spec; repeatable
+    #[arg(long, value_name = "SPEC", global = true)]
+    pub protocol: Vec<PathBuf>,
+
+    /// Load a WebAssembly dissector plugin (.wasm or .wat), run in a wasmtime sandbox; repeatable
+    #[cfg(feature = "wasm-plugins")]
+    #[arg(long, value_name = "MODULE", global = true)]
+    pub wasm_plugin: Vec<PathBuf>,
+
+    /// Configuration file (TOML)
+    #[arg(long, value_name = "FILE", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// BPF capture filter applied by libpcap, e.g. `tcp port 443`
+    #[arg(short = 'f', long, value_name = "BPF")]
+    pub filter: Option<String>,
+
+    /// Display filter evaluated on parsed packets, e.g. `udp.port == 53`
+    #[arg(short = 'Y', long, value_name = "EXPR")]
+    pub display_filter: Option<String>,
+
+    /// Describe the traffic to capture in plain words; the AI suggests BPF and display filters,
+    /// which are checked and applied once confirmed (needs DEEPSEEK_API_KEY)
+    #[arg(long, value_name = "QUESTION", conflicts_with_all = ["filter", "display_filter"])]
+    pub ask_filter: Option<String>,
+
+    /// Named filter preset (dns, web, voip, broadcast-only, no-encrypted, or from the config file)
+    #[arg(long, value_name = "NAME")]
+    pub preset: Option<String>,
+
+    /// List available filter presets and exit
+    #[arg(long)]
+    pub list_presets: bool,
+
+    /// List the built-in dissectors and what they are bound to, then exit
+    #[arg(long)]
+    pub list_dissectors: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Retransmit a pcap file onto a live interface
+    Replay {
+        /// Capture file to replay
+        file: String,
+
+        /// Interface to send packets on
+        #[arg(short, long)]
+        interface: String,
+
+        /// Packet pacing
+        #[arg(long, value_enum, default_value_t = Timing::Original)]
+        timing: Timing,
+
+        /// Packets per second when `--timing fixed` is used
+        #[arg(long, default_value_t = 1000)]
+        pps: u32,
+
+        /// Rewrite the source MAC address of every frame
+        #[arg(long, value_name = "MAC")]
+        src_mac: Option<String>,
+
+        /// Rewrite the destination MAC address of every frame
+        #[arg(long, value_name = "MAC")]
+        dst_mac: Option<String>,
+
+        /// Rewrite an IP address, e.g. `192.168.1.10=10.0.0.10` (repeatable)
+        #[arg(long = "rewrite-ip", value_name = "OLD=NEW")]
+        rewrite_ip: Vec<String>,
+    },
+
+    /// List capture devices with their flags, addresses and datalink types
+    Interfaces {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
+
+    /// Merge capture files (pcap or pcapng) into one pcap ordered by timestamp
+    Merge {
+        /// Files to merge
+        #[arg(required = true, num_args = 2..)]
+        inputs: Vec<PathBuf>,
+
+        /// Output pcap file
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// Split a capture file by packet count, size or conversation
+    #[command(group(ArgGroup::new("mode").required(true).args(["packets", "size", "per_flow"])))]
+    Split {
+        /// Capture file to split
+        input: PathBuf,
+
+        /// Output file prefix; defaults to the input path without its extension
+        #[arg(long)]
+        prefix: Option<String>,
+
+        /// Packets per output file
+        #[arg(long)]
+        packets: Option<u64>,
+
+        /// Maximum output file size, e.g. `100M`
+        #[arg(long)]
+        size: Option<String>,
+
+        /// One file per TCP/UDP conversation
+        #[arg(long)]
+        per_flow: bool,
+    },
+
+    /// Carve packets between two timestamps and/or matching a filter into a new file
+    #[command(group(ArgGroup::new("criteria").required(true).multiple(true).args(["start", "end", "filter", "display_filter"])))]
+    Slice {
+        /// Capture file to read
+        input: PathBuf,
+
+        /// Output pcap file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Keep packets at or after this time (Unix seconds or UTC `2023-11-14T22:13:20Z`)
+        #[arg(long)]
+        start: Option<String>,
+
+        /// Keep packets before this time
+        #[arg(long)]
+        end: Option<String>,
+
+        /// BPF filter applied while reading
+        #[arg(short = 'f', long, value_name = "BPF")]
+        filter: Option<String>,
+
+        /// Display filter packets must match
+        #[arg(short = 'Y', long, value_name = "EXPR")]
+        display_filter: Option<String>,
+    },
+
+    /// Write a shareable copy of a capture with anonymized addresses and payloads
+    Anonymize {
+        /// Capture file to read
+        input: PathBuf,
+
+        /// Output pcap file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// 64 hex digit key; reuse it to map addresses identically across captures
+        #[arg(long)]
+        key: Option<String>,
+
+        /// Overwrite application payloads with zeros
+        #[arg(long, conflicts_with = "truncate_payload")]
+        zero_payload: bool,
+
+        /// Keep at most this many payload bytes per packet
+        #[arg(long, value_name = "BYTES")]
+        truncate_payload: Option<usize>,
+    },
+
+    /// Build or refresh the search index stored next to capture files (`<file>.idx`)
+    Index {
+        /// Capture files or directories containing them
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Find packets or flows in indexed captures without rescanning them
+    Search {
+        /// Capture files or directories containing them
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Source or destination address or CIDR network
+        #[arg(long)]
+        ip: Option<String>,
+
+        /// Source or destination port
+        #[arg(long)]
+        port: Option<u16>,
+
+        /// Transport protocol (tcp, udp, icmp, icmpv6 or a number)
+        #[arg(long)]
+        proto: Option<String>,
+
+        /// Substring of a DNS query name or TLS server name (SNI)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Only packets at or after this time (Unix seconds or UTC `2023-11-14T22:13:20Z`)
+        #[arg(long)]
+        start: Option<String>,
+
+        /// Only packets before this time
+        #[arg(long)]
+        end: Option<String>,
+
+        /// Summarize matches per conversation instead of listing packets
+        #[arg(long)]
+        flows: bool,
+
+        /// Also copy the matching packets into this pcap file
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Tell what a host or flow did across capture files: first contacts, DNS lookups,
+    /// connections, transfers and the alerts of the --config rules and thresholds, in time order
+    Timeline {
+        /// Capture files or directories containing them, read in order
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Host address, or a flow such as `10.0.0.5:51000-203.0.113.7:443`
+        #[arg(long)]
+        focus: String,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
+
+    /// Ask questions about stored captures in plain words; queries run locally over their
+    /// indexes and only the results are sent to the AI to phrase an answer (needs DEEPSEEK_API_KEY)
+    Chat {
+        /// Capture files or directories containing them
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Subnet that counts as local for external peers (repeatable); private ranges by default
+        #[arg(long, value_name = "CIDR")]
+        local_net: Vec<IpNet>,
+    },
+
+    /// Reconstruct a TCP stream or UDP conversation from a capture file, like Wireshark's Follow Stream
+    Follow {
+        /// Capture file to read
+        input: PathBuf,
+
+        /// Follow UDP conversations instead of TCP streams
+        #[arg(long)]
+        udp: bool,
+
+        /// Stream number, as shown by --list
+        #[arg(short, long, required_unless_present = "list")]
+        stream: Option<usize>,
+
+        /// List the streams in the file instead of following one
+        #[arg(long, conflicts_with = "stream")]
+        list: bool,
+
+        /// How to print the conversation
+        #[arg(long, value_enum, default_value_t = FollowFormat::Ascii)]
+        format: FollowFormat,
+    },
+
+    /// List QUIC connections in a capture file with their application (h3 via ALPN), versions and activity
+    Quic {
+        /// Capture file to read
+        input: PathBuf,
+    },
+
+    /// Count gRPC calls and status codes per method in the HTTP/2 connections of a capture file
+    Grpc {
+        /// Capture file to read
+        input: PathBuf,
+
+        /// NSS key log file (SSLKEYLOGFILE) for decrypting TLS 1.3 connections
+        #[arg(long)]
+        keylog: Option<PathBuf>,
+    },
+
+    /// Find DNS-over-HTTPS, -TLS and -QUIC connections and the hosts bypassing the organization's resolvers
+    EncryptedDns {
+        /// Capture file to read
+        input: PathBuf,
+
+        /// Address or CIDR network of an organization resolver (repeatable); encrypted DNS to anything else is flagged
+        #[arg(long)]
+        resolver: Vec<String>,
+
+        /// NSS key log file (SSLKEYLOGFILE) for confirming DoH in TLS 1.3 connections
+        #[arg(long)]
+        keylog: Option<PathBuf>,
+    },
+
+    /// Summarize RADIUS authentication per NAS: requests, accepts, rejects and failure rate
+    Radius {
+        /// Capture file to read
+        input: PathBuf,
+
+        /// Users with the most rejections to list per NAS
+        #[arg(long, default_value_t = 5)]
+        top_users: usize,
+    },
+
+    /// List WebSocket sessions in a capture file with their messages
+    Websocket {
+        /// Capture file to read
+        input: PathBuf,
+
+        /// Characters of each text message to show
+        #[arg(long, default_value_t = 80)]
+        preview: usize,
+    },
+
+    /// Identify files (executables, archives, documents, images) carried in TCP streams of a capture
+    Files {
+        /// Capture file to read
+        input: PathBuf,
+
+        /// Raise an alert for each executable downloaded over plain HTTP
+        #[arg(long)]
+        alert_http_executables: bool,
+    },
+
+    /// Actively probe hosts (ARP who-has, ICMP echo) and list the ones that answer
+    Probe {
+        /// Interface to probe from
+        #[arg(short, long)]
+        interface: String,
+
+        /// Target address or CIDR range, e.g. `192.168.1.0/24`
+        #[arg(short, long)]
+        targets: String,
+
+        /// Skip the ARP sweep
+        #[arg(long)]
+        no_arp: bool,
+
+        /// Also send ICMP echo requests to hosts that answered ARP
+        #[arg(long)]
+        icmp: bool,
+
+        /// Seconds to keep listening for responses
+        #[arg(long, default_value_t = 3)]
+        wait: u64,
+
+        /// Milliseconds between consecutive probes
+        #[arg(long, default_value_t = 2)]
+        interval_ms: u64,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaptureMode {
+    Basic,
+    Ai,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Direction {
+    /// Packets received by the interface
+    In,
+    /// Packets sent by the interface
+    Out,
+    /// Both directions
+    Inout,
+}
+
+impl From<Direction> for pcap::Direction {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::In => pcap::Direction::In,
+            Direction::Out => pcap::Direction::Out,
+            Direction::Inout => pcap::Direction::InOut,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum TstampPrecision {
+    Micro,
+    Nano,
+}
+
+impl From<TstampPrecision> for pcap::Precision {
+    fn from(precision: TstampPrecision) -> Self {
+        match precision {
+            TstampPrecision::Micro => pcap::Precision::Micro,
+            TstampPrecision::Nano => pcap::Precision::Nano,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum TstampType {
+    /// The host's clock, of unknown characteristics
+    Host,
+    /// The host's clock, cheap but coarse
+    HostLowprec,
+    /// The host's clock, precise
+    HostHiprec,
+    /// The network adapter's clock, synchronized with the system clock
+    Adapter,
+    /// The network adapter's own clock
+    AdapterUnsynced,
+}
+
+impl From<TstampType> for pcap::TimestampType {
+    fn from(tstamp_type: TstampType) -> Self {
+        match tstamp_type {
+            TstampType::Host => pcap::TimestampType::Host,
+            TstampType::HostLowprec => pcap::TimestampType::HostLowPrec,
+            TstampType::HostHiprec => pcap::TimestampType::HostHighPrec,
+            TstampType::Adapter => pcap::TimestampType::Adapter,
+            TstampType::AdapterUnsynced => pcap::TimestampType::AdapterUnsynced,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum OutputFormat {
+    /// Human-readable log lines
+    Text,
+    /// Elasticsearch bulk JSON, compatible with `tshark -T ek`
+    Ek,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+impl From<ColorMode> for rust_sniffer::color::ColorMode {
+    fn from(mode: ColorMode) -> Self {
+        match mode {
+            ColorMode::Auto => rust_sniffer::color::ColorMode::Auto,
+            ColorMode::Always => rust_sniffer::color::ColorMode::Always,
+            ColorMode::Never => rust_sniffer::color::ColorMode::Never,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum TimeFormat {
+    /// Seconds since the epoch with microseconds
+    Epoch,
+    /// ISO 8601 in UTC
+    Iso,
+    /// ISO 8601 in the local time zone, with its offset
+    Local,
+    /// Seconds since the previous packet
+    Delta,
+    /// Seconds since the first packet
+    Relative,
+}
+
+impl From<TimeFormat> for rust_sniffer::output::TimeFormat {
+    fn from(format: TimeFormat) -> Self {
+        match format {
+            TimeFormat::Epoch => rust_sniffer::output::TimeFormat::Epoch,
+            TimeFormat::Iso => rust_sniffer::output::TimeFormat::Iso,
+            TimeFormat::Local => rust_sniffer::output::TimeFormat::Local,
+            TimeFormat::Delta => rust_sniffer::output::TimeFormat::Delta,
+            TimeFormat::Relative => rust_sniffer::output::TimeFormat::Relative,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum FollowFormat {
+    /// Printable text, other bytes shown as dots
+    Ascii,
+    /// Hexdump with offsets
+    Hex,
+}
+
+impl From<FollowFormat> for rust_sniffer::follow::FollowFormat {
+    fn from(format: FollowFormat) -> Self {
+        match format {
+            FollowFormat::Ascii => rust_sniffer::follow::FollowFormat::Ascii,
+            FollowFormat::Hex => rust_sniffer::follow::FollowFormat::Hex,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum TunnelKind {
+    /// Protocol 41 to 2002::/16 or the 192.88.99.0/24 relays
+    #[value(name = "6to4")]
+    SixToFour,
+    /// Other protocol 41 tunnels (tunnel brokers, configured tunnels)
+    #[value(name = "6in4")]
+    SixInFour,
+    /// IPv6 over UDP port 3544
+    Teredo,
+}
+
+impl From<TunnelKind> for rust_sniffer::protocols::tunnel::TunnelKind {
+    fn from(kind: TunnelKind) -> Self {
+        match kind {
+            TunnelKind::SixToFour => rust_sniffer::protocols::tunnel::TunnelKind::SixToFour,
+            TunnelKind::SixInFour => rust_sniffer::protocols::tunnel::TunnelKind::SixInFour,
+            TunnelKind::Teredo => rust_sniffer::protocols::tunnel::TunnelKind::Teredo,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ListFormat {
+    Table,
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Timing {
+    /// Keep the gaps recorded in the file
+    Original,
+    /// Fixed packets per second (see --pps)
+    Fixed,
+    /// As fast as possible
+    Max,
+}
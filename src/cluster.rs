@@ -0,0 +1,185 @@
+use crate::flow::{FlowEnd, FlowKey, FlowRecord};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Length of a flow's feature vector
+pub const FEATURES: usize = 9;
+
+/// Distance under which a flow joins an existing cluster
+pub const DEFAULT_RADIUS: f64 = 0.25;
+
+/// Flows clustered before a new cluster counts as novel; until then every
+/// kind of traffic is new
+pub const DEFAULT_WARMUP: u64 = 100;
+
+/// Clusters kept; past this a flow joins the nearest one however far it is
+const MAX_CLUSTERS: usize = 512;
+
+/// What a flow looks like, scaled so each feature is roughly within 0..1:
+/// packets, bytes and duration on a log scale, the initiator's share of the
+/// bytes, mean packet size, TCP, UDP, the class of the responder's port and
+/// whether the flow was reset
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FlowFeatures(pub [f64; FEATURES]);
+
+impl FlowFeatures {
+    pub fn from_flow(flow: &FlowRecord) -> Self {
+        let log = |value: f64, scale: f64| (1.0 + value.max(0.0)).ln() / scale;
+        let responder_port = if flow.initiator == flow.key.a { flow.key.b.1 } else { flow.key.a.1 };
+        let port_class = match responder_port {
+            0 => 0.0,
+            1..=1023 => 0.25,
+            1024..=49151 => 0.5,
+            _ => 1.0,
+        };
+        let flag = |set: bool| if set { 1.0 } else { 0.0 };
+        FlowFeatures([
+            log(flow.packets as f64, 12.0),
+            log(flow.bytes as f64, 20.0),
+            log(flow.last - flow.first, 10.0),
+            if flow.bytes > 0 { flow.bytes_out as f64 / flow.bytes as f64 } else { 0.0 },
+            if flow.packets > 0 { (flow.bytes as f64 / flow.packets as f64 / 1500.0).min(1.0) } else { 0.0 },
+            flag(flow.key.protocol == 6),
+            flag(flow.key.protocol == 17),
+            port_class,
+            flag(flow.end == FlowEnd::Reset),
+        ])
+    }
+
+    pub fn distance(&self, other: &FlowFeatures) -> f64 {
+        self.0.iter().zip(other.0.iter()).map(|(a, b)| (a - b).powi(2)).sum::<f64>().sqrt()
+    }
+}
+
+/// Flows that look alike, summarized by their mean features
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cluster {
+    pub id: usize,
+    pub centroid: FlowFeatures,
+    pub flows: u64,
+    /// The flow that started it
+    pub first: FlowKey,
+}
+
+/// Where a flow landed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClusterMatch {
+    pub cluster: usize,
+    /// Flows already in the cluster before this one
+    pub similar: u64,
+    pub distance: f64,
+    /// The flow started a cluster after the warm-up: nothing like it was seen before
+    pub novel: bool,
+}
+
+impl fmt::Display for ClusterMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.similar {
+            0 => write!(f, "unlike any previous flow (new cluster #{})", self.cluster),
+            1 => write!(f, "resembles 1 previous flow (cluster #{})", self.cluster),
+            n => write!(f, "resembles {} previous flows (cluster #{})", n, self.cluster),
+        }
+    }
+}
+
+/// Groups finished flows by similarity as they come, so unusual ones stand out.
+/// Each flow joins the nearest cluster within the radius and moves its
+/// centroid, or starts a cluster of its own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlowClusters {
+    radius: f64,
+    warmup: u64,
+    seen: u64,
+    clusters: Vec<Cluster>,
+}
+
+impl Default for FlowClusters {
+    fn default() -> Self {
+        FlowClusters::new(DEFAULT_RADIUS, DEFAULT_WARMUP)
+    }
+}
+
+impl FlowClusters {
+    pub fn new(radius: f64, warmup: u64) -> Self {
+        FlowClusters { radius, warmup, seen: 0, clusters: Vec::new() }
+    }
+
+    pub fn clusters(&self) -> &[Cluster] {
+        &self.clusters
+    }
+
+    /// Places a finished flow in a cluster
+    pub fn assign(&mut self, flow: &FlowRecord) -> ClusterMatch {
+        let features = FlowFeatures::from_flow(flow);
+        self.seen += 1;
+        let nearest = self
+            .clusters
+            .iter()
+            .enumerate()
+            .map(|(i, cluster)| (i, cluster.centroid.distance(&features)))
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        match nearest {
+            Some((i, distance)) if distance <= self.radius || self.clusters.len() >= MAX_CLUSTERS => {
+                let cluster = &mut self.clusters[i];
+                let similar = cluster.flows;
+                cluster.flows += 1;
+                let weight = 1.0 / cluster.flows as f64;
+                for (c, f) in cluster.centroid.0.iter_mut().zip(features.0) {
+                    *c += (f - *c) * weight;
+                }
+                ClusterMatch { cluster: cluster.id, similar, distance, novel: false }
+            }
+            _ => {
+                let id = self.clusters.len() + 1;
+                self.clusters.push(Cluster { id, centroid: features, flows: 1, first: flow.key });
+                ClusterMatch { cluster: id, similar: 0, distance: 0.0, novel: self.seen > self.warmup }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn flow(port: u16, packets: u64, bytes: u64, duration: f64) -> FlowRecord {
+        let client = (IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)), 40000 + packets as u16);
+        let server = (IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34)), port);
+        FlowRecord {
+            key: FlowKey::new(6, client, server),
+            initiator: client,
+            packets,
+            bytes,
+            packets_out: packets / 2,
+            bytes_out: bytes / 4,
+            first: 0.0,
+            last: duration,
+            end: FlowEnd::Fin,
+        }
+    }
+
+    #[test]
+    fn groups_similar_flows() {
+        let mut clusters = FlowClusters::new(DEFAULT_RADIUS, 3);
+        let mut last = None;
+        for packets in 20..24 {
+            last = Some(clusters.assign(&flow(443, packets, packets * 800, 2.0)));
+        }
+        let last = last.unwrap();
+        assert_eq!((last.cluster, last.similar, last.novel), (1, 3, false));
+        assert_eq!(last.to_string(), "resembles 3 previous flows (cluster #1)");
+
+        // A long, large transfer to a high port looks like nothing seen so far
+        let odd = clusters.assign(&flow(51515, 90000, 120_000_000, 3600.0));
+        assert_eq!((odd.cluster, odd.similar, odd.novel), (2, 0, true));
+        assert_eq!(clusters.clusters().len(), 2);
+    }
+
+    #[test]
+    fn nothing_is_novel_during_warmup() {
+        let mut clusters = FlowClusters::new(DEFAULT_RADIUS, 10);
+        assert!(!clusters.assign(&flow(443, 20, 16000, 2.0)).novel);
+        assert!(!clusters.assign(&flow(53, 2, 180, 0.01)).novel);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::alert::Severity;
+use crate::protocols::summary::PacketSummary;
+use std::env;
+
+/// When the human output uses ANSI colors
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Only on a terminal, and not when `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// ANSI foreground colors used by the text output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Teal,
+    BoldRed,
+}
+
+impl Color {
+    fn code(self) -> &'static str {
+        match self {
+            Color::Red => "31",
+            Color::Green => "32",
+            Color::Yellow => "33",
+            Color::Blue => "34",
+            Color::Magenta => "35",
+            Color::Teal => "36",
+            Color::BoldRed => "1;31",
+        }
+    }
+}
+
+/// Colors packets by protocol and alerts by severity, or leaves the text
+/// alone when colors are off
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    /// `terminal` tells whether the output is a TTY, for `Auto`
+    pub fn new(mode: ColorMode, terminal: bool) -> Self {
+        let enabled = match mode {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => terminal && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
+        };
+        Palette { enabled }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn paint(&self, color: Color, text: &str) -> String {
+        if self.enabled {
+            format!("\x1b[{}m{}\x1b[0m", color.code(), text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// `text` in the color of the packet's protocol; unknown protocols are left plain
+    pub fn protocol(&self, summary: Option<&PacketSummary>, text: &str) -> String {
+        match summary.and_then(protocol_color) {
+            Some(color) => self.paint(color, text),
+            None => text.to_string(),
+        }
+    }
+
+    /// Alerts are red, critical ones bold
+    pub fn severity(&self, severity: Severity, text: &str) -> String {
+        let color = match severity {
+            Severity::Critical => Color::BoldRed,
+            Severity::Low | Severity::Medium | Severity::High => Color::Red,
+        };
+        self.paint(color, text)
+    }
+}
+
+/// The color of the most specific protocol recognized: DNS yellow, HTTP green,
+/// other TCP teal, other UDP blue, ICMP and ARP magenta
+pub fn protocol_color(summary: &PacketSummary) -> Option<Color> {
+    if summary.has_port(53) || summary.has_port(5353) {
+        return Some(Color::Yellow);
+    }
+    match summary.ip_proto {
+        Some(6) if summary.has_port(80) || summary.has_port(8080) || summary.has_port(443) => Some(Color::Green),
+        Some(6) => Some(Color::Teal),
+        Some(17) => Some(Color::Blue),
+        Some(1 | 58) => Some(Color::Magenta),
+        _ if summary.ether_type == 0x0806 => Some(Color::Magenta),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn colors_by_protocol_and_severity() {
+        let dns = PacketSummary::from_ethernet(&testutil::ipv4_udp_frame(5353, 53, b"")).unwrap();
+        let https = PacketSummary::from_ethernet(&testutil::ipv4_tcp_frame(40000, 443, 0x18, b"")).unwrap();
+        let ssh = PacketSummary::from_ethernet(&testutil::ipv4_tcp_frame(40000, 22, 0x18, b"")).unwrap();
+        assert_eq!(protocol_color(&dns), Some(Color::Yellow));
+        assert_eq!(protocol_color(&https), Some(Color::Green));
+        assert_eq!(protocol_color(&ssh), Some(Color::Teal));
+
+        let palette = Palette::new(ColorMode::Always, false);
+        assert_eq!(palette.protocol(Some(&dns), "PACKET #1"), "\x1b[33mPACKET #1\x1b[0m");
+        assert_eq!(palette.protocol(None, "PACKET #1"), "PACKET #1");
+        assert_eq!(palette.severity(Severity::Critical, "alert"), "\x1b[1;31malert\x1b[0m");
+    }
+
+    #[test]
+    fn stays_plain_when_off() {
+        let palette = Palette::new(ColorMode::Never, true);
+        assert!(!palette.enabled());
+        assert_eq!(palette.severity(Severity::High, "alert"), "alert");
+        assert!(!Palette::new(ColorMode::Auto, false).enabled());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::backpressure::PipelineConfig;
+use crate::chat::ChatConfig;
+use crate::email::EmailConfig;
+use crate::error::CaptureError;
+use crate::exfil::ExfilConfig;
+use crate::rules::RuleConfig;
+use crate::stix::StixConfig;
+use crate::syslog::SyslogConfig;
+use crate::thresholds::ThresholdConfig;
+use ipnet::IpNet;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Settings loaded from the TOML configuration file (`--config`)
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Additional or overriding filter presets, keyed by name
+    pub presets: BTreeMap<String, PresetConfig>,
+    pub capture: CaptureConfig,
+    pub alerts: AlertConfig,
+    pub ai: AiConfig,
+    pub network: NetworkConfig,
+    /// Queue sizes and drop policies of the pipeline stages
+    pub pipeline: PipelineConfig,
+}
+
+/// Defaults for the capture options; command-line flags take precedence
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    pub filter: Option<String>,
+    pub display_filter: Option<String>,
+    pub preset: Option<String>,
+    /// `text` or `ek`
+    pub output: Option<String>,
+    /// `epoch`, `iso`, `local`, `delta` or `relative`
+    pub time_format: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct AlertConfig {
+    /// Tunnel kinds allowed on the network (`6to4`, `6in4`, `teredo`)
+    pub allow_tunnel: Vec<String>,
+    pub ospf_routers: Vec<Ipv4Addr>,
+    /// MaxMind country database for `country` in rule conditions
+    pub geoip: Option<PathBuf>,
+    pub rules: Vec<RuleConfig>,
+    pub thresholds: Vec<ThresholdConfig>,
+    /// Per-host upload volume baselines
+    pub exfil: Option<ExfilConfig>,
+    pub syslog: Option<SyslogConfig>,
+    pub email: Option<EmailConfig>,
+    pub chat: Vec<ChatConfig>,
+    pub stix: Option<StixConfig>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct AiConfig {
+    pub model: Option<String>,
+    pub max_tokens: Option<u32>,
+}
+
+/// What counts as the local network
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Subnets local besides the capture interface's own, e.g. other VLANs
+    pub local_subnets: Vec<IpNet>,
+    pub gateways: Vec<IpAddr>,
+    /// The gateways' hardware addresses; ARP placing a gateway anywhere else is an alert
+    pub gateway_macs: Vec<String>,
+    pub dns_servers: Vec<IpAddr>,
+    /// Learn prefixes, gateways and DNS servers from DHCP and ARP
+    pub learn: bool,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig { local_subnets: Vec::new(), gateways: Vec::new(), gateway_macs: Vec::new(), dns_servers: Vec::new(), learn: true }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct PresetConfig {
+    pub description: Option<String>,
+    pub bpf: Option<String>,
+    pub display: Option<String>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, CaptureError> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| CaptureError::InputError(format!("Cannot read config '{}': {}", path.display(), e)))?;
+        Self::parse(&text).map_err(|e| CaptureError::InputError(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, CaptureError> {
+        let config: Config = toml::from_str(text).map_err(|e| CaptureError::ParseError(e.to_string()))?;
+        config.pipeline.validate()?;
+        Ok(config)
+    }
+}
+
+static RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_reload(_signal: libc::c_int) {
+    RELOAD_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Notices when the configuration file should be re-read during a capture:
+/// on SIGHUP, or when its modification time changes (checked once a second)
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &Path) -> Self {
+        unsafe { libc::signal(libc::SIGHUP, request_reload as extern "C" fn(libc::c_int) as libc::sighandler_t) };
+        ConfigWatcher { path: path.to_path_buf(), modified: modified_time(path), last_check: Instant::now() }
+    }
+
+    /// The reloaded configuration when a reload is due and the file parses;
+    /// errors are returned so the caller can keep the settings it has
+    pub fn poll(&mut self) -> Option<Result<Config, CaptureError>> {
+        let signalled = RELOAD_REQUESTED.swap(false, Ordering::Relaxed);
+        if !signalled && self.last_check.elapsed() < Duration::from_secs(1) {
+            return None;
+        }
+        self.last_check = Instant::now();
+        let modified = modified_time(&self.path);
+        if !signalled && modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(Config::load(&self.path))
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backpressure::{DropPolicy, StageConfig};
+
+    #[test]
+    fn parses_capture_alert_and_ai_sections() {
+        let config = Config::parse(
+            r#"
+            [capture]
+            display_filter = "udp.port == 53"
+            output = "ek"
+
+            [alerts]
+            allow_tunnel = ["teredo"]
+            ospf_routers = ["10.0.0.1"]
+
+            [ai]
+            max_tokens = 500
+
+            [network]
+            local_subnets = ["10.20.0.0/16"]
+            dns_servers = ["10.20.0.53"]
+
+            [pipeline.exporters]
+            policy = "drop-oldest"
+            capacity = 100
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.capture.display_filter.as_deref(), Some("udp.port == 53"));
+        assert_eq!(config.capture.filter, None);
+        assert_eq!(config.alerts.ospf_routers, vec![Ipv4Addr::new(10, 0, 0, 1)]);
+        assert_eq!((config.ai.model, config.ai.max_tokens), (None, Some(500)));
+        assert_eq!(config.network.local_subnets, vec!["10.20.0.0/16".parse::<IpNet>().unwrap()]);
+        assert_eq!(config.network.dns_servers, vec!["10.20.0.53".parse::<IpAddr>().unwrap()]);
+        assert!(config.network.learn);
+        assert_eq!(config.pipeline.exporters, StageConfig { policy: DropPolicy::DropOldest, capacity: 100 });
+        assert_eq!(config.pipeline.pipe, StageConfig::default());
+        assert!(Config::parse("[pipeline.pipe]\npolicy = \"degrade\"\n").is_err());
+    }
+
+    #[test]
+    fn reloads_on_change_and_sighup() {
+        let path = std::env::temp_dir().join(format!("rust-sniffer-config-{}.toml", std::process::id()));
+        fs::write(&path, "[capture]\nfilter = \"tcp\"\n").unwrap();
+        let mut watcher = ConfigWatcher::new(&path);
+        assert!(watcher.poll().is_none());
+
+        fs::write(&path, "[capture]\nfilter = \"udp\"\n").unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(5)).unwrap();
+        watcher.last_check -= Duration::from_secs(2);
+        let config = watcher.poll().unwrap().unwrap();
+        assert_eq!(config.capture.filter.as_deref(), Some("udp"));
+
+        unsafe { libc::raise(libc::SIGHUP) };
+        assert!(watcher.poll().unwrap().is_ok());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,345 @@
+use crate::capfile::{create_writer, StoredPacket};
+use crate::error::CaptureError;
+use log::{info, warn};
+use pcap::{Linktype, Packet};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Longest request line accepted
+const MAX_LINE: usize = 4096;
+
+/// Connections served at once; more are turned away
+const MAX_CLIENTS: usize = 16;
+
+/// How long a reply may wait for a slow reader
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A request on the control socket, one per line: JSON such as
+/// `{"cmd": "filter", "bpf": "tcp port 80"}`, or the plain-text form
+/// `filter bpf tcp port 80`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase", deny_unknown_fields)]
+pub enum ControlRequest {
+    /// Packet counters, libpcap statistics and the active filters
+    Stats,
+    /// Open flows, largest first
+    Flows { limit: Option<usize> },
+    /// The latest alerts; the connection then receives every new alert as an `{"alert": ...}` line
+    Alerts,
+    /// Replaces the capture (BPF) and/or display filter; an empty expression clears it
+    Filter { bpf: Option<String>, display: Option<String> },
+    /// Writes the packets in the ring buffer to a pcap file
+    Dump { path: PathBuf },
+    /// Runs the AI analysis on the most recent packet (AI mode only)
+    Analyze,
+}
+
+impl ControlRequest {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        if line.starts_with('{') {
+            return serde_json::from_str(line).map_err(|e| format!("Invalid request: {}", e));
+        }
+        let (word, rest) = line.split_once(char::is_whitespace).map_or((line, ""), |(word, rest)| (word, rest.trim()));
+        match (word, rest) {
+            ("stats", "") => Ok(ControlRequest::Stats),
+            ("flows", "") => Ok(ControlRequest::Flows { limit: None }),
+            ("flows", limit) => limit.parse().map(|limit| ControlRequest::Flows { limit: Some(limit) }).map_err(|_| "Usage: flows [LIMIT]".to_string()),
+            ("alerts", "") => Ok(ControlRequest::Alerts),
+            ("analyze", "") => Ok(ControlRequest::Analyze),
+            ("dump", path) if !path.is_empty() => Ok(ControlRequest::Dump { path: path.into() }),
+            ("filter", rest) => {
+                let (kind, expression) = rest.split_once(char::is_whitespace).map_or((rest, ""), |(kind, e)| (kind, e.trim()));
+                let expression = Some(expression.to_string());
+                match kind {
+                    "bpf" => Ok(ControlRequest::Filter { bpf: expression, display: None }),
+                    "display" => Ok(ControlRequest::Filter { bpf: None, display: expression }),
+                    _ => Err("Usage: filter bpf|display EXPRESSION (none to clear)".to_string()),
+                }
+            }
+            _ => Err(format!("Unknown request '{}'; try stats, flows, alerts, filter, dump or analyze", line)),
+        }
+    }
+}
+
+/// `{"ok": true, ...fields}`
+pub fn success(mut fields: Value) -> Value {
+    if !fields.is_object() {
+        fields = json!({});
+    }
+    fields["ok"] = true.into();
+    fields
+}
+
+/// `{"ok": false, "error": message}`
+pub fn failure(message: &str) -> Value {
+    json!({ "ok": false, "error": message })
+}
+
+/// Identifies the connection a request came from, to send the reply back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientId(u64);
+
+struct Client {
+    id: ClientId,
+    stream: UnixStream,
+    pending: Vec<u8>,
+    /// Receives published alerts
+    subscribed: bool,
+}
+
+/// A Unix socket for controlling a running capture (`--control`). It never
+/// blocks: the capture loop polls it between packets.
+pub struct ControlServer {
+    path: PathBuf,
+    listener: UnixListener,
+    clients: Vec<Client>,
+    next_id: u64,
+}
+
+impl ControlServer {
+    /// Listens on `path`, replacing a socket left behind by an earlier run.
+    /// Only the owner may connect.
+    pub fn bind(path: &Path) -> Result<Self, CaptureError> {
+        let failed = |e: std::io::Error| CaptureError::Other(format!("Cannot listen on {}: {}", path.display(), e));
+        match std::fs::symlink_metadata(path) {
+            Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path).map_err(failed)?,
+            Ok(_) => return Err(CaptureError::InputError(format!("{} exists and is not a socket", path.display()))),
+            Err(_) => {}
+        }
+        let listener = UnixListener::bind(path).map_err(failed)?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).map_err(failed)?;
+        listener.set_nonblocking(true).map_err(failed)?;
+        info!("Control socket listening on {}", path.display());
+        Ok(ControlServer { path: path.to_path_buf(), listener, clients: Vec::new(), next_id: 0 })
+    }
+
+    /// Accepts new connections and returns the complete request lines received since the last poll
+    pub fn poll(&mut self) -> Vec<(ClientId, Result<ControlRequest, String>)> {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if self.clients.len() >= MAX_CLIENTS || stream.set_nonblocking(true).is_err() {
+                warn!("Turning away a control connection: {} already open", self.clients.len());
+                continue;
+            }
+            self.next_id += 1;
+            self.clients.push(Client { id: ClientId(self.next_id), stream, pending: Vec::new(), subscribed: false });
+        }
+        let mut requests = Vec::new();
+        self.clients.retain_mut(|client| {
+            let mut buffer = [0; 1024];
+            loop {
+                match client.stream.read(&mut buffer) {
+                    // A subscriber that is done sending stays until a write to it fails
+                    Ok(0) => return client.subscribed,
+                    Ok(n) => client.pending.extend_from_slice(&buffer[..n]),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(_) => return false,
+                }
+            }
+            while let Some(end) = client.pending.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = client.pending.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                if !line.trim().is_empty() {
+                    requests.push((client.id, ControlRequest::parse(&line)));
+                }
+            }
+            client.pending.len() <= MAX_LINE
+        });
+        requests
+    }
+
+    /// Sends a one-line JSON reply; a client that can't take it is disconnected
+    pub fn reply(&mut self, id: ClientId, response: &Value) {
+        let line = format!("{}\n", response);
+        self.clients.retain_mut(|client| client.id != id || send(&mut client.stream, &line));
+    }
+
+    /// Sends published events to a connection from now on
+    pub fn subscribe(&mut self, id: ClientId) {
+        if let Some(client) = self.clients.iter_mut().find(|client| client.id == id) {
+            client.subscribed = true;
+        }
+    }
+
+    /// Sends an event line to every subscribed connection
+    pub fn publish(&mut self, event: &Value) {
+        let line = format!("{}\n", event);
+        self.clients.retain_mut(|client| !client.subscribed || send(&mut client.stream, &line));
+    }
+}
+
+fn send(stream: &mut UnixStream, line: &str) -> bool {
+    stream
+        .set_nonblocking(false)
+        .and_then(|()| stream.set_write_timeout(Some(WRITE_TIMEOUT)))
+        .and_then(|()| stream.write_all(line.as_bytes()))
+        .and_then(|()| stream.set_nonblocking(true))
+        .is_ok()
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Talks to the control socket of a running capture, as `sniffctl` does
+pub struct ControlClient {
+    reader: BufReader<UnixStream>,
+}
+
+impl ControlClient {
+    pub fn connect(path: &Path) -> Result<Self, CaptureError> {
+        let stream = UnixStream::connect(path)
+            .map_err(|e| CaptureError::Other(format!("Cannot connect to {} (is the capture running with --control?): {}", path.display(), e)))?;
+        Ok(ControlClient { reader: BufReader::new(stream) })
+    }
+
+    /// Sends a request and returns its reply, or the error the capture answered with
+    pub fn request(&mut self, request: &ControlRequest) -> Result<Value, CaptureError> {
+        let line = serde_json::to_string(request).map_err(|e| CaptureError::Other(e.to_string()))? + "\n";
+        self.reader.get_mut().write_all(line.as_bytes()).map_err(|e| CaptureError::Other(format!("Control socket: {}", e)))?;
+        let reply = self.next().ok_or_else(|| CaptureError::Other("The capture closed the control connection".to_string()))??;
+        match reply["ok"].as_bool() {
+            Some(true) => Ok(reply),
+            _ => Err(CaptureError::Other(reply["error"].as_str().unwrap_or("Request failed").to_string())),
+        }
+    }
+}
+
+impl Iterator for ControlClient {
+    type Item = Result<Value, CaptureError>;
+
+    /// The next line the capture sends, such as a published alert
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => Some(serde_json::from_str(&line).map_err(|e| CaptureError::Other(format!("Invalid reply: {}", e)))),
+            Err(e) => Some(Err(CaptureError::Other(format!("Control socket: {}", e)))),
+        }
+    }
+}
+
+/// The most recent packets of a capture, kept for `dump` and `analyze`
+pub struct PacketRing {
+    capacity: usize,
+    packets: VecDeque<StoredPacket>,
+}
+
+impl PacketRing {
+    pub fn new(capacity: usize) -> Self {
+        PacketRing { capacity, packets: VecDeque::with_capacity(capacity.min(4096)) }
+    }
+
+    pub fn push(&mut self, packet: &Packet<'_>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.packets.len() == self.capacity {
+            self.packets.pop_front();
+        }
+        self.packets.push_back(StoredPacket::new(*packet.header, packet.data.to_vec()));
+    }
+
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    pub fn latest(&self) -> Option<&StoredPacket> {
+        self.packets.back()
+    }
+
+    /// Writes the buffered packets, oldest first, to a pcap file
+    pub fn dump(&self, datalink: i32, path: &Path) -> Result<usize, CaptureError> {
+        let mut writer = create_writer(Linktype(datalink), path)?;
+        for packet in &self.packets {
+            writer.write(&packet.as_packet());
+        }
+        writer.flush().map_err(|e| CaptureError::PcapError(format!("Cannot write {}: {}", path.display(), e)))?;
+        Ok(self.packets.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+
+    #[test]
+    fn parses_text_and_json_requests() {
+        assert_eq!(ControlRequest::parse("stats\n"), Ok(ControlRequest::Stats));
+        assert_eq!(
+            ControlRequest::parse("filter bpf tcp port 80"),
+            Ok(ControlRequest::Filter { bpf: Some("tcp port 80".to_string()), display: None })
+        );
+        assert_eq!(
+            ControlRequest::parse(r#"{"cmd": "filter", "display": ""}"#),
+            Ok(ControlRequest::Filter { bpf: None, display: Some(String::new()) })
+        );
+        assert_eq!(ControlRequest::parse(r#"{"cmd":"dump","path":"/tmp/x.pcap"}"#), Ok(ControlRequest::Dump { path: "/tmp/x.pcap".into() }));
+        assert!(ControlRequest::parse("dump").is_err() && ControlRequest::parse(r#"{"cmd":"reboot"}"#).is_err());
+        assert_eq!(ControlRequest::parse("flows 5"), Ok(ControlRequest::Flows { limit: Some(5) }));
+        assert!(ControlRequest::parse("flows all").is_err());
+    }
+
+    #[test]
+    fn answers_over_the_socket() {
+        let path = std::env::temp_dir().join(format!("control-{}.sock", std::process::id()));
+        let mut server = ControlServer::bind(&path).unwrap();
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.write_all(b"stats\nanalyze extra\n").unwrap();
+        let mut requests = Vec::new();
+        for _ in 0..100 {
+            requests.extend(server.poll());
+            if requests.len() == 2 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(requests[0].1, Ok(ControlRequest::Stats));
+        assert!(requests[1].1.is_err());
+        server.reply(requests[0].0, &success(json!({ "packets": 3 })));
+        let mut line = String::new();
+        BufReader::new(&client).read_line(&mut line).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&line).unwrap(), json!({ "ok": true, "packets": 3 }));
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn streams_alerts_to_subscribers() {
+        let path = std::env::temp_dir().join(format!("control-alerts-{}.sock", std::process::id()));
+        let mut server = ControlServer::bind(&path).unwrap();
+        let mut client = ControlClient::connect(&path).unwrap();
+        let follower = std::thread::spawn(move || {
+            let reply = client.request(&ControlRequest::Alerts).unwrap();
+            (reply, client.next().unwrap().unwrap())
+        });
+        let mut requests = Vec::new();
+        while requests.is_empty() {
+            requests = server.poll();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let (id, request) = requests.remove(0);
+        assert_eq!(request, Ok(ControlRequest::Alerts));
+        server.publish(&json!({ "alert": "not subscribed yet" }));
+        server.subscribe(id);
+        server.reply(id, &success(json!({ "recent": [] })));
+        server.publish(&json!({ "alert": "scan" }));
+        let (reply, event) = follower.join().unwrap();
+        assert_eq!(reply, json!({ "ok": true, "recent": [] }));
+        assert_eq!(event, json!({ "alert": "scan" }));
+    }
+}
@@ -0,0 +1,198 @@
+use crate::protocols::null::{DLT_LOOP, DLT_NULL};
+use crate::protocols::sll::{DLT_LINUX_SLL, DLT_LINUX_SLL2};
+use crate::protocols::summary::PacketSummary;
+use crate::timestamp::format_date;
+use pcap::PacketHeader;
+use serde_json::{json, Map, Value};
+
+/// Builds the two-line Elasticsearch bulk record that `tshark -T ek` emits:
+/// an index action followed by the packet with layers nested by protocol.
+/// Field names follow tshark's `<layer>_<field>` convention with dots replaced
+/// by underscores (e.g. `ip_ip_src`), and values are strings as in tshark.
+/// Packets from a multi-namespace capture carry their namespace in `frame_frame_netns`.
+pub fn ek_record(header: &PacketHeader, datalink: i32, data: &[u8], source: Option<&str>) -> String {
+    let secs = header.ts.tv_sec;
+    let micros = header.ts.tv_usec;
+    let millis = secs * 1000 + micros / 1000;
+
+    let summary = PacketSummary::from_link(datalink, data);
+    let link = link_layer(datalink);
+    let mut layers = Map::new();
+    let mut frame = Map::new();
+    add(&mut frame, "frame", "frame.time_epoch", format!("{}.{:06}000", secs, micros));
+    add(&mut frame, "frame", "frame.len", header.len.to_string());
+    add(&mut frame, "frame", "frame.cap_len", header.caplen.to_string());
+    add(&mut frame, "frame", "frame.protocols", protocols(link, summary.as_ref()));
+    if let Some(source) = source {
+        add(&mut frame, "frame", "frame.netns", source.to_string());
+    }
+    layers.insert("frame".to_string(), Value::Object(frame));
+
+    if let Some(summary) = &summary {
+        add_layers(&mut layers, link, summary);
+    }
+
+    let index = json!({ "index": { "_index": format!("packets-{}", format_date(secs)), "_type": "doc" } });
+    let doc = json!({ "timestamp": millis.to_string(), "layers": layers });
+    format!("{}\n{}", index, doc)
+}
+
+fn add(layer: &mut Map<String, Value>, name: &str, field: &str, value: String) {
+    layer.insert(format!("{}_{}", name, field.replace('.', "_")), Value::String(value));
+}
+
+/// tshark's name for the link layer of a datalink `from_link` understands
+fn link_layer(datalink: i32) -> &'static str {
+    match datalink {
+        DLT_LINUX_SLL | DLT_LINUX_SLL2 => "sll",
+        DLT_NULL | DLT_LOOP => "null",
+        _ => "eth",
+    }
+}
+
+fn add_layers(layers: &mut Map<String, Value>, link: &str, summary: &PacketSummary) {
+    let mut layer = Map::new();
+    match link {
+        "eth" => {
+            add(&mut layer, "eth", "eth.dst", summary.dst_mac.to_string());
+            add(&mut layer, "eth", "eth.src", summary.src_mac.to_string());
+            add(&mut layer, "eth", "eth.type", format!("0x{:04x}", summary.ether_type));
+        }
+        // Cooked headers carry only the sender's address
+        "sll" => {
+            add(&mut layer, "sll", "sll.src.eth", summary.src_mac.to_string());
+            add(&mut layer, "sll", "sll.etype", format!("0x{:04x}", summary.ether_type));
+        }
+        _ => {}
+    }
+    if !layer.is_empty() {
+        layers.insert(link.to_string(), Value::Object(layer));
+    }
+
+    if let Some(id) = summary.vlan {
+        let mut vlan = Map::new();
+        add(&mut vlan, "vlan", "vlan.id", id.to_string());
+        layers.insert("vlan".to_string(), Value::Object(vlan));
+    }
+
+    if let (Some(src), Some(dst)) = (summary.src_ip, summary.dst_ip) {
+        let (name, proto_field, ttl_field) = if src.is_ipv4() {
+            ("ip", "ip.proto", "ip.ttl")
+        } else {
+            ("ipv6", "ipv6.nxt", "ipv6.hlim")
+        };
+        let mut ip = Map::new();
+        add(&mut ip, name, &format!("{}.src", name), src.to_string());
+        add(&mut ip, name, &format!("{}.dst", name), dst.to_string());
+        if let Some(proto) = summary.ip_proto {
+            add(&mut ip, name, proto_field, proto.to_string());
+        }
+        if let Some(ttl) = summary.ttl {
+            add(&mut ip, name, ttl_field, ttl.to_string());
+        }
+        layers.insert(name.to_string(), Value::Object(ip));
+    }
+
+    let transport = if summary.is_tcp() {
+        "tcp"
+    } else if summary.is_udp() {
+        "udp"
+    } else {
+        return;
+    };
+    if let (Some(src), Some(dst)) = (summary.src_port, summary.dst_port) {
+        let mut layer = Map::new();
+        add(&mut layer, transport, &format!("{}.srcport", transport), src.to_string());
+        add(&mut layer, transport, &format!("{}.dstport", transport), dst.to_string());
+        if let Some(flags) = summary.tcp_flags {
+            add(&mut layer, transport, "tcp.flags", format!("0x{:04x}", flags));
+        }
+        layers.insert(transport.to_string(), Value::Object(layer));
+    }
+}
+
+/// The `frame.protocols` chain, e.g. `eth:ethertype:ip:tcp`
+fn protocols(link: &'static str, summary: Option<&PacketSummary>) -> String {
+    let Some(summary) = summary else {
+        return String::new();
+    };
+    // BSD loopback headers carry an address family rather than an EtherType
+    let mut chain = if link == "null" { vec![link] } else { vec![link, "ethertype"] };
+    if summary.vlan.is_some() {
+        chain.insert(1, "vlan");
+    }
+    match summary.ether_type {
+        0x0800 => chain.push("ip"),
+        0x86DD => chain.push("ipv6"),
+        0x0806 => chain.push("arp"),
+        _ => {}
+    }
+    match summary.ip_proto {
+        Some(6) => chain.push("tcp"),
+        Some(17) => chain.push("udp"),
+        Some(1) => chain.push("icmp"),
+        Some(58) => chain.push("icmpv6"),
+        _ => {}
+    }
+    chain.join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    fn header(len: usize) -> PacketHeader {
+        PacketHeader {
+            ts: libc::timeval { tv_sec: 1_700_000_000, tv_usec: 123_456 },
+            caplen: len as u32,
+            len: len as u32,
+        }
+    }
+
+    #[test]
+    fn formats_bulk_record() {
+        let frame = testutil::ipv4_tcp_frame(40000, 443, testutil::TCP_SYN, b"");
+        let record = ek_record(&header(frame.len()), 1, &frame, None);
+        let mut lines = record.lines();
+
+        let index: Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+        assert_eq!(index["index"]["_index"], "packets-2023-11-14");
+
+        let doc: Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+        assert_eq!(doc["timestamp"], "1700000000123");
+        let layers = &doc["layers"];
+        assert_eq!(layers["frame"]["frame_frame_protocols"], "eth:ethertype:ip:tcp");
+        assert_eq!(layers["ip"]["ip_ip_src"], "192.168.1.10");
+        assert_eq!(layers["tcp"]["tcp_tcp_dstport"], "443");
+        assert_eq!(layers["tcp"]["tcp_tcp_flags"], "0x0002");
+        assert!(lines.next().is_none());
+    }
+
+    #[test]
+    fn uses_ipv6_field_names() {
+        let frame = testutil::ipv6_udp_frame(5353, 53, b"q");
+        let doc: Value = serde_json::from_str(ek_record(&header(frame.len()), 1, &frame, None).lines().nth(1).unwrap()).unwrap();
+        assert_eq!(doc["layers"]["ipv6"]["ipv6_ipv6_dst"], "2001:db8::2");
+        assert_eq!(doc["layers"]["udp"]["udp_udp_srcport"], "5353");
+
+        let doc: Value = serde_json::from_str(ek_record(&header(frame.len()), 1, &frame, Some("vpn")).lines().nth(1).unwrap()).unwrap();
+        assert_eq!(doc["layers"]["frame"]["frame_frame_netns"], "vpn");
+    }
+
+    #[test]
+    fn decodes_cooked_captures() {
+        let frame = testutil::ipv4_udp_frame(5000, 53, b"q");
+        let mut sll2 = vec![0x08, 0x00, 0, 0, 0, 0, 0, 2, 0, 1, 0, 6];
+        sll2.extend_from_slice(&frame[6..12]);
+        sll2.extend_from_slice(&[0, 0]);
+        sll2.extend_from_slice(&frame[14..]);
+        let doc: Value = serde_json::from_str(ek_record(&header(sll2.len()), DLT_LINUX_SLL2, &sll2, None).lines().nth(1).unwrap()).unwrap();
+        let layers = &doc["layers"];
+        assert_eq!(layers["frame"]["frame_frame_protocols"], "sll:ethertype:ip:udp");
+        assert_eq!(layers["sll"]["sll_sll_src_eth"], "66:77:88:99:aa:bb");
+        assert_eq!(layers["ip"]["ip_ip_src"], "192.168.1.10");
+        assert_eq!(layers["udp"]["udp_udp_dstport"], "53");
+        assert!(layers.get("eth").is_none());
+    }
+}
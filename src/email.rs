@@ -0,0 +1,293 @@
+use crate::alert::{Alert, AlertSink, Severity};
+use crate::backpressure::{self, Receiver, Sender, StageConfig};
+use crate::error::CaptureError;
+use crate::thresholds::Quantity;
+use crate::timestamp::{format_rfc3339, parse_duration};
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use log::warn;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Alerts listed in one email; the rest are only counted
+const MAX_LISTED: usize = 200;
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How the connection to the mail server is secured
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Plain connection upgraded with STARTTLS (port 587)
+    #[default]
+    Starttls,
+    /// TLS from the start (port 465)
+    Tls,
+    /// No encryption, for a relay on the local host or network (port 25)
+    None,
+}
+
+/// Email notifications from the configuration file (`[alerts.email]`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmailConfig {
+    pub server: String,
+    /// Defaults to the usual port for `security`
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    /// Environment variable holding the password, so it stays out of the file
+    pub password_env: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Send at most one email per interval (`15m`), listing every alert since
+    /// the last one; without it each alert is mailed on its own
+    pub digest: Option<Quantity>,
+    /// Alerts below this severity are not mailed
+    #[serde(default = "default_min_severity")]
+    pub min_severity: Severity,
+}
+
+fn default_min_severity() -> Severity {
+    Severity::High
+}
+
+/// Collects alerts so that at most one email goes out per interval. The first
+/// alert after a quiet period is sent right away.
+#[derive(Debug)]
+pub struct Digest {
+    interval: Duration,
+    pending: Vec<Alert>,
+    last_sent: Option<Instant>,
+}
+
+impl Digest {
+    pub fn new(interval: Duration) -> Self {
+        Digest { interval, pending: Vec::new(), last_sent: None }
+    }
+
+    pub fn push(&mut self, alert: Alert) {
+        self.pending.push(alert);
+    }
+
+    /// The alerts to mail now, if any are waiting and the interval has passed
+    pub fn take_due(&mut self, now: Instant) -> Option<Vec<Alert>> {
+        if self.pending.is_empty() || self.last_sent.is_some_and(|last| now.duration_since(last) < self.interval) {
+            return None;
+        }
+        self.last_sent = Some(now);
+        Some(std::mem::take(&mut self.pending))
+    }
+
+    /// Everything waiting, regardless of the interval
+    pub fn take_all(&mut self) -> Vec<Alert> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Time until pending alerts are due
+    pub fn wait(&self, now: Instant) -> Option<Duration> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        Some(self.last_sent.map_or(Duration::ZERO, |last| (last + self.interval).saturating_duration_since(now)))
+    }
+}
+
+enum Request {
+    Alert(Alert),
+    Flush(mpsc::Sender<()>),
+}
+
+/// Mails alerts through an SMTP server. Mail goes out from a background thread
+/// so a slow server never holds up the capture.
+pub struct EmailSink {
+    requests: Sender<Request>,
+    min_severity: Severity,
+}
+
+impl EmailSink {
+    /// Checks the addresses and credentials and starts the mailer thread
+    pub fn start(config: &EmailConfig, queue: StageConfig) -> Result<Self, CaptureError> {
+        let invalid = |what: &str, e: &dyn std::fmt::Display| CaptureError::InputError(format!("Invalid email {}: {}", what, e));
+        let from: Mailbox = config.from.parse().map_err(|e| invalid("sender", &e))?;
+        let to = config.to.iter().map(|to| to.parse::<Mailbox>()).collect::<Result<Vec<_>, _>>().map_err(|e| invalid("recipient", &e))?;
+        if to.is_empty() {
+            return Err(CaptureError::InputError("Email notifications need at least one recipient in 'to'".to_string()));
+        }
+        let digest = match &config.digest {
+            None => None,
+            Some(Quantity::Number(seconds)) => Some(Duration::from_secs(*seconds)),
+            Some(Quantity::Text(text)) => Some(parse_duration(text)?),
+        };
+        let mut builder = match config.security {
+            SmtpSecurity::Starttls => SmtpTransport::starttls_relay(&config.server),
+            SmtpSecurity::Tls => SmtpTransport::relay(&config.server),
+            SmtpSecurity::None => Ok(SmtpTransport::builder_dangerous(&config.server)),
+        }
+        .map_err(|e| invalid("server", &e))?
+        .timeout(Some(SMTP_TIMEOUT));
+        if let Some(port) = config.port {
+            builder = builder.port(port);
+        }
+        if let Some(username) = &config.username {
+            let password = match &config.password_env {
+                Some(var) => std::env::var(var)
+                    .map_err(|_| CaptureError::InputError(format!("Environment variable {} with the SMTP password is not set", var)))?,
+                None => String::new(),
+            };
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+        let mailer = Mailer { transport: builder.build(), from, to, errors: 0 };
+        let (requests, receiver) = backpressure::channel("exporters/email", queue);
+        thread::Builder::new().name("email".to_string()).spawn(move || mailer.run(receiver, digest))
+            .map_err(|e| CaptureError::Other(format!("Cannot start the mailer: {}", e)))?;
+        Ok(EmailSink { requests, min_severity: config.min_severity })
+    }
+}
+
+impl AlertSink for EmailSink {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    fn send(&mut self, alert: &Alert) {
+        if alert.severity >= self.min_severity {
+            let _ = self.requests.send(Request::Alert(alert.clone()));
+        }
+    }
+
+    /// Mails any digest still waiting and waits for it to go out
+    fn flush(&mut self) {
+        let (done, wait) = mpsc::channel();
+        if self.requests.send(Request::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+}
+
+struct Mailer {
+    transport: SmtpTransport,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    errors: u32,
+}
+
+impl Mailer {
+    fn run(mut self, requests: Receiver<Request>, digest: Option<Duration>) {
+        let mut digest = Digest::new(digest.unwrap_or(Duration::ZERO));
+        loop {
+            let request = match digest.wait(Instant::now()) {
+                Some(wait) => requests.recv_timeout(wait),
+                None => requests.recv().ok_or(RecvTimeoutError::Disconnected),
+            };
+            match request {
+                Ok(Request::Alert(alert)) => digest.push(alert),
+                Ok(Request::Flush(done)) => {
+                    self.mail(&digest.take_all());
+                    let _ = done.send(());
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.mail(&digest.take_all());
+                    return;
+                }
+            }
+            if let Some(alerts) = digest.take_due(Instant::now()) {
+                self.mail(&alerts);
+            }
+        }
+    }
+
+    fn mail(&mut self, alerts: &[Alert]) {
+        if alerts.is_empty() {
+            return;
+        }
+        let mut message = Message::builder().from(self.from.clone()).subject(subject(alerts));
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        let result = message.body(body(alerts)).map_err(|e| e.to_string()).and_then(|m| self.transport.send(&m).map(|_| ()).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            self.errors += 1;
+            // A dead server would otherwise log on every digest
+            if self.errors <= 5 {
+                warn!("Could not email {} alert(s): {}", alerts.len(), e);
+            }
+        }
+    }
+}
+
+/// `[rust-sniffer] HIGH: <message>` for one alert, else a count by severity
+pub fn subject(alerts: &[Alert]) -> String {
+    if let [alert] = alerts {
+        return format!("[rust-sniffer] {}: {}", alert.severity, alert);
+    }
+    let mut counts: BTreeMap<std::cmp::Reverse<Severity>, usize> = BTreeMap::new();
+    for alert in alerts {
+        *counts.entry(std::cmp::Reverse(alert.severity)).or_default() += 1;
+    }
+    let counts: Vec<String> = counts.iter().map(|(severity, count)| format!("{} {}", count, severity.0)).collect();
+    format!("[rust-sniffer] {} alerts ({})", alerts.len(), counts.join(", "))
+}
+
+/// One line per alert, oldest first
+pub fn body(alerts: &[Alert]) -> String {
+    let mut body = String::new();
+    for alert in alerts.iter().take(MAX_LISTED) {
+        body.push_str(&format!("{} {:<8} {:<9} {}\n", format_rfc3339(alert.time), alert.severity.to_string(), alert.kind, alert));
+    }
+    if alerts.len() > MAX_LISTED {
+        body.push_str(&format!("... and {} more\n", alerts.len() - MAX_LISTED));
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alert(severity: Severity, message: &str) -> Alert {
+        Alert { time: 1_700_000_000.0, severity, kind: "rule", message: message.to_string(), source: None, hosts: Vec::new(), techniques: Vec::new() }
+    }
+
+    #[test]
+    fn sends_at_most_one_digest_per_interval() {
+        let start = Instant::now();
+        let mut digest = Digest::new(Duration::from_secs(900));
+        assert_eq!(digest.wait(start), None);
+        digest.push(alert(Severity::High, "first"));
+        assert_eq!(digest.wait(start), Some(Duration::ZERO));
+        assert_eq!(digest.take_due(start).unwrap().len(), 1);
+        digest.push(alert(Severity::High, "second"));
+        digest.push(alert(Severity::Critical, "third"));
+        assert_eq!(digest.take_due(start + Duration::from_secs(60)), None);
+        assert_eq!(digest.wait(start + Duration::from_secs(60)), Some(Duration::from_secs(840)));
+        let batch = digest.take_due(start + Duration::from_secs(900)).unwrap();
+        assert_eq!(subject(&batch), "[rust-sniffer] 2 alerts (1 CRITICAL, 1 HIGH)");
+        assert_eq!(body(&batch).lines().next().unwrap(), "2023-11-14T22:13:20.000000Z HIGH     rule      second");
+        assert!(digest.take_all().is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_addresses() {
+        let config = EmailConfig {
+            server: "localhost".to_string(),
+            port: Some(2525),
+            security: SmtpSecurity::None,
+            username: None,
+            password_env: None,
+            from: "sniffer@example.com".to_string(),
+            to: vec!["not an address".to_string()],
+            digest: Some(Quantity::Text("15m".to_string())),
+            min_severity: Severity::High,
+        };
+        assert!(EmailSink::start(&config, StageConfig::default()).is_err());
+        assert!(EmailSink::start(&EmailConfig { to: Vec::new(), ..config.clone() }, StageConfig::default()).is_err());
+        assert_eq!(subject(&[alert(Severity::High, "one")]), "[rust-sniffer] HIGH: one");
+    }
+}
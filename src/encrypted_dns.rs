@@ -0,0 +1,362 @@
+use crate::capfile::CaptureReader;
+use crate::error::CaptureError;
+use crate::flow::FlowKey;
+use crate::follow::{tcp_streams, StreamInfo};
+use crate::keylog::{decrypt_tls13, KeyLog};
+use crate::protocols::http2::{frames, HeaderDecoder, PREFACE};
+use crate::protocols::quic::{QuicFlow, QuicTracker};
+use crate::protocols::summary::PacketSummary;
+use crate::protocols::tls::ClientHello;
+use crate::reassembly::Side;
+use ipnet::IpNet;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Port assigned to DNS over TLS (RFC 7858)
+pub const DOT_PORT: u16 = 853;
+
+/// Server names of public DoH resolvers
+pub const KNOWN_DOH_NAMES: &[&str] = &[
+    "cloudflare-dns.com",
+    "mozilla.cloudflare-dns.com",
+    "one.one.one.one",
+    "dns.google",
+    "dns.quad9.net",
+    "doh.opendns.com",
+    "dns.adguard-dns.com",
+    "dns.adguard.com",
+    "doh.cleanbrowsing.org",
+    "dns.nextdns.io",
+    "doh.mullvad.net",
+];
+
+/// Anycast addresses of public DoH resolvers
+pub const KNOWN_DOH_ADDRESSES: &[&str] = &[
+    "1.1.1.1",
+    "1.0.0.1",
+    "8.8.8.8",
+    "8.8.4.4",
+    "9.9.9.9",
+    "149.112.112.112",
+    "208.67.222.222",
+    "208.67.220.220",
+    "94.140.14.14",
+    "94.140.15.15",
+    "2606:4700:4700::1111",
+    "2606:4700:4700::1001",
+    "2001:4860:4860::8888",
+    "2001:4860:4860::8844",
+    "2620:fe::fe",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EncryptedDnsKind {
+    DoH,
+    DoT,
+    DoQ,
+}
+
+impl fmt::Display for EncryptedDnsKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncryptedDnsKind::DoH => write!(f, "DoH"),
+            EncryptedDnsKind::DoT => write!(f, "DoT"),
+            EncryptedDnsKind::DoQ => write!(f, "DoQ"),
+        }
+    }
+}
+
+/// Why a connection was classified as encrypted DNS, strongest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Evidence {
+    /// DNS messages seen in the (decrypted or cleartext) HTTP requests
+    DnsQueries,
+    /// ALPN `doq` in a QUIC ClientHello
+    DoqAlpn,
+    /// TLS or QUIC on port 853
+    Port853,
+    /// ClientHello server name of a public resolver
+    ResolverName,
+    /// Server address of a public resolver
+    ResolverAddress,
+}
+
+impl fmt::Display for Evidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Evidence::DnsQueries => write!(f, "dns-query requests"),
+            Evidence::DoqAlpn => write!(f, "doq ALPN"),
+            Evidence::Port853 => write!(f, "port 853"),
+            Evidence::ResolverName => write!(f, "resolver SNI"),
+            Evidence::ResolverAddress => write!(f, "resolver address"),
+        }
+    }
+}
+
+/// A connection carrying DNS over an encrypted transport
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncryptedDnsFlow {
+    pub kind: EncryptedDnsKind,
+    pub evidence: Evidence,
+    pub stream: StreamInfo,
+    pub client: (IpAddr, u16),
+    pub server: (IpAddr, u16),
+    pub server_name: Option<String>,
+    /// DoH requests counted in readable HTTP; `None` when the connection couldn't be decrypted
+    pub queries: Option<u64>,
+}
+
+pub fn is_known_resolver_name(name: &str) -> bool {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    KNOWN_DOH_NAMES.iter().any(|known| name == *known || name.ends_with(&format!(".{}", known)))
+}
+
+pub fn is_known_resolver_address(addr: IpAddr) -> bool {
+    KNOWN_DOH_ADDRESSES.iter().any(|known| known.parse() == Ok(addr))
+}
+
+/// Parses a `--resolver` argument: an address or CIDR network
+pub fn parse_resolver(text: &str) -> Result<IpNet, CaptureError> {
+    text.parse::<IpNet>()
+        .or_else(|_| text.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| CaptureError::InputError(format!("Invalid address or network '{}'", text)))
+}
+
+/// Counts DoH requests in the client side of a cleartext HTTP connection:
+/// requests for `/dns-query` or carrying `application/dns-message`
+pub fn doh_requests(client: &[u8]) -> u64 {
+    if client.starts_with(PREFACE) {
+        let mut decoder = HeaderDecoder::new();
+        return frames(client)
+            .iter()
+            .filter_map(|frame| decoder.push(frame))
+            .filter(|block| {
+                block.get(":path").is_some_and(|p| p.starts_with("/dns-query"))
+                    || block.get("content-type") == Some("application/dns-message")
+                    || block.get("accept") == Some("application/dns-message")
+            })
+            .count() as u64;
+    }
+    String::from_utf8_lossy(client)
+        .split("\r\n")
+        .filter(|line| {
+            let mut parts = line.split_whitespace();
+            matches!(parts.next(), Some("GET" | "POST")) && parts.next().is_some_and(|p| p.starts_with("/dns-query"))
+        })
+        .count() as u64
+}
+
+/// Decides whether a reassembled TCP connection is DoH or DoT
+pub fn classify_stream(
+    stream: StreamInfo,
+    client_addr: (IpAddr, u16),
+    client: &[u8],
+    server: &[u8],
+    keylog: Option<&KeyLog>,
+) -> Option<EncryptedDnsFlow> {
+    let server_addr = if stream.key.a == client_addr { stream.key.b } else { stream.key.a };
+    let is_tls = client.starts_with(&[0x16, 0x03]);
+    let server_name = is_tls.then(|| client.get(5..).and_then(ClientHello::parse)).flatten().and_then(|h| h.server_name);
+
+    // Readable HTTP, either cleartext or decrypted with the key log
+    let plaintext = if is_tls { keylog.and_then(|k| decrypt_tls13(client, server, k)).map(|(c, _)| c) } else { Some(client.to_vec()) };
+    let queries = plaintext.as_deref().map(doh_requests);
+
+    let (kind, evidence) = if server_addr.1 == DOT_PORT && is_tls {
+        (EncryptedDnsKind::DoT, Evidence::Port853)
+    } else if queries.is_some_and(|q| q > 0) {
+        (EncryptedDnsKind::DoH, Evidence::DnsQueries)
+    } else if !is_tls || queries.is_some() {
+        // Cleartext or decrypted, and no DNS inside: ordinary HTTP(S)
+        return None;
+    } else if server_name.as_deref().is_some_and(is_known_resolver_name) {
+        (EncryptedDnsKind::DoH, Evidence::ResolverName)
+    } else if server_addr.1 == 443 && is_known_resolver_address(server_addr.0) {
+        (EncryptedDnsKind::DoH, Evidence::ResolverAddress)
+    } else {
+        return None;
+    };
+    Some(EncryptedDnsFlow { kind, evidence, stream, client: client_addr, server: server_addr, server_name, queries })
+}
+
+/// Decides whether a QUIC connection is DoQ, or DoH over HTTP/3 to a public resolver
+pub fn classify_quic(flow: &QuicFlow, stream: StreamInfo) -> Option<EncryptedDnsFlow> {
+    let hello = flow.hello.clone().unwrap_or_default();
+    let (kind, evidence) = match flow.label().as_str() {
+        "doq" => (EncryptedDnsKind::DoQ, Evidence::DoqAlpn),
+        "doq?" => (EncryptedDnsKind::DoQ, Evidence::Port853),
+        "h3" if hello.server_name.as_deref().is_some_and(is_known_resolver_name) => {
+            (EncryptedDnsKind::DoH, Evidence::ResolverName)
+        }
+        _ => return None,
+    };
+    Some(EncryptedDnsFlow {
+        kind,
+        evidence,
+        stream,
+        client: flow.client,
+        server: flow.server(),
+        server_name: hello.server_name,
+        queries: None,
+    })
+}
+
+/// Encrypted DNS connections in a capture, and the hosts using resolvers
+/// outside the organization's own
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EncryptedDnsReport {
+    pub flows: Vec<EncryptedDnsFlow>,
+}
+
+impl EncryptedDnsReport {
+    /// Clients with encrypted DNS to servers not in `resolvers`, and the
+    /// servers they used
+    pub fn bypassing(&self, resolvers: &[IpNet]) -> BTreeMap<IpAddr, Vec<&EncryptedDnsFlow>> {
+        let mut hosts: BTreeMap<IpAddr, Vec<&EncryptedDnsFlow>> = BTreeMap::new();
+        for flow in &self.flows {
+            if !resolvers.iter().any(|net| net.contains(&flow.server.0)) {
+                hosts.entry(flow.client.0).or_default().push(flow);
+            }
+        }
+        hosts
+    }
+
+    /// Encrypted DNS connections per client and kind
+    pub fn usage(&self) -> BTreeMap<IpAddr, BTreeMap<EncryptedDnsKind, u64>> {
+        let mut usage: BTreeMap<IpAddr, BTreeMap<EncryptedDnsKind, u64>> = BTreeMap::new();
+        for flow in &self.flows {
+            *usage.entry(flow.client.0).or_default().entry(flow.kind).or_default() += 1;
+        }
+        usage
+    }
+}
+
+/// Finds DoH, DoT and DoQ connections in a capture file. TLS 1.3
+/// connections listed in `keylog` are decrypted to confirm DoH by its requests.
+pub fn analyze_encrypted_dns(input: &Path, keylog: Option<&KeyLog>) -> Result<EncryptedDnsReport, CaptureError> {
+    let mut report = EncryptedDnsReport::default();
+    for (info, reassembler) in tcp_streams(input)? {
+        let Some(client_addr) = reassembler.client() else { continue };
+        let client = reassembler.stream(Side::Client);
+        let server = reassembler.stream(Side::Server);
+        if let Some(flow) = classify_stream(info, client_addr, &client, &server, keylog) {
+            report.flows.push(flow);
+        }
+    }
+
+    // UDP conversations are numbered like `follow --udp` so flows can be followed
+    let mut tracker = QuicTracker::new();
+    let mut conversations: HashMap<FlowKey, StreamInfo> = HashMap::new();
+    let reader = CaptureReader::open(input)?;
+    let datalink = reader.linktype().0;
+    for packet in reader {
+        let Some(summary) = PacketSummary::from_link(datalink, &packet.data) else { continue };
+        let Some(key) = FlowKey::from_summary(&summary).filter(|k| k.protocol == 17) else { continue };
+        let index = conversations.len();
+        let info = conversations.entry(key).or_insert(StreamInfo { index, key, packets: 0, bytes: 0 });
+        info.packets += 1;
+        info.bytes += packet.header.len as u64;
+        if let (Some(src), Some(dst), Some(sport), Some(dport)) =
+            (summary.src_ip, summary.dst_ip, summary.src_port, summary.dst_port)
+        {
+            tracker.push((src, sport), (dst, dport), summary.payload(&packet.data));
+        }
+    }
+    for flow in tracker.flows() {
+        if let Some(flow) = classify_quic(flow, conversations[&flow.key].clone()) {
+            report.flows.push(flow);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::http2::{FLAG_END_HEADERS, FRAME_HEADERS};
+    use crate::protocols::quic::DOQ_PORT;
+    use crate::testutil::{self, http2_frame, tls_client_hello};
+    use std::net::Ipv4Addr;
+
+    fn stream(client: (IpAddr, u16), server: (IpAddr, u16)) -> StreamInfo {
+        StreamInfo { index: 0, key: FlowKey::new(6, client, server), packets: 4, bytes: 400 }
+    }
+
+    fn host(last: u8, port: u16) -> (IpAddr, u16) {
+        (IpAddr::V4(Ipv4Addr::new(10, 0, 0, last)), port)
+    }
+
+    #[test]
+    fn classifies_dot_and_doh_by_name_or_address() {
+        let client = host(5, 50000);
+        let hello = tls_client_hello("example.com");
+
+        let dot = classify_stream(stream(client, host(53, DOT_PORT)), client, &hello, &[], None).unwrap();
+        assert_eq!((dot.kind, dot.evidence), (EncryptedDnsKind::DoT, Evidence::Port853));
+
+        let named = tls_client_hello("mozilla.cloudflare-dns.com");
+        let doh = classify_stream(stream(client, host(9, 443)), client, &named, &[], None).unwrap();
+        assert_eq!((doh.kind, doh.evidence), (EncryptedDnsKind::DoH, Evidence::ResolverName));
+        assert_eq!(doh.server_name.as_deref(), Some("mozilla.cloudflare-dns.com"));
+        assert_eq!(doh.queries, None);
+
+        let google = (IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 443);
+        let doh = classify_stream(stream(client, google), client, &hello, &[], None).unwrap();
+        assert_eq!(doh.evidence, Evidence::ResolverAddress);
+
+        assert!(classify_stream(stream(client, host(9, 443)), client, &hello, &[], None).is_none());
+    }
+
+    #[test]
+    fn counts_doh_requests_and_flags_bypassing_hosts() {
+        let mut h2 = PREFACE.to_vec();
+        let block = hpack::Encoder::new().encode(&vec![
+            (b":method".to_vec(), b"POST".to_vec()),
+            (b":path".to_vec(), b"/dns-query".to_vec()),
+            (b"content-type".to_vec(), b"application/dns-message".to_vec()),
+        ]);
+        h2.extend(http2_frame(FRAME_HEADERS, FLAG_END_HEADERS, 1, &block));
+        assert_eq!(doh_requests(&h2), 1);
+        assert_eq!(doh_requests(b"GET /dns-query?dns=AAABAAAB HTTP/1.1\r\nHost: x\r\n\r\n"), 1);
+        assert_eq!(doh_requests(b"GET / HTTP/1.1\r\n\r\n"), 0);
+
+        let (inside, outside) = (host(5, 50000), host(6, 50001));
+        let cleartext = classify_stream(stream(outside, host(80, 80)), outside, &h2, &[], None).unwrap();
+        assert_eq!((cleartext.evidence, cleartext.queries), (Evidence::DnsQueries, Some(1)));
+        let dot = classify_stream(stream(inside, host(53, DOT_PORT)), inside, &tls_client_hello("dns"), &[], None).unwrap();
+
+        let report = EncryptedDnsReport { flows: vec![cleartext, dot] };
+        let resolvers = [parse_resolver("10.0.0.0/26").unwrap()];
+        let hosts = report.bypassing(&resolvers);
+        assert_eq!(hosts.keys().collect::<Vec<_>>(), vec![&outside.0]);
+        assert_eq!(report.bypassing(&[]).len(), 2);
+        assert!(parse_resolver("resolver").is_err());
+    }
+
+    #[test]
+    fn labels_doq_and_counts_usage_per_client() {
+        let client = host(5, 50000);
+        let resolver = host(53, DOQ_PORT);
+        let initial = testutil::quic_initial(&[7; 8], &[1; 4], 0, &testutil::client_hello("dns.example", &["doq"]));
+        let mut tracker = QuicTracker::new();
+        assert!(tracker.push(client, resolver, &initial));
+        assert!(tracker.push(host(6, 50001), resolver, &[0x41, 1, 2, 3]));
+
+        let flows: Vec<EncryptedDnsFlow> = tracker
+            .flows()
+            .iter()
+            .map(|flow| classify_quic(flow, stream(flow.client, flow.server())).unwrap())
+            .collect();
+        assert_eq!((flows[0].kind, flows[0].evidence), (EncryptedDnsKind::DoQ, Evidence::DoqAlpn));
+        assert_eq!(flows[0].server_name.as_deref(), Some("dns.example"));
+        assert_eq!(flows[1].evidence, Evidence::Port853);
+
+        let dot = classify_stream(stream(client, host(53, DOT_PORT)), client, &tls_client_hello("dns"), &[], None).unwrap();
+        let report = EncryptedDnsReport { flows: [flows, vec![dot]].concat() };
+        let usage = report.usage();
+        assert_eq!(usage[&client.0], BTreeMap::from([(EncryptedDnsKind::DoT, 1), (EncryptedDnsKind::DoQ, 1)]));
+        assert_eq!(usage[&host(6, 0).0][&EncryptedDnsKind::DoQ], 1);
+    }
+}
@@ -0,0 +1,310 @@
+use crate::alert::Severity;
+use crate::attack;
+use crate::capfile::parse_size;
+use crate::error::CaptureError;
+use crate::protocols::summary::PacketSummary;
+use crate::rules::{is_internal, GeoIp};
+use crate::thresholds::Quantity;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// Destinations named in an alert
+const TOP_DESTINATIONS: usize = 3;
+
+/// Destinations counted per host and day; the rest go to `other`
+const MAX_DESTINATIONS: usize = 64;
+
+/// Upload volume monitoring from the configuration file (`[alerts.exfil]`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExfilConfig {
+    /// MaxMind ASN database; without it destinations are grouped by country
+    /// from `alerts.geoip`
+    pub asn_db: Option<PathBuf>,
+    /// Alert when a host's uploads today reach this many times its daily baseline
+    #[serde(default = "default_factor")]
+    pub factor: f64,
+    /// Uploads below this never alert; takes KB, MB, GB
+    #[serde(default = "default_min_bytes")]
+    pub min_bytes: Quantity,
+    /// Past days the baseline is the mean of
+    #[serde(default = "default_baseline_days")]
+    pub baseline_days: usize,
+    /// Days a host must have been seen before its baseline is trusted
+    #[serde(default = "default_warmup_days")]
+    pub warmup_days: usize,
+    #[serde(default)]
+    pub severity: Severity,
+}
+
+fn default_factor() -> f64 {
+    5.0
+}
+
+fn default_min_bytes() -> Quantity {
+    Quantity::Text("100MB".to_string())
+}
+
+fn default_baseline_days() -> usize {
+    14
+}
+
+fn default_warmup_days() -> usize {
+    3
+}
+
+/// Compiled upload monitoring settings, shared by every capture thread and replaced on reload
+pub struct ExfilPolicy {
+    factor: f64,
+    min_bytes: u64,
+    baseline_days: usize,
+    warmup_days: usize,
+    severity: Severity,
+    asn: Option<GeoIp>,
+    countries: Option<GeoIp>,
+}
+
+impl ExfilPolicy {
+    /// Checks the settings and opens the ASN and country databases
+    pub fn compile(config: &ExfilConfig, geoip: Option<&Path>) -> Result<Self, CaptureError> {
+        let error = |why: String| CaptureError::InputError(format!("alerts.exfil: {}", why));
+        let min_bytes = match &config.min_bytes {
+            Quantity::Number(n) => *n,
+            Quantity::Text(text) => parse_size(text.strip_suffix(['B', 'b']).unwrap_or(text)).map_err(|e| error(e.to_string()))?,
+        };
+        if config.factor <= 1.0 {
+            return Err(error("factor must be greater than 1".to_string()));
+        }
+        if config.baseline_days == 0 || config.warmup_days > config.baseline_days {
+            return Err(error("baseline_days must be positive and at least warmup_days".to_string()));
+        }
+        Ok(ExfilPolicy {
+            factor: config.factor,
+            min_bytes,
+            baseline_days: config.baseline_days,
+            warmup_days: config.warmup_days,
+            severity: config.severity,
+            asn: config.asn_db.as_deref().map(GeoIp::open).transpose()?,
+            countries: geoip.map(GeoIp::open).transpose()?,
+        })
+    }
+
+    /// The autonomous system of an address, e.g. `AS15169 Google LLC`, or its
+    /// country when there is no ASN database
+    fn destination(&self, ip: IpAddr) -> String {
+        let name = match (&self.asn, &self.countries) {
+            (Some(asn), _) => asn.asn(ip).map(|(number, org)| format!("AS{} {}", number, org).trim_end().to_string()),
+            (None, Some(countries)) => Some(countries.country(ip)).filter(|code| !code.is_empty()),
+            (None, None) => None,
+        };
+        name.unwrap_or_else(|| "unknown".to_string())
+    }
+}
+
+/// One internal host's uploads to external destinations
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HostUploads {
+    /// Days since the epoch (UTC) that `today` counts
+    pub day: i64,
+    pub today: u64,
+    /// Today's bytes per destination AS or country
+    pub destinations: BTreeMap<String, u64>,
+    /// Totals of the previous days, oldest first
+    pub history: VecDeque<u64>,
+    alerted: bool,
+}
+
+impl HostUploads {
+    /// Mean daily upload of the previous days
+    pub fn baseline(&self) -> f64 {
+        if self.history.is_empty() {
+            return 0.0;
+        }
+        self.history.iter().sum::<u64>() as f64 / self.history.len() as f64
+    }
+
+    /// The biggest destinations of today, largest first
+    pub fn top_destinations(&self, count: usize) -> Vec<(&str, u64)> {
+        let mut destinations: Vec<(&str, u64)> = self.destinations.iter().map(|(name, &bytes)| (name.as_str(), bytes)).collect();
+        destinations.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        destinations.truncate(count);
+        destinations
+    }
+
+    /// Moves today's total into the history when `day` is a later one
+    fn roll(&mut self, day: i64, keep: usize) {
+        if day <= self.day {
+            return;
+        }
+        // Days without uploads count as zero
+        let idle = (day - self.day - 1).min(keep as i64) as usize;
+        self.history.push_back(self.today);
+        self.history.extend(std::iter::repeat_n(0, idle));
+        while self.history.len() > keep {
+            self.history.pop_front();
+        }
+        self.day = day;
+        self.today = 0;
+        self.destinations.clear();
+        self.alerted = false;
+    }
+}
+
+/// Uploads of every internal host, as saved in the `--state` file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UploadVolumes {
+    hosts: HashMap<IpAddr, HostUploads>,
+}
+
+impl UploadVolumes {
+    pub fn hosts(&self) -> impl Iterator<Item = (&IpAddr, &HostUploads)> {
+        self.hosts.iter()
+    }
+}
+
+/// A host that uploaded far more than usual today
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExfilAlert {
+    pub host: IpAddr,
+    pub severity: Severity,
+    pub bytes: u64,
+    pub baseline: f64,
+    pub destinations: Vec<(String, u64)>,
+}
+
+impl ExfilAlert {
+    pub fn techniques(&self) -> Vec<String> {
+        vec![attack::EXFILTRATION_OVER_ALTERNATIVE_PROTOCOL.to_string()]
+    }
+}
+
+impl fmt::Display for ExfilAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let destinations: Vec<String> = self.destinations.iter().map(|(name, bytes)| format!("{} {}", name, megabytes(*bytes as f64))).collect();
+        write!(f, "Upload spike from {}: {} to external destinations today", self.host, megabytes(self.bytes as f64))?;
+        if self.baseline > 0.0 {
+            write!(f, ", {:.1}x its daily baseline of {}", self.bytes as f64 / self.baseline, megabytes(self.baseline))?;
+        } else {
+            write!(f, ", nothing on previous days")?;
+        }
+        write!(f, " (top: {})", destinations.join(", "))
+    }
+}
+
+/// A byte count in MB with one decimal
+pub fn megabytes(bytes: f64) -> String {
+    format!("{:.1} MB", bytes / 1_048_576.0)
+}
+
+/// Adds up each internal host's bytes to external addresses per UTC day and
+/// destination, and alerts the first time in a day a host goes over its baseline
+pub struct ExfilMonitor {
+    policy: Arc<ExfilPolicy>,
+    volumes: UploadVolumes,
+}
+
+impl ExfilMonitor {
+    pub fn new(policy: Arc<ExfilPolicy>) -> Self {
+        ExfilMonitor { policy, volumes: UploadVolumes::default() }
+    }
+
+    /// Switches to reloaded settings; the baselines are kept
+    pub fn replace(&mut self, policy: Arc<ExfilPolicy>) {
+        self.policy = policy;
+    }
+
+    pub fn volumes(&self) -> &UploadVolumes {
+        &self.volumes
+    }
+
+    /// Continues from volumes saved by an earlier capture
+    pub fn restore(&mut self, volumes: UploadVolumes) {
+        self.volumes = volumes;
+    }
+
+    /// Counts a packet seen at `time` (seconds) if it leaves the internal network
+    pub fn push(&mut self, summary: &PacketSummary, time: f64) -> Option<ExfilAlert> {
+        let (src, dst) = (summary.src_ip?, summary.dst_ip?);
+        if !is_internal(src) || is_internal(dst) || dst.is_multicast() {
+            return None;
+        }
+        let policy = &self.policy;
+        let day = (time / SECONDS_PER_DAY).floor() as i64;
+        let host = self.volumes.hosts.entry(src).or_insert_with(|| HostUploads { day, ..HostUploads::default() });
+        host.roll(day, policy.baseline_days);
+        let bytes = summary.length.max(summary.ip_end.unwrap_or(0)) as u64;
+        host.today += bytes;
+        let destination = policy.destination(dst);
+        let destination = if host.destinations.len() < MAX_DESTINATIONS || host.destinations.contains_key(&destination) {
+            destination
+        } else {
+            "other".to_string()
+        };
+        *host.destinations.entry(destination).or_default() += bytes;
+
+        let baseline = host.baseline();
+        let spike = host.today >= policy.min_bytes && host.today as f64 >= baseline * policy.factor;
+        if host.alerted || !spike || host.history.len() < policy.warmup_days {
+            return None;
+        }
+        host.alerted = true;
+        Some(ExfilAlert {
+            host: src,
+            severity: policy.severity,
+            bytes: host.today,
+            baseline,
+            destinations: host.top_destinations(TOP_DESTINATIONS).into_iter().map(|(name, bytes)| (name.to_string(), bytes)).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::testutil;
+
+    fn monitor() -> ExfilMonitor {
+        let config = Config::parse("[alerts.exfil]\nmin_bytes = \"2KB\"\nfactor = 3\nwarmup_days = 2\n").unwrap();
+        ExfilMonitor::new(Arc::new(ExfilPolicy::compile(config.alerts.exfil.as_ref().unwrap(), None).unwrap()))
+    }
+
+    #[test]
+    fn alerts_once_a_day_on_uploads_over_the_baseline() {
+        let mut monitor = monitor();
+        let frame = testutil::ipv4_tcp_frame(40000, 443, testutil::TCP_ACK, &[0; 1000]);
+        let summary = PacketSummary::from_ethernet(&frame).unwrap();
+        let day = |n: f64| n * SECONDS_PER_DAY;
+        // Two days of one packet each make the baseline
+        for n in 0..2 {
+            assert!(monitor.push(&summary, day(n as f64)).is_none());
+        }
+        let alerts: Vec<_> = (0..8).filter_map(|i| monitor.push(&summary, day(2.0) + i as f64)).collect();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].host, IpAddr::V4(testutil::client_v4()));
+        assert_eq!(alerts[0].destinations, [("unknown".to_string(), 3162)]);
+        assert!(alerts[0].to_string().starts_with("Upload spike from 192.168.1.10: 0.0 MB to external destinations today, 3.0x its daily baseline"));
+
+        // Replies coming in don't count
+        let reply = testutil::frame().ipv4(testutil::server_v4(), testutil::client_v4()).tcp(443, 40000).payload(&[0; 4000]).build();
+        assert!(monitor.push(&PacketSummary::from_ethernet(&reply).unwrap(), day(3.0)).is_none());
+        let host = &monitor.volumes().hosts[&IpAddr::V4(testutil::client_v4())];
+        assert_eq!(host.history.len(), 2);
+    }
+
+    #[test]
+    fn idle_days_lower_the_baseline() {
+        let mut host = HostUploads { day: 0, today: 900, ..HostUploads::default() };
+        host.roll(3, 14);
+        assert_eq!(host.history, [900, 0, 0]);
+        assert_eq!(host.baseline(), 300.0);
+        host.roll(40, 2);
+        assert_eq!(host.history, [0, 0]);
+    }
+}
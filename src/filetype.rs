@@ -0,0 +1,295 @@
+use crate::error::CaptureError;
+use crate::flow_review::http_request_url;
+use crate::follow::{tcp_streams, StreamInfo};
+use crate::protocols::http::HttpMessage;
+use crate::reassembly::{Side, TcpReassembler};
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Broad class of a file type, for labelling flows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FileKind {
+    Executable,
+    Archive,
+    Document,
+    Image,
+}
+
+impl FileKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileKind::Executable => "executable",
+            FileKind::Archive => "archive",
+            FileKind::Document => "document",
+            FileKind::Image => "image",
+        }
+    }
+}
+
+/// File types recognized by their magic bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    /// Windows executable or DLL
+    Pe,
+    Elf,
+    MachO,
+    Zip,
+    /// Java archive
+    Jar,
+    /// Android package
+    Apk,
+    Gzip,
+    SevenZip,
+    Rar,
+    Pdf,
+    /// docx, xlsx, pptx
+    OfficeOpenXml,
+    /// doc, xls, ppt (OLE compound file)
+    OfficeLegacy,
+    Png,
+    Jpeg,
+    Gif,
+}
+
+impl FileType {
+    /// Identifies a file from its first bytes
+    pub fn identify(data: &[u8]) -> Option<FileType> {
+        let starts = |magic: &[u8]| data.starts_with(magic);
+        Some(match data {
+            _ if starts(b"MZ") && is_pe(data) => FileType::Pe,
+            _ if starts(b"\x7fELF") => FileType::Elf,
+            _ if starts(&[0xcf, 0xfa, 0xed, 0xfe]) || starts(&[0xce, 0xfa, 0xed, 0xfe]) || starts(&[0xca, 0xfe, 0xba, 0xbe]) => FileType::MachO,
+            _ if starts(b"PK\x03\x04") => zip_type(data),
+            _ if starts(&[0x1f, 0x8b]) => FileType::Gzip,
+            _ if starts(b"7z\xbc\xaf\x27\x1c") => FileType::SevenZip,
+            _ if starts(b"Rar!\x1a\x07") => FileType::Rar,
+            _ if starts(b"%PDF-") => FileType::Pdf,
+            _ if starts(&[0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1]) => FileType::OfficeLegacy,
+            _ if starts(b"\x89PNG\r\n\x1a\n") => FileType::Png,
+            _ if starts(&[0xff, 0xd8, 0xff]) => FileType::Jpeg,
+            _ if starts(b"GIF87a") || starts(b"GIF89a") => FileType::Gif,
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileType::Pe => "PE",
+            FileType::Elf => "ELF",
+            FileType::MachO => "Mach-O",
+            FileType::Zip => "ZIP",
+            FileType::Jar => "JAR",
+            FileType::Apk => "APK",
+            FileType::Gzip => "gzip",
+            FileType::SevenZip => "7z",
+            FileType::Rar => "RAR",
+            FileType::Pdf => "PDF",
+            FileType::OfficeOpenXml => "Office Open XML",
+            FileType::OfficeLegacy => "Office (OLE)",
+            FileType::Png => "PNG",
+            FileType::Jpeg => "JPEG",
+            FileType::Gif => "GIF",
+        }
+    }
+
+    pub fn kind(&self) -> FileKind {
+        match self {
+            FileType::Pe | FileType::Elf | FileType::MachO | FileType::Jar | FileType::Apk => FileKind::Executable,
+            FileType::Zip | FileType::Gzip | FileType::SevenZip | FileType::Rar => FileKind::Archive,
+            FileType::Pdf | FileType::OfficeOpenXml | FileType::OfficeLegacy => FileKind::Document,
+            FileType::Png | FileType::Jpeg | FileType::Gif => FileKind::Image,
+        }
+    }
+}
+
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// `MZ` alone is common in text; a PE has its `PE\0\0` header where the DOS header points
+fn is_pe(data: &[u8]) -> bool {
+    let Some(offset) = data.get(0x3c..0x40).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize) else {
+        // Too little of the file captured to tell; a DOS stub is still an executable
+        return data.len() < 0x40;
+    };
+    // Real headers sit within the first few hundred bytes
+    offset < 0x1000 && data.get(offset..offset + 4).is_none_or(|signature| signature == b"PE\0\0")
+}
+
+/// Tells apart ZIP-based formats by the name of their first entry
+fn zip_type(data: &[u8]) -> FileType {
+    let name_len = data.get(26..28).map_or(0, |b| u16::from_le_bytes([b[0], b[1]]) as usize);
+    let name = data.get(30..30 + name_len).unwrap_or_default();
+    match name {
+        b"[Content_Types].xml" => FileType::OfficeOpenXml,
+        b"AndroidManifest.xml" => FileType::Apk,
+        _ if name.starts_with(b"META-INF/") => FileType::Jar,
+        _ => FileType::Zip,
+    }
+}
+
+/// A file found in one direction of a TCP stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoundFile {
+    /// Which peer sent it
+    pub side: Side,
+    /// Where it starts in that peer's stream
+    pub offset: usize,
+    pub file_type: FileType,
+    /// The HTTP request it was sent for (downloads) or with (uploads), on plain HTTP
+    pub url: Option<String>,
+}
+
+/// A TCP stream that carried files
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileTransferFlow {
+    pub stream: StreamInfo,
+    pub client: Option<(IpAddr, u16)>,
+    pub files: Vec<FoundFile>,
+}
+
+impl FileTransferFlow {
+    /// Kinds of files the flow carried, e.g. `executable` and `archive`
+    pub fn labels(&self) -> Vec<&'static str> {
+        let mut kinds: Vec<FileKind> = self.files.iter().map(|f| f.file_type.kind()).collect();
+        kinds.sort();
+        kinds.dedup();
+        kinds.iter().map(FileKind::name).collect()
+    }
+
+    /// Executables the server sent over plain HTTP
+    pub fn http_executables(&self) -> impl Iterator<Item = &FoundFile> {
+        self.files.iter().filter(|f| f.side == Side::Server && f.url.is_some() && f.file_type.kind() == FileKind::Executable)
+    }
+}
+
+/// Where HTTP/1.x message bodies start in one direction of a stream, with the
+/// header block of each message. A stream that isn't HTTP has one candidate:
+/// its start.
+fn body_offsets(data: &[u8]) -> Vec<(usize, Option<&[u8]>)> {
+    if HttpMessage::parse(data).is_err() {
+        return vec![(0, None)];
+    }
+    let mut bodies = Vec::new();
+    let mut pos = 0;
+    while let Ok(message) = HttpMessage::parse(&data[pos..]) {
+        let Some(end) = message.body_offset().map(|offset| pos + offset) else { break };
+        let mut body = end;
+        let chunked = message.header("Transfer-Encoding").is_some_and(|v| v.to_ascii_lowercase().contains("chunked"));
+        if chunked {
+            // The first chunk's size line comes before the file
+            body = find(&data[body..], b"\r\n").map_or(data.len(), |i| body + i + 2);
+        }
+        bodies.push((body, Some(&data[pos..end])));
+        pos = match message.content_length() {
+            Some(length) if !chunked => end.saturating_add(length as usize),
+            _ if chunked => match find(&data[body..], b"\r\n0\r\n\r\n") {
+                Some(i) => body + i + 7,
+                None => break,
+            },
+            // A request without a length has no body
+            None if message.is_request() => end,
+            // A response without a length runs to the end of the connection
+            _ => break,
+        };
+        if pos >= data.len() {
+            break;
+        }
+    }
+    bodies
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// Looks for files at the start of each HTTP message body, or at the start of
+/// each direction for other protocols. Responses are matched with requests in
+/// order to tell the URL a download came from.
+pub fn find_files(reassembler: &TcpReassembler) -> Vec<FoundFile> {
+    let client = reassembler.stream(Side::Client);
+    let server = reassembler.stream(Side::Server);
+    let requests: Vec<Option<String>> = body_offsets(&client).iter().map(|(_, headers)| headers.and_then(http_request_url)).collect();
+    let mut files = Vec::new();
+    for (side, data) in [(Side::Client, &client), (Side::Server, &server)] {
+        for (i, (offset, headers)) in body_offsets(data).into_iter().enumerate() {
+            let Some(file_type) = data.get(offset..).and_then(FileType::identify) else { continue };
+            let url = match side {
+                Side::Client => headers.and_then(http_request_url),
+                Side::Server => headers.and(requests.get(i).cloned().flatten()),
+            };
+            files.push(FoundFile { side, offset, file_type, url });
+        }
+    }
+    files
+}
+
+/// Every TCP stream of a capture that carried a recognized file
+pub fn file_transfers(input: &Path) -> Result<Vec<FileTransferFlow>, CaptureError> {
+    Ok(tcp_streams(input)?
+        .into_iter()
+        .filter_map(|(stream, reassembler)| {
+            let files = find_files(&reassembler);
+            (!files.is_empty()).then(|| FileTransferFlow { stream, client: reassembler.client(), files })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::FlowKey;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    fn pe() -> Vec<u8> {
+        let mut exe = b"MZ".to_vec();
+        exe.resize(0x80, 0);
+        exe[0x3c] = 0x40;
+        exe[0x40..0x44].copy_from_slice(b"PE\0\0");
+        exe
+    }
+
+    #[test]
+    fn identifies_magic_bytes() {
+        assert_eq!(FileType::identify(&pe()), Some(FileType::Pe));
+        assert_eq!(FileType::identify(b"MZ is also how some text starts, and this one is longer than a DOS header."), None);
+        assert_eq!(FileType::identify(b"\x7fELF\x02\x01\x01"), Some(FileType::Elf));
+        assert_eq!(FileType::identify(b"%PDF-1.7\n"), Some(FileType::Pdf));
+        let mut docx = b"PK\x03\x04".to_vec();
+        docx.resize(26, 0);
+        docx.extend_from_slice(&19u16.to_le_bytes());
+        docx.extend_from_slice(&[0, 0]);
+        docx.extend_from_slice(b"[Content_Types].xml");
+        assert_eq!(FileType::identify(&docx), Some(FileType::OfficeOpenXml));
+        assert_eq!(FileType::identify(b"PK\x03\x04").map(|t| t.kind()), Some(FileKind::Archive));
+    }
+
+    #[test]
+    fn finds_executables_in_http_downloads() {
+        let client = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 40000);
+        let server = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 80);
+        let mut reassembler = TcpReassembler::new();
+        let requests = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\nGET /setup.exe HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        let page = b"<html></html>";
+        let mut responses = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", page.len()).into_bytes();
+        responses.extend_from_slice(page);
+        responses.extend_from_slice(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n80\r\n");
+        responses.extend(pe());
+        reassembler.push(client, 1000, 0x18, requests, Duration::ZERO);
+        reassembler.push(server, 5000, 0x18, &responses, Duration::from_millis(5));
+
+        let files = find_files(&reassembler);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].file_type, FileType::Pe);
+        assert_eq!(files[0].url.as_deref(), Some("http://example.com/setup.exe"));
+
+        let stream = StreamInfo { index: 0, key: FlowKey::new(6, client, server), packets: 2, bytes: 0 };
+        let flow = FileTransferFlow { stream, client: reassembler.client(), files };
+        assert_eq!(flow.labels(), ["executable"]);
+        assert_eq!(flow.http_executables().count(), 1);
+    }
+}
@@ -0,0 +1,530 @@
+//! Display filters.
+//!
+//! BPF filters are applied by libpcap in the kernel; display filters are
+//! evaluated by us after parsing, so they can reference decoded fields.
+//! The syntax is a small Wireshark-like subset:
+//!
+//! ```text
+//! tcp and ip.addr == 10.0.0.0/8
+//! udp.port == 53 or (tcp.dstport >= 8000 and not tcp.flags.syn)
+//! eth.dst == ff:ff:ff:ff:ff:ff
+//! ```
+
+use crate::error::CaptureError;
+use crate::presets::FilterPreset;
+use crate::protocols::ethernet::MacAddress;
+use crate::protocols::summary::PacketSummary;
+use ipnet::IpNet;
+use pcap::{Capture, Linktype};
+use std::fmt;
+use std::net::IpAddr;
+
+/// Compiled display filter expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplayFilter {
+    source: String,
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    /// A bare field or protocol name: true when present/non-zero
+    Exists(Field),
+    Compare(Field, Op, Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(u64),
+    Ip(IpAddr),
+    Net(IpNet),
+    Mac(MacAddress),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Eth,
+    EthSrc,
+    EthDst,
+    EthAddr,
+    EthType,
+    Broadcast,
+    Multicast,
+    Vlan,
+    Arp,
+    Ip,
+    Ipv6,
+    IpSrc,
+    IpDst,
+    IpAddr,
+    IpProto,
+    IpTtl,
+    Tcp,
+    Udp,
+    Icmp,
+    Icmpv6,
+    Port,
+    SrcPort,
+    DstPort,
+    TcpPort,
+    TcpSrcPort,
+    TcpDstPort,
+    UdpPort,
+    UdpSrcPort,
+    UdpDstPort,
+    TcpFlags,
+    TcpSyn,
+    TcpAck,
+    TcpFin,
+    TcpRst,
+    FrameLen,
+}
+
+/// Field names display filters understand, e.g. to tell the AI what it can use
+pub const FIELD_NAMES: &[&str] = &[
+    "eth", "eth.src", "eth.dst", "eth.addr", "eth.type", "broadcast", "multicast", "vlan.id", "arp", "ip", "ipv6",
+    "ip.src", "ip.dst", "ip.addr", "ip.proto", "ip.ttl", "ipv6.src", "ipv6.dst", "ipv6.addr", "ipv6.nxt", "ipv6.hlim",
+    "tcp", "udp", "icmp", "icmpv6", "port", "srcport", "dstport", "tcp.port", "tcp.srcport", "tcp.dstport", "udp.port",
+    "udp.srcport", "udp.dstport", "tcp.flags", "tcp.flags.syn", "tcp.flags.ack", "tcp.flags.fin", "tcp.flags.reset",
+    "frame.len",
+];
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "eth" => Field::Eth,
+            "eth.src" => Field::EthSrc,
+            "eth.dst" => Field::EthDst,
+            "eth.addr" => Field::EthAddr,
+            "eth.type" => Field::EthType,
+            "broadcast" | "eth.broadcast" => Field::Broadcast,
+            "multicast" | "eth.multicast" => Field::Multicast,
+            "vlan" | "vlan.id" => Field::Vlan,
+            "arp" => Field::Arp,
+            "ip" => Field::Ip,
+            "ipv6" => Field::Ipv6,
+            "ip.src" | "ipv6.src" => Field::IpSrc,
+            "ip.dst" | "ipv6.dst" => Field::IpDst,
+            "ip.addr" | "ipv6.addr" => Field::IpAddr,
+            "ip.proto" | "ipv6.nxt" => Field::IpProto,
+            "ip.ttl" | "ipv6.hlim" => Field::IpTtl,
+            "tcp" => Field::Tcp,
+            "udp" => Field::Udp,
+            "icmp" => Field::Icmp,
+            "icmpv6" => Field::Icmpv6,
+            "port" => Field::Port,
+            "srcport" => Field::SrcPort,
+            "dstport" => Field::DstPort,
+            "tcp.port" => Field::TcpPort,
+            "tcp.srcport" => Field::TcpSrcPort,
+            "tcp.dstport" => Field::TcpDstPort,
+            "udp.port" => Field::UdpPort,
+            "udp.srcport" => Field::UdpSrcPort,
+            "udp.dstport" => Field::UdpDstPort,
+            "tcp.flags" => Field::TcpFlags,
+            "tcp.flags.syn" => Field::TcpSyn,
+            "tcp.flags.ack" => Field::TcpAck,
+            "tcp.flags.fin" => Field::TcpFin,
+            "tcp.flags.reset" => Field::TcpRst,
+            "frame.len" => Field::FrameLen,
+            _ => return None,
+        })
+    }
+
+    /// All values the field takes in this packet (address/port fields may have two)
+    fn values(&self, p: &PacketSummary) -> Vec<Value> {
+        let num = |v: Option<u64>| v.map(Value::Number).into_iter().collect::<Vec<_>>();
+        let ip = |v: Option<IpAddr>| v.map(Value::Ip).into_iter().collect::<Vec<_>>();
+        let flag = |bit: u8| num(p.tcp_flags.map(|f| (f & bit != 0) as u64));
+        let ports_if = |cond: bool, ports: &[Option<u16>]| -> Vec<Value> {
+            if !cond {
+                return Vec::new();
+            }
+            ports.iter().flatten().map(|p| Value::Number(*p as u64)).collect()
+        };
+        match self {
+            Field::Eth => num(Some(1)),
+            Field::EthSrc => vec![Value::Mac(p.src_mac)],
+            Field::EthDst => vec![Value::Mac(p.dst_mac)],
+            Field::EthAddr => vec![Value::Mac(p.src_mac), Value::Mac(p.dst_mac)],
+            Field::EthType => num(Some(p.ether_type as u64)),
+            Field::Broadcast => num(Some((p.dst_mac.octets() == [0xff; 6]) as u64)),
+            Field::Multicast => num(Some((p.dst_mac.octets()[0] & 0x01) as u64)),
+            Field::Vlan => num(p.vlan.map(|v| v as u64)),
+            Field::Arp => num(Some((p.ether_type == 0x0806) as u64)),
+            Field::Ip => num(Some(matches!(p.src_ip, Some(IpAddr::V4(_))) as u64)),
+            Field::Ipv6 => num(Some(matches!(p.src_ip, Some(IpAddr::V6(_))) as u64)),
+            Field::IpSrc => ip(p.src_ip),
+            Field::IpDst => ip(p.dst_ip),
+            Field::IpAddr => [ip(p.src_ip), ip(p.dst_ip)].concat(),
+            Field::IpProto => num(p.ip_proto.map(|v| v as u64)),
+            Field::IpTtl => num(p.ttl.map(|v| v as u64)),
+            Field::Tcp => num(Some(p.is_tcp() as u64)),
+            Field::Udp => num(Some(p.is_udp() as u64)),
+            Field::Icmp => num(Some((p.ip_proto == Some(1)) as u64)),
+            Field::Icmpv6 => num(Some((p.ip_proto == Some(58)) as u64)),
+            Field::Port => ports_if(true, &[p.src_port, p.dst_port]),
+            Field::SrcPort => ports_if(true, &[p.src_port]),
+            Field::DstPort => ports_if(true, &[p.dst_port]),
+            Field::TcpPort => ports_if(p.is_tcp(), &[p.src_port, p.dst_port]),
+            Field::TcpSrcPort => ports_if(p.is_tcp(), &[p.src_port]),
+            Field::TcpDstPort => ports_if(p.is_tcp(), &[p.dst_port]),
+            Field::UdpPort => ports_if(p.is_udp(), &[p.src_port, p.dst_port]),
+            Field::UdpSrcPort => ports_if(p.is_udp(), &[p.src_port]),
+            Field::UdpDstPort => ports_if(p.is_udp(), &[p.dst_port]),
+            Field::TcpFlags => num(p.tcp_flags.map(|v| v as u64)),
+            Field::TcpSyn => flag(0x02),
+            Field::TcpAck => flag(0x10),
+            Field::TcpFin => flag(0x01),
+            Field::TcpRst => flag(0x04),
+            Field::FrameLen => num(Some(p.length as u64)),
+        }
+    }
+}
+
+impl Value {
+    fn parse(token: &str) -> Option<Self> {
+        if let Some(hex) = token.strip_prefix("0x") {
+            return u64::from_str_radix(hex, 16).ok().map(Value::Number);
+        }
+        if let Ok(n) = token.parse::<u64>() {
+            return Some(Value::Number(n));
+        }
+        if let Ok(ip) = token.parse::<IpAddr>() {
+            return Some(Value::Ip(ip));
+        }
+        if let Ok(net) = token.parse::<IpNet>() {
+            return Some(Value::Net(net));
+        }
+        if let Ok(mac) = token.parse::<MacAddress>() {
+            return Some(Value::Mac(mac));
+        }
+        match token {
+            "true" => Some(Value::Number(1)),
+            "false" => Some(Value::Number(0)),
+            _ => None,
+        }
+    }
+}
+
+fn compare(actual: &Value, op: Op, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Number(a), Value::Number(b)) => match op {
+            Op::Eq => a == b,
+            Op::Ne => a != b,
+            Op::Lt => a < b,
+            Op::Le => a <= b,
+            Op::Gt => a > b,
+            Op::Ge => a >= b,
+        },
+        (Value::Ip(a), Value::Ip(b)) => match op {
+            Op::Eq => a == b,
+            Op::Ne => a != b,
+            _ => false,
+        },
+        (Value::Ip(a), Value::Net(net)) => match op {
+            Op::Eq => net.contains(a),
+            Op::Ne => !net.contains(a),
+            _ => false,
+        },
+        (Value::Mac(a), Value::Mac(b)) => match op {
+            Op::Eq => a == b,
+            Op::Ne => a != b,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+impl Expr {
+    fn matches(&self, p: &PacketSummary) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(p) && b.matches(p),
+            Expr::Or(a, b) => a.matches(p) || b.matches(p),
+            Expr::Not(e) => !e.matches(p),
+            Expr::Exists(field) => field.values(p).iter().any(|v| *v != Value::Number(0)),
+            // Like Wireshark, `!=` on multi-valued fields means "no value equals"
+            Expr::Compare(field, Op::Ne, value) => {
+                !field.values(p).iter().any(|v| compare(v, Op::Eq, value))
+            }
+            Expr::Compare(field, op, value) => field.values(p).iter().any(|v| compare(v, *op, value)),
+        }
+    }
+}
+
+impl DisplayFilter {
+    pub fn parse(source: &str) -> Result<Self, CaptureError> {
+        let tokens = tokenize(source);
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(filter_error(source, &format!("unexpected '{}'", token)));
+        }
+        Ok(DisplayFilter { source: source.to_string(), expr })
+    }
+
+    pub fn matches(&self, summary: &PacketSummary) -> bool {
+        self.expr.matches(summary)
+    }
+
+    /// Parses the frame and evaluates the filter; unparseable frames never match
+    pub fn matches_frame(&self, data: &[u8]) -> bool {
+        self.matches_link(1, data)
+    }
+
+    /// Like `matches_frame` for frames of any datalink `PacketSummary` understands
+    pub fn matches_link(&self, datalink: i32, data: &[u8]) -> bool {
+        PacketSummary::from_link(datalink, data).is_some_and(|s| self.matches(&s))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Combines two filters with a logical AND
+    pub fn and(self, other: DisplayFilter) -> DisplayFilter {
+        DisplayFilter {
+            source: format!("({}) and ({})", self.source, other.source),
+            expr: Expr::And(Box::new(self.expr), Box::new(other.expr)),
+        }
+    }
+}
+
+impl fmt::Display for DisplayFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// Filters applied to a capture: BPF inside libpcap, the display filter after parsing
+#[derive(Debug, Clone, Default)]
+pub struct CaptureFilter {
+    pub bpf: Option<String>,
+    pub display: Option<DisplayFilter>,
+}
+
+impl CaptureFilter {
+    /// Combines user-supplied filters with an optional preset (both must match)
+    pub fn new(bpf: Option<&str>, display: Option<&str>, preset: Option<&FilterPreset>) -> Result<Self, CaptureError> {
+        let bpf = match (preset.and_then(|p| p.bpf.as_deref()), bpf) {
+            (Some(a), Some(b)) => Some(format!("({}) and ({})", a, b)),
+            (a, b) => a.or(b).map(str::to_string),
+        };
+        let preset_display = preset
+            .and_then(|p| p.display.as_deref())
+            .map(DisplayFilter::parse)
+            .transpose()?;
+        let display = display.map(DisplayFilter::parse).transpose()?;
+        let display = match (preset_display, display) {
+            (Some(a), Some(b)) => Some(a.and(b)),
+            (a, b) => a.or(b),
+        };
+        Ok(CaptureFilter { bpf, display })
+    }
+
+    /// True when the frame passes the display filter (or there is none)
+    pub fn matches(&self, datalink: i32, data: &[u8]) -> bool {
+        self.display.as_ref().is_none_or(|f| f.matches_link(datalink, data))
+    }
+}
+
+/// Checks that libpcap compiles a BPF expression for Ethernet frames, before
+/// it is applied to a capture
+pub fn validate_bpf(bpf: &str) -> Result<(), CaptureError> {
+    Capture::dead(Linktype::ETHERNET)
+        .and_then(|cap| cap.compile(bpf, true))
+        .map(|_| ())
+        .map_err(|e| CaptureError::PcapError(format!("Invalid BPF '{}': {}", bpf, e)))
+}
+
+fn filter_error(source: &str, msg: &str) -> CaptureError {
+    CaptureError::InputError(format!("Invalid display filter '{}': {}", source, msg))
+}
+
+fn tokenize(source: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let chars: Vec<char> = source.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+        let symbol = match two.as_str() {
+            "==" | "!=" | "<=" | ">=" | "&&" | "||" => Some(two.clone()),
+            _ if matches!(c, '(' | ')' | '<' | '>' | '!') => Some(c.to_string()),
+            _ => None,
+        };
+        if c.is_whitespace() || symbol.is_some() {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            if let Some(symbol) = symbol {
+                i += symbol.len();
+                tokens.push(symbol);
+                continue;
+            }
+        } else {
+            current.push(c);
+        }
+        i += 1;
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn error(&self, msg: &str) -> CaptureError {
+        filter_error(&self.tokens.join(" "), msg)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, CaptureError> {
+        let mut left = self.parse_and()?;
+        while matches!(self.peek(), Some("or") | Some("||")) {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, CaptureError> {
+        let mut left = self.parse_not()?;
+        while matches!(self.peek(), Some("and") | Some("&&")) {
+            self.pos += 1;
+            let right = self.parse_not()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, CaptureError> {
+        if matches!(self.peek(), Some("not") | Some("!")) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, CaptureError> {
+        let token = self.next().ok_or_else(|| self.error("unexpected end of expression"))?;
+        if token == "(" {
+            let expr = self.parse_or()?;
+            if self.next().as_deref() != Some(")") {
+                return Err(self.error("missing ')'"));
+            }
+            return Ok(expr);
+        }
+
+        let field = Field::from_name(&token).ok_or_else(|| self.error(&format!("unknown field '{}'", token)))?;
+        let op = match self.peek() {
+            Some("==") | Some("eq") => Op::Eq,
+            Some("!=") | Some("ne") => Op::Ne,
+            Some("<") | Some("lt") => Op::Lt,
+            Some("<=") | Some("le") => Op::Le,
+            Some(">") | Some("gt") => Op::Gt,
+            Some(">=") | Some("ge") => Op::Ge,
+            _ => return Ok(Expr::Exists(field)),
+        };
+        self.pos += 1;
+        let raw = self.next().ok_or_else(|| self.error("missing value"))?;
+        let value = Value::parse(&raw).ok_or_else(|| self.error(&format!("invalid value '{}'", raw)))?;
+        Ok(Expr::Compare(field, op, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    fn matches(filter: &str, frame: &[u8]) -> bool {
+        DisplayFilter::parse(filter).unwrap().matches_frame(frame)
+    }
+
+    #[test]
+    fn matches_protocols_and_ports() {
+        let dns = testutil::dns_query_frame(1, "example.com");
+        assert!(matches("udp", &dns));
+        assert!(matches("udp.port == 53", &dns));
+        assert!(matches("udp.dstport==53 && !tcp", &dns));
+        assert!(!matches("tcp.port == 53", &dns));
+        assert!(matches("port != 80", &dns));
+        assert!(matches("frame.len > 40", &dns));
+
+        let syn = testutil::ipv4_tcp_frame(40000, 443, testutil::TCP_SYN, b"");
+        assert!(matches("tcp.flags.syn and not tcp.flags.ack", &syn));
+        assert!(matches("tcp.port == 80 or (tcp.port == 443 and ip)", &syn));
+    }
+
+    #[test]
+    fn matches_addresses() {
+        let frame = testutil::ipv4_udp_frame(1, 2, b"");
+        assert!(matches("ip.src == 192.168.1.10", &frame));
+        assert!(matches("ip.addr == 192.168.0.0/16", &frame));
+        assert!(matches("ip.dst != 10.0.0.0/8", &frame));
+        assert!(matches("eth.dst == 00:11:22:33:44:55", &frame));
+        assert!(!matches("broadcast", &frame));
+
+        let arp = testutil::arp_request(testutil::client_v4(), testutil::server_v4());
+        assert!(matches("arp and broadcast", &arp));
+        assert!(matches("eth.type == 0x0806", &arp));
+    }
+
+    #[test]
+    fn combines_preset_and_user_filters() {
+        let preset = FilterPreset {
+            name: "web".to_string(),
+            description: String::new(),
+            bpf: Some("tcp port 80".to_string()),
+            display: Some("tcp.port == 80".to_string()),
+        };
+        let filter = CaptureFilter::new(Some("host 10.0.0.1"), Some("tcp.flags.syn"), Some(&preset)).unwrap();
+        assert_eq!(filter.bpf.as_deref(), Some("(tcp port 80) and (host 10.0.0.1)"));
+        assert!(filter.matches(1, &testutil::ipv4_tcp_frame(1, 80, testutil::TCP_SYN, b"")));
+        assert!(!filter.matches(1, &testutil::ipv4_tcp_frame(1, 80, testutil::TCP_ACK, b"")));
+        assert!(CaptureFilter::default().matches(1, &[]));
+    }
+
+    #[test]
+    fn rejects_invalid_filters() {
+        assert!(DisplayFilter::parse("bogus.field == 1").is_err());
+        assert!(DisplayFilter::parse("(tcp").is_err());
+        assert!(DisplayFilter::parse("tcp.port ==").is_err());
+        assert!(DisplayFilter::parse("tcp udp").is_err());
+    }
+
+    #[test]
+    fn lists_known_fields() {
+        assert!(FIELD_NAMES.iter().all(|name| Field::from_name(name).is_some()));
+    }
+}
@@ -0,0 +1,285 @@
+use crate::builder::{TCP_FIN, TCP_RST};
+use crate::protocols::summary::PacketSummary;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Direction-independent 5-tuple identifying a conversation: both directions
+/// of a TCP connection or UDP exchange map to the same key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct FlowKey {
+    pub protocol: u8,
+    /// Lower endpoint (address, port)
+    pub a: (IpAddr, u16),
+    /// Higher endpoint (address, port)
+    pub b: (IpAddr, u16),
+}
+
+impl FlowKey {
+    pub fn new(protocol: u8, src: (IpAddr, u16), dst: (IpAddr, u16)) -> Self {
+        let (a, b) = if src <= dst { (src, dst) } else { (dst, src) };
+        FlowKey { protocol, a, b }
+    }
+
+    /// Key of an IP packet; ports are 0 for protocols without them
+    pub fn from_summary(summary: &PacketSummary) -> Option<Self> {
+        Some(FlowKey::new(
+            summary.ip_proto?,
+            (summary.src_ip?, summary.src_port.unwrap_or(0)),
+            (summary.dst_ip?, summary.dst_port.unwrap_or(0)),
+        ))
+    }
+
+    pub fn protocol_name(&self) -> String {
+        match self.protocol {
+            1 => "icmp".to_string(),
+            6 => "tcp".to_string(),
+            17 => "udp".to_string(),
+            132 => "sctp".to_string(),
+            58 => "icmpv6".to_string(),
+            other => format!("ip{}", other),
+        }
+    }
+
+    /// Filesystem-safe name, e.g. `tcp_10.0.0.1_443-10.0.0.2_51000`
+    pub fn file_stem(&self) -> String {
+        format!(
+            "{}_{}_{}-{}_{}",
+            self.protocol_name(),
+            self.a.0,
+            self.a.1,
+            self.b.0,
+            self.b.1
+        )
+        .replace(':', ".")
+    }
+}
+
+impl fmt::Display for FlowKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let endpoint = |(ip, port): (IpAddr, u16)| match ip {
+            IpAddr::V4(ip) => format!("{}:{}", ip, port),
+            IpAddr::V6(ip) => format!("[{}]:{}", ip, port),
+        };
+        write!(f, "{} {} <-> {}", self.protocol_name().to_uppercase(), endpoint(self.a), endpoint(self.b))
+    }
+}
+
+/// Why a conversation is considered over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FlowEnd {
+    /// Both sides sent FIN
+    Fin,
+    Reset,
+    /// No packets for the idle timeout
+    Idle,
+    /// Still open when the capture stopped
+    CaptureEnd,
+}
+
+impl FlowEnd {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FlowEnd::Fin => "fin",
+            FlowEnd::Reset => "rst",
+            FlowEnd::Idle => "idle",
+            FlowEnd::CaptureEnd => "end",
+        }
+    }
+}
+
+/// Totals of a finished conversation; times are capture timestamps in seconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlowRecord {
+    pub key: FlowKey,
+    /// Endpoint that sent the first packet seen
+    pub initiator: (IpAddr, u16),
+    pub packets: u64,
+    pub bytes: u64,
+    /// Packets and bytes sent by the initiator
+    pub packets_out: u64,
+    pub bytes_out: u64,
+    pub first: f64,
+    pub last: f64,
+    pub end: FlowEnd,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FlowState {
+    record: FlowRecord,
+    /// FIN seen from endpoint a, from endpoint b
+    fins: (bool, bool),
+    /// When the second FIN was seen; the final ACK still belongs to the flow
+    closed_at: Option<f64>,
+}
+
+/// Seconds a closed TCP connection lingers so its last ACK isn't counted as a new flow
+const CLOSE_LINGER: f64 = 1.0;
+
+/// Follows conversations during a capture and reports them once they end
+#[derive(Serialize, Deserialize)]
+pub struct FlowTracker {
+    flows: HashMap<FlowKey, FlowState>,
+    idle_timeout: f64,
+    last_sweep: f64,
+}
+
+impl FlowTracker {
+    pub fn new(idle_timeout: Duration) -> Self {
+        FlowTracker { flows: HashMap::new(), idle_timeout: idle_timeout.as_secs_f64(), last_sweep: 0.0 }
+    }
+
+    /// Counts a packet seen at `time` and returns the flows that ended,
+    /// by reset, FIN exchange or inactivity
+    pub fn push(&mut self, summary: &PacketSummary, time: f64) -> Vec<FlowRecord> {
+        let mut ended = self.sweep(time);
+        let Some(key) = FlowKey::from_summary(summary) else { return ended };
+        let sender = (summary.src_ip.unwrap_or(key.a.0), summary.src_port.unwrap_or(0));
+        let state = self.flows.entry(key).or_insert_with(|| FlowState {
+            record: FlowRecord {
+                key,
+                initiator: sender,
+                packets: 0,
+                bytes: 0,
+                packets_out: 0,
+                bytes_out: 0,
+                first: time,
+                last: time,
+                end: FlowEnd::CaptureEnd,
+            },
+            fins: (false, false),
+            closed_at: None,
+        });
+        state.record.packets += 1;
+        state.record.bytes += summary.length as u64;
+        if sender == state.record.initiator {
+            state.record.packets_out += 1;
+            state.record.bytes_out += summary.length as u64;
+        }
+        state.record.last = time;
+
+        let flags = summary.tcp_flags.unwrap_or(0);
+        if flags & TCP_RST != 0 {
+            if let Some(state) = self.flows.remove(&key) {
+                ended.push(FlowRecord { end: FlowEnd::Reset, ..state.record });
+            }
+        } else if flags & TCP_FIN != 0 {
+            if sender == key.a {
+                state.fins.0 = true;
+            } else {
+                state.fins.1 = true;
+            }
+            if state.fins == (true, true) && state.closed_at.is_none() {
+                state.closed_at = Some(time);
+            }
+        }
+        ended
+    }
+
+    /// Totals so far of a flow that is still open
+    pub fn flow(&self, key: &FlowKey) -> Option<&FlowRecord> {
+        self.flows.get(key).map(|state| &state.record)
+    }
+
+    /// Totals so far of every flow that is still open
+    pub fn open(&self) -> impl Iterator<Item = &FlowRecord> {
+        self.flows.values().map(|state| &state.record)
+    }
+
+    /// Every flow still open, e.g. when the capture stops
+    pub fn finish(&mut self) -> Vec<FlowRecord> {
+        let mut records: Vec<FlowRecord> = self
+            .flows
+            .drain()
+            .map(|(_, state)| {
+                let end = if state.closed_at.is_some() { FlowEnd::Fin } else { FlowEnd::CaptureEnd };
+                FlowRecord { end, ..state.record }
+            })
+            .collect();
+        records.sort_by(|a, b| a.first.total_cmp(&b.first));
+        records
+    }
+
+    /// Flows closed or idle as of `now`, checked at most once per second of capture time
+    fn sweep(&mut self, now: f64) -> Vec<FlowRecord> {
+        if now - self.last_sweep < 1.0 {
+            return Vec::new();
+        }
+        self.last_sweep = now;
+        let idle_timeout = self.idle_timeout;
+        let expired = |state: &FlowState| match state.closed_at {
+            Some(closed) => now - closed >= CLOSE_LINGER,
+            None => now - state.record.last >= idle_timeout,
+        };
+        let keys: Vec<FlowKey> = self.flows.iter().filter(|(_, state)| expired(state)).map(|(key, _)| *key).collect();
+        let mut records: Vec<FlowRecord> = keys
+            .into_iter()
+            .filter_map(|key| self.flows.remove(&key))
+            .map(|state| {
+                let end = if state.closed_at.is_some() { FlowEnd::Fin } else { FlowEnd::Idle };
+                FlowRecord { end, ..state.record }
+            })
+            .collect();
+        records.sort_by(|a, b| a.first.total_cmp(&b.first));
+        records
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn both_directions_share_a_key() {
+        let out = testutil::ipv4_tcp_frame(40000, 443, testutil::TCP_SYN, b"");
+        let key = FlowKey::from_summary(&PacketSummary::from_ethernet(&out).unwrap()).unwrap();
+        let back = FlowKey::new(6, (IpAddr::V4(testutil::server_v4()), 443), (IpAddr::V4(testutil::client_v4()), 40000));
+        assert_eq!(key, back);
+        assert_eq!(key.to_string(), "TCP 93.184.216.34:443 <-> 192.168.1.10:40000");
+        assert_eq!(key.file_stem(), "tcp_93.184.216.34_443-192.168.1.10_40000");
+    }
+
+    #[test]
+    fn ipv6_stems_are_filesystem_safe() {
+        let frame = testutil::ipv6_udp_frame(5353, 53, b"");
+        let key = FlowKey::from_summary(&PacketSummary::from_ethernet(&frame).unwrap()).unwrap();
+        assert!(!key.file_stem().contains(':'));
+        assert!(key.to_string().contains("[fe80::1]:5353"));
+
+        let arp = testutil::arp_request(testutil::client_v4(), testutil::server_v4());
+        assert!(FlowKey::from_summary(&PacketSummary::from_ethernet(&arp).unwrap()).is_none());
+    }
+
+    #[test]
+    fn tracker_ends_flows_on_reset_fin_and_idle() {
+        // Packets from the client on `port` to the server's port 80, or back when `reply`
+        let packet = |port: u16, flags: u8, reply: bool| {
+            let (client, server) = (testutil::client_v4(), testutil::server_v4());
+            let frame = match reply {
+                false => testutil::frame().ipv4(client, server).tcp(port, 80),
+                true => testutil::frame().ipv4(server, client).tcp(80, port),
+            };
+            PacketSummary::from_ethernet(&frame.tcp_flags(flags).build()).unwrap()
+        };
+        let mut tracker = FlowTracker::new(Duration::from_secs(60));
+        assert!(tracker.push(&packet(40000, testutil::TCP_SYN, false), 0.0).is_empty());
+        let reset = tracker.push(&packet(40000, testutil::TCP_RST, true), 0.5);
+        assert_eq!((reset[0].packets, reset[0].end), (2, FlowEnd::Reset));
+
+        tracker.push(&packet(40001, testutil::TCP_FIN | testutil::TCP_ACK, false), 10.0);
+        tracker.push(&packet(40001, testutil::TCP_FIN | testutil::TCP_ACK, true), 10.1);
+        tracker.push(&packet(40001, testutil::TCP_ACK, false), 10.2);
+        tracker.push(&packet(40002, testutil::TCP_SYN, false), 10.3);
+        let closed = tracker.push(&packet(40003, testutil::TCP_SYN, false), 12.0);
+        assert_eq!((closed.len(), closed[0].packets, closed[0].end), (1, 3, FlowEnd::Fin));
+        assert_eq!((closed[0].initiator.1, closed[0].packets_out), (40001, 2));
+
+        let idle = tracker.push(&packet(40003, testutil::TCP_ACK, false), 71.0);
+        assert_eq!((idle.len(), idle[0].key.b.1, idle[0].end), (1, 40002, FlowEnd::Idle));
+        let open = tracker.finish();
+        assert_eq!((open.len(), open[0].packets, open[0].end), (1, 2, FlowEnd::CaptureEnd));
+    }
+}
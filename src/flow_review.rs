@@ -0,0 +1,252 @@
+use crate::ai_analyzer::SecurityAnalysis;
+use crate::cluster::ClusterMatch;
+use crate::flow::{FlowKey, FlowRecord};
+use crate::index::{client_hello_fragment, tls_server_name};
+use crate::protocols::dns::DnsMessage;
+use crate::protocols::http::HttpMessage;
+use crate::protocols::summary::PacketSummary;
+use crate::protocols::tls::{is_grease, ClientHello};
+use md5::{Digest, Md5};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+
+/// Flows waiting for the AI; later ones are skipped until it catches up
+const MAX_PENDING: usize = 64;
+
+/// Values of one kind kept per flow, so a chatty flow can't grow without bound
+const MAX_VALUES: usize = 16;
+
+/// What the application layer of a flow gave away, for the AI to judge it by
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FlowExtractions {
+    pub dns_names: BTreeSet<String>,
+    pub http_urls: BTreeSet<String>,
+    pub tls_sni: BTreeSet<String>,
+    /// JA3 fingerprints of the ClientHellos
+    pub ja3: BTreeSet<String>,
+}
+
+impl FlowExtractions {
+    /// Picks names, URLs and fingerprints out of one packet of the flow
+    pub fn push(&mut self, summary: &PacketSummary, data: &[u8]) {
+        let payload = summary.payload(data);
+        if payload.is_empty() {
+            return;
+        }
+        let keep = |set: &mut BTreeSet<String>, value: Option<String>| {
+            if let Some(value) = value
+                && set.len() < MAX_VALUES
+            {
+                set.insert(value);
+            }
+        };
+        if summary.is_udp() && (summary.has_port(53) || summary.has_port(5353)) {
+            keep(&mut self.dns_names, DnsMessage::parse(payload).ok().and_then(|message| message.question_name()));
+        } else if summary.is_tcp() {
+            keep(&mut self.http_urls, http_request_url(payload));
+            keep(&mut self.tls_sni, tls_server_name(payload));
+            keep(&mut self.ja3, ja3(payload));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dns_names.is_empty() && self.http_urls.is_empty() && self.tls_sni.is_empty() && self.ja3.is_empty()
+    }
+}
+
+impl fmt::Display for FlowExtractions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "nothing recognized at the application layer");
+        }
+        let lists = [("DNS names", &self.dns_names), ("HTTP URLs", &self.http_urls), ("TLS SNI", &self.tls_sni), ("JA3", &self.ja3)];
+        let parts: Vec<String> = lists
+            .iter()
+            .filter(|(_, values)| !values.is_empty())
+            .map(|(name, values)| format!("{}: {}", name, values.iter().cloned().collect::<Vec<_>>().join(", ")))
+            .collect();
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+/// Why a flow was sent for review
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewTrigger {
+    /// The flow ended
+    Ended,
+    /// An alert was raised on one of its packets; the flow was still open
+    Flagged,
+}
+
+/// A flow to submit to the AI
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FlowReviewRequest {
+    pub flow: FlowRecord,
+    pub extractions: FlowExtractions,
+    pub trigger: ReviewTrigger,
+    /// How the flow compares with earlier ones, with `--cluster-flows`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<String>,
+}
+
+/// A flow with the AI's verdict attached, as kept in the `--report` file
+#[derive(Debug, Serialize)]
+pub struct ReviewedFlow {
+    #[serde(flatten)]
+    pub request: FlowReviewRequest,
+    pub verdict: SecurityAnalysis,
+}
+
+/// Gathers application-layer extractions per open flow and queues flows for
+/// an AI verdict when they end or an alert flags them. A flagged flow is
+/// reviewed once, not again when it ends.
+#[derive(Default)]
+pub struct FlowReview {
+    extractions: HashMap<FlowKey, FlowExtractions>,
+    flagged: HashSet<FlowKey>,
+    pending: VecDeque<FlowReviewRequest>,
+    skipped: u64,
+}
+
+impl FlowReview {
+    pub fn new() -> Self {
+        FlowReview::default()
+    }
+
+    /// Adds what a packet of an open flow carries
+    pub fn push(&mut self, summary: &PacketSummary, data: &[u8]) {
+        if let Some(key) = FlowKey::from_summary(summary) {
+            self.extractions.entry(key).or_default().push(summary, data);
+        }
+    }
+
+    /// Queues a flow that ended, unless it was already reviewed when flagged.
+    /// Flows unlike any seen before go first.
+    pub fn ended(&mut self, flow: &FlowRecord, cluster: Option<ClusterMatch>) {
+        let extractions = self.extractions.remove(&flow.key).unwrap_or_default();
+        if !self.flagged.remove(&flow.key) {
+            let similarity = cluster.map(|c| c.to_string());
+            let request = FlowReviewRequest { flow: flow.clone(), extractions, trigger: ReviewTrigger::Ended, similarity };
+            if cluster.is_some_and(|c| c.novel) && self.pending.len() < MAX_PENDING {
+                self.pending.push_front(request);
+            } else {
+                self.queue(request);
+            }
+        }
+    }
+
+    /// Queues an open flow that a heuristic raised an alert on, once
+    pub fn flag(&mut self, flow: &FlowRecord) {
+        if self.flagged.insert(flow.key) {
+            let extractions = self.extractions.get(&flow.key).cloned().unwrap_or_default();
+            self.queue(FlowReviewRequest { flow: flow.clone(), extractions, trigger: ReviewTrigger::Flagged, similarity: None });
+        }
+    }
+
+    /// The next flow to submit
+    pub fn take_next(&mut self) -> Option<FlowReviewRequest> {
+        self.pending.pop_front()
+    }
+
+    /// Flows not reviewed because too many were waiting
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    fn queue(&mut self, request: FlowReviewRequest) {
+        if self.pending.len() < MAX_PENDING {
+            self.pending.push_back(request);
+        } else {
+            self.skipped += 1;
+        }
+    }
+}
+
+/// `http://host/path` of an HTTP/1.x request at the start of a segment
+pub(crate) fn http_request_url(payload: &[u8]) -> Option<String> {
+    let message = HttpMessage::parse(payload).ok()?;
+    let uri = message.uri()?;
+    if uri.starts_with("http://") || uri.starts_with("https://") {
+        return Some(uri.to_string());
+    }
+    Some(match message.host() {
+        Some(host) => format!("http://{}{}", host, uri),
+        None => uri.to_string(),
+    })
+}
+
+/// JA3 fingerprint of a TLS ClientHello at the start of a segment: the MD5 of
+/// version, cipher suites, extensions, curves and point formats, GREASE values
+/// left out
+fn ja3(payload: &[u8]) -> Option<String> {
+    let hello = ClientHello::parse(client_hello_fragment(payload)?)?;
+    let list = |values: &[u16]| values.iter().filter(|&&v| !is_grease(v)).map(|v| v.to_string()).collect::<Vec<_>>().join("-");
+    let formats = hello.ec_point_formats.iter().map(|f| f.to_string()).collect::<Vec<_>>().join("-");
+    let text = format!(
+        "{},{},{},{},{}",
+        hello.version,
+        list(&hello.cipher_suites),
+        list(&hello.extensions),
+        list(&hello.supported_groups),
+        formats
+    );
+    Some(Md5::digest(text.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::FlowTracker;
+    use crate::testutil;
+    use std::time::Duration;
+
+    fn summary(frame: &[u8]) -> PacketSummary {
+        PacketSummary::from_ethernet(frame).unwrap()
+    }
+
+    #[test]
+    fn extracts_names_urls_and_fingerprints() {
+        let mut extractions = FlowExtractions::default();
+        let hello = testutil::ipv4_tcp_frame(40000, 443, 0x18, &testutil::tls_client_hello("api.example.org"));
+        extractions.push(&summary(&hello), &hello);
+        let request = testutil::ipv4_tcp_frame(40001, 80, 0x18, b"GET /login?next=%2F HTTP/1.1\r\nHost: example.com\r\n\r\n");
+        extractions.push(&summary(&request), &request);
+        let query = testutil::dns_query_frame(1, "updates.example.net");
+        extractions.push(&summary(&query), &query);
+
+        assert_eq!(extractions.tls_sni.iter().collect::<Vec<_>>(), ["api.example.org"]);
+        // md5("771,4865,0,,")
+        assert_eq!(extractions.ja3.iter().collect::<Vec<_>>(), ["1e7c622032b0cb79401b0f7be3793a1a"]);
+        assert_eq!(extractions.http_urls.iter().collect::<Vec<_>>(), ["http://example.com/login?next=%2F"]);
+        assert_eq!(extractions.dns_names.iter().collect::<Vec<_>>(), ["updates.example.net"]);
+        assert!(extractions.to_string().starts_with("DNS names: updates.example.net; HTTP URLs: "));
+        assert_eq!(http_request_url(b"SSH-2.0-OpenSSH_9.6\r\n"), None);
+    }
+
+    #[test]
+    fn queues_flows_once() {
+        let mut tracker = FlowTracker::new(Duration::from_secs(60));
+        let mut review = FlowReview::new();
+        let hello = testutil::ipv4_tcp_frame(40000, 443, 0x18, &testutil::tls_client_hello("api.example.org"));
+        tracker.push(&summary(&hello), 1.0);
+        review.push(&summary(&hello), &hello);
+        let flow = tracker.open().next().unwrap().clone();
+
+        review.flag(&flow);
+        review.flag(&flow);
+        let flagged = review.take_next().unwrap();
+        assert_eq!(flagged.trigger, ReviewTrigger::Flagged);
+        assert!(flagged.extractions.tls_sni.contains("api.example.org"));
+        assert!(review.take_next().is_none());
+        // Already reviewed when flagged
+        review.ended(&flow, None);
+        assert!(review.take_next().is_none());
+
+        let other = FlowRecord { key: FlowKey { protocol: 17, ..flow.key }, ..flow };
+        review.ended(&other, None);
+        let ended = review.take_next().unwrap();
+        assert_eq!((ended.trigger, ended.extractions.is_empty()), (ReviewTrigger::Ended, true));
+    }
+}
@@ -0,0 +1,317 @@
+use crate::capfile::{CaptureReader, StoredPacket};
+use crate::error::CaptureError;
+use crate::flow::FlowKey;
+use crate::protocols::summary::PacketSummary;
+use crate::reassembly::{Side, StreamChunk, TcpReassembler};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// How followed data is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FollowFormat {
+    /// Printable text, other bytes shown as `.`
+    Ascii,
+    /// Offset, hex bytes and ASCII columns
+    Hex,
+}
+
+impl FollowFormat {
+    /// Name used in `tshark -z follow` titles
+    pub fn name(&self) -> &'static str {
+        match self {
+            FollowFormat::Ascii => "ascii",
+            FollowFormat::Hex => "hex",
+        }
+    }
+}
+
+/// A conversation found in a capture, numbered in order of first appearance
+/// like Wireshark's `tcp.stream`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamInfo {
+    pub index: usize,
+    pub key: FlowKey,
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+/// Lists the conversations of one IP protocol in a capture file
+pub fn list_streams(input: &Path, protocol: u8) -> Result<Vec<StreamInfo>, CaptureError> {
+    let mut streams: Vec<StreamInfo> = Vec::new();
+    let mut by_key: HashMap<FlowKey, usize> = HashMap::new();
+    let reader = CaptureReader::open(input)?;
+    let datalink = reader.linktype().0;
+    for packet in reader {
+        let Some(key) = PacketSummary::from_link(datalink, &packet.data)
+            .and_then(|s| FlowKey::from_summary(&s))
+            .filter(|k| k.protocol == protocol)
+        else {
+            continue;
+        };
+        let index = *by_key.entry(key).or_insert_with(|| {
+            streams.push(StreamInfo { index: streams.len(), key, packets: 0, bytes: 0 });
+            streams.len() - 1
+        });
+        streams[index].packets += 1;
+        streams[index].bytes += packet.header.len as u64;
+    }
+    Ok(streams)
+}
+
+/// Finds TCP stream `stream` and reassembles both of its directions
+pub fn follow_tcp(input: &Path, stream: usize) -> Result<(StreamInfo, TcpReassembler), CaptureError> {
+    let info = list_streams(input, 6)?
+        .into_iter()
+        .nth(stream)
+        .ok_or_else(|| CaptureError::InputError(format!("No TCP stream {} in {}", stream, input.display())))?;
+    let mut reassembler = TcpReassembler::new();
+    let reader = CaptureReader::open(input)?;
+    let datalink = reader.linktype().0;
+    for packet in reader {
+        let Some(summary) = PacketSummary::from_link(datalink, &packet.data) else { continue };
+        if FlowKey::from_summary(&summary) != Some(info.key) {
+            continue;
+        }
+        push_segment(&mut reassembler, &summary, &packet);
+    }
+    Ok((info, reassembler))
+}
+
+/// Reassembles every TCP stream in a capture in a single pass, in stream order
+pub fn tcp_streams(input: &Path) -> Result<Vec<(StreamInfo, TcpReassembler)>, CaptureError> {
+    let mut streams: Vec<(StreamInfo, TcpReassembler)> = Vec::new();
+    let mut by_key: HashMap<FlowKey, usize> = HashMap::new();
+    let reader = CaptureReader::open(input)?;
+    let datalink = reader.linktype().0;
+    for packet in reader {
+        let Some(summary) = PacketSummary::from_link(datalink, &packet.data) else { continue };
+        let Some(key) = FlowKey::from_summary(&summary).filter(|k| k.protocol == 6) else { continue };
+        let index = *by_key.entry(key).or_insert_with(|| {
+            let info = StreamInfo { index: streams.len(), key, packets: 0, bytes: 0 };
+            streams.push((info, TcpReassembler::new()));
+            streams.len() - 1
+        });
+        let (info, reassembler) = &mut streams[index];
+        info.packets += 1;
+        info.bytes += packet.header.len as u64;
+        push_segment(reassembler, &summary, &packet);
+    }
+    Ok(streams)
+}
+
+fn push_segment(reassembler: &mut TcpReassembler, summary: &PacketSummary, packet: &StoredPacket) {
+    if let (Some(src), Some(port), Some(seq), Some(flags)) =
+        (summary.src_ip, summary.src_port, summary.tcp_seq(&packet.data), summary.tcp_flags)
+    {
+        reassembler.push((src, port), seq, flags, summary.payload(&packet.data), packet.timestamp());
+    }
+}
+
+/// Datagrams of one UDP conversation in capture order
+#[derive(Debug, Clone, PartialEq)]
+pub struct UdpConversation {
+    pub info: StreamInfo,
+    /// The first sender seen
+    pub client: (IpAddr, u16),
+    pub server: (IpAddr, u16),
+    pub datagrams: Vec<StreamChunk>,
+}
+
+/// Finds UDP conversation `stream` and collects its datagrams
+pub fn follow_udp(input: &Path, stream: usize) -> Result<UdpConversation, CaptureError> {
+    let info = list_streams(input, 17)?
+        .into_iter()
+        .nth(stream)
+        .ok_or_else(|| CaptureError::InputError(format!("No UDP stream {} in {}", stream, input.display())))?;
+    let mut client = None;
+    let mut datagrams = Vec::new();
+    let reader = CaptureReader::open(input)?;
+    let datalink = reader.linktype().0;
+    for packet in reader {
+        let Some(summary) = PacketSummary::from_link(datalink, &packet.data) else { continue };
+        if FlowKey::from_summary(&summary) != Some(info.key) {
+            continue;
+        }
+        let (Some(src), Some(port)) = (summary.src_ip, summary.src_port) else { continue };
+        let side = if *client.get_or_insert((src, port)) == (src, port) { Side::Client } else { Side::Server };
+        datagrams.push(StreamChunk {
+            side,
+            ts: packet.timestamp(),
+            data: summary.payload(&packet.data).to_vec(),
+        });
+    }
+    let client = client.unwrap_or(info.key.a);
+    let server = if client == info.key.a { info.key.b } else { info.key.a };
+    Ok(UdpConversation { info, client, server, datagrams })
+}
+
+fn endpoint((ip, port): (IpAddr, u16)) -> String {
+    match ip {
+        IpAddr::V4(ip) => format!("{}:{}", ip, port),
+        IpAddr::V6(ip) => format!("[{}]:{}", ip, port),
+    }
+}
+
+/// Renders a conversation in the style of `tshark -z follow`: client data
+/// flush left, server data indented by a tab.
+pub fn format_conversation(
+    title: &str,
+    nodes: ((IpAddr, u16), (IpAddr, u16)),
+    chunks: &[StreamChunk],
+    format: FollowFormat,
+) -> String {
+    let rule = "=".repeat(67);
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", rule);
+    let _ = writeln!(out, "Follow: {}", title);
+    let _ = writeln!(out, "Node 0: {}", endpoint(nodes.0));
+    let _ = writeln!(out, "Node 1: {}", endpoint(nodes.1));
+
+    let mut offsets = [0usize; 2];
+    for chunk in chunks {
+        let indent = if chunk.side == Side::Server { "\t" } else { "" };
+        match format {
+            FollowFormat::Ascii => {
+                for line in ascii(&chunk.data).split_inclusive('\n') {
+                    let _ = write!(out, "{}{}", indent, line);
+                }
+                if !out.ends_with('\n') {
+                    out.push('\n');
+                }
+            }
+            FollowFormat::Hex => {
+                let offset = &mut offsets[chunk.side as usize];
+                out.push_str(&hexdump(&chunk.data, *offset, indent));
+                *offset += chunk.data.len();
+            }
+        }
+    }
+    let _ = writeln!(out, "{}", rule);
+    out
+}
+
+/// Renders datagrams one by one, each introduced by a direction marker
+/// (`>` client to server, `<` server to client), its timestamp and size.
+pub fn format_datagrams(
+    title: &str,
+    nodes: ((IpAddr, u16), (IpAddr, u16)),
+    datagrams: &[StreamChunk],
+    format: FollowFormat,
+) -> String {
+    let rule = "=".repeat(67);
+    let (client, server) = (endpoint(nodes.0), endpoint(nodes.1));
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", rule);
+    let _ = writeln!(out, "Follow: {}", title);
+    let _ = writeln!(out, "Node 0: {}", client);
+    let _ = writeln!(out, "Node 1: {}", server);
+
+    for datagram in datagrams {
+        let (marker, from, to, indent) = match datagram.side {
+            Side::Client => ('>', &client, &server, ""),
+            Side::Server => ('<', &server, &client, "\t"),
+        };
+        let _ = writeln!(
+            out,
+            "{}{} {:.6} {} -> {} ({} bytes)",
+            indent,
+            marker,
+            datagram.ts.as_secs_f64(),
+            from,
+            to,
+            datagram.data.len()
+        );
+        match format {
+            FollowFormat::Ascii => {
+                for line in ascii(&datagram.data).split_inclusive('\n') {
+                    let _ = write!(out, "{}{}", indent, line);
+                }
+                if !out.ends_with('\n') {
+                    out.push('\n');
+                }
+            }
+            FollowFormat::Hex => out.push_str(&hexdump(&datagram.data, 0, indent)),
+        }
+    }
+    let _ = writeln!(out, "{}", rule);
+    out
+}
+
+/// Keeps printable ASCII and line breaks, replacing everything else with `.`
+pub fn ascii(data: &[u8]) -> String {
+    data.iter()
+        .map(|&b| match b {
+            b'\n' | b'\r' | b'\t' | 0x20..=0x7e => b as char,
+            _ => '.',
+        })
+        .collect()
+}
+
+/// Classic 16-bytes-per-line hexdump starting at `offset`
+pub fn hexdump(data: &[u8], offset: usize, indent: &str) -> String {
+    let mut out = String::new();
+    for (i, line) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+        let text: String = line.iter().map(|&b| if (0x20..0x7f).contains(&b) { b as char } else { '.' }).collect();
+        let _ = writeln!(out, "{}{:08X}  {:<48}  {}", indent, offset + i * 16, hex.join(" "), text);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    fn nodes() -> ((IpAddr, u16), (IpAddr, u16)) {
+        ((IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 40000), (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 80))
+    }
+
+    fn chunks() -> Vec<StreamChunk> {
+        vec![
+            StreamChunk { side: Side::Client, ts: Duration::ZERO, data: b"GET / HTTP/1.1\r\n\r\n".to_vec() },
+            StreamChunk { side: Side::Server, ts: Duration::ZERO, data: b"HTTP/1.1 200 OK\r\nX: \x01\r\n".to_vec() },
+        ]
+    }
+
+    #[test]
+    fn formats_ascii_conversation() {
+        let text = format_conversation("tcp,ascii", nodes(), &chunks(), FollowFormat::Ascii);
+        assert!(text.contains("Node 0: 10.0.0.1:40000"));
+        assert!(text.contains("\nGET / HTTP/1.1\r\n"));
+        assert!(text.contains("\n\tHTTP/1.1 200 OK\r\n\tX: .\r\n"));
+    }
+
+    #[test]
+    fn formats_hexdump() {
+        let dump = hexdump(b"0123456789abcdefXY", 16, "\t");
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("\t00000010  30 31 32"));
+        assert!(lines[1].starts_with("\t00000020  58 59"));
+        assert!(lines[1].ends_with("XY"));
+
+        let text = format_conversation("tcp,hex", nodes(), &chunks(), FollowFormat::Hex);
+        assert!(text.contains("\n00000000  47 45 54"));
+        assert!(text.contains("\n\t00000000  48 54 54 50"));
+    }
+
+    #[test]
+    fn formats_datagrams_with_markers() {
+        let datagrams = vec![
+            StreamChunk { side: Side::Client, ts: Duration::from_micros(1_500_000), data: b"ping".to_vec() },
+            StreamChunk { side: Side::Server, ts: Duration::from_micros(1_750_000), data: b"pong".to_vec() },
+            StreamChunk { side: Side::Client, ts: Duration::from_micros(2_000_000), data: b"ping".to_vec() },
+        ];
+        let text = format_datagrams("udp,ascii", nodes(), &datagrams, FollowFormat::Ascii);
+        assert!(text.contains("\n> 1.500000 10.0.0.1:40000 -> 10.0.0.2:80 (4 bytes)\nping\n"));
+        assert!(text.contains("\n\t< 1.750000 10.0.0.2:80 -> 10.0.0.1:40000 (4 bytes)\n\tpong\n"));
+        assert_eq!(text.lines().filter(|l| l.starts_with("> ")).count(), 2);
+
+        let hex = format_datagrams("udp,hex", nodes(), &datagrams, FollowFormat::Hex);
+        assert_eq!(hex.matches("00000000  70 69 6e 67").count(), 2);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::error::CaptureError;
+use crate::follow::tcp_streams;
+use crate::keylog::{decrypt_tls13, KeyLog};
+use crate::protocols::http2::{frames, HeaderDecoder, FRAME_DATA, FRAME_RST_STREAM, PREFACE};
+use crate::reassembly::Side;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+/// Call counters for one gRPC method
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MethodStats {
+    pub calls: u64,
+    /// Calls by `grpc-status` code
+    pub statuses: BTreeMap<u32, u64>,
+    /// Calls reset before a status arrived
+    pub reset: u64,
+    pub request_bytes: u64,
+    pub response_bytes: u64,
+}
+
+impl MethodStats {
+    /// Calls that never got a status or reset (still running, or cut off by the capture)
+    pub fn unfinished(&self) -> u64 {
+        self.calls - self.statuses.values().sum::<u64>() - self.reset
+    }
+}
+
+/// gRPC activity found in a capture
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GrpcReport {
+    /// Keyed by `/package.Service/Method`
+    pub methods: BTreeMap<String, MethodStats>,
+    /// HTTP/2 connections that were readable (cleartext or decrypted)
+    pub connections: u64,
+    /// TLS connections skipped for lack of key log secrets
+    pub encrypted: u64,
+}
+
+/// Canonical name of a gRPC status code
+pub fn status_name(code: u32) -> &'static str {
+    match code {
+        0 => "OK",
+        1 => "CANCELLED",
+        2 => "UNKNOWN",
+        3 => "INVALID_ARGUMENT",
+        4 => "DEADLINE_EXCEEDED",
+        5 => "NOT_FOUND",
+        6 => "ALREADY_EXISTS",
+        7 => "PERMISSION_DENIED",
+        8 => "RESOURCE_EXHAUSTED",
+        9 => "FAILED_PRECONDITION",
+        10 => "ABORTED",
+        11 => "OUT_OF_RANGE",
+        12 => "UNIMPLEMENTED",
+        13 => "INTERNAL",
+        14 => "UNAVAILABLE",
+        15 => "DATA_LOSS",
+        16 => "UNAUTHENTICATED",
+        _ => "INVALID",
+    }
+}
+
+impl GrpcReport {
+    /// Adds one HTTP/2 connection given the cleartext each side sent.
+    /// Returns false when the client data doesn't start with the HTTP/2 preface.
+    pub fn add_connection(&mut self, client: &[u8], server: &[u8]) -> bool {
+        if !client.starts_with(PREFACE) {
+            return false;
+        }
+        self.connections += 1;
+
+        // Requests first, so responses can be matched to their method by stream
+        let mut calls: HashMap<u32, String> = HashMap::new();
+        let mut decoder = HeaderDecoder::new();
+        for frame in frames(client) {
+            if frame.frame_type() == FRAME_DATA {
+                if let Some(stats) = calls.get(&frame.stream_id()).and_then(|m| self.methods.get_mut(m)) {
+                    stats.request_bytes += frame.content().len() as u64;
+                }
+                continue;
+            }
+            let Some(block) = decoder.push(&frame) else { continue };
+            let is_grpc = block.get("content-type").is_some_and(|t| t.starts_with("application/grpc"));
+            if let (true, Some(path)) = (is_grpc, block.get(":path")) {
+                self.methods.entry(path.to_string()).or_default().calls += 1;
+                calls.insert(block.stream_id, path.to_string());
+            }
+        }
+
+        let mut decoder = HeaderDecoder::new();
+        let mut finished: HashSet<u32> = HashSet::new();
+        for frame in frames(server) {
+            let Some(method) = calls.get(&frame.stream_id()) else {
+                // Still keep HPACK state in step
+                decoder.push(&frame);
+                continue;
+            };
+            let stats = self.methods.get_mut(method).expect("method registered with its call");
+            match frame.frame_type() {
+                FRAME_DATA => stats.response_bytes += frame.content().len() as u64,
+                FRAME_RST_STREAM => {
+                    if finished.insert(frame.stream_id()) {
+                        stats.reset += 1;
+                    }
+                }
+                _ => {
+                    let Some(block) = decoder.push(&frame) else { continue };
+                    if let Some(code) = block.get("grpc-status").and_then(|c| c.trim().parse().ok())
+                        && finished.insert(block.stream_id)
+                    {
+                        *stats.statuses.entry(code).or_default() += 1;
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Finds gRPC calls in every HTTP/2 connection of a capture: cleartext
+/// (h2c with prior knowledge) or TLS 1.3 with secrets from `keylog`
+pub fn analyze_grpc(input: &Path, keylog: Option<&KeyLog>) -> Result<GrpcReport, CaptureError> {
+    let mut report = GrpcReport::default();
+    for (_, reassembler) in tcp_streams(input)? {
+        let client = reassembler.stream(Side::Client);
+        let server = reassembler.stream(Side::Server);
+        if client.starts_with(PREFACE) {
+            report.add_connection(&client, &server);
+        } else if client.starts_with(&[0x16, 0x03]) {
+            match keylog.and_then(|k| decrypt_tls13(&client, &server, k)) {
+                Some((client, server)) => {
+                    report.add_connection(&client, &server);
+                }
+                None => report.encrypted += 1,
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::http2::{FLAG_END_HEADERS, FLAG_END_STREAM, FRAME_HEADERS, FRAME_SETTINGS};
+    use crate::testutil::http2_frame;
+
+    fn headers(encoder: &mut hpack::Encoder, stream: u32, flags: u8, fields: &[(&str, &str)]) -> Vec<u8> {
+        let fields = fields.iter().map(|(n, v)| (n.as_bytes().to_vec(), v.as_bytes().to_vec())).collect();
+        http2_frame(FRAME_HEADERS, FLAG_END_HEADERS | flags, stream, &encoder.encode(&fields))
+    }
+
+    fn request(encoder: &mut hpack::Encoder, stream: u32, path: &str) -> Vec<u8> {
+        let mut out = headers(encoder, stream, 0, &[
+            (":method", "POST"),
+            (":path", path),
+            ("content-type", "application/grpc+proto"),
+        ]);
+        out.extend(http2_frame(FRAME_DATA, FLAG_END_STREAM, stream, &[0, 0, 0, 0, 2, 8, 1]));
+        out
+    }
+
+    #[test]
+    fn counts_calls_and_statuses_per_method() {
+        let mut client = PREFACE.to_vec();
+        let mut server = Vec::new();
+        let (mut requests, mut responses) = (hpack::Encoder::new(), hpack::Encoder::new());
+        client.extend(http2_frame(FRAME_SETTINGS, 0, 0, &[]));
+        client.extend(request(&mut requests, 1, "/shop.Cart/Add"));
+        client.extend(request(&mut requests, 3, "/shop.Cart/Add"));
+        client.extend(request(&mut requests, 5, "/shop.Cart/Checkout"));
+        client.extend(headers(&mut requests, 7, FLAG_END_STREAM, &[(":method", "GET"), (":path", "/index.html")]));
+
+        server.extend(http2_frame(FRAME_SETTINGS, 0, 0, &[]));
+        for (stream, status) in [(1, "0"), (3, "14")] {
+            server.extend(headers(&mut responses, stream, 0, &[(":status", "200"), ("content-type", "application/grpc")]));
+            server.extend(http2_frame(FRAME_DATA, 0, stream, &[0, 0, 0, 0, 0]));
+            server.extend(headers(&mut responses, stream, FLAG_END_STREAM, &[("grpc-status", status)]));
+        }
+        // Trailers-only response
+        server.extend(headers(&mut responses, 5, FLAG_END_STREAM, &[(":status", "200"), ("grpc-status", "12")]));
+
+        let mut report = GrpcReport::default();
+        assert!(report.add_connection(&client, &server));
+        assert!(!report.add_connection(b"GET / HTTP/1.1\r\n", b""));
+        assert_eq!(report.connections, 1);
+        assert_eq!(report.methods.len(), 2);
+
+        let add = &report.methods["/shop.Cart/Add"];
+        assert_eq!(add.calls, 2);
+        assert_eq!(add.statuses, BTreeMap::from([(0, 1), (14, 1)]));
+        assert_eq!(add.request_bytes, 14);
+        assert_eq!(add.response_bytes, 10);
+        assert_eq!(add.unfinished(), 0);
+
+        let checkout = &report.methods["/shop.Cart/Checkout"];
+        assert_eq!(checkout.statuses, BTreeMap::from([(12, 1)]));
+        assert_eq!(status_name(12), "UNIMPLEMENTED");
+    }
+}
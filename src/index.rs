@@ -0,0 +1,366 @@
+use crate::capfile::{create_writer, CaptureReader, StoredPacket};
+use crate::error::CaptureError;
+use crate::flow::FlowKey;
+use crate::protocols::dns::DnsMessage;
+use crate::protocols::summary::PacketSummary;
+use crate::protocols::tls::{ClientHello, TlsRecord, CONTENT_HANDSHAKE};
+use ipnet::IpNet;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Bumped whenever the index layout changes so stale sidecars get rebuilt
+const INDEX_VERSION: u32 = 1;
+
+/// Searchable fields of one packet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// 1-based position in the capture file
+    pub number: u64,
+    /// Seconds since the Unix epoch
+    pub ts: f64,
+    pub len: u32,
+    pub src: Option<IpAddr>,
+    pub dst: Option<IpAddr>,
+    pub proto: Option<u8>,
+    pub sport: Option<u16>,
+    pub dport: Option<u16>,
+    /// DNS question name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns: Option<String>,
+    /// TLS server name from a ClientHello
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sni: Option<String>,
+}
+
+impl IndexEntry {
+    pub fn from_packet(number: u64, datalink: i32, packet: &StoredPacket) -> Self {
+        let ts = packet.timestamp().as_secs_f64();
+        let len = packet.header.len;
+        let Some(summary) = PacketSummary::from_link(datalink, &packet.data) else {
+            return IndexEntry { number, ts, len, src: None, dst: None, proto: None, sport: None, dport: None, dns: None, sni: None };
+        };
+        let payload = summary.payload(&packet.data);
+        let dns = if summary.is_udp() && (summary.has_port(53) || summary.has_port(5353)) {
+            DnsMessage::parse(payload).ok().and_then(|message| message.question_name())
+        } else {
+            None
+        };
+        let sni = if summary.is_tcp() { tls_server_name(payload) } else { None };
+        IndexEntry {
+            number,
+            ts,
+            len,
+            src: summary.src_ip,
+            dst: summary.dst_ip,
+            proto: summary.ip_proto,
+            sport: summary.src_port,
+            dport: summary.dst_port,
+            dns,
+            sni,
+        }
+    }
+
+    pub fn flow_key(&self) -> Option<FlowKey> {
+        Some(FlowKey::new(
+            self.proto?,
+            (self.src?, self.sport.unwrap_or(0)),
+            (self.dst?, self.dport.unwrap_or(0)),
+        ))
+    }
+}
+
+/// Packet index stored next to a capture file as `<file>.idx`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CaptureIndex {
+    pub version: u32,
+    /// Size and modification time of the capture when it was indexed
+    pub source_len: u64,
+    pub source_modified: u64,
+    pub entries: Vec<IndexEntry>,
+}
+
+impl CaptureIndex {
+    pub fn sidecar_path(capture: &Path) -> PathBuf {
+        let mut name = capture.as_os_str().to_owned();
+        name.push(".idx");
+        PathBuf::from(name)
+    }
+
+    pub fn from_packets(datalink: i32, packets: impl Iterator<Item = StoredPacket>) -> Self {
+        let entries = packets
+            .enumerate()
+            .map(|(i, packet)| IndexEntry::from_packet(i as u64 + 1, datalink, &packet))
+            .collect();
+        CaptureIndex { version: INDEX_VERSION, source_len: 0, source_modified: 0, entries }
+    }
+
+    /// Reads the whole capture and writes its sidecar index
+    pub fn build(capture: &Path) -> Result<Self, CaptureError> {
+        let reader = CaptureReader::open(capture)?;
+        let mut index = CaptureIndex::from_packets(reader.linktype().0, reader);
+        (index.source_len, index.source_modified) = source_stamp(capture)?;
+        let json = serde_json::to_vec(&index).map_err(|e| CaptureError::Other(e.to_string()))?;
+        let sidecar = CaptureIndex::sidecar_path(capture);
+        fs::write(&sidecar, json)
+            .map_err(|e| CaptureError::Other(format!("Cannot write {}: {}", sidecar.display(), e)))?;
+        info!("Indexed {} packets of {}", index.entries.len(), capture.display());
+        Ok(index)
+    }
+
+    /// Loads the sidecar index, rebuilding it when missing or out of date
+    pub fn load_or_build(capture: &Path) -> Result<Self, CaptureError> {
+        let stamp = source_stamp(capture)?;
+        let sidecar = CaptureIndex::sidecar_path(capture);
+        if let Ok(bytes) = fs::read(&sidecar) {
+            match serde_json::from_slice::<CaptureIndex>(&bytes) {
+                Ok(index) if index.version == INDEX_VERSION && (index.source_len, index.source_modified) == stamp => {
+                    return Ok(index);
+                }
+                Ok(_) => info!("{} is out of date, reindexing", sidecar.display()),
+                Err(e) => warn!("Ignoring unreadable index {}: {}", sidecar.display(), e),
+            }
+        }
+        CaptureIndex::build(capture)
+    }
+}
+
+fn source_stamp(capture: &Path) -> Result<(u64, u64), CaptureError> {
+    let meta = fs::metadata(capture)
+        .map_err(|e| CaptureError::InputError(format!("{}: {}", capture.display(), e)))?;
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs());
+    Ok((meta.len(), modified))
+}
+
+/// Criteria for `search`; every set criterion must match
+#[derive(Debug, Default, Clone)]
+pub struct SearchQuery {
+    /// Source or destination inside this network (a single host is a /32 or /128)
+    pub net: Option<IpNet>,
+    pub port: Option<u16>,
+    pub proto: Option<u8>,
+    /// Case-insensitive substring of the DNS question or TLS server name
+    pub name: Option<String>,
+    pub start: Option<Duration>,
+    pub end: Option<Duration>,
+}
+
+impl SearchQuery {
+    pub fn matches(&self, entry: &IndexEntry) -> bool {
+        let in_net = |net: &IpNet| [entry.src, entry.dst].iter().flatten().any(|ip| net.contains(ip));
+        let has_name = |needle: &String| {
+            let needle = needle.to_lowercase();
+            [&entry.dns, &entry.sni].iter().filter_map(|n| n.as_ref()).any(|n| n.to_lowercase().contains(&needle))
+        };
+        self.net.as_ref().is_none_or(in_net)
+            && self.port.is_none_or(|p| entry.sport == Some(p) || entry.dport == Some(p))
+            && self.proto.is_none_or(|p| entry.proto == Some(p))
+            && self.name.as_ref().is_none_or(has_name)
+            && self.start.is_none_or(|s| entry.ts >= s.as_secs_f64())
+            && self.end.is_none_or(|e| entry.ts < e.as_secs_f64())
+    }
+}
+
+/// Parses `tcp`, `udp`, `icmp`, `icmpv6` or an IP protocol number
+pub fn parse_protocol(text: &str) -> Result<u8, CaptureError> {
+    match text.to_lowercase().as_str() {
+        "icmp" => Ok(1),
+        "tcp" => Ok(6),
+        "udp" => Ok(17),
+        "icmpv6" => Ok(58),
+        other => other
+            .parse()
+            .map_err(|_| CaptureError::InputError(format!("Unknown protocol '{}'", text))),
+    }
+}
+
+/// Expands directories to the capture files they contain
+pub fn capture_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>, CaptureError> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let entries = fs::read_dir(path)
+                .map_err(|e| CaptureError::InputError(format!("{}: {}", path.display(), e)))?;
+            let mut found: Vec<PathBuf> = entries
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| matches!(p.extension().and_then(|e| e.to_str()), Some("pcap" | "pcapng" | "cap")))
+                .collect();
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+/// A packet found by `search`
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub file: PathBuf,
+    pub entry: IndexEntry,
+}
+
+/// Looks up matching packets through the sidecar indexes of `files`
+pub fn search(files: &[PathBuf], query: &SearchQuery) -> Result<Vec<SearchHit>, CaptureError> {
+    let mut hits = Vec::new();
+    for file in files {
+        let index = CaptureIndex::load_or_build(file)?;
+        hits.extend(
+            index
+                .entries
+                .into_iter()
+                .filter(|e| query.matches(e))
+                .map(|entry| SearchHit { file: file.clone(), entry }),
+        );
+    }
+    Ok(hits)
+}
+
+/// Per-conversation totals of a set of hits
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowHit {
+    pub key: FlowKey,
+    pub packets: u64,
+    pub bytes: u64,
+    pub first: f64,
+    pub last: f64,
+}
+
+pub fn group_flows(hits: &[SearchHit]) -> Vec<FlowHit> {
+    let mut flows: BTreeMap<FlowKey, FlowHit> = BTreeMap::new();
+    for hit in hits {
+        let Some(key) = hit.entry.flow_key() else { continue };
+        let flow = flows.entry(key).or_insert(FlowHit {
+            key,
+            packets: 0,
+            bytes: 0,
+            first: hit.entry.ts,
+            last: hit.entry.ts,
+        });
+        flow.packets += 1;
+        flow.bytes += hit.entry.len as u64;
+        flow.first = flow.first.min(hit.entry.ts);
+        flow.last = flow.last.max(hit.entry.ts);
+    }
+    flows.into_values().collect()
+}
+
+/// Copies the packets behind `hits` into a new pcap, reading only the files that matched
+pub fn extract_hits(hits: &[SearchHit], output: &Path) -> Result<u64, CaptureError> {
+    let mut by_file: BTreeMap<&Path, Vec<u64>> = BTreeMap::new();
+    for hit in hits {
+        by_file.entry(&hit.file).or_default().push(hit.entry.number);
+    }
+    let mut writer = None;
+    let mut written = 0;
+    for (file, mut numbers) in by_file {
+        numbers.sort_unstable();
+        let reader = CaptureReader::open(file)?;
+        if writer.is_none() {
+            writer = Some(create_writer(reader.linktype(), output)?);
+        }
+        let last = numbers.iter().copied().max().unwrap_or(0);
+        for (i, packet) in reader.enumerate().take(last as usize) {
+            if numbers.binary_search(&(i as u64 + 1)).is_ok() {
+                if let Some(writer) = &mut writer {
+                    writer.write(&packet.as_packet());
+                }
+                written += 1;
+            }
+        }
+    }
+    if let Some(mut writer) = writer {
+        writer.flush().map_err(|e| CaptureError::PcapError(e.to_string()))?;
+    }
+    Ok(written)
+}
+
+/// server_name extension of a TLS ClientHello at the start of a segment
+pub(crate) fn tls_server_name(payload: &[u8]) -> Option<String> {
+    ClientHello::parse(client_hello_fragment(payload)?)?.server_name
+}
+
+/// Fragment of a handshake record at the start of a segment
+pub(crate) fn client_hello_fragment(payload: &[u8]) -> Option<&[u8]> {
+    TlsRecord::parse(payload).ok().filter(|record| record.content_type() == CONTENT_HANDSHAKE).map(|record| record.fragment())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+    use pcap::PacketHeader;
+
+    fn stored(secs: i64, data: Vec<u8>) -> StoredPacket {
+        let len = data.len() as u32;
+        StoredPacket::new(PacketHeader { ts: libc::timeval { tv_sec: secs, tv_usec: 0 }, caplen: len, len }, data)
+    }
+
+    fn sample_index() -> CaptureIndex {
+        CaptureIndex::from_packets(
+            1,
+            vec![
+                stored(100, testutil::dns_query_frame(1, "Updates.Example.com")),
+                stored(101, testutil::ipv4_tcp_frame(40000, 443, testutil::TCP_ACK, &testutil::tls_client_hello("api.example.org"))),
+                stored(102, testutil::frame().ipv4(testutil::server_v4(), testutil::client_v4()).tcp(443, 40000).build()),
+                stored(103, testutil::arp_request(testutil::client_v4(), testutil::server_v4())),
+            ]
+            .into_iter(),
+        )
+    }
+
+    #[test]
+    fn indexes_names_and_addresses() {
+        let index = sample_index();
+        assert_eq!(index.entries[0].dns.as_deref(), Some("Updates.Example.com"));
+        assert_eq!(index.entries[1].sni.as_deref(), Some("api.example.org"));
+        assert_eq!(index.entries[2].number, 3);
+        assert_eq!(index.entries[3].src, None);
+
+        let json = serde_json::to_string(&index).unwrap();
+        let back: CaptureIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.entries, index.entries);
+    }
+
+    #[test]
+    fn matches_queries() {
+        let index = sample_index();
+        let count = |query: SearchQuery| index.entries.iter().filter(|e| query.matches(e)).count();
+        assert_eq!(count(SearchQuery { name: Some("example".into()), ..Default::default() }), 2);
+        assert_eq!(count(SearchQuery { port: Some(443), proto: Some(6), ..Default::default() }), 2);
+        assert_eq!(count(SearchQuery { net: Some("93.184.216.0/24".parse().unwrap()), ..Default::default() }), 3);
+        assert_eq!(
+            count(SearchQuery {
+                start: Some(Duration::from_secs(101)),
+                end: Some(Duration::from_secs(103)),
+                ..Default::default()
+            }),
+            2
+        );
+    }
+
+    #[test]
+    fn groups_hits_into_flows() {
+        let hits: Vec<SearchHit> = sample_index()
+            .entries
+            .into_iter()
+            .map(|entry| SearchHit { file: PathBuf::from("a.pcap"), entry })
+            .collect();
+        let flows = group_flows(&hits);
+        assert_eq!(flows.len(), 2);
+        let tls = flows.iter().find(|f| f.key.protocol == 6).unwrap();
+        assert_eq!(tls.packets, 2);
+        assert_eq!((tls.first, tls.last), (101.0, 102.0));
+        assert_eq!(parse_protocol("UDP").unwrap(), 17);
+        assert!(parse_protocol("carrier-pigeon").is_err());
+    }
+}
@@ -0,0 +1,276 @@
+use crate::error::CaptureError;
+use log::debug;
+use pcap::{Capture, ConnectionStatus, Device};
+use serde::Serialize;
+use std::io::{BufRead, Write};
+use std::net::IpAddr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Capture device description used by the interface picker and listings
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InterfaceInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub addresses: Vec<IpAddr>,
+    pub up: bool,
+    pub running: bool,
+    pub loopback: bool,
+    pub wireless: bool,
+    pub connected: Option<bool>,
+    /// Datalink types the device can be opened with; `None` when it couldn't be opened
+    pub datalinks: Option<Vec<String>>,
+}
+
+impl From<&Device> for InterfaceInfo {
+    fn from(device: &Device) -> Self {
+        InterfaceInfo {
+            name: device.name.clone(),
+            description: device.desc.clone(),
+            addresses: device.addresses.iter().map(|a| a.addr).collect(),
+            up: device.flags.is_up(),
+            running: device.flags.is_running(),
+            loopback: device.flags.is_loopback(),
+            wireless: device.flags.is_wireless(),
+            connected: match device.flags.connection_status {
+                ConnectionStatus::Connected => Some(true),
+                ConnectionStatus::Disconnected => Some(false),
+                _ => None,
+            },
+            datalinks: None,
+        }
+    }
+}
+
+impl InterfaceInfo {
+    /// Short link status such as `up`, `down` or `no-carrier`
+    pub fn link_status(&self) -> &'static str {
+        match (self.up, self.running, self.connected) {
+            (false, _, _) => "down",
+            (true, _, Some(false)) => "no-carrier",
+            (true, true, _) => "up",
+            (true, false, _) => "no-carrier",
+        }
+    }
+
+    /// Comma-separated flag names (up, running, loopback, wireless)
+    pub fn flags_text(&self) -> String {
+        let flags: Vec<&str> = [
+            (self.up, "up"),
+            (self.running, "running"),
+            (self.loopback, "loopback"),
+            (self.wireless, "wireless"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, name)| *name)
+        .collect();
+        if flags.is_empty() { "-".to_string() } else { flags.join(",") }
+    }
+
+    pub fn addresses_text(&self) -> String {
+        if self.addresses.is_empty() {
+            "-".to_string()
+        } else {
+            self.addresses.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ")
+        }
+    }
+}
+
+pub fn list_interfaces() -> Result<Vec<InterfaceInfo>, CaptureError> {
+    Ok(Device::list()
+        .map_err(|e| CaptureError::PcapError(e.to_string()))?
+        .iter()
+        .map(InterfaceInfo::from)
+        .collect())
+}
+
+/// Opens the device briefly to ask which datalink types it supports
+pub fn supported_datalinks(name: &str) -> Option<Vec<String>> {
+    let cap = Capture::from_device(name)
+        .ok()?
+        .open()
+        .map_err(|e| debug!("Cannot open '{}' to list datalinks: {}", name, e))
+        .ok()?;
+    let links = cap.list_datalinks().ok()?;
+    Some(
+        links
+            .iter()
+            .map(|l| l.get_name().unwrap_or_else(|_| format!("DLT_{}", l.0)))
+            .collect(),
+    )
+}
+
+/// Renders interfaces as an aligned text table
+pub fn format_table(interfaces: &[InterfaceInfo]) -> String {
+    let mut out = format!(
+        "{:<16} {:<10} {:<26} {:<28} {}\n",
+        "NAME", "STATUS", "FLAGS", "DATALINKS", "ADDRESSES"
+    );
+    for iface in interfaces {
+        let datalinks = match &iface.datalinks {
+            Some(links) if !links.is_empty() => links.join(","),
+            _ => "n/a".to_string(),
+        };
+        out.push_str(&format!(
+            "{:<16} {:<10} {:<26} {:<28} {}\n",
+            iface.name,
+            iface.link_status(),
+            iface.flags_text(),
+            datalinks,
+            iface.addresses_text()
+        ));
+    }
+    out
+}
+
+/// Counts packets on every interface in parallel for `window`, returning
+/// packets/sec per interface or `None` where the device couldn't be opened
+/// (typically missing privileges or a down link).
+pub fn measure_packet_rates(names: &[String], window: Duration) -> Vec<Option<f64>> {
+    let handles: Vec<_> = names
+        .iter()
+        .cloned()
+        .map(|name| thread::spawn(move || sample_rate(&name, window)))
+        .collect();
+    handles.into_iter().map(|h| h.join().ok().flatten()).collect()
+}
+
+fn sample_rate(name: &str, window: Duration) -> Option<f64> {
+    let mut cap = Capture::from_device(name)
+        .ok()?
+        .immediate_mode(true)
+        .snaplen(64)
+        .open()
+        .map_err(|e| debug!("Rate preview unavailable for '{}': {}", name, e))
+        .ok()?
+        .setnonblock()
+        .ok()?;
+    let start = Instant::now();
+    let mut packets = 0u64;
+    while start.elapsed() < window {
+        match cap.next_packet() {
+            Ok(_) => packets += 1,
+            Err(_) => thread::sleep(Duration::from_millis(2)),
+        }
+    }
+    Some(packets as f64 / window.as_secs_f64())
+}
+
+/// Prints a numbered menu of interfaces and reads the user's choice.
+/// An empty answer selects the first interface that is up and not loopback.
+pub fn pick_interface<R: BufRead, W: Write>(
+    interfaces: &[InterfaceInfo],
+    rates: &[Option<f64>],
+    input: &mut R,
+    output: &mut W,
+) -> Result<String, CaptureError> {
+    if interfaces.is_empty() {
+        return Err(CaptureError::InterfaceNotFound("no capture devices available".to_string()));
+    }
+    let io_err = |e: std::io::Error| CaptureError::InputError(e.to_string());
+    let default = interfaces
+        .iter()
+        .position(|i| i.link_status() == "up" && !i.loopback)
+        .unwrap_or(0);
+
+    writeln!(output, "Available interfaces:").map_err(io_err)?;
+    for (index, iface) in interfaces.iter().enumerate() {
+        let rate = match rates.get(index).copied().flatten() {
+            Some(rate) => format!("{:.0} pkt/s", rate),
+            None => "n/a".to_string(),
+        };
+        writeln!(
+            output,
+            "  {:>2}) {:<16} {:<10} {:>10}  {}{}",
+            index + 1,
+            iface.name,
+            iface.link_status(),
+            rate,
+            iface.addresses_text(),
+            if iface.loopback { " (loopback)" } else { "" }
+        )
+        .map_err(io_err)?;
+    }
+
+    loop {
+        write!(output, "Select interface [{}]: ", default + 1).map_err(io_err)?;
+        output.flush().map_err(io_err)?;
+        let mut line = String::new();
+        if input.read_line(&mut line).map_err(io_err)? == 0 {
+            return Err(CaptureError::InputError("no interface selected".to_string()));
+        }
+        let answer = line.trim();
+        if answer.is_empty() {
+            return Ok(interfaces[default].name.clone());
+        }
+        if let Some(iface) = interfaces.iter().find(|i| i.name == answer) {
+            return Ok(iface.name.clone());
+        }
+        match answer.parse::<usize>() {
+            Ok(n) if (1..=interfaces.len()).contains(&n) => return Ok(interfaces[n - 1].name.clone()),
+            _ => writeln!(output, "Invalid choice '{}'", answer).map_err(io_err)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn iface(name: &str, up: bool, loopback: bool) -> InterfaceInfo {
+        InterfaceInfo {
+            name: name.to_string(),
+            description: None,
+            addresses: vec!["10.0.0.1".parse().unwrap()],
+            up,
+            running: up,
+            loopback,
+            wireless: false,
+            connected: None,
+            datalinks: None,
+        }
+    }
+
+    #[test]
+    fn picks_by_number_name_or_default() {
+        let list = vec![iface("lo", true, true), iface("eth0", true, false), iface("eth1", false, false)];
+        let rates = vec![Some(12.0), None, None];
+        let mut out = Vec::new();
+
+        let choice = pick_interface(&list, &rates, &mut Cursor::new("3\n"), &mut out).unwrap();
+        assert_eq!(choice, "eth1");
+        let menu = String::from_utf8(out).unwrap();
+        assert!(menu.contains("12 pkt/s"));
+        assert!(menu.contains("down"));
+
+        let choice = pick_interface(&list, &rates, &mut Cursor::new("\n"), &mut Vec::new()).unwrap();
+        assert_eq!(choice, "eth0");
+
+        let choice = pick_interface(&list, &rates, &mut Cursor::new("9\nlo\n"), &mut Vec::new()).unwrap();
+        assert_eq!(choice, "lo");
+    }
+
+    #[test]
+    fn formats_table_and_json() {
+        let mut eth = iface("eth0", true, false);
+        eth.datalinks = Some(vec!["EN10MB".to_string(), "DOCSIS".to_string()]);
+        let table = format_table(&[eth.clone(), iface("lo", true, true)]);
+        assert!(table.contains("EN10MB,DOCSIS"));
+        assert!(table.contains("up,running,loopback"));
+        assert!(table.lines().nth(2).unwrap().contains("n/a"));
+
+        let json = serde_json::to_value(&eth).unwrap();
+        assert_eq!(json["name"], "eth0");
+        assert_eq!(json["addresses"][0], "10.0.0.1");
+        assert_eq!(json["datalinks"][0], "EN10MB");
+    }
+
+    #[test]
+    fn errors_without_input_or_devices() {
+        assert!(pick_interface(&[], &[], &mut Cursor::new(""), &mut Vec::new()).is_err());
+        let list = vec![iface("eth0", true, false)];
+        assert!(pick_interface(&list, &[], &mut Cursor::new(""), &mut Vec::new()).is_err());
+    }
+}
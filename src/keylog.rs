@@ -0,0 +1,306 @@
+use crate::error::CaptureError;
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes128Gcm, Aes256Gcm, KeyInit};
+use hkdf::Hkdf;
+use sha2::{Sha256, Sha384};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// TLS 1.3 cipher suites whose records can be decrypted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherSuite {
+    /// TLS_AES_128_GCM_SHA256 (also protects QUIC Initial packets)
+    Aes128GcmSha256,
+    /// TLS_AES_256_GCM_SHA384
+    Aes256GcmSha384,
+}
+
+impl CipherSuite {
+    pub fn from_id(id: u16) -> Option<Self> {
+        match id {
+            0x1301 => Some(CipherSuite::Aes128GcmSha256),
+            0x1302 => Some(CipherSuite::Aes256GcmSha384),
+            _ => None,
+        }
+    }
+
+    pub fn key_len(&self) -> usize {
+        match self {
+            CipherSuite::Aes128GcmSha256 => 16,
+            CipherSuite::Aes256GcmSha384 => 32,
+        }
+    }
+
+    /// HKDF-Expand-Label (RFC 8446 section 7.1) with an empty context
+    pub fn expand_label(&self, secret: &[u8], label: &str, len: usize) -> Vec<u8> {
+        let full = format!("tls13 {}", label);
+        let mut info = Vec::with_capacity(4 + full.len());
+        info.extend_from_slice(&(len as u16).to_be_bytes());
+        info.push(full.len() as u8);
+        info.extend_from_slice(full.as_bytes());
+        info.push(0);
+        let mut out = vec![0u8; len];
+        let expanded = match self {
+            CipherSuite::Aes128GcmSha256 => Hkdf::<Sha256>::from_prk(secret).map(|h| h.expand(&info, &mut out)),
+            CipherSuite::Aes256GcmSha384 => Hkdf::<Sha384>::from_prk(secret).map(|h| h.expand(&info, &mut out)),
+        };
+        // Secrets shorter than the hash are rejected; the zero key then fails to authenticate anything
+        if !matches!(expanded, Ok(Ok(()))) {
+            out.fill(0);
+        }
+        out
+    }
+}
+
+/// Secrets logged for one TLS 1.3 connection
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TlsSecrets {
+    pub client_handshake: Option<Vec<u8>>,
+    pub server_handshake: Option<Vec<u8>>,
+    pub client_traffic: Option<Vec<u8>>,
+    pub server_traffic: Option<Vec<u8>>,
+}
+
+/// Secrets from an NSS key log file (as written by browsers and curl when
+/// `SSLKEYLOGFILE` is set), keyed by ClientHello random
+#[derive(Debug, Clone, Default)]
+pub struct KeyLog {
+    secrets: HashMap<[u8; 32], TlsSecrets>,
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok()).collect()
+}
+
+impl KeyLog {
+    /// Parses key log lines, ignoring comments and labels other than the
+    /// TLS 1.3 handshake and first application traffic secrets
+    pub fn parse(text: &str) -> Self {
+        let mut keylog = KeyLog::default();
+        for line in text.lines() {
+            let mut parts = line.split_whitespace();
+            let (Some(label), Some(random), Some(secret)) = (parts.next(), parts.next(), parts.next()) else {
+                continue;
+            };
+            let (Some(random), Some(secret)) = (from_hex(random), from_hex(secret)) else { continue };
+            let Ok(random) = <[u8; 32]>::try_from(random) else { continue };
+            let entry = keylog.secrets.entry(random).or_default();
+            match label {
+                "CLIENT_HANDSHAKE_TRAFFIC_SECRET" => entry.client_handshake = Some(secret),
+                "SERVER_HANDSHAKE_TRAFFIC_SECRET" => entry.server_handshake = Some(secret),
+                "CLIENT_TRAFFIC_SECRET_0" => entry.client_traffic = Some(secret),
+                "SERVER_TRAFFIC_SECRET_0" => entry.server_traffic = Some(secret),
+                _ => {}
+            }
+        }
+        keylog.secrets.retain(|_, s| s.client_traffic.is_some() || s.server_traffic.is_some());
+        keylog
+    }
+
+    pub fn load(path: &Path) -> Result<Self, CaptureError> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| CaptureError::InputError(format!("Cannot read key log {}: {}", path.display(), e)))?;
+        Ok(Self::parse(&text))
+    }
+
+    pub fn get(&self, client_random: &[u8; 32]) -> Option<&TlsSecrets> {
+        self.secrets.get(client_random)
+    }
+
+    /// Number of connections with usable secrets
+    pub fn len(&self) -> usize {
+        self.secrets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.secrets.is_empty()
+    }
+}
+
+enum Cipher {
+    Aes128(Box<Aes128Gcm>),
+    Aes256(Box<Aes256Gcm>),
+}
+
+/// Record protection for one direction and one traffic secret
+pub struct RecordKey {
+    cipher: Cipher,
+    iv: [u8; 12],
+    seq: u64,
+}
+
+impl RecordKey {
+    pub fn new(suite: CipherSuite, secret: &[u8]) -> Self {
+        let key = suite.expand_label(secret, "key", suite.key_len());
+        let mut iv = [0u8; 12];
+        iv.copy_from_slice(&suite.expand_label(secret, "iv", 12));
+        let cipher = match suite {
+            CipherSuite::Aes128GcmSha256 => Cipher::Aes128(Box::new(Aes128Gcm::new_from_slice(&key).expect("16-byte key"))),
+            CipherSuite::Aes256GcmSha384 => Cipher::Aes256(Box::new(Aes256Gcm::new_from_slice(&key).expect("32-byte key"))),
+        };
+        RecordKey { cipher, iv, seq: 0 }
+    }
+
+    fn nonce(&self) -> [u8; 12] {
+        let mut nonce = self.iv;
+        for (n, s) in nonce[4..].iter_mut().zip(self.seq.to_be_bytes()) {
+            *n ^= s;
+        }
+        nonce
+    }
+
+    /// Decrypts one record (5-byte header plus body), returning the inner
+    /// content type and content. The sequence number only advances on success.
+    pub fn open(&mut self, header: &[u8], body: &[u8]) -> Option<(u8, Vec<u8>)> {
+        let nonce = self.nonce();
+        let payload = Payload { msg: body, aad: header };
+        let mut plain = match &self.cipher {
+            Cipher::Aes128(c) => c.decrypt(&nonce.into(), payload),
+            Cipher::Aes256(c) => c.decrypt(&nonce.into(), payload),
+        }
+        .ok()?;
+        self.seq += 1;
+        // Strip padding; the last non-zero byte is the real content type
+        let end = plain.iter().rposition(|&b| b != 0)?;
+        let content_type = plain[end];
+        plain.truncate(end);
+        Some((content_type, plain))
+    }
+
+    /// Encrypts `content` as a TLS 1.3 record of inner type `content_type`
+    pub fn seal(&mut self, content_type: u8, content: &[u8]) -> Vec<u8> {
+        let mut inner = content.to_vec();
+        inner.push(content_type);
+        let mut record = vec![23, 0x03, 0x03];
+        record.extend_from_slice(&((inner.len() + 16) as u16).to_be_bytes());
+        let nonce = self.nonce();
+        let payload = Payload { msg: &inner, aad: &record };
+        let sealed = match &self.cipher {
+            Cipher::Aes128(c) => c.encrypt(&nonce.into(), payload),
+            Cipher::Aes256(c) => c.encrypt(&nonce.into(), payload),
+        }
+        .expect("AES-GCM encryption does not fail");
+        self.seq += 1;
+        record.extend_from_slice(&sealed);
+        record
+    }
+}
+
+/// Splits a TLS byte stream into `(content type, header, body)` records,
+/// stopping at a truncated record
+pub fn records(stream: &[u8]) -> Vec<(u8, &[u8], &[u8])> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos + 5 <= stream.len() {
+        let len = u16::from_be_bytes([stream[pos + 3], stream[pos + 4]]) as usize;
+        let Some(body) = stream.get(pos + 5..pos + 5 + len) else { break };
+        out.push((stream[pos], &stream[pos..pos + 5], body));
+        pos += 5 + len;
+    }
+    out
+}
+
+/// Decrypts the application data of both directions of a TLS 1.3
+/// connection, given the reassembled client and server byte streams.
+/// Returns `None` when the hellos are missing, the key log has no entry for
+/// the connection, or the negotiated cipher suite is not supported.
+pub fn decrypt_tls13(client: &[u8], server: &[u8], keylog: &KeyLog) -> Option<(Vec<u8>, Vec<u8>)> {
+    let client_records = records(client);
+    let server_records = records(server);
+
+    // ClientHello: handshake header (4), version (2), random (32)
+    let (_, _, hello) = client_records.iter().find(|(t, _, body)| *t == 22 && body.first() == Some(&1))?;
+    let random: [u8; 32] = hello.get(6..38)?.try_into().ok()?;
+    let secrets = keylog.get(&random)?;
+
+    // ServerHello: handshake header (4), version (2), random (32), session id, cipher suite
+    let (_, _, hello) = server_records.iter().find(|(t, _, body)| *t == 22 && body.first() == Some(&2))?;
+    let session_id_len = *hello.get(38)? as usize;
+    let suite_at = 39 + session_id_len;
+    let suite = CipherSuite::from_id(u16::from_be_bytes([*hello.get(suite_at)?, *hello.get(suite_at + 1)?]))?;
+
+    let decrypt = |records: &[(u8, &[u8], &[u8])], handshake: &Option<Vec<u8>>, traffic: &Option<Vec<u8>>| {
+        let mut handshake = handshake.as_deref().map(|s| RecordKey::new(suite, s));
+        let mut traffic = traffic.as_deref().map(|s| RecordKey::new(suite, s));
+        let mut plain = Vec::new();
+        for (content_type, header, body) in records {
+            if *content_type != 23 {
+                continue;
+            }
+            // Handshake and application records share the outer type, so try both keys
+            if let Some((inner, data)) = traffic.as_mut().and_then(|k| k.open(header, body)) {
+                if inner == 23 {
+                    plain.extend_from_slice(&data);
+                }
+            } else if let Some(key) = handshake.as_mut() {
+                key.open(header, body);
+            }
+        }
+        plain
+    };
+    Some((
+        decrypt(&client_records, &secrets.client_handshake, &secrets.client_traffic),
+        decrypt(&server_records, &secrets.server_handshake, &secrets.server_traffic),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn parses_key_log_lines() {
+        let random = "11".repeat(32);
+        let text = format!(
+            "# comment\nCLIENT_RANDOM {r} {s}\nCLIENT_TRAFFIC_SECRET_0 {r} {s}\nSERVER_TRAFFIC_SECRET_0 {r} {t}\nbogus line\nCLIENT_TRAFFIC_SECRET_0 abcd {s}\n",
+            r = random,
+            s = "22".repeat(32),
+            t = "33".repeat(32)
+        );
+        let keylog = KeyLog::parse(&text);
+        assert_eq!(keylog.len(), 1);
+        let secrets = keylog.get(&[0x11; 32]).unwrap();
+        assert_eq!(secrets.client_traffic, Some(vec![0x22; 32]));
+        assert_eq!(secrets.server_traffic, Some(vec![0x33; 32]));
+        assert_eq!(secrets.client_handshake, None);
+    }
+
+    #[test]
+    fn decrypts_both_directions() {
+        for (suite, id) in [(CipherSuite::Aes128GcmSha256, 0x1301), (CipherSuite::Aes256GcmSha384, 0x1302)] {
+            let hash_len = if id == 0x1301 { 32 } else { 48 };
+            let keys = |byte: u8| vec![byte; hash_len];
+            let mut client = testutil::tls_client_hello("example.com");
+            let random: [u8; 32] = client[11..43].try_into().unwrap();
+            let mut server = testutil::tls_server_hello(id);
+
+            // Server handshake record, then application data in both directions
+            server.extend(RecordKey::new(suite, &keys(2)).seal(22, b"encrypted extensions"));
+            let mut server_traffic = RecordKey::new(suite, &keys(4));
+            server.extend(server_traffic.seal(23, b"hello "));
+            server.extend(server_traffic.seal(23, b"client"));
+            client.extend(RecordKey::new(suite, &keys(3)).seal(23, b"GET /"));
+
+            let mut keylog = KeyLog::default();
+            keylog.secrets.insert(
+                random,
+                TlsSecrets {
+                    client_handshake: Some(keys(1)),
+                    server_handshake: Some(keys(2)),
+                    client_traffic: Some(keys(3)),
+                    server_traffic: Some(keys(4)),
+                },
+            );
+            let (client_plain, server_plain) = decrypt_tls13(&client, &server, &keylog).unwrap();
+            assert_eq!(client_plain, b"GET /");
+            assert_eq!(server_plain, b"hello client");
+
+            assert!(decrypt_tls13(&client, &server, &KeyLog::default()).is_none());
+        }
+    }
+}
@@ -0,0 +1,68 @@
+pub mod error;
+pub mod protocols;  // Protocol parsing
+pub mod ai_analyzer;  // AI analysis
+pub mod alert;  // Alert severities and notification sinks
+pub mod anonymize;  // Capture anonymization
+pub mod ask;  // Chat questions answered by local queries over stored captures
+pub mod attack;  // MITRE ATT&CK technique IDs for alerts
+pub mod backpressure;  // Bounded queues between pipeline stages and their drop policies
+pub mod benchmark;  // Offline throughput measurement
+pub mod builder;  // Packet crafting
+pub mod capfile;  // Capture file tools (merge, split, slice)
+pub mod capture;  // Live capture handle settings
+pub mod chat;  // Slack, Discord and Telegram alert notifications
+pub mod cluster;  // Flow feature vectors and similarity clustering
+pub mod color;  // ANSI colors for the text output
+pub mod config;  // Configuration file
+pub mod control;  // Unix control socket for running captures
+pub mod ek;  // tshark-compatible Elasticsearch output
+pub mod email;  // Alert emails over SMTP, one by one or as digests
+pub mod encrypted_dns;  // DNS over HTTPS/TLS detection
+pub mod exfil;  // Per-host upload volumes to external destinations against daily baselines
+pub mod filetype;  // Magic-byte file type identification in reassembled transfers
+pub mod filter;  // Display filters
+pub mod flow;  // Conversation keys
+pub mod flow_review;  // AI verdicts on finished and flagged flows
+pub mod follow;  // Follow TCP/UDP conversations
+pub mod grpc;  // gRPC call statistics from HTTP/2 connections
+pub mod index;  // Searchable capture file indexes
+pub mod interfaces;  // Capture device discovery
+pub mod keylog;  // TLS 1.3 decryption with key log files
+pub mod localnet;  // Local network knowledge: prefixes, gateways and DNS servers
+pub mod meta;  // Per-packet capture metadata: number, interface, lengths, direction
+pub mod mtu;  // Frame size statistics and path MTU problems
+pub mod netns;  // Linux network namespaces
+pub mod output;  // Packet text: verbosity levels and time formats
+pub mod pipe;  // Live pcap output to named pipes
+pub mod plugin;  // Dissector plugins loaded from shared libraries
+pub mod poisoning;  // LLMNR/mDNS/NetBIOS name poisoning detection
+pub mod presets;  // Named filter presets
+pub mod privileges;  // Capture privilege checks
+pub mod probe;  // Active host verification
+pub mod protocol_spec;  // Dissectors compiled from TOML protocol descriptions
+pub mod radius;  // RADIUS authentication statistics
+pub mod reassembly;  // TCP stream reassembly
+pub mod replay;  // Retransmitting capture files
+pub mod routing;  // Routing protocol monitoring
+pub mod rules;  // Scripted alert rules over packet and flow fields
+pub mod sandbox;  // Seccomp filter for packet parsing
+pub mod scripting;  // Lua hooks run on packets, flows and alerts
+pub mod session;  // Machine-readable end-of-capture reports
+pub mod siem;  // CEF and LEEF event strings for SIEMs
+pub mod sniffer;  // Library capture API: parsed packet and flow event streams
+pub mod state;  // Monitor state checkpoints that survive restarts
+pub mod stats;  // Periodic capture statistics with packet and drop rates
+pub mod stix;  // STIX 2.1 indicator bundles and TAXII upload
+pub mod stream;  // Async packet source for tokio
+pub mod syslog;  // RFC 5424 syslog output for alerts and flow summaries
+pub mod thresholds;  // Rate thresholds over counters and traffic volumes
+pub mod timeline;  // Incident timelines of a host or flow across captures
+pub mod timestamp;  // Date/time parsing and formatting
+pub mod truncation;  // Snaplen truncation statistics
+#[cfg(feature = "wasm-plugins")]
+pub mod wasm_plugin;  // Dissector plugins in a WebAssembly sandbox
+pub mod websocket;  // WebSocket sessions on upgraded HTTP connections
+pub mod wpa;  // WPA handshake export for hashcat
+
+#[cfg(any(test, feature = "testutil"))]
+pub mod testutil;  // Synthetic packets for tests and benchmarks
@@ -0,0 +1,298 @@
+use crate::config::NetworkConfig;
+use crate::error::CaptureError;
+use crate::meta::Direction;
+use crate::protocols::arp::ArpPacket;
+use crate::protocols::ethernet::MacAddress;
+use crate::protocols::summary::PacketSummary;
+use ipnet::{IpNet, Ipv4Net};
+use log::info;
+use std::collections::{btree_map, BTreeMap, HashSet};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+
+const DHCP_SERVER_PORT: u16 = 67;
+const DHCP_CLIENT_PORT: u16 = 68;
+const DHCP_MAGIC_COOKIE: [u8; 4] = [0x63, 0x82, 0x53, 0x63];
+const DHCP_ACK: u8 = 5;
+
+/// What the capture knows about the network it sits on: local prefixes, the
+/// gateways and their hardware addresses, and the DNS servers. Configured
+/// values are kept; the rest is learned from DHCP acknowledgements and ARP.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocalNetwork {
+    prefixes: Vec<IpNet>,
+    /// Gateway addresses with their hardware address once seen in ARP
+    gateways: BTreeMap<IpAddr, Option<MacAddress>>,
+    /// Configured gateway hardware addresses; ARP for a gateway from any other is an alert
+    pinned_macs: Vec<MacAddress>,
+    dns_servers: Vec<IpAddr>,
+    learn: bool,
+    /// Clients already reported for querying an unknown DNS server, with the server
+    resolver_alerts: HashSet<(IpAddr, IpAddr)>,
+}
+
+impl LocalNetwork {
+    /// What is known before any packet: the `[network]` settings
+    pub fn from_config(config: &NetworkConfig) -> Result<Self, CaptureError> {
+        let pinned_macs = config
+            .gateway_macs
+            .iter()
+            .map(|mac| mac.parse().map_err(|_| CaptureError::InputError(format!("Invalid gateway MAC '{}' in config", mac))))
+            .collect::<Result<_, _>>()?;
+        Ok(LocalNetwork {
+            prefixes: config.local_subnets.clone(),
+            gateways: config.gateways.iter().map(|&ip| (ip, None)).collect(),
+            pinned_macs,
+            dns_servers: config.dns_servers.clone(),
+            learn: config.learn,
+            resolver_alerts: HashSet::new(),
+        })
+    }
+
+    /// Only what is given, without learning from traffic
+    pub fn with_prefixes(prefixes: Vec<IpNet>) -> Self {
+        LocalNetwork { prefixes, ..LocalNetwork::default() }
+    }
+
+    pub fn add_prefixes(&mut self, prefixes: impl IntoIterator<Item = IpNet>) {
+        for prefix in prefixes {
+            if !self.prefixes.contains(&prefix) {
+                self.prefixes.push(prefix);
+            }
+        }
+    }
+
+    pub fn prefixes(&self) -> &[IpNet] {
+        &self.prefixes
+    }
+
+    pub fn dns_servers(&self) -> &[IpAddr] {
+        &self.dns_servers
+    }
+
+    pub fn is_local(&self, ip: IpAddr) -> bool {
+        self.prefixes.iter().any(|prefix| prefix.contains(&ip))
+    }
+
+    pub fn is_gateway(&self, ip: IpAddr) -> bool {
+        self.gateways.contains_key(&ip)
+    }
+
+    pub fn is_gateway_mac(&self, mac: MacAddress) -> bool {
+        self.pinned_macs.contains(&mac) || self.gateways.values().any(|known| *known == Some(mac))
+    }
+
+    pub fn is_dns_server(&self, ip: IpAddr) -> bool {
+        self.dns_servers.contains(&ip)
+    }
+
+    /// Compares both ends with the local prefixes; multicast and broadcast
+    /// destinations stay on the link, so they count as local. `Unknown` when
+    /// no prefix is known.
+    pub fn classify(&self, src: IpAddr, dst: IpAddr) -> Direction {
+        if self.prefixes.is_empty() {
+            return Direction::Unknown;
+        }
+        let on_link = dst.is_multicast() || dst == IpAddr::V4(Ipv4Addr::BROADCAST);
+        match (self.is_local(src), on_link || self.is_local(dst)) {
+            (true, true) => Direction::Local,
+            (true, false) => Direction::Outbound,
+            (false, true) => Direction::Inbound,
+            (false, false) => Direction::Transit,
+        }
+    }
+
+    /// Picks up prefixes, gateways and DNS servers from a DHCP acknowledgement
+    /// and gateway hardware addresses from ARP, returning alerts for ARP that
+    /// moves a gateway to another hardware address
+    pub fn learn(&mut self, summary: &PacketSummary, data: &[u8]) -> Vec<String> {
+        if summary.ether_type == 0x0806 {
+            return self.learn_arp(summary.payload(data));
+        }
+        if self.learn && summary.is_udp() && summary.src_port == Some(DHCP_SERVER_PORT) && summary.dst_port == Some(DHCP_CLIENT_PORT) {
+            self.learn_dhcp(summary.payload(data));
+        }
+        Vec::new()
+    }
+
+    fn learn_arp(&mut self, payload: &[u8]) -> Vec<String> {
+        let Ok(arp) = ArpPacket::parse(payload) else { return Vec::new() };
+        let ip = IpAddr::V4(arp.sender_ip());
+        let mac = arp.sender_mac();
+        let Some(known) = self.gateways.get_mut(&ip) else { return Vec::new() };
+        if !self.pinned_macs.is_empty() {
+            return if self.pinned_macs.contains(&mac) {
+                Vec::new()
+            } else {
+                vec![format!("ARP claims gateway {} is at {}, not a configured gateway MAC", ip, mac)]
+            };
+        }
+        match *known {
+            Some(previous) if previous != mac => vec![format!("ARP moves gateway {} from {} to {}", ip, previous, mac)],
+            Some(_) => Vec::new(),
+            None if self.learn => {
+                info!("Learned gateway {} at {}", ip, mac);
+                *known = Some(mac);
+                Vec::new()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    fn learn_dhcp(&mut self, payload: &[u8]) {
+        let Some(lease) = DhcpLease::parse(payload) else { return };
+        if let Some(prefix) = lease.prefix
+            && !self.prefixes.contains(&prefix)
+        {
+            info!("Learned local prefix {} from DHCP", prefix);
+            self.prefixes.push(prefix);
+        }
+        for router in lease.routers {
+            if let btree_map::Entry::Vacant(entry) = self.gateways.entry(router) {
+                info!("Learned gateway {} from DHCP", router);
+                entry.insert(None);
+            }
+        }
+        for server in lease.dns_servers {
+            if !self.dns_servers.contains(&server) {
+                info!("Learned DNS server {} from DHCP", server);
+                self.dns_servers.push(server);
+            }
+        }
+    }
+
+    /// Flags a local host sending DNS queries to a server other than the
+    /// known ones, once per host and server; nothing until a DNS server is known
+    pub fn unexpected_resolver_alert(&mut self, summary: &PacketSummary) -> Option<String> {
+        let (Some(client), Some(server)) = (summary.src_ip, summary.dst_ip) else { return None };
+        let query = summary.dst_port == Some(53) && (summary.is_udp() || summary.is_tcp());
+        if !query || self.dns_servers.is_empty() || self.is_dns_server(server) || !self.is_local(client) || self.is_dns_server(client) {
+            return None;
+        }
+        self.resolver_alerts.insert((client, server)).then(|| format!("{} sends DNS queries to {}, which is not a known DNS server", client, server))
+    }
+}
+
+/// The network as a short paragraph for the AI prompt
+impl fmt::Display for LocalNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |items: Vec<String>| if items.is_empty() { "unknown".to_string() } else { items.join(", ") };
+        let gateways = self.gateways.iter().map(|(ip, mac)| match mac {
+            Some(mac) => format!("{} ({})", ip, mac),
+            None => ip.to_string(),
+        });
+        write!(
+            f,
+            "Local prefixes: {}. Gateways: {}. DNS servers: {}.",
+            list(self.prefixes.iter().map(ToString::to_string).collect()),
+            list(gateways.collect()),
+            list(self.dns_servers.iter().map(ToString::to_string).collect())
+        )
+    }
+}
+
+/// What a DHCP acknowledgement tells about the network
+struct DhcpLease {
+    prefix: Option<IpNet>,
+    routers: Vec<IpAddr>,
+    dns_servers: Vec<IpAddr>,
+}
+
+impl DhcpLease {
+    /// Reads a BOOTREPLY carrying a DHCPACK; other messages give `None`
+    fn parse(payload: &[u8]) -> Option<Self> {
+        if payload.len() < 240 || payload[0] != 2 || payload[236..240] != DHCP_MAGIC_COOKIE {
+            return None;
+        }
+        let address = Ipv4Addr::new(payload[16], payload[17], payload[18], payload[19]);
+        let mut lease = DhcpLease { prefix: None, routers: Vec::new(), dns_servers: Vec::new() };
+        let mut acknowledged = false;
+        let mut options = &payload[240..];
+        while let [code, rest @ ..] = options {
+            match code {
+                0 => {
+                    options = rest;
+                    continue;
+                }
+                255 => break,
+                _ => {}
+            }
+            let [len, rest @ ..] = rest else { break };
+            let Some(value) = rest.get(..*len as usize) else { break };
+            let addresses = || value.chunks_exact(4).map(|ip| IpAddr::V4(Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3])));
+            match code {
+                53 => acknowledged = value == [DHCP_ACK],
+                1 if value.len() == 4 => {
+                    let mask = Ipv4Addr::new(value[0], value[1], value[2], value[3]);
+                    lease.prefix = Ipv4Net::with_netmask(address, mask).ok().map(|net| IpNet::V4(net.trunc()));
+                }
+                3 => lease.routers.extend(addresses()),
+                6 => lease.dns_servers.extend(addresses()),
+                _ => {}
+            }
+            options = &rest[*len as usize..];
+        }
+        acknowledged.then_some(lease)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::PacketBuilder;
+    use crate::protocols::arp::{build_arp, ARP_REPLY};
+
+    fn dhcp_ack() -> Vec<u8> {
+        let mut bootp = vec![0u8; 240];
+        bootp[0] = 2;
+        bootp[16..20].copy_from_slice(&[192, 168, 1, 10]);
+        bootp[236..240].copy_from_slice(&DHCP_MAGIC_COOKIE);
+        bootp.extend([53, 1, DHCP_ACK, 0, 1, 4, 255, 255, 255, 0, 3, 4, 192, 168, 1, 1, 6, 8, 192, 168, 1, 1, 9, 9, 9, 9, 255]);
+        PacketBuilder::ethernet().ipv4(Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 10)).udp(67, 68).payload(&bootp).build()
+    }
+
+    fn arp_from(ip: [u8; 4], mac: u8) -> Vec<u8> {
+        let arp = build_arp(ARP_REPLY, [2, 0, 0, 0, 0, mac], Ipv4Addr::from(ip), [2, 0, 0, 0, 0, 0x10], Ipv4Addr::new(192, 168, 1, 10));
+        PacketBuilder::ethernet().src_mac([2, 0, 0, 0, 0, mac]).ether_type(0x0806).payload(&arp).build()
+    }
+
+    fn learn(network: &mut LocalNetwork, frame: &[u8]) -> Vec<String> {
+        network.learn(&PacketSummary::from_ethernet(frame).unwrap(), frame)
+    }
+
+    #[test]
+    fn learns_from_dhcp_and_arp() {
+        let mut network = LocalNetwork::from_config(&NetworkConfig::default()).unwrap();
+        assert!(learn(&mut network, &dhcp_ack()).is_empty());
+        let ip = |text: &str| text.parse::<IpAddr>().unwrap();
+        assert!(network.is_local(ip("192.168.1.200")) && !network.is_local(ip("192.168.2.1")));
+        assert!(network.is_gateway(ip("192.168.1.1")));
+        assert_eq!(network.dns_servers(), [ip("192.168.1.1"), ip("9.9.9.9")]);
+
+        assert!(learn(&mut network, &arp_from([192, 168, 1, 1], 0xaa)).is_empty());
+        assert!(network.is_gateway_mac(MacAddress::new([2, 0, 0, 0, 0, 0xaa])));
+        assert!(learn(&mut network, &arp_from([192, 168, 1, 7], 0xbb)).is_empty());
+        assert_eq!(learn(&mut network, &arp_from([192, 168, 1, 1], 0xbb)), ["ARP moves gateway 192.168.1.1 from 02:00:00:00:00:aa to 02:00:00:00:00:bb"]);
+        assert_eq!(
+            network.to_string(),
+            "Local prefixes: 192.168.1.0/24. Gateways: 192.168.1.1 (02:00:00:00:00:aa). DNS servers: 192.168.1.1, 9.9.9.9."
+        );
+    }
+
+    #[test]
+    fn flags_queries_to_unknown_resolvers() {
+        let config = NetworkConfig {
+            local_subnets: vec!["192.168.1.0/24".parse().unwrap()],
+            dns_servers: vec!["192.168.1.1".parse().unwrap()],
+            gateways: vec!["192.168.1.1".parse().unwrap()],
+            gateway_macs: vec!["02:00:00:00:00:aa".to_string()],
+            ..NetworkConfig::default()
+        };
+        let mut network = LocalNetwork::from_config(&config).unwrap();
+        let query = PacketSummary::from_ethernet(&crate::testutil::ipv4_udp_frame(5353, 53, b"")).unwrap();
+        assert_eq!(network.unexpected_resolver_alert(&query).unwrap(), "192.168.1.10 sends DNS queries to 93.184.216.34, which is not a known DNS server");
+        assert_eq!(network.unexpected_resolver_alert(&query), None);
+        assert_eq!(learn(&mut network, &arp_from([192, 168, 1, 1], 0xbb)), ["ARP claims gateway 192.168.1.1 is at 02:00:00:00:00:bb, not a configured gateway MAC"]);
+        assert!(LocalNetwork::from_config(&NetworkConfig { gateway_macs: vec!["router".to_string()], ..config }).is_err());
+    }
+}
@@ -1,104 +1,1707 @@
-use error::CaptureError;
-use pcap::{Capture, Device};
-use std::{thread, time::Duration};
-use log::{info, warn, error, debug};
-use std::io::{self, Write};
+use clap::{Parser, ValueEnum};
+use ipnet::IpNet;
+use pcap::{Activated, Capture, Savefile};
+use std::collections::VecDeque;
+use std::sync::{Arc, Barrier};
+use std::{thread, time::{Duration, Instant}};
+use log::{debug, error, info, warn};
+use std::io::{self, IsTerminal, Write};
 use std::env;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
 
-mod error;
-mod protocols;  // New module for protocol parsing
-mod ai_analyzer;  // New module for AI analysis
+mod cli;
 
+use cli::{CaptureMode, Cli, Command, ListFormat, OutputFormat, Timing};
+use rust_sniffer::error::CaptureError;
+use rust_sniffer::protocols::bacnet;
+use rust_sniffer::protocols::iec104;
+use rust_sniffer::protocols::ldap;
+use rust_sniffer::protocols::opcua;
+use rust_sniffer::protocols::profinet;
+use rust_sniffer::protocols::quic::{hex, version_name, QuicPacketType, QuicTracker};
+use rust_sniffer::protocols::registry::dissectors;
+use rust_sniffer::protocols::summary::PacketSummary;
+use rust_sniffer::protocols::tacacs;
+use rust_sniffer::protocols::tunnel::{self, TunnelKind};
+use rust_sniffer::protocols::websocket::opcode_name;
+use rust_sniffer::ai_analyzer::{AIAnalyzer, FilterSuggestion, SecurityAnalysis};
+use rust_sniffer::alert::{Alert, AlertSinks, Severity};
+use rust_sniffer::anonymize::{anonymize_file, parse_key, random_key, Anonymizer, PayloadPolicy};
+use rust_sniffer::ask::TrafficStore;
+use rust_sniffer::attack;
+use rust_sniffer::backpressure::{self, DropWatch, PipelineConfig};
+use rust_sniffer::benchmark::run_benchmark;
+use rust_sniffer::capture::{self, CaptureConfig};
+use rust_sniffer::capfile::{merge_files, parse_size, slice_file, split_file, CaptureReader, Slice, SplitMode, StoredPacket};
+use rust_sniffer::chat::ChatSink;
+use rust_sniffer::cluster::FlowClusters;
+use rust_sniffer::color::Palette;
+use rust_sniffer::config::{Config, ConfigWatcher};
+use rust_sniffer::control::{self, ClientId, ControlRequest, ControlServer, PacketRing};
+use rust_sniffer::ek::ek_record;
+use rust_sniffer::email::EmailSink;
+use rust_sniffer::encrypted_dns::{analyze_encrypted_dns, parse_resolver, EncryptedDnsKind};
+use rust_sniffer::exfil::{megabytes, ExfilMonitor, ExfilPolicy};
+use rust_sniffer::filetype::file_transfers;
+use rust_sniffer::filter::{CaptureFilter, DisplayFilter};
+use rust_sniffer::flow::{FlowKey, FlowRecord, FlowTracker};
+use rust_sniffer::flow_review::{FlowReview, FlowReviewRequest, ReviewedFlow};
+use rust_sniffer::follow::{follow_tcp, follow_udp, format_conversation, format_datagrams, list_streams};
+use rust_sniffer::grpc::{analyze_grpc, status_name};
+use rust_sniffer::index::{capture_files, extract_hits, group_flows, parse_protocol, search, CaptureIndex, SearchQuery};
+use rust_sniffer::interfaces::{format_table, list_interfaces, measure_packet_rates, pick_interface, supported_datalinks};
+use rust_sniffer::keylog::KeyLog;
+use rust_sniffer::localnet::LocalNetwork;
+use rust_sniffer::meta::{Direction, DirectionStats, PacketMeta, Stamper};
+use rust_sniffer::mtu::{FrameSizes, MtuMonitor};
+use rust_sniffer::netns;
+use rust_sniffer::output::{self, TimeFormat, Verbosity};
+use rust_sniffer::pipe::PipeWriter;
+use rust_sniffer::plugin::{self, DissectorRegistry, LoadedPlugin};
+use rust_sniffer::poisoning::PoisoningDetector;
+use rust_sniffer::presets::{all_presets, find_preset};
+use rust_sniffer::privileges;
+use rust_sniffer::probe::{parse_targets, run_probe, ProbeConfig};
+use rust_sniffer::radius::analyze_radius;
+use rust_sniffer::reassembly::Side;
+use rust_sniffer::routing::{GatewayMonitor, OspfMonitor};
+use rust_sniffer::replay::{replay_file, ReplayTiming, RewriteRules};
+use rust_sniffer::protocol_spec::SpecDissector;
+use rust_sniffer::rules::{GeoIp, RuleEngine, RuleSet};
+use rust_sniffer::sandbox;
+use rust_sniffer::scripting::{Script, ScriptHooks, FLOW_IDLE_TIMEOUT};
+use rust_sniffer::session::{CaptureParameters, PcapStats, SessionRecorder};
+use rust_sniffer::state::{SavedState, Snapshot, StateFile};
+use rust_sniffer::stats::StatsReporter;
+use rust_sniffer::stix::StixSink;
+use rust_sniffer::stream::PacketStream;
+use rust_sniffer::syslog::{SyslogConfig, SyslogSink};
+use rust_sniffer::thresholds::{ThresholdEngine, ThresholdSet};
+use rust_sniffer::timeline::{build_timeline, Focus};
+use rust_sniffer::timestamp::parse_timestamp;
+use rust_sniffer::truncation::TruncationStats;
+use rust_sniffer::websocket::websocket_sessions;
+use rust_sniffer::wpa::{is_wifi_datalink, WpaExporter};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::task::JoinSet;
 
 
-use protocols::ethernet::EthernetFrame;
-use protocols::frame_control::FrameControlInfo;
-use ai_analyzer::AIAnalyzer;
 
 
-
-
-//TODO fix the interface name to automatic
 #[tokio::main]
 async fn main() -> Result<(), CaptureError> {
-    let interface_name = "enp4s0"; // Replace with your network interface name
-    env_logger::init();
-    info!("Select the capture mode (1: Basic, 2: AI): ");
-    let mut input = String::new();
-    match io::stdin().read_line(&mut input) {
-        Ok(_) => {
-            let choice: u8 = input.trim().parse().unwrap_or(1);
-            match choice {
-                1 => {
-                    start_capture(interface_name)?;
+    let mut cli = Cli::parse();
+    let write_style = match cli.color {
+        cli::ColorMode::Auto => env_logger::WriteStyle::Auto,
+        cli::ColorMode::Always => env_logger::WriteStyle::Always,
+        cli::ColorMode::Never => env_logger::WriteStyle::Never,
+    };
+    env_logger::Builder::from_default_env().write_style(write_style).init();
+
+    let mut registry = DissectorRegistry::default();
+    for path in &cli.plugin {
+        registry.register(Box::new(LoadedPlugin::load(path)?));
+    }
+    for path in &cli.protocol {
+        registry.register(Box::new(SpecDissector::load(path)?));
+    }
+    #[cfg(feature = "wasm-plugins")]
+    for path in &cli.wasm_plugin {
+        registry.register(Box::new(rust_sniffer::wasm_plugin::WasmDissector::load(path)?));
+    }
+    if !registry.is_empty() {
+        plugin::install(registry);
+    }
+
+    if cli.list_dissectors {
+        for (binding, name) in dissectors().bindings() {
+            println!("{:<24} {}", binding, name);
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = &cli.benchmark {
+        let report = run_benchmark(path)?;
+        println!("{}", report);
+        return Ok(());
+    }
+
+    match cli.command.take() {
+        Some(Command::Chat { paths, local_net }) => return chat(&paths, local_net, cli.config.as_deref()).await,
+        Some(command) => return run_command(command, cli.config.as_deref()),
+        None => {}
+    }
+
+    let config = match &cli.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    if cli.list_presets {
+        for preset in all_presets(&config) {
+            println!("{:<16} {}", preset.name, preset.description);
+            println!("{:<16} bpf: {}", "", preset.bpf.as_deref().unwrap_or("-"));
+            println!("{:<16} display: {}", "", preset.display.as_deref().unwrap_or("-"));
+        }
+        return Ok(());
+    }
+    if let Some(question) = cli.ask_filter.clone() {
+        let suggestion = ask_filter(&question, &cli, &config).await?;
+        cli.filter = suggestion.bpf;
+        cli.display_filter = suggestion.display_filter;
+    }
+    let (filter, options) = capture_settings(&cli, &config)?;
+    if cli.monitor && filter.display.is_some() {
+        warn!("Display filters only understand Ethernet frames and will drop 802.11 traffic in monitor mode");
+    }
+    if let Some(path) = &cli.read_file {
+        return read_capture(path, &filter, &options);
+    }
+    if cli.netns.len() > 1 {
+        let interface = cli.interface.as_deref().unwrap_or("any");
+        return start_namespace_captures(&cli.netns, interface, &filter, &options);
+    }
+    // Before device discovery, so the picker and the capture see the namespace's interfaces
+    if let Some(target) = cli.netns.first() {
+        netns::enter(target)?;
+    }
+    let interface = match cli.interface.clone() {
+        Some(name) => name,
+        None => choose_interface()?,
+    };
+    let interface_name = interface.as_str();
+    let reloader = match &cli.config {
+        Some(_) if cli.sandbox => {
+            warn!("The configuration file is not reloaded under --sandbox, which can't reopen it");
+            None
+        }
+        Some(path) => Some(Reloader { cli: &cli, watcher: ConfigWatcher::new(path) }),
+        None => None,
+    };
+
+    match cli.mode {
+        CaptureMode::Basic => start_capture(interface_name, &filter, &options, reloader)?,
+        CaptureMode::Ai => {
+            let api_key = env::var("DEEPSEEK_API_KEY").map_err(|_| CaptureError::InputError("--mode ai needs DEEPSEEK_API_KEY".to_string()))?;
+            let mut analyzer = AIAnalyzer::new(&api_key);
+            analyzer.configure(&config.ai);
+            start_capture_with_ai(interface_name, analyzer, &filter, &options, reloader).await?;
+        }
+    }
+    Ok(())
+}
+
+
+
+/// Has the AI translate `--ask-filter` into filters, checks that they compile
+/// and asks before they are used
+async fn ask_filter(question: &str, cli: &Cli, config: &Config) -> Result<FilterSuggestion, CaptureError> {
+    let api_key = env::var("DEEPSEEK_API_KEY").map_err(|_| CaptureError::InputError("--ask-filter needs DEEPSEEK_API_KEY".to_string()))?;
+    let mut analyzer = AIAnalyzer::new(&api_key);
+    analyzer.configure(&config.ai);
+    let mut network = LocalNetwork::from_config(&config.network)?;
+    if let Some(interface) = &cli.interface {
+        network.add_prefixes(capture::interface_networks(interface));
+    }
+    println!("Asking the AI for a filter...");
+    let suggestion = analyzer.suggest_filter(question, &network).await.map_err(|e| CaptureError::Other(format!("AI filter suggestion failed: {}", e)))?;
+    println!("{}", suggestion);
+    suggestion.validate()?;
+    print!("Apply these filters? [y/N] ");
+    io::stdout().flush().map_err(|e| CaptureError::InputError(e.to_string()))?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).map_err(|e| CaptureError::InputError(e.to_string()))?;
+    if !matches!(answer.trim(), "y" | "Y" | "yes") {
+        return Err(CaptureError::InputError("Suggested filters not applied".to_string()));
+    }
+    Ok(suggestion)
+}
+
+/// Answers questions typed on the terminal about stored captures until an
+/// empty line or end of input. Each question becomes a query planned by the
+/// AI and run here; the AI only sees the store's overview and the query result.
+async fn chat(paths: &[PathBuf], local_net: Vec<IpNet>, config: Option<&Path>) -> Result<(), CaptureError> {
+    let config = match config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    let api_key = env::var("DEEPSEEK_API_KEY").map_err(|_| CaptureError::InputError("chat needs DEEPSEEK_API_KEY".to_string()))?;
+    let mut analyzer = AIAnalyzer::new(&api_key);
+    analyzer.configure(&config.ai);
+    let mut network = LocalNetwork::from_config(&config.network)?;
+    network.add_prefixes(local_net);
+    let store = TrafficStore::load(&capture_files(paths)?, network)?;
+    if store.is_empty() {
+        return Err(CaptureError::InputError("No packets in the given captures".to_string()));
+    }
+    println!("Loaded {}. Ask a question, or press Enter to quit.", store);
+    let overview = store.to_string();
+    loop {
+        print!("> ");
+        io::stdout().flush().map_err(|e| CaptureError::InputError(e.to_string()))?;
+        let mut question = String::new();
+        if io::stdin().read_line(&mut question).map_err(|e| CaptureError::InputError(e.to_string()))? == 0 || question.trim().is_empty() {
+            return Ok(());
+        }
+        let question = question.trim();
+        let query = match analyzer.plan_query(question, &overview).await {
+            Ok(query) => query,
+            Err(e) => {
+                eprintln!("Could not turn the question into a query: {}", e);
+                continue;
+            }
+        };
+        let result = match store.run(&query) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+        info!("Query {:?} matched {} packets", query, result.packets);
+        match analyzer.summarize_query(question, &query, &result).await {
+            Ok(answer) => println!("{}", answer),
+            Err(e) => {
+                // The local result still answers the question
+                eprintln!("Could not summarize the answer: {}", e);
+                for row in &result.rows {
+                    println!("  {:<40} {}", row.key, row.value);
                 }
-                2 => {
-                    let api_key = env::var("DEEPSEEK_API_KEY").expect("DEEPSEEK_API_KEY enviroment variable not set"); //
-                    let analyzer = AIAnalyzer::new(&api_key);
-                    start_capture_with_ai(interface_name, analyzer).await?;
+            }
+        }
+    }
+}
+
+type AlertEngines = (Option<Arc<RuleSet>>, Option<Arc<ThresholdSet>>);
+
+/// Rule and threshold sets from the configuration file, when it has any
+fn alert_engines(config: &Config) -> Result<AlertEngines, CaptureError> {
+    let rules = match &config.alerts.rules {
+        rules if rules.is_empty() => None,
+        rules => {
+            let geoip = config.alerts.geoip.as_deref().map(GeoIp::open).transpose()?;
+            Some(Arc::new(RuleSet::compile(rules, geoip)?))
+        }
+    };
+    let thresholds = match &config.alerts.thresholds {
+        thresholds if thresholds.is_empty() => None,
+        thresholds => Some(Arc::new(ThresholdSet::compile(thresholds)?)),
+    };
+    Ok((rules, thresholds))
+}
+
+/// Builds the filters and capture options from the command line, falling back
+/// to the configuration file for anything not given as a flag
+fn capture_settings(cli: &Cli, config: &Config) -> Result<(CaptureFilter, CaptureOptions), CaptureError> {
+    let capture = &config.capture;
+    let preset = cli.preset.as_deref().or(capture.preset.as_deref()).map(|name| find_preset(name, config)).transpose()?;
+    let filter = CaptureFilter::new(
+        cli.filter.as_deref().or(capture.filter.as_deref()),
+        cli.display_filter.as_deref().or(capture.display_filter.as_deref()),
+        preset.as_ref(),
+    )?;
+    let output = match (cli.output, &capture.output) {
+        (Some(output), _) => output,
+        (None, Some(name)) => OutputFormat::from_str(name, true)
+            .map_err(|_| CaptureError::InputError(format!("Unknown output format '{}' in config", name)))?,
+        (None, None) => OutputFormat::Text,
+    };
+    let time_format = match (cli.time_format, &capture.time_format) {
+        (Some(format), _) => format.into(),
+        (None, Some(name)) => cli::TimeFormat::from_str(name, true)
+            .map_err(|_| CaptureError::InputError(format!("Unknown time format '{}' in config", name)))?
+            .into(),
+        (None, None) => TimeFormat::default(),
+    };
+    let allowed_tunnels = if cli.allow_tunnel.is_empty() {
+        config
+            .alerts
+            .allow_tunnel
+            .iter()
+            .map(|name| cli::TunnelKind::from_str(name, true).map(Into::into))
+            .collect::<Result<_, _>>()
+            .map_err(|e| CaptureError::InputError(format!("Invalid allow_tunnel in config: {}", e)))?
+    } else {
+        cli.allow_tunnel.iter().map(|&kind| kind.into()).collect()
+    };
+    let ospf_routers = if cli.ospf_router.is_empty() { config.alerts.ospf_routers.clone() } else { cli.ospf_router.clone() };
+    let (rules, thresholds) = alert_engines(config)?;
+    let exfil = config.alerts.exfil.as_ref().map(|exfil| ExfilPolicy::compile(exfil, config.alerts.geoip.as_deref())).transpose()?.map(Arc::new);
+    let mut network = config.network.clone();
+    if !cli.local_net.is_empty() {
+        network.local_subnets = cli.local_net.clone();
+    }
+    let mut sinks = AlertSinks::default();
+    let syslog = cli.syslog.as_deref().map(SyslogConfig::new).or_else(|| config.alerts.syslog.clone());
+    if let Some(syslog) = syslog {
+        sinks.add(Box::new(SyslogSink::connect(&syslog)?));
+    }
+    if let Some(email) = &config.alerts.email {
+        sinks.add(Box::new(EmailSink::start(email, config.pipeline.exporters)?));
+    }
+    for chat in &config.alerts.chat {
+        sinks.add(Box::new(ChatSink::start(chat, config.pipeline.exporters)?));
+    }
+    if let Some(stix) = &config.alerts.stix {
+        sinks.add(Box::new(StixSink::new(stix)?));
+    }
+    let options = CaptureOptions {
+        palette: Palette::new(cli.color.into(), io::stdout().is_terminal()),
+        verbosity: Verbosity::from_flags(cli.quiet, cli.verbose),
+        time_format,
+        monitor: cli.monitor,
+        promisc: !cli.no_promisc,
+        direction: cli.direction.map(Into::into),
+        snaplen: cli.snaplen.unwrap_or(capture::DEFAULT_SNAPLEN),
+        precision: cli.tstamp_precision.into(),
+        tstamp_type: cli.tstamp_type.map(Into::into),
+        stats_interval: Duration::from_secs(cli.stats_interval),
+        cluster_flows: cli.cluster_flows,
+        write_file: cli.write_file.clone(),
+        pipe: cli.pipe.clone(),
+        wpa_export: cli.wpa_export.clone(),
+        report: cli.report.clone(),
+        state: cli.state.clone(),
+        control: cli.control.clone(),
+        ring_buffer: cli.ring_buffer,
+        checkpoint_interval: Duration::from_secs(cli.checkpoint_interval),
+        allowed_tunnels,
+        ospf_routers,
+        rules,
+        thresholds,
+        exfil,
+        sinks: Arc::new(sinks),
+        output,
+        user: cli.user.clone(),
+        group: cli.group.clone(),
+        sandbox: cli.sandbox,
+        script: cli.script.as_deref().map(Script::read).transpose()?,
+        pipeline: config.pipeline,
+        network: LocalNetwork::from_config(&network)?,
+    };
+    Ok((filter, options))
+}
+
+/// Re-reads the configuration file during a live capture (`--config`) and
+/// rebuilds the settings that can change without reopening the interface
+pub struct Reloader<'a> {
+    cli: &'a Cli,
+    watcher: ConfigWatcher,
+}
+
+impl Reloader<'_> {
+    /// New settings when the file changed or SIGHUP arrived; a broken file keeps the current ones
+    fn poll(&mut self) -> Option<(Config, CaptureFilter, CaptureOptions)> {
+        let reloaded = self
+            .watcher
+            .poll()?
+            .and_then(|config| capture_settings(self.cli, &config).map(|(filter, options)| (config, filter, options)));
+        match reloaded {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                warn!("Keeping the current configuration: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Applies reloaded filters, output format and alert settings to a running
+/// capture; monitors keep what they have learned so far
+fn apply_reload(
+    cap: &mut Capture<pcap::Active>,
+    filter: &mut CaptureFilter,
+    printer: &mut PacketPrinter,
+    monitors: &mut Monitors<'_>,
+    reloaded: (CaptureFilter, CaptureOptions),
+) {
+    let (new_filter, new_options) = reloaded;
+    if new_filter.bpf != filter.bpf {
+        // An empty program matches everything
+        if let Err(e) = cap.filter(new_filter.bpf.as_deref().unwrap_or(""), true) {
+            warn!("Keeping the current configuration: invalid BPF: {}", e);
+            return;
+        }
+        info!("Capture filter: {}", new_filter.bpf.as_deref().unwrap_or("none"));
+    }
+    *filter = new_filter;
+    *printer = new_options.printer();
+    monitors.reconfigure(&new_options);
+    info!("Configuration reloaded");
+}
+
+/// Shows the interactive interface picker, or fails when there is no terminal to ask on
+fn choose_interface() -> Result<String, CaptureError> {
+    if !io::stdin().is_terminal() {
+        return Err(CaptureError::InterfaceNotFound(
+            "no interface given; pass --interface <name>".to_string(),
+        ));
+    }
+    let interfaces = list_interfaces()?;
+    println!("Measuring traffic on {} interfaces...", interfaces.len());
+    let names: Vec<String> = interfaces.iter().map(|i| i.name.clone()).collect();
+    let rates = measure_packet_rates(&names, Duration::from_secs(1));
+    pick_interface(&interfaces, &rates, &mut io::stdin().lock(), &mut io::stdout())
+}
+
+fn run_command(command: Command, config: Option<&Path>) -> Result<(), CaptureError> {
+    match command {
+        Command::Replay { file, interface, timing, pps, src_mac, dst_mac, rewrite_ip } => {
+            let timing = match timing {
+                Timing::Original => ReplayTiming::Original,
+                Timing::Fixed => ReplayTiming::FixedRate(pps),
+                Timing::Max => ReplayTiming::MaxSpeed,
+            };
+            let mut rules = RewriteRules::default();
+            let parse_mac = |mac: String| {
+                mac.parse().map_err(|e| CaptureError::InputError(format!("{}: {}", mac, e)))
+            };
+            rules.src_mac = src_mac.map(parse_mac).transpose()?;
+            rules.dst_mac = dst_mac.map(parse_mac).transpose()?;
+            for spec in &rewrite_ip {
+                rules.add_ip_mapping(spec)?;
+            }
+            let report = replay_file(&file, &interface, timing, &rules)?;
+            println!(
+                "Sent {} packets ({} bytes) in {:.3}s, {} failed",
+                report.sent,
+                report.bytes,
+                report.elapsed.as_secs_f64(),
+                report.failed
+            );
+        }
+        Command::Interfaces { format } => {
+            let mut interfaces = list_interfaces()?;
+            for iface in &mut interfaces {
+                iface.datalinks = supported_datalinks(&iface.name);
+            }
+            match format {
+                ListFormat::Table => print!("{}", format_table(&interfaces)),
+                ListFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&interfaces).map_err(|e| CaptureError::Other(e.to_string()))?
+                ),
+            }
+        }
+        Command::Merge { inputs, output } => {
+            let count = merge_files(&inputs, &output)?;
+            println!("Wrote {} packets to {}", count, output.display());
+        }
+        Command::Split { input, prefix, packets, size, per_flow } => {
+            let mode = match (packets, size) {
+                (Some(packets), _) => SplitMode::Packets(packets),
+                (_, Some(size)) => SplitMode::Bytes(parse_size(&size)?),
+                _ if per_flow => SplitMode::Flow,
+                _ => unreachable!("clap requires one split mode"),
+            };
+            let prefix = prefix.unwrap_or_else(|| input.with_extension("").display().to_string());
+            let files = split_file(&input, &prefix, mode)?;
+            for file in &files {
+                println!("{}", file.display());
+            }
+            println!("Wrote {} files", files.len());
+        }
+        Command::Slice { input, output, start, end, filter, display_filter } => {
+            let slice = Slice {
+                start: start.as_deref().map(parse_timestamp).transpose()?,
+                end: end.as_deref().map(parse_timestamp).transpose()?,
+                display: display_filter.as_deref().map(DisplayFilter::parse).transpose()?,
+            };
+            let kept = slice_file(&input, &output, &slice, filter.as_deref())?;
+            println!("Wrote {} packets to {}", kept, output.display());
+        }
+        Command::Anonymize { input, output, key, zero_payload, truncate_payload } => {
+            let key = match key {
+                Some(hex) => parse_key(&hex)?,
+                None => {
+                    let key = random_key()?;
+                    let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+                    eprintln!("Generated key {} (pass it with --key to anonymize other captures consistently)", hex);
+                    key
                 }
-                _ => {
-                    println!("Invalid choice. Defaulting to basic capture.");
-                    start_capture(interface_name)?;
+            };
+            let payload = match (zero_payload, truncate_payload) {
+                (true, _) => PayloadPolicy::Zero,
+                (_, Some(bytes)) => PayloadPolicy::Truncate(bytes),
+                _ => PayloadPolicy::Keep,
+            };
+            let count = anonymize_file(&input, &output, &mut Anonymizer::new(&key, payload))?;
+            println!("Wrote {} anonymized packets to {}", count, output.display());
+        }
+        Command::Index { paths } => {
+            for file in capture_files(&paths)? {
+                let index = CaptureIndex::build(&file)?;
+                println!("{}: {} packets", file.display(), index.entries.len());
+            }
+        }
+        Command::Search { paths, ip, port, proto, name, start, end, flows, output } => {
+            let net = ip
+                .map(|ip| {
+                    ip.parse::<ipnet::IpNet>()
+                        .or_else(|_| ip.parse::<std::net::IpAddr>().map(ipnet::IpNet::from))
+                        .map_err(|_| CaptureError::InputError(format!("Invalid address or network '{}'", ip)))
+                })
+                .transpose()?;
+            let query = SearchQuery {
+                net,
+                port,
+                proto: proto.as_deref().map(parse_protocol).transpose()?,
+                name,
+                start: start.as_deref().map(parse_timestamp).transpose()?,
+                end: end.as_deref().map(parse_timestamp).transpose()?,
+            };
+            let hits = search(&capture_files(&paths)?, &query)?;
+            if flows {
+                println!("{:<60} {:>8} {:>10} {:>17} {:>17}", "FLOW", "PACKETS", "BYTES", "FIRST", "LAST");
+                for flow in group_flows(&hits) {
+                    println!(
+                        "{:<60} {:>8} {:>10} {:>17.6} {:>17.6}",
+                        flow.key.to_string(), flow.packets, flow.bytes, flow.first, flow.last
+                    );
+                }
+            } else {
+                for hit in &hits {
+                    let entry = &hit.entry;
+                    let endpoint = |ip: Option<std::net::IpAddr>, port: Option<u16>| match (ip, port) {
+                        (Some(ip), Some(port)) => format!("{}:{}", ip, port),
+                        (Some(ip), None) => ip.to_string(),
+                        _ => "-".to_string(),
+                    };
+                    println!(
+                        "{}#{} {:.6} {} -> {} {}",
+                        hit.file.display(),
+                        entry.number,
+                        entry.ts,
+                        endpoint(entry.src, entry.sport),
+                        endpoint(entry.dst, entry.dport),
+                        entry.dns.as_deref().or(entry.sni.as_deref()).unwrap_or("")
+                    );
                 }
             }
+            println!("{} matching packets", hits.len());
+            if let Some(output) = output {
+                let written = extract_hits(&hits, &output)?;
+                println!("Wrote {} packets to {}", written, output.display());
+            }
+        }
+        Command::Timeline { paths, focus, format } => {
+            let config = match config {
+                Some(path) => Config::load(path)?,
+                None => Config::default(),
+            };
+            let (rules, thresholds) = alert_engines(&config)?;
+            let timeline = build_timeline(&capture_files(&paths)?, Focus::parse(&focus)?, rules, thresholds)?;
+            match format {
+                ListFormat::Table => print!("{}", timeline.to_text()),
+                ListFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&timeline).map_err(|e| CaptureError::Other(e.to_string()))?
+                ),
+            }
         }
-        Err(e) => {
-            eprintln!("Error reading input: {}", e);
-            return Err(CaptureError::InputError(e.to_string()));
+        Command::Follow { input, udp, stream, list, format } => {
+            let protocol = if udp { 17 } else { 6 };
+            if list {
+                println!("{:>6}  {:<60} {:>8} {:>10}", "STREAM", "FLOW", "PACKETS", "BYTES");
+                for info in list_streams(&input, protocol)? {
+                    println!("{:>6}  {:<60} {:>8} {:>10}", info.index, info.key.to_string(), info.packets, info.bytes);
+                }
+                return Ok(());
+            }
+            let format: rust_sniffer::follow::FollowFormat = format.into();
+            let stream = stream.unwrap_or_default();
+            if udp {
+                let conversation = follow_udp(&input, stream)?;
+                let title = format!("udp,{}", format.name());
+                let nodes = (conversation.client, conversation.server);
+                print!("{}", format_datagrams(&title, nodes, &conversation.datagrams, format));
+                return Ok(());
+            }
+            let (info, reassembler) = follow_tcp(&input, stream)?;
+            let client = reassembler.client().unwrap_or(info.key.a);
+            let server = if client == info.key.a { info.key.b } else { info.key.a };
+            let title = format!("tcp,{}", format.name());
+            print!("{}", format_conversation(&title, (client, server), reassembler.chunks(), format));
+            let (client_missing, server_missing) = reassembler.missing_bytes();
+            if client_missing + server_missing > 0 {
+                warn!(
+                    "Stream has gaps: {} bytes missing from client, {} from server",
+                    client_missing, server_missing
+                );
+            }
+        }
+        Command::Quic { input } => {
+            let mut tracker = QuicTracker::new();
+            let reader = CaptureReader::open(&input)?;
+            let datalink = reader.linktype().0;
+            for packet in reader {
+                let Some(summary) = PacketSummary::from_link(datalink, &packet.data) else { continue };
+                if let (true, Some(src), Some(dst), Some(sport), Some(dport)) =
+                    (summary.is_udp(), summary.src_ip, summary.dst_ip, summary.src_port, summary.dst_port)
+                {
+                    tracker.push((src, sport), (dst, dport), summary.payload(&packet.data));
+                }
+            }
+            for flow in tracker.flows() {
+                let hello = flow.hello.clone().unwrap_or_default();
+                println!(
+                    "{}  {}  {}  sni={} alpn={}",
+                    flow.key,
+                    flow.label(),
+                    flow.version.map_or("-".to_string(), version_name),
+                    hello.server_name.as_deref().unwrap_or("-"),
+                    if hello.alpn.is_empty() { "-".to_string() } else { hello.alpn.join(",") }
+                );
+                let counts: Vec<String> = flow.packets.iter().map(|(t, n)| format!("{} {}", t, n)).collect();
+                println!("    packets: {}", counts.join(", "));
+                println!(
+                    "    client cid {} server cid {} ({} distinct DCIDs)",
+                    flow.client_cid.as_deref().map_or("-".to_string(), hex),
+                    flow.server_cid.as_deref().map_or("-".to_string(), hex),
+                    flow.connection_ids.len()
+                );
+                if flow.packets.contains_key(&QuicPacketType::OneRtt) {
+                    println!(
+                        "    1-RTT bytes: {} client->server, {} server->client; {} spin bit flips",
+                        flow.client_bytes, flow.server_bytes, flow.spin_flips
+                    );
+                }
+            }
+            println!("{} QUIC connections", tracker.flows().len());
         }
+        Command::Grpc { input, keylog } => {
+            let keylog = keylog.as_deref().map(KeyLog::load).transpose()?;
+            let report = analyze_grpc(&input, keylog.as_ref())?;
+            println!("{:<50} {:>6} {:>10} {:>10}  STATUS", "METHOD", "CALLS", "REQ BYTES", "RESP BYTES");
+            for (method, stats) in &report.methods {
+                let mut statuses: Vec<String> =
+                    stats.statuses.iter().map(|(code, n)| format!("{} {}", status_name(*code), n)).collect();
+                if stats.reset > 0 {
+                    statuses.push(format!("reset {}", stats.reset));
+                }
+                if stats.unfinished() > 0 {
+                    statuses.push(format!("unfinished {}", stats.unfinished()));
+                }
+                println!(
+                    "{:<50} {:>6} {:>10} {:>10}  {}",
+                    method, stats.calls, stats.request_bytes, stats.response_bytes, statuses.join(", ")
+                );
+            }
+            println!("{} HTTP/2 connections analyzed", report.connections);
+            if report.encrypted > 0 {
+                warn!("{} TLS connections could not be decrypted; pass --keylog with their secrets", report.encrypted);
+            }
+        }
+        Command::EncryptedDns { input, resolver, keylog } => {
+            let resolvers = resolver.iter().map(|r| parse_resolver(r)).collect::<Result<Vec<_>, _>>()?;
+            let keylog = keylog.as_deref().map(KeyLog::load).transpose()?;
+            let report = analyze_encrypted_dns(&input, keylog.as_ref())?;
+            for flow in &report.flows {
+                println!(
+                    "Stream {}: {}  {} -> {}:{}  sni={}  ({}{})",
+                    flow.stream.index,
+                    flow.kind,
+                    flow.client.0,
+                    flow.server.0,
+                    flow.server.1,
+                    flow.server_name.as_deref().unwrap_or("-"),
+                    flow.evidence,
+                    flow.queries.map(|q| format!(", {} queries", q)).unwrap_or_default()
+                );
+            }
+            println!("{} encrypted DNS connections", report.flows.len());
+            let usage = report.usage();
+            if !usage.is_empty() {
+                println!("{:<40} {:>5} {:>5} {:>5}", "CLIENT", "DoH", "DoT", "DoQ");
+                for (client, kinds) in &usage {
+                    let count = |kind| kinds.get(&kind).copied().unwrap_or(0);
+                    println!(
+                        "{:<40} {:>5} {:>5} {:>5}",
+                        client,
+                        count(EncryptedDnsKind::DoH),
+                        count(EncryptedDnsKind::DoT),
+                        count(EncryptedDnsKind::DoQ)
+                    );
+                }
+            }
+            let hosts = report.bypassing(&resolvers);
+            if !hosts.is_empty() {
+                println!("Hosts bypassing the organization's resolvers:");
+                for (host, flows) in &hosts {
+                    let mut servers: Vec<String> = flows.iter().map(|f| format!("{} {}", f.kind, f.server.0)).collect();
+                    servers.dedup();
+                    println!("    {}  {}", host, servers.join(", "));
+                }
+            }
+        }
+        Command::Radius { input, top_users } => {
+            let stats = analyze_radius(&input)?;
+            println!("{:<16} {:<20} {:>8} {:>8} {:>8} {:>10} {:>8}", "NAS", "IDENTIFIER", "REQUESTS", "ACCEPTS", "REJECTS", "CHALLENGES", "FAILURE");
+            for (nas, nas_stats) in &stats.nas {
+                println!(
+                    "{:<16} {:<20} {:>8} {:>8} {:>8} {:>10} {:>8}",
+                    nas,
+                    nas_stats.identifier.as_deref().unwrap_or("-"),
+                    nas_stats.requests,
+                    nas_stats.accepts,
+                    nas_stats.rejects,
+                    nas_stats.challenges,
+                    nas_stats.failure_rate().map(|r| format!("{:.1}%", r * 100.0)).unwrap_or_else(|| "-".to_string())
+                );
+                let mut users: Vec<_> = nas_stats.failed_users.iter().collect();
+                users.sort_by(|a, b| b.1.cmp(a.1));
+                for (user, count) in users.into_iter().take(top_users) {
+                    println!("    {:<30} {} rejected", user, count);
+                }
+            }
+            if stats.unanswered() > 0 {
+                println!("{} requests without an answer", stats.unanswered());
+            }
+        }
+        Command::Websocket { input, preview } => {
+            let sessions = websocket_sessions(&input)?;
+            for session in &sessions {
+                let handshake = &session.handshake;
+                println!(
+                    "Stream {}: {}  GET {}{}{}{}",
+                    session.stream.index,
+                    session.stream.key,
+                    handshake.host.as_deref().unwrap_or(""),
+                    handshake.path,
+                    handshake.protocol.as_deref().map(|p| format!("  protocol={}", p)).unwrap_or_default(),
+                    handshake.extensions.as_deref().map(|e| format!("  extensions={}", e)).unwrap_or_default()
+                );
+                for entry in &session.messages {
+                    let marker = if entry.side == Side::Client { '>' } else { '<' };
+                    let message = &entry.message;
+                    let fragments = if message.fragments > 1 { format!(" in {} fragments", message.fragments) } else { String::new() };
+                    let text = message.text_preview(preview).map(|t| format!(": {}", t)).unwrap_or_default();
+                    println!(
+                        "    {:.6} {} {} {} bytes{}{}",
+                        entry.ts.as_secs_f64(),
+                        marker,
+                        opcode_name(message.opcode),
+                        message.data.len(),
+                        fragments,
+                        text
+                    );
+                }
+            }
+            println!("{} WebSocket sessions", sessions.len());
+        }
+        Command::Files { input, alert_http_executables } => {
+            let flows = file_transfers(&input)?;
+            for flow in &flows {
+                println!("Stream {}: {}  {}", flow.stream.index, flow.stream.key, flow.labels().join(", "));
+                for file in &flow.files {
+                    let marker = if file.side == Side::Client { '>' } else { '<' };
+                    let url = file.url.as_deref().map(|u| format!("  {}", u)).unwrap_or_default();
+                    println!("    {} {} at offset {}{}", marker, file.file_type, file.offset, url);
+                }
+                if alert_http_executables {
+                    for file in flow.http_executables() {
+                        warn!(
+                            "{} executable downloaded over plain HTTP by {}: {}",
+                            file.file_type,
+                            flow.client.map_or_else(|| flow.stream.key.to_string(), |(ip, _)| ip.to_string()),
+                            file.url.as_deref().unwrap_or_default()
+                        );
+                    }
+                }
+            }
+            println!("{} streams carrying files", flows.len());
+        }
+        Command::Probe { interface, targets, no_arp, icmp, wait, interval_ms } => {
+            let config = ProbeConfig {
+                interface,
+                targets: parse_targets(&targets)?,
+                arp: !no_arp,
+                icmp,
+                wait: Duration::from_secs(wait),
+                interval: Duration::from_millis(interval_ms),
+            };
+            let hosts = run_probe(&config)?;
+            println!("{:<16} {:<18} {:<4} {:<5} RTT", "IP", "MAC", "ARP", "ICMP");
+            for host in &hosts {
+                println!(
+                    "{:<16} {:<18} {:<4} {:<5} {}",
+                    host.ip,
+                    host.mac.map(|m| m.to_string()).unwrap_or_else(|| "-".to_string()),
+                    if host.arp_reply { "yes" } else { "no" },
+                    if host.icmp_reply { "yes" } else { "no" },
+                    host.rtt.map(|r| format!("{:.2}ms", r.as_secs_f64() * 1000.0)).unwrap_or_default()
+                );
+            }
+            println!("{} of {} targets verified", hosts.len(), config.targets.len());
+        }
+        // Needs the async runtime, so main runs it before getting here
+        Command::Chat { .. } => unreachable!("chat is handled in main"),
     }
     Ok(())
 }
 
+/// Analyzes packets from a capture file, or from stdin when `path` is `-`
+fn read_capture(path: &str, filter: &CaptureFilter, options: &CaptureOptions) -> Result<(), CaptureError> {
+    let source = if path == "-" { "stdin" } else { path };
+    info!("Reading packets from {}", source);
+
+    // libpcap reads standard input when given "-"
+    let mut cap = Capture::from_file(path)
+        .map_err(|e| CaptureError::PcapError(format!("Cannot read {}: {}", source, e)))?;
+    if let Some(bpf) = &filter.bpf {
+        cap.filter(bpf, true).map_err(|e| CaptureError::PcapError(format!("Invalid BPF '{}': {}", bpf, e)))?;
+        info!("Capture filter: {}", bpf);
+    }
+    let datalink = cap.get_datalink().0;
+    let mut savefile = open_write_file(&cap, options)?;
+    let mut pipe = options.pipe.as_deref().map(|p| PipeWriter::open(&cap, p, options.pipeline.pipe)).transpose()?;
+    let mut wpa = open_wpa_export(options, datalink)?;
+    let session = open_session_report(options, filter, source, "file")?;
+    let state = open_state(options)?;
+    drop_privileges(options)?;
+    enter_sandbox(options)?;
+    let mut monitors = Monitors::new(options, source)?;
+    monitors.session = session;
+    if let Some(state) = state {
+        monitors.restore(state);
+    }
+
+    let mut count = 0;
+    loop {
+        match cap.next_packet() {
+            Ok(packet) if !filter.matches(datalink, packet.data) => {}
+            Ok(packet) => {
+                let meta = monitors.stamp(&packet, datalink);
+                options.printer().print(&packet, &meta, None);
+                monitors.push(&packet, &meta);
+                if let Some(savefile) = &mut savefile {
+                    savefile.write(&packet);
+                }
+                if let Some(pipe) = &mut pipe {
+                    pipe.write(&packet);
+                }
+                if let Some(wpa) = &mut wpa {
+                    wpa.push(datalink, packet.data)?;
+                }
+                count += 1;
+            }
+            Err(pcap::Error::NoMorePackets) => break,
+            Err(e) => return Err(CaptureError::PcapError(format!("Error reading {}: {}", source, e))),
+        }
+    }
+
+    monitors.finish();
+    log_shed();
+    info!("Finished reading {}. Total packets: {}", source, count);
+    if let Some(wpa) = &wpa {
+        info!(
+            "{} complete 4-way handshakes, {} PMKIDs; {} hashes exported",
+            wpa.tracker().complete_handshakes(),
+            wpa.tracker().pmkids(),
+            wpa.written
+        );
+    }
+    Ok(())
+}
+
+/// How the capture handle is opened
+pub struct CaptureOptions {
+    pub monitor: bool,
+    pub promisc: bool,
+    pub direction: Option<pcap::Direction>,
+    /// Bytes captured per packet (`--snaplen`)
+    pub snaplen: i32,
+    pub precision: pcap::Precision,
+    pub tstamp_type: Option<pcap::TimestampType>,
+    /// Time between samples of libpcap's counters (`--stats-interval`)
+    pub stats_interval: Duration,
+    /// Group finished flows by similarity (`--cluster-flows`)
+    pub cluster_flows: bool,
+    /// Pcap file the filtered packets are saved to (`--write-file`)
+    pub write_file: Option<PathBuf>,
+    pub pipe: Option<PathBuf>,
+    pub wpa_export: Option<PathBuf>,
+    /// JSON session report written when the capture stops (`--report`)
+    pub report: Option<PathBuf>,
+    /// Checkpoint file for monitor state (`--state`)
+    pub state: Option<PathBuf>,
+    pub checkpoint_interval: Duration,
+    /// Control socket of a live capture (`--control`) and the packets kept for it
+    pub control: Option<PathBuf>,
+    pub ring_buffer: usize,
+    pub allowed_tunnels: Vec<TunnelKind>,
+    pub ospf_routers: Vec<Ipv4Addr>,
+    /// Alert rules from the configuration file
+    pub rules: Option<Arc<RuleSet>>,
+    /// Rate thresholds from the configuration file
+    pub thresholds: Option<Arc<ThresholdSet>>,
+    /// Upload volume baselines from the configuration file
+    pub exfil: Option<Arc<ExfilPolicy>>,
+    /// Where alerts are delivered besides the log
+    pub sinks: Arc<AlertSinks>,
+    pub output: OutputFormat,
+    /// Colors of the text output (`--color`)
+    pub palette: Palette,
+    /// How much of each packet is printed (`-q`, `-v`, `-vv`)
+    pub verbosity: Verbosity,
+    /// How packet times are printed (`--time-format`)
+    pub time_format: TimeFormat,
+    /// Account to switch to once the capture is open (`--user`/`--group`)
+    pub user: Option<String>,
+    pub group: Option<String>,
+    /// Parse packets under a seccomp filter (`--sandbox`)
+    pub sandbox: bool,
+    /// Lua hooks to run on packets, flows and alerts (`--script`)
+    pub script: Option<Script>,
+    /// Queue sizes and drop policies from the configuration file
+    pub pipeline: PipelineConfig,
+    /// Local prefixes (`--local-net` or the config), gateways and DNS servers
+    /// known before the capture starts
+    pub network: LocalNetwork,
+}
+
+impl CaptureOptions {
+    fn printer(&self) -> PacketPrinter {
+        PacketPrinter { output: self.output, verbosity: self.verbosity, time_format: self.time_format, palette: self.palette }
+    }
+
+    /// How to open the handle for a live capture on `interface`
+    fn capture_config(&self, interface: &str, filter: &CaptureFilter) -> CaptureConfig {
+        CaptureConfig::new(interface)
+            .promisc(self.promisc)
+            .monitor(self.monitor)
+            .snaplen(self.snaplen)
+            .direction(self.direction)
+            .bpf(filter.bpf.as_deref())
+            .precision(self.precision)
+            .tstamp_type(self.tstamp_type)
+    }
+}
+
+/// Logs the pipeline stages that dropped, degraded or waited during the capture
+fn log_shed() {
+    for (stage, shed) in backpressure::shed_report() {
+        if !shed.nothing_shed() {
+            warn!(
+                "Pipeline stage {} fell behind: {} passed, {} dropped, {} degraded, {} waits for room",
+                stage, shed.passed, shed.dropped, shed.degraded, shed.blocked
+            );
+        }
+    }
+}
+
+/// Opens the hashcat export when requested, warning when the link type can't carry handshakes
+fn open_wpa_export(options: &CaptureOptions, datalink: i32) -> Result<Option<WpaExporter>, CaptureError> {
+    let Some(path) = &options.wpa_export else { return Ok(None) };
+    if !is_wifi_datalink(datalink) {
+        warn!("--wpa-export needs 802.11 frames (use --monitor); datalink {} carries none", datalink);
+    }
+    WpaExporter::create(path).map(Some)
+}
+
+/// Creates the `--write-file` pcap with the capture's link type and timestamp precision
+fn open_write_file<T: Activated + ?Sized>(cap: &Capture<T>, options: &CaptureOptions) -> Result<Option<Savefile>, CaptureError> {
+    let Some(path) = &options.write_file else { return Ok(None) };
+    let savefile = cap.savefile(path).map_err(|e| CaptureError::PcapError(format!("Cannot create {}: {}", path.display(), e)))?;
+    info!("Saving packets to {}", path.display());
+    Ok(Some(savefile))
+}
 
+/// Creates the `--report` file now, while it can still be opened
+fn open_session_report(options: &CaptureOptions, filter: &CaptureFilter, source: &str, mode: &'static str) -> Result<Option<SessionRecorder>, CaptureError> {
+    let Some(path) = &options.report else { return Ok(None) };
+    let parameters = CaptureParameters {
+        source: source.to_string(),
+        mode,
+        bpf: filter.bpf.clone(),
+        display_filter: filter.display.as_ref().map(ToString::to_string),
+        snaplen: options.snaplen,
+        promisc: options.promisc,
+        monitor: options.monitor,
+    };
+    SessionRecorder::create(path, parameters).map(Some)
+}
 
-pub fn start_capture(interface_name: &str) -> Result<(), CaptureError> {
-    info!("Starting packet capture on '{}'", interface_name);
+/// Reads the `--state` checkpoint before privileges are dropped
+fn open_state(options: &CaptureOptions) -> Result<Option<(StateFile, Option<SavedState>)>, CaptureError> {
+    let Some(path) = &options.state else { return Ok(None) };
+    if options.sandbox {
+        warn!("--state is not saved under --sandbox, which can't create files");
+        return Ok(None);
+    }
+    let file = StateFile::new(path, options.checkpoint_interval);
+    let saved = file.load()?;
+    Ok(Some((file, saved)))
+}
 
-    let iface = Device::list()
-        .map_err(|e| CaptureError::PcapError(e.to_string()))?
-        .into_iter()
-        .find(|d| d.name == interface_name)
-        .ok_or_else(|| CaptureError::InterfaceNotFound(interface_name.to_string()))?;
-    info!("Interface found: {}", iface.name);
+/// Alerts sent to a control connection that starts following them
+const RECENT_ALERTS: usize = 50;
 
-    let mut cap = Capture::from_device(iface).map_err(|e| CaptureError::PcapError(e.to_string()))?
-        .promisc(true)
-        .immediate_mode(true)
-        .open().map_err(|e| CaptureError::PcapError(e.to_string()))?
-        .setnonblock().map_err(|e| CaptureError::PcapError(e.to_string()))?;
+/// The control socket of a live capture with the recent packets it can dump
+struct Control {
+    server: ControlServer,
+    ring: PacketRing,
+    recent: VecDeque<Alert>,
+}
+
+impl Control {
+    /// Hands the alerts raised since the last call to the connections following them
+    fn publish(&mut self, alerts: Vec<Alert>) {
+        for alert in alerts {
+            self.server.publish(&serde_json::json!({ "alert": alert }));
+            if self.recent.len() == RECENT_ALERTS {
+                self.recent.pop_front();
+            }
+            self.recent.push_back(alert);
+        }
+    }
+}
+
+/// Binds the `--control` socket before privileges are dropped
+fn open_control(options: &CaptureOptions) -> Result<Option<Control>, CaptureError> {
+    let Some(path) = &options.control else { return Ok(None) };
+    if options.sandbox {
+        warn!("--control is not available under --sandbox, which can't accept connections or create dumps");
+        return Ok(None);
+    }
+    Ok(Some(Control { server: ControlServer::bind(path)?, ring: PacketRing::new(options.ring_buffer), recent: VecDeque::new() }))
+}
+
+/// Open flows listed when a request gives no limit
+const DEFAULT_FLOW_LIMIT: usize = 50;
+
+/// Answers a control request that doesn't need the AI analyzer
+fn handle_control(
+    client: ClientId,
+    request: ControlRequest,
+    cap: &mut Capture<pcap::Active>,
+    filter: &mut CaptureFilter,
+    count: u32,
+    control: &mut Control,
+    monitors: &Monitors<'_>,
+) -> serde_json::Value {
+    let ring = &control.ring;
+    match request {
+        ControlRequest::Stats => {
+            let mut stats = serde_json::json!({
+                "packets": count,
+                "buffered": ring.len(),
+                "bpf": filter.bpf,
+                "display": filter.display.as_ref().map(ToString::to_string),
+                "pipeline": backpressure::shed_report(),
+                "directions": monitors.directions,
+            });
+            if let Ok(pcap) = cap.stats() {
+                stats["received"] = pcap.received.into();
+                stats["dropped"] = pcap.dropped.into();
+                stats["if_dropped"] = pcap.if_dropped.into();
+            }
+            control::success(stats)
+        }
+        ControlRequest::Flows { limit } => {
+            let mut flows: Vec<&FlowRecord> = monitors.flows.iter().flat_map(FlowTracker::open).collect();
+            flows.sort_by_key(|flow| std::cmp::Reverse(flow.bytes));
+            let total = flows.len();
+            flows.truncate(limit.unwrap_or(DEFAULT_FLOW_LIMIT));
+            control::success(serde_json::json!({ "open": total, "flows": flows }))
+        }
+        ControlRequest::Alerts => {
+            control.server.subscribe(client);
+            control::success(serde_json::json!({ "recent": control.recent }))
+        }
+        ControlRequest::Filter { bpf, display } => {
+            // Given fields replace the current filter, empty ones clear it
+            let pick = |new: Option<String>, current: Option<String>| match new {
+                Some(text) if text.trim().is_empty() => None,
+                Some(text) => Some(text),
+                None => current,
+            };
+            let bpf = pick(bpf, filter.bpf.clone());
+            let display = pick(display, filter.display.as_ref().map(ToString::to_string));
+            let new_filter = match CaptureFilter::new(bpf.as_deref(), display.as_deref(), None) {
+                Ok(new_filter) => new_filter,
+                Err(e) => return control::failure(&e.to_string()),
+            };
+            if new_filter.bpf != filter.bpf
+                && let Err(e) = cap.filter(new_filter.bpf.as_deref().unwrap_or(""), true)
+            {
+                return control::failure(&format!("Invalid BPF: {}", e));
+            }
+            info!("Filters changed over the control socket: bpf {}, display {}", bpf.as_deref().unwrap_or("none"), display.as_deref().unwrap_or("none"));
+            *filter = new_filter;
+            control::success(serde_json::json!({ "bpf": bpf, "display": display }))
+        }
+        ControlRequest::Dump { path } => match ring.dump(cap.get_datalink().0, &path) {
+            Ok(written) => {
+                info!("Dumped {} buffered packets to {}", written, path.display());
+                control::success(serde_json::json!({ "path": path, "packets": written }))
+            }
+            Err(e) => control::failure(&e.to_string()),
+        },
+        ControlRequest::Analyze => control::failure("AI analysis is only available in AI capture mode"),
+    }
+}
+
+/// Security checks run on every packet of a live capture or file, logging
+/// their alerts as warnings
+struct Monitors<'a> {
+    allowed_tunnels: Vec<TunnelKind>,
+    /// Namespace the packets come from when capturing in several at once
+    source: Option<&'a str>,
+    /// Colors alerts by severity
+    palette: Palette,
+    poisoning: PoisoningDetector,
+    ospf: OspfMonitor,
+    gateways: GatewayMonitor,
+    rules: Option<RuleEngine>,
+    thresholds: Option<ThresholdEngine>,
+    exfil: Option<ExfilMonitor>,
+    script: Option<ScriptHooks>,
+    sinks: Arc<AlertSinks>,
+    /// Conversations followed for sinks that take flow summaries
+    flows: Option<FlowTracker>,
+    /// Flows queued for an AI verdict, in AI captures
+    flow_review: Option<FlowReview>,
+    /// Kinds of flows seen so far (`--cluster-flows`)
+    clusters: Option<FlowClusters>,
+    /// Flow of the packet being checked, for alerts to flag
+    current_flow: Option<FlowKey>,
+    truncation: TruncationStats,
+    mtu: MtuMonitor,
+    /// Numbers packets and finds their interface; `any` captures are split by ingress interface
+    stamper: Stamper,
+    /// Traffic per direction, logged when the capture stops
+    directions: DirectionStats,
+    frame_sizes: FrameSizes,
+    /// Statistics for the `--report` file
+    session: Option<SessionRecorder>,
+    /// Where state is checkpointed (`--state`)
+    state: Option<StateFile>,
+    /// Alerts not yet handed to the control socket, when there is one
+    alert_feed: Option<Vec<Alert>>,
+}
+
+impl<'a> Monitors<'a> {
+    fn new(options: &CaptureOptions, interface: &str) -> Result<Self, CaptureError> {
+        Ok(Monitors {
+            allowed_tunnels: options.allowed_tunnels.clone(),
+            source: None,
+            palette: options.palette,
+            poisoning: PoisoningDetector::new(),
+            ospf: OspfMonitor::new(options.ospf_routers.clone()),
+            gateways: GatewayMonitor::new(),
+            rules: options.rules.clone().map(RuleEngine::new),
+            thresholds: options.thresholds.clone().map(ThresholdEngine::new),
+            exfil: options.exfil.clone().map(ExfilMonitor::new),
+            script: options.script.as_ref().map(ScriptHooks::new).transpose()?,
+            sinks: options.sinks.clone(),
+            flows: (options.sinks.wants_flows() || options.cluster_flows).then(|| FlowTracker::new(FLOW_IDLE_TIMEOUT)),
+            flow_review: None,
+            clusters: options.cluster_flows.then(FlowClusters::default),
+            current_flow: None,
+            truncation: TruncationStats::default(),
+            mtu: MtuMonitor::new(),
+            stamper: Stamper::new(interface, options.network.clone()),
+            directions: DirectionStats::default(),
+            frame_sizes: FrameSizes::default(),
+            session: None,
+            state: None,
+            alert_feed: None,
+        })
+    }
+
+    /// Monitors whose alerts are tagged with the namespace they were raised in
+    fn tagged(options: &CaptureOptions, interface: &str, source: &'a str) -> Result<Self, CaptureError> {
+        Ok(Monitors { source: Some(source), ..Monitors::new(options, interface)? })
+    }
+
+    /// Picks up reloaded alert settings without forgetting routers seen so far
+    fn reconfigure(&mut self, options: &CaptureOptions) {
+        self.allowed_tunnels = options.allowed_tunnels.clone();
+        self.ospf.set_expected(options.ospf_routers.clone());
+        match (&mut self.rules, options.rules.clone()) {
+            (Some(engine), Some(rules)) => engine.replace(rules),
+            (rules, new) => *rules = new.map(RuleEngine::new),
+        }
+        match (&mut self.thresholds, options.thresholds.clone()) {
+            (Some(engine), Some(thresholds)) => engine.replace(thresholds),
+            (thresholds, new) => *thresholds = new.map(ThresholdEngine::new),
+        }
+        match (&mut self.exfil, options.exfil.clone()) {
+            (Some(monitor), Some(policy)) => monitor.replace(policy),
+            (exfil, new) => *exfil = new.map(ExfilMonitor::new),
+        }
+        self.sinks = options.sinks.clone();
+        let wants_flows = self.sinks.wants_flows() || self.alert_feed.is_some() || self.flow_review.is_some() || self.clusters.is_some();
+        if wants_flows != self.flows.is_some() {
+            self.flows = wants_flows.then(|| FlowTracker::new(FLOW_IDLE_TIMEOUT));
+        }
+    }
+
+    /// Follows flows and keeps alerts for the control socket
+    fn enable_control(&mut self) {
+        self.flows.get_or_insert_with(|| FlowTracker::new(FLOW_IDLE_TIMEOUT));
+        self.alert_feed = Some(Vec::new());
+    }
+
+    /// Follows flows and queues them for the AI when they end or an alert flags them
+    fn enable_flow_review(&mut self) {
+        self.flows.get_or_insert_with(|| FlowTracker::new(FLOW_IDLE_TIMEOUT));
+        self.flow_review = Some(FlowReview::new());
+    }
+
+    /// The next flow waiting for an AI verdict
+    fn next_flow_review(&mut self) -> Option<FlowReviewRequest> {
+        self.flow_review.as_mut()?.take_next()
+    }
+
+    /// Alerts raised since the last call
+    fn take_alerts(&mut self) -> Vec<Alert> {
+        self.alert_feed.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Continues from a checkpoint and keeps saving to its file
+    fn restore(&mut self, (file, saved): (StateFile, Option<SavedState>)) {
+        if let Some(saved) = saved {
+            // Open flows only matter to sinks that still take flow summaries
+            if self.flows.is_some() && saved.flows.is_some() {
+                self.flows = saved.flows;
+            }
+            self.ospf.restore(saved.ospf);
+            self.gateways = saved.gateways;
+            self.poisoning = saved.names;
+            self.frame_sizes = saved.frame_sizes;
+            if self.clusters.is_some() && saved.clusters.is_some() {
+                self.clusters = saved.clusters;
+            }
+            if let (Some(exfil), Some(uploads)) = (&mut self.exfil, saved.uploads) {
+                exfil.restore(uploads);
+            }
+        }
+        self.state = Some(file);
+    }
+
+    /// Saves state to the `--state` file when the checkpoint interval has passed, or now when `force`d
+    fn checkpoint(&mut self, force: bool) {
+        let Some(state) = &mut self.state else { return };
+        if !force && !state.due() {
+            return;
+        }
+        let snapshot = Snapshot {
+            flows: self.flows.as_ref(),
+            ospf: &self.ospf,
+            gateways: &self.gateways,
+            names: &self.poisoning,
+            frame_sizes: &self.frame_sizes,
+            clusters: self.clusters.as_ref(),
+            uploads: self.exfil.as_ref().map(ExfilMonitor::volumes),
+        };
+        match state.save(&snapshot) {
+            Ok(()) if force => info!("State saved to {}", state.path().display()),
+            Ok(()) => {}
+            Err(e) => warn!("{}", e),
+        }
+    }
+
+    /// Logs an alert and hands it to the `--script` hooks, the notification sinks and the session report
+    fn report(&mut self, time: f64, severity: Severity, kind: &'static str, message: &dyn std::fmt::Display, hosts: Vec<IpAddr>, techniques: Vec<String>) {
+        let source = self.source.map(str::to_string);
+        let alert = Alert { time, severity, kind, message: message.to_string(), source, hosts, techniques };
+        warn!("{}", self.palette.severity(severity, &alert.to_string()));
+        if let Some(script) = &self.script {
+            script.on_alert(&alert.message, self.source);
+        }
+        self.sinks.send(&alert);
+        if let Some(session) = &mut self.session {
+            session.push_alert(&alert);
+        }
+        if let Some(feed) = &mut self.alert_feed {
+            feed.push(alert);
+        }
+        if let (Some(review), Some(flows), Some(key)) = (&mut self.flow_review, &self.flows, &self.current_flow)
+            && let Some(flow) = flows.flow(key)
+        {
+            review.flag(flow);
+        }
+    }
+
+    /// Hands a flow that ended to the sinks, places it among the kinds of flows
+    /// seen so far and queues it for the AI. A flow unlike any before raises an alert.
+    fn end_flow(&mut self, flow: &FlowRecord) {
+        self.sinks.send_flow(flow);
+        let cluster = self.clusters.as_mut().map(|clusters| clusters.assign(flow));
+        if let Some(cluster) = cluster {
+            debug!("Flow {} {}", flow.key, cluster);
+            if cluster.novel {
+                let message = format!("Novel flow {}: {}", flow.key, cluster);
+                self.report(flow.last, Severity::Low, "builtin", &message, vec![flow.key.a.0, flow.key.b.0], Vec::new());
+            }
+        }
+        if let Some(review) = &mut self.flow_review {
+            review.ended(flow, cluster);
+        }
+    }
+
+    /// Numbers a packet that passed the filters, before any of it is skipped under load
+    fn stamp(&mut self, packet: &pcap::Packet<'_>, datalink: i32) -> PacketMeta {
+        self.stamper.stamp(datalink, packet.header, packet.data)
+    }
+
+    /// Runs every check on one packet, reporting the alerts it raises
+    fn push(&mut self, packet: &pcap::Packet<'_>, meta: &PacketMeta) {
+        let data = packet.data;
+        self.current_flow = None;
+        let datalink = meta.datalink;
+        let time = meta.time();
+        self.truncation.push(meta.caplen, meta.wirelen);
+        self.checkpoint(false);
+        self.frame_sizes.push(&meta.interface, meta.wirelen as usize);
+        self.directions.push(meta.direction, meta.wirelen as u64);
+        // `--script` hooks see the packet first
+        if let Some(script) = &mut self.script
+            && let Some(tag) = script.on_packet(time, datalink, data, self.source)
+        {
+            info!("Tag: {}", tag);
+        }
+        let summary = PacketSummary::from_link(datalink, data);
+        if let Some(session) = &mut self.session {
+            session.push(summary.as_ref(), meta.wirelen as u64, time, meta.direction);
+        }
+        let Some(summary) = summary else { return };
+        // Flow sinks get each flow as it ends; AI captures also queue flows for review
+        let ended = self.flows.as_mut().map(|flows| flows.push(&summary, time)).unwrap_or_default();
+        for flow in ended {
+            self.end_flow(&flow);
+        }
+        if let Some(review) = &mut self.flow_review {
+            review.push(&summary, data);
+            self.current_flow = FlowKey::from_summary(&summary);
+        }
+        // Cleartext credentials, disallowed tunnels and industrial control commands
+        let alerts = [
+            (tunnel::policy_alert(&summary, data, &self.allowed_tunnels), &[attack::PROTOCOL_TUNNELING][..]),
+            (ldap::cleartext_bind_alert(&summary, data), &[attack::UNSECURED_CREDENTIALS]),
+            (tacacs::unencrypted_session_alert(&summary, data), &[attack::UNSECURED_CREDENTIALS]),
+            (bacnet::sensitive_service_alert(&summary, data), &[attack::UNAUTHORIZED_COMMAND_MESSAGE]),
+            (iec104::control_command_alert(&summary, data), &[attack::UNAUTHORIZED_COMMAND_MESSAGE]),
+            (profinet::set_request_alert(&summary, data), &[attack::MODIFY_PARAMETER]),
+            (opcua::insecure_channel_alert(&summary, data), &[]),
+        ];
+        for (alert, techniques) in alerts {
+            if let Some(alert) = alert {
+                self.report(time, Severity::Medium, "builtin", &alert, Vec::new(), techniques.iter().map(|t| t.to_string()).collect());
+            }
+        }
+        // ARP moving a gateway, DNS queries to unknown servers
+        let network = self.stamper.network_mut();
+        let gateway_alerts = network.learn(&summary, data);
+        let resolver_alert = network.unexpected_resolver_alert(&summary);
+        for alert in gateway_alerts {
+            self.report(time, Severity::High, "builtin", &alert, Vec::new(), vec![attack::ARP_CACHE_POISONING.to_string()]);
+        }
+        if let Some(alert) = resolver_alert {
+            self.report(time, Severity::Medium, "builtin", &alert, summary.src_ip.into_iter().collect(), vec![attack::DNS.to_string()]);
+        }
+        // Configured rules, thresholds and upload baselines
+        if let Some(rules) = &mut self.rules {
+            for alert in rules.push(&summary, time, meta.direction) {
+                self.report(time, alert.severity, "rule", &alert, alert.hosts(), alert.techniques.clone());
+            }
+        }
+        if let Some(thresholds) = &mut self.thresholds {
+            for alert in thresholds.push(&summary, data, time) {
+                self.report(time, alert.severity, "threshold", &alert, alert.hosts.clone(), alert.techniques.clone());
+            }
+        }
+        if let Some(alert) = self.exfil.as_mut().and_then(|exfil| exfil.push(&summary, time)) {
+            self.report(time, alert.severity, "builtin", &alert, vec![alert.host], alert.techniques());
+        }
+        // Path MTU black holes and lost fragments
+        for alert in self.mtu.push(&summary, data, time) {
+            self.report(time, Severity::Medium, "builtin", &alert, Vec::new(), Vec::new());
+        }
+        // Name resolution poisoning, rogue OSPF routers and gateway takeovers
+        for alert in self.poisoning.push(&summary, data) {
+            self.report(time, Severity::Medium, "builtin", &alert, vec![alert.responder_ip], vec![attack::LLMNR_NBT_NS_POISONING.to_string()]);
+        }
+        for alert in self.ospf.push(&summary, data).into_iter().chain(self.gateways.push(&summary, data)) {
+            self.report(time, Severity::Medium, "builtin", &alert, Vec::new(), vec![attack::ADVERSARY_IN_THE_MIDDLE.to_string()]);
+        }
+    }
+
+    /// Wraps up when the capture stops: ends open flows and logs the totals
+    fn finish(&mut self) {
+        if let Some(script) = &mut self.script {
+            script.finish();
+        }
+        // Open flows are saved to carry on after a restart instead of being reported as ended
+        if self.state.is_some() {
+            self.checkpoint(true);
+        } else if let Some(flows) = &mut self.flows {
+            for flow in flows.finish() {
+                self.end_flow(&flow);
+            }
+        }
+        if let (Some(session), Some(clusters)) = (&mut self.session, &self.clusters) {
+            session.set_flow_clusters(clusters.clusters().to_vec());
+        }
+        // Batching notification sinks send what they hold
+        self.sinks.flush();
+        // Frame sizes, traffic, uploads and capture problems for the log
+        let tag = self.source.map(|s| format!("[{}] ", s)).unwrap_or_default();
+        for (interface, sizes) in self.frame_sizes.interfaces() {
+            info!("{}Frame sizes on {}: {}", tag, interface, sizes);
+        }
+        let traffic: Vec<String> = self
+            .directions
+            .iter()
+            .filter(|&(direction, _)| direction != Direction::Unknown)
+            .map(|(direction, volume)| format!("{} {} packets/{} bytes", direction, volume.packets, volume.bytes))
+            .collect();
+        if !traffic.is_empty() {
+            info!("{}Traffic by direction: {}", tag, traffic.join(", "));
+        }
+        if let Some(exfil) = &self.exfil {
+            let mut hosts: Vec<_> = exfil.volumes().hosts().filter(|(_, uploads)| uploads.today > 0).collect();
+            hosts.sort_by_key(|(_, uploads)| std::cmp::Reverse(uploads.today));
+            for (host, uploads) in hosts.into_iter().take(5) {
+                let top: Vec<String> = uploads.top_destinations(3).iter().map(|(name, bytes)| format!("{} {}", name, megabytes(*bytes as f64))).collect();
+                info!("{}Uploaded by {} today: {} (baseline {}; {})", tag, host, megabytes(uploads.today as f64), megabytes(uploads.baseline()), top.join(", "));
+            }
+        }
+        if let Some(warning) = self.truncation.warning() {
+            warn!("{}{}", tag, warning);
+        }
+        if let Some(review) = &self.flow_review
+            && review.skipped() > 0
+        {
+            warn!("{}{} flows were not sent to the AI because too many were waiting", tag, review.skipped());
+        }
+        // The `--report` file
+        if let Some(session) = &mut self.session {
+            match session.write() {
+                Ok(()) => info!("{}Session report written to {}", tag, session.path().display()),
+                Err(e) => warn!("{}{}", tag, e),
+            }
+        }
+    }
+}
+
+/// How packets that pass the filters are printed: format, verbosity and colors
+#[derive(Clone, Copy)]
+struct PacketPrinter {
+    output: OutputFormat,
+    verbosity: Verbosity,
+    time_format: TimeFormat,
+    palette: Palette,
+}
+
+impl PacketPrinter {
+    /// Writes the packet to stdout, apart from the log
+    fn print(&self, packet: &pcap::Packet<'_>, meta: &PacketMeta, source: Option<&str>) {
+        match self.output {
+            OutputFormat::Text => {
+                if let Some(text) = output::packet_text(packet.data, meta, self.verbosity, self.time_format, self.palette, source) {
+                    println!("{}", text);
+                }
+            }
+            OutputFormat::Ek => println!("{}", ek_record(&meta.micro_header(), meta.datalink, packet.data, source)),
+        }
+    }
+}
+
+/// Drops root once every handle and output file is open, when `--user` asks for it
+fn drop_privileges(options: &CaptureOptions) -> Result<(), CaptureError> {
+    match &options.user {
+        Some(user) => privileges::drop_privileges(user, options.group.as_deref()),
+        None => Ok(()),
+    }
+}
+
+/// Confines the calling thread with seccomp when `--sandbox` asks for it; must
+/// come after privileges are dropped, which needs system calls the filter denies
+fn enter_sandbox(options: &CaptureOptions) -> Result<(), CaptureError> {
+    if options.sandbox {
+        sandbox::restrict_current_thread()?;
+    }
+    Ok(())
+}
+
+/// A live capture's outputs, monitors and control socket, set up the same
+/// way by every capture mode
+struct CaptureSession<'a> {
+    datalink: i32,
+    filter: CaptureFilter,
+    printer: PacketPrinter,
+    monitors: Monitors<'a>,
+    control: Option<Control>,
+    /// `--write-file`, flushed at every stats sample
+    savefile: Option<Savefile>,
+    pipe: Option<PipeWriter>,
+    wpa: Option<WpaExporter>,
+    load: DropWatch,
+    stats: StatsReporter,
+}
+
+impl<'a> CaptureSession<'a> {
+    /// Opens the handle and every output file and socket, then drops
+    /// privileges; `mode` names the capture mode in the `--report` file
+    fn open(interface_name: &str, filter: &CaptureFilter, options: &CaptureOptions, mode: &'static str) -> Result<(Capture<pcap::Active>, Self), CaptureError> {
+        info!("Starting packet capture on '{}'", interface_name);
+        let cap = options.capture_config(interface_name, filter).open()?;
+        let datalink = cap.get_datalink().0;
+        let savefile = open_write_file(&cap, options)?;
+        let pipe = options.pipe.as_deref().map(|p| PipeWriter::open(&cap, p, options.pipeline.pipe)).transpose()?;
+        let wpa = open_wpa_export(options, datalink)?;
+        let session = open_session_report(options, filter, interface_name, mode)?;
+        let state = open_state(options)?;
+        let control = open_control(options)?;
+        drop_privileges(options)?;
+        if mode == "ai" {
+            if options.sandbox {
+                warn!("--sandbox is not applied in AI mode, which needs network access for the API");
+            }
+        } else {
+            enter_sandbox(options)?;
+        }
+        let mut monitors = Monitors::new(options, interface_name)?;
+        monitors.stamper.network_mut().add_prefixes(capture::interface_networks(interface_name));
+        monitors.stamper.set_precision(capture::timestamp_precision(&cap));
+        monitors.session = session;
+        if control.is_some() {
+            monitors.enable_control();
+        }
+        if let Some(state) = state {
+            monitors.restore(state);
+        }
+        let session = CaptureSession {
+            datalink,
+            filter: filter.clone(),
+            printer: options.printer(),
+            monitors,
+            control,
+            savefile,
+            pipe,
+            wpa,
+            load: DropWatch::new(options.pipeline.packets),
+            stats: StatsReporter::new(options.stats_interval, Instant::now()),
+        };
+        Ok((cap, session))
+    }
+
+    /// Prints, monitors and forwards a packet, returning `false` for one the display filter rejects
+    fn handle(&mut self, packet: &pcap::Packet<'_>) -> Result<bool, CaptureError> {
+        let datalink = self.datalink;
+        if !self.filter.matches(datalink, packet.data) {
+            return Ok(false);
+        }
+        let meta = self.monitors.stamp(packet, datalink);
+        // Under load only the cheap outputs below keep up with the capture
+        if !self.load.shed_load() {
+            self.printer.print(packet, &meta, None);
+            self.monitors.push(packet, &meta);
+        }
+        if let Some(control) = &mut self.control {
+            // Dumps are written with microsecond timestamps
+            let header = meta.micro_header();
+            control.ring.push(&pcap::Packet::new(&header, packet.data));
+            control.publish(self.monitors.take_alerts());
+        }
+        if let Some(savefile) = &mut self.savefile {
+            savefile.write(packet);
+        }
+        if let Some(pipe) = &mut self.pipe {
+            pipe.write(packet);
+        }
+        if let Some(wpa) = &mut self.wpa {
+            wpa.push(datalink, packet.data)?;
+        }
+        Ok(true)
+    }
+
+    /// Samples libpcap's counters when the stats interval has passed, logs
+    /// their rates and degrades parsing after new drops
+    fn update_stats(&mut self, cap: &mut Capture<pcap::Active>, count: u32) {
+        let now = Instant::now();
+        if !self.stats.due(now) {
+            return;
+        }
+        if let Some(savefile) = &mut self.savefile
+            && let Err(e) = savefile.flush()
+        {
+            warn!("Cannot write to --write-file: {}", e);
+        }
+        let stats = match cap.stats() {
+            Ok(stats) => PcapStats { received: stats.received, dropped: stats.dropped, if_dropped: stats.if_dropped },
+            Err(e) => {
+                warn!("Unable to retrieve stats: {:?}", e);
+                return;
+            }
+        };
+        self.load.observe(stats.dropped);
+        if let Some(session) = &mut self.monitors.session {
+            session.set_pcap_stats(stats);
+        }
+        if let Some(report) = self.stats.sample(now, stats, count as u64)
+            && !report.is_idle()
+        {
+            info!("Stats => {}", report);
+        }
+    }
+
+    fn reload(&mut self, cap: &mut Capture<pcap::Active>, reloaded: (CaptureFilter, CaptureOptions)) {
+        apply_reload(cap, &mut self.filter, &mut self.printer, &mut self.monitors, reloaded);
+    }
+
+    /// Requests waiting on the control socket, if there is one
+    fn control_requests(&mut self) -> Vec<(ClientId, Result<ControlRequest, String>)> {
+        self.control.as_mut().map(|control| control.server.poll()).unwrap_or_default()
+    }
+
+    /// Answers a control request that doesn't need the AI analyzer
+    fn answer(&mut self, client: ClientId, request: ControlRequest, cap: &mut Capture<pcap::Active>, count: u32) {
+        let Some(control) = &mut self.control else { return };
+        let response = handle_control(client, request, cap, &mut self.filter, count, control, &self.monitors);
+        control.server.reply(client, &response);
+    }
+
+    fn reply(&mut self, client: ClientId, response: &serde_json::Value) {
+        if let Some(control) = &mut self.control {
+            control.server.reply(client, response);
+        }
+    }
+
+    fn finish(mut self, count: u32) {
+        self.monitors.finish();
+        log_shed();
+        info!("Capture completed. Total packets: {}", count);
+    }
+}
+
+pub fn start_capture(
+    interface_name: &str,
+    filter: &CaptureFilter,
+    options: &CaptureOptions,
+    mut reloader: Option<Reloader<'_>>,
+) -> Result<(), CaptureError> {
+    let (mut cap, mut session) = CaptureSession::open(interface_name, filter, options, "live")?;
 
     let mut count = 0;
-    let mut last_stats = None;
     loop {
-        match cap.stats() {
-            Ok(stats) => {
-                let current = (stats.received, stats.dropped, stats.if_dropped);
-                if last_stats != Some(current) {
-                    last_stats = Some(current);
-                    let (received, dropped, if_dropped) = current;
-                    info!("Stats => received: {}, dropped: {}, kernel drop: {}", received, dropped, if_dropped);
-                    info!("Delta recv - processed: {}", received.saturating_sub(count));
-                }
+        if let Some((_, new_filter, new_options)) = reloader.as_mut().and_then(|r| r.poll()) {
+            session.reload(&mut cap, (new_filter, new_options));
+        }
+        session.update_stats(&mut cap, count);
+        for (client, request) in session.control_requests() {
+            match request {
+                Ok(request) => session.answer(client, request, &mut cap, count),
+                Err(e) => session.reply(client, &control::failure(&e)),
             }
-            Err(e) => warn!("Unable to retrieve stats: {:?}", e),
         }
 
         match cap.next_packet() {
             Ok(packet) => {
-                info!(
-                    "PACKET len = {}, ts = {}.{}",
-                    packet.data.len(),
-                    packet.header.ts.tv_sec,
-                    packet.header.ts.tv_usec
-                );
-                
-                // Parse frame control information from the packet
-                if let Some(frame_control) = analyze_frame_control(&packet.data) {
-                    info!("Frame Control: {}", frame_control);
-                }
-                
+                session.handle(&packet)?;
                 count += 1;
             }
             Err(pcap::Error::PcapError(e)) if e.contains("Packets are not available") => {
@@ -112,7 +1715,7 @@ pub fn start_capture(interface_name: &str) -> Result<(), CaptureError> {
                 break;
             }
             Err(pcap::Error::PcapError(e)) if e.contains("Operation not permitted") => {
-                error!("Missing privileges. Try:\nsudo setcap cap_net_raw,cap_net_admin=eip ./your_binary");
+                error!("Missing privileges. Try:\n{}", privileges::privilege_hint());
                 break;
             }
             Err(e) => {
@@ -122,138 +1725,313 @@ pub fn start_capture(interface_name: &str) -> Result<(), CaptureError> {
         }
     }
 
-    info!("Capture completed. Total packets: {}", count);
+    session.finish(count);
     Ok(())
 }
 
-async fn start_capture_with_ai(interface_name: &str, analyzer: AIAnalyzer) -> Result<(), Box<dyn std::error::Error>> {
-    info!("Starting packet capture on '{}'", interface_name);
-
-    let iface = Device::list()
-        .map_err(|e| CaptureError::PcapError(e.to_string()))?
-        .into_iter()
-        .find(|d| d.name == interface_name)
-        .ok_or_else(|| CaptureError::InterfaceNotFound(interface_name.to_string()))?;
+/// A filtered packet from one of several namespace captures
+struct TaggedPacket {
+    source: usize,
+    datalink: i32,
+    precision: pcap::Precision,
+    header: pcap::PacketHeader,
+    data: Vec<u8>,
+}
 
-    info!("Interface found: {}", iface.name);
+/// Captures on the same interface in several network namespaces at once, one
+/// thread per namespace, tagging every packet and alert with its namespace
+fn start_namespace_captures(targets: &[String], interface_name: &str, filter: &CaptureFilter, options: &CaptureOptions) -> Result<(), CaptureError> {
+    if options.write_file.is_some() || options.pipe.is_some() || options.wpa_export.is_some() || options.report.is_some() || options.state.is_some() || options.control.is_some() {
+        return Err(CaptureError::InputError("--write-file, --pipe, --wpa-export, --report, --state and --control describe a single capture; pass one --netns".to_string()));
+    }
+    info!("Starting packet capture on '{}' in {} namespaces", interface_name, targets.len());
 
-    let mut cap = Capture::from_device(iface)
-        .map_err(|e| CaptureError::PcapError(e.to_string()))?
-        .promisc(true)
-        .immediate_mode(true)
-        .open()
-        .map_err(|e| CaptureError::PcapError(e.to_string()))?
-        .setnonblock()
-        .map_err(|e| CaptureError::PcapError(e.to_string()))?;
+    let (tx, rx) = backpressure::channel("packets", options.pipeline.packets);
+    // Every thread needs root to enter its namespace, so privileges are dropped once all have opened
+    let opened = Barrier::new(targets.len() + 1);
+    thread::scope(|scope| {
+        for (source, target) in targets.iter().enumerate() {
+            let tx = tx.clone();
+            let opened = &opened;
+            scope.spawn(move || {
+                if let Err(e) = capture_in_namespace(source, target, interface_name, filter, tx, options, opened) {
+                    error!("[{}] {}", target, e);
+                }
+            });
+        }
+        drop(tx);
+        // Capture threads wait again until privileges are dropped before entering the sandbox
+        opened.wait();
+        let dropped = drop_privileges(options);
+        opened.wait();
+        dropped?;
+        enter_sandbox(options)?;
 
-    let mut count = 0;
-    let mut last_stats = None;
-    let mut first_packet_analyzed = false;
+        let mut monitors = targets.iter().map(|t| Monitors::tagged(options, interface_name, t)).collect::<Result<Vec<_>, _>>()?;
+        let mut count = 0;
+        while let Some(packet) = rx.recv() {
+            count += 1;
+            let monitors = &mut monitors[packet.source];
+            let tagged = pcap::Packet::new(&packet.header, &packet.data);
+            monitors.stamper.set_precision(packet.precision);
+            let meta = monitors.stamp(&tagged, packet.datalink);
+            if rx.shed_load() {
+                continue;
+            }
+            options.printer().print(&tagged, &meta, Some(targets[packet.source].as_str()));
+            monitors.push(&tagged, &meta);
+        }
+        monitors.iter_mut().for_each(Monitors::finish);
+        log_shed();
+        info!("Capture completed. Total packets: {}", count);
+        Ok(())
+    })
+}
 
+/// Enters one namespace on the calling thread and forwards its filtered packets
+fn capture_in_namespace(
+    source: usize,
+    target: &str,
+    interface_name: &str,
+    filter: &CaptureFilter,
+    tx: backpressure::Sender<TaggedPacket>,
+    options: &CaptureOptions,
+    opened: &Barrier,
+) -> Result<(), CaptureError> {
+    let capture = netns::enter(target).and_then(|()| options.capture_config(interface_name, filter).open());
+    opened.wait();
+    opened.wait();
+    let mut cap = capture?;
+    let datalink = cap.get_datalink().0;
+    let precision = capture::timestamp_precision(&cap);
+    enter_sandbox(options)?;
     loop {
-        match cap.stats() {
-            Ok(stats) => {
-                let current = (stats.received, stats.dropped, stats.if_dropped);
-                if last_stats != Some(current) {
-                    last_stats = Some(current);
-                    let (received, dropped, if_dropped) = current;
-                    info!("Stats => received: {}, dropped: {}, kernel drop: {}", received, dropped, if_dropped);
-                    info!("Delta recv - processed: {}", received.saturating_sub(count));
+        match cap.next_packet() {
+            Ok(packet) if !filter.matches(datalink, packet.data) => {}
+            Ok(packet) => {
+                let packet = TaggedPacket { source, datalink, precision, header: *packet.header, data: packet.data.to_vec() };
+                if tx.send(packet).is_err() {
+                    return Ok(());
                 }
             }
-            Err(e) => warn!("Unable to retrieve stats: {:?}", e),
+            Err(pcap::Error::PcapError(e)) if e.contains("Packets are not available") => {
+                thread::sleep(Duration::from_micros(500));
+            }
+            Err(pcap::Error::TimeoutExpired) => {
+                thread::sleep(Duration::from_micros(500));
+            }
+            Err(pcap::Error::PcapError(e)) if e.contains("Interrupted") => return Ok(()),
+            Err(e) => return Err(CaptureError::PcapError(e.to_string())),
         }
+    }
+}
 
-        if first_packet_analyzed {
-            // If we've already analyzed the first packet, wait for user input
-            println!("Press Enter to continue capturing packets...");
-            let mut input = String::new();
-            io::stdin().read_line(&mut input)?;
-            first_packet_analyzed = false; // Reset so we can continue capturing
-            println!("Continuing packet capture...");
-        }
+/// How often an AI capture checks the configuration, libpcap statistics and the control socket
+const HOUSEKEEPING_INTERVAL: Duration = Duration::from_millis(100);
 
-        match cap.next_packet() {
-            Ok(packet) => {
-                info!(
-                    "PACKET len = {}, ts = {}.{}",
-                    packet.data.len(),
-                    packet.header.ts.tv_sec,
-                    packet.header.ts.tv_usec
-                );
-                
-                // Parse frame control information from the packet
-                if let Some(frame_control) = analyze_frame_control(&packet.data) {
-                    info!("Frame Control: {}", frame_control);
+/// AI analyses running at once; flows waiting for review start as others finish
+const MAX_ANALYSES_IN_FLIGHT: usize = 4;
+
+/// What asked for an AI analysis, to know where the result goes
+enum AnalysisOrigin {
+    FirstPacket,
+    Terminal,
+    Control(ClientId),
+    /// A flow that ended or was flagged, whose verdict is attached to it in the report
+    Flow(Box<FlowReviewRequest>),
+}
+
+type AnalysisResult = (AnalysisOrigin, Result<SecurityAnalysis, String>);
+
+/// Runs one AI analysis as its own task, so the capture goes on while the API answers
+fn spawn_analysis(tasks: &mut JoinSet<AnalysisResult>, analyzer: &AIAnalyzer, packet: StoredPacket, network: &LocalNetwork, origin: AnalysisOrigin) {
+    let analyzer = analyzer.clone();
+    let network = network.clone();
+    tasks.spawn(async move { (origin, analyzer.analyze_packet_security(&packet.as_packet(), &network).await.map_err(|e| e.to_string())) });
+}
+
+/// Submits queued flows while fewer than [`MAX_ANALYSES_IN_FLIGHT`] analyses are running
+fn spawn_flow_reviews(tasks: &mut JoinSet<AnalysisResult>, analyzer: &AIAnalyzer, monitors: &mut Monitors<'_>) {
+    while tasks.len() < MAX_ANALYSES_IN_FLIGHT
+        && let Some(request) = monitors.next_flow_review()
+    {
+        let analyzer = analyzer.clone();
+        let network = monitors.stamper.network().clone();
+        tasks.spawn(async move {
+            let result = analyzer.analyze_flow(&request, &network).await.map_err(|e| e.to_string());
+            (AnalysisOrigin::Flow(Box::new(request)), result)
+        });
+    }
+}
+
+fn print_analysis(heading: &str, analysis: &SecurityAnalysis) {
+    println!("\n==== {} ====", heading);
+    println!("Security Score: {:.2}", analysis.security_score);
+    println!("\nPotential Threats:");
+    for threat in &analysis.potential_threats {
+        println!("  - {}", threat);
+    }
+    if !analysis.attack_techniques.is_empty() {
+        println!("\nATT&CK Techniques: {}", analysis.attack_techniques.join(", "));
+    }
+    println!("\nRecommendations:");
+    for recommendation in &analysis.recommendations {
+        println!("  - {}", recommendation);
+    }
+    println!("==============================\n");
+}
+
+/// Lines typed on the terminal. They are read on a thread of their own: a
+/// blocking read inside the runtime would keep it from shutting down.
+fn terminal_lines() -> Option<UnboundedReceiver<String>> {
+    if !io::stdin().is_terminal() {
+        return None;
+    }
+    let (lines, receiver) = unbounded_channel();
+    thread::Builder::new()
+        .name("stdin".to_string())
+        .spawn(move || {
+            for line in io::stdin().lines() {
+                if line.is_err() || lines.send(line.unwrap_or_default()).is_err() {
+                    return;
+                }
+            }
+        })
+        .ok()?;
+    Some(receiver)
+}
+
+/// The next line typed, or never when there is no terminal or it was closed
+async fn next_line(input: &mut Option<UnboundedReceiver<String>>) -> Option<String> {
+    match input {
+        Some(lines) => lines.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn start_capture_with_ai(
+    interface_name: &str,
+    mut analyzer: AIAnalyzer,
+    filter: &CaptureFilter,
+    options: &CaptureOptions,
+    mut reloader: Option<Reloader<'_>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (cap, mut session) = CaptureSession::open(interface_name, filter, options, "ai")?;
+    session.monitors.enable_flow_review();
+    let mut packets = PacketStream::new(cap)?;
+
+    let mut count = 0;
+    let mut latest: Option<StoredPacket> = None;
+    let mut analyses = JoinSet::new();
+    let mut housekeeping = tokio::time::interval(HOUSEKEEPING_INTERVAL);
+    let mut stats_timer = tokio::time::interval(options.stats_interval);
+    let mut input = terminal_lines();
+    let interrupted = tokio::signal::ctrl_c();
+    tokio::pin!(interrupted);
+
+    loop {
+        tokio::select! {
+            packet = packets.next_packet() => {
+                let packet = match packet {
+                    Ok(packet) => packet,
+                    Err(pcap::Error::PcapError(e)) if e.contains("Interrupted") => {
+                        warn!("Capture interrupted cleanly");
+                        break;
+                    }
+                    Err(pcap::Error::PcapError(e)) if e.contains("Operation not permitted") => {
+                        error!("Missing privileges. Try:\n{}", privileges::privilege_hint());
+                        break;
+                    }
+                    Err(e) => {
+                        error!("Unknown error: {:?}", e);
+                        break;
+                    }
+                };
+                if !session.handle(&packet.as_packet())? {
+                    // Filtered out: not counted so the first displayed packet is analyzed
+                    continue;
                 }
-                
                 count += 1;
-                
-                // Analyze first packet with AI
                 if count == 1 {
                     println!("Analyzing security of first packet...");
-                    
-                    match analyzer.analyze_packet_security(&packet).await {
-                        Ok(analysis) => {
-                            println!("\n==== AI SECURITY ANALYSIS ====");
-                            println!("Security Score: {:.2}", analysis.security_score);
-                            println!("\nPotential Threats:");
-                            for threat in &analysis.potential_threats {
-                                println!("  - {}", threat);
-                            }
-                            println!("\nRecommendations:");
-                            for recommendation in &analysis.recommendations {
-                                println!("  - {}", recommendation);
+                    spawn_analysis(&mut analyses, &analyzer, packet.clone(), session.monitors.stamper.network(), AnalysisOrigin::FirstPacket);
+                }
+                latest = Some(packet);
+                spawn_flow_reviews(&mut analyses, &analyzer, &mut session.monitors);
+            }
+            Some(joined) = analyses.join_next() => {
+                let Ok((origin, result)) = joined else { continue };
+                spawn_flow_reviews(&mut analyses, &analyzer, &mut session.monitors);
+                let (origin, result) = match (origin, result) {
+                    (AnalysisOrigin::Flow(request), result) => {
+                        match result {
+                            Ok(verdict) => {
+                                print_analysis(&format!("AI FLOW ANALYSIS: {}", request.flow.key), &verdict);
+                                if let Some(report) = &mut session.monitors.session {
+                                    report.push_reviewed_flow(ReviewedFlow { request: *request, verdict });
+                                }
                             }
-                            println!("==============================\n");
-                            
-                            first_packet_analyzed = true;
-                        },
-                        Err(e) => {
-                            eprintln!("Error analyzing packet: {}", e);
-                            // Continue capturing even if AI analysis fails
+                            Err(e) => eprintln!("Error analyzing flow {}: {}", request.flow.key, e),
+                        }
+                        continue;
+                    }
+                    other => other,
+                };
+                let response = match result {
+                    Ok(analysis) => {
+                        let response = control::success(serde_json::json!({ "analysis": analysis }));
+                        if !matches!(origin, AnalysisOrigin::Control(_)) {
+                            print_analysis("AI SECURITY ANALYSIS", &analysis);
+                            println!("Press Enter to analyze the latest packet");
+                        }
+                        if let Some(report) = &mut session.monitors.session {
+                            report.push_analysis(analysis);
                         }
+                        response
+                    }
+                    Err(e) => {
+                        // Capturing goes on even if the AI analysis fails
+                        eprintln!("Error analyzing packet: {}", e);
+                        control::failure(&format!("AI analysis failed: {}", e))
                     }
+                };
+                if let AnalysisOrigin::Control(client) = origin {
+                    session.reply(client, &response);
                 }
             }
-            Err(pcap::Error::PcapError(e)) if e.contains("Packets are not available") => {
-                thread::sleep(Duration::from_micros(500));
-            }
-            Err(pcap::Error::TimeoutExpired) => {
-                thread::sleep(Duration::from_micros(500));
+            line = next_line(&mut input) => match (line, &latest) {
+                (None, _) => input = None,
+                (Some(_), Some(packet)) => {
+                    println!("Analyzing security of the latest packet...");
+                    spawn_analysis(&mut analyses, &analyzer, packet.clone(), session.monitors.stamper.network(), AnalysisOrigin::Terminal);
+                }
+                (Some(_), None) => println!("No packet captured yet"),
+            },
+            _ = stats_timer.tick() => session.update_stats(packets.capture(), count),
+            _ = housekeeping.tick() => {
+                if let Some((config, new_filter, new_options)) = reloader.as_mut().and_then(|r| r.poll()) {
+                    session.reload(packets.capture(), (new_filter, new_options));
+                    analyzer.configure(&config.ai);
+                }
+                for (client, request) in session.control_requests() {
+                    match request {
+                        // Answered when the analysis task finishes
+                        Ok(ControlRequest::Analyze) => match &latest {
+                            Some(packet) => spawn_analysis(&mut analyses, &analyzer, packet.clone(), session.monitors.stamper.network(), AnalysisOrigin::Control(client)),
+                            None => session.reply(client, &control::failure("No packet captured yet")),
+                        },
+                        Ok(request) => session.answer(client, request, packets.capture(), count),
+                        Err(e) => session.reply(client, &control::failure(&e)),
+                    }
+                }
             }
-            Err(pcap::Error::PcapError(e)) if e.contains("Interrupted") => {
+            _ = &mut interrupted => {
                 warn!("Capture interrupted cleanly");
                 break;
             }
-            Err(pcap::Error::PcapError(e)) if e.contains("Operation not permitted") => {
-                error!("Missing privileges. Try:\nsudo setcap cap_net_raw,cap_net_admin=eip ./your_binary");
-                break;
-            }
-            Err(e) => {
-                error!("Unknown error: {:?}", e);
-                break;
-            }
         }
     }
 
-    info!("Capture completed. Total packets: {}", count);
+    session.finish(count);
     Ok(())
 }
-
-/// Analyzes a packet's raw data and extracts frame control information
-fn analyze_frame_control(data: &[u8]) -> Option<FrameControlInfo> {
-    if data.len() < 14 {  // Minimum Ethernet frame size
-        debug!("Packet too small to contain valid frame control data");
-        return None;
-    }
-    
-    // Try to parse as Ethernet frame
-    match EthernetFrame::parse(data) {
-        Ok(eth_frame) => Some(eth_frame.get_frame_control()),
-        Err(e) => {
-            debug!("Failed to parse frame control: {}", e);
-            None
-        }
-    }
-}
@@ -0,0 +1,265 @@
+use crate::localnet::LocalNetwork;
+use crate::protocols::sll::{self, PacketType, SllHeader};
+use crate::protocols::summary::PacketSummary;
+use pcap::{PacketHeader, Precision};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Which way a packet travelled relative to the local network
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// From outside to a local address
+    Inbound,
+    /// From a local address to outside
+    Outbound,
+    /// Between two local addresses
+    Local,
+    /// Between two outside addresses, as seen on a router or tap
+    Transit,
+    Unknown,
+}
+
+impl Direction {
+    /// What the link layer records; only Linux cooked captures (the "any"
+    /// device) say whether a packet was sent or received
+    pub fn from_link(datalink: i32, data: &[u8]) -> Self {
+        match SllHeader::parse(datalink, data).map(|header| header.packet_type()) {
+            Ok(PacketType::Outgoing) => Direction::Outbound,
+            Ok(PacketType::Host | PacketType::Broadcast | PacketType::Multicast) => Direction::Inbound,
+            _ => Direction::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Direction::Inbound => "inbound",
+            Direction::Outbound => "outbound",
+            Direction::Local => "local",
+            Direction::Transit => "transit",
+            Direction::Unknown => "unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Packets and bytes seen in one direction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Volume {
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+/// Traffic totals per direction, for separate inbound and outbound bandwidth
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct DirectionStats(BTreeMap<Direction, Volume>);
+
+impl DirectionStats {
+    pub fn push(&mut self, direction: Direction, bytes: u64) {
+        let volume = self.0.entry(direction).or_default();
+        volume.packets += 1;
+        volume.bytes += bytes;
+    }
+
+    pub fn get(&self, direction: Direction) -> Volume {
+        self.0.get(&direction).copied().unwrap_or_default()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Direction, Volume)> + '_ {
+        self.0.iter().map(|(&direction, &volume)| (direction, volume))
+    }
+}
+
+/// What the capture knows about a packet besides its bytes
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketMeta {
+    /// Position among the packets that passed the filters, from 1
+    pub number: u64,
+    /// Interface the packet was captured on; the ingress interface on "any"
+    pub interface: Arc<str>,
+    pub datalink: i32,
+    /// Bytes captured, at most the snaplen
+    pub caplen: u32,
+    /// Bytes on the wire
+    pub wirelen: u32,
+    pub timestamp: SystemTime,
+    /// Whether the timestamp has nanoseconds, from a capture opened with
+    /// nanosecond precision
+    pub precision: Precision,
+    /// Time since the first packet of the capture
+    pub since_start: Duration,
+    /// Time since the packet before, zero for the first
+    pub since_previous: Duration,
+    pub direction: Direction,
+}
+
+impl PacketMeta {
+    /// Capture time in seconds since the epoch
+    pub fn time(&self) -> f64 {
+        self.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+    }
+
+    /// Whether the snaplen cut the packet short
+    pub fn truncated(&self) -> bool {
+        self.caplen < self.wirelen
+    }
+
+    /// A header with a microsecond timestamp, for outputs that assume one
+    /// whatever the capture's precision
+    pub fn micro_header(&self) -> PacketHeader {
+        let since_epoch = self.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let ts = libc::timeval { tv_sec: since_epoch.as_secs() as libc::time_t, tv_usec: since_epoch.subsec_micros() as libc::suseconds_t };
+        PacketHeader { ts, caplen: self.caplen, len: self.wirelen }
+    }
+}
+
+/// Numbers the packets of one capture and fills in their metadata
+pub struct Stamper {
+    interface: Arc<str>,
+    numbered: u64,
+    /// Ingress interface names by index, looked up once each
+    names: HashMap<u32, Arc<str>>,
+    network: LocalNetwork,
+    /// Timestamps of the first and the latest packet
+    first: Option<SystemTime>,
+    previous: Option<SystemTime>,
+    /// What the fraction in packet headers counts
+    precision: Precision,
+}
+
+impl Stamper {
+    pub fn new(interface: &str, network: LocalNetwork) -> Self {
+        Stamper { interface: interface.into(), numbered: 0, names: HashMap::new(), network, first: None, previous: None, precision: Precision::Micro }
+    }
+
+    /// The local network packets are classified against, which detectors
+    /// consult and which learns from the traffic
+    pub fn network(&self) -> &LocalNetwork {
+        &self.network
+    }
+
+    pub fn network_mut(&mut self) -> &mut LocalNetwork {
+        &mut self.network
+    }
+
+    /// Reads header timestamps as nanoseconds, for captures opened with that precision
+    pub fn set_precision(&mut self, precision: Precision) {
+        self.precision = precision;
+    }
+
+    /// By address when the local prefixes are known and the packet is IP,
+    /// else by what the link layer records
+    fn direction(&self, datalink: i32, data: &[u8]) -> Direction {
+        if !self.network.prefixes().is_empty()
+            && let Some(summary) = PacketSummary::from_link(datalink, data)
+            && let (Some(src), Some(dst)) = (summary.src_ip, summary.dst_ip)
+        {
+            return self.network.classify(src, dst);
+        }
+        Direction::from_link(datalink, data)
+    }
+
+    pub fn stamp(&mut self, datalink: i32, header: &PacketHeader, data: &[u8]) -> PacketMeta {
+        self.numbered += 1;
+        let fraction = header.ts.tv_usec.max(0) as u32;
+        let nanos = if self.precision == Precision::Nano { fraction } else { fraction.saturating_mul(1000) };
+        let since_epoch = Duration::new(header.ts.tv_sec.max(0) as u64, nanos);
+        let timestamp = UNIX_EPOCH + since_epoch;
+        // Out-of-order timestamps give zero rather than a negative time
+        let first = *self.first.get_or_insert(timestamp);
+        let since_start = timestamp.duration_since(first).unwrap_or_default();
+        let since_previous = self.previous.replace(timestamp).and_then(|previous| timestamp.duration_since(previous).ok()).unwrap_or_default();
+        PacketMeta {
+            number: self.numbered,
+            interface: self.ingress_interface(datalink, data),
+            datalink,
+            caplen: header.caplen,
+            wirelen: header.len,
+            timestamp,
+            precision: self.precision,
+            since_start,
+            since_previous,
+            direction: self.direction(datalink, data),
+        }
+    }
+
+    /// The ingress interface of an SLL2 packet, else the capture's interface
+    fn ingress_interface(&mut self, datalink: i32, data: &[u8]) -> Arc<str> {
+        let index = (datalink == sll::DLT_LINUX_SLL2).then(|| SllHeader::parse(datalink, data).ok()).flatten().and_then(|h| h.interface_index());
+        let Some(index) = index else { return self.interface.clone() };
+        self.names.entry(index).or_insert_with(|| sll::interface_name(index).unwrap_or_else(|| format!("#{}", index)).into()).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+    use std::net::IpAddr;
+
+    fn header(caplen: u32, len: u32) -> PacketHeader {
+        PacketHeader { ts: libc::timeval { tv_sec: 1_700_000_000, tv_usec: 250_000 }, caplen, len }
+    }
+
+    #[test]
+    fn numbers_and_stamps_packets() {
+        let mut stamper = Stamper::new("eth0", LocalNetwork::default());
+        let first = stamper.stamp(1, &header(60, 60), &[0; 60]);
+        let second = stamper.stamp(1, &header(96, 1500), &[0; 96]);
+        assert_eq!((first.number, second.number), (1, 2));
+        assert_eq!(&*first.interface, "eth0");
+        assert_eq!(first.time(), 1_700_000_000.25);
+        assert!(!first.truncated() && second.truncated());
+        let mut later = header(60, 60);
+        later.ts.tv_sec += 2;
+        let third = stamper.stamp(1, &later, &[0; 60]);
+        assert_eq!((first.since_start, third.since_start, third.since_previous), (Duration::ZERO, Duration::from_secs(2), Duration::from_secs(2)));
+        assert_eq!(first.micro_header().ts.tv_usec, 250_000);
+
+        stamper.set_precision(Precision::Nano);
+        let nano = stamper.stamp(1, &header(60, 60), &[0; 60]);
+        assert_eq!(nano.timestamp.duration_since(UNIX_EPOCH).unwrap().subsec_nanos(), 250_000);
+        assert_eq!(nano.micro_header().ts.tv_usec, 250);
+        assert_eq!(first.direction, Direction::Unknown);
+    }
+
+    #[test]
+    fn reads_the_direction_of_cooked_packets() {
+        // SLL v1: packet type, ARPHRD, address length, address, protocol
+        let mut cooked = vec![0, 4, 0, 1, 0, 6, 0, 0, 0, 0, 0, 0, 0, 0, 0x08, 0x00];
+        assert_eq!(Direction::from_link(sll::DLT_LINUX_SLL, &cooked), Direction::Outbound);
+        cooked[1] = 0;
+        assert_eq!(Direction::from_link(sll::DLT_LINUX_SLL, &cooked), Direction::Inbound);
+        cooked[1] = 3;
+        assert_eq!(Direction::from_link(sll::DLT_LINUX_SLL, &cooked), Direction::Unknown);
+    }
+
+    #[test]
+    fn classifies_by_local_networks() {
+        let local = LocalNetwork::with_prefixes(vec!["192.168.1.0/24".parse().unwrap(), "fd00::/8".parse().unwrap()]);
+        let ip = |text: &str| text.parse::<IpAddr>().unwrap();
+        assert_eq!(local.classify(ip("192.168.1.10"), ip("93.184.216.34")), Direction::Outbound);
+        assert_eq!(local.classify(ip("93.184.216.34"), ip("192.168.1.10")), Direction::Inbound);
+        assert_eq!(local.classify(ip("192.168.1.10"), ip("192.168.1.1")), Direction::Local);
+        assert_eq!(local.classify(ip("fd00::1"), ip("ff02::fb")), Direction::Local);
+        assert_eq!(local.classify(ip("8.8.8.8"), ip("1.1.1.1")), Direction::Transit);
+        assert_eq!(LocalNetwork::default().classify(ip("192.168.1.10"), ip("1.1.1.1")), Direction::Unknown);
+
+        let mut stamper = Stamper::new("eth0", local);
+        let frame = testutil::ipv4_udp_frame(5353, 53, b"");
+        let meta = stamper.stamp(1, &header(frame.len() as u32, frame.len() as u32), &frame);
+        assert_eq!(meta.direction, Direction::Outbound);
+
+        let mut stats = DirectionStats::default();
+        stats.push(meta.direction, meta.wirelen as u64);
+        stats.push(Direction::Outbound, 100);
+        assert_eq!(stats.get(Direction::Outbound), Volume { packets: 2, bytes: frame.len() as u64 + 100 });
+        assert_eq!(stats.get(Direction::Inbound), Volume::default());
+    }
+}
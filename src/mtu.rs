@@ -0,0 +1,218 @@
+use crate::protocols::ethernet::MAX_STANDARD_FRAME;
+use crate::protocols::icmp::{IcmpKind, IcmpMessage, OriginalDatagram};
+use crate::protocols::summary::PacketSummary;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+
+/// Upper bounds of the histogram buckets, as in the RMON etherStats
+/// counters plus jumbo ranges; the last bucket is open-ended
+const BUCKETS: [usize; 9] = [64, 127, 255, 511, 1023, MAX_STANDARD_FRAME, 2047, 4095, 9216];
+
+/// Retransmissions of an oversized segment after an ICMP "too big" before a flow is flagged
+pub const RETRANSMIT_THRESHOLD: u32 = 2;
+
+/// How long a "too big" report is matched against later segments, in seconds
+const REPORT_TIMEOUT: f64 = 60.0;
+
+/// Frame lengths (as on the wire, not as captured) in size buckets
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrameSizeHistogram {
+    counts: [u64; BUCKETS.len() + 1],
+    pub largest: usize,
+}
+
+impl FrameSizeHistogram {
+    pub fn push(&mut self, len: usize) {
+        let bucket = BUCKETS.iter().position(|&max| len <= max).unwrap_or(BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.largest = self.largest.max(len);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Frames longer than a standard Ethernet frame
+    pub fn jumbo(&self) -> u64 {
+        let standard = BUCKETS.iter().position(|&max| max == MAX_STANDARD_FRAME).expect("standard bucket");
+        self.counts[standard + 1..].iter().sum()
+    }
+
+    /// Non-empty buckets as (label, count)
+    pub fn buckets(&self) -> Vec<(String, u64)> {
+        let mut lower = 0;
+        let mut buckets = Vec::new();
+        for (i, &count) in self.counts.iter().enumerate() {
+            let label = match BUCKETS.get(i) {
+                Some(&upper) => format!("{}-{}", lower, upper),
+                None => format!("{}+", lower),
+            };
+            if count > 0 {
+                buckets.push((label, count));
+            }
+            lower = BUCKETS.get(i).map_or(lower, |upper| upper + 1);
+        }
+        buckets
+    }
+}
+
+impl fmt::Display for FrameSizeHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let buckets: Vec<String> = self.buckets().into_iter().map(|(label, count)| format!("{}: {}", label, count)).collect();
+        write!(f, "{} frames, {} jumbo, largest {} bytes [{}]", self.total(), self.jumbo(), self.largest, buckets.join(", "))
+    }
+}
+
+/// Frame size histograms per capture interface
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FrameSizes {
+    interfaces: BTreeMap<String, FrameSizeHistogram>,
+}
+
+impl FrameSizes {
+    pub fn push(&mut self, interface: &str, len: usize) {
+        if let Some(histogram) = self.interfaces.get_mut(interface) {
+            histogram.push(len);
+        } else {
+            self.interfaces.entry(interface.to_string()).or_default().push(len);
+        }
+    }
+
+    pub fn interfaces(&self) -> &BTreeMap<String, FrameSizeHistogram> {
+        &self.interfaces
+    }
+}
+
+/// The segment quoted by an ICMP "too big" message and what followed it
+#[derive(Debug)]
+struct TooBig {
+    mtu: u32,
+    reporter: IpAddr,
+    seq: u32,
+    time: f64,
+    retransmits: u32,
+}
+
+/// Flags TCP flows with likely path MTU problems: an ICMP "fragmentation
+/// needed" (IPv4) or "packet too big" (ICMPv6) report followed by
+/// retransmissions of the same oversized segment, meaning the sender never
+/// lowered its segment size (a PMTU black hole). Lost fragments reported by
+/// ICMP "reassembly time exceeded" are flagged as well.
+#[derive(Debug, Default)]
+pub struct MtuMonitor {
+    reports: HashMap<(SocketAddr, SocketAddr), TooBig>,
+}
+
+impl MtuMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds one summarized packet, returning alert messages
+    pub fn push(&mut self, summary: &PacketSummary, data: &[u8], time: f64) -> Vec<String> {
+        match summary.ip_proto {
+            Some(1 | 58) => self.icmp(summary, summary.payload(data), time),
+            Some(6) => self.tcp(summary, data, time),
+            _ => Vec::new(),
+        }
+    }
+
+    fn icmp(&mut self, summary: &PacketSummary, icmp: &[u8], time: f64) -> Vec<String> {
+        let message = if summary.ip_proto == Some(58) { IcmpMessage::parse_v6(icmp) } else { IcmpMessage::parse(icmp) };
+        let (Some(reporter), Ok(message)) = (summary.src_ip, message) else { return Vec::new() };
+        let Some(quoted) = message.original_datagram() else { return Vec::new() };
+        if let Some(mtu) = message.mtu() {
+            self.too_big(reporter, mtu, &quoted, time);
+            return Vec::new();
+        }
+        // Fragment reassembly time exceeded
+        if message.kind() != IcmpKind::TimeExceeded || message.code() != 1 {
+            return Vec::new();
+        }
+        vec![format!(
+            "Fragments lost: {} timed out reassembling a packet from {} to {}; check for MTU mismatches or filtered fragments",
+            reporter, quoted.src, quoted.dst
+        )]
+    }
+
+    fn too_big(&mut self, reporter: IpAddr, mtu: u32, quoted: &OriginalDatagram<'_>, time: f64) {
+        let (6, Some((src_port, dst_port))) = (quoted.protocol, quoted.ports) else { return };
+        let seq = quoted.payload.get(4..8).map_or(0, |s| u32::from_be_bytes([s[0], s[1], s[2], s[3]]));
+        let key = (SocketAddr::new(quoted.src, src_port), SocketAddr::new(quoted.dst, dst_port));
+        self.reports.retain(|_, report| time - report.time < REPORT_TIMEOUT);
+        self.reports.insert(key, TooBig { mtu, reporter, seq, time, retransmits: 0 });
+    }
+
+    fn tcp(&mut self, summary: &PacketSummary, data: &[u8], time: f64) -> Vec<String> {
+        if self.reports.is_empty() {
+            return Vec::new();
+        }
+        let (Some(src), Some(dst), Some(src_port), Some(dst_port)) = (summary.src_ip, summary.dst_ip, summary.src_port, summary.dst_port)
+        else {
+            return Vec::new();
+        };
+        let key = (SocketAddr::new(src, src_port), SocketAddr::new(dst, dst_port));
+        let Some(report) = self.reports.get_mut(&key) else { return Vec::new() };
+        let (Some(seq), Some(transport), Some(ip_end)) = (summary.tcp_seq(data), summary.transport_offset, summary.ip_end) else {
+            return Vec::new();
+        };
+        let ip_header = if src.is_ipv6() { 40 } else { 20 };
+        let size = ip_end.saturating_sub(transport) + ip_header;
+        if seq != report.seq || size as u32 <= report.mtu || time - report.time >= REPORT_TIMEOUT {
+            return Vec::new();
+        }
+        report.retransmits += 1;
+        if report.retransmits != RETRANSMIT_THRESHOLD {
+            return Vec::new();
+        }
+        vec![format!(
+            "Likely path MTU black hole on {} -> {}: {} reported a {}-byte MTU, but the {}-byte segment at seq {} was retransmitted {} times unchanged",
+            key.0, key.1, report.reporter, report.mtu, size, seq, report.retransmits
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn buckets_frame_sizes() {
+        let mut sizes = FrameSizes::default();
+        for len in [60, 60, 1514, 1518, 1522, 9014, 16000] {
+            sizes.push("eth0", len);
+        }
+        sizes.push("lo", 65536);
+        let eth0 = &sizes.interfaces()["eth0"];
+        assert_eq!((eth0.total(), eth0.jumbo(), eth0.largest), (7, 3, 16000));
+        assert_eq!(
+            eth0.to_string(),
+            "7 frames, 3 jumbo, largest 16000 bytes [0-64: 2, 1024-1518: 2, 1519-2047: 1, 4096-9216: 1, 9217+: 1]"
+        );
+        assert_eq!(sizes.interfaces()["lo"].jumbo(), 1);
+    }
+
+    #[test]
+    fn flags_pmtu_black_holes() {
+        let (client, server, router) = (testutil::client_v4(), testutil::server_v4(), Ipv4Addr::new(10, 0, 0, 1));
+        let segment = testutil::ipv4(server, client, 6, &testutil::tcp(443, 40000, 5000, 1, testutil::TCP_ACK, &[0; 1460]));
+        let frame = testutil::ethernet(testutil::SRC_MAC, testutil::DST_MAC, testutil::ETHERTYPE_IPV4, &segment);
+        let mut icmp = vec![3, 4, 0, 0, 0, 0, 0x05, 0x78]; // next-hop MTU 1400
+        icmp.extend_from_slice(&segment[..28]);
+        let too_big = testutil::ethernet(testutil::SRC_MAC, testutil::DST_MAC, testutil::ETHERTYPE_IPV4, &testutil::ipv4(router, server, 1, &icmp));
+
+        let mut monitor = MtuMonitor::new();
+        let mut push = |data: &[u8], time: f64| monitor.push(&PacketSummary::from_ethernet(data).unwrap(), data, time);
+        assert!(push(&frame, 0.0).is_empty());
+        assert!(push(&too_big, 0.1).is_empty());
+        assert!(push(&frame, 0.5).is_empty());
+        let alerts = push(&frame, 1.5);
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].contains("93.184.216.34:443 -> 192.168.1.10:40000: 10.0.0.1 reported a 1400-byte MTU"));
+        assert!(push(&frame, 3.0).is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+use crate::error::CaptureError;
+use log::info;
+use std::fs::File;
+use std::io;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Directory where `ip netns add` keeps its named namespaces
+pub const NETNS_RUN_DIR: &str = "/var/run/netns";
+
+/// Resolves a `--netns` argument: a PID selects that process's namespace,
+/// a path is used as is and anything else is a name created by `ip netns`
+pub fn namespace_path(target: &str) -> PathBuf {
+    if target.parse::<u32>().is_ok() {
+        PathBuf::from(format!("/proc/{}/ns/net", target))
+    } else if target.contains('/') {
+        PathBuf::from(target)
+    } else {
+        PathBuf::from(NETNS_RUN_DIR).join(target)
+    }
+}
+
+/// Resolves a target like `namespace_path`, also accepting `docker:<container>`,
+/// which asks Docker for the PID of the container's init process
+pub fn resolve(target: &str) -> Result<PathBuf, CaptureError> {
+    let Some(container) = target.strip_prefix("docker:") else {
+        return Ok(namespace_path(target));
+    };
+    let output = Command::new("docker")
+        .args(["inspect", "-f", "{{.State.Pid}}", container])
+        .output()
+        .map_err(|e| CaptureError::Other(format!("Cannot run docker: {}", e)))?;
+    let pid = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    match pid.parse::<u32>() {
+        Ok(pid) if output.status.success() && pid != 0 => Ok(namespace_path(&pid.to_string())),
+        _ => Err(CaptureError::InputError(format!("Container '{}' is not running", container))),
+    }
+}
+
+/// Moves the calling thread into the target network namespace. Only that
+/// thread switches, so it must be the one that lists devices and opens the capture.
+pub fn enter(target: &str) -> Result<(), CaptureError> {
+    let path = resolve(target)?;
+    let file = File::open(&path)
+        .map_err(|e| CaptureError::InputError(format!("Cannot open network namespace {}: {}", path.display(), e)))?;
+    setns(&file).map_err(|e| {
+        CaptureError::Other(format!("Cannot enter network namespace {}: {} (requires CAP_SYS_ADMIN)", path.display(), e))
+    })?;
+    info!("Entered network namespace {}", path.display());
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn setns(file: &File) -> io::Result<()> {
+    if unsafe { libc::setns(file.as_raw_fd(), libc::CLONE_NEWNET) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn setns(_file: &File) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "network namespaces are Linux-only"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_targets() {
+        assert_eq!(namespace_path("1234"), PathBuf::from("/proc/1234/ns/net"));
+        assert_eq!(namespace_path("vpn"), PathBuf::from("/var/run/netns/vpn"));
+        assert_eq!(namespace_path("/run/docker/netns/4f2a"), PathBuf::from("/run/docker/netns/4f2a"));
+        assert!(enter("/nonexistent/netns").is_err());
+    }
+}
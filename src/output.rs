@@ -0,0 +1,153 @@
+use crate::color::Palette;
+use crate::follow::hexdump;
+use crate::meta::{Direction, PacketMeta};
+use crate::protocols::analyze_link_layer;
+use crate::protocols::summary::PacketSummary;
+use crate::timestamp::{format_iso8601, local_offset};
+use pcap::Precision;
+use std::fmt::Write;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// How much of each packet the text output shows, set with `-q`, `-v` and
+/// `-vv`; diagnostics stay under `RUST_LOG`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// No per-packet output, only alerts and end-of-capture summaries
+    Quiet,
+    /// One line per packet and its decoded fields
+    #[default]
+    Normal,
+    /// Also what each field means
+    Verbose,
+    /// Also a hexdump of the captured bytes
+    Debug,
+}
+
+impl Verbosity {
+    pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Debug,
+        }
+    }
+}
+
+/// How packet times are shown (`--time-format`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// Seconds since the epoch with microseconds: `1700000000.000250`
+    #[default]
+    Epoch,
+    /// ISO 8601 in UTC: `2023-11-14T22:13:20.000250Z`
+    Iso,
+    /// ISO 8601 in the system time zone, with its offset
+    Local,
+    /// Seconds since the packet before: `+0.000250`
+    Delta,
+    /// Seconds since the first packet of the capture
+    Relative,
+}
+
+impl TimeFormat {
+    pub fn format(self, meta: &PacketMeta) -> String {
+        let since_epoch = meta.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let nanos = meta.precision == Precision::Nano;
+        let seconds = |time: Duration| match nanos {
+            true => format!("{}.{:09}", time.as_secs(), time.subsec_nanos()),
+            false => format!("{}.{:06}", time.as_secs(), time.subsec_micros()),
+        };
+        match self {
+            TimeFormat::Epoch => seconds(since_epoch),
+            TimeFormat::Iso => format_iso8601(since_epoch, 0, nanos),
+            TimeFormat::Local => format_iso8601(since_epoch, local_offset(since_epoch.as_secs() as i64), nanos),
+            TimeFormat::Delta => format!("+{}", seconds(meta.since_previous)),
+            TimeFormat::Relative => seconds(meta.since_start),
+        }
+    }
+}
+
+/// A packet as text at the given verbosity, `None` when quiet. `source` tags
+/// packets captured in several namespaces at once.
+pub fn packet_text(data: &[u8], meta: &PacketMeta, verbosity: Verbosity, time_format: TimeFormat, palette: Palette, source: Option<&str>) -> Option<String> {
+    if verbosity == Verbosity::Quiet {
+        return None;
+    }
+    let line = format!(
+        "PACKET #{} {}len = {}{}, ts = {}{}",
+        meta.number,
+        source.map(|s| format!("[{}] ", s)).unwrap_or_default(),
+        data.len(),
+        if meta.truncated() { format!(" (truncated from {})", meta.wirelen) } else { String::new() },
+        time_format.format(meta),
+        if meta.direction == Direction::Unknown { String::new() } else { format!(", {} on {}", meta.direction, meta.interface) }
+    );
+    let summary = palette.enabled().then(|| PacketSummary::from_link(meta.datalink, data)).flatten();
+    let mut text = palette.protocol(summary.as_ref(), &line);
+    if let Some(info) = analyze_link_layer(meta.datalink, data) {
+        let _ = write!(text, "\nProtocol: {:?}", info.protocol_type);
+        for field in &info.control_fields {
+            let _ = match verbosity {
+                Verbosity::Normal => write!(text, "\n  {}", field),
+                _ => write!(text, "\n  {}: {} ({})", field.name, field.value, field.description),
+            };
+        }
+    }
+    if verbosity == Verbosity::Debug {
+        text.push('\n');
+        text.push_str(hexdump(data, 0, "  ").trim_end());
+    }
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+    use std::sync::Arc;
+
+    fn meta(len: usize) -> PacketMeta {
+        PacketMeta {
+            number: 7,
+            interface: Arc::from("eth0"),
+            datalink: 1,
+            caplen: len as u32,
+            wirelen: len as u32,
+            timestamp: UNIX_EPOCH + Duration::from_micros(1_700_000_000_000_250),
+            precision: Precision::Micro,
+            since_start: Duration::from_millis(1500),
+            since_previous: Duration::from_micros(250),
+            direction: Direction::Outbound,
+        }
+    }
+
+    #[test]
+    fn prints_more_at_each_level() {
+        let frame = testutil::ipv4_udp_frame(5353, 53, b"");
+        let text = |verbosity| packet_text(&frame, &meta(frame.len()), verbosity, TimeFormat::Epoch, Palette::default(), None);
+        assert_eq!(text(Verbosity::Quiet), None);
+        let normal = text(Verbosity::Normal).unwrap();
+        assert!(normal.starts_with(&format!("PACKET #7 len = {}, ts = 1700000000.000250, outbound on eth0\nProtocol: ", frame.len())));
+        let verbose = text(Verbosity::Verbose).unwrap();
+        assert!(verbose.len() > normal.len() && verbose.contains(" ("));
+        let debug = text(Verbosity::Debug).unwrap();
+        assert!(debug.starts_with(&verbose) && debug.contains("00000000"));
+    }
+
+    #[test]
+    fn formats_times() {
+        let meta = meta(60);
+        assert_eq!(TimeFormat::Iso.format(&meta), "2023-11-14T22:13:20.000250Z");
+        assert_eq!(TimeFormat::Delta.format(&meta), "+0.000250");
+        assert_eq!(TimeFormat::Relative.format(&meta), "1.500000");
+    }
+
+    #[test]
+    fn maps_the_flags() {
+        assert_eq!(Verbosity::from_flags(true, 2), Verbosity::Quiet);
+        assert_eq!(Verbosity::from_flags(false, 0), Verbosity::Normal);
+        assert_eq!(Verbosity::from_flags(false, 1), Verbosity::Verbose);
+        assert_eq!(Verbosity::from_flags(false, 3), Verbosity::Debug);
+    }
+}
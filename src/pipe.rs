@@ -0,0 +1,124 @@
+use crate::backpressure::{self, Receiver, Sender, StageConfig};
+use crate::capfile::StoredPacket;
+use crate::error::CaptureError;
+use log::{info, warn};
+use pcap::{Activated, Capture, Packet, Savefile};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+
+/// Streams packets in pcap format to a named pipe so tools like Wireshark
+/// (`wireshark -k -i /tmp/sniff.fifo`) can follow the capture live. Packets
+/// are written from a thread of its own, so a slow reader only fills the
+/// `pipe` queue instead of stalling the capture.
+pub struct PipeWriter {
+    packets: Option<Sender<StoredPacket>>,
+    writer: Option<JoinHandle<()>>,
+    open: Arc<AtomicBool>,
+}
+
+impl PipeWriter {
+    /// Creates the FIFO if needed and opens it, blocking until a reader attaches
+    pub fn open<T: Activated + ?Sized>(cap: &Capture<T>, path: &Path, queue: StageConfig) -> Result<Self, CaptureError> {
+        ensure_fifo(path)?;
+        info!("Waiting for a reader on {} (e.g. wireshark -k -i {})", path.display(), path.display());
+        let savefile = cap
+            .savefile(path)
+            .map_err(|e| CaptureError::PcapError(format!("Cannot open pipe {}: {}", path.display(), e)))?;
+        info!("Reader attached to {}", path.display());
+        let (packets, receiver) = backpressure::channel("pipe", queue);
+        let open = Arc::new(AtomicBool::new(true));
+        let (path, still_open) = (path.to_path_buf(), open.clone());
+        let writer = thread::Builder::new()
+            .name("pipe".to_string())
+            .spawn(move || write_packets(savefile, &path, receiver, &still_open))
+            .map_err(|e| CaptureError::Other(format!("Cannot start the pipe writer: {}", e)))?;
+        Ok(PipeWriter { packets: Some(packets), writer: Some(writer), open })
+    }
+
+    /// Queues a packet; stops piping once the reader goes away
+    pub fn write(&mut self, packet: &Packet<'_>) {
+        if let Some(packets) = &self.packets
+            && packets.send(StoredPacket::new(*packet.header, packet.data.to_vec())).is_err()
+        {
+            self.packets = None;
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for PipeWriter {
+    /// Lets the writer finish the queued packets
+    fn drop(&mut self) {
+        self.packets = None;
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Writes and flushes each packet until the capture stops or the reader goes away
+fn write_packets(mut savefile: Savefile, path: &Path, packets: Receiver<StoredPacket>, open: &AtomicBool) {
+    for packet in packets {
+        savefile.write(&packet.as_packet());
+        if let Err(e) = savefile.flush() {
+            warn!("Reader on {} went away ({}); no longer piping", path.display(), e);
+            open.store(false, Ordering::Relaxed);
+            return;
+        }
+    }
+}
+
+/// Creates a FIFO at `path`, accepting an existing FIFO but refusing to clobber other files
+pub fn ensure_fifo(path: &Path) -> Result<(), CaptureError> {
+    match path.metadata() {
+        Ok(meta) if meta.file_type().is_fifo() => return Ok(()),
+        Ok(_) => {
+            return Err(CaptureError::InputError(format!(
+                "{} exists and is not a named pipe",
+                path.display()
+            )))
+        }
+        Err(_) => {}
+    }
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| CaptureError::InputError(e.to_string()))?;
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+        return Err(CaptureError::Other(format!(
+            "Cannot create FIFO {}: {}",
+            path.display(),
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creates_fifo_and_refuses_regular_files() {
+        let dir = std::env::temp_dir().join(format!("rust-sniffer-pipe-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let fifo = dir.join("sniff.fifo");
+        ensure_fifo(&fifo).unwrap();
+        assert!(fifo.metadata().unwrap().file_type().is_fifo());
+        // Reusing an existing FIFO is fine
+        ensure_fifo(&fifo).unwrap();
+
+        let regular = dir.join("capture.pcap");
+        std::fs::write(&regular, b"").unwrap();
+        assert!(ensure_fifo(&regular).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,285 @@
+use crate::error::CaptureError;
+use crate::protocols::frame_control::ControlField;
+use crate::protocols::summary::PacketSummary;
+use log::info;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Version of the C ABI between the sniffer and plugin libraries; bumped on
+/// any change to `PluginDescriptor` or the callbacks
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Symbol a plugin library exports, returning its `PluginDescriptor`
+pub const PLUGIN_ENTRY_POINT: &str = "rust_sniffer_plugin_v1";
+
+/// Where a dissector is invoked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Registration {
+    TcpPort(u16),
+    UdpPort(u16),
+    EtherType(u16),
+}
+
+/// Decoder for a protocol the sniffer doesn't know. Implement it in a
+/// `cdylib` crate and export it with `export_dissector!`; the sniffer loads
+/// it with `--plugin` and calls it for packets matching its registrations.
+pub trait Dissector: Send + Sync {
+    fn name(&self) -> String;
+
+    fn registrations(&self) -> Vec<Registration>;
+
+    /// Fields decoded from the TCP/UDP payload, or from the bytes after the
+    /// Ethernet header for EtherType registrations
+    fn dissect(&self, payload: &[u8]) -> Vec<ControlField>;
+}
+
+/// Callback a plugin calls once per decoded field; the strings are only borrowed for the call
+pub type EmitField = extern "C" fn(ctx: *mut c_void, name: *const c_char, value: *const c_char, description: *const c_char);
+
+/// Registration as it crosses the ABI: kind 0 is a TCP port, 1 a UDP port, 2 an EtherType
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RawRegistration {
+    pub kind: u8,
+    pub value: u16,
+}
+
+/// What a plugin's entry point returns. It lives as long as the process.
+#[repr(C)]
+pub struct PluginDescriptor {
+    pub abi_version: u32,
+    pub name: *const c_char,
+    pub registrations: *const RawRegistration,
+    pub registration_count: usize,
+    pub instance: *const c_void,
+    pub dissect: extern "C" fn(instance: *const c_void, data: *const u8, len: usize, emit: EmitField, ctx: *mut c_void),
+}
+
+impl PluginDescriptor {
+    /// Wraps a dissector in a descriptor that is never freed; used by `export_dissector!`
+    pub fn leak(dissector: Box<dyn Dissector>) -> *const PluginDescriptor {
+        let name = CString::new(dissector.name()).unwrap_or_default().into_raw();
+        let registrations: Vec<RawRegistration> = dissector
+            .registrations()
+            .into_iter()
+            .map(|registration| match registration {
+                Registration::TcpPort(port) => RawRegistration { kind: 0, value: port },
+                Registration::UdpPort(port) => RawRegistration { kind: 1, value: port },
+                Registration::EtherType(ether_type) => RawRegistration { kind: 2, value: ether_type },
+            })
+            .collect();
+        let registrations = Box::leak(registrations.into_boxed_slice());
+        let instance = Box::into_raw(Box::new(dissector)) as *const c_void;
+        Box::into_raw(Box::new(PluginDescriptor {
+            abi_version: PLUGIN_ABI_VERSION,
+            name,
+            registrations: registrations.as_ptr(),
+            registration_count: registrations.len(),
+            instance,
+            dissect: dissect_trampoline,
+        }))
+    }
+}
+
+extern "C" fn dissect_trampoline(instance: *const c_void, data: *const u8, len: usize, emit: EmitField, ctx: *mut c_void) {
+    let dissector = unsafe { &*(instance as *const Box<dyn Dissector>) };
+    let payload = unsafe { std::slice::from_raw_parts(data, len) };
+    for field in dissector.dissect(payload) {
+        let c = |s: String| CString::new(s.replace('\0', "")).unwrap_or_default();
+        let (name, value, description) = (c(field.name), c(field.value), c(field.description));
+        emit(ctx, name.as_ptr(), value.as_ptr(), description.as_ptr());
+    }
+}
+
+/// Exports a `Dissector` from a plugin library:
+/// `rust_sniffer::export_dissector!(MyProtocol::default());`
+#[macro_export]
+macro_rules! export_dissector {
+    ($dissector:expr) => {
+        #[unsafe(no_mangle)]
+        pub extern "C" fn rust_sniffer_plugin_v1() -> *const $crate::plugin::PluginDescriptor {
+            $crate::plugin::PluginDescriptor::leak(Box::new($dissector))
+        }
+    };
+}
+
+/// A dissector behind the C ABI, from a shared library or `PluginDescriptor::leak`
+pub struct LoadedPlugin {
+    descriptor: *const PluginDescriptor,
+    name: String,
+    registrations: Vec<Registration>,
+}
+
+// The descriptor is immutable and plugins must be thread safe, as `Dissector` requires
+unsafe impl Send for LoadedPlugin {}
+unsafe impl Sync for LoadedPlugin {}
+
+impl LoadedPlugin {
+    /// Loads a plugin library and checks its ABI version. The library stays
+    /// loaded for the life of the process.
+    pub fn load(path: &Path) -> Result<Self, CaptureError> {
+        let error = |what: String| CaptureError::InputError(format!("Plugin {}: {}", path.display(), what));
+        let c_path = CString::new(path.as_os_str().as_encoded_bytes()).map_err(|e| error(e.to_string()))?;
+        let handle = unsafe { libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            return Err(error(dl_error()));
+        }
+        let symbol = CString::new(PLUGIN_ENTRY_POINT).unwrap_or_default();
+        let entry = unsafe { libc::dlsym(handle, symbol.as_ptr()) };
+        if entry.is_null() {
+            return Err(error(format!("no {} symbol; was it built with export_dissector!?", PLUGIN_ENTRY_POINT)));
+        }
+        let entry: extern "C" fn() -> *const PluginDescriptor = unsafe { std::mem::transmute(entry) };
+        unsafe { Self::from_descriptor(entry()) }.map_err(error)
+    }
+
+    /// Wraps a descriptor, which must stay valid for the life of the process
+    ///
+    /// # Safety
+    /// `descriptor` must be null or point to a `PluginDescriptor` built by `PluginDescriptor::leak`
+    /// or laid out identically by a plugin of the same ABI version
+    pub unsafe fn from_descriptor(descriptor: *const PluginDescriptor) -> Result<Self, String> {
+        let Some(d) = (unsafe { descriptor.as_ref() }) else {
+            return Err("entry point returned no descriptor".to_string());
+        };
+        if d.abi_version != PLUGIN_ABI_VERSION {
+            return Err(format!("ABI version {}, expected {}", d.abi_version, PLUGIN_ABI_VERSION));
+        }
+        let name = unsafe { CStr::from_ptr(d.name) }.to_string_lossy().into_owned();
+        let raw = unsafe { std::slice::from_raw_parts(d.registrations, d.registration_count) };
+        let registrations = raw
+            .iter()
+            .filter_map(|r| match r.kind {
+                0 => Some(Registration::TcpPort(r.value)),
+                1 => Some(Registration::UdpPort(r.value)),
+                2 => Some(Registration::EtherType(r.value)),
+                _ => None,
+            })
+            .collect();
+        Ok(LoadedPlugin { descriptor, name, registrations })
+    }
+}
+
+extern "C" fn collect_field(ctx: *mut c_void, name: *const c_char, value: *const c_char, description: *const c_char) {
+    let fields = unsafe { &mut *(ctx as *mut Vec<ControlField>) };
+    let text = |s: *const c_char| if s.is_null() { String::new() } else { unsafe { CStr::from_ptr(s) }.to_string_lossy().into_owned() };
+    fields.push(ControlField { name: text(name), value: text(value), description: text(description) });
+}
+
+impl Dissector for LoadedPlugin {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn registrations(&self) -> Vec<Registration> {
+        self.registrations.clone()
+    }
+
+    fn dissect(&self, payload: &[u8]) -> Vec<ControlField> {
+        let d = unsafe { &*self.descriptor };
+        let mut fields: Vec<ControlField> = Vec::new();
+        (d.dissect)(d.instance, payload.as_ptr(), payload.len(), collect_field, &mut fields as *mut _ as *mut c_void);
+        fields
+    }
+}
+
+fn dl_error() -> String {
+    let message = unsafe { libc::dlerror() };
+    if message.is_null() {
+        return "unknown dlopen error".to_string();
+    }
+    unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned()
+}
+
+/// Dissectors consulted before the built-in ones, in registration order
+#[derive(Default)]
+pub struct DissectorRegistry {
+    dissectors: Vec<Box<dyn Dissector>>,
+}
+
+impl DissectorRegistry {
+    pub fn register(&mut self, dissector: Box<dyn Dissector>) {
+        info!("Registered dissector {} for {:?}", dissector.name(), dissector.registrations());
+        self.dissectors.push(dissector);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dissectors.is_empty()
+    }
+
+    /// Fields from the first dissector registered for the packet, if any
+    pub fn dissect(&self, summary: &PacketSummary, data: &[u8]) -> Option<Vec<ControlField>> {
+        let dissector = self.dissectors.iter().find(|d| d.registrations().iter().any(|r| matches(r, summary)))?;
+        let mut fields = vec![ControlField {
+            name: "Dissector".to_string(),
+            value: dissector.name(),
+            description: "Plugin".to_string(),
+        }];
+        fields.extend(dissector.dissect(summary.payload(data)));
+        Some(fields)
+    }
+}
+
+fn matches(registration: &Registration, summary: &PacketSummary) -> bool {
+    match *registration {
+        Registration::TcpPort(port) => summary.is_tcp() && summary.has_port(port),
+        Registration::UdpPort(port) => summary.is_udp() && summary.has_port(port),
+        Registration::EtherType(ether_type) => summary.ether_type == ether_type && summary.ip_proto.is_none(),
+    }
+}
+
+static REGISTRY: OnceLock<DissectorRegistry> = OnceLock::new();
+
+/// Makes the registry visible to packet analysis; only the first call has an effect
+pub fn install(registry: DissectorRegistry) {
+    let _ = REGISTRY.set(registry);
+}
+
+/// Plugin fields for a packet when an installed dissector is registered for it
+pub fn dissect(summary: &PacketSummary, data: &[u8]) -> Option<Vec<ControlField>> {
+    REGISTRY.get()?.dissect(summary, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    struct Echo;
+
+    impl Dissector for Echo {
+        fn name(&self) -> String {
+            "echo".to_string()
+        }
+
+        fn registrations(&self) -> Vec<Registration> {
+            vec![Registration::UdpPort(7)]
+        }
+
+        fn dissect(&self, payload: &[u8]) -> Vec<ControlField> {
+            vec![ControlField {
+                name: "Echo".to_string(),
+                value: String::from_utf8_lossy(payload).into_owned(),
+                description: format!("{} bytes", payload.len()),
+            }]
+        }
+    }
+
+    #[test]
+    fn dissects_through_the_c_abi() {
+        let plugin = unsafe { LoadedPlugin::from_descriptor(PluginDescriptor::leak(Box::new(Echo))) }.unwrap();
+        assert_eq!(plugin.registrations(), vec![Registration::UdpPort(7)]);
+        let mut registry = DissectorRegistry::default();
+        registry.register(Box::new(plugin));
+
+        let frame = testutil::ipv4_udp_frame(40000, 7, b"hello");
+        let fields = registry.dissect(&PacketSummary::from_ethernet(&frame).unwrap(), &frame).unwrap();
+        assert_eq!(fields[0].value, "echo");
+        assert_eq!((fields[1].value.as_str(), fields[1].description.as_str()), ("hello", "5 bytes"));
+
+        let other = testutil::ipv4_udp_frame(40000, 53, b"q");
+        assert!(registry.dissect(&PacketSummary::from_ethernet(&other).unwrap(), &other).is_none());
+        assert!(LoadedPlugin::load(Path::new("/nonexistent/libplugin.so")).is_err());
+    }
+}
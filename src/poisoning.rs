@@ -0,0 +1,165 @@
+use crate::protocols::ethernet::MacAddress;
+use crate::protocols::name_resolution::{NameMessage, NameProtocol};
+use crate::protocols::summary::PacketSummary;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::net::IpAddr;
+
+/// Why a responder looks like it is spoofing answers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoisoningReason {
+    /// The responder answered for more than one name; a host normally only
+    /// answers for its own, while Responder-style tools answer for anything
+    ManyNames(usize),
+    /// The name had already been answered by another host
+    Conflict { mac: MacAddress, ip: IpAddr },
+}
+
+/// A suspicious answer to an LLMNR, mDNS or NetBIOS name query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoisoningAlert {
+    pub protocol: NameProtocol,
+    pub name: String,
+    pub responder_mac: MacAddress,
+    pub responder_ip: IpAddr,
+    /// Address the answer points clients to
+    pub answer: IpAddr,
+    pub reason: PoisoningReason,
+}
+
+impl fmt::Display for PoisoningAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Possible {} poisoning: {} ({}) answered '{}' with {}",
+            self.protocol.name(),
+            self.responder_ip,
+            self.responder_mac,
+            self.name,
+            self.answer
+        )?;
+        match &self.reason {
+            PoisoningReason::ManyNames(count) => write!(f, " after answering for {} names", count),
+            PoisoningReason::Conflict { mac, ip } => write!(f, ", previously answered by {} ({})", ip, mac),
+        }
+    }
+}
+
+/// Watches responses to local name resolution queries and reports hosts
+/// that answer for names that aren't theirs
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PoisoningDetector {
+    /// Names each responder MAC has answered for
+    answered: HashMap<MacAddress, BTreeSet<String>>,
+    /// First responder seen for each name
+    owners: HashMap<String, (MacAddress, IpAddr)>,
+    alerted: HashSet<(MacAddress, String)>,
+}
+
+impl PoisoningDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds one captured packet, returning the alerts it raised
+    pub fn push(&mut self, summary: &PacketSummary, data: &[u8]) -> Vec<PoisoningAlert> {
+        // Responders are told apart by MAC, which loopback captures don't have
+        if summary.src_mac.octets() == [0; 6] {
+            return Vec::new();
+        }
+        let (Some(src_ip), Some(src_port)) = (summary.src_ip, summary.src_port) else { return Vec::new() };
+        let Some(protocol) = NameProtocol::from_port(src_port).filter(|_| summary.is_udp()) else { return Vec::new() };
+        let Ok(message) = NameMessage::parse(protocol, summary.payload(data)) else { return Vec::new() };
+        if !message.is_response() || message.opcode() != 0 {
+            return Vec::new();
+        }
+
+        let mac = summary.src_mac;
+        let mut alerts = Vec::new();
+        for answer in message.answers().into_iter().filter(|a| !a.group) {
+            let name = answer.name.to_lowercase();
+            let names = self.answered.entry(mac).or_default();
+            names.insert(name.clone());
+            let count = names.len();
+            let owner = *self.owners.entry(name.clone()).or_insert((mac, src_ip));
+
+            let reason = if owner.0 != mac {
+                PoisoningReason::Conflict { mac: owner.0, ip: owner.1 }
+            } else if count > 1 {
+                PoisoningReason::ManyNames(count)
+            } else {
+                continue;
+            };
+            if self.alerted.insert((mac, name)) {
+                alerts.push(PoisoningAlert {
+                    protocol,
+                    name: answer.name,
+                    responder_mac: mac,
+                    responder_ip: src_ip,
+                    answer: answer.address,
+                    reason,
+                });
+            }
+        }
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::name_resolution::{LLMNR_PORT, NBNS_PORT};
+    use crate::protocols::null::DLT_NULL;
+    use crate::testutil;
+    use std::net::Ipv4Addr;
+
+    const ATTACKER_MAC: [u8; 6] = [0xde, 0xad, 0xbe, 0xef, 0x00, 0x01];
+
+    fn response(mac: [u8; 6], ip: Ipv4Addr, port: u16, payload: &[u8]) -> Vec<u8> {
+        testutil::frame().src_mac(mac).ipv4(ip, testutil::client_v4()).udp(port, 50000).payload(payload).build()
+    }
+
+    fn push(detector: &mut PoisoningDetector, frame: &[u8]) -> Vec<PoisoningAlert> {
+        detector.push(&PacketSummary::from_ethernet(frame).unwrap(), frame)
+    }
+
+    #[test]
+    fn flags_a_host_answering_for_many_names() {
+        let attacker = Ipv4Addr::new(192, 168, 1, 66);
+        let mut detector = PoisoningDetector::new();
+        let first = response(ATTACKER_MAC, attacker, LLMNR_PORT, &testutil::dns_response(1, "fileserv", &[attacker]));
+        assert!(push(&mut detector, &first).is_empty());
+
+        let second = response(ATTACKER_MAC, attacker, NBNS_PORT, &testutil::nbns_response(2, "WPAD", attacker));
+        let alerts = push(&mut detector, &second);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].protocol, NameProtocol::Nbns);
+        assert_eq!(alerts[0].reason, PoisoningReason::ManyNames(2));
+        assert_eq!(alerts[0].responder_mac, MacAddress::new(ATTACKER_MAC));
+        assert!(alerts[0].to_string().contains("192.168.1.66 (de:ad:be:ef:00:01) answered 'WPAD'"));
+
+        // Reported once per responder and name
+        assert!(push(&mut detector, &second).is_empty());
+
+        // Loopback packets carry no MAC to tell responders apart
+        let third = response(ATTACKER_MAC, attacker, LLMNR_PORT, &testutil::dns_response(3, "intranet", &[attacker]));
+        let mut looped = vec![2, 0, 0, 0];
+        looped.extend_from_slice(&third[14..]);
+        assert!(detector.push(&PacketSummary::from_link(DLT_NULL, &looped).unwrap(), &looped).is_empty());
+    }
+
+    #[test]
+    fn flags_conflicting_answers() {
+        let mut detector = PoisoningDetector::new();
+        let legit = response(testutil::SRC_MAC, testutil::server_v4(), LLMNR_PORT, &testutil::dns_response(1, "printer", &[testutil::server_v4()]));
+        assert!(push(&mut detector, &legit).is_empty());
+        assert!(push(&mut detector, &legit).is_empty());
+
+        let attacker = Ipv4Addr::new(192, 168, 1, 66);
+        let spoofed = response(ATTACKER_MAC, attacker, LLMNR_PORT, &testutil::dns_response(7, "PRINTER", &[attacker]));
+        let alerts = push(&mut detector, &spoofed);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].reason, PoisoningReason::Conflict { mac: MacAddress::new(testutil::SRC_MAC), ip: IpAddr::V4(testutil::server_v4()) });
+    }
+}
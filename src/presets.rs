@@ -0,0 +1,124 @@
+use crate::config::Config;
+use crate::error::CaptureError;
+
+/// A named pair of capture (BPF) and display filters
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterPreset {
+    pub name: String,
+    pub description: String,
+    pub bpf: Option<String>,
+    pub display: Option<String>,
+}
+
+const BUILTIN: &[(&str, &str, &str, &str)] = &[
+    (
+        "dns",
+        "DNS, mDNS and LLMNR name resolution",
+        "port 53 or udp port 5353 or udp port 5355",
+        "port == 53 or udp.port == 5353 or udp.port == 5355",
+    ),
+    (
+        "web",
+        "HTTP and HTTPS, including common alternate ports and QUIC",
+        "tcp port 80 or tcp port 443 or tcp port 8080 or tcp port 8443 or udp port 443",
+        "tcp.port == 80 or tcp.port == 443 or tcp.port == 8080 or tcp.port == 8443 or udp.port == 443",
+    ),
+    (
+        "voip",
+        "SIP signalling and RTP media in the usual port range",
+        "port 5060 or port 5061 or (udp portrange 10000-20000)",
+        "port == 5060 or port == 5061 or (udp.port >= 10000 and udp.port <= 20000)",
+    ),
+    (
+        "broadcast-only",
+        "Broadcast and multicast frames (ARP, DHCP, discovery protocols)",
+        "broadcast or multicast",
+        "broadcast or multicast",
+    ),
+    (
+        "no-encrypted",
+        "Hide TLS, SSH, QUIC, IPsec and DNS-over-TLS so cleartext stands out",
+        "not (tcp port 443 or tcp port 22 or udp port 443 or port 853 or udp port 500 or udp port 4500 or ip proto 50)",
+        "not (tcp.port == 443 or tcp.port == 22 or udp.port == 443 or port == 853 or udp.port == 500 or udp.port == 4500 or ip.proto == 50)",
+    ),
+];
+
+/// Built-in presets followed by any defined or overridden in the config file
+pub fn all_presets(config: &Config) -> Vec<FilterPreset> {
+    let mut presets: Vec<FilterPreset> = BUILTIN
+        .iter()
+        .map(|(name, description, bpf, display)| FilterPreset {
+            name: name.to_string(),
+            description: description.to_string(),
+            bpf: Some(bpf.to_string()),
+            display: Some(display.to_string()),
+        })
+        .collect();
+
+    for (name, custom) in &config.presets {
+        let preset = FilterPreset {
+            name: name.clone(),
+            description: custom.description.clone().unwrap_or_else(|| "Custom preset".to_string()),
+            bpf: custom.bpf.clone(),
+            display: custom.display.clone(),
+        };
+        match presets.iter_mut().find(|p| p.name == *name) {
+            Some(existing) => *existing = preset,
+            None => presets.push(preset),
+        }
+    }
+    presets
+}
+
+pub fn find_preset(name: &str, config: &Config) -> Result<FilterPreset, CaptureError> {
+    all_presets(config)
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| CaptureError::InputError(format!("Unknown preset '{}'", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::DisplayFilter;
+    use crate::testutil;
+
+    #[test]
+    fn builtin_display_filters_compile() {
+        for preset in all_presets(&Config::default()) {
+            DisplayFilter::parse(preset.display.as_deref().unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn presets_select_expected_traffic() {
+        let dns = DisplayFilter::parse(&find_preset("dns", &Config::default()).unwrap().display.unwrap()).unwrap();
+        assert!(dns.matches_frame(&testutil::dns_query_frame(1, "example.com")));
+
+        let cleartext = find_preset("no-encrypted", &Config::default()).unwrap().display.unwrap();
+        let cleartext = DisplayFilter::parse(&cleartext).unwrap();
+        assert!(!cleartext.matches_frame(&testutil::ipv4_tcp_frame(40000, 443, testutil::TCP_ACK, b"")));
+        assert!(cleartext.matches_frame(&testutil::ipv4_tcp_frame(40000, 80, testutil::TCP_ACK, b"")));
+    }
+
+    #[test]
+    fn config_overrides_and_extends() {
+        let config = Config::parse(
+            r#"
+            [presets.lab]
+            description = "Lab telemetry"
+            bpf = "udp port 9999"
+            display = "udp.port == 9999"
+
+            [presets.dns]
+            bpf = "port 53"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(find_preset("lab", &config).unwrap().bpf.as_deref(), Some("udp port 9999"));
+        let dns = find_preset("dns", &config).unwrap();
+        assert_eq!(dns.bpf.as_deref(), Some("port 53"));
+        assert_eq!(dns.display, None);
+        assert!(find_preset("missing", &config).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use crate::error::CaptureError;
+use log::info;
+use std::ffi::CString;
+#[cfg(not(target_os = "linux"))]
+use std::path::Path;
+
+/// Bit positions in the Linux capability sets
+pub const CAP_NET_ADMIN: u32 = 12;
+pub const CAP_NET_RAW: u32 = 13;
+
+/// Effective capability set from the contents of `/proc/<pid>/status`
+pub fn effective_capabilities(status: &str) -> Option<u64> {
+    let hex = status.lines().find_map(|line| line.strip_prefix("CapEff:"))?;
+    u64::from_str_radix(hex.trim(), 16).ok()
+}
+
+/// Capabilities a capture needs that the set lacks; CAP_NET_ADMIN is only
+/// needed to put a wireless interface into monitor mode
+pub fn missing_capabilities(effective: u64, monitor: bool) -> Vec<&'static str> {
+    let mut missing = Vec::new();
+    if effective & (1 << CAP_NET_RAW) == 0 {
+        missing.push("cap_net_raw");
+    }
+    if monitor && effective & (1 << CAP_NET_ADMIN) == 0 {
+        missing.push("cap_net_admin");
+    }
+    missing
+}
+
+/// Checks whether this process can open a live capture and, when it likely
+/// can't, returns what is missing and how to fix it on this platform
+pub fn preflight(monitor: bool) -> Option<String> {
+    platform_preflight(monitor)
+}
+
+/// Platform-specific advice for a capture that fails for lack of privileges
+pub fn privilege_hint() -> &'static str {
+    if cfg!(target_os = "linux") {
+        "sudo setcap cap_net_raw,cap_net_admin=eip ./your_binary"
+    } else if cfg!(target_os = "macos") {
+        "run with sudo, or give your user read access to /dev/bpf* (e.g. Wireshark's ChmodBPF, which adds the access_bpf group)"
+    } else if cfg!(windows) {
+        "install Npcap, or run as Administrator if it was installed with admin-only access"
+    } else {
+        "run as root, or give your user read access to /dev/bpf*"
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn platform_preflight(monitor: bool) -> Option<String> {
+    // Root in a user namespace or container can still lack the capabilities, so check them rather than the UID
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let missing = missing_capabilities(effective_capabilities(&status)?, monitor);
+    if missing.is_empty() {
+        return None;
+    }
+    let binary = std::env::current_exe().map(|p| p.display().to_string()).unwrap_or_else(|_| "./rust-sniffer".to_string());
+    Some(format!(
+        "Missing {} for live capture. Run with sudo, or grant the binary the capabilities once:\n  sudo setcap cap_net_raw,cap_net_admin=eip {}",
+        missing.join(" and "),
+        binary
+    ))
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn platform_preflight(_monitor: bool) -> Option<String> {
+    if unsafe { libc::geteuid() } == 0 {
+        return None;
+    }
+    let devices = ["/dev/bpf", "/dev/bpf0"];
+    let readable = devices.iter().filter(|d| Path::new(d).exists()).any(|d| {
+        let path = CString::new(*d).unwrap_or_default();
+        let result = unsafe { libc::access(path.as_ptr(), libc::R_OK) };
+        result == 0
+    });
+    if readable {
+        return None;
+    }
+    let fix = if cfg!(target_os = "macos") {
+        "Run with sudo, or install Wireshark's ChmodBPF helper and add your user to the access_bpf group"
+    } else {
+        "Run as root, or give your user read access to /dev/bpf* (e.g. a devfs rule)"
+    };
+    Some(format!("No read access to the BPF devices needed for live capture. {}", fix))
+}
+
+#[cfg(windows)]
+fn platform_preflight(_monitor: bool) -> Option<String> {
+    let system_root = std::env::var("SystemRoot").unwrap_or_else(|_| r"C:\Windows".to_string());
+    if Path::new(&system_root).join("System32").join("Npcap").exists() {
+        return None;
+    }
+    Some("Npcap is not installed. Install it from https://npcap.com (WinPcap API-compatible mode), then run from an Administrator prompt if it was installed with admin-only access".to_string())
+}
+
+/// Switches the whole process to an unprivileged account once the capture
+/// handles are open, so parser bugs can't be leveraged into root. The group
+/// defaults to the user's primary group; supplementary groups are cleared.
+pub fn drop_privileges(user: &str, group: Option<&str>) -> Result<(), CaptureError> {
+    let c_user = CString::new(user).map_err(|e| CaptureError::InputError(e.to_string()))?;
+    let passwd = unsafe { libc::getpwnam(c_user.as_ptr()) };
+    if passwd.is_null() {
+        return Err(CaptureError::InputError(format!("Unknown user '{}'", user)));
+    }
+    let (uid, mut gid) = unsafe { ((*passwd).pw_uid, (*passwd).pw_gid) };
+    if let Some(group) = group {
+        let c_group = CString::new(group).map_err(|e| CaptureError::InputError(e.to_string()))?;
+        let entry = unsafe { libc::getgrnam(c_group.as_ptr()) };
+        if entry.is_null() {
+            return Err(CaptureError::InputError(format!("Unknown group '{}'", group)));
+        }
+        gid = unsafe { (*entry).gr_gid };
+    }
+
+    // Groups first: once the UID changes we may no longer set them
+    let failed = |what: &str| CaptureError::Other(format!("Cannot {}: {}", what, std::io::Error::last_os_error()));
+    if unsafe { libc::setgroups(1, &gid) } != 0 {
+        return Err(failed("clear supplementary groups"));
+    }
+    if unsafe { libc::setgid(gid) } != 0 {
+        return Err(failed("change group"));
+    }
+    if unsafe { libc::setuid(uid) } != 0 {
+        return Err(failed("change user"));
+    }
+    // Root must not be recoverable
+    if uid != 0 && unsafe { libc::setuid(0) } == 0 {
+        return Err(CaptureError::Other("Privileges were not dropped: setuid(0) still succeeds".to_string()));
+    }
+    info!("Dropped privileges to user {} (uid {}, gid {})", user, uid, gid);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_effective_capabilities() {
+        let status = "Name:\trust-sniffer\nCapInh:\t0000000000000000\nCapEff:\t0000000000003000\n";
+        let effective = effective_capabilities(status).unwrap();
+        assert!(missing_capabilities(effective, true).is_empty());
+
+        let raw_only = 1 << CAP_NET_RAW;
+        assert!(missing_capabilities(raw_only, false).is_empty());
+        assert_eq!(missing_capabilities(raw_only, true), vec!["cap_net_admin"]);
+        assert_eq!(missing_capabilities(0, false), vec!["cap_net_raw"]);
+        assert_eq!(effective_capabilities("Name:\tx\n"), None);
+    }
+
+    #[test]
+    fn rejects_unknown_accounts() {
+        assert!(matches!(drop_privileges("no-such-user-for-tests", None), Err(CaptureError::InputError(_))));
+        assert!(matches!(drop_privileges("root", Some("no-such-group-for-tests")), Err(CaptureError::InputError(_))));
+    }
+}
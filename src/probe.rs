@@ -0,0 +1,306 @@
+use crate::builder::PacketBuilder;
+use crate::error::CaptureError;
+use crate::protocols::arp::{build_arp, ArpPacket, ARP_REPLY, ARP_REQUEST};
+use crate::protocols::ethernet::{EthernetFrame, MacAddress};
+use crate::protocols::ipv4::IPv4Packet;
+use ipnet::Ipv4Net;
+use log::{debug, info, warn};
+use pcap::{Active, Capture, Device};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Settings for an active probe sweep
+#[derive(Debug, Clone)]
+pub struct ProbeConfig {
+    pub interface: String,
+    pub targets: Vec<Ipv4Addr>,
+    pub arp: bool,
+    pub icmp: bool,
+    pub wait: Duration,
+    /// Pause between consecutive probes so the segment isn't flooded
+    pub interval: Duration,
+}
+
+/// A host that answered at least one probe
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedHost {
+    pub ip: Ipv4Addr,
+    pub mac: Option<MacAddress>,
+    pub arp_reply: bool,
+    pub icmp_reply: bool,
+    pub rtt: Option<Duration>,
+}
+
+/// Correlates responses seen in the capture stream with the probes we sent
+pub struct ProbeTracker {
+    local_mac: MacAddress,
+    local_ip: Ipv4Addr,
+    ident: u16,
+    sent_at: HashMap<Ipv4Addr, Instant>,
+    hosts: BTreeMap<Ipv4Addr, VerifiedHost>,
+}
+
+impl ProbeTracker {
+    pub fn new(local_mac: MacAddress, local_ip: Ipv4Addr, ident: u16) -> Self {
+        ProbeTracker {
+            local_mac,
+            local_ip,
+            ident,
+            sent_at: HashMap::new(),
+            hosts: BTreeMap::new(),
+        }
+    }
+
+    pub fn arp_probe(&mut self, target: Ipv4Addr, now: Instant) -> Vec<u8> {
+        self.sent_at.entry(target).or_insert(now);
+        let arp = build_arp(ARP_REQUEST, self.local_mac.octets(), self.local_ip, [0; 6], target);
+        PacketBuilder::ethernet()
+            .src_mac(self.local_mac.octets())
+            .dst_mac([0xff; 6])
+            .ether_type(0x0806)
+            .payload(&arp)
+            .build()
+    }
+
+    pub fn icmp_probe(&mut self, target: Ipv4Addr, target_mac: MacAddress, seq: u16, now: Instant) -> Vec<u8> {
+        self.sent_at.insert(target, now);
+        PacketBuilder::ethernet()
+            .src_mac(self.local_mac.octets())
+            .dst_mac(target_mac.octets())
+            .ipv4(self.local_ip, target)
+            .icmp_echo_request(self.ident, seq)
+            .build()
+    }
+
+    /// Inspects a captured frame and records it if it answers one of our probes
+    pub fn observe(&mut self, frame: &[u8], now: Instant) -> Option<Ipv4Addr> {
+        let eth = EthernetFrame::parse(frame).ok()?;
+        match eth.ether_type().value() {
+            0x0806 => {
+                let arp = ArpPacket::parse(eth.payload()).ok()?;
+                if arp.operation() != ARP_REPLY || arp.target_ip() != self.local_ip {
+                    return None;
+                }
+                let ip = arp.sender_ip();
+                let rtt = self.rtt(ip, now)?;
+                let host = self.host(ip);
+                host.mac = Some(arp.sender_mac());
+                host.arp_reply = true;
+                host.rtt.get_or_insert(rtt);
+                Some(ip)
+            }
+            0x0800 => {
+                let ipv4 = IPv4Packet::parse(eth.payload()).ok()?;
+                let icmp = ipv4.payload();
+                let is_reply = ipv4.protocol() == 1
+                    && ipv4.destination_ip() == self.local_ip
+                    && icmp.len() >= 8
+                    && icmp[0] == 0
+                    && u16::from_be_bytes([icmp[4], icmp[5]]) == self.ident;
+                if !is_reply {
+                    return None;
+                }
+                let ip = ipv4.source_ip();
+                let rtt = self.rtt(ip, now)?;
+                let mac = eth.src_mac();
+                let host = self.host(ip);
+                host.mac.get_or_insert(mac);
+                host.icmp_reply = true;
+                host.rtt = Some(rtt);
+                Some(ip)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn mac_of(&self, ip: Ipv4Addr) -> Option<MacAddress> {
+        self.hosts.get(&ip).and_then(|h| h.mac)
+    }
+
+    pub fn into_hosts(self) -> Vec<VerifiedHost> {
+        self.hosts.into_values().collect()
+    }
+
+    fn rtt(&self, ip: Ipv4Addr, now: Instant) -> Option<Duration> {
+        self.sent_at.get(&ip).map(|sent| now.saturating_duration_since(*sent))
+    }
+
+    fn host(&mut self, ip: Ipv4Addr) -> &mut VerifiedHost {
+        self.hosts.entry(ip).or_insert(VerifiedHost {
+            ip,
+            mac: None,
+            arp_reply: false,
+            icmp_reply: false,
+            rtt: None,
+        })
+    }
+}
+
+/// Expands a CIDR (`192.168.1.0/24`) or single address into probe targets
+pub fn parse_targets(spec: &str) -> Result<Vec<Ipv4Addr>, CaptureError> {
+    if let Ok(ip) = spec.parse::<Ipv4Addr>() {
+        return Ok(vec![ip]);
+    }
+    let net: Ipv4Net = spec
+        .parse()
+        .map_err(|_| CaptureError::InputError(format!("Invalid target '{}'", spec)))?;
+    if net.prefix_len() < 16 {
+        return Err(CaptureError::InputError(format!("Refusing to sweep more than a /16: '{}'", spec)));
+    }
+    Ok(net.hosts().collect())
+}
+
+/// Reads the hardware address of an interface from sysfs
+pub fn interface_mac(interface: &str) -> Result<MacAddress, CaptureError> {
+    let path = format!("/sys/class/net/{}/address", interface);
+    let text = fs::read_to_string(&path)
+        .map_err(|e| CaptureError::NetworkError(format!("Cannot read MAC of '{}': {}", interface, e)))?;
+    text.trim()
+        .parse()
+        .map_err(|e| CaptureError::NetworkError(format!("Cannot parse MAC of '{}': {}", interface, e)))
+}
+
+/// Sends the configured probes and returns the hosts that answered
+pub fn run_probe(config: &ProbeConfig) -> Result<Vec<VerifiedHost>, CaptureError> {
+    let iface = Device::list()
+        .map_err(|e| CaptureError::PcapError(e.to_string()))?
+        .into_iter()
+        .find(|d| d.name == config.interface)
+        .ok_or_else(|| CaptureError::InterfaceNotFound(config.interface.clone()))?;
+    let local_ip = iface
+        .addresses
+        .iter()
+        .find_map(|a| match a.addr {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+        .ok_or_else(|| CaptureError::NetworkError(format!("'{}' has no IPv4 address", config.interface)))?;
+    let local_mac = interface_mac(&config.interface)?;
+
+    let mut cap = Capture::from_device(iface)
+        .map_err(|e| CaptureError::PcapError(e.to_string()))?
+        .immediate_mode(true)
+        .open()
+        .map_err(|e| CaptureError::PcapError(e.to_string()))?
+        .setnonblock()
+        .map_err(|e| CaptureError::PcapError(e.to_string()))?;
+    cap.filter("arp or icmp", true).map_err(|e| CaptureError::PcapError(e.to_string()))?;
+
+    info!(
+        "Probing {} targets from {} ({}) on '{}'",
+        config.targets.len(),
+        local_ip,
+        local_mac,
+        config.interface
+    );
+    let mut tracker = ProbeTracker::new(local_mac, local_ip, std::process::id() as u16);
+
+    if config.arp {
+        for target in &config.targets {
+            let frame = tracker.arp_probe(*target, Instant::now());
+            send(&mut cap, &frame);
+            drain(&mut cap, &mut tracker);
+            thread::sleep(config.interval);
+        }
+        collect_until(&mut cap, &mut tracker, Instant::now() + config.wait / 2);
+    }
+
+    if config.icmp {
+        for (seq, target) in config.targets.iter().enumerate() {
+            match tracker.mac_of(*target) {
+                Some(mac) => {
+                    let frame = tracker.icmp_probe(*target, mac, seq as u16, Instant::now());
+                    send(&mut cap, &frame);
+                    drain(&mut cap, &mut tracker);
+                    thread::sleep(config.interval);
+                }
+                None => debug!("No MAC known for {}, skipping ICMP echo", target),
+            }
+        }
+    }
+
+    collect_until(&mut cap, &mut tracker, Instant::now() + config.wait);
+    Ok(tracker.into_hosts())
+}
+
+fn send(cap: &mut Capture<Active>, frame: &[u8]) {
+    if let Err(e) = cap.sendpacket(frame) {
+        warn!("Failed to send probe: {}", e);
+    }
+}
+
+/// Processes every response currently buffered without waiting
+fn drain(cap: &mut Capture<Active>, tracker: &mut ProbeTracker) {
+    while let Ok(packet) = cap.next_packet() {
+        if let Some(ip) = tracker.observe(packet.data, Instant::now()) {
+            debug!("Probe response from {}", ip);
+        }
+    }
+}
+
+fn collect_until(cap: &mut Capture<Active>, tracker: &mut ProbeTracker, deadline: Instant) {
+    while Instant::now() < deadline {
+        drain(cap, tracker);
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> ProbeTracker {
+        ProbeTracker::new(MacAddress::new([2, 0, 0, 0, 0, 1]), Ipv4Addr::new(10, 0, 0, 1), 0x4242)
+    }
+
+    #[test]
+    fn correlates_arp_reply() {
+        let mut tracker = tracker();
+        let start = Instant::now();
+        let target = Ipv4Addr::new(10, 0, 0, 7);
+        let probe = tracker.arp_probe(target, start);
+        assert_eq!(ArpPacket::parse(&probe[14..]).unwrap().target_ip(), target);
+
+        let reply_body = build_arp(ARP_REPLY, [7; 6], target, [2, 0, 0, 0, 0, 1], Ipv4Addr::new(10, 0, 0, 1));
+        let reply = PacketBuilder::ethernet().src_mac([7; 6]).ether_type(0x0806).payload(&reply_body).build();
+        assert_eq!(tracker.observe(&reply, start + Duration::from_millis(3)), Some(target));
+
+        // Unsolicited replies are ignored
+        let other_body = build_arp(ARP_REPLY, [8; 6], Ipv4Addr::new(10, 0, 0, 8), [2, 0, 0, 0, 0, 1], Ipv4Addr::new(10, 0, 0, 1));
+        let other = PacketBuilder::ethernet().ether_type(0x0806).payload(&other_body).build();
+        assert_eq!(tracker.observe(&other, start), None);
+
+        let hosts = tracker.into_hosts();
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].mac, Some(MacAddress::new([7; 6])));
+        assert_eq!(hosts[0].rtt, Some(Duration::from_millis(3)));
+    }
+
+    #[test]
+    fn correlates_icmp_echo_reply() {
+        let mut tracker = tracker();
+        let start = Instant::now();
+        let target = Ipv4Addr::new(10, 0, 0, 9);
+        tracker.icmp_probe(target, MacAddress::new([9; 6]), 1, start);
+
+        let mut reply = PacketBuilder::ethernet()
+            .src_mac([9; 6])
+            .ipv4(target, Ipv4Addr::new(10, 0, 0, 1))
+            .icmp_echo_request(0x4242, 1)
+            .build();
+        reply[14 + 20] = 0; // echo reply
+        assert_eq!(tracker.observe(&reply, start), Some(target));
+        assert!(tracker.into_hosts()[0].icmp_reply);
+    }
+
+    #[test]
+    fn expands_targets() {
+        assert_eq!(parse_targets("10.0.0.0/30").unwrap().len(), 2);
+        assert_eq!(parse_targets("10.0.0.5").unwrap(), vec![Ipv4Addr::new(10, 0, 0, 5)]);
+        assert!(parse_targets("10.0.0.0/8").is_err());
+        assert!(parse_targets("nope").is_err());
+    }
+}
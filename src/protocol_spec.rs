@@ -0,0 +1,377 @@
+use crate::error::CaptureError;
+use crate::plugin::{Dissector, Registration};
+use crate::protocols::frame_control::ControlField;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+/// A simple binary protocol described in TOML (`--protocol`), e.g.
+///
+/// ```toml
+/// name = "Lab Telemetry"
+/// udp_ports = [9000]
+///
+/// [[fields]]
+/// name = "Version"
+/// type = "u8"
+/// mask = 0xF0
+///
+/// [[fields]]
+/// name = "Kind"
+/// type = "u8"
+/// offset = 0
+/// mask = 0x0F
+/// enum = { 1 = "Hello", 2 = "Reading" }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProtocolSpec {
+    pub name: String,
+    #[serde(default)]
+    pub udp_ports: Vec<u16>,
+    #[serde(default)]
+    pub tcp_ports: Vec<u16>,
+    #[serde(default)]
+    pub ether_types: Vec<u16>,
+    /// Byte order of multi-byte fields unless a field overrides it
+    #[serde(default)]
+    pub endian: Endian,
+    pub fields: Vec<FieldSpec>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Endian {
+    #[default]
+    Big,
+    Little,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    Ipv4,
+    Ipv6,
+    Mac,
+    Bytes,
+    String,
+}
+
+impl FieldType {
+    /// Width in bytes, `None` for variable-length types
+    fn size(&self) -> Option<usize> {
+        match self {
+            FieldType::U8 | FieldType::I8 => Some(1),
+            FieldType::U16 | FieldType::I16 => Some(2),
+            FieldType::U32 | FieldType::I32 | FieldType::F32 | FieldType::Ipv4 => Some(4),
+            FieldType::U64 | FieldType::I64 | FieldType::F64 => Some(8),
+            FieldType::Ipv6 => Some(16),
+            FieldType::Mac => Some(6),
+            FieldType::Bytes | FieldType::String => None,
+        }
+    }
+
+    fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            FieldType::U8 | FieldType::U16 | FieldType::U32 | FieldType::U64 | FieldType::I8 | FieldType::I16 | FieldType::I32 | FieldType::I64
+        )
+    }
+}
+
+/// Length of a `bytes` or `string` field: a number of bytes, the name of an
+/// earlier integer field holding it, or `"rest"` for the rest of the payload
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Length {
+    Fixed(usize),
+    Field(String),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldSpec {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: FieldType,
+    /// Byte offset from the start of the payload; defaults to right after the previous field
+    pub offset: Option<usize>,
+    pub length: Option<Length>,
+    pub endian: Option<Endian>,
+    /// Bits of an integer field to keep, shifted down (e.g. `0xF0` for the high nibble)
+    pub mask: Option<u64>,
+    /// Names for integer values; keys are decimal or `0x` hex
+    #[serde(default, rename = "enum")]
+    pub values: BTreeMap<String, String>,
+    #[serde(default)]
+    pub description: String,
+}
+
+enum FieldLength {
+    Fixed(usize),
+    /// Value of the field at this index
+    Field(usize),
+    Rest,
+}
+
+struct Field {
+    name: String,
+    kind: FieldType,
+    offset: Option<usize>,
+    length: FieldLength,
+    endian: Endian,
+    mask: Option<u64>,
+    values: BTreeMap<u64, String>,
+    description: String,
+}
+
+/// A dissector compiled from a `ProtocolSpec`
+pub struct SpecDissector {
+    name: String,
+    registrations: Vec<Registration>,
+    fields: Vec<Field>,
+}
+
+impl SpecDissector {
+    pub fn load(path: &Path) -> Result<Self, CaptureError> {
+        let error = |what: String| CaptureError::InputError(format!("Protocol {}: {}", path.display(), what));
+        let text = std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let spec: ProtocolSpec = toml::from_str(&text).map_err(|e| error(e.to_string()))?;
+        Self::compile(&spec).map_err(error)
+    }
+
+    /// Checks the spec and resolves length references
+    pub fn compile(spec: &ProtocolSpec) -> Result<Self, String> {
+        let registrations: Vec<Registration> = spec
+            .udp_ports
+            .iter()
+            .map(|&port| Registration::UdpPort(port))
+            .chain(spec.tcp_ports.iter().map(|&port| Registration::TcpPort(port)))
+            .chain(spec.ether_types.iter().map(|&ether_type| Registration::EtherType(ether_type)))
+            .collect();
+        if registrations.is_empty() {
+            return Err("no udp_ports, tcp_ports or ether_types to register".to_string());
+        }
+
+        let mut fields: Vec<Field> = Vec::with_capacity(spec.fields.len());
+        for field in &spec.fields {
+            let context = |what: &str| format!("field '{}': {}", field.name, what);
+            let length = match (field.kind.size(), &field.length) {
+                (Some(size), None) => FieldLength::Fixed(size),
+                (Some(_), Some(_)) => return Err(context("only bytes and string fields take a length")),
+                (None, None) => return Err(context("needs a length")),
+                (None, Some(Length::Fixed(n))) => FieldLength::Fixed(*n),
+                (None, Some(Length::Field(name))) if name == "rest" => FieldLength::Rest,
+                (None, Some(Length::Field(name))) => match fields.iter().position(|f| &f.name == name) {
+                    Some(index) if fields[index].kind.is_integer() => FieldLength::Field(index),
+                    Some(_) => return Err(context(&format!("length field '{}' is not an integer", name))),
+                    None => return Err(context(&format!("length field '{}' must come earlier", name))),
+                },
+            };
+            if (field.mask.is_some() || !field.values.is_empty()) && !field.kind.is_integer() {
+                return Err(context("mask and enum need an integer type"));
+            }
+            let values = field
+                .values
+                .iter()
+                .map(|(key, name)| parse_number(key).map(|value| (value, name.clone())).ok_or_else(|| context(&format!("bad enum value '{}'", key))))
+                .collect::<Result<_, _>>()?;
+            fields.push(Field {
+                name: field.name.clone(),
+                kind: field.kind,
+                offset: field.offset,
+                length,
+                endian: field.endian.unwrap_or(spec.endian),
+                mask: field.mask,
+                values,
+                description: field.description.clone(),
+            });
+        }
+        Ok(SpecDissector { name: spec.name.clone(), registrations, fields })
+    }
+}
+
+fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Unsigned value of up to 8 bytes in the given byte order
+fn read_uint(bytes: &[u8], endian: Endian) -> u64 {
+    let fold = |value: u64, &byte: &u8| (value << 8) | byte as u64;
+    match endian {
+        Endian::Big => bytes.iter().fold(0, fold),
+        Endian::Little => bytes.iter().rev().fold(0, fold),
+    }
+}
+
+impl Field {
+    /// Display value and integer value (for length references) of the field's bytes
+    fn decode(&self, bytes: &[u8]) -> (String, Option<u64>) {
+        let raw = || read_uint(bytes, self.endian);
+        let bits = bytes.len() as u32 * 8;
+        match self.kind {
+            FieldType::U8 | FieldType::U16 | FieldType::U32 | FieldType::U64 => {
+                let value = match self.mask {
+                    Some(mask) => (raw() & mask) >> mask.trailing_zeros(),
+                    None => raw(),
+                };
+                (value.to_string(), Some(value))
+            }
+            FieldType::I8 | FieldType::I16 | FieldType::I32 | FieldType::I64 => {
+                let value = match self.mask {
+                    Some(mask) => (raw() & mask) >> mask.trailing_zeros(),
+                    None => raw(),
+                };
+                // Sign-extend from the field's width
+                let signed = ((value << (64 - bits)) as i64) >> (64 - bits);
+                (signed.to_string(), Some(value))
+            }
+            FieldType::F32 => (f32::from_bits(raw() as u32).to_string(), None),
+            FieldType::F64 => (f64::from_bits(raw()).to_string(), None),
+            FieldType::Ipv4 => (Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).to_string(), None),
+            FieldType::Ipv6 => {
+                let octets: [u8; 16] = bytes.try_into().unwrap_or_default();
+                (Ipv6Addr::from(octets).to_string(), None)
+            }
+            FieldType::Mac => (bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":"), None),
+            FieldType::Bytes => (bytes.iter().map(|b| format!("{:02x}", b)).collect(), None),
+            FieldType::String => (String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string(), None),
+        }
+    }
+}
+
+impl Dissector for SpecDissector {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn registrations(&self) -> Vec<Registration> {
+        self.registrations.clone()
+    }
+
+    /// Decodes fields in order, stopping at the first one that doesn't fit in the payload
+    fn dissect(&self, payload: &[u8]) -> Vec<ControlField> {
+        let mut fields = Vec::new();
+        let mut numbers: Vec<Option<u64>> = Vec::with_capacity(self.fields.len());
+        let mut cursor = 0;
+        for field in &self.fields {
+            let start = field.offset.unwrap_or(cursor);
+            let length = match field.length {
+                FieldLength::Fixed(n) => Some(n),
+                FieldLength::Field(index) => numbers[index].map(|n| n as usize),
+                FieldLength::Rest => Some(payload.len().saturating_sub(start)),
+            };
+            let Some(bytes) = length.and_then(|n| payload.get(start..start.checked_add(n)?)) else {
+                fields.push(ControlField {
+                    name: field.name.clone(),
+                    value: "<truncated>".to_string(),
+                    description: format!("{} bytes of payload", payload.len()),
+                });
+                break;
+            };
+            let (value, number) = field.decode(bytes);
+            let description = match number {
+                Some(n) if !field.values.is_empty() => field.values.get(&n).cloned().unwrap_or_else(|| format!("Unknown ({})", n)),
+                _ => field.description.clone(),
+            };
+            fields.push(ControlField { name: field.name.clone(), value, description });
+            numbers.push(number);
+            cursor = start + bytes.len();
+        }
+        fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TELEMETRY: &str = r#"
+        name = "Lab Telemetry"
+        udp_ports = [9000]
+
+        [[fields]]
+        name = "Version"
+        type = "u8"
+        mask = 0xF0
+
+        [[fields]]
+        name = "Kind"
+        type = "u8"
+        offset = 0
+        mask = 0x0F
+        enum = { 1 = "Hello", 2 = "Reading" }
+
+        [[fields]]
+        name = "Temperature"
+        type = "i16"
+        endian = "little"
+        description = "Centidegrees"
+
+        [[fields]]
+        name = "Label Length"
+        type = "u8"
+
+        [[fields]]
+        name = "Label"
+        type = "string"
+        length = "Label Length"
+
+        [[fields]]
+        name = "Trailer"
+        type = "bytes"
+        length = "rest"
+    "#;
+
+    #[test]
+    fn decodes_fields_from_spec() {
+        let spec: ProtocolSpec = toml::from_str(TELEMETRY).unwrap();
+        let dissector = SpecDissector::compile(&spec).unwrap();
+        assert_eq!(dissector.registrations(), vec![Registration::UdpPort(9000)]);
+
+        let fields = dissector.dissect(&[0x32, 0x18, 0xFC, 4, b'r', b'o', b'o', b'f', 0xAB, 0xCD]);
+        let values: Vec<(&str, &str, &str)> =
+            fields.iter().map(|f| (f.name.as_str(), f.value.as_str(), f.description.as_str())).collect();
+        assert_eq!(
+            values,
+            vec![
+                ("Version", "3", ""),
+                ("Kind", "2", "Reading"),
+                ("Temperature", "-1000", "Centidegrees"),
+                ("Label Length", "4", ""),
+                ("Label", "roof", ""),
+                ("Trailer", "abcd", ""),
+            ]
+        );
+
+        let short = dissector.dissect(&[0x31, 0x18, 0xFC, 9, b'x']);
+        assert_eq!((short[1].description.as_str(), short[4].value.as_str()), ("Hello", "<truncated>"));
+    }
+
+    #[test]
+    fn rejects_inconsistent_specs() {
+        let compile = |fields: &str| {
+            let spec: ProtocolSpec = toml::from_str(&format!("name = \"p\"\nudp_ports = [1]\n{}", fields)).unwrap();
+            SpecDissector::compile(&spec).err().unwrap_or_default()
+        };
+        assert!(compile("[[fields]]\nname = \"Data\"\ntype = \"bytes\"").contains("needs a length"));
+        assert!(compile("[[fields]]\nname = \"Data\"\ntype = \"string\"\nlength = \"Size\"").contains("must come earlier"));
+        assert!(compile("[[fields]]\nname = \"A\"\ntype = \"ipv4\"\nenum = { 1 = \"x\" }").contains("integer type"));
+        let spec: ProtocolSpec = toml::from_str("name = \"p\"\nfields = []").unwrap();
+        assert!(SpecDissector::compile(&spec).is_err());
+    }
+}
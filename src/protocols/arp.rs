@@ -0,0 +1,141 @@
+use super::ethernet::MacAddress;
+use super::frame_control::ControlField;
+use std::fmt;
+use std::net::Ipv4Addr;
+
+/// ARP packet parser (Ethernet/IPv4 only)
+pub struct ArpPacket<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum ArpError {
+    TooShort,
+    Unsupported,
+}
+
+impl fmt::Display for ArpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArpError::TooShort => write!(f, "Packet too short for ARP"),
+            ArpError::Unsupported => write!(f, "Unsupported ARP hardware/protocol type"),
+        }
+    }
+}
+
+pub const ARP_REQUEST: u16 = 1;
+pub const ARP_REPLY: u16 = 2;
+
+impl<'a> ArpPacket<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, ArpError> {
+        if data.len() < 28 {
+            return Err(ArpError::TooShort);
+        }
+
+        // Ethernet hardware (1), IPv4 protocol (0x0800), 6/4 byte addresses
+        if data[0..6] != [0x00, 0x01, 0x08, 0x00, 0x06, 0x04] {
+            return Err(ArpError::Unsupported);
+        }
+
+        Ok(ArpPacket { data })
+    }
+
+    pub fn operation(&self) -> u16 {
+        ((self.data[6] as u16) << 8) | (self.data[7] as u16)
+    }
+
+    pub fn sender_mac(&self) -> MacAddress {
+        let mut mac = [0u8; 6];
+        mac.copy_from_slice(&self.data[8..14]);
+        MacAddress::new(mac)
+    }
+
+    pub fn sender_ip(&self) -> Ipv4Addr {
+        Ipv4Addr::new(self.data[14], self.data[15], self.data[16], self.data[17])
+    }
+
+    pub fn target_mac(&self) -> MacAddress {
+        let mut mac = [0u8; 6];
+        mac.copy_from_slice(&self.data[18..24]);
+        MacAddress::new(mac)
+    }
+
+    pub fn target_ip(&self) -> Ipv4Addr {
+        Ipv4Addr::new(self.data[24], self.data[25], self.data[26], self.data[27])
+    }
+
+    pub fn get_operation_name(&self) -> String {
+        match self.operation() {
+            ARP_REQUEST => "Request".to_string(),
+            ARP_REPLY => "Reply".to_string(),
+            op => format!("Unknown ({})", op),
+        }
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        vec![
+            ControlField {
+                name: "ARP Operation".to_string(),
+                value: self.operation().to_string(),
+                description: self.get_operation_name(),
+            },
+            ControlField {
+                name: "Sender MAC".to_string(),
+                value: self.sender_mac().to_string(),
+                description: "Sender hardware address".to_string(),
+            },
+            ControlField {
+                name: "Sender IP".to_string(),
+                value: self.sender_ip().to_string(),
+                description: "Sender protocol address".to_string(),
+            },
+            ControlField {
+                name: "Target MAC".to_string(),
+                value: self.target_mac().to_string(),
+                description: "Target hardware address".to_string(),
+            },
+            ControlField {
+                name: "Target IP".to_string(),
+                value: self.target_ip().to_string(),
+                description: "Target protocol address".to_string(),
+            },
+        ]
+    }
+}
+
+/// Builds the 28-byte ARP body for an Ethernet/IPv4 request or reply
+pub fn build_arp(operation: u16, sender_mac: [u8; 6], sender_ip: Ipv4Addr, target_mac: [u8; 6], target_ip: Ipv4Addr) -> Vec<u8> {
+    let mut arp = vec![0x00, 0x01, 0x08, 0x00, 0x06, 0x04];
+    arp.extend_from_slice(&operation.to_be_bytes());
+    arp.extend_from_slice(&sender_mac);
+    arp.extend_from_slice(&sender_ip.octets());
+    arp.extend_from_slice(&target_mac);
+    arp.extend_from_slice(&target_ip.octets());
+    arp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn parses_request() {
+        let frame = testutil::arp_request(testutil::client_v4(), testutil::server_v4());
+        let arp = ArpPacket::parse(&frame[14..]).unwrap();
+        assert_eq!(arp.get_operation_name(), "Request");
+        assert_eq!(arp.sender_mac().octets(), testutil::SRC_MAC);
+        assert_eq!(arp.sender_ip(), testutil::client_v4());
+        assert_eq!(arp.target_ip(), testutil::server_v4());
+        assert_eq!(arp.get_control_fields().len(), 5);
+    }
+
+    #[test]
+    fn round_trips_built_reply() {
+        let body = build_arp(ARP_REPLY, [1; 6], testutil::server_v4(), [2; 6], testutil::client_v4());
+        let arp = ArpPacket::parse(&body).unwrap();
+        assert_eq!(arp.operation(), ARP_REPLY);
+        assert_eq!(arp.target_mac().octets(), [2; 6]);
+        assert!(matches!(ArpPacket::parse(&body[..20]), Err(ArpError::TooShort)));
+    }
+}
@@ -0,0 +1,421 @@
+use super::frame_control::ControlField;
+use super::summary::PacketSummary;
+use std::fmt;
+
+pub const BACNET_PORT: u16 = 47808;
+
+const BVLC_TYPE: u8 = 0x81;
+const BVLC_FORWARDED_NPDU: u8 = 0x04;
+
+pub const APDU_CONFIRMED_REQUEST: u8 = 0;
+pub const APDU_UNCONFIRMED_REQUEST: u8 = 1;
+
+pub const SERVICE_WRITE_PROPERTY: u8 = 15;
+pub const SERVICE_WRITE_PROPERTY_MULTIPLE: u8 = 16;
+pub const SERVICE_DEVICE_COMMUNICATION_CONTROL: u8 = 17;
+pub const SERVICE_REINITIALIZE_DEVICE: u8 = 20;
+
+/// Confirmed services that change a device's state or configuration
+const SENSITIVE_SERVICES: [u8; 7] = [
+    7,  // atomicWriteFile
+    10, // createObject
+    11, // deleteObject
+    SERVICE_WRITE_PROPERTY,
+    SERVICE_WRITE_PROPERTY_MULTIPLE,
+    SERVICE_DEVICE_COMMUNICATION_CONTROL,
+    SERVICE_REINITIALIZE_DEVICE,
+];
+const UNCONFIRMED_WRITE_GROUP: u8 = 10;
+
+/// BACnet/IP packet parser: BVLC header, NPDU and APDU (ASHRAE 135)
+pub struct BacnetPacket<'a> {
+    data: &'a [u8],
+    /// Offset of the APDU, or of the network layer message type
+    apdu: usize,
+    network_message: bool,
+}
+
+#[derive(Debug)]
+pub enum BacnetError {
+    TooShort,
+    NotBacnetIp(u8),
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for BacnetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BacnetError::TooShort => write!(f, "Packet too short for BACnet/IP header"),
+            BacnetError::NotBacnetIp(t) => write!(f, "BVLC type 0x{:02x} is not BACnet/IP", t),
+            BacnetError::UnsupportedVersion(v) => write!(f, "Unsupported BACnet NPDU version {}", v),
+        }
+    }
+}
+
+impl<'a> BacnetPacket<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, BacnetError> {
+        if data.len() < 4 {
+            return Err(BacnetError::TooShort);
+        }
+        if data[0] != BVLC_TYPE {
+            return Err(BacnetError::NotBacnetIp(data[0]));
+        }
+        // Forwarded NPDUs carry the original sender's address and port
+        let npdu = if data[1] == BVLC_FORWARDED_NPDU { 10 } else { 4 };
+        let Some(&[version, control]) = data.get(npdu..npdu + 2) else { return Err(BacnetError::TooShort) };
+        if version != 1 {
+            return Err(BacnetError::UnsupportedVersion(version));
+        }
+
+        let mut offset = npdu + 2;
+        for present in [control & 0x20 != 0, control & 0x08 != 0] {
+            // DNET/SNET, address length and address
+            if present {
+                let len = *data.get(offset + 2).ok_or(BacnetError::TooShort)? as usize;
+                offset += 3 + len;
+            }
+        }
+        if control & 0x20 != 0 {
+            offset += 1; // hop count
+        }
+        if offset >= data.len() {
+            return Err(BacnetError::TooShort);
+        }
+        Ok(BacnetPacket { data, apdu: offset, network_message: control & 0x80 != 0 })
+    }
+
+    pub fn bvlc_function(&self) -> u8 {
+        self.data[1]
+    }
+
+    /// True for NPDUs carrying a network layer message instead of an APDU
+    pub fn is_network_message(&self) -> bool {
+        self.network_message
+    }
+
+    pub fn network_message_type(&self) -> Option<u8> {
+        self.network_message.then(|| self.data[self.apdu])
+    }
+
+    fn apdu(&self) -> Option<&'a [u8]> {
+        (!self.network_message).then(|| &self.data[self.apdu..])
+    }
+
+    pub fn apdu_type(&self) -> Option<u8> {
+        Some(self.apdu()?[0] >> 4)
+    }
+
+    fn segmented(&self) -> bool {
+        self.apdu().is_some_and(|apdu| apdu[0] & 0x08 != 0)
+    }
+
+    pub fn invoke_id(&self) -> Option<u8> {
+        let apdu = self.apdu()?;
+        match apdu[0] >> 4 {
+            APDU_CONFIRMED_REQUEST => apdu.get(2).copied(),
+            2..=7 => apdu.get(1).copied(),
+            _ => None,
+        }
+    }
+
+    pub fn service_choice(&self) -> Option<u8> {
+        let apdu = self.apdu()?;
+        let at = match apdu[0] >> 4 {
+            APDU_CONFIRMED_REQUEST if self.segmented() => 5,
+            APDU_CONFIRMED_REQUEST => 3,
+            APDU_UNCONFIRMED_REQUEST => 1,
+            3 if self.segmented() => 4,
+            2 | 3 | 5 => 2,
+            _ => return None,
+        };
+        apdu.get(at).copied()
+    }
+
+    /// Tagged parameters of a request
+    fn service_data(&self) -> Option<&'a [u8]> {
+        let apdu = self.apdu()?;
+        match apdu[0] >> 4 {
+            APDU_CONFIRMED_REQUEST if self.segmented() => apdu.get(6..),
+            APDU_CONFIRMED_REQUEST => apdu.get(4..),
+            APDU_UNCONFIRMED_REQUEST => apdu.get(2..),
+            _ => None,
+        }
+    }
+
+    pub fn get_service_name(&self) -> Option<String> {
+        let choice = self.service_choice()?;
+        Some(match self.apdu_type()? {
+            APDU_UNCONFIRMED_REQUEST => unconfirmed_service_name(choice),
+            _ => confirmed_service_name(choice),
+        })
+    }
+
+    /// Object type and instance of the request's first object identifier
+    pub fn object_id(&self) -> Option<(u16, u32)> {
+        let (tag, value, _) = read_tag(self.service_data()?)?;
+        // Context tag 0 in most requests, application tag 12 in I-Am and I-Have
+        if !matches!(tag, Tag::Context(0) | Tag::Application(12)) || value.len() != 4 {
+            return None;
+        }
+        let id = u32::from_be_bytes([value[0], value[1], value[2], value[3]]);
+        Some(((id >> 22) as u16, id & 0x3F_FFFF))
+    }
+
+    /// Property identifier of ReadProperty/WriteProperty style requests
+    pub fn property_id(&self) -> Option<u32> {
+        let (Tag::Context(0), _, rest) = read_tag(self.service_data()?)? else { return None };
+        let (Tag::Context(1), value, _) = read_tag(rest)? else { return None };
+        (value.len() <= 4).then(|| value.iter().fold(0, |acc, &b| acc << 8 | b as u32))
+    }
+
+    /// Write or device management request
+    pub fn is_sensitive(&self) -> bool {
+        match (self.apdu_type(), self.service_choice()) {
+            (Some(APDU_CONFIRMED_REQUEST), Some(choice)) => SENSITIVE_SERVICES.contains(&choice),
+            (Some(APDU_UNCONFIRMED_REQUEST), Some(choice)) => choice == UNCONFIRMED_WRITE_GROUP,
+            _ => false,
+        }
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![ControlField {
+            name: "BACnet BVLC".to_string(),
+            value: bvlc_function_name(self.bvlc_function()),
+            description: "BACnet/IP virtual link function".to_string(),
+        }];
+        if let Some(kind) = self.network_message_type() {
+            fields.push(ControlField {
+                name: "BACnet Network Message".to_string(),
+                value: format!("0x{:02x}", kind),
+                description: "Network layer message".to_string(),
+            });
+            return fields;
+        }
+        if let (Some(kind), Some(service)) = (self.apdu_type(), self.get_service_name()) {
+            fields.push(ControlField {
+                name: "BACnet Service".to_string(),
+                value: service,
+                description: match self.invoke_id() {
+                    Some(id) => format!("{}, invoke ID {}", apdu_type_name(kind), id),
+                    None => apdu_type_name(kind),
+                },
+            });
+        } else if let Some(kind) = self.apdu_type() {
+            fields.push(ControlField {
+                name: "BACnet APDU".to_string(),
+                value: apdu_type_name(kind),
+                description: format!("Type {}", kind),
+            });
+        }
+        if let Some((object_type, instance)) = self.object_id() {
+            fields.push(ControlField {
+                name: "BACnet Object".to_string(),
+                value: format!("{} {}", object_type_name(object_type), instance),
+                description: format!("Type {}", object_type),
+            });
+        }
+        if let Some(property) = self.property_id() {
+            fields.push(ControlField {
+                name: "BACnet Property".to_string(),
+                value: property_name(property),
+                description: format!("Property {}", property),
+            });
+        }
+        fields
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tag {
+    Application(u8),
+    Context(u8),
+}
+
+/// Reads one tag with a short (up to 4 byte) or one-byte extended length
+fn read_tag(data: &[u8]) -> Option<(Tag, &[u8], &[u8])> {
+    let (&first, rest) = data.split_first()?;
+    let number = first >> 4;
+    let tag = if first & 0x08 != 0 { Tag::Context(number) } else { Tag::Application(number) };
+    let (len, rest) = match first & 0x07 {
+        5 => (*rest.first()? as usize, &rest[1..]),
+        // Opening/closing tags of constructed values
+        6 | 7 => return None,
+        len => (len as usize, rest),
+    };
+    Some((tag, rest.get(..len)?, &rest[len..]))
+}
+
+/// Warning text for a write or device management request
+pub fn sensitive_service_alert(summary: &PacketSummary, data: &[u8]) -> Option<String> {
+    if !summary.is_udp() || !summary.has_port(BACNET_PORT) {
+        return None;
+    }
+    let packet = BacnetPacket::parse(summary.payload(data)).ok()?;
+    if !packet.is_sensitive() {
+        return None;
+    }
+    let target = packet
+        .object_id()
+        .map(|(object_type, instance)| format!(" on {} {}", object_type_name(object_type), instance))
+        .unwrap_or_default();
+    let property = packet.property_id().map(|p| format!(" ({})", property_name(p))).unwrap_or_default();
+    Some(format!(
+        "BACnet {}{}{}: {} -> {}",
+        packet.get_service_name()?,
+        target,
+        property,
+        summary.src_ip?,
+        summary.dst_ip?
+    ))
+}
+
+pub fn bvlc_function_name(function: u8) -> String {
+    match function {
+        0x00 => "BVLC-Result".to_string(),
+        0x01 => "Write-Broadcast-Distribution-Table".to_string(),
+        0x02 => "Read-Broadcast-Distribution-Table".to_string(),
+        0x04 => "Forwarded-NPDU".to_string(),
+        0x05 => "Register-Foreign-Device".to_string(),
+        0x09 => "Distribute-Broadcast-To-Network".to_string(),
+        0x0A => "Original-Unicast-NPDU".to_string(),
+        0x0B => "Original-Broadcast-NPDU".to_string(),
+        f => format!("Unknown ({})", f),
+    }
+}
+
+pub fn apdu_type_name(kind: u8) -> String {
+    match kind {
+        0 => "Confirmed-Request".to_string(),
+        1 => "Unconfirmed-Request".to_string(),
+        2 => "SimpleACK".to_string(),
+        3 => "ComplexACK".to_string(),
+        4 => "SegmentACK".to_string(),
+        5 => "Error".to_string(),
+        6 => "Reject".to_string(),
+        7 => "Abort".to_string(),
+        k => format!("Unknown ({})", k),
+    }
+}
+
+pub fn confirmed_service_name(choice: u8) -> String {
+    match choice {
+        0 => "acknowledgeAlarm".to_string(),
+        1 => "confirmedCOVNotification".to_string(),
+        2 => "confirmedEventNotification".to_string(),
+        5 => "subscribeCOV".to_string(),
+        6 => "atomicReadFile".to_string(),
+        7 => "atomicWriteFile".to_string(),
+        8 => "addListElement".to_string(),
+        9 => "removeListElement".to_string(),
+        10 => "createObject".to_string(),
+        11 => "deleteObject".to_string(),
+        12 => "readProperty".to_string(),
+        14 => "readPropertyMultiple".to_string(),
+        15 => "writeProperty".to_string(),
+        16 => "writePropertyMultiple".to_string(),
+        17 => "deviceCommunicationControl".to_string(),
+        18 => "confirmedPrivateTransfer".to_string(),
+        19 => "confirmedTextMessage".to_string(),
+        20 => "reinitializeDevice".to_string(),
+        26 => "readRange".to_string(),
+        c => format!("Unknown ({})", c),
+    }
+}
+
+pub fn unconfirmed_service_name(choice: u8) -> String {
+    match choice {
+        0 => "i-Am".to_string(),
+        1 => "i-Have".to_string(),
+        2 => "unconfirmedCOVNotification".to_string(),
+        3 => "unconfirmedEventNotification".to_string(),
+        4 => "unconfirmedPrivateTransfer".to_string(),
+        5 => "unconfirmedTextMessage".to_string(),
+        6 => "timeSynchronization".to_string(),
+        7 => "who-Has".to_string(),
+        8 => "who-Is".to_string(),
+        9 => "utcTimeSynchronization".to_string(),
+        UNCONFIRMED_WRITE_GROUP => "writeGroup".to_string(),
+        c => format!("Unknown ({})", c),
+    }
+}
+
+pub fn object_type_name(object_type: u16) -> String {
+    match object_type {
+        0 => "analog-input".to_string(),
+        1 => "analog-output".to_string(),
+        2 => "analog-value".to_string(),
+        3 => "binary-input".to_string(),
+        4 => "binary-output".to_string(),
+        5 => "binary-value".to_string(),
+        6 => "calendar".to_string(),
+        8 => "device".to_string(),
+        10 => "file".to_string(),
+        13 => "multi-state-input".to_string(),
+        14 => "multi-state-output".to_string(),
+        17 => "schedule".to_string(),
+        19 => "multi-state-value".to_string(),
+        20 => "trend-log".to_string(),
+        t => format!("Unknown ({})", t),
+    }
+}
+
+pub fn property_name(property: u32) -> String {
+    match property {
+        28 => "description".to_string(),
+        75 => "object-identifier".to_string(),
+        76 => "object-list".to_string(),
+        77 => "object-name".to_string(),
+        79 => "object-type".to_string(),
+        81 => "out-of-service".to_string(),
+        85 => "present-value".to_string(),
+        87 => "priority-array".to_string(),
+        104 => "relinquish-default".to_string(),
+        111 => "status-flags".to_string(),
+        112 => "system-status".to_string(),
+        117 => "units".to_string(),
+        120 => "vendor-identifier".to_string(),
+        121 => "vendor-name".to_string(),
+        p => format!("Unknown ({})", p),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    /// writeProperty of analog-output 3 present-value, priority 8
+    const WRITE_PROPERTY: [u8; 22] = [
+        0x81, 0x0A, 0x00, 0x16, 0x01, 0x04, 0x00, 0x05, 0x07, 0x0F, 0x0C, 0x00, 0x40, 0x00, 0x03, 0x19, 0x55, 0x3E, 0x44,
+        0x42, 0x3F, 0x49,
+    ];
+
+    #[test]
+    fn decodes_write_property() {
+        let packet = BacnetPacket::parse(&WRITE_PROPERTY).unwrap();
+        assert_eq!(packet.get_service_name().as_deref(), Some("writeProperty"));
+        assert_eq!(packet.invoke_id(), Some(7));
+        assert_eq!(packet.object_id(), Some((1, 3)));
+        assert_eq!(packet.property_id(), Some(85));
+        assert!(packet.is_sensitive());
+        assert!(matches!(BacnetPacket::parse(&[0x82, 0x0A, 0, 6, 1, 0]), Err(BacnetError::NotBacnetIp(0x82))));
+    }
+
+    #[test]
+    fn decodes_i_am_behind_a_router() {
+        // Source network 5, 1-byte MAC address 0x21
+        let data = [0x81, 0x0B, 0x00, 0x12, 0x01, 0x08, 0x00, 0x05, 0x01, 0x21, 0x10, 0x00, 0xC4, 0x02, 0x00, 0x04, 0xD2, 0x22];
+        let packet = BacnetPacket::parse(&data).unwrap();
+        assert_eq!(packet.get_service_name().as_deref(), Some("i-Am"));
+        assert_eq!(packet.object_id(), Some((8, 1234)));
+        assert!(!packet.is_sensitive());
+    }
+
+    #[test]
+    fn alerts_on_writes() {
+        let frame = testutil::ipv4_udp_frame(BACNET_PORT, BACNET_PORT, &WRITE_PROPERTY);
+        let summary = PacketSummary::from_ethernet(&frame).unwrap();
+        let alert = sensitive_service_alert(&summary, &frame).unwrap();
+        assert!(alert.starts_with("BACnet writeProperty on analog-output 3 (present-value)"));
+    }
+}
@@ -0,0 +1,229 @@
+use super::frame_control::ControlField;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// SNAP organization code and protocol ID of CDP
+pub const CDP_OUI: [u8; 3] = [0x00, 0x00, 0x0C];
+pub const CDP_PID: u16 = 0x2000;
+
+const TLV_DEVICE_ID: u16 = 0x0001;
+const TLV_ADDRESSES: u16 = 0x0002;
+const TLV_PORT_ID: u16 = 0x0003;
+const TLV_CAPABILITIES: u16 = 0x0004;
+const TLV_SOFTWARE: u16 = 0x0005;
+const TLV_PLATFORM: u16 = 0x0006;
+const TLV_NATIVE_VLAN: u16 = 0x000A;
+const TLV_DUPLEX: u16 = 0x000B;
+const TLV_MANAGEMENT_ADDRESSES: u16 = 0x0016;
+
+/// Cisco Discovery Protocol packet parser
+pub struct CdpPacket<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum CdpError {
+    TooShort,
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for CdpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CdpError::TooShort => write!(f, "Packet too short for CDP header"),
+            CdpError::UnsupportedVersion(v) => write!(f, "Unsupported CDP version {}", v),
+        }
+    }
+}
+
+impl<'a> CdpPacket<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, CdpError> {
+        if data.len() < 4 {
+            return Err(CdpError::TooShort);
+        }
+        if !matches!(data[0], 1 | 2) {
+            return Err(CdpError::UnsupportedVersion(data[0]));
+        }
+        Ok(CdpPacket { data })
+    }
+
+    pub fn version(&self) -> u8 {
+        self.data[0]
+    }
+
+    /// Seconds the receiver should keep this information
+    pub fn ttl(&self) -> u8 {
+        self.data[1]
+    }
+
+    /// (type, value) pairs; the TLV length field includes its 4-byte header
+    pub fn tlvs(&self) -> Vec<(u16, &'a [u8])> {
+        let mut tlvs = Vec::new();
+        let mut rest = &self.data[4..];
+        while rest.len() >= 4 {
+            let tlv_type = u16::from_be_bytes([rest[0], rest[1]]);
+            let len = u16::from_be_bytes([rest[2], rest[3]]) as usize;
+            if len < 4 || len > rest.len() {
+                break;
+            }
+            tlvs.push((tlv_type, &rest[4..len]));
+            rest = &rest[len..];
+        }
+        tlvs
+    }
+
+    fn tlv(&self, tlv_type: u16) -> Option<&'a [u8]> {
+        self.tlvs().into_iter().find(|(t, _)| *t == tlv_type).map(|(_, value)| value)
+    }
+
+    fn text(&self, tlv_type: u16) -> Option<String> {
+        self.tlv(tlv_type).map(|v| String::from_utf8_lossy(v).trim_end_matches('\0').to_string())
+    }
+
+    pub fn device_id(&self) -> Option<String> {
+        self.text(TLV_DEVICE_ID)
+    }
+
+    pub fn port_id(&self) -> Option<String> {
+        self.text(TLV_PORT_ID)
+    }
+
+    pub fn platform(&self) -> Option<String> {
+        self.text(TLV_PLATFORM)
+    }
+
+    pub fn software_version(&self) -> Option<String> {
+        self.text(TLV_SOFTWARE)
+    }
+
+    pub fn native_vlan(&self) -> Option<u16> {
+        let value = self.tlv(TLV_NATIVE_VLAN)?;
+        Some(u16::from_be_bytes([*value.first()?, *value.get(1)?]))
+    }
+
+    pub fn capabilities(&self) -> Option<u32> {
+        let value: [u8; 4] = self.tlv(TLV_CAPABILITIES)?.try_into().ok()?;
+        Some(u32::from_be_bytes(value))
+    }
+
+    /// Full duplex when true
+    pub fn duplex(&self) -> Option<bool> {
+        self.tlv(TLV_DUPLEX)?.first().map(|&d| d != 0)
+    }
+
+    /// Interface addresses of the sender
+    pub fn addresses(&self) -> Vec<IpAddr> {
+        self.tlv(TLV_ADDRESSES).map(parse_addresses).unwrap_or_default()
+    }
+
+    pub fn management_addresses(&self) -> Vec<IpAddr> {
+        self.tlv(TLV_MANAGEMENT_ADDRESSES).map(parse_addresses).unwrap_or_default()
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![ControlField {
+            name: "CDP Version".to_string(),
+            value: self.version().to_string(),
+            description: format!("Hold time {}s", self.ttl()),
+        }];
+        let mut text = |name: &str, value: Option<String>, description: &str| {
+            if let Some(value) = value {
+                fields.push(ControlField { name: name.to_string(), value, description: description.to_string() });
+            }
+        };
+        text("CDP Device ID", self.device_id(), "Neighbor device name");
+        text("CDP Port ID", self.port_id(), "Sending interface");
+        text("CDP Platform", self.platform(), "Hardware platform");
+        text("CDP Native VLAN", self.native_vlan().map(|v| v.to_string()), "Untagged VLAN on the port");
+        text(
+            "CDP Capabilities",
+            self.capabilities().map(|c| format!("0x{:08x}", c)),
+            &self.capabilities().map(capability_names).unwrap_or_default(),
+        );
+        text(
+            "CDP Duplex",
+            self.duplex().map(|full| if full { "Full" } else { "Half" }.to_string()),
+            "Port duplex",
+        );
+        let join = |addresses: Vec<IpAddr>| {
+            (!addresses.is_empty()).then(|| addresses.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", "))
+        };
+        text("CDP Addresses", join(self.addresses()), "Interface addresses");
+        text("CDP Management Address", join(self.management_addresses()), "Management addresses");
+        fields
+    }
+}
+
+/// Decodes an address list: a count, then NLPID or 802.2 protocol, length and address for each
+fn parse_addresses(value: &[u8]) -> Vec<IpAddr> {
+    let mut addresses = Vec::new();
+    let Some(count) = value.get(..4) else { return addresses };
+    let count = u32::from_be_bytes([count[0], count[1], count[2], count[3]]);
+    let mut rest = &value[4..];
+    for _ in 0..count {
+        let Some(&protocol_len) = rest.get(1) else { break };
+        let protocol_len = protocol_len as usize;
+        let Some(protocol) = rest.get(2..2 + protocol_len) else { break };
+        let Some(len) = rest.get(2 + protocol_len..4 + protocol_len) else { break };
+        let len = u16::from_be_bytes([len[0], len[1]]) as usize;
+        let start = 4 + protocol_len;
+        let Some(address) = rest.get(start..start + len) else { break };
+        match (protocol, address.len()) {
+            ([0xCC], 4) => addresses.push(IpAddr::V4(Ipv4Addr::new(address[0], address[1], address[2], address[3]))),
+            ([.., 0x86, 0xDD], 16) => {
+                let octets: [u8; 16] = address.try_into().unwrap_or_default();
+                addresses.push(IpAddr::V6(Ipv6Addr::from(octets)));
+            }
+            _ => {}
+        }
+        rest = &rest[start + len..];
+    }
+    addresses
+}
+
+fn capability_names(capabilities: u32) -> String {
+    let names = [
+        (0x01, "Router"),
+        (0x02, "Transparent Bridge"),
+        (0x04, "Source Route Bridge"),
+        (0x08, "Switch"),
+        (0x10, "Host"),
+        (0x20, "IGMP"),
+        (0x40, "Repeater"),
+        (0x80, "VoIP Phone"),
+    ];
+    names.iter().filter(|(bit, _)| capabilities & bit != 0).map(|(_, name)| *name).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tlv(tlv_type: u16, value: &[u8]) -> Vec<u8> {
+        let mut tlv = tlv_type.to_be_bytes().to_vec();
+        tlv.extend_from_slice(&(value.len() as u16 + 4).to_be_bytes());
+        tlv.extend_from_slice(value);
+        tlv
+    }
+
+    #[test]
+    fn decodes_neighbor_information() {
+        let mut data = vec![2, 180, 0, 0];
+        data.extend(tlv(TLV_DEVICE_ID, b"sw-core-1.example.com"));
+        data.extend(tlv(TLV_PORT_ID, b"GigabitEthernet1/0/24"));
+        data.extend(tlv(TLV_PLATFORM, b"cisco WS-C3850-48P"));
+        data.extend(tlv(TLV_CAPABILITIES, &0x28u32.to_be_bytes()));
+        data.extend(tlv(TLV_NATIVE_VLAN, &[0, 10]));
+        data.extend(tlv(TLV_MANAGEMENT_ADDRESSES, &[0, 0, 0, 1, 1, 1, 0xCC, 0, 4, 10, 0, 0, 2]));
+
+        let packet = CdpPacket::parse(&data).unwrap();
+        assert_eq!(packet.device_id().as_deref(), Some("sw-core-1.example.com"));
+        assert_eq!(packet.port_id().as_deref(), Some("GigabitEthernet1/0/24"));
+        assert_eq!(packet.platform().as_deref(), Some("cisco WS-C3850-48P"));
+        assert_eq!(packet.native_vlan(), Some(10));
+        assert_eq!(packet.management_addresses(), vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))]);
+        let fields = packet.get_control_fields();
+        assert!(fields.iter().any(|f| f.name == "CDP Capabilities" && f.description == "Switch, IGMP"));
+        assert!(matches!(CdpPacket::parse(&[3, 0, 0, 0]), Err(CdpError::UnsupportedVersion(3))));
+    }
+}
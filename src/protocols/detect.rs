@@ -0,0 +1,219 @@
+use super::frame_control::ControlField;
+use super::http2::PREFACE;
+use super::quic::{QUIC_V1, QUIC_V2};
+
+/// Detections below this confidence are not reported
+pub const MIN_CONFIDENCE: u8 = 50;
+
+/// An application protocol recognized from the payload alone, whatever the port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Detection {
+    pub protocol: &'static str,
+    /// 0-100; exact magic strings score high, loose structural matches lower
+    pub confidence: u8,
+    pub evidence: &'static str,
+}
+
+impl Detection {
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        vec![ControlField {
+            name: "Detected Protocol".to_string(),
+            value: self.protocol.to_string(),
+            description: format!("{}% confidence: {}", self.confidence, self.evidence),
+        }]
+    }
+}
+
+const HTTP_METHODS: &[&[u8]] =
+    &[b"GET ", b"POST ", b"PUT ", b"HEAD ", b"DELETE ", b"OPTIONS ", b"PATCH ", b"CONNECT ", b"TRACE "];
+
+/// Identifies the protocol of a TCP segment's payload by its content
+pub fn detect_tcp(payload: &[u8]) -> Option<Detection> {
+    let detection = if payload.starts_with(PREFACE) {
+        found("HTTP/2", 100, "connection preface")
+    } else if payload.starts_with(b"SSH-2.0-") || payload.starts_with(b"SSH-1.99-") {
+        found("SSH", 100, "version banner")
+    } else if payload.starts_with(b"\x13BitTorrent protocol") {
+        found("BitTorrent", 100, "peer handshake")
+    } else if is_smb(payload) {
+        found("SMB", 95, "SMB header after NetBIOS session")
+    } else if let Some(detection) = http_or_rtsp(payload) {
+        detection
+    } else if is_tls_handshake(payload) {
+        found("TLS", 90, "handshake record")
+    } else if is_tls_record(payload) {
+        found("TLS", 60, "record header")
+    } else {
+        greeting(payload)?
+    };
+    Some(detection).filter(|d| d.confidence >= MIN_CONFIDENCE)
+}
+
+/// Identifies the protocol of a UDP datagram by its content
+pub fn detect_udp(payload: &[u8]) -> Option<Detection> {
+    let detection = if is_quic_long_header(payload) {
+        found("QUIC", 90, "long header with a known version")
+    } else if is_dns(payload) {
+        found("DNS", 70, "header with matching question section")
+    } else if payload.starts_with(b"d1:ad2:id20:") || payload.starts_with(b"d1:rd2:id20:") {
+        found("BitTorrent DHT", 90, "bencoded KRPC message")
+    } else if payload.starts_with(b"M-SEARCH * HTTP/1.1\r\n") || payload.starts_with(b"NOTIFY * HTTP/1.1\r\n") {
+        found("SSDP", 95, "HTTP over UDP request line")
+    } else {
+        return None;
+    };
+    Some(detection).filter(|d| d.confidence >= MIN_CONFIDENCE)
+}
+
+fn found(protocol: &'static str, confidence: u8, evidence: &'static str) -> Detection {
+    Detection { protocol, confidence, evidence }
+}
+
+fn http_or_rtsp(payload: &[u8]) -> Option<Detection> {
+    let line = first_line(payload)?;
+    if line.starts_with(b"HTTP/1.") && line.get(8) == Some(&b' ') && line.get(9..12).is_some_and(|s| s.iter().all(u8::is_ascii_digit)) {
+        return Some(found("HTTP", 95, "status line"));
+    }
+    if line.starts_with(b"RTSP/1.0 ") {
+        return Some(found("RTSP", 95, "status line"));
+    }
+    if !HTTP_METHODS.iter().any(|m| line.starts_with(m)) {
+        return None;
+    }
+    if line.ends_with(b" HTTP/1.1") || line.ends_with(b" HTTP/1.0") {
+        Some(found("HTTP", 95, "request line"))
+    } else if line.ends_with(b" RTSP/1.0") {
+        Some(found("RTSP", 95, "request line"))
+    } else {
+        // A method without a version could be the start of anything textual
+        Some(found("HTTP", 50, "request method"))
+    }
+}
+
+/// The first CRLF-terminated line, if it is printable ASCII
+fn first_line(payload: &[u8]) -> Option<&[u8]> {
+    let end = payload.windows(2).position(|w| w == b"\r\n")?;
+    let line = &payload[..end];
+    line.iter().all(|b| b.is_ascii_graphic() || *b == b' ').then_some(line)
+}
+
+/// SMTP, FTP and POP3/IMAP servers greet first with a recognizable line
+fn greeting(payload: &[u8]) -> Option<Detection> {
+    let line = first_line(payload)?;
+    if line.starts_with(b"220 ") || line.starts_with(b"220-") {
+        let text = line.to_ascii_uppercase();
+        let contains = |needle: &[u8]| text.windows(needle.len()).any(|w| w == needle);
+        return Some(if contains(b"SMTP") {
+            found("SMTP", 90, "220 greeting naming SMTP")
+        } else if contains(b"FTP") {
+            found("FTP", 90, "220 greeting naming FTP")
+        } else {
+            found("SMTP/FTP", 55, "220 greeting")
+        });
+    }
+    if line.starts_with(b"EHLO ") || line.starts_with(b"HELO ") {
+        return Some(found("SMTP", 80, "client greeting"));
+    }
+    if line.starts_with(b"+OK") {
+        return Some(found("POP3", 60, "+OK greeting"));
+    }
+    if line.starts_with(b"* OK") {
+        return Some(found("IMAP", 80, "untagged OK greeting"));
+    }
+    None
+}
+
+fn is_tls_record(payload: &[u8]) -> bool {
+    payload.len() >= 5
+        && (0x14..=0x17).contains(&payload[0])
+        && payload[1] == 0x03
+        && payload[2] <= 0x04
+        && u16::from_be_bytes([payload[3], payload[4]]) <= 16384 + 2048
+}
+
+/// A handshake record holding a ClientHello or ServerHello of matching length
+fn is_tls_handshake(payload: &[u8]) -> bool {
+    if !is_tls_record(payload) || payload[0] != 0x16 || payload.len() < 9 {
+        return false;
+    }
+    let record_len = u16::from_be_bytes([payload[3], payload[4]]) as usize;
+    let message_len = u32::from_be_bytes([0, payload[6], payload[7], payload[8]]) as usize;
+    matches!(payload[5], 0x01 | 0x02) && message_len + 4 <= record_len
+}
+
+fn is_smb(payload: &[u8]) -> bool {
+    // NetBIOS session message: type 0, 24-bit length
+    payload.len() >= 8
+        && payload[0] == 0x00
+        && (payload[4..8] == *b"\xffSMB" || payload[4..8] == *b"\xfeSMB")
+        && u32::from_be_bytes([0, payload[1], payload[2], payload[3]]) as usize >= payload.len() - 4
+}
+
+fn is_quic_long_header(payload: &[u8]) -> bool {
+    if payload.len() < 7 || payload[0] & 0xc0 != 0xc0 {
+        return false;
+    }
+    let version = u32::from_be_bytes([payload[1], payload[2], payload[3], payload[4]]);
+    (version == QUIC_V1 || version == QUIC_V2) && payload[5] <= 20
+}
+
+/// A DNS header whose question count matches a well-formed first question
+fn is_dns(payload: &[u8]) -> bool {
+    if payload.len() < 17 {
+        return false;
+    }
+    let flags = u16::from_be_bytes([payload[2], payload[3]]);
+    let questions = u16::from_be_bytes([payload[4], payload[5]]);
+    // Opcode 0-2 or 4-5, Z bit clear, exactly one question as in practice
+    let opcode = (flags >> 11) & 0xf;
+    if questions != 1 || !matches!(opcode, 0..=2 | 4 | 5) || flags & 0x0040 != 0 {
+        return false;
+    }
+    let mut pos = 12;
+    loop {
+        let Some(&len) = payload.get(pos) else { return false };
+        pos += 1;
+        match len {
+            0 => break,
+            1..=63 => {
+                let Some(label) = payload.get(pos..pos + len as usize) else { return false };
+                if !label.iter().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_')) {
+                    return false;
+                }
+                pos += len as usize;
+            }
+            _ => return false,
+        }
+    }
+    // QTYPE and QCLASS (IN, or IN with the mDNS unicast-response bit)
+    payload.get(pos + 2..pos + 4).is_some_and(|class| matches!(u16::from_be_bytes([class[0], class[1]]) & 0x7fff, 1 | 255))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn recognizes_tcp_protocols_by_content() {
+        let detect = |payload: &[u8]| detect_tcp(payload).map(|d| (d.protocol, d.confidence));
+        assert_eq!(detect(b"GET /index.html HTTP/1.1\r\nHost: a\r\n\r\n"), Some(("HTTP", 95)));
+        assert_eq!(detect(b"HTTP/1.1 404 Not Found\r\n\r\n"), Some(("HTTP", 95)));
+        assert_eq!(detect(b"SSH-2.0-OpenSSH_9.6\r\n"), Some(("SSH", 100)));
+        assert_eq!(detect(b"220 mail.example.com ESMTP Postfix\r\n"), Some(("SMTP", 90)));
+        assert_eq!(detect(PREFACE), Some(("HTTP/2", 100)));
+        let hello = [&[0x16, 0x03, 0x01, 0x00, 0x30, 0x01, 0x00, 0x00, 0x2c, 0x03, 0x03][..], &[0; 44]].concat();
+        assert_eq!(detect(&hello), Some(("TLS", 90)));
+        assert_eq!(detect(b"hello world\r\n"), None);
+        assert_eq!(detect(b"\x00\x01\x02\x03"), None);
+    }
+
+    #[test]
+    fn recognizes_udp_protocols_by_content() {
+        let query = testutil::dns_query_frame(1, "example.com");
+        assert_eq!(detect_udp(&query[42..]).map(|d| d.protocol), Some("DNS"));
+        let initial = [0xc3, 0x00, 0x00, 0x00, 0x01, 0x08, 1, 2, 3, 4, 5, 6, 7, 8, 0x00];
+        assert_eq!(detect_udp(&initial).map(|d| d.protocol), Some("QUIC"));
+        assert_eq!(detect_udp(&[0u8; 40]), None);
+    }
+}
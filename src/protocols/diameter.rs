@@ -0,0 +1,300 @@
+use super::frame_control::ControlField;
+use std::fmt;
+
+pub const DIAMETER_PORT: u16 = 3868;
+/// SCTP payload protocol identifier of Diameter
+pub const DIAMETER_PPID: u32 = 46;
+
+const FLAG_REQUEST: u8 = 0x80;
+const AVP_FLAG_VENDOR: u8 = 0x80;
+
+pub const AVP_USER_NAME: u32 = 1;
+pub const AVP_SESSION_ID: u32 = 263;
+pub const AVP_ORIGIN_HOST: u32 = 264;
+pub const AVP_RESULT_CODE: u32 = 268;
+pub const AVP_DESTINATION_REALM: u32 = 283;
+pub const AVP_DESTINATION_HOST: u32 = 293;
+pub const AVP_ORIGIN_REALM: u32 = 296;
+
+/// Diameter message parser (RFC 6733)
+pub struct DiameterMessage<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum DiameterError {
+    TooShort,
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for DiameterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiameterError::TooShort => write!(f, "Packet too short for Diameter message"),
+            DiameterError::UnsupportedVersion(v) => write!(f, "Unsupported Diameter version {}", v),
+        }
+    }
+}
+
+/// A single attribute-value pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Avp<'a> {
+    pub code: u32,
+    pub vendor: Option<u32>,
+    pub data: &'a [u8],
+}
+
+impl<'a> DiameterMessage<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, DiameterError> {
+        if data.len() < 20 {
+            return Err(DiameterError::TooShort);
+        }
+        if data[0] != 1 {
+            return Err(DiameterError::UnsupportedVersion(data[0]));
+        }
+        let length = u32::from_be_bytes([0, data[1], data[2], data[3]]) as usize;
+        if length < 20 || data.len() < length {
+            return Err(DiameterError::TooShort);
+        }
+        Ok(DiameterMessage { data: &data[..length] })
+    }
+
+    /// Every complete message in a TCP segment or SCTP chunk
+    pub fn parse_all(mut data: &'a [u8]) -> Vec<Self> {
+        let mut messages = Vec::new();
+        while let Ok(message) = DiameterMessage::parse(data) {
+            data = &data[message.data.len()..];
+            messages.push(message);
+        }
+        messages
+    }
+
+    pub fn is_request(&self) -> bool {
+        self.data[4] & FLAG_REQUEST != 0
+    }
+
+    pub fn command_code(&self) -> u32 {
+        u32::from_be_bytes([0, self.data[5], self.data[6], self.data[7]])
+    }
+
+    pub fn application_id(&self) -> u32 {
+        u32::from_be_bytes([self.data[8], self.data[9], self.data[10], self.data[11]])
+    }
+
+    pub fn hop_by_hop(&self) -> u32 {
+        u32::from_be_bytes([self.data[12], self.data[13], self.data[14], self.data[15]])
+    }
+
+    pub fn end_to_end(&self) -> u32 {
+        u32::from_be_bytes([self.data[16], self.data[17], self.data[18], self.data[19]])
+    }
+
+    /// Top-level AVPs; each is padded to a 4-byte boundary
+    pub fn avps(&self) -> Vec<Avp<'a>> {
+        let mut avps = Vec::new();
+        let mut rest = &self.data[20..];
+        while rest.len() >= 8 {
+            let code = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]);
+            let len = u32::from_be_bytes([0, rest[5], rest[6], rest[7]]) as usize;
+            let header = if rest[4] & AVP_FLAG_VENDOR != 0 { 12 } else { 8 };
+            if len < header || len > rest.len() {
+                break;
+            }
+            let vendor = (header == 12).then(|| u32::from_be_bytes([rest[8], rest[9], rest[10], rest[11]]));
+            avps.push(Avp { code, vendor, data: &rest[header..len] });
+            rest = &rest[len.next_multiple_of(4).min(rest.len())..];
+        }
+        avps
+    }
+
+    fn avp(&self, code: u32) -> Option<Avp<'a>> {
+        self.avps().into_iter().find(|avp| avp.code == code && avp.vendor.is_none())
+    }
+
+    fn text(&self, code: u32) -> Option<String> {
+        self.avp(code).map(|avp| String::from_utf8_lossy(avp.data).into_owned())
+    }
+
+    pub fn session_id(&self) -> Option<String> {
+        self.text(AVP_SESSION_ID)
+    }
+
+    pub fn origin_host(&self) -> Option<String> {
+        self.text(AVP_ORIGIN_HOST)
+    }
+
+    pub fn origin_realm(&self) -> Option<String> {
+        self.text(AVP_ORIGIN_REALM)
+    }
+
+    pub fn destination_realm(&self) -> Option<String> {
+        self.text(AVP_DESTINATION_REALM)
+    }
+
+    pub fn user_name(&self) -> Option<String> {
+        self.text(AVP_USER_NAME)
+    }
+
+    pub fn result_code(&self) -> Option<u32> {
+        let value: [u8; 4] = self.avp(AVP_RESULT_CODE)?.data.try_into().ok()?;
+        Some(u32::from_be_bytes(value))
+    }
+
+    pub fn get_command_name(&self) -> String {
+        let kind = if self.is_request() { "Request" } else { "Answer" };
+        format!("{} {}", command_name(self.command_code()), kind)
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![
+            ControlField {
+                name: "Diameter Command".to_string(),
+                value: self.get_command_name(),
+                description: format!("Code {}, hop-by-hop 0x{:08x}", self.command_code(), self.hop_by_hop()),
+            },
+            ControlField {
+                name: "Diameter Application".to_string(),
+                value: application_name(self.application_id()),
+                description: format!("Application ID {}", self.application_id()),
+            },
+        ];
+        if let Some(session) = self.session_id() {
+            fields.push(ControlField {
+                name: "Diameter Session-Id".to_string(),
+                value: session,
+                description: "Session identifier".to_string(),
+            });
+        }
+        if let Some(host) = self.origin_host() {
+            fields.push(ControlField {
+                name: "Diameter Origin-Host".to_string(),
+                value: host,
+                description: self.origin_realm().map(|realm| format!("Realm {}", realm)).unwrap_or_default(),
+            });
+        }
+        if let Some(realm) = self.destination_realm() {
+            fields.push(ControlField {
+                name: "Diameter Destination-Realm".to_string(),
+                value: realm,
+                description: "Realm the request is routed to".to_string(),
+            });
+        }
+        if let Some(user) = self.user_name() {
+            fields.push(ControlField {
+                name: "Diameter User-Name".to_string(),
+                value: user,
+                description: "Subscriber identity".to_string(),
+            });
+        }
+        if let Some(code) = self.result_code() {
+            fields.push(ControlField {
+                name: "Diameter Result-Code".to_string(),
+                value: code.to_string(),
+                description: result_code_name(code),
+            });
+        }
+        fields
+    }
+}
+
+pub fn command_name(code: u32) -> String {
+    match code {
+        257 => "Capabilities-Exchange".to_string(),
+        258 => "Re-Auth".to_string(),
+        271 => "Accounting".to_string(),
+        272 => "Credit-Control".to_string(),
+        274 => "Abort-Session".to_string(),
+        275 => "Session-Termination".to_string(),
+        280 => "Device-Watchdog".to_string(),
+        282 => "Disconnect-Peer".to_string(),
+        300 => "User-Authorization".to_string(),
+        301 => "Server-Assignment".to_string(),
+        302 => "Location-Info".to_string(),
+        303 => "Multimedia-Auth".to_string(),
+        316 => "Update-Location".to_string(),
+        317 => "Cancel-Location".to_string(),
+        318 => "Authentication-Information".to_string(),
+        319 => "Insert-Subscriber-Data".to_string(),
+        320 => "Delete-Subscriber-Data".to_string(),
+        321 => "Purge-UE".to_string(),
+        323 => "Notify".to_string(),
+        c => format!("Unknown ({})", c),
+    }
+}
+
+pub fn application_name(id: u32) -> String {
+    match id {
+        0 => "Diameter Common Messages".to_string(),
+        3 => "Base Accounting".to_string(),
+        4 => "Credit Control".to_string(),
+        16777216 => "3GPP Cx".to_string(),
+        16777217 => "3GPP Sh".to_string(),
+        16777236 => "3GPP Rx".to_string(),
+        16777238 => "3GPP Gx".to_string(),
+        16777251 => "3GPP S6a/S6d".to_string(),
+        16777252 => "3GPP S13".to_string(),
+        16777272 => "3GPP S6b".to_string(),
+        0xFFFF_FFFF => "Relay".to_string(),
+        a => format!("Unknown ({})", a),
+    }
+}
+
+pub fn result_code_name(code: u32) -> String {
+    match code {
+        1001 => "Multi-round auth".to_string(),
+        2001 => "Success".to_string(),
+        2002 => "Limited success".to_string(),
+        3001 => "Command unsupported".to_string(),
+        3002 => "Unable to deliver".to_string(),
+        3003 => "Realm not served".to_string(),
+        3004 => "Too busy".to_string(),
+        3007 => "Application unsupported".to_string(),
+        4001 => "Authentication rejected".to_string(),
+        5001 => "AVP unsupported".to_string(),
+        5003 => "Authorization rejected".to_string(),
+        5005 => "Missing AVP".to_string(),
+        5012 => "Unable to comply".to_string(),
+        c => match c / 1000 {
+            1 => "Informational".to_string(),
+            2 => "Success".to_string(),
+            3 => "Protocol error".to_string(),
+            4 => "Transient failure".to_string(),
+            5 => "Permanent failure".to_string(),
+            _ => format!("Unknown ({})", c),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn decodes_credit_control_request() {
+        let data = testutil::diameter_message(
+            FLAG_REQUEST,
+            272,
+            4,
+            &[(AVP_SESSION_ID, b"pgw.example.com;1;42"), (AVP_ORIGIN_HOST, b"pgw.example.com"), (AVP_ORIGIN_REALM, b"example.com")],
+        );
+        let message = DiameterMessage::parse(&data).unwrap();
+        assert_eq!(message.get_command_name(), "Credit-Control Request");
+        assert_eq!(application_name(message.application_id()), "Credit Control");
+        assert_eq!(message.session_id().as_deref(), Some("pgw.example.com;1;42"));
+        let fields = message.get_control_fields();
+        assert!(fields.iter().any(|f| f.name == "Diameter Origin-Host" && f.description == "Realm example.com"));
+    }
+
+    #[test]
+    fn splits_messages_and_reads_result_codes() {
+        let mut data = testutil::diameter_message(0, 280, 0, &[(AVP_RESULT_CODE, &2001u32.to_be_bytes())]);
+        data.extend(testutil::diameter_message(FLAG_REQUEST, 280, 0, &[(AVP_ORIGIN_HOST, b"hss")]));
+        let messages = DiameterMessage::parse_all(&data);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].get_command_name(), "Device-Watchdog Answer");
+        assert_eq!(messages[0].result_code(), Some(2001));
+        assert_eq!(messages[1].origin_host().as_deref(), Some("hss"));
+        assert!(matches!(DiameterMessage::parse(&[2; 20]), Err(DiameterError::UnsupportedVersion(2))));
+    }
+}
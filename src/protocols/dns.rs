@@ -0,0 +1,366 @@
+use super::frame_control::ControlField;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+pub const DNS_PORT: u16 = 53;
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_CNAME: u16 = 5;
+pub const TYPE_MX: u16 = 15;
+pub const TYPE_TXT: u16 = 16;
+pub const TYPE_AAAA: u16 = 28;
+
+/// Compression pointers followed while reading one name
+const MAX_POINTERS: usize = 32;
+
+/// DNS message parser (RFC 1035)
+pub struct DnsMessage<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum DnsError {
+    TooShort,
+}
+
+impl fmt::Display for DnsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsError::TooShort => write!(f, "Packet too short for DNS header"),
+        }
+    }
+}
+
+/// An entry of the question section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsQuestion {
+    pub name: String,
+    pub qtype: u16,
+}
+
+/// Data of an answer record, decoded for the common types
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Cname(String),
+    Mx { preference: u16, exchange: String },
+    Txt(Vec<String>),
+    Other { rtype: u16, length: usize },
+}
+
+impl RecordData {
+    /// The address of an A or AAAA record
+    pub fn address(&self) -> Option<IpAddr> {
+        match self {
+            RecordData::A(ip) => Some(IpAddr::V4(*ip)),
+            RecordData::Aaaa(ip) => Some(IpAddr::V6(*ip)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RecordData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordData::A(ip) => write!(f, "A {}", ip),
+            RecordData::Aaaa(ip) => write!(f, "AAAA {}", ip),
+            RecordData::Cname(name) => write!(f, "CNAME {}", name),
+            RecordData::Mx { preference, exchange } => write!(f, "MX {} {}", preference, exchange),
+            RecordData::Txt(strings) => {
+                let strings: Vec<String> = strings.iter().map(|s| format!("{:?}", s)).collect();
+                write!(f, "TXT {}", strings.join(" "))
+            }
+            RecordData::Other { rtype, length } => write!(f, "{} ({} bytes)", type_name(*rtype), length),
+        }
+    }
+}
+
+/// A resource record of the answer section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsRecord {
+    pub name: String,
+    pub ttl: u32,
+    pub data: RecordData,
+}
+
+/// Mnemonic of a record type, e.g. `A` or `TYPE65`
+pub fn type_name(rtype: u16) -> String {
+    let name = match rtype {
+        TYPE_A => "A",
+        2 => "NS",
+        TYPE_CNAME => "CNAME",
+        6 => "SOA",
+        12 => "PTR",
+        TYPE_MX => "MX",
+        TYPE_TXT => "TXT",
+        TYPE_AAAA => "AAAA",
+        33 => "SRV",
+        41 => "OPT",
+        64 => "SVCB",
+        65 => "HTTPS",
+        255 => "ANY",
+        other => return format!("TYPE{}", other),
+    };
+    name.to_string()
+}
+
+/// Reads a (possibly compressed) name at `at` of a message in the DNS layout,
+/// returning it and the offset just past it. Shared with LLMNR, mDNS and NBNS.
+pub(crate) fn read_name(message: &[u8], mut at: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    let mut pointers = 0;
+    loop {
+        let len = *message.get(at)? as usize;
+        match len {
+            0 => {
+                let name = if labels.is_empty() { ".".to_string() } else { labels.join(".") };
+                return Some((name, end.unwrap_or(at + 1)));
+            }
+            0xC0.. => {
+                pointers += 1;
+                if pointers > MAX_POINTERS {
+                    return None;
+                }
+                let pointer = (u16::from_be_bytes([*message.get(at)?, *message.get(at + 1)?]) & 0x3FFF) as usize;
+                end.get_or_insert(at + 2);
+                at = pointer;
+            }
+            // Extended and binary labels are obsolete
+            0x40.. => return None,
+            _ => {
+                let label = message.get(at + 1..at + 1 + len)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                at += 1 + len;
+            }
+        }
+    }
+}
+
+impl<'a> DnsMessage<'a> {
+    /// Parses a message as carried over UDP
+    pub fn parse(data: &'a [u8]) -> Result<Self, DnsError> {
+        if data.len() < 12 {
+            return Err(DnsError::TooShort);
+        }
+        Ok(DnsMessage { data })
+    }
+
+    /// Parses a message at the start of a TCP segment, after its two-byte length
+    pub fn parse_tcp(data: &'a [u8]) -> Result<Self, DnsError> {
+        let length = u16::from_be_bytes([*data.first().ok_or(DnsError::TooShort)?, *data.get(1).ok_or(DnsError::TooShort)?]) as usize;
+        let end = (2 + length).min(data.len());
+        Self::parse(&data[2..end])
+    }
+
+    fn u16_at(&self, at: usize) -> u16 {
+        u16::from_be_bytes([self.data[at], self.data[at + 1]])
+    }
+
+    pub fn id(&self) -> u16 {
+        self.u16_at(0)
+    }
+
+    pub fn flags(&self) -> u16 {
+        self.u16_at(2)
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.flags() & 0x8000 != 0
+    }
+
+    pub fn opcode(&self) -> u8 {
+        ((self.flags() >> 11) & 0x0F) as u8
+    }
+
+    /// Response code: 0 NOERROR, 2 SERVFAIL, 3 NXDOMAIN, ...
+    pub fn rcode(&self) -> u8 {
+        (self.flags() & 0x0F) as u8
+    }
+
+    pub fn questions(&self) -> Vec<DnsQuestion> {
+        let mut questions = Vec::new();
+        let mut at = 12;
+        for _ in 0..self.u16_at(4) {
+            let Some((name, next)) = read_name(self.data, at) else { break };
+            let Some(qtype) = self.data.get(next..next + 2) else { break };
+            questions.push(DnsQuestion { name, qtype: u16::from_be_bytes([qtype[0], qtype[1]]) });
+            at = next + 4;
+        }
+        questions
+    }
+
+    /// Name of the first question
+    pub fn question_name(&self) -> Option<String> {
+        self.questions().into_iter().next().map(|question| question.name)
+    }
+
+    /// Records of the answer section; the authority and additional sections are skipped
+    pub fn answers(&self) -> Vec<DnsRecord> {
+        let mut at = 12;
+        for _ in 0..self.u16_at(4) {
+            let Some((_, next)) = read_name(self.data, at) else { return Vec::new() };
+            at = next + 4;
+        }
+        let mut answers = Vec::new();
+        for _ in 0..self.u16_at(6) {
+            let Some((name, next)) = read_name(self.data, at) else { break };
+            let Some(header) = self.data.get(next..next + 10) else { break };
+            let rtype = u16::from_be_bytes([header[0], header[1]]);
+            let ttl = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+            let rdlength = u16::from_be_bytes([header[8], header[9]]) as usize;
+            let start = next + 10;
+            let Some(rdata) = self.data.get(start..start + rdlength) else { break };
+            let data = match (rtype, rdata.len()) {
+                (TYPE_A, 4) => RecordData::A(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3])),
+                (TYPE_AAAA, 16) => RecordData::Aaaa(Ipv6Addr::from(<[u8; 16]>::try_from(rdata).unwrap_or_default())),
+                (TYPE_CNAME, _) => match read_name(self.data, start) {
+                    Some((cname, _)) => RecordData::Cname(cname),
+                    None => break,
+                },
+                (TYPE_MX, 3..) => match read_name(self.data, start + 2) {
+                    Some((exchange, _)) => RecordData::Mx { preference: u16::from_be_bytes([rdata[0], rdata[1]]), exchange },
+                    None => break,
+                },
+                (TYPE_TXT, _) => RecordData::Txt(character_strings(rdata)),
+                (rtype, length) => RecordData::Other { rtype, length },
+            };
+            answers.push(DnsRecord { name, ttl, data });
+            at = start + rdlength;
+        }
+        answers
+    }
+
+    /// Name of the response code, e.g. `NXDOMAIN`
+    pub fn get_rcode_name(&self) -> String {
+        let name = match self.rcode() {
+            0 => "NOERROR",
+            1 => "FORMERR",
+            2 => "SERVFAIL",
+            3 => "NXDOMAIN",
+            4 => "NOTIMP",
+            5 => "REFUSED",
+            other => return format!("rcode {}", other),
+        };
+        name.to_string()
+    }
+
+    /// What the flags say, e.g. `Standard query, recursion desired` or `Response, NXDOMAIN`
+    pub fn get_flags_description(&self) -> String {
+        let opcode = match self.opcode() {
+            0 => "Standard query".to_string(),
+            1 => "Inverse query".to_string(),
+            2 => "Status".to_string(),
+            4 => "Notify".to_string(),
+            5 => "Update".to_string(),
+            other => format!("Opcode {}", other),
+        };
+        let flag = |bit: u16, name: &str| (self.flags() & bit != 0).then(|| name.to_string());
+        let parts: Vec<String> = if self.is_response() {
+            let kind = if self.opcode() == 0 { "Response".to_string() } else { format!("{} response", opcode) };
+            [Some(kind), Some(self.get_rcode_name()), flag(0x0400, "authoritative"), flag(0x0200, "truncated")].into_iter().flatten().collect()
+        } else {
+            [Some(opcode), flag(0x0100, "recursion desired")].into_iter().flatten().collect()
+        };
+        parts.join(", ")
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![
+            ControlField {
+                name: "DNS Transaction ID".to_string(),
+                value: format!("0x{:04x}", self.id()),
+                description: "Matches responses to queries".to_string(),
+            },
+            ControlField {
+                name: "DNS Flags".to_string(),
+                value: format!("0x{:04x}", self.flags()),
+                description: self.get_flags_description(),
+            },
+        ];
+        for question in self.questions() {
+            fields.push(ControlField {
+                name: "DNS Query".to_string(),
+                value: question.name,
+                description: format!("{} record asked for", type_name(question.qtype)),
+            });
+        }
+        for answer in self.answers() {
+            fields.push(ControlField {
+                name: "DNS Answer".to_string(),
+                value: format!("{} {}", answer.name, answer.data),
+                description: format!("TTL {} seconds", answer.ttl),
+            });
+        }
+        fields
+    }
+}
+
+/// The length-prefixed strings of TXT record data
+fn character_strings(mut rdata: &[u8]) -> Vec<String> {
+    let mut strings = Vec::new();
+    while let Some((&len, rest)) = rdata.split_first() {
+        let Some(text) = rest.get(..len as usize) else { break };
+        strings.push(String::from_utf8_lossy(text).into_owned());
+        rdata = &rest[len as usize..];
+    }
+    strings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn parses_query_and_response() {
+        let query = testutil::dns_query(0x1234, "example.com", TYPE_AAAA);
+        let message = DnsMessage::parse(&query).unwrap();
+        assert!(!message.is_response());
+        assert_eq!(message.questions(), [DnsQuestion { name: "example.com".to_string(), qtype: TYPE_AAAA }]);
+        assert_eq!(message.get_flags_description(), "Standard query, recursion desired");
+
+        let response = testutil::dns_response(0x1234, "example.com", &[testutil::server_v4()]);
+        let message = DnsMessage::parse(&response).unwrap();
+        assert_eq!((message.id(), message.rcode()), (0x1234, 0));
+        let answers = message.answers();
+        assert_eq!(answers.len(), 1);
+        assert_eq!(answers[0].data.address(), Some(IpAddr::V4(testutil::server_v4())));
+        let fields = message.get_control_fields();
+        assert!(fields.iter().any(|f| f.name == "DNS Answer" && f.value == "example.com A 93.184.216.34" && f.description == "TTL 300 seconds"));
+
+        let mut tcp = (response.len() as u16).to_be_bytes().to_vec();
+        tcp.extend_from_slice(&response);
+        assert_eq!(DnsMessage::parse_tcp(&tcp).unwrap().id(), 0x1234);
+        assert!(DnsMessage::parse(&response[..8]).is_err());
+    }
+
+    #[test]
+    fn decodes_cname_mx_and_txt_records() {
+        let mut response = testutil::dns_response(7, "example.com", &[]);
+        response[7] = 3;
+        let record = |rtype: u16, rdata: &[u8]| {
+            let mut record = vec![0xc0, 0x0c];
+            record.extend_from_slice(&rtype.to_be_bytes());
+            record.extend_from_slice(&[0, 1, 0, 0, 0, 60]);
+            record.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            record.extend_from_slice(rdata);
+            record
+        };
+        // www.example.com, compressed against the question name
+        response.extend(record(TYPE_CNAME, &[3, b'w', b'w', b'w', 0xc0, 0x0c]));
+        response.extend(record(TYPE_MX, &[0, 10, 4, b'm', b'a', b'i', b'l', 0xc0, 0x0c]));
+        response.extend(record(TYPE_TXT, b"\x0bv=spf1 -all\x02ok"));
+
+        let answers: Vec<String> = DnsMessage::parse(&response).unwrap().answers().iter().map(|a| a.data.to_string()).collect();
+        assert_eq!(answers, ["CNAME www.example.com", "MX 10 mail.example.com", "TXT \"v=spf1 -all\" \"ok\""]);
+
+        // A pointer loop ends the answers instead of hanging
+        let mut looping = testutil::dns_response(8, "example.com", &[]);
+        looping[7] = 1;
+        let at = looping.len() + 12;
+        looping.extend(record(TYPE_CNAME, &[0xc0, at as u8]));
+        assert!(DnsMessage::parse(&looping).unwrap().answers().is_empty());
+    }
+}
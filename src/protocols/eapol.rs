@@ -0,0 +1,329 @@
+use super::frame_control::ControlField;
+use std::fmt;
+
+pub const ETHERTYPE_EAPOL: u16 = 0x888E;
+
+pub const EAPOL_EAP: u8 = 0;
+pub const EAPOL_START: u8 = 1;
+pub const EAPOL_LOGOFF: u8 = 2;
+pub const EAPOL_KEY: u8 = 3;
+
+pub const KEY_INFO_PAIRWISE: u16 = 0x0008;
+pub const KEY_INFO_INSTALL: u16 = 0x0040;
+pub const KEY_INFO_ACK: u16 = 0x0080;
+pub const KEY_INFO_MIC: u16 = 0x0100;
+pub const KEY_INFO_SECURE: u16 = 0x0200;
+
+/// Offsets inside an EAPOL-Key body (after the 4-byte EAPOL header)
+const KEY_NONCE: usize = 13;
+const KEY_MIC: usize = 77;
+const KEY_DATA_LEN: usize = 93;
+const KEY_DATA: usize = 95;
+
+/// EAPOL (802.1X) packet parser
+pub struct EapolPacket<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum EapolError {
+    TooShort,
+}
+
+impl fmt::Display for EapolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EapolError::TooShort => write!(f, "Packet too short for EAPOL"),
+        }
+    }
+}
+
+impl<'a> EapolPacket<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, EapolError> {
+        if data.len() < 4 {
+            return Err(EapolError::TooShort);
+        }
+        // Ethernet padding may follow the body
+        let len = 4 + u16::from_be_bytes([data[2], data[3]]) as usize;
+        let data = data.get(..len).ok_or(EapolError::TooShort)?;
+        if data[1] == EAPOL_KEY && data.len() < 4 + KEY_DATA {
+            return Err(EapolError::TooShort);
+        }
+        Ok(EapolPacket { data })
+    }
+
+    pub fn version(&self) -> u8 {
+        self.data[0]
+    }
+
+    pub fn packet_type(&self) -> u8 {
+        self.data[1]
+    }
+
+    pub fn body(&self) -> &'a [u8] {
+        &self.data[4..]
+    }
+
+    /// Whole packet, header included (what the EAPOL-Key MIC covers)
+    pub fn bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn get_type_name(&self) -> String {
+        match self.packet_type() {
+            EAPOL_EAP => "EAP Packet".to_string(),
+            EAPOL_START => "Start".to_string(),
+            EAPOL_LOGOFF => "Logoff".to_string(),
+            EAPOL_KEY => "Key".to_string(),
+            4 => "Encapsulated ASF Alert".to_string(),
+            5 => "MKA".to_string(),
+            t => format!("Unknown ({})", t),
+        }
+    }
+
+    /// EAP message carried in an EAP Packet
+    pub fn eap(&self) -> Option<EapMessage<'a>> {
+        (self.packet_type() == EAPOL_EAP).then(|| EapMessage::parse(self.body()).ok()).flatten()
+    }
+
+    /// Key descriptor carried in an EAPOL-Key frame
+    pub fn key(&self) -> Option<EapolKey<'a>> {
+        (self.packet_type() == EAPOL_KEY).then_some(EapolKey { body: self.body() })
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![ControlField {
+            name: "EAPOL Type".to_string(),
+            value: self.packet_type().to_string(),
+            description: format!("{} (802.1X-{})", self.get_type_name(), self.version()),
+        }];
+        if let Some(eap) = self.eap() {
+            fields.push(ControlField {
+                name: "EAP Code".to_string(),
+                value: eap.code().to_string(),
+                description: eap.get_code_name(),
+            });
+            if let Some(method) = eap.method() {
+                fields.push(ControlField {
+                    name: "EAP Method".to_string(),
+                    value: method.to_string(),
+                    description: eap_method_name(method),
+                });
+            }
+            if let Some(identity) = eap.identity() {
+                fields.push(ControlField {
+                    name: "EAP Identity".to_string(),
+                    value: identity,
+                    description: "Identity announced by the supplicant".to_string(),
+                });
+            }
+        }
+        if let Some(key) = self.key() {
+            fields.push(ControlField {
+                name: "Key Info".to_string(),
+                value: format!("0x{:04x}", key.key_info()),
+                description: key.get_flags_description(),
+            });
+            if let Some(message) = key.handshake_message() {
+                fields.push(ControlField {
+                    name: "Handshake Message".to_string(),
+                    value: message.to_string(),
+                    description: format!("4-way handshake message {} of 4", message),
+                });
+            }
+            fields.push(ControlField {
+                name: "Replay Counter".to_string(),
+                value: key.replay_counter().to_string(),
+                description: "Key replay counter".to_string(),
+            });
+        }
+        fields
+    }
+}
+
+/// EAP packet (RFC 3748)
+pub struct EapMessage<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> EapMessage<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, EapolError> {
+        if data.len() < 4 {
+            return Err(EapolError::TooShort);
+        }
+        let len = u16::from_be_bytes([data[2], data[3]]) as usize;
+        let data = data.get(..len.max(4)).ok_or(EapolError::TooShort)?;
+        Ok(EapMessage { data })
+    }
+
+    pub fn code(&self) -> u8 {
+        self.data[0]
+    }
+
+    pub fn identifier(&self) -> u8 {
+        self.data[1]
+    }
+
+    /// Method type of a Request or Response
+    pub fn method(&self) -> Option<u8> {
+        matches!(self.code(), 1 | 2).then(|| self.data.get(4).copied()).flatten()
+    }
+
+    /// Identity from an Identity Response
+    pub fn identity(&self) -> Option<String> {
+        (self.code() == 2 && self.method() == Some(1))
+            .then(|| String::from_utf8_lossy(&self.data[5..]).into_owned())
+    }
+
+    pub fn get_code_name(&self) -> String {
+        match self.code() {
+            1 => "Request".to_string(),
+            2 => "Response".to_string(),
+            3 => "Success".to_string(),
+            4 => "Failure".to_string(),
+            c => format!("Unknown ({})", c),
+        }
+    }
+}
+
+pub fn eap_method_name(method: u8) -> String {
+    match method {
+        1 => "Identity".to_string(),
+        2 => "Notification".to_string(),
+        3 => "Legacy Nak".to_string(),
+        4 => "MD5-Challenge".to_string(),
+        6 => "GTC".to_string(),
+        13 => "EAP-TLS".to_string(),
+        17 => "LEAP".to_string(),
+        18 => "EAP-SIM".to_string(),
+        21 => "EAP-TTLS".to_string(),
+        23 => "EAP-AKA".to_string(),
+        25 => "PEAP".to_string(),
+        26 => "MSCHAPv2".to_string(),
+        43 => "EAP-FAST".to_string(),
+        50 => "EAP-AKA'".to_string(),
+        52 => "EAP-pwd".to_string(),
+        254 => "Expanded".to_string(),
+        m => format!("Unknown ({})", m),
+    }
+}
+
+/// EAPOL-Key descriptor (802.11 RSN or WPA)
+pub struct EapolKey<'a> {
+    body: &'a [u8],
+}
+
+impl<'a> EapolKey<'a> {
+    /// 2 for RSN (WPA2/3), 254 for WPA
+    pub fn descriptor_type(&self) -> u8 {
+        self.body[0]
+    }
+
+    pub fn key_info(&self) -> u16 {
+        u16::from_be_bytes([self.body[1], self.body[2]])
+    }
+
+    /// Key descriptor version: 1 HMAC-MD5/RC4, 2 HMAC-SHA1/AES, 3 AES-CMAC
+    pub fn descriptor_version(&self) -> u8 {
+        (self.key_info() & 0x0007) as u8
+    }
+
+    pub fn replay_counter(&self) -> u64 {
+        u64::from_be_bytes(self.body[5..13].try_into().expect("8 bytes"))
+    }
+
+    pub fn nonce(&self) -> &'a [u8] {
+        &self.body[KEY_NONCE..KEY_NONCE + 32]
+    }
+
+    pub fn mic(&self) -> &'a [u8] {
+        &self.body[KEY_MIC..KEY_MIC + 16]
+    }
+
+    pub fn key_data(&self) -> &'a [u8] {
+        let len = u16::from_be_bytes([self.body[KEY_DATA_LEN], self.body[KEY_DATA_LEN + 1]]) as usize;
+        let end = (KEY_DATA + len).min(self.body.len());
+        &self.body[KEY_DATA..end]
+    }
+
+    fn has(&self, flag: u16) -> bool {
+        self.key_info() & flag != 0
+    }
+
+    /// Position in the 4-way handshake, `None` for group key messages
+    pub fn handshake_message(&self) -> Option<u8> {
+        if !self.has(KEY_INFO_PAIRWISE) {
+            return None;
+        }
+        Some(match (self.has(KEY_INFO_ACK), self.has(KEY_INFO_MIC), self.has(KEY_INFO_INSTALL)) {
+            (true, false, _) => 1,
+            (true, true, true) => 3,
+            (false, true, _) if self.nonce().iter().all(|&b| b == 0) || (self.has(KEY_INFO_SECURE) && self.key_data().is_empty()) => 4,
+            (false, true, _) => 2,
+            _ => return None,
+        })
+    }
+
+    pub fn get_flags_description(&self) -> String {
+        let names = [
+            (KEY_INFO_PAIRWISE, "Pairwise"),
+            (KEY_INFO_INSTALL, "Install"),
+            (KEY_INFO_ACK, "Ack"),
+            (KEY_INFO_MIC, "MIC"),
+            (KEY_INFO_SECURE, "Secure"),
+            (0x0400, "Error"),
+            (0x0800, "Request"),
+            (0x1000, "Encrypted Key Data"),
+        ];
+        let desc: Vec<&str> = names.iter().filter(|(flag, _)| self.has(*flag)).map(|(_, name)| *name).collect();
+        if desc.is_empty() { "None".to_string() } else { desc.join(", ") }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::eapol_key;
+
+    #[test]
+    fn decodes_eap_identity() {
+        let mut data = vec![1, EAPOL_EAP, 0, 10, 2, 7, 0, 10, 1];
+        data.extend_from_slice(b"alice");
+        data.extend_from_slice(&[0; 8]); // Ethernet padding
+        let packet = EapolPacket::parse(&data).unwrap();
+        let eap = packet.eap().unwrap();
+        assert_eq!(eap.get_code_name(), "Response");
+        assert_eq!(eap.identifier(), 7);
+        assert_eq!(eap.identity().as_deref(), Some("alice"));
+        let fields = packet.get_control_fields();
+        assert!(fields.iter().any(|f| f.name == "EAP Method" && f.description == "Identity"));
+        assert!(EapolPacket::parse(&[1, EAPOL_KEY, 0, 10]).is_err());
+    }
+
+    #[test]
+    fn numbers_four_way_handshake_messages() {
+        let anonce = [0xa1; 32];
+        let snonce = [0x5e; 32];
+        let messages = [
+            eapol_key(KEY_INFO_PAIRWISE | KEY_INFO_ACK | 2, 1, &anonce, &[0; 16], &[0xdd; 22]),
+            eapol_key(KEY_INFO_PAIRWISE | KEY_INFO_MIC | 2, 1, &snonce, &[0x33; 16], &[0x30; 22]),
+            eapol_key(KEY_INFO_PAIRWISE | KEY_INFO_ACK | KEY_INFO_MIC | KEY_INFO_INSTALL | KEY_INFO_SECURE | 2, 2, &anonce, &[0x44; 16], &[0; 56]),
+            eapol_key(KEY_INFO_PAIRWISE | KEY_INFO_MIC | KEY_INFO_SECURE | 2, 2, &[0; 32], &[0x55; 16], &[]),
+        ];
+        for (i, data) in messages.iter().enumerate() {
+            let packet = EapolPacket::parse(data).unwrap();
+            let key = packet.key().unwrap();
+            assert_eq!(key.handshake_message(), Some(i as u8 + 1));
+            assert_eq!(key.descriptor_version(), 2);
+        }
+        let m2 = EapolPacket::parse(&messages[1]).unwrap();
+        let key = m2.key().unwrap();
+        assert_eq!(key.nonce(), &snonce);
+        assert_eq!(key.mic(), &[0x33; 16]);
+        assert_eq!(key.key_data().len(), 22);
+        assert_eq!(key.get_flags_description(), "Pairwise, MIC");
+
+        let group = eapol_key(KEY_INFO_ACK | KEY_INFO_MIC | KEY_INFO_SECURE | 2, 3, &[0; 32], &[1; 16], &[0; 24]);
+        assert_eq!(EapolPacket::parse(&group).unwrap().key().unwrap().handshake_message(), None);
+    }
+}
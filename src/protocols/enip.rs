@@ -0,0 +1,424 @@
+use super::frame_control::ControlField;
+use std::fmt;
+
+/// Encapsulation port for explicit messaging (TCP and UDP)
+pub const ENIP_PORT: u16 = 44818;
+/// Class 1 implicit I/O connections
+pub const ENIP_IO_PORT: u16 = 2222;
+
+pub const CMD_LIST_IDENTITY: u16 = 0x0063;
+pub const CMD_REGISTER_SESSION: u16 = 0x0065;
+pub const CMD_SEND_RR_DATA: u16 = 0x006F;
+pub const CMD_SEND_UNIT_DATA: u16 = 0x0070;
+
+const ITEM_CONNECTED_ADDRESS: u16 = 0x00A1;
+const ITEM_CONNECTED_DATA: u16 = 0x00B1;
+const ITEM_UNCONNECTED_DATA: u16 = 0x00B2;
+const ITEM_SEQUENCED_ADDRESS: u16 = 0x8002;
+
+const CLASS_CONNECTION_MANAGER: u16 = 0x06;
+
+/// How a CIP message travels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Messaging {
+    /// Request/response through SendRRData
+    ExplicitUnconnected,
+    /// Class 3 connection through SendUnitData
+    ExplicitConnected,
+    /// Class 1 cyclic I/O data over UDP
+    Implicit,
+}
+
+impl Messaging {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Messaging::ExplicitUnconnected => "Explicit (unconnected)",
+            Messaging::ExplicitConnected => "Explicit (connected)",
+            Messaging::Implicit => "Implicit I/O",
+        }
+    }
+}
+
+/// Common Packet Format item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpfItem<'a> {
+    pub type_id: u16,
+    pub data: &'a [u8],
+}
+
+/// Parses a CPF item list: a count, then type, length and data for each
+pub fn parse_cpf(data: &[u8]) -> Vec<CpfItem<'_>> {
+    let mut items = Vec::new();
+    let Some(count) = data.get(..2) else { return items };
+    let count = u16::from_le_bytes([count[0], count[1]]);
+    let mut rest = &data[2..];
+    for _ in 0..count {
+        let Some(header) = rest.get(..4) else { break };
+        let type_id = u16::from_le_bytes([header[0], header[1]]);
+        let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+        let Some(item) = rest.get(4..4 + len) else { break };
+        items.push(CpfItem { type_id, data: item });
+        rest = &rest[4 + len..];
+    }
+    items
+}
+
+/// EtherNet/IP encapsulation parser (ODVA Vol. 2)
+pub struct EnipPacket<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum EnipError {
+    TooShort,
+}
+
+impl fmt::Display for EnipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnipError::TooShort => write!(f, "Packet too short for EtherNet/IP encapsulation header"),
+        }
+    }
+}
+
+impl<'a> EnipPacket<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, EnipError> {
+        if data.len() < 24 {
+            return Err(EnipError::TooShort);
+        }
+        let length = u16::from_le_bytes([data[2], data[3]]) as usize;
+        if data.len() < 24 + length {
+            return Err(EnipError::TooShort);
+        }
+        Ok(EnipPacket { data: &data[..24 + length] })
+    }
+
+    pub fn command(&self) -> u16 {
+        u16::from_le_bytes([self.data[0], self.data[1]])
+    }
+
+    pub fn session_handle(&self) -> u32 {
+        u32::from_le_bytes([self.data[4], self.data[5], self.data[6], self.data[7]])
+    }
+
+    pub fn status(&self) -> u32 {
+        u32::from_le_bytes([self.data[8], self.data[9], self.data[10], self.data[11]])
+    }
+
+    pub fn command_data(&self) -> &'a [u8] {
+        &self.data[24..]
+    }
+
+    /// CPF items of SendRRData and SendUnitData, after the interface handle and timeout
+    pub fn items(&self) -> Vec<CpfItem<'a>> {
+        match self.command() {
+            CMD_SEND_RR_DATA | CMD_SEND_UNIT_DATA => self.command_data().get(6..).map(parse_cpf).unwrap_or_default(),
+            CMD_LIST_IDENTITY => parse_cpf(self.command_data()),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Embedded CIP message and how it is carried
+    pub fn cip(&self) -> Option<(Messaging, CipMessage<'a>)> {
+        let items = self.items();
+        match self.command() {
+            CMD_SEND_RR_DATA => {
+                let item = items.iter().find(|item| item.type_id == ITEM_UNCONNECTED_DATA)?;
+                Some((Messaging::ExplicitUnconnected, CipMessage::parse(item.data)?))
+            }
+            CMD_SEND_UNIT_DATA => {
+                // Connected data starts with a 2-byte sequence count
+                let item = items.iter().find(|item| item.type_id == ITEM_CONNECTED_DATA)?;
+                Some((Messaging::ExplicitConnected, CipMessage::parse(item.data.get(2..)?)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Product name from a ListIdentity response
+    pub fn product_name(&self) -> Option<String> {
+        if self.command() != CMD_LIST_IDENTITY {
+            return None;
+        }
+        // Identity item: version, socket address, vendor, device type, product code, revision, status, serial, name
+        let item = self.items().into_iter().find(|item| item.type_id == 0x000C)?;
+        let len = *item.data.get(32)? as usize;
+        Some(String::from_utf8_lossy(item.data.get(33..33 + len)?).into_owned())
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![ControlField {
+            name: "ENIP Command".to_string(),
+            value: command_name(self.command()),
+            description: format!("Session 0x{:08x}", self.session_handle()),
+        }];
+        if self.status() != 0 {
+            fields.push(ControlField {
+                name: "ENIP Status".to_string(),
+                value: format!("0x{:08x}", self.status()),
+                description: "Encapsulation error".to_string(),
+            });
+        }
+        if let Some(name) = self.product_name() {
+            fields.push(ControlField {
+                name: "ENIP Product".to_string(),
+                value: name,
+                description: "ListIdentity product name".to_string(),
+            });
+        }
+        if let Some((messaging, cip)) = self.cip() {
+            fields.push(ControlField {
+                name: "CIP Messaging".to_string(),
+                value: messaging.name().to_string(),
+                description: command_name(self.command()),
+            });
+            fields.extend(cip.get_control_fields());
+        }
+        fields
+    }
+}
+
+/// Class, instance, attribute or symbolic tag addressed by a request path
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CipPath {
+    pub class: Option<u16>,
+    pub instance: Option<u32>,
+    pub attribute: Option<u16>,
+    pub symbol: Option<String>,
+}
+
+impl fmt::Display for CipPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(symbol) = &self.symbol {
+            parts.push(format!("Tag {}", symbol));
+        }
+        if let Some(class) = self.class {
+            parts.push(format!("Class 0x{:02x} ({})", class, class_name(class)));
+        }
+        if let Some(instance) = self.instance {
+            parts.push(format!("Instance {}", instance));
+        }
+        if let Some(attribute) = self.attribute {
+            parts.push(format!("Attribute {}", attribute));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// CIP request or response (ODVA Vol. 1)
+pub struct CipMessage<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> CipMessage<'a> {
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        (data.len() >= 2).then_some(CipMessage { data })
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.data[0] & 0x80 != 0
+    }
+
+    pub fn service(&self) -> u8 {
+        self.data[0] & 0x7F
+    }
+
+    /// General status of a response
+    pub fn general_status(&self) -> Option<u8> {
+        if !self.is_response() {
+            return None;
+        }
+        self.data.get(2).copied()
+    }
+
+    /// Logical and ANSI symbolic segments of a request path
+    pub fn path(&self) -> Option<CipPath> {
+        if self.is_response() {
+            return None;
+        }
+        let words = self.data[1] as usize;
+        let mut segments = self.data.get(2..2 + words * 2)?;
+        let mut path = CipPath::default();
+        while let Some((&segment, rest)) = segments.split_first() {
+            let (value, used) = match segment {
+                0x20 | 0x24 | 0x30 => (*rest.first()? as u32, 1),
+                0x21 | 0x25 | 0x31 => (u16::from_le_bytes([*rest.get(1)?, *rest.get(2)?]) as u32, 3),
+                0x26 => (u32::from_le_bytes([*rest.get(1)?, *rest.get(2)?, *rest.get(3)?, *rest.get(4)?]), 5),
+                0x91 => {
+                    let len = *rest.first()? as usize;
+                    path.symbol = Some(String::from_utf8_lossy(rest.get(1..1 + len)?).into_owned());
+                    // Padded to an even segment length
+                    segments = &rest[(1 + len + len % 2).min(rest.len())..];
+                    continue;
+                }
+                _ => break,
+            };
+            match segment & 0xFC {
+                0x20 => path.class = Some(value as u16),
+                0x24 => path.instance = Some(value),
+                0x30 => path.attribute = Some(value as u16),
+                _ => {}
+            }
+            segments = &rest[used..];
+        }
+        Some(path)
+    }
+
+    pub fn get_service_name(&self) -> String {
+        service_name(self.service(), self.path().and_then(|path| path.class))
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![ControlField {
+            name: "CIP Service".to_string(),
+            value: self.get_service_name(),
+            description: match self.general_status() {
+                Some(0) => "Response, success".to_string(),
+                Some(status) => format!("Response, status 0x{:02x}", status),
+                None => "Request".to_string(),
+            },
+        }];
+        if let Some(path) = self.path().filter(|path| *path != CipPath::default()) {
+            fields.push(ControlField {
+                name: "CIP Path".to_string(),
+                value: path.to_string(),
+                description: "Request path".to_string(),
+            });
+        }
+        fields
+    }
+}
+
+/// Control fields for a class 1 I/O datagram, which has no encapsulation header
+pub fn implicit_io_control_fields(data: &[u8]) -> Vec<ControlField> {
+    let items = parse_cpf(data);
+    let Some(address) = items.iter().find(|item| matches!(item.type_id, ITEM_SEQUENCED_ADDRESS | ITEM_CONNECTED_ADDRESS)) else {
+        return Vec::new();
+    };
+    let Some(id) = address.data.get(..4) else { return Vec::new() };
+    let connection = u32::from_le_bytes([id[0], id[1], id[2], id[3]]);
+    let sequence = address.data.get(4..8).map(|s| u32::from_le_bytes([s[0], s[1], s[2], s[3]]));
+    let mut fields = vec![ControlField {
+        name: "CIP Messaging".to_string(),
+        value: Messaging::Implicit.name().to_string(),
+        description: match sequence {
+            Some(sequence) => format!("Connection 0x{:08x}, sequence {}", connection, sequence),
+            None => format!("Connection 0x{:08x}", connection),
+        },
+    }];
+    if let Some(data) = items.iter().find(|item| item.type_id == ITEM_CONNECTED_DATA) {
+        fields.push(ControlField {
+            name: "CIP I/O Data".to_string(),
+            value: format!("{} bytes", data.data.len()),
+            description: "Cyclic I/O data".to_string(),
+        });
+    }
+    fields
+}
+
+pub fn command_name(command: u16) -> String {
+    match command {
+        0x0000 => "NOP".to_string(),
+        0x0004 => "ListServices".to_string(),
+        CMD_LIST_IDENTITY => "ListIdentity".to_string(),
+        0x0064 => "ListInterfaces".to_string(),
+        CMD_REGISTER_SESSION => "RegisterSession".to_string(),
+        0x0066 => "UnRegisterSession".to_string(),
+        CMD_SEND_RR_DATA => "SendRRData".to_string(),
+        CMD_SEND_UNIT_DATA => "SendUnitData".to_string(),
+        c => format!("Unknown (0x{:04x})", c),
+    }
+}
+
+/// Service name; codes from 0x4B are object specific, so the class matters
+pub fn service_name(service: u8, class: Option<u16>) -> String {
+    match (service, class) {
+        (0x01, _) => "Get_Attributes_All".to_string(),
+        (0x02, _) => "Set_Attributes_All".to_string(),
+        (0x03, _) => "Get_Attribute_List".to_string(),
+        (0x04, _) => "Set_Attribute_List".to_string(),
+        (0x05, _) => "Reset".to_string(),
+        (0x06, _) => "Start".to_string(),
+        (0x07, _) => "Stop".to_string(),
+        (0x08, _) => "Create".to_string(),
+        (0x09, _) => "Delete".to_string(),
+        (0x0A, _) => "Multiple_Service_Packet".to_string(),
+        (0x0E, _) => "Get_Attribute_Single".to_string(),
+        (0x10, _) => "Set_Attribute_Single".to_string(),
+        (0x4E, Some(CLASS_CONNECTION_MANAGER)) => "Forward_Close".to_string(),
+        (0x52, Some(CLASS_CONNECTION_MANAGER)) => "Unconnected_Send".to_string(),
+        (0x54, Some(CLASS_CONNECTION_MANAGER)) => "Forward_Open".to_string(),
+        (0x5B, Some(CLASS_CONNECTION_MANAGER)) => "Large_Forward_Open".to_string(),
+        (0x4C, _) => "Read_Tag".to_string(),
+        (0x4D, _) => "Write_Tag".to_string(),
+        (0x52, _) => "Read_Tag_Fragmented".to_string(),
+        (0x53, _) => "Write_Tag_Fragmented".to_string(),
+        (s, _) => format!("Unknown (0x{:02x})", s),
+    }
+}
+
+pub fn class_name(class: u16) -> String {
+    match class {
+        0x01 => "Identity".to_string(),
+        0x02 => "Message Router".to_string(),
+        0x04 => "Assembly".to_string(),
+        CLASS_CONNECTION_MANAGER => "Connection Manager".to_string(),
+        0x6B => "Symbol".to_string(),
+        0xAC => "Program".to_string(),
+        0xF5 => "TCP/IP Interface".to_string(),
+        0xF6 => "Ethernet Link".to_string(),
+        c => format!("Unknown (0x{:02x})", c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encapsulate(command: u16, session: u32, body: &[u8]) -> Vec<u8> {
+        let mut packet = command.to_le_bytes().to_vec();
+        packet.extend_from_slice(&(body.len() as u16).to_le_bytes());
+        packet.extend_from_slice(&session.to_le_bytes());
+        packet.extend_from_slice(&[0; 16]);
+        packet.extend_from_slice(body);
+        packet
+    }
+
+    #[test]
+    fn decodes_unconnected_get_attribute_single() {
+        // Interface handle, timeout, null address item, unconnected data item
+        let mut body = vec![0, 0, 0, 0, 10, 0, 2, 0, 0, 0, 0, 0, 0xB2, 0, 8, 0];
+        body.extend_from_slice(&[0x0E, 0x03, 0x20, 0x01, 0x24, 0x01, 0x30, 0x07]);
+        let data = encapsulate(CMD_SEND_RR_DATA, 0x1234, &body);
+        let packet = EnipPacket::parse(&data).unwrap();
+        let (messaging, cip) = packet.cip().unwrap();
+        assert_eq!(messaging, Messaging::ExplicitUnconnected);
+        assert_eq!(cip.get_service_name(), "Get_Attribute_Single");
+        assert_eq!(cip.path().unwrap().to_string(), "Class 0x01 (Identity), Instance 1, Attribute 7");
+        assert!(packet.get_control_fields().iter().any(|f| f.name == "ENIP Command" && f.description == "Session 0x00001234"));
+    }
+
+    #[test]
+    fn decodes_connected_tag_reads() {
+        // Connected address item, connected data item with sequence count, Read_Tag "Motor1"
+        let mut body = vec![0, 0, 0, 0, 0, 0, 2, 0, 0xA1, 0, 4, 0, 1, 0, 0, 0x80, 0xB1, 0, 14, 0, 5, 0];
+        body.extend_from_slice(&[0x4C, 0x04, 0x91, 0x06]);
+        body.extend_from_slice(b"Motor1");
+        body.extend_from_slice(&[0x01, 0x00]);
+        let data = encapsulate(CMD_SEND_UNIT_DATA, 1, &body);
+        let (messaging, cip) = EnipPacket::parse(&data).unwrap().cip().unwrap();
+        assert_eq!(messaging, Messaging::ExplicitConnected);
+        assert_eq!(cip.get_service_name(), "Read_Tag");
+        assert_eq!(cip.path().unwrap().symbol.as_deref(), Some("Motor1"));
+    }
+
+    #[test]
+    fn decodes_implicit_io() {
+        let data = [2, 0, 0x02, 0x80, 8, 0, 0x11, 0x22, 0x33, 0x44, 7, 0, 0, 0, 0xB1, 0, 4, 0, 1, 2, 3, 4];
+        let fields = implicit_io_control_fields(&data);
+        assert_eq!(fields[0].value, "Implicit I/O");
+        assert_eq!(fields[0].description, "Connection 0x44332211, sequence 7");
+        assert_eq!(fields[1].value, "4 bytes");
+    }
+}
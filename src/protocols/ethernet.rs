@@ -1,17 +1,27 @@
+use super::cdp::{CdpPacket, CDP_OUI, CDP_PID};
+use super::eapol::ETHERTYPE_EAPOL;
 use super::frame_control::{FrameControlInfo, ProtocolType, ControlField};
-use super::ipv4::IPv4Packet;
-use super::ipv6::IPv6Packet;
+use super::isis::IsisPdu;
+use super::llc::{LlcHeader, SAP_OSI};
+use super::registry::dissectors;
+use super::profinet::ETHERTYPE_PROFINET;
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::str::FromStr;
+
+/// Largest standard Ethernet frame (1500-byte MTU, with a VLAN tag or the FCS);
+/// anything longer is a jumbo frame
+pub const MAX_STANDARD_FRAME: usize = 1518;
 
 /// Ethernet frame parser
 pub struct EthernetFrame<'a> {
     data: &'a [u8],
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct MacAddress([u8; 6]);
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct EtherType(u16);
 
 /// Error types for Ethernet frame parsing
@@ -91,25 +101,24 @@ impl<'a> EthernetFrame<'a> {
                 description: etype.get_protocol_description(),
             },
         ];
+        if self.data.len() > MAX_STANDARD_FRAME {
+            control_fields.push(ControlField {
+                name: "Jumbo Frame".to_string(),
+                value: format!("{} bytes", self.data.len()),
+                description: format!("Longer than the {}-byte Ethernet maximum; every hop needs a larger MTU", MAX_STANDARD_FRAME),
+            });
+        }
         
         // Add deeper protocol inspection based on EtherType
         match etype.0 {
-            0x0800 => {
-                // IPv4
-                if let Ok(ipv4) = IPv4Packet::parse(self.payload()) {
-                    let ipv4_control = ipv4.get_control_fields();
-                    control_fields.extend(ipv4_control);
-                }
-            },
-            0x86DD => {
-                // IPv6
-                if let Ok(ipv6) = IPv6Packet::parse(self.payload()) {
-                    let ipv6_control = ipv6.get_control_fields();
-                    control_fields.extend(ipv6_control);
+            len @ ..=0x05DC => {
+                // 802.3 length field: an LLC header follows
+                let payload = &self.payload()[..(len as usize).min(self.payload().len())];
+                if let Ok(llc) = LlcHeader::parse(payload) {
+                    control_fields.extend(llc_control_fields(&llc));
                 }
             },
-            // Other protocols can be added here
-            _ => {}
+            ether_type => control_fields.extend(dissectors().dissect_network(ether_type, self.payload())),
         }
         
         FrameControlInfo {
@@ -119,6 +128,26 @@ impl<'a> EthernetFrame<'a> {
     }
 }
 
+/// Fields of the protocols identified by an LLC header
+fn llc_control_fields(llc: &LlcHeader<'_>) -> Vec<ControlField> {
+    let mut fields = vec![ControlField {
+        name: "LLC SAP".to_string(),
+        value: format!("0x{:02x}/0x{:02x}", llc.dsap(), llc.ssap()),
+        description: match llc.snap() {
+            Some((oui, pid)) => format!("SNAP OUI {:02x}{:02x}{:02x}, PID 0x{:04x}", oui[0], oui[1], oui[2], pid),
+            None if llc.dsap() == SAP_OSI => "OSI".to_string(),
+            None => "DSAP/SSAP".to_string(),
+        },
+    }];
+    if llc.dsap() == SAP_OSI && let Ok(isis) = IsisPdu::parse(llc.payload()) {
+        fields.extend(isis.get_control_fields());
+    }
+    if llc.snap() == Some((CDP_OUI, CDP_PID)) && let Ok(cdp) = CdpPacket::parse(llc.payload()) {
+        fields.extend(cdp.get_control_fields());
+    }
+    fields
+}
+
 impl fmt::Display for MacAddress {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -129,6 +158,33 @@ impl fmt::Display for MacAddress {
     }
 }
 
+impl MacAddress {
+    pub fn new(octets: [u8; 6]) -> Self {
+        MacAddress(octets)
+    }
+
+    pub fn octets(&self) -> [u8; 6] {
+        self.0
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = EthernetError;
+
+    /// Parses `aa:bb:cc:dd:ee:ff` (or `-` separated) notation
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split([':', '-']).collect();
+        if parts.len() != 6 {
+            return Err(EthernetError::InvalidFormat);
+        }
+        let mut mac = [0u8; 6];
+        for (byte, part) in mac.iter_mut().zip(parts) {
+            *byte = u8::from_str_radix(part, 16).map_err(|_| EthernetError::InvalidFormat)?;
+        }
+        Ok(MacAddress(mac))
+    }
+}
+
 impl fmt::Display for EtherType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "0x{:04x}", self.0)
@@ -136,6 +192,10 @@ impl fmt::Display for EtherType {
 }
 
 impl EtherType {
+    pub fn value(&self) -> u16 {
+        self.0
+    }
+
     pub fn get_protocol_description(&self) -> String {
         match self.0 {
             0x0800 => "IPv4".to_string(),
@@ -143,7 +203,73 @@ impl EtherType {
             0x86DD => "IPv6".to_string(),
             0x8100 => "VLAN".to_string(),
             0x88CC => "LLDP".to_string(),
+            ETHERTYPE_EAPOL => "EAPOL".to_string(),
+            ETHERTYPE_PROFINET => "PROFINET".to_string(),
+            ..=0x05DC => "802.3 length (LLC)".to_string(),
             _ => format!("Unknown (0x{:04x})", self.0),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn parses_addresses_and_ether_type() {
+        let frame = testutil::ipv4_udp_frame(1234, 53, b"");
+        let eth = EthernetFrame::parse(&frame).unwrap();
+        assert_eq!(eth.src_mac().to_string(), "66:77:88:99:aa:bb");
+        assert_eq!(eth.dest_mac().to_string(), "00:11:22:33:44:55");
+        assert_eq!(eth.ether_type().get_protocol_description(), "IPv4");
+        assert_eq!(eth.payload().len(), frame.len() - 14);
+    }
+
+    #[test]
+    fn parses_mac_strings() {
+        let mac: MacAddress = "00:1A:2b:3c:4d:5e".parse().unwrap();
+        assert_eq!(mac.octets(), [0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e]);
+        assert!("00:1a:2b".parse::<MacAddress>().is_err());
+        assert!("zz:1a:2b:3c:4d:5e".parse::<MacAddress>().is_err());
+    }
+
+    #[test]
+    fn rejects_short_frames() {
+        assert!(matches!(EthernetFrame::parse(&[0u8; 13]), Err(EthernetError::TooShort)));
+    }
+
+    #[test]
+    fn frame_control_includes_network_layer() {
+        let v4 = testutil::ipv4_tcp_frame(1, 2, testutil::TCP_SYN, b"");
+        let fields = EthernetFrame::parse(&v4).unwrap().get_frame_control().control_fields;
+        assert!(fields.iter().any(|f| f.name == "TTL"));
+        assert!(!fields.iter().any(|f| f.name == "Jumbo Frame"));
+
+        let jumbo = testutil::ipv4_tcp_frame(1, 2, testutil::TCP_ACK, &[0; 8960]);
+        let fields = EthernetFrame::parse(&jumbo).unwrap().get_frame_control().control_fields;
+        assert!(fields.iter().any(|f| f.name == "Jumbo Frame" && f.value == "9014 bytes"));
+        assert!(fields.iter().any(|f| f.name == "Total Length" && f.value == "9000"));
+
+        let v6 = testutil::ipv6_udp_frame(1, 2, b"");
+        let fields = EthernetFrame::parse(&v6).unwrap().get_frame_control().control_fields;
+        assert!(fields.iter().any(|f| f.name == "Hop Limit"));
+
+        let arp = testutil::arp_request(testutil::client_v4(), testutil::server_v4());
+        let fields = EthernetFrame::parse(&arp).unwrap().get_frame_control().control_fields;
+        assert!(fields.iter().any(|f| f.name == "ARP Operation"));
+
+        let key = testutil::eapol_key(0x008a, 1, &[1; 32], &[0; 16], &[]);
+        let eapol = testutil::ethernet(testutil::SRC_MAC, testutil::DST_MAC, ETHERTYPE_EAPOL, &key);
+        let fields = EthernetFrame::parse(&eapol).unwrap().get_frame_control().control_fields;
+        assert!(fields.iter().any(|f| f.name == "Handshake Message" && f.value == "1"));
+
+        // IS-IS CSNP in an 802.3 frame with an OSI LLC header
+        let mut llc = vec![0xFE, 0xFE, 0x03, 0x83, 33, 1, 0, 24, 1, 0, 0, 0, 0];
+        llc.extend_from_slice(&[0x19, 0x21, 0x68, 0x00, 0x10, 0x01, 0x00]);
+        llc.extend_from_slice(&[0; 16]);
+        let isis = testutil::ethernet(testutil::SRC_MAC, [0x01, 0x80, 0xc2, 0, 0, 0x14], llc.len() as u16, &llc);
+        let fields = EthernetFrame::parse(&isis).unwrap().get_frame_control().control_fields;
+        assert!(fields.iter().any(|f| f.name == "IS-IS System ID" && f.value == "1921.6800.1001"));
+    }
+}
@@ -0,0 +1,203 @@
+use super::frame_control::ControlField;
+use super::ipv4::IPv4Packet;
+use super::ipv6::IPv6Packet;
+use std::fmt;
+
+pub const GTP_C_PORT: u16 = 2123;
+pub const GTP_U_PORT: u16 = 2152;
+
+/// Message type of a GTP-U packet carrying a subscriber packet
+pub const GTP_G_PDU: u8 = 255;
+
+const FLAG_EXTENSION: u8 = 0x04;
+const FLAG_SEQUENCE: u8 = 0x02;
+const FLAG_N_PDU: u8 = 0x01;
+const FLAG_TEID: u8 = 0x08;
+
+/// GTPv1 (GTP-U and GTP-C, 3GPP TS 29.281/29.060) and GTPv2-C (TS 29.274)
+/// header parser
+pub struct GtpPacket<'a> {
+    data: &'a [u8],
+    header_len: usize,
+}
+
+#[derive(Debug)]
+pub enum GtpError {
+    TooShort,
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for GtpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GtpError::TooShort => write!(f, "Packet too short for GTP header"),
+            GtpError::UnsupportedVersion(v) => write!(f, "Unsupported GTP version {}", v),
+        }
+    }
+}
+
+impl<'a> GtpPacket<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, GtpError> {
+        if data.len() < 8 {
+            return Err(GtpError::TooShort);
+        }
+        let flags = data[0];
+        let header_len = match flags >> 5 {
+            1 if flags & (FLAG_EXTENSION | FLAG_SEQUENCE | FLAG_N_PDU) == 0 => 8,
+            1 => {
+                // Sequence number, N-PDU number and next extension type are present together
+                let mut len = 12;
+                let mut next = *data.get(11).ok_or(GtpError::TooShort)?;
+                while flags & FLAG_EXTENSION != 0 && next != 0 {
+                    let units = *data.get(len).ok_or(GtpError::TooShort)? as usize;
+                    if units == 0 {
+                        return Err(GtpError::TooShort);
+                    }
+                    len += units * 4;
+                    next = *data.get(len - 1).ok_or(GtpError::TooShort)?;
+                }
+                len
+            }
+            2 if flags & FLAG_TEID != 0 => 12,
+            2 => 8,
+            v => return Err(GtpError::UnsupportedVersion(v)),
+        };
+        if data.len() < header_len {
+            return Err(GtpError::TooShort);
+        }
+        Ok(GtpPacket { data, header_len })
+    }
+
+    pub fn version(&self) -> u8 {
+        self.data[0] >> 5
+    }
+
+    pub fn message_type(&self) -> u8 {
+        self.data[1]
+    }
+
+    /// Tunnel endpoint identifier of the receiving side
+    pub fn teid(&self) -> Option<u32> {
+        if self.version() == 2 && self.data[0] & FLAG_TEID == 0 {
+            return None;
+        }
+        Some(u32::from_be_bytes([self.data[4], self.data[5], self.data[6], self.data[7]]))
+    }
+
+    pub fn sequence(&self) -> Option<u32> {
+        let d = self.data;
+        match self.version() {
+            1 if d[0] & FLAG_SEQUENCE != 0 => Some(u16::from_be_bytes([d[8], d[9]]) as u32),
+            1 => None,
+            _ => {
+                let at = if d[0] & FLAG_TEID != 0 { 8 } else { 4 };
+                Some(u32::from_be_bytes([0, d[at], d[at + 1], d[at + 2]]))
+            }
+        }
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[self.header_len..]
+    }
+
+    /// Subscriber IP packet carried in a G-PDU
+    pub fn inner_packet(&self) -> Option<&'a [u8]> {
+        if self.version() != 1 || self.message_type() != GTP_G_PDU {
+            return None;
+        }
+        let payload = self.payload();
+        matches!(payload.first()? >> 4, 4 | 6).then_some(payload)
+    }
+
+    pub fn get_message_name(&self) -> String {
+        message_name(self.version(), self.message_type())
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![ControlField {
+            name: "GTP Message".to_string(),
+            value: self.get_message_name(),
+            description: format!("GTPv{}", self.version()),
+        }];
+        if let Some(teid) = self.teid() {
+            fields.push(ControlField {
+                name: "GTP TEID".to_string(),
+                value: format!("0x{:08x}", teid),
+                description: "Tunnel endpoint identifier".to_string(),
+            });
+        }
+        if let Some(sequence) = self.sequence() {
+            fields.push(ControlField {
+                name: "GTP Sequence".to_string(),
+                value: sequence.to_string(),
+                description: "Sequence number".to_string(),
+            });
+        }
+        let inner = match self.inner_packet() {
+            Some(ip) if ip[0] >> 4 == 4 => IPv4Packet::parse(ip).map(|ip| ip.get_control_fields()).unwrap_or_default(),
+            Some(ip) => IPv6Packet::parse(ip).map(|ip| ip.get_control_fields()).unwrap_or_default(),
+            None => Vec::new(),
+        };
+        fields.extend(inner.into_iter().map(|field| ControlField { name: format!("Inner {}", field.name), ..field }));
+        fields
+    }
+}
+
+pub fn message_name(version: u8, message_type: u8) -> String {
+    match (version, message_type) {
+        (_, 1) => "Echo Request".to_string(),
+        (_, 2) => "Echo Response".to_string(),
+        (1, 16) => "Create PDP Context Request".to_string(),
+        (1, 17) => "Create PDP Context Response".to_string(),
+        (1, 18) => "Update PDP Context Request".to_string(),
+        (1, 19) => "Update PDP Context Response".to_string(),
+        (1, 20) => "Delete PDP Context Request".to_string(),
+        (1, 21) => "Delete PDP Context Response".to_string(),
+        (1, 26) => "Error Indication".to_string(),
+        (1, 254) => "End Marker".to_string(),
+        (1, GTP_G_PDU) => "G-PDU".to_string(),
+        (2, 32) => "Create Session Request".to_string(),
+        (2, 33) => "Create Session Response".to_string(),
+        (2, 34) => "Modify Bearer Request".to_string(),
+        (2, 35) => "Modify Bearer Response".to_string(),
+        (2, 36) => "Delete Session Request".to_string(),
+        (2, 37) => "Delete Session Response".to_string(),
+        (2, 170) => "Release Access Bearers Request".to_string(),
+        (2, 171) => "Release Access Bearers Response".to_string(),
+        (_, t) => format!("Unknown ({})", t),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn decapsulates_g_pdus() {
+        let inner = testutil::ipv4(testutil::client_v4(), testutil::server_v4(), 17, &testutil::udp(1, 53, b""));
+        let data = testutil::gtp_u(0x1234_5678, &inner);
+        let packet = GtpPacket::parse(&data).unwrap();
+        assert_eq!(packet.get_message_name(), "G-PDU");
+        assert_eq!(packet.teid(), Some(0x1234_5678));
+        assert_eq!(packet.inner_packet(), Some(&inner[..]));
+        let fields = packet.get_control_fields();
+        assert!(fields.iter().any(|f| f.name == "Inner Source IP" && f.value == testutil::client_v4().to_string()));
+    }
+
+    #[test]
+    fn skips_extension_headers_and_parses_v2() {
+        // S and E set, one PDU session container extension
+        let mut data = vec![0x36, GTP_G_PDU, 0, 0, 0, 0, 0, 1, 0, 7, 0, 0x85, 1, 0x10, 0x09, 0x00];
+        data.extend_from_slice(&testutil::ipv6(testutil::client_v6(), testutil::server_v6(), 17, &[0; 8]));
+        let packet = GtpPacket::parse(&data).unwrap();
+        assert_eq!(packet.sequence(), Some(7));
+        assert_eq!(packet.inner_packet().unwrap()[0] >> 4, 6);
+
+        let create = [0x48, 32, 0, 8, 0, 0, 0, 0, 0, 0, 0x2a, 0];
+        let packet = GtpPacket::parse(&create).unwrap();
+        assert_eq!(packet.get_message_name(), "Create Session Request");
+        assert_eq!((packet.teid(), packet.sequence()), (Some(0), Some(42)));
+        assert!(matches!(GtpPacket::parse(&[0x60; 8]), Err(GtpError::UnsupportedVersion(3))));
+    }
+}
@@ -0,0 +1,151 @@
+use super::frame_control::ControlField;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+pub const HSRP_PORT: u16 = 1985;
+pub const HSRP_IPV6_PORT: u16 = 2029;
+
+pub const STATE_STANDBY: u8 = 8;
+pub const STATE_ACTIVE: u8 = 16;
+
+/// Group State TLV of an HSRPv2 packet
+const TLV_GROUP_STATE: u8 = 1;
+
+/// Cisco Hot Standby Router Protocol message (version 1, RFC 2281, and the
+/// TLV-based version 2)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HsrpMessage {
+    pub version: u8,
+    pub opcode: u8,
+    pub state: u8,
+    pub group: u16,
+    pub priority: u32,
+    pub virtual_ip: IpAddr,
+}
+
+#[derive(Debug)]
+pub enum HsrpError {
+    TooShort,
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for HsrpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HsrpError::TooShort => write!(f, "Packet too short for HSRP message"),
+            HsrpError::UnsupportedVersion(v) => write!(f, "Unsupported HSRP version {}", v),
+        }
+    }
+}
+
+impl HsrpMessage {
+    pub fn parse(data: &[u8]) -> Result<Self, HsrpError> {
+        match *data.first().ok_or(HsrpError::TooShort)? {
+            0 => {
+                if data.len() < 20 {
+                    return Err(HsrpError::TooShort);
+                }
+                Ok(HsrpMessage {
+                    version: 1,
+                    opcode: data[1],
+                    state: data[2],
+                    group: data[6] as u16,
+                    priority: data[5] as u32,
+                    virtual_ip: IpAddr::V4(Ipv4Addr::new(data[16], data[17], data[18], data[19])),
+                })
+            }
+            TLV_GROUP_STATE => {
+                // type, length, version, opcode, state, IP version, group, identifier, priority, hello, hold, virtual IP
+                let tlv = data.get(..42).ok_or(HsrpError::TooShort)?;
+                if tlv[2] != 2 {
+                    return Err(HsrpError::UnsupportedVersion(tlv[2]));
+                }
+                let virtual_ip = match tlv[5] {
+                    6 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(&tlv[26..42]).unwrap_or_default())),
+                    _ => IpAddr::V4(Ipv4Addr::new(tlv[26], tlv[27], tlv[28], tlv[29])),
+                };
+                Ok(HsrpMessage {
+                    version: 2,
+                    opcode: tlv[3],
+                    state: tlv[4],
+                    group: u16::from_be_bytes([tlv[6], tlv[7]]),
+                    priority: u32::from_be_bytes([tlv[14], tlv[15], tlv[16], tlv[17]]),
+                    virtual_ip,
+                })
+            }
+            v => Err(HsrpError::UnsupportedVersion(v)),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state == STATE_ACTIVE
+    }
+
+    pub fn get_opcode_name(&self) -> String {
+        match self.opcode {
+            0 => "Hello".to_string(),
+            1 => "Coup".to_string(),
+            2 => "Resign".to_string(),
+            3 => "Advertise".to_string(),
+            o => format!("Unknown ({})", o),
+        }
+    }
+
+    pub fn get_state_name(&self) -> String {
+        match self.state {
+            0 => "Initial".to_string(),
+            1 => "Learn".to_string(),
+            2 => "Listen".to_string(),
+            4 => "Speak".to_string(),
+            STATE_STANDBY => "Standby".to_string(),
+            STATE_ACTIVE => "Active".to_string(),
+            s => format!("Unknown ({})", s),
+        }
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        vec![
+            ControlField {
+                name: "HSRP Group".to_string(),
+                value: self.group.to_string(),
+                description: format!("HSRPv{} {}", self.version, self.get_opcode_name()),
+            },
+            ControlField {
+                name: "HSRP State".to_string(),
+                value: self.get_state_name(),
+                description: format!("Priority {}", self.priority),
+            },
+            ControlField {
+                name: "HSRP Virtual IP".to_string(),
+                value: self.virtual_ip.to_string(),
+                description: "Gateway address shared by the group".to_string(),
+            },
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_v1_and_v2() {
+        let v1 = [0, 0, STATE_ACTIVE, 3, 10, 110, 1, 0, b'c', b'i', b's', b'c', b'o', 0, 0, 0, 192, 168, 1, 254];
+        let message = HsrpMessage::parse(&v1).unwrap();
+        assert!(message.is_active());
+        assert_eq!((message.group, message.priority), (1, 110));
+        assert_eq!(message.virtual_ip, IpAddr::V4(Ipv4Addr::new(192, 168, 1, 254)));
+
+        let mut v2 = vec![TLV_GROUP_STATE, 40, 2, 0, STATE_STANDBY, 4, 0, 20];
+        v2.extend_from_slice(&[0, 0, 0x0c, 0x9f, 0xf0, 0x14]);
+        v2.extend_from_slice(&90u32.to_be_bytes());
+        v2.extend_from_slice(&[0; 8]);
+        v2.extend_from_slice(&[10, 0, 0, 1]);
+        v2.extend_from_slice(&[0; 12]);
+        let message = HsrpMessage::parse(&v2).unwrap();
+        assert_eq!((message.version, message.group, message.priority), (2, 20, 90));
+        assert_eq!(message.get_state_name(), "Standby");
+        assert_eq!(message.virtual_ip, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        assert!(HsrpMessage::parse(&[0; 10]).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use super::frame_control::ControlField;
+use std::fmt;
+
+/// TCP ports HTTP/1.x is decoded on; other ports are left to protocol detection
+pub const HTTP_PORTS: [u16; 2] = [80, 8080];
+
+/// Header lines read from one message
+const MAX_HEADERS: usize = 64;
+
+/// HTTP/1.x request or response head parser. A segment may end in the middle
+/// of the head; the complete lines before the cut are still read.
+pub struct HttpMessage<'a> {
+    start_line: StartLine<'a>,
+    headers: Vec<(&'a str, &'a str)>,
+    /// Offset of the body, once the blank line ending the head was seen
+    body_offset: Option<usize>,
+}
+
+#[derive(Debug)]
+pub enum HttpError {
+    NotHttp,
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::NotHttp => write!(f, "Data does not start with an HTTP/1.x request or status line"),
+        }
+    }
+}
+
+/// The first line of a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartLine<'a> {
+    Request { method: &'a str, uri: &'a str, version: &'a str },
+    Response { version: &'a str, status: u16, reason: &'a str },
+}
+
+impl<'a> StartLine<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        if line.starts_with("HTTP/1.") {
+            let (version, rest) = line.split_once(' ')?;
+            let (status, reason) = rest.split_once(' ').unwrap_or((rest, ""));
+            if status.len() != 3 {
+                return None;
+            }
+            return Some(StartLine::Response { version, status: status.parse().ok()?, reason });
+        }
+        let mut parts = line.splitn(3, ' ');
+        let (method, uri, version) = (parts.next()?, parts.next()?, parts.next()?);
+        if method.is_empty() || !method.bytes().all(|b| b.is_ascii_uppercase()) || uri.is_empty() || !version.starts_with("HTTP/1.") {
+            return None;
+        }
+        Some(StartLine::Request { method, uri, version })
+    }
+}
+
+impl<'a> HttpMessage<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, HttpError> {
+        let mut lines = Lines { data, at: 0 };
+        let start_line = lines.next().and_then(StartLine::parse).ok_or(HttpError::NotHttp)?;
+        let mut headers = Vec::new();
+        let mut body_offset = None;
+        while let Some(line) = lines.next() {
+            if line.is_empty() {
+                body_offset = Some(lines.at);
+                break;
+            }
+            if headers.len() == MAX_HEADERS {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim(), value.trim()));
+            }
+        }
+        Ok(HttpMessage { start_line, headers, body_offset })
+    }
+
+    pub fn start_line(&self) -> StartLine<'a> {
+        self.start_line
+    }
+
+    pub fn is_request(&self) -> bool {
+        matches!(self.start_line, StartLine::Request { .. })
+    }
+
+    pub fn method(&self) -> Option<&'a str> {
+        match self.start_line {
+            StartLine::Request { method, .. } => Some(method),
+            StartLine::Response { .. } => None,
+        }
+    }
+
+    pub fn uri(&self) -> Option<&'a str> {
+        match self.start_line {
+            StartLine::Request { uri, .. } => Some(uri),
+            StartLine::Response { .. } => None,
+        }
+    }
+
+    pub fn status_code(&self) -> Option<u16> {
+        match self.start_line {
+            StartLine::Response { status, .. } => Some(status),
+            StartLine::Request { .. } => None,
+        }
+    }
+
+    pub fn version(&self) -> &'a str {
+        match self.start_line {
+            StartLine::Request { version, .. } | StartLine::Response { version, .. } => version,
+        }
+    }
+
+    pub fn headers(&self) -> &[(&'a str, &'a str)] {
+        &self.headers
+    }
+
+    /// The first header of that name, compared case-insensitively
+    pub fn header(&self, name: &str) -> Option<&'a str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, value)| *value)
+    }
+
+    pub fn host(&self) -> Option<&'a str> {
+        self.header("Host")
+    }
+
+    pub fn content_type(&self) -> Option<&'a str> {
+        self.header("Content-Type")
+    }
+
+    pub fn user_agent(&self) -> Option<&'a str> {
+        self.header("User-Agent")
+    }
+
+    pub fn content_length(&self) -> Option<u64> {
+        self.header("Content-Length")?.parse().ok()
+    }
+
+    /// Where the body starts, `None` when the head didn't end within the data
+    pub fn body_offset(&self) -> Option<usize> {
+        self.body_offset
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = match self.start_line {
+            StartLine::Request { method, uri, version } => vec![
+                ControlField { name: "HTTP Method".to_string(), value: method.to_string(), description: format!("{} request", version) },
+                ControlField { name: "HTTP URI".to_string(), value: uri.to_string(), description: "Requested resource".to_string() },
+            ],
+            StartLine::Response { version, status, reason } => vec![ControlField {
+                name: "HTTP Status".to_string(),
+                value: status.to_string(),
+                description: format!("{} {}", version, if reason.is_empty() { status_reason(status) } else { reason }),
+            }],
+        };
+        let headers = [
+            ("HTTP Host", self.host(), "Server the request is for"),
+            ("HTTP User-Agent", self.user_agent(), "Client software"),
+            ("HTTP Content-Type", self.content_type(), "Media type of the body"),
+        ];
+        for (name, value, description) in headers {
+            if let Some(value) = value {
+                fields.push(ControlField { name: name.to_string(), value: value.to_string(), description: description.to_string() });
+            }
+        }
+        if let Some(length) = self.content_length() {
+            fields.push(ControlField {
+                name: "HTTP Content-Length".to_string(),
+                value: length.to_string(),
+                description: "Body size in bytes".to_string(),
+            });
+        }
+        fields
+    }
+}
+
+/// Reason phrase of common status codes, for responses that leave it out
+fn status_reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+/// CRLF (or bare LF) terminated lines of printable text; stops at the first
+/// unterminated or binary line
+struct Lines<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let rest = &self.data[self.at..];
+        let end = rest.iter().position(|&b| b == b'\n')?;
+        let line = rest[..end].strip_suffix(b"\r").unwrap_or(&rest[..end]);
+        let line = std::str::from_utf8(line).ok().filter(|line| !line.chars().any(|c| c.is_control() && c != '\t'))?;
+        self.at += end + 1;
+        Some(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_request_head() {
+        let request = b"POST /upload?id=7 HTTP/1.1\r\nHost: example.com\r\nUser-Agent: curl/8.5.0\r\ncontent-type: application/json\r\nContent-Length: 2\r\n\r\n{}";
+        let message = HttpMessage::parse(request).unwrap();
+        assert_eq!((message.method(), message.uri(), message.version()), (Some("POST"), Some("/upload?id=7"), "HTTP/1.1"));
+        assert_eq!(message.content_type(), Some("application/json"));
+        assert_eq!(message.body_offset().map(|at| &request[at..]), Some(&b"{}"[..]));
+        let fields = message.get_control_fields();
+        assert!(fields.iter().any(|f| f.name == "HTTP Host" && f.value == "example.com"));
+        assert!(fields.iter().any(|f| f.name == "HTTP User-Agent" && f.value == "curl/8.5.0"));
+
+        // Cut in the middle of a header line
+        let cut = HttpMessage::parse(b"GET / HTTP/1.0\r\nHost: a\r\nUser-Ag").unwrap();
+        assert_eq!((cut.host(), cut.user_agent(), cut.body_offset()), (Some("a"), None, None));
+        assert!(HttpMessage::parse(b"SSH-2.0-OpenSSH_9.6\r\n").is_err());
+        assert!(HttpMessage::parse(b"get / HTTP/1.1\r\n").is_err());
+    }
+
+    #[test]
+    fn parses_status_line() {
+        let message = HttpMessage::parse(b"HTTP/1.1 404 Not Found\r\nContent-Type: text/html\r\n\r\n").unwrap();
+        assert_eq!((message.is_request(), message.status_code(), message.version()), (false, Some(404), "HTTP/1.1"));
+        let fields = HttpMessage::parse(b"HTTP/1.1 200\r\n\r\n").unwrap().get_control_fields();
+        assert_eq!((fields[0].value.as_str(), fields[0].description.as_str()), ("200", "HTTP/1.1 OK"));
+        assert!(HttpMessage::parse(b"HTTP/1.1 20 OK\r\n").is_err());
+    }
+}
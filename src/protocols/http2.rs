@@ -0,0 +1,239 @@
+use super::frame_control::ControlField;
+use std::fmt;
+
+/// Client connection preface that starts every HTTP/2 connection
+pub const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+pub const FRAME_DATA: u8 = 0x0;
+pub const FRAME_HEADERS: u8 = 0x1;
+pub const FRAME_RST_STREAM: u8 = 0x3;
+pub const FRAME_SETTINGS: u8 = 0x4;
+pub const FRAME_CONTINUATION: u8 = 0x9;
+
+pub const FLAG_END_STREAM: u8 = 0x1;
+pub const FLAG_END_HEADERS: u8 = 0x4;
+pub const FLAG_PADDED: u8 = 0x8;
+pub const FLAG_PRIORITY: u8 = 0x20;
+
+/// HTTP/2 frame parser
+pub struct Http2Frame<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum Http2Error {
+    TooShort,
+    Truncated,
+}
+
+impl fmt::Display for Http2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Http2Error::TooShort => write!(f, "Data too short for HTTP/2 frame header"),
+            Http2Error::Truncated => write!(f, "HTTP/2 frame payload truncated"),
+        }
+    }
+}
+
+impl<'a> Http2Frame<'a> {
+    /// Parses the frame at the start of `data`, which must hold the whole frame
+    pub fn parse(data: &'a [u8]) -> Result<Self, Http2Error> {
+        if data.len() < 9 {
+            return Err(Http2Error::TooShort);
+        }
+        let length = u32::from_be_bytes([0, data[0], data[1], data[2]]) as usize;
+        let data = data.get(..9 + length).ok_or(Http2Error::Truncated)?;
+        Ok(Http2Frame { data })
+    }
+
+    /// Payload length from the frame header
+    pub fn length(&self) -> usize {
+        self.data.len() - 9
+    }
+
+    pub fn frame_type(&self) -> u8 {
+        self.data[3]
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.data[4]
+    }
+
+    pub fn stream_id(&self) -> u32 {
+        u32::from_be_bytes([self.data[5] & 0x7f, self.data[6], self.data[7], self.data[8]])
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[9..]
+    }
+
+    pub fn end_stream(&self) -> bool {
+        self.flags() & FLAG_END_STREAM != 0
+    }
+
+    pub fn end_headers(&self) -> bool {
+        self.flags() & FLAG_END_HEADERS != 0
+    }
+
+    /// Payload with padding (and for HEADERS, priority fields) removed
+    pub fn content(&self) -> &'a [u8] {
+        let payload = self.payload();
+        let padded = self.flags() & FLAG_PADDED != 0 && matches!(self.frame_type(), FRAME_DATA | FRAME_HEADERS);
+        let (start, pad) = if padded { (1, payload.first().copied().unwrap_or(0) as usize) } else { (0, 0) };
+        let start = if self.frame_type() == FRAME_HEADERS && self.flags() & FLAG_PRIORITY != 0 { start + 5 } else { start };
+        let end = payload.len().saturating_sub(pad);
+        payload.get(start..end).unwrap_or_default()
+    }
+
+    pub fn get_type_name(&self) -> String {
+        match self.frame_type() {
+            FRAME_DATA => "DATA".to_string(),
+            FRAME_HEADERS => "HEADERS".to_string(),
+            0x2 => "PRIORITY".to_string(),
+            FRAME_RST_STREAM => "RST_STREAM".to_string(),
+            FRAME_SETTINGS => "SETTINGS".to_string(),
+            0x5 => "PUSH_PROMISE".to_string(),
+            0x6 => "PING".to_string(),
+            0x7 => "GOAWAY".to_string(),
+            0x8 => "WINDOW_UPDATE".to_string(),
+            FRAME_CONTINUATION => "CONTINUATION".to_string(),
+            t => format!("Unknown (0x{:02x})", t),
+        }
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        vec![
+            ControlField {
+                name: "HTTP/2 Frame".to_string(),
+                value: self.get_type_name(),
+                description: format!("{} bytes, flags 0x{:02x}", self.length(), self.flags()),
+            },
+            ControlField {
+                name: "HTTP/2 Stream".to_string(),
+                value: self.stream_id().to_string(),
+                description: "Stream identifier".to_string(),
+            },
+        ]
+    }
+}
+
+/// Splits one direction of an HTTP/2 connection into frames, skipping the
+/// client preface and stopping at a truncated frame
+pub fn frames(stream: &[u8]) -> Vec<Http2Frame<'_>> {
+    let mut rest = stream.strip_prefix(PREFACE).unwrap_or(stream);
+    let mut out = Vec::new();
+    while let Ok(frame) = Http2Frame::parse(rest) {
+        rest = &rest[9 + frame.length()..];
+        out.push(frame);
+    }
+    out
+}
+
+/// A complete header block (HEADERS plus any CONTINUATION frames)
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderBlock {
+    pub stream_id: u32,
+    pub headers: Vec<(String, String)>,
+    pub end_stream: bool,
+}
+
+impl HeaderBlock {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Decodes header blocks for one direction of a connection; HPACK state
+/// carries over between blocks, so every frame must be fed in order.
+pub struct HeaderDecoder {
+    hpack: hpack::Decoder<'static>,
+    pending: Option<(u32, Vec<u8>, bool)>,
+    /// Set once a block fails to decode; later blocks would be garbage
+    failed: bool,
+}
+
+impl Default for HeaderDecoder {
+    fn default() -> Self {
+        HeaderDecoder { hpack: hpack::Decoder::new(), pending: None, failed: false }
+    }
+}
+
+impl HeaderDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds a frame, returning a header block once it is complete
+    pub fn push(&mut self, frame: &Http2Frame) -> Option<HeaderBlock> {
+        match frame.frame_type() {
+            FRAME_HEADERS => {
+                self.pending = Some((frame.stream_id(), frame.content().to_vec(), frame.end_stream()));
+            }
+            FRAME_CONTINUATION => {
+                let (stream_id, block, _) = self.pending.as_mut()?;
+                if *stream_id != frame.stream_id() {
+                    return None;
+                }
+                block.extend_from_slice(frame.content());
+            }
+            _ => return None,
+        }
+        if !frame.end_headers() {
+            return None;
+        }
+        let (stream_id, block, end_stream) = self.pending.take()?;
+        if self.failed {
+            return None;
+        }
+        let Ok(headers) = self.hpack.decode(&block) else {
+            self.failed = true;
+            return None;
+        };
+        let headers = headers
+            .into_iter()
+            .map(|(n, v)| (String::from_utf8_lossy(&n).into_owned(), String::from_utf8_lossy(&v).into_owned()))
+            .collect();
+        Some(HeaderBlock { stream_id, headers, end_stream })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn parses_frames_after_preface() {
+        let mut stream = PREFACE.to_vec();
+        stream.extend(testutil::http2_frame(FRAME_SETTINGS, 0, 0, &[]));
+        stream.extend(testutil::http2_frame(FRAME_DATA, FLAG_END_STREAM | FLAG_PADDED, 1, &[2, b'h', b'i', 0, 0]));
+        stream.extend_from_slice(&[0, 0, 9, 0]); // truncated
+
+        let frames = frames(&stream);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].get_type_name(), "SETTINGS");
+        assert_eq!(frames[1].stream_id(), 1);
+        assert!(frames[1].end_stream());
+        assert_eq!(frames[1].content(), b"hi");
+        assert_eq!(frames[1].get_control_fields()[0].value, "DATA");
+        assert!(matches!(Http2Frame::parse(&[0; 4]), Err(Http2Error::TooShort)));
+    }
+
+    #[test]
+    fn decodes_split_header_blocks() {
+        let block = hpack::Encoder::new().encode(&vec![
+            (b":method".to_vec(), b"POST".to_vec()),
+            (b":path".to_vec(), b"/pkg.Service/Method".to_vec()),
+        ]);
+        let (first, rest) = block.split_at(block.len() / 2);
+        let headers = testutil::http2_frame(FRAME_HEADERS, 0, 3, first);
+        let continuation = testutil::http2_frame(FRAME_CONTINUATION, FLAG_END_HEADERS, 3, rest);
+
+        let mut decoder = HeaderDecoder::new();
+        assert!(decoder.push(&Http2Frame::parse(&headers).unwrap()).is_none());
+        let decoded = decoder.push(&Http2Frame::parse(&continuation).unwrap()).unwrap();
+        assert_eq!(decoded.stream_id, 3);
+        assert_eq!(decoded.get(":path"), Some("/pkg.Service/Method"));
+        assert!(!decoded.end_stream);
+    }
+}
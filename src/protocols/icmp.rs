@@ -0,0 +1,332 @@
+use super::frame_control::ControlField;
+use super::ipv4::IPv4Packet;
+use super::ipv6::IPv6Packet;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// IP protocol number of ICMP
+pub const IP_PROTO_ICMP: u8 = 1;
+
+/// IPv6 next header value of ICMPv6
+pub const IP_PROTO_ICMPV6: u8 = 58;
+
+/// ICMPv6 Neighbor Discovery option holding the packet a Redirect was sent for
+const ND_OPTION_REDIRECTED_HEADER: u8 = 4;
+
+/// ICMP or ICMPv6 message parser
+pub struct IcmpMessage<'a> {
+    data: &'a [u8],
+    v6: bool,
+}
+
+#[derive(Debug)]
+pub enum IcmpError {
+    TooShort,
+}
+
+impl fmt::Display for IcmpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IcmpError::TooShort => write!(f, "Packet too short for ICMP header"),
+        }
+    }
+}
+
+/// What a message is, with the fields particular to its type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IcmpKind {
+    EchoRequest { identifier: u16, sequence: u16 },
+    EchoReply { identifier: u16, sequence: u16 },
+    DestinationUnreachable,
+    /// ICMPv6 Packet Too Big, with the MTU of the next link
+    PacketTooBig { mtu: u32 },
+    TimeExceeded,
+    /// ICMP: the gateway to use instead; ICMPv6: the better next hop (target)
+    Redirect { gateway: IpAddr },
+    Other,
+}
+
+/// The start of the packet an error message was sent about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OriginalDatagram<'a> {
+    pub src: IpAddr,
+    pub dst: IpAddr,
+    pub protocol: u8,
+    /// Ports of a quoted TCP, UDP or SCTP header
+    pub ports: Option<(u16, u16)>,
+    /// The quoted IP packet, as far as the message carries it
+    pub data: &'a [u8],
+    /// The quoted packet from its transport header on
+    pub payload: &'a [u8],
+}
+
+impl<'a> OriginalDatagram<'a> {
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let (src, dst, protocol, header_length): (IpAddr, IpAddr, u8, usize) = match data.first()? >> 4 {
+            4 => {
+                let ip = IPv4Packet::parse(data).ok()?;
+                (ip.source_ip().into(), ip.destination_ip().into(), ip.protocol(), ip.header_length() as usize)
+            }
+            6 => {
+                let ip = IPv6Packet::parse(data).ok()?;
+                (ip.source_ip().into(), ip.destination_ip().into(), ip.next_header(), 40)
+            }
+            _ => return None,
+        };
+        let payload = data.get(header_length..).unwrap_or_default();
+        let ports = match (protocol, payload) {
+            (6 | 17 | 132, [a, b, c, d, ..]) => Some((u16::from_be_bytes([*a, *b]), u16::from_be_bytes([*c, *d]))),
+            _ => None,
+        };
+        Some(OriginalDatagram { src, dst, protocol, ports, data, payload })
+    }
+}
+
+impl fmt::Display for OriginalDatagram<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let protocol = match self.protocol {
+            1 => "ICMP".to_string(),
+            6 => "TCP".to_string(),
+            17 => "UDP".to_string(),
+            58 => "ICMPv6".to_string(),
+            132 => "SCTP".to_string(),
+            other => format!("protocol {}", other),
+        };
+        match self.ports {
+            Some((sport, dport)) => write!(f, "{} {}:{} -> {}:{}", protocol, self.src, sport, self.dst, dport),
+            None => write!(f, "{} {} -> {}", protocol, self.src, self.dst),
+        }
+    }
+}
+
+impl<'a> IcmpMessage<'a> {
+    /// Parses an ICMP (IPv4) message
+    pub fn parse(data: &'a [u8]) -> Result<Self, IcmpError> {
+        Self::parse_with(data, false)
+    }
+
+    /// Parses an ICMPv6 message
+    pub fn parse_v6(data: &'a [u8]) -> Result<Self, IcmpError> {
+        Self::parse_with(data, true)
+    }
+
+    fn parse_with(data: &'a [u8], v6: bool) -> Result<Self, IcmpError> {
+        // Type, code, checksum and the 4 bytes every type has after them
+        if data.len() < 8 {
+            return Err(IcmpError::TooShort);
+        }
+        Ok(IcmpMessage { data, v6 })
+    }
+
+    pub fn is_v6(&self) -> bool {
+        self.v6
+    }
+
+    pub fn icmp_type(&self) -> u8 {
+        self.data[0]
+    }
+
+    pub fn code(&self) -> u8 {
+        self.data[1]
+    }
+
+    pub fn checksum(&self) -> u16 {
+        u16::from_be_bytes([self.data[2], self.data[3]])
+    }
+
+    fn u16_at(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.data[offset], self.data[offset + 1]])
+    }
+
+    pub fn kind(&self) -> IcmpKind {
+        let echo = || (self.u16_at(4), self.u16_at(6));
+        match (self.v6, self.icmp_type()) {
+            (false, 8) | (true, 128) => {
+                let (identifier, sequence) = echo();
+                IcmpKind::EchoRequest { identifier, sequence }
+            }
+            (false, 0) | (true, 129) => {
+                let (identifier, sequence) = echo();
+                IcmpKind::EchoReply { identifier, sequence }
+            }
+            (false, 3) | (true, 1) => IcmpKind::DestinationUnreachable,
+            (true, 2) => IcmpKind::PacketTooBig { mtu: u32::from_be_bytes([self.data[4], self.data[5], self.data[6], self.data[7]]) },
+            (false, 11) | (true, 3) => IcmpKind::TimeExceeded,
+            (false, 5) => IcmpKind::Redirect { gateway: Ipv4Addr::new(self.data[4], self.data[5], self.data[6], self.data[7]).into() },
+            (true, 137) if self.data.len() >= 40 => {
+                let target: [u8; 16] = self.data[8..24].try_into().unwrap_or_default();
+                IcmpKind::Redirect { gateway: Ipv6Addr::from(target).into() }
+            }
+            _ => IcmpKind::Other,
+        }
+    }
+
+    /// Next-hop MTU of an ICMP "fragmentation needed" or ICMPv6 "packet too big"
+    pub fn mtu(&self) -> Option<u32> {
+        match self.kind() {
+            IcmpKind::PacketTooBig { mtu } => Some(mtu),
+            IcmpKind::DestinationUnreachable if !self.v6 && self.code() == 4 => Some(self.u16_at(6) as u32),
+            _ => None,
+        }
+    }
+
+    /// Whether the message reports a problem with another packet
+    pub fn is_error(&self) -> bool {
+        matches!((self.v6, self.icmp_type()), (false, 3 | 4 | 5 | 11 | 12) | (true, 1..=4 | 137))
+    }
+
+    /// The packet an error message quotes: after the header, or for an ICMPv6
+    /// Redirect in its Redirected Header option
+    pub fn original_datagram(&self) -> Option<OriginalDatagram<'a>> {
+        if !self.is_error() {
+            return None;
+        }
+        if !(self.v6 && self.icmp_type() == 137) {
+            return OriginalDatagram::parse(&self.data[8..]);
+        }
+        let mut options = self.data.get(40..)?;
+        while options.len() >= 8 {
+            let length = options[1] as usize * 8;
+            if length == 0 || length > options.len() {
+                return None;
+            }
+            if options[0] == ND_OPTION_REDIRECTED_HEADER {
+                return OriginalDatagram::parse(&options[8..length]);
+            }
+            options = &options[length..];
+        }
+        None
+    }
+
+    pub fn get_type_name(&self) -> String {
+        let name = match (self.v6, self.icmp_type()) {
+            (false, 0) | (true, 129) => "Echo Reply",
+            (false, 3) | (true, 1) => "Destination Unreachable",
+            (false, 4) => "Source Quench",
+            (false, 5) | (true, 137) => "Redirect",
+            (false, 8) | (true, 128) => "Echo Request",
+            (false, 9) => "Router Advertisement",
+            (false, 10) => "Router Solicitation",
+            (false, 11) | (true, 3) => "Time Exceeded",
+            (false, 12) | (true, 4) => "Parameter Problem",
+            (false, 13) => "Timestamp",
+            (false, 14) => "Timestamp Reply",
+            (true, 2) => "Packet Too Big",
+            (true, 130) => "Multicast Listener Query",
+            (true, 131) => "Multicast Listener Report",
+            (true, 133) => "Router Solicitation",
+            (true, 134) => "Router Advertisement",
+            (true, 135) => "Neighbor Solicitation",
+            (true, 136) => "Neighbor Advertisement",
+            (true, 143) => "Multicast Listener Report v2",
+            (_, other) => return format!("Unknown ({})", other),
+        };
+        name.to_string()
+    }
+
+    pub fn get_code_description(&self) -> String {
+        let description = match (self.v6, self.icmp_type(), self.code()) {
+            (false, 3, 0) => "Network unreachable",
+            (false, 3, 1) => "Host unreachable",
+            (false, 3, 2) => "Protocol unreachable",
+            (false, 3, 3) => "Port unreachable",
+            (false, 3, 4) => "Fragmentation needed and DF set",
+            (false, 3, 5) => "Source route failed",
+            (false, 3, 6) => "Destination network unknown",
+            (false, 3, 7) => "Destination host unknown",
+            (false, 3, 9 | 10 | 13) => "Administratively prohibited",
+            (false, 5, 0) => "Redirect for the network",
+            (false, 5, 1) => "Redirect for the host",
+            (false, 5, 2) => "Redirect for the type of service and network",
+            (false, 5, 3) => "Redirect for the type of service and host",
+            (false, 11, 0) | (true, 3, 0) => "TTL exceeded in transit",
+            (false, 11, 1) | (true, 3, 1) => "Fragment reassembly time exceeded",
+            (true, 1, 0) => "No route to destination",
+            (true, 1, 1) => "Administratively prohibited",
+            (true, 1, 2) => "Beyond scope of source address",
+            (true, 1, 3) => "Address unreachable",
+            (true, 1, 4) => "Port unreachable",
+            (true, 1, 5) => "Source address failed ingress/egress policy",
+            (true, 1, 6) => "Reject route to destination",
+            (_, _, 0) => "No code",
+            _ => return format!("Code {}", self.code()),
+        };
+        description.to_string()
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let prefix = if self.v6 { "ICMPv6" } else { "ICMP" };
+        let field = |name: &str, value: String, description: String| ControlField { name: format!("{} {}", prefix, name), value, description };
+        let mut fields = vec![
+            field("Type", self.icmp_type().to_string(), self.get_type_name()),
+            field("Code", self.code().to_string(), self.get_code_description()),
+            field("Checksum", format!("0x{:04x}", self.checksum()), "Checksum over the message".to_string()),
+        ];
+        match self.kind() {
+            IcmpKind::EchoRequest { identifier, sequence } | IcmpKind::EchoReply { identifier, sequence } => {
+                fields.push(field("Identifier", identifier.to_string(), "Matches replies to requests".to_string()));
+                fields.push(field("Sequence", sequence.to_string(), "Echo sequence number".to_string()));
+            }
+            IcmpKind::Redirect { gateway } => {
+                fields.push(field("Redirect Gateway", gateway.to_string(), "Better first hop for the destination".to_string()));
+            }
+            _ => {}
+        }
+        if let Some(mtu) = self.mtu() {
+            fields.push(field("MTU", mtu.to_string(), "MTU of the next-hop link".to_string()));
+        }
+        if let Some(original) = self.original_datagram() {
+            fields.push(field("Original Datagram", original.to_string(), "Packet the error is about".to_string()));
+            let ip_fields = match original.src {
+                IpAddr::V4(_) => IPv4Packet::parse(original.data).map(|ip| ip.get_control_fields()).unwrap_or_default(),
+                IpAddr::V6(_) => IPv6Packet::parse(original.data).map(|ip| ip.get_control_fields()).unwrap_or_default(),
+            };
+            fields.extend(ip_fields.into_iter().map(|field| ControlField { name: format!("Original {}", field.name), ..field }));
+        }
+        fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn parses_echo_and_redirect() {
+        let echo = IcmpMessage::parse(&[8, 0, 0xf7, 0xfd, 0, 1, 0, 2]).unwrap();
+        assert_eq!(echo.kind(), IcmpKind::EchoRequest { identifier: 1, sequence: 2 });
+        assert_eq!(echo.get_type_name(), "Echo Request");
+        assert!(echo.original_datagram().is_none());
+        let reply = IcmpMessage::parse_v6(&[129, 0, 0, 0, 0, 1, 0, 2]).unwrap();
+        assert_eq!(reply.kind(), IcmpKind::EchoReply { identifier: 1, sequence: 2 });
+
+        let mut redirect = vec![5, 1, 0, 0, 192, 168, 1, 254];
+        redirect.extend(testutil::ipv4(testutil::client_v4(), testutil::server_v4(), testutil::PROTO_UDP, &testutil::udp(5353, 53, b"")));
+        let redirect = IcmpMessage::parse(&redirect).unwrap();
+        assert_eq!(redirect.kind(), IcmpKind::Redirect { gateway: "192.168.1.254".parse().unwrap() });
+        assert_eq!(redirect.get_code_description(), "Redirect for the host");
+        assert_eq!(redirect.original_datagram().unwrap().ports, Some((5353, 53)));
+        assert!(IcmpMessage::parse(&[8, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn decodes_the_original_datagram_of_errors() {
+        let segment = testutil::tcp(40000, 443, 1, 0, testutil::TCP_SYN, &[]);
+        let mut too_big = vec![2, 0, 0, 0, 0, 0, 0x05, 0x00];
+        too_big.extend(testutil::ipv6(testutil::client_v6(), testutil::server_v6(), testutil::PROTO_TCP, &segment));
+        let message = IcmpMessage::parse_v6(&too_big).unwrap();
+        assert_eq!(message.mtu(), Some(1280));
+        let original = message.original_datagram().unwrap();
+        assert_eq!((original.src, original.protocol), (IpAddr::V6(testutil::client_v6()), 6));
+
+        // IPv4 errors quote the IP header and only 8 bytes after it
+        let quoted = testutil::ipv4(testutil::client_v4(), testutil::server_v4(), testutil::PROTO_TCP, &segment);
+        let mut unreachable = vec![3, 3, 0, 0, 0, 0, 0, 0];
+        unreachable.extend_from_slice(&quoted[..28]);
+        let fields = IcmpMessage::parse(&unreachable).unwrap().get_control_fields();
+        assert!(fields.iter().any(|f| f.name == "ICMP Code" && f.description == "Port unreachable"));
+        assert!(fields.iter().any(|f| f.name == "ICMP Original Datagram" && f.value == "TCP 192.168.1.10:40000 -> 93.184.216.34:443"));
+        assert!(fields.iter().any(|f| f.name == "Original Destination IP" && f.value == "93.184.216.34"));
+    }
+}
@@ -0,0 +1,368 @@
+use super::frame_control::ControlField;
+use super::summary::PacketSummary;
+use std::fmt;
+
+pub const IEC104_PORT: u16 = 2404;
+
+const START: u8 = 0x68;
+
+/// Cause of transmission of a command being issued
+pub const COT_ACTIVATION: u8 = 6;
+/// C_RP_NA_1, reset process
+const TYPE_RESET_PROCESS: u8 = 105;
+
+/// APCI frame format, from the low bits of the first control octet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// Numbered information transfer carrying an ASDU
+    I,
+    /// Numbered supervisory acknowledgement
+    S,
+    /// Unnumbered control (STARTDT, STOPDT, TESTFR)
+    U,
+}
+
+/// IEC 60870-5-104 APDU parser: APCI header and, for I-format frames, the ASDU
+pub struct Iec104Apdu<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum Iec104Error {
+    TooShort,
+    BadStart(u8),
+}
+
+impl fmt::Display for Iec104Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Iec104Error::TooShort => write!(f, "Packet too short for IEC 104 APDU"),
+            Iec104Error::BadStart(b) => write!(f, "Start byte 0x{:02x} is not 0x68", b),
+        }
+    }
+}
+
+impl<'a> Iec104Apdu<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, Iec104Error> {
+        if data.len() < 6 {
+            return Err(Iec104Error::TooShort);
+        }
+        if data[0] != START {
+            return Err(Iec104Error::BadStart(data[0]));
+        }
+        let length = data[1] as usize;
+        if length < 4 || data.len() < 2 + length {
+            return Err(Iec104Error::TooShort);
+        }
+        Ok(Iec104Apdu { data: &data[..2 + length] })
+    }
+
+    /// Every complete APDU in a TCP segment
+    pub fn parse_all(mut data: &'a [u8]) -> Vec<Self> {
+        let mut apdus = Vec::new();
+        while let Ok(apdu) = Iec104Apdu::parse(data) {
+            data = &data[apdu.data.len()..];
+            apdus.push(apdu);
+        }
+        apdus
+    }
+
+    pub fn format(&self) -> FrameFormat {
+        match self.data[2] & 0x03 {
+            0 | 2 => FrameFormat::I,
+            1 => FrameFormat::S,
+            _ => FrameFormat::U,
+        }
+    }
+
+    pub fn send_sequence(&self) -> Option<u16> {
+        (self.format() == FrameFormat::I).then(|| u16::from_le_bytes([self.data[2], self.data[3]]) >> 1)
+    }
+
+    pub fn receive_sequence(&self) -> Option<u16> {
+        (self.format() != FrameFormat::U).then(|| u16::from_le_bytes([self.data[4], self.data[5]]) >> 1)
+    }
+
+    pub fn get_u_function_name(&self) -> Option<String> {
+        if self.format() != FrameFormat::U {
+            return None;
+        }
+        Some(match self.data[2] {
+            0x07 => "STARTDT act".to_string(),
+            0x0B => "STARTDT con".to_string(),
+            0x13 => "STOPDT act".to_string(),
+            0x23 => "STOPDT con".to_string(),
+            0x43 => "TESTFR act".to_string(),
+            0x83 => "TESTFR con".to_string(),
+            f => format!("Unknown (0x{:02x})", f),
+        })
+    }
+
+    fn asdu(&self) -> Option<&'a [u8]> {
+        (self.format() == FrameFormat::I).then(|| &self.data[6..]).filter(|asdu| asdu.len() >= 6)
+    }
+
+    pub fn type_id(&self) -> Option<u8> {
+        Some(self.asdu()?[0])
+    }
+
+    /// Number of information objects
+    pub fn object_count(&self) -> Option<u8> {
+        Some(self.asdu()?[1] & 0x7F)
+    }
+
+    /// Objects share one IOA and follow each other (SQ bit)
+    fn sequence_of_elements(&self) -> bool {
+        self.asdu().is_some_and(|asdu| asdu[1] & 0x80 != 0)
+    }
+
+    pub fn cause(&self) -> Option<u8> {
+        Some(self.asdu()?[2] & 0x3F)
+    }
+
+    /// Negative confirmation (P/N bit)
+    pub fn is_negative(&self) -> bool {
+        self.asdu().is_some_and(|asdu| asdu[2] & 0x40 != 0)
+    }
+
+    pub fn is_test(&self) -> bool {
+        self.asdu().is_some_and(|asdu| asdu[2] & 0x80 != 0)
+    }
+
+    pub fn originator(&self) -> Option<u8> {
+        Some(self.asdu()?[3])
+    }
+
+    /// Common address of the ASDU (station address)
+    pub fn common_address(&self) -> Option<u16> {
+        let asdu = self.asdu()?;
+        Some(u16::from_le_bytes([asdu[4], asdu[5]]))
+    }
+
+    /// Information object addresses. Only the first is known when the
+    /// element size of the type is not.
+    pub fn ioas(&self) -> Vec<u32> {
+        let Some(asdu) = self.asdu() else { return Vec::new() };
+        let ioa = |at: usize| asdu.get(at..at + 3).map(|b| u32::from_le_bytes([b[0], b[1], b[2], 0]));
+        let count = asdu[1] as usize & 0x7F;
+        let (Some(size), false) = (element_size(asdu[0]), self.sequence_of_elements()) else {
+            return ioa(6).into_iter().collect();
+        };
+        (0..count).map_while(|i| ioa(6 + i * (3 + size))).collect()
+    }
+
+    /// Command in the control direction (process or reset)
+    pub fn is_command(&self) -> bool {
+        self.type_id().is_some_and(|t| matches!(t, 45..=64 | TYPE_RESET_PROCESS))
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = Vec::new();
+        match self.format() {
+            FrameFormat::U => fields.push(ControlField {
+                name: "IEC 104 Frame".to_string(),
+                value: "U".to_string(),
+                description: self.get_u_function_name().unwrap_or_default(),
+            }),
+            FrameFormat::S => fields.push(ControlField {
+                name: "IEC 104 Frame".to_string(),
+                value: "S".to_string(),
+                description: format!("N(R) {}", self.receive_sequence().unwrap_or_default()),
+            }),
+            FrameFormat::I => fields.push(ControlField {
+                name: "IEC 104 Frame".to_string(),
+                value: "I".to_string(),
+                description: format!(
+                    "N(S) {}, N(R) {}",
+                    self.send_sequence().unwrap_or_default(),
+                    self.receive_sequence().unwrap_or_default()
+                ),
+            }),
+        }
+        let (Some(type_id), Some(cause), Some(address)) = (self.type_id(), self.cause(), self.common_address()) else {
+            return fields;
+        };
+        fields.push(ControlField {
+            name: "IEC 104 Type".to_string(),
+            value: type_name(type_id),
+            description: format!("Type {}, {} object(s)", type_id, self.object_count().unwrap_or_default()),
+        });
+        let mut flags = Vec::new();
+        if self.is_negative() {
+            flags.push("negative");
+        }
+        if self.is_test() {
+            flags.push("test");
+        }
+        fields.push(ControlField {
+            name: "IEC 104 Cause".to_string(),
+            value: cause_name(cause),
+            description: if flags.is_empty() { format!("Cause {}", cause) } else { format!("Cause {} ({})", cause, flags.join(", ")) },
+        });
+        fields.push(ControlField {
+            name: "IEC 104 Common Address".to_string(),
+            value: address.to_string(),
+            description: format!("Originator {}", self.originator().unwrap_or_default()),
+        });
+        let ioas = self.ioas();
+        if !ioas.is_empty() {
+            fields.push(ControlField {
+                name: "IEC 104 IOA".to_string(),
+                value: ioas.iter().map(|ioa| ioa.to_string()).collect::<Vec<_>>().join(", "),
+                description: "Information object addresses".to_string(),
+            });
+        }
+        fields
+    }
+}
+
+/// Size of one information element (without IOA) for the common types
+fn element_size(type_id: u8) -> Option<usize> {
+    Some(match type_id {
+        1 | 3 | 45 | 46 | 47 | 70 | 100 | 101 | TYPE_RESET_PROCESS => 1,
+        102 => 0,
+        5 => 2,
+        9 | 11 | 48 | 49 => 3,
+        51 => 4,
+        7 | 13 | 50 => 5,
+        103 => 7,
+        30 | 31 | 58 | 59 | 60 => 8,
+        107 => 9,
+        34 | 35 | 61 | 62 => 10,
+        64 => 11,
+        36 | 63 => 12,
+        _ => return None,
+    })
+}
+
+pub fn type_name(type_id: u8) -> String {
+    match type_id {
+        1 => "M_SP_NA_1 single-point".to_string(),
+        3 => "M_DP_NA_1 double-point".to_string(),
+        5 => "M_ST_NA_1 step position".to_string(),
+        7 => "M_BO_NA_1 bitstring".to_string(),
+        9 => "M_ME_NA_1 normalized value".to_string(),
+        11 => "M_ME_NB_1 scaled value".to_string(),
+        13 => "M_ME_NC_1 float value".to_string(),
+        30 => "M_SP_TB_1 single-point with time".to_string(),
+        31 => "M_DP_TB_1 double-point with time".to_string(),
+        34 => "M_ME_TD_1 normalized value with time".to_string(),
+        35 => "M_ME_TE_1 scaled value with time".to_string(),
+        36 => "M_ME_TF_1 float value with time".to_string(),
+        45 => "C_SC_NA_1 single command".to_string(),
+        46 => "C_DC_NA_1 double command".to_string(),
+        47 => "C_RC_NA_1 regulating step command".to_string(),
+        48 => "C_SE_NA_1 set point normalized".to_string(),
+        49 => "C_SE_NB_1 set point scaled".to_string(),
+        50 => "C_SE_NC_1 set point float".to_string(),
+        51 => "C_BO_NA_1 bitstring command".to_string(),
+        58 => "C_SC_TA_1 single command with time".to_string(),
+        59 => "C_DC_TA_1 double command with time".to_string(),
+        60 => "C_RC_TA_1 regulating step command with time".to_string(),
+        61 => "C_SE_TA_1 set point normalized with time".to_string(),
+        62 => "C_SE_TB_1 set point scaled with time".to_string(),
+        63 => "C_SE_TC_1 set point float with time".to_string(),
+        64 => "C_BO_TA_1 bitstring command with time".to_string(),
+        70 => "M_EI_NA_1 end of initialization".to_string(),
+        100 => "C_IC_NA_1 interrogation".to_string(),
+        101 => "C_CI_NA_1 counter interrogation".to_string(),
+        102 => "C_RD_NA_1 read".to_string(),
+        103 => "C_CS_NA_1 clock synchronization".to_string(),
+        TYPE_RESET_PROCESS => "C_RP_NA_1 reset process".to_string(),
+        107 => "C_TS_TA_1 test with time".to_string(),
+        t => format!("Unknown ({})", t),
+    }
+}
+
+pub fn cause_name(cause: u8) -> String {
+    match cause {
+        1 => "Periodic".to_string(),
+        2 => "Background scan".to_string(),
+        3 => "Spontaneous".to_string(),
+        4 => "Initialized".to_string(),
+        5 => "Request".to_string(),
+        COT_ACTIVATION => "Activation".to_string(),
+        7 => "Activation confirmation".to_string(),
+        8 => "Deactivation".to_string(),
+        9 => "Deactivation confirmation".to_string(),
+        10 => "Activation termination".to_string(),
+        11 => "Remote command".to_string(),
+        12 => "Local command".to_string(),
+        20 => "Interrogated by station".to_string(),
+        44 => "Unknown type".to_string(),
+        45 => "Unknown cause".to_string(),
+        46 => "Unknown common address".to_string(),
+        47 => "Unknown object address".to_string(),
+        c => format!("Unknown ({})", c),
+    }
+}
+
+/// Warning text for a command activation sent to an outstation
+pub fn control_command_alert(summary: &PacketSummary, data: &[u8]) -> Option<String> {
+    if !summary.is_tcp() || !summary.has_port(IEC104_PORT) {
+        return None;
+    }
+    let apdus = Iec104Apdu::parse_all(summary.payload(data));
+    let command = apdus.iter().find(|apdu| apdu.is_command() && apdu.cause() == Some(COT_ACTIVATION))?;
+    let ioas = command.ioas().iter().map(|ioa| ioa.to_string()).collect::<Vec<_>>().join(", ");
+    Some(format!(
+        "IEC 104 command {} to station {} IOA {}: {} -> {}",
+        type_name(command.type_id()?),
+        command.common_address()?,
+        ioas,
+        summary.src_ip?,
+        summary.dst_ip?
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    /// I-format C_SC_NA_1 activation: station 1, IOA 5000, close
+    const SINGLE_COMMAND: [u8; 16] = [0x68, 0x0E, 0x02, 0x00, 0x04, 0x00, 45, 0x01, 0x06, 0x00, 0x01, 0x00, 0x88, 0x13, 0x00, 0x01];
+
+    #[test]
+    fn decodes_apci_and_asdu() {
+        let apdu = Iec104Apdu::parse(&SINGLE_COMMAND).unwrap();
+        assert_eq!(apdu.format(), FrameFormat::I);
+        assert_eq!((apdu.send_sequence(), apdu.receive_sequence()), (Some(1), Some(2)));
+        assert_eq!(apdu.type_id(), Some(45));
+        assert_eq!(apdu.cause(), Some(COT_ACTIVATION));
+        assert_eq!(apdu.common_address(), Some(1));
+        assert_eq!(apdu.ioas(), vec![5000]);
+        assert!(apdu.is_command());
+
+        let testfr = Iec104Apdu::parse(&[0x68, 0x04, 0x43, 0x00, 0x00, 0x00]).unwrap();
+        assert_eq!(testfr.get_u_function_name().as_deref(), Some("TESTFR act"));
+        assert!(matches!(Iec104Apdu::parse(&[0x69, 4, 0, 0, 0, 0]), Err(Iec104Error::BadStart(0x69))));
+    }
+
+    #[test]
+    fn lists_objects_of_measurements() {
+        // Two M_ME_NC_1 floats, spontaneous, after an S-format frame
+        let mut data = vec![0x68, 0x04, 0x01, 0x00, 0x0A, 0x00];
+        data.extend_from_slice(&[0x68, 0x1A, 0x00, 0x00, 0x00, 0x00, 13, 0x02, 0x03, 0x00, 0x07, 0x00]);
+        data.extend_from_slice(&[0x01, 0x40, 0x00, 0, 0, 0x80, 0x3F, 0x00]);
+        data.extend_from_slice(&[0x02, 0x40, 0x00, 0, 0, 0x00, 0x40, 0x00]);
+        let apdus = Iec104Apdu::parse_all(&data);
+        assert_eq!(apdus.len(), 2);
+        assert_eq!(apdus[0].receive_sequence(), Some(5));
+        assert_eq!(apdus[1].ioas(), vec![0x4001, 0x4002]);
+        assert!(!apdus[1].is_command());
+    }
+
+    #[test]
+    fn alerts_on_command_activation() {
+        let frame = testutil::ipv4_tcp_frame(50000, IEC104_PORT, testutil::TCP_PSH, &SINGLE_COMMAND);
+        let summary = PacketSummary::from_ethernet(&frame).unwrap();
+        let alert = control_command_alert(&summary, &frame).unwrap();
+        assert!(alert.starts_with("IEC 104 command C_SC_NA_1 single command to station 1 IOA 5000"));
+
+        let mut confirmation = SINGLE_COMMAND;
+        confirmation[8] = 7;
+        let frame = testutil::ipv4_tcp_frame(IEC104_PORT, 50000, testutil::TCP_PSH, &confirmation);
+        assert!(control_command_alert(&PacketSummary::from_ethernet(&frame).unwrap(), &frame).is_none());
+    }
+}
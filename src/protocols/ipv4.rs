@@ -38,6 +38,9 @@ impl<'a> IPv4Packet<'a> {
         if ihl < 5 {
             return Err(IPv4Error::InvalidHeaderLength);
         }
+        if ihl as usize * 4 > data.len() {
+            return Err(IPv4Error::TooShort);
+        }
         
         Ok(IPv4Packet { data })
     }
@@ -94,6 +97,12 @@ impl<'a> IPv4Packet<'a> {
         Ipv4Addr::new(self.data[16], self.data[17], self.data[18], self.data[19])
     }
     
+    /// Get payload data following the header (options included in the header)
+    pub fn payload(&self) -> &[u8] {
+        let start = (self.header_length() as usize).min(self.data.len());
+        &self.data[start..]
+    }
+    
     pub fn get_protocol_name(&self) -> String {
         match self.protocol() {
             1 => "ICMP".to_string(),
@@ -188,4 +197,45 @@ impl<'a> IPv4Packet<'a> {
             },
         ]
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn parses_header_fields() {
+        let bytes = testutil::ipv4(testutil::client_v4(), testutil::server_v4(), testutil::PROTO_UDP, &[0; 8]);
+        let packet = IPv4Packet::parse(&bytes).unwrap();
+        assert_eq!(packet.version(), 4);
+        assert_eq!(packet.header_length(), 20);
+        assert_eq!(packet.total_length(), 28);
+        assert_eq!(packet.ttl(), 64);
+        assert_eq!(packet.get_protocol_name(), "UDP");
+        assert_eq!(packet.get_flags_description(), "Don't Fragment");
+        assert_eq!(packet.source_ip(), testutil::client_v4());
+        assert_eq!(packet.destination_ip(), testutil::server_v4());
+        assert_eq!(testutil::internet_checksum(&bytes[..20]), 0);
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let mut bytes = testutil::ipv4(testutil::client_v4(), testutil::server_v4(), testutil::PROTO_TCP, &[]);
+        assert!(matches!(IPv4Packet::parse(&bytes[..19]), Err(IPv4Error::TooShort)));
+        bytes[0] = 0x44;
+        assert!(matches!(IPv4Packet::parse(&bytes), Err(IPv4Error::InvalidHeaderLength)));
+        bytes[0] = 0x65;
+        assert!(matches!(IPv4Packet::parse(&bytes), Err(IPv4Error::InvalidVersion)));
+    }
+
+    #[test]
+    fn rejects_header_length_beyond_the_data() {
+        let mut bytes = testutil::ipv4(testutil::client_v4(), testutil::server_v4(), testutil::PROTO_UDP, &testutil::udp(5353, 53, b""));
+        // IHL 12 (48 bytes) in a 28-byte packet
+        bytes[0] = 0x4c;
+        assert!(matches!(IPv4Packet::parse(&bytes), Err(IPv4Error::TooShort)));
+        let frame = testutil::ethernet(testutil::SRC_MAC, testutil::DST_MAC, testutil::ETHERTYPE_IPV4, &bytes);
+        assert!(crate::protocols::analyze_frame_control(&frame).is_some());
+    }
+}
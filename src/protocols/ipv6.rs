@@ -73,6 +73,11 @@ impl<'a> IPv6Packet<'a> {
         Ipv6Addr::from(addr)
     }
     
+    /// Get payload data following the fixed 40-byte header
+    pub fn payload(&self) -> &[u8] {
+        &self.data[40..]
+    }
+    
     pub fn get_next_header_name(&self) -> String {
         match self.next_header() {
             0 => "Hop-by-Hop Options".to_string(),
@@ -134,4 +139,30 @@ impl<'a> IPv6Packet<'a> {
             },
         ]
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn parses_header_fields() {
+        let bytes = testutil::ipv6(testutil::client_v6(), testutil::server_v6(), testutil::PROTO_TCP, &[0; 20]);
+        let packet = IPv6Packet::parse(&bytes).unwrap();
+        assert_eq!(packet.version(), 6);
+        assert_eq!(packet.payload_length(), 20);
+        assert_eq!(packet.hop_limit(), 64);
+        assert_eq!(packet.get_next_header_name(), "TCP");
+        assert_eq!(packet.source_ip(), testutil::client_v6());
+        assert_eq!(packet.destination_ip(), testutil::server_v6());
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let mut bytes = testutil::ipv6(testutil::client_v6(), testutil::server_v6(), testutil::PROTO_UDP, &[]);
+        assert!(matches!(IPv6Packet::parse(&bytes[..39]), Err(IPv6Error::TooShort)));
+        bytes[0] = 0x40;
+        assert!(matches!(IPv6Packet::parse(&bytes), Err(IPv6Error::InvalidVersion)));
+    }
+}
@@ -0,0 +1,220 @@
+use super::frame_control::ControlField;
+use std::fmt;
+
+/// Network layer protocol identifier of IS-IS
+pub const NLPID_ISIS: u8 = 0x83;
+
+const TLV_AREA_ADDRESSES: u8 = 1;
+const TLV_IS_NEIGHBORS: u8 = 6;
+const TLV_HOSTNAME: u8 = 137;
+
+/// IS-IS PDU parser (ISO 10589), as carried in LLC frames with DSAP 0xFE
+pub struct IsisPdu<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum IsisError {
+    TooShort,
+    NotIsis(u8),
+}
+
+impl fmt::Display for IsisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IsisError::TooShort => write!(f, "Packet too short for IS-IS PDU"),
+            IsisError::NotIsis(nlpid) => write!(f, "NLPID 0x{:02x} is not IS-IS", nlpid),
+        }
+    }
+}
+
+/// Formats a 6-byte system ID as three dotted groups, e.g. `1921.6800.1001`
+pub fn format_system_id(id: &[u8]) -> String {
+    id.chunks(2).map(|pair| pair.iter().map(|b| format!("{:02x}", b)).collect::<String>()).collect::<Vec<_>>().join(".")
+}
+
+/// Formats an area address as the AFI followed by two-byte groups, e.g. `49.0001`
+pub fn format_area(area: &[u8]) -> String {
+    let Some((afi, rest)) = area.split_first() else { return String::new() };
+    let mut groups = vec![format!("{:02x}", afi)];
+    groups.extend(rest.chunks(2).map(|pair| pair.iter().map(|b| format!("{:02x}", b)).collect::<String>()));
+    groups.join(".")
+}
+
+impl<'a> IsisPdu<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, IsisError> {
+        if data.len() < 8 {
+            return Err(IsisError::TooShort);
+        }
+        if data[0] != NLPID_ISIS {
+            return Err(IsisError::NotIsis(data[0]));
+        }
+        if data.len() < data[1] as usize {
+            return Err(IsisError::TooShort);
+        }
+        Ok(IsisPdu { data })
+    }
+
+    pub fn pdu_type(&self) -> u8 {
+        self.data[4] & 0x1F
+    }
+
+    pub fn get_type_name(&self) -> String {
+        match self.pdu_type() {
+            15 => "L1 LAN Hello".to_string(),
+            16 => "L2 LAN Hello".to_string(),
+            17 => "P2P Hello".to_string(),
+            18 => "L1 LSP".to_string(),
+            20 => "L2 LSP".to_string(),
+            24 => "L1 CSNP".to_string(),
+            25 => "L2 CSNP".to_string(),
+            26 => "L1 PSNP".to_string(),
+            27 => "L2 PSNP".to_string(),
+            t => format!("Unknown ({})", t),
+        }
+    }
+
+    fn header_len(&self) -> usize {
+        self.data[1] as usize
+    }
+
+    /// System ID of the sender (hellos, SNPs) or the originator (LSPs)
+    pub fn system_id(&self) -> Option<String> {
+        let at = match self.pdu_type() {
+            15..=17 => 9,
+            18 | 20 => 12,
+            24..=27 => 10,
+            _ => return None,
+        };
+        self.data.get(at..at + 6).map(format_system_id)
+    }
+
+    /// LSP ID (system ID, pseudonode and fragment) of an LSP
+    pub fn lsp_id(&self) -> Option<String> {
+        if !matches!(self.pdu_type(), 18 | 20) {
+            return None;
+        }
+        let id = self.data.get(12..20)?;
+        Some(format!("{}.{:02x}-{:02x}", format_system_id(&id[..6]), id[6], id[7]))
+    }
+
+    /// LSP sequence number
+    pub fn sequence(&self) -> Option<u32> {
+        if !matches!(self.pdu_type(), 18 | 20) {
+            return None;
+        }
+        let seq = self.data.get(20..24)?;
+        Some(u32::from_be_bytes([seq[0], seq[1], seq[2], seq[3]]))
+    }
+
+    /// Variable-length fields after the PDU-specific header
+    pub fn tlvs(&self) -> Vec<(u8, &'a [u8])> {
+        let mut tlvs = Vec::new();
+        let mut rest = &self.data[self.header_len().min(self.data.len())..];
+        while let [tlv_type, len, ..] = *rest {
+            let Some(value) = rest.get(2..2 + len as usize) else { break };
+            tlvs.push((tlv_type, value));
+            rest = &rest[2 + len as usize..];
+        }
+        tlvs
+    }
+
+    pub fn area_addresses(&self) -> Vec<String> {
+        let mut areas = Vec::new();
+        for (_, mut value) in self.tlvs().into_iter().filter(|(t, _)| *t == TLV_AREA_ADDRESSES) {
+            while let Some((&len, rest)) = value.split_first() {
+                let Some(area) = rest.get(..len as usize) else { break };
+                areas.push(format_area(area));
+                value = &rest[len as usize..];
+            }
+        }
+        areas
+    }
+
+    pub fn hostname(&self) -> Option<String> {
+        self.tlvs().into_iter().find(|(t, _)| *t == TLV_HOSTNAME).map(|(_, v)| String::from_utf8_lossy(v).into_owned())
+    }
+
+    /// Number of neighbor MAC addresses listed in a LAN Hello
+    pub fn lan_neighbors(&self) -> usize {
+        self.tlvs().into_iter().filter(|(t, _)| *t == TLV_IS_NEIGHBORS).map(|(_, v)| v.len() / 6).sum()
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![ControlField {
+            name: "IS-IS PDU".to_string(),
+            value: self.get_type_name(),
+            description: format!("Type {}", self.pdu_type()),
+        }];
+        if let Some(system_id) = self.system_id() {
+            fields.push(ControlField {
+                name: "IS-IS System ID".to_string(),
+                value: system_id,
+                description: self.hostname().unwrap_or_else(|| "No hostname advertised".to_string()),
+            });
+        }
+        if let (Some(lsp_id), Some(sequence)) = (self.lsp_id(), self.sequence()) {
+            fields.push(ControlField {
+                name: "IS-IS LSP ID".to_string(),
+                value: lsp_id,
+                description: format!("Sequence 0x{:08x}", sequence),
+            });
+        }
+        let areas = self.area_addresses();
+        if !areas.is_empty() {
+            fields.push(ControlField {
+                name: "IS-IS Areas".to_string(),
+                value: areas.join(", "),
+                description: "Area addresses".to_string(),
+            });
+        }
+        if matches!(self.pdu_type(), 15 | 16) {
+            fields.push(ControlField {
+                name: "IS-IS Neighbors".to_string(),
+                value: self.lan_neighbors().to_string(),
+                description: "Routers heard on the LAN".to_string(),
+            });
+        }
+        fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lan_hello() -> Vec<u8> {
+        let mut pdu = vec![NLPID_ISIS, 27, 1, 0, 15, 1, 0, 0];
+        pdu.push(1); // circuit type L1
+        pdu.extend_from_slice(&[0x19, 0x21, 0x68, 0x00, 0x10, 0x01]);
+        pdu.extend_from_slice(&[0, 30, 0, 0, 64]);
+        pdu.extend_from_slice(&[0x19, 0x21, 0x68, 0x00, 0x10, 0x01, 0x01]);
+        pdu.extend_from_slice(&[TLV_AREA_ADDRESSES, 4, 3, 0x49, 0x00, 0x01]);
+        pdu.extend_from_slice(&[TLV_IS_NEIGHBORS, 6, 0, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        pdu.extend_from_slice(&[TLV_HOSTNAME, 4]);
+        pdu.extend_from_slice(b"core");
+        pdu
+    }
+
+    #[test]
+    fn decodes_lan_hello() {
+        let data = lan_hello();
+        let pdu = IsisPdu::parse(&data).unwrap();
+        assert_eq!(pdu.get_type_name(), "L1 LAN Hello");
+        assert_eq!(pdu.system_id().as_deref(), Some("1921.6800.1001"));
+        assert_eq!(pdu.area_addresses(), vec!["49.0001".to_string()]);
+        assert_eq!(pdu.hostname().as_deref(), Some("core"));
+        assert_eq!(pdu.lan_neighbors(), 1);
+        assert!(matches!(IsisPdu::parse(&[0x82, 8, 1, 0, 0, 1, 0, 0]), Err(IsisError::NotIsis(0x82))));
+    }
+
+    #[test]
+    fn decodes_lsp_ids() {
+        let mut pdu = vec![NLPID_ISIS, 27, 1, 0, 20, 1, 0, 0, 0, 40, 4, 0xb0];
+        pdu.extend_from_slice(&[0x19, 0x21, 0x68, 0x00, 0x10, 0x02, 0x00, 0x00]);
+        pdu.extend_from_slice(&[0, 0, 0, 5, 0, 0, 0x03]);
+        let pdu = IsisPdu::parse(&pdu).unwrap();
+        assert_eq!(pdu.lsp_id().as_deref(), Some("1921.6800.1002.00-00"));
+        assert_eq!(pdu.sequence(), Some(5));
+    }
+}
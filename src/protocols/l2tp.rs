@@ -0,0 +1,214 @@
+use super::frame_control::ControlField;
+use super::ppp::PppFrame;
+use std::fmt;
+
+pub const L2TP_PORT: u16 = 1701;
+
+const FLAG_TYPE: u16 = 0x8000;
+const FLAG_LENGTH: u16 = 0x4000;
+const FLAG_SEQUENCE: u16 = 0x0800;
+const FLAG_OFFSET: u16 = 0x0200;
+
+/// L2TPv2 packet parser (RFC 2661)
+pub struct L2tpPacket<'a> {
+    data: &'a [u8],
+    header_len: usize,
+}
+
+#[derive(Debug)]
+pub enum L2tpError {
+    TooShort,
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for L2tpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            L2tpError::TooShort => write!(f, "Packet too short for L2TP header"),
+            L2tpError::UnsupportedVersion(v) => write!(f, "Unsupported L2TP version {}", v),
+        }
+    }
+}
+
+impl<'a> L2tpPacket<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, L2tpError> {
+        if data.len() < 6 {
+            return Err(L2tpError::TooShort);
+        }
+        let flags = u16::from_be_bytes([data[0], data[1]]);
+        let version = (flags & 0x000F) as u8;
+        if version != 2 {
+            return Err(L2tpError::UnsupportedVersion(version));
+        }
+        let mut header_len = 6;
+        if flags & FLAG_LENGTH != 0 {
+            header_len += 2;
+        }
+        if flags & FLAG_SEQUENCE != 0 {
+            header_len += 4;
+        }
+        if flags & FLAG_OFFSET != 0 {
+            let offset = data.get(header_len..header_len + 2).ok_or(L2tpError::TooShort)?;
+            header_len += 2 + u16::from_be_bytes([offset[0], offset[1]]) as usize;
+        }
+        if data.len() < header_len {
+            return Err(L2tpError::TooShort);
+        }
+        Ok(L2tpPacket { data, header_len })
+    }
+
+    fn flags(&self) -> u16 {
+        u16::from_be_bytes([self.data[0], self.data[1]])
+    }
+
+    pub fn is_control(&self) -> bool {
+        self.flags() & FLAG_TYPE != 0
+    }
+
+    /// Offset of the tunnel ID (after the optional length field)
+    fn ids_at(&self) -> usize {
+        if self.flags() & FLAG_LENGTH != 0 { 4 } else { 2 }
+    }
+
+    pub fn tunnel_id(&self) -> u16 {
+        let at = self.ids_at();
+        u16::from_be_bytes([self.data[at], self.data[at + 1]])
+    }
+
+    pub fn session_id(&self) -> u16 {
+        let at = self.ids_at() + 2;
+        u16::from_be_bytes([self.data[at], self.data[at + 1]])
+    }
+
+    /// Ns and Nr, when the sequence fields are present
+    pub fn sequence(&self) -> Option<(u16, u16)> {
+        if self.flags() & FLAG_SEQUENCE == 0 {
+            return None;
+        }
+        let at = self.ids_at() + 4;
+        Some((
+            u16::from_be_bytes([self.data[at], self.data[at + 1]]),
+            u16::from_be_bytes([self.data[at + 2], self.data[at + 3]]),
+        ))
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[self.header_len..]
+    }
+
+    /// Message Type AVP of a control message; `None` for a zero-length
+    /// acknowledgement (ZLB) or when the first AVP is hidden
+    pub fn message_type(&self) -> Option<u16> {
+        if !self.is_control() {
+            return None;
+        }
+        let avp = self.payload();
+        let flags = u16::from_be_bytes([*avp.first()?, *avp.get(1)?]);
+        let vendor = u16::from_be_bytes([*avp.get(2)?, *avp.get(3)?]);
+        let attribute = u16::from_be_bytes([*avp.get(4)?, *avp.get(5)?]);
+        if flags & 0x4000 != 0 || vendor != 0 || attribute != 0 {
+            return None;
+        }
+        Some(u16::from_be_bytes([*avp.get(6)?, *avp.get(7)?]))
+    }
+
+    /// PPP frame carried in a data message
+    pub fn ppp(&self) -> Option<PppFrame<'a>> {
+        if self.is_control() {
+            return None;
+        }
+        PppFrame::parse(self.payload()).ok()
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let kind = if self.is_control() {
+            match self.message_type() {
+                Some(t) => message_type_name(t),
+                None if self.payload().is_empty() => "ZLB".to_string(),
+                None => "Control".to_string(),
+            }
+        } else {
+            "Data".to_string()
+        };
+        let mut fields = vec![
+            ControlField {
+                name: "L2TP Message".to_string(),
+                value: kind,
+                description: if self.is_control() { "Control message" } else { "Data message" }.to_string(),
+            },
+            ControlField {
+                name: "L2TP Tunnel".to_string(),
+                value: self.tunnel_id().to_string(),
+                description: format!("Session {}", self.session_id()),
+            },
+        ];
+        if let Some((ns, nr)) = self.sequence() {
+            fields.push(ControlField {
+                name: "L2TP Sequence".to_string(),
+                value: format!("{}/{}", ns, nr),
+                description: "Ns/Nr".to_string(),
+            });
+        }
+        if let Some(ppp) = self.ppp() {
+            fields.extend(ppp.get_control_fields());
+        }
+        fields
+    }
+}
+
+pub fn message_type_name(message_type: u16) -> String {
+    match message_type {
+        1 => "SCCRQ".to_string(),
+        2 => "SCCRP".to_string(),
+        3 => "SCCCN".to_string(),
+        4 => "StopCCN".to_string(),
+        6 => "HELLO".to_string(),
+        7 => "OCRQ".to_string(),
+        8 => "OCRP".to_string(),
+        9 => "OCCN".to_string(),
+        10 => "ICRQ".to_string(),
+        11 => "ICRP".to_string(),
+        12 => "ICCN".to_string(),
+        14 => "CDN".to_string(),
+        15 => "WEN".to_string(),
+        16 => "SLI".to_string(),
+        t => format!("Unknown ({})", t),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::ppp::PPP_IPV4;
+    use crate::testutil;
+
+    #[test]
+    fn decodes_control_messages() {
+        // T, L, S set; tunnel 0, session 0, Ns 0, Nr 0, Message Type AVP = SCCRQ
+        let sccrq = [0xC8, 0x02, 0, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0x80, 8, 0, 0, 0, 0, 0, 1];
+        let packet = L2tpPacket::parse(&sccrq).unwrap();
+        assert!(packet.is_control());
+        assert_eq!(packet.message_type(), Some(1));
+        assert_eq!(packet.get_control_fields()[0].value, "SCCRQ");
+
+        let zlb = [0xC8, 0x02, 0, 12, 0, 7, 0, 0, 0, 1, 0, 1];
+        let packet = L2tpPacket::parse(&zlb).unwrap();
+        assert_eq!(packet.tunnel_id(), 7);
+        assert_eq!(packet.sequence(), Some((1, 1)));
+        assert_eq!(packet.get_control_fields()[0].value, "ZLB");
+
+        assert!(matches!(L2tpPacket::parse(&[0x00, 0x03, 0, 0, 0, 0]), Err(L2tpError::UnsupportedVersion(3))));
+    }
+
+    #[test]
+    fn decodes_ppp_in_data_messages() {
+        let ip = testutil::ipv4(testutil::client_v4(), testutil::server_v4(), 6, &[0; 20]);
+        let mut data = vec![0x00, 0x02, 0x12, 0x34, 0x00, 0x09, 0xFF, 0x03, 0x00, 0x21];
+        data.extend_from_slice(&ip);
+        let packet = L2tpPacket::parse(&data).unwrap();
+        assert_eq!((packet.tunnel_id(), packet.session_id()), (0x1234, 9));
+        assert_eq!(packet.ppp().unwrap().protocol(), PPP_IPV4);
+        let fields = packet.get_control_fields();
+        assert!(fields.iter().any(|f| f.name == "Inner Protocol" && f.description == "TCP"));
+    }
+}
@@ -0,0 +1,413 @@
+use super::frame_control::ControlField;
+use super::summary::PacketSummary;
+use std::fmt;
+
+pub const LDAP_PORT: u16 = 389;
+
+/// Extended operation OID requesting TLS on the connection (RFC 4511 section 4.14)
+pub const START_TLS_OID: &str = "1.3.6.1.4.1.1466.20037";
+
+const TAG_BIND_REQUEST: u8 = 0x60;
+const TAG_SEARCH_REQUEST: u8 = 0x63;
+const TAG_EXTENDED_REQUEST: u8 = 0x77;
+
+/// Splits one BER element off `data`, returning its tag, contents and the rest
+fn read_tlv(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)? as usize;
+    let (len, header) = if first & 0x80 == 0 {
+        (first, 2)
+    } else {
+        let octets = first & 0x7F;
+        if octets == 0 || octets > 4 {
+            return None;
+        }
+        let len = data.get(2..2 + octets)?.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+        (len, 2 + octets)
+    };
+    let value = data.get(header..header + len)?;
+    Some((tag, value, &data[header + len..]))
+}
+
+fn read_integer(value: &[u8]) -> i64 {
+    let initial = if value.first().is_some_and(|b| b & 0x80 != 0) { -1 } else { 0 };
+    value.iter().take(8).fold(initial, |acc, &b| (acc << 8) | b as i64)
+}
+
+fn text(value: &[u8]) -> String {
+    String::from_utf8_lossy(value).into_owned()
+}
+
+/// How a BindRequest authenticates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BindAuth {
+    /// Simple authentication; an empty password is an anonymous or unauthenticated bind
+    Simple(Vec<u8>),
+    Sasl(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindRequest {
+    pub version: i64,
+    pub dn: String,
+    pub auth: BindAuth,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchRequest {
+    pub base: String,
+    pub scope: i64,
+    /// Filter in RFC 4515 string form
+    pub filter: String,
+}
+
+/// LDAPv3 message parser (RFC 4511)
+pub struct LdapMessage<'a> {
+    message_id: i64,
+    op_tag: u8,
+    op: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum LdapError {
+    TooShort,
+    InvalidEncoding,
+}
+
+impl fmt::Display for LdapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LdapError::TooShort => write!(f, "Packet too short for LDAP message"),
+            LdapError::InvalidEncoding => write!(f, "Invalid BER encoding in LDAP message"),
+        }
+    }
+}
+
+impl<'a> LdapMessage<'a> {
+    /// Parses the first message in `data`
+    pub fn parse(data: &'a [u8]) -> Result<Self, LdapError> {
+        Self::parse_with_rest(data).map(|(message, _)| message)
+    }
+
+    fn parse_with_rest(data: &'a [u8]) -> Result<(Self, &'a [u8]), LdapError> {
+        if data.len() < 2 {
+            return Err(LdapError::TooShort);
+        }
+        let (tag, body, rest) = read_tlv(data).ok_or(LdapError::TooShort)?;
+        if tag != 0x30 {
+            return Err(LdapError::InvalidEncoding);
+        }
+        let (id_tag, id, body) = read_tlv(body).ok_or(LdapError::InvalidEncoding)?;
+        let (op_tag, op, _) = read_tlv(body).ok_or(LdapError::InvalidEncoding)?;
+        if id_tag != 0x02 || op_tag & 0x40 == 0 {
+            return Err(LdapError::InvalidEncoding);
+        }
+        Ok((LdapMessage { message_id: read_integer(id), op_tag, op }, rest))
+    }
+
+    /// Every complete message in a segment (clients often pipeline requests)
+    pub fn parse_all(mut data: &'a [u8]) -> Vec<Self> {
+        let mut messages = Vec::new();
+        while let Ok((message, rest)) = Self::parse_with_rest(data) {
+            messages.push(message);
+            data = rest;
+        }
+        messages
+    }
+
+    pub fn message_id(&self) -> i64 {
+        self.message_id
+    }
+
+    /// Application tag number of the protocol operation
+    pub fn operation(&self) -> u8 {
+        self.op_tag & 0x1F
+    }
+
+    pub fn get_operation_name(&self) -> String {
+        operation_name(self.operation())
+    }
+
+    pub fn bind(&self) -> Option<BindRequest> {
+        if self.op_tag != TAG_BIND_REQUEST {
+            return None;
+        }
+        let (_, version, rest) = read_tlv(self.op)?;
+        let (_, dn, rest) = read_tlv(rest)?;
+        let (auth_tag, auth, _) = read_tlv(rest)?;
+        let auth = match auth_tag {
+            0x80 => BindAuth::Simple(auth.to_vec()),
+            0xA3 => BindAuth::Sasl(read_tlv(auth).map(|(_, mechanism, _)| text(mechanism)).unwrap_or_default()),
+            _ => return None,
+        };
+        Some(BindRequest { version: read_integer(version), dn: text(dn), auth })
+    }
+
+    pub fn search(&self) -> Option<SearchRequest> {
+        if self.op_tag != TAG_SEARCH_REQUEST {
+            return None;
+        }
+        let (_, base, rest) = read_tlv(self.op)?;
+        let (_, scope, rest) = read_tlv(rest)?;
+        // derefAliases, sizeLimit, timeLimit, typesOnly
+        let (_, _, rest) = read_tlv(rest)?;
+        let (_, _, rest) = read_tlv(rest)?;
+        let (_, _, rest) = read_tlv(rest)?;
+        let (_, _, rest) = read_tlv(rest)?;
+        let filter = format_filter(rest, 0)?;
+        Some(SearchRequest { base: text(base), scope: read_integer(scope), filter })
+    }
+
+    /// Result code of a response operation
+    pub fn result_code(&self) -> Option<i64> {
+        match self.operation() {
+            1 | 5 | 7 | 9 | 11 | 13 | 15 | 24 => {
+                let (tag, code, _) = read_tlv(self.op)?;
+                (tag == 0x0A).then(|| read_integer(code))
+            }
+            _ => None,
+        }
+    }
+
+    /// OID of an ExtendedRequest
+    pub fn extended_name(&self) -> Option<String> {
+        if self.op_tag != TAG_EXTENDED_REQUEST {
+            return None;
+        }
+        let (tag, name, _) = read_tlv(self.op)?;
+        (tag == 0x80).then(|| text(name))
+    }
+
+    pub fn is_start_tls(&self) -> bool {
+        self.extended_name().as_deref() == Some(START_TLS_OID)
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![ControlField {
+            name: "LDAP Operation".to_string(),
+            value: self.get_operation_name(),
+            description: format!("Message ID {}", self.message_id),
+        }];
+        if let Some(bind) = self.bind() {
+            fields.push(ControlField {
+                name: "LDAP Bind DN".to_string(),
+                value: bind.dn,
+                description: format!("LDAPv{}", bind.version),
+            });
+            let (value, description) = match bind.auth {
+                BindAuth::Simple(password) if password.is_empty() => ("simple".to_string(), "Anonymous bind".to_string()),
+                BindAuth::Simple(password) => ("simple".to_string(), format!("Cleartext password ({} bytes)", password.len())),
+                BindAuth::Sasl(mechanism) => ("SASL".to_string(), mechanism),
+            };
+            fields.push(ControlField { name: "LDAP Auth".to_string(), value, description });
+        }
+        if let Some(search) = self.search() {
+            let scope = match search.scope {
+                0 => "base object",
+                1 => "single level",
+                2 => "whole subtree",
+                _ => "unknown scope",
+            };
+            fields.push(ControlField { name: "LDAP Search Base".to_string(), value: search.base, description: scope.to_string() });
+            fields.push(ControlField {
+                name: "LDAP Filter".to_string(),
+                value: search.filter,
+                description: "Search filter".to_string(),
+            });
+        }
+        if let Some(name) = self.extended_name() {
+            let description = if self.is_start_tls() { "StartTLS" } else { "Extended operation" };
+            fields.push(ControlField { name: "LDAP Extended".to_string(), value: name, description: description.to_string() });
+        }
+        if let Some(code) = self.result_code() {
+            fields.push(ControlField {
+                name: "LDAP Result".to_string(),
+                value: code.to_string(),
+                description: result_name(code),
+            });
+        }
+        fields
+    }
+}
+
+/// Renders a BER-encoded search filter as an RFC 4515 string
+fn format_filter(data: &[u8], depth: usize) -> Option<String> {
+    if depth > 16 {
+        return None;
+    }
+    let (tag, value, _) = read_tlv(data)?;
+    let pair = |op: &str| -> Option<String> {
+        let (_, attribute, rest) = read_tlv(value)?;
+        let (_, assertion, _) = read_tlv(rest)?;
+        Some(format!("({}{}{})", text(attribute), op, text(assertion)))
+    };
+    match tag {
+        0xA0 | 0xA1 => {
+            let mut inner = String::new();
+            let mut rest = value;
+            while !rest.is_empty() {
+                inner.push_str(&format_filter(rest, depth + 1)?);
+                rest = read_tlv(rest)?.2;
+            }
+            Some(format!("({}{})", if tag == 0xA0 { '&' } else { '|' }, inner))
+        }
+        0xA2 => Some(format!("(!{})", format_filter(value, depth + 1)?)),
+        0xA3 => pair("="),
+        0xA5 => pair(">="),
+        0xA6 => pair("<="),
+        0xA8 => pair("~="),
+        0x87 => Some(format!("({}=*)", text(value))),
+        0xA4 => {
+            let (_, attribute, rest) = read_tlv(value)?;
+            let (_, mut substrings, _) = read_tlv(rest)?;
+            let mut pattern = String::new();
+            let mut last = None;
+            while let Some((kind, part, rest)) = read_tlv(substrings) {
+                // initial [0], any [1], final [2]
+                if kind != 0x80 && !pattern.ends_with('*') {
+                    pattern.push('*');
+                }
+                pattern.push_str(&text(part));
+                last = Some(kind);
+                substrings = rest;
+            }
+            if last != Some(0x82) {
+                pattern.push('*');
+            }
+            Some(format!("({}={})", text(attribute), pattern))
+        }
+        0xA9 => Some("(extensible match)".to_string()),
+        _ => None,
+    }
+}
+
+pub fn operation_name(operation: u8) -> String {
+    match operation {
+        0 => "BindRequest".to_string(),
+        1 => "BindResponse".to_string(),
+        2 => "UnbindRequest".to_string(),
+        3 => "SearchRequest".to_string(),
+        4 => "SearchResultEntry".to_string(),
+        5 => "SearchResultDone".to_string(),
+        6 => "ModifyRequest".to_string(),
+        7 => "ModifyResponse".to_string(),
+        8 => "AddRequest".to_string(),
+        9 => "AddResponse".to_string(),
+        10 => "DelRequest".to_string(),
+        11 => "DelResponse".to_string(),
+        12 => "ModifyDNRequest".to_string(),
+        13 => "ModifyDNResponse".to_string(),
+        14 => "CompareRequest".to_string(),
+        15 => "CompareResponse".to_string(),
+        16 => "AbandonRequest".to_string(),
+        19 => "SearchResultReference".to_string(),
+        23 => "ExtendedRequest".to_string(),
+        24 => "ExtendedResponse".to_string(),
+        25 => "IntermediateResponse".to_string(),
+        op => format!("Unknown ({})", op),
+    }
+}
+
+pub fn result_name(code: i64) -> String {
+    match code {
+        0 => "success".to_string(),
+        1 => "operationsError".to_string(),
+        2 => "protocolError".to_string(),
+        7 => "authMethodNotSupported".to_string(),
+        8 => "strongerAuthRequired".to_string(),
+        13 => "confidentialityRequired".to_string(),
+        14 => "saslBindInProgress".to_string(),
+        32 => "noSuchObject".to_string(),
+        34 => "invalidDNSyntax".to_string(),
+        48 => "inappropriateAuthentication".to_string(),
+        49 => "invalidCredentials".to_string(),
+        50 => "insufficientAccessRights".to_string(),
+        53 => "unwillingToPerform".to_string(),
+        c => format!("Unknown ({})", c),
+    }
+}
+
+/// Alert text for a simple bind sending a password in cleartext on port 389.
+/// After StartTLS the connection carries TLS records, so a bind that still
+/// decodes here went over the wire unprotected.
+pub fn cleartext_bind_alert(summary: &PacketSummary, data: &[u8]) -> Option<String> {
+    if !summary.is_tcp() || summary.dst_port != Some(LDAP_PORT) {
+        return None;
+    }
+    LdapMessage::parse_all(summary.payload(data)).iter().find_map(|message| {
+        let bind = message.bind()?;
+        match bind.auth {
+            BindAuth::Simple(password) if !password.is_empty() => Some(format!(
+                "Cleartext LDAP simple bind: {} -> {} as '{}' ({}-byte password, no StartTLS)",
+                summary.src_ip?,
+                summary.dst_ip?,
+                bind.dn,
+                password.len()
+            )),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn decodes_bind_requests_and_alerts_on_cleartext() {
+        let request = testutil::ldap_simple_bind(1, "cn=admin,dc=example,dc=com", "secret");
+        let message = LdapMessage::parse(&request).unwrap();
+        assert_eq!(message.get_operation_name(), "BindRequest");
+        let bind = message.bind().unwrap();
+        assert_eq!((bind.version, bind.dn.as_str()), (3, "cn=admin,dc=example,dc=com"));
+        assert_eq!(bind.auth, BindAuth::Simple(b"secret".to_vec()));
+
+        let frame = testutil::ipv4_tcp_frame(50000, LDAP_PORT, testutil::TCP_PSH | testutil::TCP_ACK, &request);
+        let summary = PacketSummary::from_ethernet(&frame).unwrap();
+        let alert = cleartext_bind_alert(&summary, &frame).unwrap();
+        assert!(alert.contains("'cn=admin,dc=example,dc=com' (6-byte password"));
+
+        let anonymous = testutil::ldap_simple_bind(1, "", "");
+        let frame = testutil::ipv4_tcp_frame(50000, LDAP_PORT, testutil::TCP_PSH | testutil::TCP_ACK, &anonymous);
+        assert!(cleartext_bind_alert(&PacketSummary::from_ethernet(&frame).unwrap(), &frame).is_none());
+    }
+
+    #[test]
+    fn renders_search_filters() {
+        // (&(objectClass=user)(sAMAccountName=a*)(!(mail=*)))
+        let filter = [
+            &[0xA0, 0x34][..],
+            &[0xA3, 0x13, 0x04, 0x0B], b"objectClass", &[0x04, 0x04], b"user",
+            &[0xA4, 0x15, 0x04, 0x0E], b"sAMAccountName", &[0x30, 0x03, 0x80, 0x01, b'a'],
+            &[0xA2, 0x06, 0x87, 0x04], b"mail",
+        ]
+        .concat();
+        let mut op = vec![0x04, 0x11];
+        op.extend_from_slice(b"dc=example,dc=com");
+        op.extend_from_slice(&[0x0A, 0x01, 0x02, 0x0A, 0x01, 0x00, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00, 0x01, 0x01, 0x00]);
+        op.extend_from_slice(&filter);
+        op.extend_from_slice(&[0x30, 0x00]);
+        let mut message = vec![0x02, 0x01, 0x02, 0x63, op.len() as u8];
+        message.extend_from_slice(&op);
+        let mut data = vec![0x30, message.len() as u8];
+        data.extend_from_slice(&message);
+
+        let search = LdapMessage::parse(&data).unwrap().search().unwrap();
+        assert_eq!(search.base, "dc=example,dc=com");
+        assert_eq!(search.scope, 2);
+        assert_eq!(search.filter, "(&(objectClass=user)(sAMAccountName=a*)(!(mail=*)))");
+    }
+
+    #[test]
+    fn parses_pipelined_messages_and_results() {
+        let mut data = testutil::ldap_simple_bind(1, "cn=a", "pw");
+        // BindResponse, invalidCredentials
+        data.extend_from_slice(&[0x30, 0x0C, 0x02, 0x01, 0x01, 0x61, 0x07, 0x0A, 0x01, 0x31, 0x04, 0x00, 0x04, 0x00]);
+        let messages = LdapMessage::parse_all(&data);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].result_code(), Some(49));
+        assert_eq!(messages[1].get_control_fields().last().unwrap().description, "invalidCredentials");
+        assert!(LdapMessage::parse(&[0x16, 0x03, 0x01]).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt;
+
+/// DSAP/SSAP of OSI network layer PDUs (IS-IS, ES-IS)
+pub const SAP_OSI: u8 = 0xFE;
+/// DSAP/SSAP announcing a SNAP header
+pub const SAP_SNAP: u8 = 0xAA;
+
+/// IEEE 802.2 LLC header, with the SNAP extension when present. Carried
+/// by 802.3 frames, whose EtherType field is a length (1500 or less).
+pub struct LlcHeader<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum LlcError {
+    TooShort,
+}
+
+impl fmt::Display for LlcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LlcError::TooShort => write!(f, "Packet too short for LLC header"),
+        }
+    }
+}
+
+impl<'a> LlcHeader<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, LlcError> {
+        if data.len() < 3 || (data[0] == SAP_SNAP && data.len() < 8) {
+            return Err(LlcError::TooShort);
+        }
+        Ok(LlcHeader { data })
+    }
+
+    pub fn dsap(&self) -> u8 {
+        self.data[0]
+    }
+
+    pub fn ssap(&self) -> u8 {
+        self.data[1]
+    }
+
+    pub fn control(&self) -> u8 {
+        self.data[2]
+    }
+
+    /// Organization code and protocol ID of a SNAP header
+    pub fn snap(&self) -> Option<([u8; 3], u16)> {
+        if self.dsap() != SAP_SNAP {
+            return None;
+        }
+        let d = self.data;
+        Some(([d[3], d[4], d[5]], u16::from_be_bytes([d[6], d[7]])))
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        let header = if self.snap().is_some() { 8 } else { 3 };
+        &self.data[header..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_plain_and_snap_headers() {
+        let osi = LlcHeader::parse(&[0xFE, 0xFE, 0x03, 0x83]).unwrap();
+        assert_eq!((osi.dsap(), osi.snap(), osi.payload()), (SAP_OSI, None, &[0x83][..]));
+
+        let snap = LlcHeader::parse(&[0xAA, 0xAA, 0x03, 0x00, 0x00, 0x0C, 0x20, 0x00, 0x02]).unwrap();
+        assert_eq!(snap.snap(), Some(([0x00, 0x00, 0x0C], 0x2000)));
+        assert_eq!(snap.payload(), &[0x02]);
+        assert!(LlcHeader::parse(&[0xAA, 0xAA, 0x03]).is_err());
+    }
+}
@@ -1,5 +1,271 @@
+pub mod arp;
+pub mod bacnet;
+pub mod cdp;
+pub mod detect;
+pub mod diameter;
+pub mod dns;
+pub mod eapol;
+pub mod enip;
 pub mod ethernet;
 pub mod frame_control;
+pub mod gtp;
+pub mod hsrp;
+pub mod http;
+pub mod http2;
+pub mod icmp;
+pub mod iec104;
 pub mod ipv4;
 pub mod ipv6;
-pub mod wifi;
\ No newline at end of file
+pub mod isis;
+pub mod l2tp;
+pub mod ldap;
+pub mod llc;
+pub mod name_resolution;
+pub mod null;
+pub mod opcua;
+pub mod ospf;
+pub mod ppp;
+pub mod profinet;
+pub mod quic;
+pub mod radius;
+pub mod radiotap;
+pub mod registry;
+pub mod sctp;
+pub mod sll;
+pub mod someip;
+pub mod summary;
+pub mod tacacs;
+pub mod tcp;
+pub mod tls;
+pub mod tunnel;
+pub mod usb;
+pub mod vrrp;
+pub mod websocket;
+pub mod wifi;
+
+use log::debug;
+
+use ethernet::EthernetFrame;
+use frame_control::{ControlField, FrameControlInfo, ProtocolType};
+use registry::dissectors;
+use summary::PacketSummary;
+
+/// Analyzes a packet's raw data and extracts frame control information
+pub fn analyze_frame_control(data: &[u8]) -> Option<FrameControlInfo> {
+    if data.len() < 14 {  // Minimum Ethernet frame size
+        debug!("Packet too small to contain valid frame control data");
+        return None;
+    }
+    
+    // Try to parse as Ethernet frame
+    match EthernetFrame::parse(data) {
+        Ok(eth_frame) => {
+            let mut info = eth_frame.get_frame_control();
+            info.control_fields.extend(application_control_fields(data));
+            Some(info)
+        }
+        Err(e) => {
+            debug!("Failed to parse frame control: {}", e);
+            None
+        }
+    }
+}
+
+/// Application-layer fields for payloads recognized by port
+fn application_control_fields(data: &[u8]) -> Vec<ControlField> {
+    let Some(summary) = PacketSummary::from_ethernet(data) else {
+        return Vec::new();
+    };
+    application_fields(&summary, data)
+}
+
+/// Application-layer fields for an already summarized packet; `data` is the
+/// buffer the summary's offsets refer to
+fn application_fields(summary: &PacketSummary, data: &[u8]) -> Vec<ControlField> {
+    // Plugins registered for a port or EtherType take precedence
+    let mut fields = crate::plugin::dissect(summary, data).unwrap_or_else(|| dissectors().dissect_packet(summary, data));
+    fields.extend(truncation_field(summary, data));
+    fields
+}
+
+/// Marks a packet cut short by the snaplen, so fields missing past the cut
+/// read as truncated rather than absent
+fn truncation_field(summary: &PacketSummary, data: &[u8]) -> Option<ControlField> {
+    let missing = summary.missing_bytes(data);
+    if missing == 0 {
+        return None;
+    }
+    // A transport header that didn't fit leaves the summary without ports
+    let name = match summary.ip_proto {
+        Some(6 | 17 | 132) if summary.transport_offset.is_none() => format!("{} Header", summary.protocol_name()),
+        _ => "Payload".to_string(),
+    };
+    Some(ControlField {
+        name,
+        value: "<truncated>".to_string(),
+        description: format!("{} bytes beyond the snapshot length were not captured", missing),
+    })
+}
+
+/// Analyzes a Linux cooked capture: the cooked header, then the packet it carries
+fn sll_frame_control(datalink: i32, data: &[u8]) -> Option<FrameControlInfo> {
+    let sll = sll::SllHeader::parse(datalink, data).ok()?;
+    let mut control_fields = sll.get_control_fields();
+    control_fields.extend(network_fields(sll.protocol(), sll.payload()));
+    if let Some(summary) = PacketSummary::from_sll(datalink, data) {
+        control_fields.extend(application_fields(&summary, data));
+    }
+    Some(FrameControlInfo { protocol_type: ProtocolType::Other("Linux cooked".to_string()), control_fields })
+}
+
+/// Analyzes a BSD loopback or utun packet: the address family, then the IP packet
+fn null_frame_control(datalink: i32, data: &[u8]) -> Option<FrameControlInfo> {
+    let null = null::NullHeader::parse(datalink, data).ok()?;
+    let mut control_fields = null.get_control_fields();
+    control_fields.extend(network_fields(null.ether_type(), null.payload()));
+    if let Some(summary) = PacketSummary::from_null(datalink, data) {
+        control_fields.extend(application_fields(&summary, data));
+    }
+    Some(FrameControlInfo { protocol_type: ProtocolType::Other("Loopback".to_string()), control_fields })
+}
+
+/// Network layer fields of a packet behind a pseudo-header, by EtherType
+fn network_fields(ether_type: u16, payload: &[u8]) -> Vec<ControlField> {
+    dissectors().dissect_network(ether_type, payload)
+}
+
+/// Dispatches on the capture's datalink type (DLT_*) before analyzing the frame
+pub fn analyze_link_layer(datalink: i32, data: &[u8]) -> Option<FrameControlInfo> {
+    match datalink {
+        // DLT_IEEE802_11
+        105 => wifi::WifiFrame::parse(data).ok().map(|frame| frame.get_frame_control()),
+        // DLT_IEEE802_11_RADIO
+        127 => wifi::radiotap_frame_control(data),
+        // DLT_PPP, DLT_PPP_SERIAL
+        9 | 50 => ppp::PppFrame::parse(data).ok().map(|frame| frame.get_frame_control()),
+        sll::DLT_LINUX_SLL | sll::DLT_LINUX_SLL2 => sll_frame_control(datalink, data),
+        null::DLT_NULL | null::DLT_LOOP => null_frame_control(datalink, data),
+        usb::DLT_USB_LINUX => usb::UsbUrb::parse(data, false).ok().map(|urb| urb.get_frame_control()),
+        usb::DLT_USB_LINUX_MMAPPED => usb::UsbUrb::parse(data, true).ok().map(|urb| urb.get_frame_control()),
+        _ => analyze_frame_control(data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn analyzes_synthetic_frames() {
+        assert!(analyze_frame_control(&testutil::dns_query_frame(0x1234, "example.com")).is_some());
+        assert!(analyze_frame_control(&testutil::ipv6_tcp_frame(1, 2, testutil::TCP_ACK, b"hi")).is_some());
+        assert!(analyze_frame_control(&[0u8; 10]).is_none());
+    }
+
+    #[test]
+    fn includes_quic_fields() {
+        let frame = testutil::quic_initial_frame(50000, &[1; 8], "example.com", &["h3"]);
+        let fields = analyze_frame_control(&frame).unwrap().control_fields;
+        let field = |name: &str| fields.iter().find(|f| f.name == name).map(|f| f.value.clone());
+        assert_eq!(field("QUIC Packet").as_deref(), Some("Initial"));
+        assert_eq!(field("QUIC SNI").as_deref(), Some("example.com"));
+        assert_eq!(field("QUIC ALPN").as_deref(), Some("h3"));
+    }
+
+    #[test]
+    fn includes_l2tp_fields() {
+        let frame = testutil::ipv4_udp_frame(l2tp::L2TP_PORT, l2tp::L2TP_PORT, &[0x00, 0x02, 0, 1, 0, 2, 0xC0, 0x21, 9, 0, 0, 4]);
+        let fields = analyze_frame_control(&frame).unwrap().control_fields;
+        assert!(fields.iter().any(|f| f.name == "L2TP Tunnel" && f.value == "1"));
+        assert!(fields.iter().any(|f| f.name == "PPP Protocol" && f.description == "LCP"));
+    }
+
+    #[test]
+    fn includes_gtp_inner_packet_fields() {
+        let ldap = testutil::ldap_simple_bind(1, "cn=admin", "secret");
+        let inner = testutil::ipv4(testutil::client_v4(), testutil::server_v4(), 6, &testutil::tcp(50000, ldap::LDAP_PORT, 1, 0, testutil::TCP_PSH, &ldap));
+        let frame = testutil::ipv4_udp_frame(gtp::GTP_U_PORT, gtp::GTP_U_PORT, &testutil::gtp_u(7, &inner));
+        let fields = analyze_frame_control(&frame).unwrap().control_fields;
+        assert!(fields.iter().any(|f| f.name == "GTP TEID" && f.value == "0x00000007"));
+        assert!(fields.iter().any(|f| f.name == "Inner Destination IP" && f.value == testutil::server_v4().to_string()));
+        assert!(fields.iter().any(|f| f.name.starts_with("LDAP")));
+    }
+
+    #[test]
+    fn includes_tcp_header_fields() {
+        for frame in [testutil::ipv4_tcp_frame(40000, 22, testutil::TCP_SYN, b""), testutil::ipv6_tcp_frame(40000, 22, testutil::TCP_SYN, b"")] {
+            let fields = analyze_frame_control(&frame).unwrap().control_fields;
+            assert!(fields.iter().any(|f| f.name == "Destination Port" && f.value == "22"));
+            assert!(fields.iter().any(|f| f.name == "TCP Flags" && f.description == "SYN"));
+        }
+    }
+
+    #[test]
+    fn includes_http_request_fields() {
+        let frame = testutil::ipv4_tcp_frame(40000, 8080, testutil::TCP_PSH | testutil::TCP_ACK, b"GET /status HTTP/1.1\r\nHost: printer.lan\r\n\r\n");
+        let fields = analyze_frame_control(&frame).unwrap().control_fields;
+        assert!(fields.iter().any(|f| f.name == "TCP Flags"));
+        assert!(fields.iter().any(|f| f.name == "HTTP URI" && f.value == "/status"));
+        assert!(fields.iter().any(|f| f.name == "HTTP Host" && f.value == "printer.lan"));
+    }
+
+    #[test]
+    fn includes_tls_server_name() {
+        let frame = testutil::ipv4_tcp_frame(40000, 443, testutil::TCP_PSH | testutil::TCP_ACK, &testutil::tls_client_hello("api.example.org"));
+        let fields = analyze_frame_control(&frame).unwrap().control_fields;
+        assert!(fields.iter().any(|f| f.name == "TLS SNI" && f.value == "api.example.org"));
+        assert!(!fields.iter().any(|f| f.name == "Detected Protocol"));
+    }
+
+    #[test]
+    fn includes_icmp_messages() {
+        let ip = testutil::ipv4(testutil::server_v4(), testutil::client_v4(), icmp::IP_PROTO_ICMP, &[0, 0, 0, 0, 0x12, 0x34, 0, 7]);
+        let frame = testutil::ethernet(testutil::SRC_MAC, testutil::DST_MAC, testutil::ETHERTYPE_IPV4, &ip);
+        let fields = analyze_frame_control(&frame).unwrap().control_fields;
+        assert!(fields.iter().any(|f| f.name == "ICMP Type" && f.description == "Echo Reply"));
+        assert!(fields.iter().any(|f| f.name == "ICMP Sequence" && f.value == "7"));
+    }
+
+    #[test]
+    fn includes_diameter_over_sctp() {
+        let message = testutil::diameter_message(0x80, 316, 16777251, &[(diameter::AVP_ORIGIN_HOST, b"mme1.epc.example.org")]);
+        let sctp = testutil::sctp_data(36412, diameter::DIAMETER_PORT, diameter::DIAMETER_PPID, &message);
+        let ip = testutil::ipv4(testutil::client_v4(), testutil::server_v4(), sctp::IP_PROTO_SCTP, &sctp);
+        let frame = testutil::ethernet(testutil::SRC_MAC, testutil::DST_MAC, testutil::ETHERTYPE_IPV4, &ip);
+        let fields = analyze_frame_control(&frame).unwrap().control_fields;
+        assert!(fields.iter().any(|f| f.name == "Diameter Command" && f.value == "Update-Location Request"));
+        assert!(fields.iter().any(|f| f.name == "Diameter Application" && f.value == "3GPP S6a/S6d"));
+    }
+
+    #[test]
+    fn marks_truncated_packets() {
+        let frame = testutil::ipv4_tcp_frame(40000, 8080, testutil::TCP_ACK, b"GET / HTTP/1.1\r\n\r\n");
+        let field = |data: &[u8]| {
+            let fields = analyze_frame_control(data).unwrap().control_fields;
+            fields.iter().find(|f| f.value == "<truncated>").map(|f| (f.name.clone(), f.description.clone()))
+        };
+        assert_eq!(field(&frame), None);
+        let (name, description) = field(&frame[..frame.len() - 4]).unwrap();
+        assert_eq!((name.as_str(), description.as_str()), ("Payload", "4 bytes beyond the snapshot length were not captured"));
+        assert_eq!(field(&frame[..40]).unwrap().0, "TCP Header");
+    }
+
+    #[test]
+    fn dispatches_on_datalink() {
+        let frame = testutil::ipv4_udp_frame(1, 2, b"");
+        assert!(analyze_link_layer(1, &frame).is_some());
+        // An Ethernet frame is not a valid radiotap header
+        assert!(analyze_link_layer(127, &frame).is_none());
+        let ppp = analyze_link_layer(9, &[0xFF, 0x03, 0xC0, 0x21, 1, 1, 0, 4]).unwrap();
+        assert_eq!(ppp.control_fields[1].description, "Configure-Request");
+        let mut cooked = vec![0x08, 0x00, 0, 0, 0, 0, 0, 1, 0, 1, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        cooked.extend_from_slice(&frame[14..]);
+        let cooked = analyze_link_layer(sll::DLT_LINUX_SLL2, &cooked).unwrap();
+        assert!(cooked.control_fields.iter().any(|f| f.name == "Packet Type" && f.value == "Sent by us"));
+        assert!(cooked.control_fields.iter().any(|f| f.name == "Source IP" && f.value == testutil::client_v4().to_string()));
+        let urb = analyze_link_layer(usb::DLT_USB_LINUX_MMAPPED, &[0; 64]).unwrap();
+        assert_eq!(urb.control_fields[1].value, "Isochronous");
+        assert!(analyze_link_layer(usb::DLT_USB_LINUX_MMAPPED, &[0; 48]).is_none());
+    }
+}
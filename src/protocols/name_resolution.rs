@@ -0,0 +1,252 @@
+use super::dns;
+use super::frame_control::ControlField;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+pub const NBNS_PORT: u16 = 137;
+pub const MDNS_PORT: u16 = 5353;
+pub const LLMNR_PORT: u16 = 5355;
+
+const TYPE_A: u16 = 1;
+const TYPE_NB: u16 = 32;
+const TYPE_AAAA: u16 = 28;
+
+/// Group bit in the flags of an NB resource record
+const NB_GROUP: u16 = 0x8000;
+
+/// Local name resolution protocols that fall back to multicast or broadcast
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum NameProtocol {
+    Llmnr,
+    Mdns,
+    Nbns,
+}
+
+impl NameProtocol {
+    /// Protocol carried on a UDP port, if any
+    pub fn from_port(port: u16) -> Option<Self> {
+        match port {
+            LLMNR_PORT => Some(NameProtocol::Llmnr),
+            MDNS_PORT => Some(NameProtocol::Mdns),
+            NBNS_PORT => Some(NameProtocol::Nbns),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NameProtocol::Llmnr => "LLMNR",
+            NameProtocol::Mdns => "mDNS",
+            NameProtocol::Nbns => "NBNS",
+        }
+    }
+}
+
+/// An address record in a response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameAnswer {
+    pub name: String,
+    pub address: IpAddr,
+    /// NetBIOS group name (workgroup/domain), which many hosts legitimately share
+    pub group: bool,
+}
+
+/// LLMNR (RFC 4795), mDNS (RFC 6762) and NetBIOS name service (RFC 1002)
+/// message parser. All three share the DNS header and record layout;
+/// NetBIOS names are additionally first-level encoded.
+pub struct NameMessage<'a> {
+    protocol: NameProtocol,
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum NameResolutionError {
+    TooShort,
+}
+
+impl fmt::Display for NameResolutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NameResolutionError::TooShort => write!(f, "Packet too short for name service header"),
+        }
+    }
+}
+
+impl<'a> NameMessage<'a> {
+    pub fn parse(protocol: NameProtocol, data: &'a [u8]) -> Result<Self, NameResolutionError> {
+        if data.len() < 12 {
+            return Err(NameResolutionError::TooShort);
+        }
+        Ok(NameMessage { protocol, data })
+    }
+
+    pub fn protocol(&self) -> NameProtocol {
+        self.protocol
+    }
+
+    fn u16_at(&self, at: usize) -> u16 {
+        u16::from_be_bytes([self.data[at], self.data[at + 1]])
+    }
+
+    pub fn id(&self) -> u16 {
+        self.u16_at(0)
+    }
+
+    pub fn flags(&self) -> u16 {
+        self.u16_at(2)
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.flags() & 0x8000 != 0
+    }
+
+    /// Opcode: 0 for queries; NBNS also uses 5 (registration), 6 (release) and 8 (refresh)
+    pub fn opcode(&self) -> u8 {
+        ((self.flags() >> 11) & 0x0F) as u8
+    }
+
+    /// Names asked for in the question section
+    pub fn questions(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut at = 12;
+        for _ in 0..self.u16_at(4) {
+            let Some((name, next)) = self.read_name(at) else { break };
+            names.push(name);
+            at = next + 4;
+        }
+        names
+    }
+
+    /// A, AAAA and NB records in the answer section
+    pub fn answers(&self) -> Vec<NameAnswer> {
+        let mut at = 12;
+        for _ in 0..self.u16_at(4) {
+            let Some((_, next)) = self.read_name(at) else { return Vec::new() };
+            at = next + 4;
+        }
+        let mut answers = Vec::new();
+        for _ in 0..self.u16_at(6) {
+            let Some((name, next)) = self.read_name(at) else { break };
+            let Some(header) = self.data.get(next..next + 10) else { break };
+            let rtype = u16::from_be_bytes([header[0], header[1]]);
+            let rdlength = u16::from_be_bytes([header[8], header[9]]) as usize;
+            let Some(rdata) = self.data.get(next + 10..next + 10 + rdlength) else { break };
+            match (rtype, rdata.len()) {
+                (TYPE_A, 4) => answers.push(NameAnswer {
+                    name: name.clone(),
+                    address: IpAddr::V4(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3])),
+                    group: false,
+                }),
+                (TYPE_AAAA, 16) => {
+                    let mut octets = [0u8; 16];
+                    octets.copy_from_slice(rdata);
+                    answers.push(NameAnswer { name: name.clone(), address: IpAddr::V6(Ipv6Addr::from(octets)), group: false });
+                }
+                (TYPE_NB, _) => {
+                    for entry in rdata.chunks_exact(6) {
+                        answers.push(NameAnswer {
+                            name: name.clone(),
+                            address: IpAddr::V4(Ipv4Addr::new(entry[2], entry[3], entry[4], entry[5])),
+                            group: u16::from_be_bytes([entry[0], entry[1]]) & NB_GROUP != 0,
+                        });
+                    }
+                }
+                _ => {}
+            }
+            at = next + 10 + rdlength;
+        }
+        answers
+    }
+
+    /// Reads a name like DNS does, then undoes the NetBIOS encoding for NBNS
+    fn read_name(&self, at: usize) -> Option<(String, usize)> {
+        let (name, next) = dns::read_name(self.data, at)?;
+        let name = match self.protocol {
+            NameProtocol::Nbns => decode_netbios_name(&name).unwrap_or(name),
+            _ => name,
+        };
+        Some((name, next))
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let kind = match (self.is_response(), self.opcode()) {
+            (false, 0) => "Query",
+            (true, 0) => "Response",
+            (_, 5) => "Registration",
+            (_, 6) => "Release",
+            (_, 8) => "Refresh",
+            _ => "Other",
+        };
+        let mut fields = vec![ControlField {
+            name: format!("{} Message", self.protocol.name()),
+            value: kind.to_string(),
+            description: format!("Transaction ID 0x{:04x}", self.id()),
+        }];
+        for name in self.questions() {
+            fields.push(ControlField {
+                name: format!("{} Query", self.protocol.name()),
+                value: name,
+                description: "Name being resolved".to_string(),
+            });
+        }
+        for answer in self.answers() {
+            fields.push(ControlField {
+                name: format!("{} Answer", self.protocol.name()),
+                value: answer.name,
+                description: if answer.group { format!("{} (group)", answer.address) } else { answer.address.to_string() },
+            });
+        }
+        fields
+    }
+}
+
+/// Decodes a first-level encoded NetBIOS name (32 letters 'A'..'P'),
+/// dropping the padding and the suffix byte
+pub fn decode_netbios_name(encoded: &str) -> Option<String> {
+    let (label, scope) = encoded.split_once('.').map_or((encoded, None), |(l, s)| (l, Some(s)));
+    let bytes = label.as_bytes();
+    if bytes.len() != 32 || !bytes.iter().all(|b| (b'A'..=b'P').contains(b)) {
+        return None;
+    }
+    let decoded: Vec<u8> = bytes.chunks_exact(2).map(|pair| ((pair[0] - b'A') << 4) | (pair[1] - b'A')).collect();
+    let name = String::from_utf8_lossy(&decoded[..15]).trim_end().to_string();
+    Some(match scope {
+        Some(scope) => format!("{}.{}", name, scope),
+        None => name,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn parses_llmnr_query_and_response() {
+        let query = testutil::dns_query(0x4242, "fileserv", 1);
+        let message = NameMessage::parse(NameProtocol::Llmnr, &query).unwrap();
+        assert!(!message.is_response());
+        assert_eq!(message.questions(), vec!["fileserv".to_string()]);
+
+        let response = testutil::dns_response(0x4242, "fileserv", &[testutil::client_v4()]);
+        let message = NameMessage::parse(NameProtocol::Llmnr, &response).unwrap();
+        assert!(message.is_response());
+        let answers = message.answers();
+        assert_eq!(answers.len(), 1);
+        assert_eq!((answers[0].name.as_str(), answers[0].address), ("fileserv", IpAddr::V4(testutil::client_v4())));
+        assert!(message.get_control_fields().iter().any(|f| f.name == "LLMNR Answer" && f.value == "fileserv"));
+    }
+
+    #[test]
+    fn decodes_netbios_names() {
+        let response = testutil::nbns_response(0x8001, "WPAD", testutil::client_v4());
+        let message = NameMessage::parse(NameProtocol::Nbns, &response).unwrap();
+        assert!(message.questions().is_empty());
+        assert_eq!(message.answers()[0].name, "WPAD");
+        assert!(!message.answers()[0].group);
+
+        assert_eq!(decode_netbios_name("FHFAEBEECACACACACACACACACACACAAA").as_deref(), Some("WPAD"));
+        assert!(decode_netbios_name("wpad").is_none());
+        assert!(NameMessage::parse(NameProtocol::Nbns, &[0; 4]).is_err());
+    }
+}
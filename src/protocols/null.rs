@@ -0,0 +1,97 @@
+use super::frame_control::ControlField;
+use std::fmt;
+
+/// BSD loopback encapsulation: a 4-byte address family in the capturing
+/// host's byte order. Used by lo0 and utun interfaces on macOS and the BSDs.
+pub const DLT_NULL: i32 = 0;
+/// OpenBSD loopback, where the address family is in network byte order
+pub const DLT_LOOP: i32 = 108;
+
+/// Loopback/tunnel pseudo-header carrying only the payload's address family
+pub struct NullHeader<'a> {
+    data: &'a [u8],
+    family: u32,
+}
+
+#[derive(Debug)]
+pub enum NullError {
+    TooShort,
+    UnsupportedDatalink(i32),
+}
+
+impl fmt::Display for NullError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NullError::TooShort => write!(f, "Packet too short for loopback header"),
+            NullError::UnsupportedDatalink(d) => write!(f, "Datalink {} is not a loopback capture", d),
+        }
+    }
+}
+
+impl<'a> NullHeader<'a> {
+    pub fn parse(datalink: i32, data: &'a [u8]) -> Result<Self, NullError> {
+        let bytes: [u8; 4] = data.get(..4).ok_or(NullError::TooShort)?.try_into().unwrap_or_default();
+        let family = match datalink {
+            DLT_LOOP => u32::from_be_bytes(bytes),
+            // Files may come from a host of either byte order; families are small numbers
+            DLT_NULL => match u32::from_le_bytes(bytes) {
+                family if family > 0xFFFF => u32::from_be_bytes(bytes),
+                family => family,
+            },
+            d => return Err(NullError::UnsupportedDatalink(d)),
+        };
+        Ok(NullHeader { data, family })
+    }
+
+    pub fn address_family(&self) -> u32 {
+        self.family
+    }
+
+    /// EtherType equivalent of the address family, 0 when unknown
+    pub fn ether_type(&self) -> u16 {
+        match self.family {
+            2 => 0x0800,
+            // AF_INET6 differs per OS: Linux 10, NetBSD/OpenBSD 24, FreeBSD 28, macOS 30
+            10 | 24 | 28 | 30 => 0x86DD,
+            _ => 0,
+        }
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[4..]
+    }
+
+    pub fn get_family_name(&self) -> String {
+        match self.ether_type() {
+            0x0800 => "IPv4".to_string(),
+            0x86DD => "IPv6".to_string(),
+            _ => format!("Unknown ({})", self.family),
+        }
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        vec![ControlField {
+            name: "Address Family".to_string(),
+            value: self.get_family_name(),
+            description: format!("AF {}", self.family),
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_family_in_either_byte_order() {
+        let utun = [30, 0, 0, 0, 0x60];
+        let null = NullHeader::parse(DLT_NULL, &utun).unwrap();
+        assert_eq!((null.address_family(), null.ether_type()), (30, 0x86DD));
+        assert_eq!(null.payload(), &[0x60]);
+
+        let big_endian = [0, 0, 0, 2];
+        assert_eq!(NullHeader::parse(DLT_NULL, &big_endian).unwrap().ether_type(), 0x0800);
+        assert_eq!(NullHeader::parse(DLT_LOOP, &big_endian).unwrap().get_family_name(), "IPv4");
+        assert!(NullHeader::parse(DLT_NULL, &utun[..3]).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+use super::frame_control::ControlField;
+use super::summary::PacketSummary;
+use std::fmt;
+
+pub const OPC_UA_PORT: u16 = 4840;
+
+pub const SECURITY_POLICY_NONE: &str = "http://opcfoundation.org/UA/SecurityPolicy#None";
+
+/// OPC UA binary (UA TCP / UA Secure Conversation) message parser
+pub struct OpcUaMessage<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum OpcUaError {
+    TooShort,
+    UnknownType([u8; 3]),
+}
+
+impl fmt::Display for OpcUaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpcUaError::TooShort => write!(f, "Packet too short for OPC UA message header"),
+            OpcUaError::UnknownType(t) => write!(f, "Unknown OPC UA message type {}", String::from_utf8_lossy(t)),
+        }
+    }
+}
+
+/// Reads an OPC UA String or ByteString: an Int32 length (-1 for null) and the bytes
+fn read_string(data: &[u8]) -> Option<(Option<&[u8]>, &[u8])> {
+    let len = i32::from_le_bytes(data.get(..4)?.try_into().ok()?);
+    if len < 0 {
+        return Some((None, &data[4..]));
+    }
+    let value = data.get(4..4 + len as usize)?;
+    Some((Some(value), &data[4 + len as usize..]))
+}
+
+impl<'a> OpcUaMessage<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, OpcUaError> {
+        if data.len() < 8 {
+            return Err(OpcUaError::TooShort);
+        }
+        let message_type = [data[0], data[1], data[2]];
+        if !matches!(&message_type, b"HEL" | b"ACK" | b"ERR" | b"RHE" | b"OPN" | b"MSG" | b"CLO") {
+            return Err(OpcUaError::UnknownType(message_type));
+        }
+        let size = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        if size < 8 || data.len() < size {
+            return Err(OpcUaError::TooShort);
+        }
+        Ok(OpcUaMessage { data: &data[..size] })
+    }
+
+    /// Every complete message in a TCP segment
+    pub fn parse_all(mut data: &'a [u8]) -> Vec<Self> {
+        let mut messages = Vec::new();
+        while let Ok(message) = OpcUaMessage::parse(data) {
+            data = &data[message.data.len()..];
+            messages.push(message);
+        }
+        messages
+    }
+
+    pub fn message_type(&self) -> &'a [u8] {
+        &self.data[..3]
+    }
+
+    /// `F` final, `C` intermediate or `A` abort
+    pub fn chunk_type(&self) -> char {
+        self.data[3] as char
+    }
+
+    pub fn get_type_name(&self) -> String {
+        match self.message_type() {
+            b"HEL" => "Hello".to_string(),
+            b"ACK" => "Acknowledge".to_string(),
+            b"ERR" => "Error".to_string(),
+            b"RHE" => "Reverse Hello".to_string(),
+            b"OPN" => "Open Secure Channel".to_string(),
+            b"MSG" => "Message".to_string(),
+            b"CLO" => "Close Secure Channel".to_string(),
+            t => format!("Unknown ({})", String::from_utf8_lossy(t)),
+        }
+    }
+
+    fn body(&self) -> &'a [u8] {
+        &self.data[8..]
+    }
+
+    /// Endpoint URL requested by a Hello
+    pub fn endpoint_url(&self) -> Option<String> {
+        if self.message_type() != b"HEL" {
+            return None;
+        }
+        // Protocol version, buffer sizes, max message size and chunk count
+        let (url, _) = read_string(self.body().get(20..)?)?;
+        Some(String::from_utf8_lossy(url?).into_owned())
+    }
+
+    pub fn secure_channel_id(&self) -> Option<u32> {
+        if !matches!(self.message_type(), b"OPN" | b"MSG" | b"CLO") {
+            return None;
+        }
+        let id = self.body().get(..4)?;
+        Some(u32::from_le_bytes([id[0], id[1], id[2], id[3]]))
+    }
+
+    /// Security policy URI from the asymmetric security header of an OPN
+    pub fn security_policy(&self) -> Option<String> {
+        if self.message_type() != b"OPN" {
+            return None;
+        }
+        let (uri, _) = read_string(self.body().get(4..)?)?;
+        Some(String::from_utf8_lossy(uri?).into_owned())
+    }
+
+    /// Status code and reason of an ERR message
+    pub fn error(&self) -> Option<(u32, String)> {
+        if self.message_type() != b"ERR" {
+            return None;
+        }
+        let code = self.body().get(..4)?;
+        let code = u32::from_le_bytes([code[0], code[1], code[2], code[3]]);
+        let reason = read_string(&self.body()[4..]).and_then(|(reason, _)| reason);
+        Some((code, reason.map(|r| String::from_utf8_lossy(r).into_owned()).unwrap_or_default()))
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![ControlField {
+            name: "OPC UA Message".to_string(),
+            value: self.get_type_name(),
+            description: format!("Chunk {}, {} bytes", self.chunk_type(), self.data.len()),
+        }];
+        if let Some(url) = self.endpoint_url() {
+            fields.push(ControlField {
+                name: "OPC UA Endpoint".to_string(),
+                value: url,
+                description: "Endpoint URL".to_string(),
+            });
+        }
+        if let Some(id) = self.secure_channel_id() {
+            fields.push(ControlField {
+                name: "OPC UA Secure Channel".to_string(),
+                value: id.to_string(),
+                description: "Secure channel ID".to_string(),
+            });
+        }
+        if let Some(policy) = self.security_policy() {
+            fields.push(ControlField {
+                name: "OPC UA Security Policy".to_string(),
+                value: policy.rsplit('#').next().unwrap_or_default().to_string(),
+                description: policy,
+            });
+        }
+        if let Some((code, reason)) = self.error() {
+            fields.push(ControlField {
+                name: "OPC UA Error".to_string(),
+                value: format!("0x{:08x}", code),
+                description: reason,
+            });
+        }
+        fields
+    }
+}
+
+/// Warning text for a secure channel opened towards a server without security
+pub fn insecure_channel_alert(summary: &PacketSummary, data: &[u8]) -> Option<String> {
+    if !summary.is_tcp() || summary.dst_port != Some(OPC_UA_PORT) {
+        return None;
+    }
+    let messages = OpcUaMessage::parse_all(summary.payload(data));
+    messages.iter().find(|m| m.security_policy().as_deref() == Some(SECURITY_POLICY_NONE))?;
+    Some(format!("OPC UA secure channel with SecurityPolicy None: {} -> {}", summary.src_ip?, summary.dst_ip?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    fn message(message_type: &[u8; 3], body: &[u8]) -> Vec<u8> {
+        let mut data = message_type.to_vec();
+        data.push(b'F');
+        data.extend_from_slice(&(body.len() as u32 + 8).to_le_bytes());
+        data.extend_from_slice(body);
+        data
+    }
+
+    fn string(value: &str) -> Vec<u8> {
+        let mut data = (value.len() as i32).to_le_bytes().to_vec();
+        data.extend_from_slice(value.as_bytes());
+        data
+    }
+
+    fn open(policy: &str) -> Vec<u8> {
+        let mut body = vec![0, 0, 0, 0];
+        body.extend(string(policy));
+        body.extend_from_slice(&(-1i32).to_le_bytes());
+        body.extend_from_slice(&(-1i32).to_le_bytes());
+        message(b"OPN", &body)
+    }
+
+    #[test]
+    fn decodes_hello_and_open() {
+        let mut body = vec![0; 20];
+        body.extend(string("opc.tcp://plc.example.com:4840/UA"));
+        let mut data = message(b"HEL", &body);
+        data.extend(open(SECURITY_POLICY_NONE));
+        let messages = OpcUaMessage::parse_all(&data);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].endpoint_url().as_deref(), Some("opc.tcp://plc.example.com:4840/UA"));
+        let fields = messages[1].get_control_fields();
+        assert!(fields.iter().any(|f| f.name == "OPC UA Security Policy" && f.value == "None"));
+        assert!(matches!(OpcUaMessage::parse(b"GET / HTTP/1.1"), Err(OpcUaError::UnknownType(_))));
+    }
+
+    #[test]
+    fn alerts_on_security_policy_none() {
+        let frame = testutil::ipv4_tcp_frame(50000, OPC_UA_PORT, testutil::TCP_PSH, &open(SECURITY_POLICY_NONE));
+        let summary = PacketSummary::from_ethernet(&frame).unwrap();
+        assert!(insecure_channel_alert(&summary, &frame).unwrap().contains("SecurityPolicy None"));
+
+        let secure = open("http://opcfoundation.org/UA/SecurityPolicy#Basic256Sha256");
+        let frame = testutil::ipv4_tcp_frame(50000, OPC_UA_PORT, testutil::TCP_PSH, &secure);
+        assert!(insecure_channel_alert(&PacketSummary::from_ethernet(&frame).unwrap(), &frame).is_none());
+    }
+}
@@ -0,0 +1,254 @@
+use super::frame_control::ControlField;
+use std::fmt;
+use std::net::Ipv4Addr;
+
+pub const IP_PROTO_OSPF: u8 = 89;
+
+pub const OSPF_HELLO: u8 = 1;
+pub const OSPF_DB_DESCRIPTION: u8 = 2;
+pub const OSPF_LS_REQUEST: u8 = 3;
+pub const OSPF_LS_UPDATE: u8 = 4;
+pub const OSPF_LS_ACK: u8 = 5;
+
+/// Offset of the designated router in a Hello body, after the mask, interval,
+/// options, priority and dead interval (v2) or the interface ID, priority,
+/// options and intervals (v3)
+const HELLO_DR: usize = 12;
+
+/// Header of one link state advertisement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LsaHeader {
+    pub age: u16,
+    pub lsa_type: u16,
+    pub link_state_id: Ipv4Addr,
+    pub advertising_router: Ipv4Addr,
+    pub sequence: u32,
+}
+
+/// OSPFv2 (RFC 2328) and OSPFv3 (RFC 5340) packet parser
+pub struct OspfPacket<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum OspfError {
+    TooShort,
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for OspfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OspfError::TooShort => write!(f, "Packet too short for OSPF header"),
+            OspfError::UnsupportedVersion(v) => write!(f, "Unsupported OSPF version {}", v),
+        }
+    }
+}
+
+fn ipv4_at(data: &[u8], at: usize) -> Option<Ipv4Addr> {
+    let octets: [u8; 4] = data.get(at..at + 4)?.try_into().ok()?;
+    Some(Ipv4Addr::from(octets))
+}
+
+impl<'a> OspfPacket<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, OspfError> {
+        let version = *data.first().ok_or(OspfError::TooShort)?;
+        let header_len = match version {
+            2 => 24,
+            3 => 16,
+            v => return Err(OspfError::UnsupportedVersion(v)),
+        };
+        if data.len() < header_len {
+            return Err(OspfError::TooShort);
+        }
+        let length = u16::from_be_bytes([data[2], data[3]]) as usize;
+        let end = length.clamp(header_len, data.len());
+        Ok(OspfPacket { data: &data[..end] })
+    }
+
+    pub fn version(&self) -> u8 {
+        self.data[0]
+    }
+
+    pub fn packet_type(&self) -> u8 {
+        self.data[1]
+    }
+
+    pub fn router_id(&self) -> Ipv4Addr {
+        Ipv4Addr::new(self.data[4], self.data[5], self.data[6], self.data[7])
+    }
+
+    pub fn area_id(&self) -> Ipv4Addr {
+        Ipv4Addr::new(self.data[8], self.data[9], self.data[10], self.data[11])
+    }
+
+    fn body(&self) -> &'a [u8] {
+        &self.data[if self.version() == 2 { 24 } else { 16 }..]
+    }
+
+    pub fn get_type_name(&self) -> String {
+        match self.packet_type() {
+            OSPF_HELLO => "Hello".to_string(),
+            OSPF_DB_DESCRIPTION => "DB Description".to_string(),
+            OSPF_LS_REQUEST => "LS Request".to_string(),
+            OSPF_LS_UPDATE => "LS Update".to_string(),
+            OSPF_LS_ACK => "LS Acknowledge".to_string(),
+            t => format!("Unknown ({})", t),
+        }
+    }
+
+    /// Router priority from a Hello
+    pub fn priority(&self) -> Option<u8> {
+        if self.packet_type() != OSPF_HELLO {
+            return None;
+        }
+        self.body().get(if self.version() == 2 { 7 } else { 4 }).copied()
+    }
+
+    /// Designated and backup designated router from a Hello
+    pub fn designated_routers(&self) -> Option<(Ipv4Addr, Ipv4Addr)> {
+        if self.packet_type() != OSPF_HELLO {
+            return None;
+        }
+        Some((ipv4_at(self.body(), HELLO_DR)?, ipv4_at(self.body(), HELLO_DR + 4)?))
+    }
+
+    /// Router IDs the sender has heard Hellos from on this link
+    pub fn neighbors(&self) -> Vec<Ipv4Addr> {
+        if self.packet_type() != OSPF_HELLO {
+            return Vec::new();
+        }
+        self.body()
+            .get(HELLO_DR + 8..)
+            .unwrap_or_default()
+            .chunks_exact(4)
+            .map(|id| Ipv4Addr::new(id[0], id[1], id[2], id[3]))
+            .collect()
+    }
+
+    /// LSA headers carried in an LS Update or LS Acknowledge
+    pub fn lsas(&self) -> Vec<LsaHeader> {
+        let (mut rest, count) = match self.packet_type() {
+            OSPF_LS_UPDATE => {
+                let body = self.body();
+                let Some(count) = body.get(..4) else { return Vec::new() };
+                (&body[4..], Some(u32::from_be_bytes([count[0], count[1], count[2], count[3]])))
+            }
+            OSPF_LS_ACK => (self.body(), None),
+            _ => return Vec::new(),
+        };
+        let mut lsas = Vec::new();
+        while rest.len() >= 20 && count.is_none_or(|c| (lsas.len() as u32) < c) {
+            let lsa_type = if self.version() == 2 {
+                rest[3] as u16
+            } else {
+                u16::from_be_bytes([rest[2], rest[3]])
+            };
+            lsas.push(LsaHeader {
+                age: u16::from_be_bytes([rest[0], rest[1]]),
+                lsa_type,
+                link_state_id: Ipv4Addr::new(rest[4], rest[5], rest[6], rest[7]),
+                advertising_router: Ipv4Addr::new(rest[8], rest[9], rest[10], rest[11]),
+                sequence: u32::from_be_bytes([rest[12], rest[13], rest[14], rest[15]]),
+            });
+            // Acknowledgements carry bare headers, updates the whole LSA
+            let len = if count.is_some() { u16::from_be_bytes([rest[18], rest[19]]) as usize } else { 20 };
+            if len < 20 || len > rest.len() {
+                break;
+            }
+            rest = &rest[len..];
+        }
+        lsas
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![
+            ControlField {
+                name: "OSPF Type".to_string(),
+                value: self.get_type_name(),
+                description: format!("OSPFv{}", self.version()),
+            },
+            ControlField {
+                name: "OSPF Router ID".to_string(),
+                value: self.router_id().to_string(),
+                description: format!("Area {}", self.area_id()),
+            },
+        ];
+        if let (Some(priority), Some((dr, bdr))) = (self.priority(), self.designated_routers()) {
+            fields.push(ControlField {
+                name: "OSPF Priority".to_string(),
+                value: priority.to_string(),
+                description: format!("DR {}, BDR {}", dr, bdr),
+            });
+            let neighbors = self.neighbors();
+            fields.push(ControlField {
+                name: "OSPF Neighbors".to_string(),
+                value: neighbors.len().to_string(),
+                description: neighbors.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(", "),
+            });
+        }
+        for lsa in self.lsas() {
+            fields.push(ControlField {
+                name: "OSPF LSA".to_string(),
+                value: lsa_type_name(self.version(), lsa.lsa_type),
+                description: format!(
+                    "ID {} from {}, seq 0x{:08x}, age {}",
+                    lsa.link_state_id, lsa.advertising_router, lsa.sequence, lsa.age
+                ),
+            });
+        }
+        fields
+    }
+}
+
+pub fn lsa_type_name(version: u8, lsa_type: u16) -> String {
+    match (version, lsa_type) {
+        (2, 1) | (3, 0x2001) => "Router-LSA".to_string(),
+        (2, 2) | (3, 0x2002) => "Network-LSA".to_string(),
+        (2, 3) => "Summary-LSA (network)".to_string(),
+        (2, 4) => "Summary-LSA (ASBR)".to_string(),
+        (3, 0x2003) => "Inter-Area-Prefix-LSA".to_string(),
+        (3, 0x2004) => "Inter-Area-Router-LSA".to_string(),
+        (2, 5) | (3, 0x4005) => "AS-External-LSA".to_string(),
+        (2, 7) | (3, 0x2007) => "NSSA-LSA".to_string(),
+        (3, 0x0008) => "Link-LSA".to_string(),
+        (3, 0x2009) => "Intra-Area-Prefix-LSA".to_string(),
+        (_, t) => format!("Unknown (0x{:x})", t),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn decodes_hello_neighbors() {
+        let hello = testutil::ospf_hello(Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::UNSPECIFIED, &[Ipv4Addr::new(2, 2, 2, 2)]);
+        let packet = OspfPacket::parse(&hello).unwrap();
+        assert_eq!(packet.get_type_name(), "Hello");
+        assert_eq!(packet.router_id(), Ipv4Addr::new(1, 1, 1, 1));
+        assert_eq!(packet.area_id(), Ipv4Addr::UNSPECIFIED);
+        assert_eq!(packet.priority(), Some(1));
+        assert_eq!(packet.neighbors(), vec![Ipv4Addr::new(2, 2, 2, 2)]);
+        assert!(packet.get_control_fields().iter().any(|f| f.name == "OSPF Neighbors" && f.description == "2.2.2.2"));
+        assert!(matches!(OspfPacket::parse(&[4, 1]), Err(OspfError::UnsupportedVersion(4))));
+    }
+
+    #[test]
+    fn decodes_ls_update_headers() {
+        let mut lsa = vec![0, 10, 0x22, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0x80, 0, 0, 1, 0, 0, 0, 24];
+        lsa.extend_from_slice(&[0; 4]);
+        let mut packet = vec![2, OSPF_LS_UPDATE, 0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0];
+        packet.extend_from_slice(&[0; 8]);
+        packet.extend_from_slice(&1u32.to_be_bytes());
+        packet.extend_from_slice(&lsa);
+        let length = packet.len() as u16;
+        packet[2..4].copy_from_slice(&length.to_be_bytes());
+
+        let lsas = OspfPacket::parse(&packet).unwrap().lsas();
+        assert_eq!(lsas.len(), 1);
+        assert_eq!(lsa_type_name(2, lsas[0].lsa_type), "Router-LSA");
+        assert_eq!(lsas[0].sequence, 0x80000001);
+    }
+}
@@ -0,0 +1,199 @@
+use super::frame_control::{ControlField, FrameControlInfo, ProtocolType};
+use super::ipv4::IPv4Packet;
+use super::ipv6::IPv6Packet;
+use std::fmt;
+
+pub const PPP_IPV4: u16 = 0x0021;
+pub const PPP_IPV6: u16 = 0x0057;
+pub const PPP_LCP: u16 = 0xC021;
+pub const PPP_PAP: u16 = 0xC023;
+pub const PPP_CHAP: u16 = 0xC223;
+pub const PPP_IPCP: u16 = 0x8021;
+pub const PPP_IPV6CP: u16 = 0x8057;
+
+/// PPP frame parser (RFC 1661), with or without the HDLC-like
+/// address/control bytes and with compressed protocol fields
+pub struct PppFrame<'a> {
+    protocol: u16,
+    payload: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum PppError {
+    TooShort,
+}
+
+impl fmt::Display for PppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PppError::TooShort => write!(f, "Packet too short for PPP frame"),
+        }
+    }
+}
+
+impl<'a> PppFrame<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, PppError> {
+        let data = data.strip_prefix(&[0xFF, 0x03]).unwrap_or(data);
+        let first = *data.first().ok_or(PppError::TooShort)?;
+        // Protocol numbers are odd in their low byte, so an odd first byte is a compressed field
+        if first & 0x01 != 0 {
+            return Ok(PppFrame { protocol: first as u16, payload: &data[1..] });
+        }
+        let second = *data.get(1).ok_or(PppError::TooShort)?;
+        Ok(PppFrame { protocol: u16::from_be_bytes([first, second]), payload: &data[2..] })
+    }
+
+    pub fn protocol(&self) -> u16 {
+        self.protocol
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    pub fn get_protocol_name(&self) -> String {
+        protocol_name(self.protocol)
+    }
+
+    /// Code of an LCP/NCP/PAP/CHAP packet
+    pub fn code(&self) -> Option<u8> {
+        match self.protocol {
+            0x8000..=0xFFFF => self.payload.first().copied(),
+            _ => None,
+        }
+    }
+
+    pub fn get_code_name(&self) -> Option<String> {
+        let code = self.code()?;
+        let name = match (self.protocol, code) {
+            (PPP_PAP, 1) => "Authenticate-Request",
+            (PPP_PAP, 2) => "Authenticate-Ack",
+            (PPP_PAP, 3) => "Authenticate-Nak",
+            (PPP_CHAP, 1) => "Challenge",
+            (PPP_CHAP, 2) => "Response",
+            (PPP_CHAP, 3) => "Success",
+            (PPP_CHAP, 4) => "Failure",
+            (PPP_PAP | PPP_CHAP, _) => return Some(format!("Unknown ({})", code)),
+            (_, 1) => "Configure-Request",
+            (_, 2) => "Configure-Ack",
+            (_, 3) => "Configure-Nak",
+            (_, 4) => "Configure-Reject",
+            (_, 5) => "Terminate-Request",
+            (_, 6) => "Terminate-Ack",
+            (_, 7) => "Code-Reject",
+            (_, 8) => "Protocol-Reject",
+            (_, 9) => "Echo-Request",
+            (_, 10) => "Echo-Reply",
+            (_, 11) => "Discard-Request",
+            _ => return Some(format!("Unknown ({})", code)),
+        };
+        Some(name.to_string())
+    }
+
+    /// Peer-ID of a PAP Authenticate-Request or Name of a CHAP Challenge/Response
+    pub fn peer_name(&self) -> Option<String> {
+        let p = self.payload;
+        let name = match (self.protocol, self.code()?) {
+            (PPP_PAP, 1) => {
+                let len = *p.get(4)? as usize;
+                p.get(5..5 + len)?
+            }
+            (PPP_CHAP, 1 | 2) => {
+                let end = (u16::from_be_bytes([*p.get(2)?, *p.get(3)?]) as usize).min(p.len());
+                let value_len = *p.get(4)? as usize;
+                p.get(5 + value_len..end)?
+            }
+            _ => return None,
+        };
+        Some(String::from_utf8_lossy(name).into_owned())
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![ControlField {
+            name: "PPP Protocol".to_string(),
+            value: format!("0x{:04x}", self.protocol),
+            description: self.get_protocol_name(),
+        }];
+        if let Some(code) = self.get_code_name() {
+            fields.push(ControlField {
+                name: "PPP Code".to_string(),
+                value: self.code().unwrap_or_default().to_string(),
+                description: code,
+            });
+        }
+        if let Some(name) = self.peer_name() {
+            fields.push(ControlField {
+                name: "PPP Peer".to_string(),
+                value: name,
+                description: "Name sent for authentication".to_string(),
+            });
+        }
+        let inner = match self.protocol {
+            PPP_IPV4 => IPv4Packet::parse(self.payload).map(|ip| ip.get_control_fields()).unwrap_or_default(),
+            PPP_IPV6 => IPv6Packet::parse(self.payload).map(|ip| ip.get_control_fields()).unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        fields.extend(inner.into_iter().map(|field| ControlField { name: format!("Inner {}", field.name), ..field }));
+        fields
+    }
+
+    pub fn get_frame_control(&self) -> FrameControlInfo {
+        FrameControlInfo {
+            protocol_type: ProtocolType::Other("PPP".to_string()),
+            control_fields: self.get_control_fields(),
+        }
+    }
+}
+
+pub fn protocol_name(protocol: u16) -> String {
+    match protocol {
+        PPP_IPV4 => "IPv4".to_string(),
+        PPP_IPV6 => "IPv6".to_string(),
+        0x002D => "Van Jacobson compressed TCP/IP".to_string(),
+        0x002F => "Van Jacobson uncompressed TCP/IP".to_string(),
+        0x00FD => "Compressed datagram".to_string(),
+        PPP_IPCP => "IPCP".to_string(),
+        PPP_IPV6CP => "IPV6CP".to_string(),
+        0x80FD => "CCP".to_string(),
+        PPP_LCP => "LCP".to_string(),
+        PPP_PAP => "PAP".to_string(),
+        0xC025 => "Link Quality Report".to_string(),
+        PPP_CHAP => "CHAP".to_string(),
+        0xC227 => "EAP".to_string(),
+        p => format!("Unknown (0x{:04x})", p),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn parses_full_and_compressed_protocol_fields() {
+        let lcp = [0xFF, 0x03, 0xC0, 0x21, 9, 1, 0, 8, 0, 0, 0, 0];
+        let frame = PppFrame::parse(&lcp).unwrap();
+        assert_eq!(frame.get_protocol_name(), "LCP");
+        assert_eq!(frame.get_code_name().as_deref(), Some("Echo-Request"));
+
+        let ip = testutil::ipv4(testutil::client_v4(), testutil::server_v4(), 17, &testutil::udp(1, 2, b""));
+        let mut compressed = vec![0x21];
+        compressed.extend_from_slice(&ip);
+        let frame = PppFrame::parse(&compressed).unwrap();
+        assert_eq!(frame.protocol(), PPP_IPV4);
+        let fields = frame.get_control_fields();
+        assert!(fields.iter().any(|f| f.name == "Inner Source IP" && f.value == testutil::client_v4().to_string()));
+        assert!(PppFrame::parse(&[0xFF, 0x03]).is_err());
+    }
+
+    #[test]
+    fn extracts_authentication_names() {
+        let pap = [0xC0, 0x23, 1, 1, 0, 14, 5, b'a', b'l', b'i', b'c', b'e', 3, b'p', b'w', b'd'];
+        assert_eq!(PppFrame::parse(&pap).unwrap().peer_name().as_deref(), Some("alice"));
+
+        let chap = [0xC2, 0x23, 2, 1, 0, 10, 2, 0xaa, 0xbb, b'b', b'o', b'b', b'!'];
+        let frame = PppFrame::parse(&chap).unwrap();
+        assert_eq!(frame.get_code_name().as_deref(), Some("Response"));
+        assert_eq!(frame.peer_name().as_deref(), Some("bob"));
+    }
+}
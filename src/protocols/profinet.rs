@@ -0,0 +1,282 @@
+use super::frame_control::ControlField;
+use super::summary::PacketSummary;
+use std::fmt;
+use std::net::Ipv4Addr;
+
+pub const ETHERTYPE_PROFINET: u16 = 0x8892;
+
+pub const DCP_GET: u8 = 3;
+pub const DCP_SET: u8 = 4;
+pub const DCP_IDENTIFY: u8 = 5;
+pub const DCP_HELLO: u8 = 6;
+
+const OPTION_IP: u8 = 1;
+const OPTION_DEVICE: u8 = 2;
+const OPTION_CONTROL: u8 = 5;
+
+/// PROFINET real-time frame (EtherType 0x8892), identified by its Frame ID
+pub struct ProfinetFrame<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum ProfinetError {
+    TooShort,
+}
+
+impl fmt::Display for ProfinetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfinetError::TooShort => write!(f, "Packet too short for PROFINET frame"),
+        }
+    }
+}
+
+impl<'a> ProfinetFrame<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, ProfinetError> {
+        if data.len() < 2 {
+            return Err(ProfinetError::TooShort);
+        }
+        Ok(ProfinetFrame { data })
+    }
+
+    pub fn frame_id(&self) -> u16 {
+        u16::from_be_bytes([self.data[0], self.data[1]])
+    }
+
+    pub fn get_frame_type_name(&self) -> String {
+        match self.frame_id() {
+            0x0100..=0x7FFF => "RT class 3 (isochronous)".to_string(),
+            0x8000..=0xBFFF => "RT class 1 (cyclic)".to_string(),
+            0xC000..=0xFBFF => "RT class UDP".to_string(),
+            0xFC01 => "Alarm high".to_string(),
+            0xFE01 => "Alarm low".to_string(),
+            0xFEFC => "DCP Hello".to_string(),
+            0xFEFD => "DCP Get/Set".to_string(),
+            0xFEFE => "DCP Identify request".to_string(),
+            0xFEFF => "DCP Identify response".to_string(),
+            id => format!("Unknown (0x{:04x})", id),
+        }
+    }
+
+    /// Discovery and basic Configuration Protocol PDU
+    pub fn dcp(&self) -> Option<DcpPacket<'a>> {
+        if !(0xFEFC..=0xFEFF).contains(&self.frame_id()) {
+            return None;
+        }
+        DcpPacket::parse(&self.data[2..])
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![ControlField {
+            name: "PROFINET Frame ID".to_string(),
+            value: format!("0x{:04x}", self.frame_id()),
+            description: self.get_frame_type_name(),
+        }];
+        if let Some(dcp) = self.dcp() {
+            fields.extend(dcp.get_control_fields());
+        }
+        fields
+    }
+}
+
+/// One DCP block, without its block info or qualifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DcpBlock<'a> {
+    pub option: u8,
+    pub suboption: u8,
+    pub data: &'a [u8],
+}
+
+impl DcpBlock<'_> {
+    pub fn name(&self) -> String {
+        block_name(self.option, self.suboption)
+    }
+
+    /// Human readable value for the blocks we understand
+    pub fn value(&self) -> String {
+        let d = self.data;
+        match (self.option, self.suboption) {
+            (OPTION_IP, 1) if d.len() == 6 => d.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":"),
+            (OPTION_IP, 2) if d.len() >= 12 => {
+                let ip = |at: usize| Ipv4Addr::new(d[at], d[at + 1], d[at + 2], d[at + 3]);
+                format!("{} mask {} gateway {}", ip(0), ip(4), ip(8))
+            }
+            (OPTION_DEVICE, 1 | 2 | 6) => String::from_utf8_lossy(d).trim_end_matches('\0').to_string(),
+            (OPTION_DEVICE, 3) if d.len() >= 4 => {
+                format!("vendor 0x{:04x} device 0x{:04x}", u16::from_be_bytes([d[0], d[1]]), u16::from_be_bytes([d[2], d[3]]))
+            }
+            _ => format!("{} bytes", d.len()),
+        }
+    }
+}
+
+/// DCP PDU following the Frame ID
+pub struct DcpPacket<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> DcpPacket<'a> {
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let length = u16::from_be_bytes([*data.get(8)?, *data.get(9)?]) as usize;
+        (data.len() >= 10 + length).then(|| DcpPacket { data: &data[..10 + length] })
+    }
+
+    pub fn service_id(&self) -> u8 {
+        self.data[0]
+    }
+
+    /// 0 for requests, 1 for successful responses
+    pub fn service_type(&self) -> u8 {
+        self.data[1]
+    }
+
+    pub fn is_request(&self) -> bool {
+        self.service_type() == 0
+    }
+
+    pub fn xid(&self) -> u32 {
+        u32::from_be_bytes([self.data[2], self.data[3], self.data[4], self.data[5]])
+    }
+
+    pub fn blocks(&self) -> Vec<DcpBlock<'a>> {
+        // Set requests carry a block qualifier, responses and Hellos a block info
+        let prefix = match (self.service_id(), self.is_request()) {
+            (DCP_GET, true) => return Vec::new(),
+            (DCP_SET, true) | (DCP_IDENTIFY | DCP_GET, false) | (DCP_HELLO, _) => 2,
+            _ => 0,
+        };
+        let mut blocks = Vec::new();
+        let mut rest = &self.data[10..];
+        while rest.len() >= 4 {
+            let len = u16::from_be_bytes([rest[2], rest[3]]) as usize;
+            let Some(value) = rest.get(4..4 + len) else { break };
+            blocks.push(DcpBlock { option: rest[0], suboption: rest[1], data: value.get(prefix..).unwrap_or_default() });
+            // Blocks are padded to an even length
+            rest = &rest[(4 + len + len % 2).min(rest.len())..];
+        }
+        blocks
+    }
+
+    /// Set request blocks that rename, readdress or reset the device
+    pub fn reconfiguring_blocks(&self) -> Vec<DcpBlock<'a>> {
+        if self.service_id() != DCP_SET || !self.is_request() {
+            return Vec::new();
+        }
+        self.blocks()
+            .into_iter()
+            .filter(|b| matches!((b.option, b.suboption), (OPTION_IP, 2) | (OPTION_DEVICE, 2) | (OPTION_CONTROL, 5 | 6)))
+            .collect()
+    }
+
+    pub fn get_service_name(&self) -> String {
+        let service = match self.service_id() {
+            DCP_GET => "Get",
+            DCP_SET => "Set",
+            DCP_IDENTIFY => "Identify",
+            DCP_HELLO => "Hello",
+            s => return format!("Unknown ({})", s),
+        };
+        match self.service_type() {
+            0 => format!("{} Request", service),
+            1 => format!("{} Response", service),
+            5 => format!("{} Response (not supported)", service),
+            t => format!("{} ({})", service, t),
+        }
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![ControlField {
+            name: "DCP Service".to_string(),
+            value: self.get_service_name(),
+            description: format!("Xid 0x{:08x}", self.xid()),
+        }];
+        fields.extend(self.blocks().into_iter().map(|block| ControlField {
+            name: format!("DCP {}", block.name()),
+            value: block.value(),
+            description: format!("Option {}/{}", block.option, block.suboption),
+        }));
+        fields
+    }
+}
+
+pub fn block_name(option: u8, suboption: u8) -> String {
+    match (option, suboption) {
+        (OPTION_IP, 1) => "MAC Address".to_string(),
+        (OPTION_IP, 2) => "IP Parameter".to_string(),
+        (OPTION_IP, 3) => "Full IP Suite".to_string(),
+        (OPTION_DEVICE, 1) => "Vendor".to_string(),
+        (OPTION_DEVICE, 2) => "Name of Station".to_string(),
+        (OPTION_DEVICE, 3) => "Device ID".to_string(),
+        (OPTION_DEVICE, 4) => "Device Role".to_string(),
+        (OPTION_DEVICE, 5) => "Device Options".to_string(),
+        (OPTION_DEVICE, 6) => "Alias Name".to_string(),
+        (3, _) => "DHCP".to_string(),
+        (OPTION_CONTROL, 1) => "Start Transaction".to_string(),
+        (OPTION_CONTROL, 2) => "End Transaction".to_string(),
+        (OPTION_CONTROL, 3) => "Signal".to_string(),
+        (OPTION_CONTROL, 4) => "Response".to_string(),
+        (OPTION_CONTROL, 5) => "Factory Reset".to_string(),
+        (OPTION_CONTROL, 6) => "Reset to Factory".to_string(),
+        (6, _) => "Device Initiative".to_string(),
+        (0xFF, 0xFF) => "All Selector".to_string(),
+        (o, s) => format!("Unknown ({}/{})", o, s),
+    }
+}
+
+/// Warning text for a DCP Set request that renames, readdresses or resets a device
+pub fn set_request_alert(summary: &PacketSummary, data: &[u8]) -> Option<String> {
+    if summary.ether_type != ETHERTYPE_PROFINET {
+        return None;
+    }
+    let dcp = ProfinetFrame::parse(summary.payload(data)).ok()?.dcp()?;
+    let changes = dcp.reconfiguring_blocks();
+    if changes.is_empty() {
+        return None;
+    }
+    let changes = changes.iter().map(|b| format!("{} = {}", b.name(), b.value())).collect::<Vec<_>>().join(", ");
+    Some(format!("PROFINET DCP Set from {} to {}: {}", summary.src_mac, summary.dst_mac, changes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    fn dcp_frame(frame_id: u16, service: u8, service_type: u8, blocks: &[u8]) -> Vec<u8> {
+        let mut pdu = frame_id.to_be_bytes().to_vec();
+        pdu.extend_from_slice(&[service, service_type, 0, 0, 0, 9, 0, 0]);
+        pdu.extend_from_slice(&(blocks.len() as u16).to_be_bytes());
+        pdu.extend_from_slice(blocks);
+        testutil::ethernet(testutil::SRC_MAC, testutil::DST_MAC, ETHERTYPE_PROFINET, &pdu)
+    }
+
+    #[test]
+    fn decodes_identify_response() {
+        let mut blocks = vec![OPTION_DEVICE, 2, 0, 9, 0, 0];
+        blocks.extend_from_slice(b"plc-1a2");
+        blocks.push(0);
+        blocks.extend_from_slice(&[OPTION_IP, 2, 0, 14, 0, 1, 192, 168, 0, 10, 255, 255, 255, 0, 192, 168, 0, 1]);
+        let frame = dcp_frame(0xFEFF, DCP_IDENTIFY, 1, &blocks);
+        let profinet = ProfinetFrame::parse(&frame[14..]).unwrap();
+        let dcp = profinet.dcp().unwrap();
+        assert_eq!(dcp.get_service_name(), "Identify Response");
+        let fields = profinet.get_control_fields();
+        assert!(fields.iter().any(|f| f.name == "DCP Name of Station" && f.value == "plc-1a2"));
+        assert!(fields.iter().any(|f| f.name == "DCP IP Parameter" && f.value == "192.168.0.10 mask 255.255.255.0 gateway 192.168.0.1"));
+        assert!(dcp.reconfiguring_blocks().is_empty());
+    }
+
+    #[test]
+    fn alerts_on_set_requests() {
+        let mut blocks = vec![OPTION_DEVICE, 2, 0, 6, 0, 1];
+        blocks.extend_from_slice(b"evil");
+        let frame = dcp_frame(0xFEFD, DCP_SET, 0, &blocks);
+        let summary = PacketSummary::from_ethernet(&frame).unwrap();
+        let alert = set_request_alert(&summary, &frame).unwrap();
+        assert!(alert.ends_with("Name of Station = evil"));
+
+        let identify = dcp_frame(0xFEFE, DCP_IDENTIFY, 0, &[0xFF, 0xFF, 0, 0]);
+        assert!(set_request_alert(&PacketSummary::from_ethernet(&identify).unwrap(), &identify).is_none());
+    }
+}
@@ -0,0 +1,798 @@
+use super::frame_control::ControlField;
+use super::tls::ClientHello;
+use crate::flow::FlowKey;
+use crate::keylog::CipherSuite;
+use aes::cipher::{BlockEncrypt, KeyInit};
+use aes::Aes128;
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::Aes128Gcm;
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::net::IpAddr;
+
+pub const QUIC_V1: u32 = 0x0000_0001;
+pub const QUIC_V2: u32 = 0x6b33_43cf;
+
+/// UDP port for DNS over QUIC (RFC 9250)
+pub const DOQ_PORT: u16 = 853;
+
+/// RFC 9001 section 5.2
+const INITIAL_SALT_V1: [u8; 20] = [
+    0x38, 0x76, 0x2c, 0xf7, 0xf5, 0x59, 0x34, 0xb3, 0x4d, 0x17, 0x9a, 0xe6, 0xa4, 0xc8, 0x0c, 0xad, 0xcc, 0xbb, 0x7f, 0x0a,
+];
+
+/// QUIC packet parser. Long headers are self-describing; short (1-RTT)
+/// headers need the destination connection ID length from the connection.
+pub struct QuicPacket<'a> {
+    data: &'a [u8],
+    packet_type: QuicPacketType,
+    version: Option<u32>,
+    dcid: &'a [u8],
+    scid: Option<&'a [u8]>,
+    token: Option<&'a [u8]>,
+    /// Offset of the protected packet number
+    pn_offset: usize,
+    /// Bytes this packet occupies in the datagram
+    length: usize,
+}
+
+#[derive(Debug)]
+pub enum QuicError {
+    TooShort,
+    NotQuic,
+    InvalidLength,
+    Decryption,
+}
+
+impl fmt::Display for QuicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuicError::TooShort => write!(f, "Packet too short for QUIC header"),
+            QuicError::NotQuic => write!(f, "Fixed bit not set, not a QUIC packet"),
+            QuicError::InvalidLength => write!(f, "QUIC length field exceeds datagram"),
+            QuicError::Decryption => write!(f, "Failed to remove QUIC Initial protection"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum QuicPacketType {
+    Initial,
+    ZeroRtt,
+    Handshake,
+    Retry,
+    VersionNegotiation,
+    OneRtt,
+}
+
+impl fmt::Display for QuicPacketType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            QuicPacketType::Initial => "Initial",
+            QuicPacketType::ZeroRtt => "0-RTT",
+            QuicPacketType::Handshake => "Handshake",
+            QuicPacketType::Retry => "Retry",
+            QuicPacketType::VersionNegotiation => "Version Negotiation",
+            QuicPacketType::OneRtt => "1-RTT",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Reads a QUIC variable-length integer, returning it and its encoded size
+pub fn read_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let first = *data.first()?;
+    let len = 1usize << (first >> 6);
+    let bytes = data.get(..len)?;
+    let mut value = (first & 0x3f) as u64;
+    for &b in &bytes[1..] {
+        value = (value << 8) | b as u64;
+    }
+    Some((value, len))
+}
+
+pub fn version_name(version: u32) -> String {
+    match version {
+        0 => "Negotiation".to_string(),
+        QUIC_V1 => "QUICv1".to_string(),
+        QUIC_V2 => "QUICv2".to_string(),
+        v if v >> 8 == 0xff0000 => format!("draft-{}", v & 0xff),
+        v => format!("0x{:08x}", v),
+    }
+}
+
+/// Encodes a QUIC variable-length integer in its shortest form
+pub fn encode_varint(value: u64) -> Vec<u8> {
+    match value {
+        0..=0x3f => vec![value as u8],
+        0x40..=0x3fff => ((value as u16) | 0x4000).to_be_bytes().to_vec(),
+        0x4000..=0x3fff_ffff => ((value as u32) | 0x8000_0000).to_be_bytes().to_vec(),
+        _ => (value | 0xc000_0000_0000_0000).to_be_bytes().to_vec(),
+    }
+}
+
+impl<'a> QuicPacket<'a> {
+    /// Parses a long-header packet at the start of `data`
+    pub fn parse(data: &'a [u8]) -> Result<Self, QuicError> {
+        if data.len() < 7 {
+            return Err(QuicError::TooShort);
+        }
+        if data[0] & 0x80 == 0 {
+            // Short headers can't be parsed without the connection ID length
+            return Err(QuicError::NotQuic);
+        }
+        let version = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+        let dcid_len = data[5] as usize;
+        let dcid = data.get(6..6 + dcid_len).ok_or(QuicError::TooShort)?;
+        let mut pos = 6 + dcid_len;
+        let scid_len = *data.get(pos).ok_or(QuicError::TooShort)? as usize;
+        let scid = data.get(pos + 1..pos + 1 + scid_len).ok_or(QuicError::TooShort)?;
+        pos += 1 + scid_len;
+
+        if version == 0 {
+            return Ok(QuicPacket {
+                data,
+                packet_type: QuicPacketType::VersionNegotiation,
+                version: Some(0),
+                dcid,
+                scid: Some(scid),
+                token: None,
+                pn_offset: data.len(),
+                length: data.len(),
+            });
+        }
+        if data[0] & 0x40 == 0 {
+            return Err(QuicError::NotQuic);
+        }
+
+        let bits = (data[0] >> 4) & 0x03;
+        let packet_type = match (version == QUIC_V2, bits) {
+            (false, 0) | (true, 1) => QuicPacketType::Initial,
+            (false, 1) | (true, 2) => QuicPacketType::ZeroRtt,
+            (false, 2) | (true, 3) => QuicPacketType::Handshake,
+            _ => QuicPacketType::Retry,
+        };
+        let mut packet = QuicPacket {
+            data,
+            packet_type,
+            version: Some(version),
+            dcid,
+            scid: Some(scid),
+            token: None,
+            pn_offset: data.len(),
+            length: data.len(),
+        };
+        if packet_type == QuicPacketType::Retry {
+            // Retry token runs to the 16-byte integrity tag at the end
+            packet.token = data.get(pos..data.len().saturating_sub(16));
+            return Ok(packet);
+        }
+        if packet_type == QuicPacketType::Initial {
+            let (token_len, size) = read_varint(&data[pos..]).ok_or(QuicError::TooShort)?;
+            pos += size;
+            let token = data.get(pos..pos + token_len as usize).ok_or(QuicError::InvalidLength)?;
+            packet.token = Some(token);
+            pos += token_len as usize;
+        }
+        let (length, size) = read_varint(data.get(pos..).ok_or(QuicError::TooShort)?).ok_or(QuicError::TooShort)?;
+        pos += size;
+        let end = pos.checked_add(length as usize).filter(|&end| end <= data.len()).ok_or(QuicError::InvalidLength)?;
+        packet.pn_offset = pos;
+        packet.length = end;
+        Ok(packet)
+    }
+
+    /// Parses a short-header (1-RTT) packet whose destination connection ID
+    /// is `dcid_len` bytes long
+    pub fn parse_short(data: &'a [u8], dcid_len: usize) -> Result<Self, QuicError> {
+        if data.len() < 1 + dcid_len {
+            return Err(QuicError::TooShort);
+        }
+        if data[0] & 0x80 != 0 || data[0] & 0x40 == 0 {
+            return Err(QuicError::NotQuic);
+        }
+        Ok(QuicPacket {
+            data,
+            packet_type: QuicPacketType::OneRtt,
+            version: None,
+            dcid: &data[1..1 + dcid_len],
+            scid: None,
+            token: None,
+            pn_offset: 1 + dcid_len,
+            length: data.len(),
+        })
+    }
+
+    /// Splits a UDP datagram into its coalesced packets. A trailing short
+    /// header packet is only returned when its connection ID length is known.
+    pub fn parse_datagram(data: &'a [u8], short_dcid_len: Option<usize>) -> Vec<QuicPacket<'a>> {
+        let mut packets = Vec::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            if rest[0] & 0x80 == 0 {
+                if let Some(packet) = short_dcid_len.and_then(|len| QuicPacket::parse_short(rest, len).ok()) {
+                    packets.push(packet);
+                }
+                break;
+            }
+            let Ok(packet) = QuicPacket::parse(rest) else { break };
+            rest = &rest[packet.length..];
+            packets.push(packet);
+        }
+        packets
+    }
+
+    pub fn packet_type(&self) -> QuicPacketType {
+        self.packet_type
+    }
+
+    pub fn version(&self) -> Option<u32> {
+        self.version
+    }
+
+    pub fn dcid(&self) -> &'a [u8] {
+        self.dcid
+    }
+
+    pub fn scid(&self) -> Option<&'a [u8]> {
+        self.scid
+    }
+
+    pub fn token(&self) -> Option<&'a [u8]> {
+        self.token
+    }
+
+    /// Bytes this packet occupies in its datagram
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// The latency spin bit, only present in short headers
+    pub fn spin_bit(&self) -> Option<bool> {
+        (self.packet_type == QuicPacketType::OneRtt).then_some(self.data[0] & 0x20 != 0)
+    }
+
+    /// Versions offered in a Version Negotiation packet
+    pub fn supported_versions(&self) -> Vec<u32> {
+        if self.packet_type != QuicPacketType::VersionNegotiation {
+            return Vec::new();
+        }
+        let start = 7 + self.dcid.len() + self.scid.map_or(0, <[u8]>::len);
+        self.data[start.min(self.data.len())..]
+            .chunks_exact(4)
+            .map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+            .collect()
+    }
+
+    pub fn get_version_name(&self) -> String {
+        self.version.map_or("-".to_string(), version_name)
+    }
+
+    /// Removes Initial packet protection from a client Initial, whose keys
+    /// derive from its own destination connection ID, returning the frames.
+    pub fn decrypt_initial(&self) -> Result<Vec<u8>, QuicError> {
+        if self.packet_type != QuicPacketType::Initial || self.version != Some(QUIC_V1) {
+            return Err(QuicError::Decryption);
+        }
+        InitialKeys::client(self.dcid).open(&self.data[..self.length], self.pn_offset)
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![
+            ControlField {
+                name: "QUIC Packet".to_string(),
+                value: self.packet_type.to_string(),
+                description: if self.version.is_some() { "Long header" } else { "Short header" }.to_string(),
+            },
+            ControlField {
+                name: "QUIC DCID".to_string(),
+                value: hex(self.dcid),
+                description: "Destination connection ID".to_string(),
+            },
+        ];
+        if self.version.is_some() {
+            fields.push(ControlField {
+                name: "QUIC Version".to_string(),
+                value: self.get_version_name(),
+                description: "Protocol version".to_string(),
+            });
+        }
+        if let Some(scid) = self.scid {
+            fields.push(ControlField {
+                name: "QUIC SCID".to_string(),
+                value: hex(scid),
+                description: "Source connection ID".to_string(),
+            });
+        }
+        if let Some(spin) = self.spin_bit() {
+            fields.push(ControlField {
+                name: "QUIC Spin Bit".to_string(),
+                value: (spin as u8).to_string(),
+                description: "Latency spin bit".to_string(),
+            });
+        }
+        fields
+    }
+}
+
+/// Lowercase hex without separators, as Wireshark shows connection IDs
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Initial packet keys (RFC 9001 section 5.2), which anyone who sees the
+/// client's first destination connection ID can derive
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitialKeys {
+    pub key: [u8; 16],
+    pub iv: [u8; 12],
+    pub hp: [u8; 16],
+}
+
+impl InitialKeys {
+    pub fn client(dcid: &[u8]) -> Self {
+        Self::derive(dcid, "client in")
+    }
+
+    pub fn server(dcid: &[u8]) -> Self {
+        Self::derive(dcid, "server in")
+    }
+
+    fn derive(dcid: &[u8], label: &str) -> Self {
+        let suite = CipherSuite::Aes128GcmSha256;
+        let (initial_secret, _) = Hkdf::<Sha256>::extract(Some(&INITIAL_SALT_V1), dcid);
+        let secret = suite.expand_label(&initial_secret, label, 32);
+        let mut keys = InitialKeys { key: [0; 16], iv: [0; 12], hp: [0; 16] };
+        keys.key.copy_from_slice(&suite.expand_label(&secret, "quic key", 16));
+        keys.iv.copy_from_slice(&suite.expand_label(&secret, "quic iv", 12));
+        keys.hp.copy_from_slice(&suite.expand_label(&secret, "quic hp", 16));
+        keys
+    }
+
+    /// AES header protection mask for the 16-byte sample
+    pub fn header_mask(&self, sample: &[u8]) -> [u8; 16] {
+        let mut block = aes::Block::clone_from_slice(&sample[..16]);
+        Aes128::new_from_slice(&self.hp).expect("16-byte key").encrypt_block(&mut block);
+        block.into()
+    }
+
+    pub fn nonce(&self, packet_number: u64) -> [u8; 12] {
+        let mut nonce = self.iv;
+        for (n, p) in nonce[4..].iter_mut().zip(packet_number.to_be_bytes()) {
+            *n ^= p;
+        }
+        nonce
+    }
+
+    /// Removes header protection and decrypts one packet
+    fn open(&self, packet: &[u8], pn_offset: usize) -> Result<Vec<u8>, QuicError> {
+        let sample = packet.get(pn_offset + 4..pn_offset + 20).ok_or(QuicError::TooShort)?;
+        let mask = self.header_mask(sample);
+        let mut header = packet.to_vec();
+        header[0] ^= mask[0] & if header[0] & 0x80 != 0 { 0x0f } else { 0x1f };
+        let pn_len = (header[0] & 0x03) as usize + 1;
+        let mut packet_number = 0u64;
+        for i in 0..pn_len {
+            header[pn_offset + i] ^= mask[1 + i];
+            packet_number = (packet_number << 8) | header[pn_offset + i] as u64;
+        }
+        let (aad, ciphertext) = header.split_at(pn_offset + pn_len);
+        Aes128Gcm::new_from_slice(&self.key)
+            .expect("16-byte key")
+            .decrypt(&self.nonce(packet_number).into(), Payload { msg: ciphertext, aad })
+            .map_err(|_| QuicError::Decryption)
+    }
+}
+
+/// Collects the CRYPTO frames `(offset, data)` from decrypted Initial or
+/// Handshake frames, stopping at the first frame type not allowed there.
+pub fn crypto_frames(frames: &[u8]) -> Vec<(u64, &[u8])> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    let varint = |pos: &mut usize| -> Option<u64> {
+        let (value, size) = read_varint(frames.get(*pos..)?)?;
+        *pos += size;
+        Some(value)
+    };
+    while pos < frames.len() {
+        let Some(frame_type) = varint(&mut pos) else { break };
+        match frame_type {
+            0x00 | 0x01 => {} // PADDING, PING
+            0x02 | 0x03 => {
+                // ACK: largest, delay, range count, first range, ranges, ECN counts
+                let parsed = (|| {
+                    varint(&mut pos)?;
+                    varint(&mut pos)?;
+                    let ranges = varint(&mut pos)?;
+                    varint(&mut pos)?;
+                    for _ in 0..ranges * 2 {
+                        varint(&mut pos)?;
+                    }
+                    if frame_type == 0x03 {
+                        for _ in 0..3 {
+                            varint(&mut pos)?;
+                        }
+                    }
+                    Some(())
+                })();
+                if parsed.is_none() {
+                    break;
+                }
+            }
+            0x06 => {
+                let (Some(offset), Some(len)) = (varint(&mut pos), varint(&mut pos)) else { break };
+                let Some(data) = frames.get(pos..pos + len as usize) else { break };
+                out.push((offset, data));
+                pos += len as usize;
+            }
+            _ => break,
+        }
+    }
+    out
+}
+
+/// Control fields for the QUIC packets in one UDP payload, including the
+/// server name and ALPN of a client Initial
+pub fn datagram_control_fields(payload: &[u8]) -> Vec<ControlField> {
+    let mut fields = Vec::new();
+    for packet in QuicPacket::parse_datagram(payload, Some(0)) {
+        if packet.packet_type() == QuicPacketType::OneRtt {
+            // Without the connection ID length only the first byte is meaningful
+            fields.push(ControlField {
+                name: "QUIC Packet".to_string(),
+                value: packet.packet_type().to_string(),
+                description: "Short header".to_string(),
+            });
+            fields.push(ControlField {
+                name: "QUIC Spin Bit".to_string(),
+                value: u8::from(packet.spin_bit() == Some(true)).to_string(),
+                description: "Latency spin bit".to_string(),
+            });
+            continue;
+        }
+        fields.extend(packet.get_control_fields());
+        let hello = packet.decrypt_initial().ok().and_then(|frames| {
+            let crypto = crypto_frames(&frames);
+            crypto.iter().find(|(offset, _)| *offset == 0).and_then(|(_, data)| ClientHello::parse(data))
+        });
+        if let Some(hello) = hello {
+            if let Some(name) = hello.server_name {
+                fields.push(ControlField {
+                    name: "QUIC SNI".to_string(),
+                    value: name,
+                    description: "TLS server name".to_string(),
+                });
+            }
+            if !hello.alpn.is_empty() {
+                fields.push(ControlField {
+                    name: "QUIC ALPN".to_string(),
+                    value: hello.alpn.join(","),
+                    description: "Offered application protocols".to_string(),
+                });
+            }
+        }
+    }
+    fields
+}
+
+/// What was learned about one QUIC connection (one UDP 5-tuple)
+#[derive(Debug, Clone)]
+pub struct QuicFlow {
+    pub key: FlowKey,
+    /// Sender of the first Initial, or the non-443 side when joined mid-connection
+    pub client: (IpAddr, u16),
+    pub version: Option<u32>,
+    /// Connection ID chosen by the client (its SCID)
+    pub client_cid: Option<Vec<u8>>,
+    /// Connection ID chosen by the server
+    pub server_cid: Option<Vec<u8>>,
+    /// Every destination connection ID seen; more than two suggests migration or rotation
+    pub connection_ids: BTreeSet<Vec<u8>>,
+    pub hello: Option<ClientHello>,
+    pub packets: BTreeMap<QuicPacketType, u64>,
+    /// Short-header (application data) bytes sent by each side
+    pub client_bytes: u64,
+    pub server_bytes: u64,
+    /// Spin bit transitions in client packets, roughly one per round trip
+    pub spin_flips: u64,
+    last_spin: Option<bool>,
+    /// CRYPTO data from client Initials, by offset, until the ClientHello parses
+    crypto: BTreeMap<u64, Vec<u8>>,
+}
+
+impl QuicFlow {
+    fn new(key: FlowKey, client: (IpAddr, u16)) -> Self {
+        QuicFlow {
+            key,
+            client,
+            version: None,
+            client_cid: None,
+            server_cid: None,
+            connection_ids: BTreeSet::new(),
+            hello: None,
+            packets: BTreeMap::new(),
+            client_bytes: 0,
+            server_bytes: 0,
+            spin_flips: 0,
+            last_spin: None,
+            crypto: BTreeMap::new(),
+        }
+    }
+
+    pub fn server(&self) -> (IpAddr, u16) {
+        if self.client == self.key.a { self.key.b } else { self.key.a }
+    }
+
+    /// Application protocol: from ALPN when the ClientHello was seen, otherwise
+    /// `doq?` for UDP 853 (DNS over QUIC), `h3?` for UDP 443 (where QUIC is
+    /// almost always HTTP/3) or plain `quic`
+    pub fn label(&self) -> String {
+        let alpn = self.hello.as_ref().map(|h| h.alpn.as_slice()).unwrap_or_default();
+        if alpn.iter().any(|a| a == "h3" || a.starts_with("h3-")) {
+            "h3".to_string()
+        } else if alpn.iter().any(|a| a == "doq" || a.starts_with("doq-")) {
+            "doq".to_string()
+        } else if let Some(first) = alpn.first() {
+            first.clone()
+        } else if self.server().1 == DOQ_PORT {
+            "doq?".to_string()
+        } else if self.server().1 == 443 {
+            "h3?".to_string()
+        } else {
+            "quic".to_string()
+        }
+    }
+
+    pub fn total_packets(&self) -> u64 {
+        self.packets.values().sum()
+    }
+
+    fn push(&mut self, from_client: bool, payload: &[u8]) {
+        // Short headers carry the connection ID chosen by the receiver
+        let short_dcid_len = if from_client { &self.server_cid } else { &self.client_cid }.as_ref().map(Vec::len);
+        let packets = QuicPacket::parse_datagram(payload, short_dcid_len.or(Some(0)));
+        for packet in &packets {
+            *self.packets.entry(packet.packet_type()).or_default() += 1;
+            if short_dcid_len.is_some() || packet.packet_type() != QuicPacketType::OneRtt {
+                self.connection_ids.insert(packet.dcid().to_vec());
+            }
+            match packet.packet_type() {
+                QuicPacketType::OneRtt => {
+                    if from_client {
+                        self.client_bytes += packet.length() as u64;
+                        let spin = packet.spin_bit();
+                        if self.last_spin.is_some() && spin != self.last_spin {
+                            self.spin_flips += 1;
+                        }
+                        self.last_spin = spin;
+                    } else {
+                        self.server_bytes += packet.length() as u64;
+                    }
+                }
+                QuicPacketType::VersionNegotiation => {}
+                _ => {
+                    if self.version.is_none() {
+                        self.version = packet.version();
+                    }
+                    let scid = packet.scid().map(<[u8]>::to_vec);
+                    if from_client {
+                        self.client_cid = self.client_cid.take().or(scid);
+                    } else {
+                        self.server_cid = self.server_cid.take().or(scid);
+                    }
+                }
+            }
+            if from_client && packet.packet_type() == QuicPacketType::Initial && self.hello.is_none() {
+                self.collect_hello(packet);
+            }
+        }
+    }
+
+    fn collect_hello(&mut self, packet: &QuicPacket) {
+        let Ok(frames) = packet.decrypt_initial() else { return };
+        for (offset, data) in crypto_frames(&frames) {
+            self.crypto.entry(offset).or_insert_with(|| data.to_vec());
+        }
+        // Reassemble from offset 0 until the ClientHello is complete
+        let mut handshake = Vec::new();
+        for (&offset, data) in &self.crypto {
+            if offset as usize > handshake.len() {
+                break;
+            }
+            let skip = handshake.len() - offset as usize;
+            handshake.extend_from_slice(data.get(skip..).unwrap_or_default());
+        }
+        let complete = handshake.len() >= 4
+            && handshake.len() >= 4 + u32::from_be_bytes([0, handshake[1], handshake[2], handshake[3]]) as usize;
+        if complete {
+            self.hello = ClientHello::parse(&handshake);
+            self.crypto.clear();
+        }
+    }
+}
+
+/// Follows QUIC connections across datagrams, keyed by UDP 5-tuple
+#[derive(Debug, Default)]
+pub struct QuicTracker {
+    flows: Vec<QuicFlow>,
+    by_key: HashMap<FlowKey, usize>,
+}
+
+impl QuicTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds one UDP datagram, returning whether it was taken as QUIC. New
+    /// connections start at a long header with a known version, or at any
+    /// QUIC-looking packet on port 443 or 853.
+    pub fn push(&mut self, src: (IpAddr, u16), dst: (IpAddr, u16), payload: &[u8]) -> bool {
+        let key = FlowKey::new(17, src, dst);
+        let index = match self.by_key.get(&key) {
+            Some(&index) => index,
+            None => {
+                let client = match QuicPacket::parse(payload) {
+                    Ok(packet) if packet.packet_type() == QuicPacketType::Initial && known_version(packet.version()) => src,
+                    _ if payload.first().is_some_and(|b| b & 0x40 != 0) && (is_quic_port(src.1) || is_quic_port(dst.1)) => {
+                        if is_quic_port(src.1) { dst } else { src }
+                    }
+                    _ => return false,
+                };
+                self.flows.push(QuicFlow::new(key, client));
+                self.by_key.insert(key, self.flows.len() - 1);
+                self.flows.len() - 1
+            }
+        };
+        let flow = &mut self.flows[index];
+        let from_client = flow.client == src;
+        flow.push(from_client, payload);
+        true
+    }
+
+    /// Connections in order of first appearance
+    pub fn flows(&self) -> &[QuicFlow] {
+        &self.flows
+    }
+}
+
+/// Well-known server ports for QUIC: HTTP/3 and DNS over QUIC
+fn is_quic_port(port: u16) -> bool {
+    port == 443 || port == DOQ_PORT
+}
+
+fn known_version(version: Option<u32>) -> bool {
+    matches!(version, Some(QUIC_V1 | QUIC_V2)) || version.is_some_and(|v| v >> 8 == 0xff0000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    fn from_hex(text: &str) -> Vec<u8> {
+        (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap()).collect()
+    }
+
+    #[test]
+    fn derives_rfc9001_initial_keys() {
+        // RFC 9001 appendix A.1
+        let keys = InitialKeys::client(&from_hex("8394c8f03e515708"));
+        assert_eq!(hex(&keys.key), "1f369613dd76d5467730efcbe3b1a22d");
+        assert_eq!(hex(&keys.iv), "fa044b2f42a3fd3b46fb255c");
+        assert_eq!(hex(&keys.hp), "9f50449e04a0e810283a1e9933adedd2");
+        let server = InitialKeys::server(&from_hex("8394c8f03e515708"));
+        assert_eq!(hex(&server.key), "cf3a5331653c364c88f0f379b6067e37");
+    }
+
+    #[test]
+    fn reads_varints() {
+        assert_eq!(read_varint(&[0x25]), Some((37, 1)));
+        assert_eq!(read_varint(&[0x7b, 0xbd]), Some((15293, 2)));
+        assert_eq!(read_varint(&[0x9d, 0x7f, 0x3e, 0x7d]), Some((494878333, 4)));
+        assert_eq!(read_varint(&[0x7b]), None);
+        for value in [0, 63, 64, 15293, 494878333, 151288809941952652] {
+            let encoded = encode_varint(value);
+            assert_eq!(read_varint(&encoded), Some((value, encoded.len())));
+        }
+    }
+
+    #[test]
+    fn decrypts_client_initial() {
+        let hello = testutil::client_hello("example.com", &["h3"]);
+        let datagram = testutil::quic_initial(&[0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08], &[0xaa; 4], 0, &hello);
+        assert!(datagram.len() >= 1200);
+
+        let packet = QuicPacket::parse(&datagram).unwrap();
+        assert_eq!(packet.packet_type(), QuicPacketType::Initial);
+        assert_eq!(packet.get_version_name(), "QUICv1");
+        assert_eq!(hex(packet.dcid()), "8394c8f03e515708");
+        assert_eq!(packet.scid(), Some(&[0xaa; 4][..]));
+        assert_eq!(packet.length(), datagram.len());
+
+        let frames = packet.decrypt_initial().unwrap();
+        let crypto = crypto_frames(&frames);
+        assert_eq!(crypto.len(), 1);
+        assert_eq!(crypto[0].0, 0);
+        let info = ClientHello::parse(crypto[0].1).unwrap();
+        assert_eq!(info.server_name.as_deref(), Some("example.com"));
+        assert_eq!(info.alpn, vec!["h3".to_string()]);
+    }
+
+    #[test]
+    fn parses_short_header_and_coalesced_packets() {
+        let short = [0x60, 1, 2, 3, 4, 0xde, 0xad];
+        let packet = QuicPacket::parse_short(&short, 4).unwrap();
+        assert_eq!(packet.packet_type(), QuicPacketType::OneRtt);
+        assert_eq!(packet.spin_bit(), Some(true));
+        assert_eq!(packet.dcid(), &[1, 2, 3, 4]);
+        assert!(QuicPacket::parse(&short).is_err());
+
+        // Handshake packet (5-byte body) followed by the short packet
+        let mut datagram = vec![0xe0, 0, 0, 0, 1, 4, 1, 2, 3, 4, 0, 5, 0, 0, 0, 0, 0];
+        datagram.extend_from_slice(&short);
+        let packets = QuicPacket::parse_datagram(&datagram, Some(4));
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].packet_type(), QuicPacketType::Handshake);
+        assert_eq!(packets[0].scid(), Some(&[][..]));
+        assert_eq!(packets[1].packet_type(), QuicPacketType::OneRtt);
+        assert_eq!(QuicPacket::parse_datagram(&datagram, None).len(), 1);
+    }
+
+    #[test]
+    fn tracks_connections_and_labels_h3() {
+        use std::net::Ipv4Addr;
+        let client = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 50000);
+        let server = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 443);
+        let client_cid = [0xc1, 0x1e, 0x47, 0x00];
+        let initial = testutil::quic_initial(&[7; 8], &client_cid, 0, &testutil::client_hello("cdn.example.com", &["h3"]));
+
+        let mut tracker = QuicTracker::new();
+        assert!(tracker.push(client, server, &initial));
+        // Server Handshake choosing a 2-byte connection ID
+        assert!(tracker.push(server, client, &[0xe0, 0, 0, 0, 1, 4, 0xc1, 0x1e, 0x47, 0x00, 2, 0x5e, 0x5e, 1, 0]));
+        for (i, spin) in [0x40u8, 0x60, 0x60, 0x40].iter().enumerate() {
+            assert!(tracker.push(client, server, &[*spin, 0x5e, 0x5e, i as u8, 0, 0]));
+        }
+        assert!(tracker.push(server, client, &[0x40, 0xc1, 0x1e, 0x47, 0x00, 9, 9, 9]));
+        assert!(!tracker.push(client, (server.0, 5353), b"\x00not quic"));
+
+        let flows = tracker.flows();
+        assert_eq!(flows.len(), 1);
+        let flow = &flows[0];
+        assert_eq!(flow.label(), "h3");
+        assert_eq!(flow.client, client);
+        assert_eq!(flow.hello.as_ref().unwrap().server_name.as_deref(), Some("cdn.example.com"));
+        assert_eq!(flow.server_cid.as_deref(), Some(&[0x5e, 0x5e][..]));
+        assert_eq!(flow.packets[&QuicPacketType::OneRtt], 5);
+        assert_eq!((flow.client_bytes, flow.server_bytes), (24, 8));
+        assert_eq!(flow.spin_flips, 2);
+        assert!(flow.connection_ids.contains(&vec![0x5e, 0x5e]));
+    }
+
+    #[test]
+    fn labels_mid_connection_flows_by_port() {
+        use std::net::Ipv4Addr;
+        let client = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 50000);
+        let server = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 443);
+        let mut tracker = QuicTracker::new();
+        assert!(tracker.push(server, client, &[0x41, 1, 2, 3]));
+        assert_eq!(tracker.flows()[0].client, client);
+        assert_eq!(tracker.flows()[0].label(), "h3?");
+        assert_eq!(tracker.flows()[0].server_bytes, 4);
+
+        let resolver = (server.0, DOQ_PORT);
+        assert!(tracker.push(client, resolver, &[0x41, 1, 2, 3]));
+        assert_eq!(tracker.flows()[1].label(), "doq?");
+    }
+
+    #[test]
+    fn parses_version_negotiation() {
+        let data = [0x80, 0, 0, 0, 0, 1, 9, 1, 8, 0, 0, 0, 1, 0x6b, 0x33, 0x43, 0xcf];
+        let packet = QuicPacket::parse(&data).unwrap();
+        assert_eq!(packet.packet_type(), QuicPacketType::VersionNegotiation);
+        assert_eq!(packet.supported_versions(), vec![QUIC_V1, QUIC_V2]);
+        assert!(matches!(QuicPacket::parse(&[0x80, 0, 0]), Err(QuicError::TooShort)));
+    }
+}
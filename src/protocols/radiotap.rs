@@ -0,0 +1,205 @@
+use super::frame_control::ControlField;
+use std::fmt;
+
+/// Radiotap header parser (monitor-mode capture metadata preceding 802.11 frames)
+pub struct RadiotapHeader<'a> {
+    data: &'a [u8],
+    length: usize,
+    flags: Option<u8>,
+    rate: Option<u8>,
+    channel_freq: Option<u16>,
+    signal_dbm: Option<i8>,
+    noise_dbm: Option<i8>,
+}
+
+#[derive(Debug)]
+pub enum RadiotapError {
+    TooShort,
+    InvalidVersion,
+    InvalidLength,
+}
+
+impl fmt::Display for RadiotapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RadiotapError::TooShort => write!(f, "Packet too short for radiotap header"),
+            RadiotapError::InvalidVersion => write!(f, "Invalid radiotap version"),
+            RadiotapError::InvalidLength => write!(f, "Invalid radiotap header length"),
+        }
+    }
+}
+
+/// (alignment, size) of the radiotap fields we know, indexed by present bit
+const FIELDS: [(usize, usize); 14] = [
+    (8, 8), // TSFT
+    (1, 1), // Flags
+    (1, 1), // Rate
+    (2, 4), // Channel
+    (2, 2), // FHSS
+    (1, 1), // dBm antenna signal
+    (1, 1), // dBm antenna noise
+    (2, 2), // Lock quality
+    (2, 2), // TX attenuation
+    (2, 2), // dB TX attenuation
+    (1, 1), // dBm TX power
+    (1, 1), // Antenna
+    (1, 1), // dB antenna signal
+    (1, 1), // dB antenna noise
+];
+
+impl<'a> RadiotapHeader<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, RadiotapError> {
+        if data.len() < 8 {
+            return Err(RadiotapError::TooShort);
+        }
+        if data[0] != 0 {
+            return Err(RadiotapError::InvalidVersion);
+        }
+        let length = u16::from_le_bytes([data[2], data[3]]) as usize;
+        if length < 8 || length > data.len() {
+            return Err(RadiotapError::InvalidLength);
+        }
+
+        let mut header = RadiotapHeader {
+            data,
+            length,
+            flags: None,
+            rate: None,
+            channel_freq: None,
+            signal_dbm: None,
+            noise_dbm: None,
+        };
+
+        // Skip any extended present bitmaps (bit 31 set)
+        let present = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+        let mut offset = 8;
+        let mut word = present;
+        while word & 0x8000_0000 != 0 && offset + 4 <= length {
+            word = u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+            offset += 4;
+        }
+
+        for (bit, (align, size)) in FIELDS.iter().enumerate() {
+            if present & (1 << bit) == 0 {
+                continue;
+            }
+            offset = offset.div_ceil(*align) * align;
+            if offset + size > length {
+                break;
+            }
+            let field = &data[offset..offset + size];
+            match bit {
+                1 => header.flags = Some(field[0]),
+                2 => header.rate = Some(field[0]),
+                3 => header.channel_freq = Some(u16::from_le_bytes([field[0], field[1]])),
+                5 => header.signal_dbm = Some(field[0] as i8),
+                6 => header.noise_dbm = Some(field[0] as i8),
+                _ => {}
+            }
+            offset += size;
+        }
+        Ok(header)
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// The 802.11 frame following the header, without the FCS when one is flagged
+    pub fn payload(&self) -> &'a [u8] {
+        let end = if self.has_fcs() && self.data.len() >= self.length + 4 {
+            self.data.len() - 4
+        } else {
+            self.data.len()
+        };
+        &self.data[self.length..end]
+    }
+
+    pub fn has_fcs(&self) -> bool {
+        self.flags.is_some_and(|f| f & 0x10 != 0)
+    }
+
+    pub fn channel_freq(&self) -> Option<u16> {
+        self.channel_freq
+    }
+
+    pub fn signal_dbm(&self) -> Option<i8> {
+        self.signal_dbm
+    }
+
+    /// Converts a centre frequency in MHz to an 802.11 channel number
+    pub fn channel_number(&self) -> Option<u16> {
+        match self.channel_freq? {
+            2484 => Some(14),
+            f @ 2412..=2472 => Some((f - 2407) / 5),
+            f @ 5000..=5895 => Some((f - 5000) / 5),
+            f @ 5955..=7115 => Some((f - 5950) / 5),
+            _ => None,
+        }
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = Vec::new();
+        if let Some(freq) = self.channel_freq {
+            fields.push(ControlField {
+                name: "Channel".to_string(),
+                value: self.channel_number().map(|c| c.to_string()).unwrap_or_else(|| "?".to_string()),
+                description: format!("{} MHz", freq),
+            });
+        }
+        if let Some(rate) = self.rate {
+            fields.push(ControlField {
+                name: "Data Rate".to_string(),
+                value: format!("{:.1}", rate as f32 / 2.0),
+                description: "Mb/s".to_string(),
+            });
+        }
+        if let Some(signal) = self.signal_dbm {
+            fields.push(ControlField {
+                name: "Signal".to_string(),
+                value: signal.to_string(),
+                description: "Antenna signal in dBm".to_string(),
+            });
+        }
+        if let Some(noise) = self.noise_dbm {
+            fields.push(ControlField {
+                name: "Noise".to_string(),
+                value: noise.to_string(),
+                description: "Antenna noise in dBm".to_string(),
+            });
+        }
+        fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_common_fields() {
+        // present: flags, rate, channel, dBm signal
+        let mut data = vec![0, 0, 16, 0, 0x2e, 0, 0, 0];
+        data.extend_from_slice(&[0x10, 0x02]); // flags (FCS), rate 1 Mb/s
+        data.extend_from_slice(&2437u16.to_le_bytes());
+        data.extend_from_slice(&[0xa0, 0x00]); // channel flags
+        data.extend_from_slice(&[(-42i8) as u8, 0]); // signal, padding
+        data.extend_from_slice(&[0x80, 0x00, 0, 0]); // beacon start
+        data.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]); // FCS
+
+        let header = RadiotapHeader::parse(&data).unwrap();
+        assert_eq!(header.length(), 16);
+        assert_eq!(header.channel_number(), Some(6));
+        assert_eq!(header.signal_dbm(), Some(-42));
+        assert!(header.has_fcs());
+        assert_eq!(header.payload(), &[0x80, 0x00, 0, 0]);
+        assert_eq!(header.get_control_fields().len(), 3);
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        assert!(matches!(RadiotapHeader::parse(&[0; 4]), Err(RadiotapError::TooShort)));
+        assert!(matches!(RadiotapHeader::parse(&[1, 0, 8, 0, 0, 0, 0, 0]), Err(RadiotapError::InvalidVersion)));
+        assert!(matches!(RadiotapHeader::parse(&[0, 0, 64, 0, 0, 0, 0, 0]), Err(RadiotapError::InvalidLength)));
+    }
+}
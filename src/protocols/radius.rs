@@ -0,0 +1,226 @@
+use super::frame_control::ControlField;
+use super::quic::hex;
+use std::fmt;
+use std::net::Ipv4Addr;
+
+pub const RADIUS_AUTH_PORT: u16 = 1812;
+pub const RADIUS_ACCT_PORT: u16 = 1813;
+
+pub const ACCESS_REQUEST: u8 = 1;
+pub const ACCESS_ACCEPT: u8 = 2;
+pub const ACCESS_REJECT: u8 = 3;
+pub const ACCOUNTING_REQUEST: u8 = 4;
+pub const ACCOUNTING_RESPONSE: u8 = 5;
+pub const ACCESS_CHALLENGE: u8 = 11;
+
+pub const ATTR_USER_NAME: u8 = 1;
+pub const ATTR_USER_PASSWORD: u8 = 2;
+pub const ATTR_NAS_IP_ADDRESS: u8 = 4;
+pub const ATTR_FRAMED_IP_ADDRESS: u8 = 8;
+pub const ATTR_REPLY_MESSAGE: u8 = 18;
+pub const ATTR_CALLING_STATION_ID: u8 = 31;
+pub const ATTR_NAS_IDENTIFIER: u8 = 32;
+
+/// RADIUS packet parser (RFC 2865, accounting per RFC 2866)
+pub struct RadiusPacket<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum RadiusError {
+    TooShort,
+    InvalidLength(u16),
+}
+
+impl fmt::Display for RadiusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RadiusError::TooShort => write!(f, "Packet too short for RADIUS header"),
+            RadiusError::InvalidLength(len) => write!(f, "Invalid RADIUS length {}", len),
+        }
+    }
+}
+
+impl<'a> RadiusPacket<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, RadiusError> {
+        if data.len() < 20 {
+            return Err(RadiusError::TooShort);
+        }
+        let length = u16::from_be_bytes([data[2], data[3]]);
+        if !(20..=4096).contains(&length) || length as usize > data.len() {
+            return Err(RadiusError::InvalidLength(length));
+        }
+        // Octets past the length field are padding
+        Ok(RadiusPacket { data: &data[..length as usize] })
+    }
+
+    pub fn code(&self) -> u8 {
+        self.data[0]
+    }
+
+    pub fn identifier(&self) -> u8 {
+        self.data[1]
+    }
+
+    pub fn authenticator(&self) -> &'a [u8] {
+        &self.data[4..20]
+    }
+
+    pub fn get_code_name(&self) -> String {
+        code_name(self.code())
+    }
+
+    /// Attributes as (type, value) pairs, stopping at the first malformed one
+    pub fn attributes(&self) -> Vec<(u8, &'a [u8])> {
+        let mut attributes = Vec::new();
+        let mut rest = &self.data[20..];
+        while let [attr_type, len, ..] = *rest {
+            let len = len as usize;
+            if len < 2 || len > rest.len() {
+                break;
+            }
+            attributes.push((attr_type, &rest[2..len]));
+            rest = &rest[len..];
+        }
+        attributes
+    }
+
+    pub fn attribute(&self, attr_type: u8) -> Option<&'a [u8]> {
+        self.attributes().into_iter().find(|(t, _)| *t == attr_type).map(|(_, value)| value)
+    }
+
+    pub fn user_name(&self) -> Option<String> {
+        self.attribute(ATTR_USER_NAME).map(|v| String::from_utf8_lossy(v).into_owned())
+    }
+
+    pub fn nas_ip(&self) -> Option<Ipv4Addr> {
+        self.attribute(ATTR_NAS_IP_ADDRESS).and_then(ipv4)
+    }
+
+    pub fn nas_identifier(&self) -> Option<String> {
+        self.attribute(ATTR_NAS_IDENTIFIER).map(|v| String::from_utf8_lossy(v).into_owned())
+    }
+
+    pub fn framed_ip(&self) -> Option<Ipv4Addr> {
+        self.attribute(ATTR_FRAMED_IP_ADDRESS).and_then(ipv4)
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![ControlField {
+            name: "RADIUS Code".to_string(),
+            value: self.code().to_string(),
+            description: format!("{} (ID {})", self.get_code_name(), self.identifier()),
+        }];
+        for (attr_type, value) in self.attributes() {
+            let value = match attr_type {
+                // Obfuscated with the shared secret; the content is never shown
+                ATTR_USER_PASSWORD => format!("<{} bytes hidden>", value.len()),
+                ATTR_NAS_IP_ADDRESS | ATTR_FRAMED_IP_ADDRESS => match ipv4(value) {
+                    Some(addr) => addr.to_string(),
+                    None => hex(value),
+                },
+                ATTR_USER_NAME | ATTR_REPLY_MESSAGE | ATTR_CALLING_STATION_ID | ATTR_NAS_IDENTIFIER => {
+                    String::from_utf8_lossy(value).into_owned()
+                }
+                _ => hex(value),
+            };
+            fields.push(ControlField {
+                name: "RADIUS Attribute".to_string(),
+                value,
+                description: attribute_name(attr_type),
+            });
+        }
+        fields
+    }
+}
+
+fn ipv4(value: &[u8]) -> Option<Ipv4Addr> {
+    let octets: [u8; 4] = value.try_into().ok()?;
+    Some(Ipv4Addr::from(octets))
+}
+
+pub fn code_name(code: u8) -> String {
+    match code {
+        ACCESS_REQUEST => "Access-Request".to_string(),
+        ACCESS_ACCEPT => "Access-Accept".to_string(),
+        ACCESS_REJECT => "Access-Reject".to_string(),
+        ACCOUNTING_REQUEST => "Accounting-Request".to_string(),
+        ACCOUNTING_RESPONSE => "Accounting-Response".to_string(),
+        ACCESS_CHALLENGE => "Access-Challenge".to_string(),
+        12 => "Status-Server".to_string(),
+        13 => "Status-Client".to_string(),
+        40 => "Disconnect-Request".to_string(),
+        41 => "Disconnect-ACK".to_string(),
+        42 => "Disconnect-NAK".to_string(),
+        43 => "CoA-Request".to_string(),
+        44 => "CoA-ACK".to_string(),
+        45 => "CoA-NAK".to_string(),
+        c => format!("Unknown ({})", c),
+    }
+}
+
+pub fn attribute_name(attr_type: u8) -> String {
+    match attr_type {
+        ATTR_USER_NAME => "User-Name".to_string(),
+        ATTR_USER_PASSWORD => "User-Password".to_string(),
+        3 => "CHAP-Password".to_string(),
+        ATTR_NAS_IP_ADDRESS => "NAS-IP-Address".to_string(),
+        5 => "NAS-Port".to_string(),
+        6 => "Service-Type".to_string(),
+        7 => "Framed-Protocol".to_string(),
+        ATTR_FRAMED_IP_ADDRESS => "Framed-IP-Address".to_string(),
+        11 => "Filter-Id".to_string(),
+        12 => "Framed-MTU".to_string(),
+        ATTR_REPLY_MESSAGE => "Reply-Message".to_string(),
+        24 => "State".to_string(),
+        25 => "Class".to_string(),
+        26 => "Vendor-Specific".to_string(),
+        27 => "Session-Timeout".to_string(),
+        30 => "Called-Station-Id".to_string(),
+        ATTR_CALLING_STATION_ID => "Calling-Station-Id".to_string(),
+        ATTR_NAS_IDENTIFIER => "NAS-Identifier".to_string(),
+        40 => "Acct-Status-Type".to_string(),
+        44 => "Acct-Session-Id".to_string(),
+        61 => "NAS-Port-Type".to_string(),
+        79 => "EAP-Message".to_string(),
+        80 => "Message-Authenticator".to_string(),
+        t => format!("Unknown ({})", t),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn decodes_access_request_attributes() {
+        let data = testutil::radius_packet(
+            ACCESS_REQUEST,
+            7,
+            &[
+                (ATTR_USER_NAME, b"alice".as_slice()),
+                (ATTR_USER_PASSWORD, &[0x5a; 16]),
+                (ATTR_NAS_IP_ADDRESS, &[10, 0, 0, 1]),
+                (ATTR_FRAMED_IP_ADDRESS, &[10, 9, 8, 7]),
+            ],
+        );
+        let packet = RadiusPacket::parse(&data).unwrap();
+        assert_eq!(packet.get_code_name(), "Access-Request");
+        assert_eq!(packet.identifier(), 7);
+        assert_eq!(packet.user_name().as_deref(), Some("alice"));
+        assert_eq!(packet.nas_ip(), Some(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(packet.framed_ip(), Some(Ipv4Addr::new(10, 9, 8, 7)));
+        let fields = packet.get_control_fields();
+        assert!(fields.iter().any(|f| f.description == "User-Password" && f.value == "<16 bytes hidden>"));
+    }
+
+    #[test]
+    fn rejects_bad_lengths() {
+        let mut data = testutil::radius_packet(ACCESS_ACCEPT, 1, &[]);
+        assert!(RadiusPacket::parse(&data).unwrap().attributes().is_empty());
+        data[3] = 200;
+        assert!(matches!(RadiusPacket::parse(&data), Err(RadiusError::InvalidLength(200))));
+        assert!(RadiusPacket::parse(&data[..10]).is_err());
+    }
+}
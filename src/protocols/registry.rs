@@ -0,0 +1,295 @@
+use super::frame_control::ControlField;
+use super::summary::PacketSummary;
+use super::{
+    arp, bacnet, detect, diameter, dns, eapol, enip, gtp, hsrp, http, icmp, iec104, ipv4, ipv6, l2tp, ldap, name_resolution, opcua, ospf,
+    profinet, quic, radius, sctp, someip, tacacs, tcp, tls, tunnel, vrrp,
+};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Decodes the payload after a link-layer header
+pub type NetworkDissector = fn(payload: &[u8]) -> Vec<ControlField>;
+
+/// Decodes a packet from its summary and the buffer the summary's offsets refer to
+pub type PacketDissector = fn(summary: &PacketSummary, data: &[u8]) -> Vec<ControlField>;
+
+/// What a packet dissector is registered for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    IpProto(u8),
+    TcpPort(u16),
+    UdpPort(u16),
+    /// Tried on TCP or UDP payloads that no port dissector decoded; the
+    /// dissector must recognize the payload itself and return nothing otherwise
+    TcpHeuristic,
+    UdpHeuristic,
+}
+
+#[derive(Clone, Copy)]
+pub struct Entry<F> {
+    pub name: &'static str,
+    pub dissect: F,
+}
+
+/// Dissectors by the layer and value they attach to. Dispatch walks the
+/// layers: the EtherType selects the network dissector, then the IP protocol,
+/// the TCP/UDP ports (lower port first) and finally the heuristics select the
+/// application dissector. Within a binding, dissectors are tried in
+/// registration order until one returns fields.
+#[derive(Default)]
+pub struct DissectorTable {
+    ether_types: HashMap<u16, Vec<Entry<NetworkDissector>>>,
+    packets: HashMap<Binding, Vec<Entry<PacketDissector>>>,
+}
+
+impl DissectorTable {
+    pub fn bind_ether_type(&mut self, ether_type: u16, name: &'static str, dissect: NetworkDissector) {
+        self.ether_types.entry(ether_type).or_default().push(Entry { name, dissect });
+    }
+
+    pub fn bind(&mut self, binding: Binding, name: &'static str, dissect: PacketDissector) {
+        self.packets.entry(binding).or_default().push(Entry { name, dissect });
+    }
+
+    /// Every registered binding with the dissector names, sorted
+    pub fn bindings(&self) -> Vec<(String, &'static str)> {
+        let ether_types = self.ether_types.iter().flat_map(|(t, entries)| entries.iter().map(move |e| (format!("EtherType 0x{:04x}", t), e.name)));
+        let packets = self.packets.iter().flat_map(|(binding, entries)| entries.iter().map(move |e| (format!("{:?}", binding), e.name)));
+        let mut all: Vec<_> = ether_types.chain(packets).collect();
+        all.sort();
+        all
+    }
+
+    /// Network layer fields of the payload behind a link header
+    pub fn dissect_network(&self, ether_type: u16, payload: &[u8]) -> Vec<ControlField> {
+        first_match(self.ether_types.get(&ether_type).into_iter().flatten().map(|entry| (entry.dissect)(payload)))
+    }
+
+    /// Application layer fields of a summarized packet
+    pub fn dissect_packet(&self, summary: &PacketSummary, data: &[u8]) -> Vec<ControlField> {
+        let mut bindings = Vec::with_capacity(4);
+        if let Some(proto) = summary.ip_proto {
+            bindings.push(Binding::IpProto(proto));
+        }
+        let mut ports: Vec<u16> = [summary.src_port, summary.dst_port].into_iter().flatten().collect();
+        ports.sort_unstable();
+        ports.dedup();
+        if summary.is_tcp() {
+            bindings.extend(ports.iter().map(|&port| Binding::TcpPort(port)));
+            bindings.push(Binding::TcpHeuristic);
+        } else if summary.is_udp() {
+            bindings.extend(ports.iter().map(|&port| Binding::UdpPort(port)));
+            bindings.push(Binding::UdpHeuristic);
+        }
+        let entries = bindings.iter().filter_map(|binding| self.packets.get(binding)).flatten();
+        first_match(entries.map(|entry| (entry.dissect)(summary, data)))
+    }
+}
+
+/// Fields from the first dissector that recognized its input
+fn first_match(results: impl Iterator<Item = Vec<ControlField>>) -> Vec<ControlField> {
+    results.into_iter().find(|fields| !fields.is_empty()).unwrap_or_default()
+}
+
+static DISSECTORS: OnceLock<DissectorTable> = OnceLock::new();
+
+/// The built-in dissectors
+pub fn dissectors() -> &'static DissectorTable {
+    DISSECTORS.get_or_init(builtin)
+}
+
+fn builtin() -> DissectorTable {
+    let mut table = DissectorTable::default();
+    table.bind_ether_type(0x0800, "IPv4", ipv4_fields);
+    table.bind_ether_type(0x86DD, "IPv6", ipv6_fields);
+    table.bind_ether_type(0x0806, "ARP", |p| arp::ArpPacket::parse(p).map(|arp| arp.get_control_fields()).unwrap_or_default());
+    table.bind_ether_type(eapol::ETHERTYPE_EAPOL, "EAPOL", |p| eapol::EapolPacket::parse(p).map(|e| e.get_control_fields()).unwrap_or_default());
+    table.bind_ether_type(profinet::ETHERTYPE_PROFINET, "PROFINET", |p| {
+        profinet::ProfinetFrame::parse(p).map(|f| f.get_control_fields()).unwrap_or_default()
+    });
+
+    table.bind(Binding::IpProto(icmp::IP_PROTO_ICMP), "ICMP", |s, data| {
+        icmp::IcmpMessage::parse(s.payload(data)).map(|m| m.get_control_fields()).unwrap_or_default()
+    });
+    table.bind(Binding::IpProto(icmp::IP_PROTO_ICMPV6), "ICMPv6", |s, data| {
+        icmp::IcmpMessage::parse_v6(s.payload(data)).map(|m| m.get_control_fields()).unwrap_or_default()
+    });
+    table.bind(Binding::IpProto(ospf::IP_PROTO_OSPF), "OSPF", |s, data| {
+        ospf::OspfPacket::parse(s.payload(data)).map(|o| o.get_control_fields()).unwrap_or_default()
+    });
+    table.bind(Binding::IpProto(vrrp::IP_PROTO_VRRP), "VRRP", |s, data| {
+        let ipv6 = s.src_ip.is_some_and(|ip| ip.is_ipv6());
+        vrrp::VrrpPacket::parse(s.payload(data), ipv6).map(|v| v.get_control_fields()).unwrap_or_default()
+    });
+    table.bind(Binding::IpProto(41), "6in4", tunnel::control_fields);
+    table.bind(Binding::IpProto(132), "SCTP/Diameter", sctp_diameter);
+
+    for port in [443, quic::DOQ_PORT] {
+        table.bind(Binding::UdpPort(port), "QUIC", |s, data| quic::datagram_control_fields(s.payload(data)));
+    }
+    for port in [hsrp::HSRP_PORT, hsrp::HSRP_IPV6_PORT] {
+        table.bind(Binding::UdpPort(port), "HSRP", |s, data| {
+            hsrp::HsrpMessage::parse(s.payload(data)).map(|h| h.get_control_fields()).unwrap_or_default()
+        });
+    }
+    table.bind(Binding::UdpPort(dns::DNS_PORT), "DNS", |s, data| {
+        dns::DnsMessage::parse(s.payload(data)).map(|m| m.get_control_fields()).unwrap_or_default()
+    });
+    table.bind(Binding::TcpPort(dns::DNS_PORT), "DNS", |s, data| {
+        dns::DnsMessage::parse_tcp(s.payload(data)).map(|m| m.get_control_fields()).unwrap_or_default()
+    });
+    table.bind(Binding::UdpPort(tunnel::TEREDO_PORT), "Teredo", tunnel::control_fields);
+    for port in [name_resolution::LLMNR_PORT, name_resolution::MDNS_PORT, name_resolution::NBNS_PORT] {
+        table.bind(Binding::UdpPort(port), "Name resolution", name_resolution);
+    }
+    for port in http::HTTP_PORTS {
+        table.bind(Binding::TcpPort(port), "HTTP", |s, data| {
+            http::HttpMessage::parse(s.payload(data)).map(|m| m.get_control_fields()).unwrap_or_default()
+        });
+    }
+    table.bind(Binding::TcpPort(tls::TLS_PORT), "TLS", |s, data| tls::control_fields(s.payload(data)));
+    table.bind(Binding::TcpPort(ldap::LDAP_PORT), "LDAP", |s, data| {
+        ldap::LdapMessage::parse_all(s.payload(data)).iter().flat_map(|m| m.get_control_fields()).collect()
+    });
+    for port in [radius::RADIUS_AUTH_PORT, radius::RADIUS_ACCT_PORT] {
+        table.bind(Binding::UdpPort(port), "RADIUS", |s, data| {
+            radius::RadiusPacket::parse(s.payload(data)).map(|r| r.get_control_fields()).unwrap_or_default()
+        });
+    }
+    table.bind(Binding::TcpPort(tacacs::TACACS_PORT), "TACACS+", |s, data| {
+        tacacs::TacacsPacket::parse(s.payload(data)).map(|t| t.get_control_fields()).unwrap_or_default()
+    });
+    for port in [gtp::GTP_U_PORT, gtp::GTP_C_PORT] {
+        table.bind(Binding::UdpPort(port), "GTP", gtp_fields);
+    }
+    table.bind(Binding::TcpPort(diameter::DIAMETER_PORT), "Diameter", |s, data| {
+        diameter::DiameterMessage::parse_all(s.payload(data)).iter().flat_map(|m| m.get_control_fields()).collect()
+    });
+    table.bind(Binding::UdpPort(bacnet::BACNET_PORT), "BACnet", |s, data| {
+        bacnet::BacnetPacket::parse(s.payload(data)).map(|b| b.get_control_fields()).unwrap_or_default()
+    });
+    table.bind(Binding::TcpPort(iec104::IEC104_PORT), "IEC 104", |s, data| {
+        iec104::Iec104Apdu::parse_all(s.payload(data)).iter().flat_map(|a| a.get_control_fields()).collect()
+    });
+    let enip: PacketDissector = |s, data| enip::EnipPacket::parse(s.payload(data)).map(|e| e.get_control_fields()).unwrap_or_default();
+    table.bind(Binding::TcpPort(enip::ENIP_PORT), "EtherNet/IP", enip);
+    table.bind(Binding::UdpPort(enip::ENIP_PORT), "EtherNet/IP", enip);
+    table.bind(Binding::UdpPort(enip::ENIP_IO_PORT), "EtherNet/IP I/O", |s, data| enip::implicit_io_control_fields(s.payload(data)));
+    table.bind(Binding::TcpPort(opcua::OPC_UA_PORT), "OPC UA", |s, data| {
+        opcua::OpcUaMessage::parse_all(s.payload(data)).iter().flat_map(|m| m.get_control_fields()).collect()
+    });
+    table.bind(Binding::UdpPort(l2tp::L2TP_PORT), "L2TP", |s, data| {
+        l2tp::L2tpPacket::parse(s.payload(data)).map(|l2tp| l2tp.get_control_fields()).unwrap_or_default()
+    });
+    // SOME/IP services use configured ports; only SD has a fixed one. Elsewhere
+    // the datagram must consist of well-formed messages exactly.
+    table.bind(Binding::UdpHeuristic, "SOME/IP", |s, data| {
+        someip::SomeIpMessage::parse_all(s.payload(data)).iter().flat_map(|m| m.get_control_fields()).collect()
+    });
+    // Last resort: name the protocol from magic bytes and handshake patterns,
+    // so e.g. SSH on 2222 or TLS on 8443 is still recognized
+    table.bind(Binding::TcpHeuristic, "Content detection", |s, data| {
+        detect::detect_tcp(s.payload(data)).map(|d| d.get_control_fields()).unwrap_or_default()
+    });
+    table.bind(Binding::UdpHeuristic, "Content detection", |s, data| {
+        detect::detect_udp(s.payload(data)).map(|d| d.get_control_fields()).unwrap_or_default()
+    });
+    table
+}
+
+fn ipv4_fields(payload: &[u8]) -> Vec<ControlField> {
+    let Ok(ip) = ipv4::IPv4Packet::parse(payload) else { return Vec::new() };
+    let mut fields = ip.get_control_fields();
+    // Later fragments carry no transport header
+    if ip.fragment_offset() == 0 {
+        // Ethernet pads short packets past the IP total length
+        let header_length = ip.header_length() as usize;
+        let end = (ip.total_length() as usize).min(payload.len()).max(header_length);
+        if let Some(transport) = payload.get(header_length..end) {
+            fields.extend(transport_fields(ip.protocol(), transport));
+        }
+    }
+    fields
+}
+
+fn ipv6_fields(payload: &[u8]) -> Vec<ControlField> {
+    let Ok(ip) = ipv6::IPv6Packet::parse(payload) else { return Vec::new() };
+    let mut fields = ip.get_control_fields();
+    fields.extend(transport_fields(ip.next_header(), ip.payload()));
+    fields
+}
+
+/// Fields of the transport header behind an IP header
+fn transport_fields(protocol: u8, payload: &[u8]) -> Vec<ControlField> {
+    match protocol {
+        tcp::IP_PROTO_TCP => tcp::TcpSegment::parse(payload).map(|t| t.get_control_fields()).unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+fn name_resolution(summary: &PacketSummary, data: &[u8]) -> Vec<ControlField> {
+    let protocol = [summary.src_port, summary.dst_port].into_iter().flatten().find_map(name_resolution::NameProtocol::from_port);
+    let Some(protocol) = protocol else { return Vec::new() };
+    name_resolution::NameMessage::parse(protocol, summary.payload(data)).map(|m| m.get_control_fields()).unwrap_or_default()
+}
+
+fn gtp_fields(summary: &PacketSummary, data: &[u8]) -> Vec<ControlField> {
+    let Ok(packet) = gtp::GtpPacket::parse(summary.payload(data)) else { return Vec::new() };
+    let mut fields = packet.get_control_fields();
+    // Subscriber traffic is analyzed like any other IP packet
+    if let Some(inner) = packet.inner_packet()
+        && let Some(inner_summary) = PacketSummary::from_ip(inner)
+    {
+        fields.extend(super::application_fields(&inner_summary, inner));
+    }
+    fields
+}
+
+fn sctp_diameter(summary: &PacketSummary, data: &[u8]) -> Vec<ControlField> {
+    let port = summary.has_port(diameter::DIAMETER_PORT);
+    sctp::data_chunks(summary.payload(data))
+        .into_iter()
+        .filter(|chunk| chunk.ppid == diameter::DIAMETER_PPID || (chunk.ppid == 0 && port))
+        .flat_map(|chunk| diameter::DiameterMessage::parse_all(chunk.data))
+        .flat_map(|m| m.get_control_fields())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn dispatches_by_layer_and_falls_back_to_heuristics() {
+        fn named(name: &str) -> Vec<ControlField> {
+            vec![ControlField { name: name.to_string(), value: String::new(), description: String::new() }]
+        }
+        let mut table = DissectorTable::default();
+        table.bind(Binding::UdpPort(7), "echo", |_, _| named("echo"));
+        table.bind(Binding::UdpPort(40000), "never", |_, _| named("never"));
+        table.bind(Binding::UdpPort(9), "empty", |_, _| Vec::new());
+        table.bind(Binding::UdpHeuristic, "guess", |_, _| named("guess"));
+
+        let dissect = |src: u16, dst: u16| {
+            let frame = testutil::ipv4_udp_frame(src, dst, b"x");
+            table.dissect_packet(&PacketSummary::from_ethernet(&frame).unwrap(), &frame)[0].name.clone()
+        };
+        // The lower port wins; a dissector that recognizes nothing passes the packet on
+        assert_eq!(dissect(40000, 7), "echo");
+        assert_eq!(dissect(50000, 9), "guess");
+        assert!(table.bindings().contains(&("UdpHeuristic".to_string(), "guess")));
+    }
+
+    #[test]
+    fn builtin_table_covers_every_layer() {
+        let table = dissectors();
+        assert!(!table.dissect_network(0x0806, &testutil::arp_request(testutil::client_v4(), testutil::server_v4())[14..]).is_empty());
+        let query = testutil::dns_query_frame(1, "example.com");
+        assert_eq!(table.dissect_packet(&PacketSummary::from_ethernet(&query).unwrap(), &query)[0].name, "DNS Transaction ID");
+        let ssh = testutil::ipv4_tcp_frame(40000, 2222, testutil::TCP_PSH | testutil::TCP_ACK, b"SSH-2.0-OpenSSH_9.6\r\n");
+        assert_eq!(table.dissect_packet(&PacketSummary::from_ethernet(&ssh).unwrap(), &ssh)[0].value, "SSH");
+        let names: Vec<&str> = table.bindings().into_iter().map(|(_, name)| name).collect();
+        assert!(["IPv4", "OSPF", "QUIC", "LDAP", "SOME/IP"].iter().all(|name| names.contains(name)));
+    }
+}
@@ -0,0 +1,49 @@
+/// IP protocol number of SCTP
+pub const IP_PROTO_SCTP: u8 = 132;
+
+const CHUNK_DATA: u8 = 0;
+
+/// User data carried by an SCTP DATA chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataChunk<'a> {
+    pub stream: u16,
+    /// Payload protocol identifier
+    pub ppid: u32,
+    pub data: &'a [u8],
+}
+
+/// DATA chunks in the chunk list following the SCTP common header.
+/// Chunk lengths exclude the padding to a 4-byte boundary.
+pub fn data_chunks(chunks: &[u8]) -> Vec<DataChunk<'_>> {
+    let mut found = Vec::new();
+    let mut rest = chunks;
+    while rest.len() >= 4 {
+        let len = u16::from_be_bytes([rest[2], rest[3]]) as usize;
+        if len < 4 || len > rest.len() {
+            break;
+        }
+        if rest[0] == CHUNK_DATA && len >= 16 {
+            found.push(DataChunk {
+                stream: u16::from_be_bytes([rest[8], rest[9]]),
+                ppid: u32::from_be_bytes([rest[12], rest[13], rest[14], rest[15]]),
+                data: &rest[16..len],
+            });
+        }
+        rest = &rest[len.next_multiple_of(4).min(rest.len())..];
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_data_chunks_after_control_chunks() {
+        // SACK, then a DATA chunk with 3 bytes of user data and one byte of padding
+        let mut chunks = vec![3, 0, 0, 16, 0, 0, 0, 1, 0, 0, 0x10, 0, 0, 0, 0, 0];
+        chunks.extend_from_slice(&[0, 3, 0, 19, 0, 0, 0, 2, 0, 1, 0, 0, 0, 0, 0, 46, b'a', b'b', b'c', 0]);
+        let found = data_chunks(&chunks);
+        assert_eq!(found, vec![DataChunk { stream: 1, ppid: 46, data: b"abc" }]);
+    }
+}
@@ -0,0 +1,182 @@
+use super::frame_control::ControlField;
+use std::ffi::CStr;
+use std::fmt;
+
+/// Linux cooked capture v1, used by the "any" device (16-byte header)
+pub const DLT_LINUX_SLL: i32 = 113;
+/// Linux cooked capture v2, which adds the interface index (20-byte header)
+pub const DLT_LINUX_SLL2: i32 = 276;
+
+/// Direction of the packet relative to the capturing host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketType {
+    Host,
+    Broadcast,
+    Multicast,
+    OtherHost,
+    Outgoing,
+    Other(u16),
+}
+
+impl PacketType {
+    fn from_u16(value: u16) -> Self {
+        match value {
+            0 => PacketType::Host,
+            1 => PacketType::Broadcast,
+            2 => PacketType::Multicast,
+            3 => PacketType::OtherHost,
+            4 => PacketType::Outgoing,
+            v => PacketType::Other(v),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            PacketType::Host => "Unicast to us".to_string(),
+            PacketType::Broadcast => "Broadcast".to_string(),
+            PacketType::Multicast => "Multicast".to_string(),
+            PacketType::OtherHost => "Unicast to another host".to_string(),
+            PacketType::Outgoing => "Sent by us".to_string(),
+            PacketType::Other(v) => format!("Unknown ({})", v),
+        }
+    }
+}
+
+/// Linux "cooked" capture header (DLT_LINUX_SLL / DLT_LINUX_SLL2), which
+/// replaces the link-layer header on the "any" device
+pub struct SllHeader<'a> {
+    data: &'a [u8],
+    version: u8,
+}
+
+#[derive(Debug)]
+pub enum SllError {
+    TooShort,
+    UnsupportedDatalink(i32),
+}
+
+impl fmt::Display for SllError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SllError::TooShort => write!(f, "Packet too short for Linux cooked header"),
+            SllError::UnsupportedDatalink(d) => write!(f, "Datalink {} is not a Linux cooked capture", d),
+        }
+    }
+}
+
+impl<'a> SllHeader<'a> {
+    pub fn parse(datalink: i32, data: &'a [u8]) -> Result<Self, SllError> {
+        let (version, len) = match datalink {
+            DLT_LINUX_SLL => (1, 16),
+            DLT_LINUX_SLL2 => (2, 20),
+            d => return Err(SllError::UnsupportedDatalink(d)),
+        };
+        if data.len() < len {
+            return Err(SllError::TooShort);
+        }
+        Ok(SllHeader { data, version })
+    }
+
+    pub fn header_len(&self) -> usize {
+        if self.version == 1 { 16 } else { 20 }
+    }
+
+    /// EtherType of the payload
+    pub fn protocol(&self) -> u16 {
+        let at = if self.version == 1 { 14 } else { 0 };
+        u16::from_be_bytes([self.data[at], self.data[at + 1]])
+    }
+
+    /// Index of the interface the packet was seen on (SLL2 only)
+    pub fn interface_index(&self) -> Option<u32> {
+        (self.version == 2).then(|| u32::from_be_bytes([self.data[4], self.data[5], self.data[6], self.data[7]]))
+    }
+
+    pub fn packet_type(&self) -> PacketType {
+        match self.version {
+            1 => PacketType::from_u16(u16::from_be_bytes([self.data[0], self.data[1]])),
+            _ => PacketType::from_u16(self.data[10] as u16),
+        }
+    }
+
+    /// ARPHRD_ type of the interface (1 for Ethernet, 772 for loopback)
+    pub fn hardware_type(&self) -> u16 {
+        let at = if self.version == 1 { 2 } else { 8 };
+        u16::from_be_bytes([self.data[at], self.data[at + 1]])
+    }
+
+    /// Link-layer source address, when the interface has one
+    pub fn address(&self) -> &'a [u8] {
+        let (len_at, addr_at) = if self.version == 1 { (5, 6) } else { (11, 12) };
+        let len = (self.data[len_at] as usize).min(8);
+        &self.data[addr_at..addr_at + len]
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[self.header_len()..]
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = Vec::new();
+        if let Some(index) = self.interface_index() {
+            fields.push(ControlField {
+                name: "Interface".to_string(),
+                value: interface_name(index).unwrap_or_else(|| format!("#{}", index)),
+                description: format!("Ingress interface index {}", index),
+            });
+        }
+        fields.push(ControlField {
+            name: "Packet Type".to_string(),
+            value: self.packet_type().name(),
+            description: format!("ARPHRD {}", self.hardware_type()),
+        });
+        if !self.address().is_empty() {
+            fields.push(ControlField {
+                name: "Source Address".to_string(),
+                value: self.address().iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":"),
+                description: "Link-layer source address".to_string(),
+            });
+        }
+        fields.push(ControlField {
+            name: "Protocol".to_string(),
+            value: format!("0x{:04x}", self.protocol()),
+            description: "EtherType of the payload".to_string(),
+        });
+        fields
+    }
+}
+
+/// Name of a local interface by index, as the kernel reports it now
+pub fn interface_name(index: u32) -> Option<String> {
+    let mut buf = [0 as libc::c_char; libc::IF_NAMESIZE];
+    // SAFETY: buf holds IF_NAMESIZE bytes, as if_indextoname requires
+    let name = unsafe { libc::if_indextoname(index, buf.as_mut_ptr()) };
+    if name.is_null() {
+        return None;
+    }
+    // SAFETY: on success buf holds a NUL-terminated name
+    Some(unsafe { CStr::from_ptr(buf.as_ptr()) }.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_both_versions() {
+        let mut v1 = vec![0, 4, 0, 1, 0, 6, 0x02, 0, 0, 0, 0, 1, 0, 0, 0x08, 0x00];
+        v1.extend_from_slice(&[0x45]);
+        let sll = SllHeader::parse(DLT_LINUX_SLL, &v1).unwrap();
+        assert_eq!(sll.packet_type(), PacketType::Outgoing);
+        assert_eq!((sll.protocol(), sll.interface_index()), (0x0800, None));
+        assert_eq!(sll.address(), &[0x02, 0, 0, 0, 0, 1]);
+        assert_eq!(sll.payload(), &[0x45]);
+
+        let v2 = [0x86, 0xDD, 0, 0, 0, 0, 0, 1, 0x03, 0x04, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let sll = SllHeader::parse(DLT_LINUX_SLL2, &v2).unwrap();
+        assert_eq!((sll.protocol(), sll.interface_index(), sll.hardware_type()), (0x86DD, Some(1), 772));
+        assert_eq!(sll.packet_type(), PacketType::Host);
+        assert!(sll.address().is_empty());
+        assert!(SllHeader::parse(DLT_LINUX_SLL2, &v2[..16]).is_err());
+    }
+}
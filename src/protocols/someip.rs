@@ -0,0 +1,344 @@
+use super::frame_control::ControlField;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Service discovery port
+pub const SOMEIP_SD_PORT: u16 = 30490;
+
+const SD_SERVICE: u16 = 0xFFFF;
+const SD_METHOD: u16 = 0x8100;
+/// Set on message types of segmented (SOME/IP-TP) messages
+const TP_FLAG: u8 = 0x20;
+
+/// SOME/IP message parser (AUTOSAR PRS_SOMEIPProtocol)
+pub struct SomeIpMessage<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum SomeIpError {
+    TooShort,
+    UnsupportedVersion(u8),
+    UnknownMessageType(u8),
+}
+
+impl fmt::Display for SomeIpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SomeIpError::TooShort => write!(f, "Packet too short for SOME/IP header"),
+            SomeIpError::UnsupportedVersion(v) => write!(f, "Unsupported SOME/IP protocol version {}", v),
+            SomeIpError::UnknownMessageType(t) => write!(f, "Unknown SOME/IP message type 0x{:02x}", t),
+        }
+    }
+}
+
+impl<'a> SomeIpMessage<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, SomeIpError> {
+        if data.len() < 16 {
+            return Err(SomeIpError::TooShort);
+        }
+        // Length covers everything after the length field
+        let length = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        if length < 8 || data.len() - 8 < length {
+            return Err(SomeIpError::TooShort);
+        }
+        if data[12] != 1 {
+            return Err(SomeIpError::UnsupportedVersion(data[12]));
+        }
+        if !matches!(data[14] & !TP_FLAG, 0x00 | 0x01 | 0x02 | 0x80 | 0x81) {
+            return Err(SomeIpError::UnknownMessageType(data[14]));
+        }
+        Ok(SomeIpMessage { data: &data[..8 + length] })
+    }
+
+    /// Every message in a datagram, or an empty list unless they fill it exactly
+    pub fn parse_all(mut data: &'a [u8]) -> Vec<Self> {
+        let mut messages = Vec::new();
+        while !data.is_empty() {
+            let Ok(message) = SomeIpMessage::parse(data) else { return Vec::new() };
+            data = &data[message.data.len()..];
+            messages.push(message);
+        }
+        messages
+    }
+
+    pub fn service_id(&self) -> u16 {
+        u16::from_be_bytes([self.data[0], self.data[1]])
+    }
+
+    /// Method ID; event IDs have the high bit set
+    pub fn method_id(&self) -> u16 {
+        u16::from_be_bytes([self.data[2], self.data[3]])
+    }
+
+    pub fn client_id(&self) -> u16 {
+        u16::from_be_bytes([self.data[8], self.data[9]])
+    }
+
+    pub fn session_id(&self) -> u16 {
+        u16::from_be_bytes([self.data[10], self.data[11]])
+    }
+
+    pub fn interface_version(&self) -> u8 {
+        self.data[13]
+    }
+
+    pub fn message_type(&self) -> u8 {
+        self.data[14]
+    }
+
+    pub fn return_code(&self) -> u8 {
+        self.data[15]
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[16..]
+    }
+
+    pub fn is_service_discovery(&self) -> bool {
+        self.service_id() == SD_SERVICE && self.method_id() == SD_METHOD
+    }
+
+    pub fn get_message_type_name(&self) -> String {
+        let name = match self.message_type() & !TP_FLAG {
+            0x00 => "Request",
+            0x01 => "Request (no return)",
+            0x02 => "Notification",
+            0x80 => "Response",
+            0x81 => "Error",
+            t => return format!("Unknown (0x{:02x})", t),
+        };
+        if self.message_type() & TP_FLAG != 0 { format!("{} (TP segment)", name) } else { name.to_string() }
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let method = self.method_id();
+        let mut fields = vec![
+            ControlField {
+                name: "SOME/IP Service".to_string(),
+                value: format!("0x{:04x}", self.service_id()),
+                description: if method & 0x8000 != 0 {
+                    format!("Event 0x{:04x}", method)
+                } else {
+                    format!("Method 0x{:04x}", method)
+                },
+            },
+            ControlField {
+                name: "SOME/IP Message".to_string(),
+                value: self.get_message_type_name(),
+                description: format!(
+                    "Client 0x{:04x}, session {}, interface version {}",
+                    self.client_id(),
+                    self.session_id(),
+                    self.interface_version()
+                ),
+            },
+        ];
+        if self.message_type() & 0x80 != 0 {
+            fields.push(ControlField {
+                name: "SOME/IP Return Code".to_string(),
+                value: return_code_name(self.return_code()),
+                description: format!("0x{:02x}", self.return_code()),
+            });
+        }
+        if self.is_service_discovery() && let Some(sd) = ServiceDiscovery::parse(self.payload()) {
+            fields.extend(sd.get_control_fields());
+        }
+        fields
+    }
+}
+
+/// SOME/IP-SD entry (service or eventgroup)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SdEntry {
+    pub entry_type: u8,
+    pub service_id: u16,
+    pub instance_id: u16,
+    pub major_version: u8,
+    pub ttl: u32,
+    /// Minor version of service entries, eventgroup ID of eventgroup entries
+    pub minor_or_eventgroup: u32,
+}
+
+impl SdEntry {
+    fn is_eventgroup(&self) -> bool {
+        matches!(self.entry_type, 0x06 | 0x07)
+    }
+
+    pub fn get_type_name(&self) -> String {
+        // A TTL of zero withdraws the offer or subscription
+        match (self.entry_type, self.ttl) {
+            (0x00, _) => "FindService".to_string(),
+            (0x01, 0) => "StopOfferService".to_string(),
+            (0x01, _) => "OfferService".to_string(),
+            (0x06, 0) => "StopSubscribeEventgroup".to_string(),
+            (0x06, _) => "SubscribeEventgroup".to_string(),
+            (0x07, 0) => "SubscribeEventgroupNack".to_string(),
+            (0x07, _) => "SubscribeEventgroupAck".to_string(),
+            (t, _) => format!("Unknown (0x{:02x})", t),
+        }
+    }
+}
+
+/// Endpoint announced in an SD option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SdEndpoint {
+    pub address: IpAddr,
+    /// IP protocol number (6 or 17)
+    pub protocol: u8,
+    pub port: u16,
+    pub multicast: bool,
+}
+
+/// SOME/IP-SD payload: flags, entries array and options array
+pub struct ServiceDiscovery<'a> {
+    entries: &'a [u8],
+    options: &'a [u8],
+}
+
+impl<'a> ServiceDiscovery<'a> {
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let entries_len = u32::from_be_bytes(data.get(4..8)?.try_into().ok()?) as usize;
+        let entries = data.get(8..8 + entries_len)?;
+        let rest = &data[8 + entries_len..];
+        let options_len = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        let options = rest.get(4..4 + options_len)?;
+        Some(ServiceDiscovery { entries, options })
+    }
+
+    pub fn entries(&self) -> Vec<SdEntry> {
+        self.entries
+            .chunks_exact(16)
+            .map(|e| SdEntry {
+                entry_type: e[0],
+                service_id: u16::from_be_bytes([e[4], e[5]]),
+                instance_id: u16::from_be_bytes([e[6], e[7]]),
+                major_version: e[8],
+                ttl: u32::from_be_bytes([0, e[9], e[10], e[11]]),
+                minor_or_eventgroup: match e[0] {
+                    0x06 | 0x07 => u16::from_be_bytes([e[14], e[15]]) as u32,
+                    _ => u32::from_be_bytes([e[12], e[13], e[14], e[15]]),
+                },
+            })
+            .collect()
+    }
+
+    /// IPv4/IPv6 endpoint and multicast options
+    pub fn endpoints(&self) -> Vec<SdEndpoint> {
+        let mut endpoints = Vec::new();
+        let mut rest = self.options;
+        while rest.len() >= 3 {
+            // The length excludes itself and the type
+            let len = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+            let Some(option) = rest.get(3..3 + len) else { break };
+            let multicast = matches!(rest[2], 0x14 | 0x16);
+            match (rest[2], option.len()) {
+                (0x04 | 0x14 | 0x24, 9) => endpoints.push(SdEndpoint {
+                    address: IpAddr::V4(Ipv4Addr::new(option[1], option[2], option[3], option[4])),
+                    protocol: option[6],
+                    port: u16::from_be_bytes([option[7], option[8]]),
+                    multicast,
+                }),
+                (0x06 | 0x16 | 0x26, 21) => {
+                    let octets: [u8; 16] = option[1..17].try_into().unwrap_or_default();
+                    endpoints.push(SdEndpoint {
+                        address: IpAddr::V6(Ipv6Addr::from(octets)),
+                        protocol: option[18],
+                        port: u16::from_be_bytes([option[19], option[20]]),
+                        multicast,
+                    });
+                }
+                _ => {}
+            }
+            rest = &rest[3 + len..];
+        }
+        endpoints
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields: Vec<ControlField> = self
+            .entries()
+            .iter()
+            .map(|entry| ControlField {
+                name: "SOME/IP-SD Entry".to_string(),
+                value: if entry.is_eventgroup() {
+                    format!("{} 0x{:04x}.0x{:04x} eventgroup 0x{:04x}", entry.get_type_name(), entry.service_id, entry.instance_id, entry.minor_or_eventgroup)
+                } else {
+                    format!("{} 0x{:04x}.0x{:04x} v{}.{}", entry.get_type_name(), entry.service_id, entry.instance_id, entry.major_version, entry.minor_or_eventgroup)
+                },
+                description: format!("TTL {}", entry.ttl),
+            })
+            .collect();
+        fields.extend(self.endpoints().iter().map(|endpoint| {
+            let address = match endpoint.address {
+                IpAddr::V4(ip) => format!("{}:{}", ip, endpoint.port),
+                IpAddr::V6(ip) => format!("[{}]:{}", ip, endpoint.port),
+            };
+            ControlField {
+                name: "SOME/IP-SD Endpoint".to_string(),
+                value: format!("{}/{}", address, if endpoint.protocol == 6 { "TCP" } else { "UDP" }),
+                description: if endpoint.multicast { "Multicast".to_string() } else { "Unicast".to_string() },
+            }
+        }));
+        fields
+    }
+}
+
+pub fn return_code_name(code: u8) -> String {
+    match code {
+        0x00 => "E_OK".to_string(),
+        0x01 => "E_NOT_OK".to_string(),
+        0x02 => "E_UNKNOWN_SERVICE".to_string(),
+        0x03 => "E_UNKNOWN_METHOD".to_string(),
+        0x04 => "E_NOT_READY".to_string(),
+        0x05 => "E_NOT_REACHABLE".to_string(),
+        0x06 => "E_TIMEOUT".to_string(),
+        0x07 => "E_WRONG_PROTOCOL_VERSION".to_string(),
+        0x08 => "E_WRONG_INTERFACE_VERSION".to_string(),
+        0x09 => "E_MALFORMED_MESSAGE".to_string(),
+        0x0A => "E_WRONG_MESSAGE_TYPE".to_string(),
+        c => format!("Unknown (0x{:02x})", c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(service: u16, method: u16, message_type: u8, return_code: u8, payload: &[u8]) -> Vec<u8> {
+        let mut data = service.to_be_bytes().to_vec();
+        data.extend_from_slice(&method.to_be_bytes());
+        data.extend_from_slice(&(payload.len() as u32 + 8).to_be_bytes());
+        data.extend_from_slice(&[0x00, 0x01, 0x00, 0x2a, 0x01, 0x01, message_type, return_code]);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    #[test]
+    fn decodes_requests_and_responses() {
+        let mut data = header(0x1234, 0x0005, 0x00, 0x00, &[1, 2]);
+        data.extend(header(0x1234, 0x0005, 0x81, 0x03, &[]));
+        let messages = SomeIpMessage::parse_all(&data);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].session_id(), 42);
+        assert_eq!(messages[0].payload(), &[1, 2]);
+        let fields = messages[1].get_control_fields();
+        assert!(fields.iter().any(|f| f.name == "SOME/IP Return Code" && f.value == "E_UNKNOWN_METHOD"));
+        // Trailing bytes that aren't a message reject the whole datagram
+        data.push(0);
+        assert!(SomeIpMessage::parse_all(&data).is_empty());
+    }
+
+    #[test]
+    fn decodes_service_discovery() {
+        let mut sd = vec![0xC0, 0, 0, 0, 0, 0, 0, 16];
+        sd.extend_from_slice(&[0x01, 0, 0, 0x10, 0x12, 0x34, 0x00, 0x01, 1, 0, 0, 3, 0, 0, 0, 0]);
+        sd.extend_from_slice(&[0, 0, 0, 12, 0, 9, 0x04, 0, 192, 168, 0, 20, 0, 17, 0x77, 0x35]);
+        let data = header(SD_SERVICE, SD_METHOD, 0x02, 0x00, &sd);
+        let message = SomeIpMessage::parse(&data).unwrap();
+        assert!(message.is_service_discovery());
+        let fields = message.get_control_fields();
+        assert!(fields.iter().any(|f| f.name == "SOME/IP-SD Entry" && f.value == "OfferService 0x1234.0x0001 v1.0"));
+        assert!(fields.iter().any(|f| f.name == "SOME/IP-SD Endpoint" && f.value == "192.168.0.20:30517/UDP"));
+    }
+}
@@ -0,0 +1,348 @@
+use super::ethernet::{EthernetFrame, MacAddress};
+use super::null::{NullHeader, DLT_LOOP, DLT_NULL};
+use super::sll::{SllHeader, DLT_LINUX_SLL, DLT_LINUX_SLL2};
+use super::tcp::TcpSegment;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Key header fields of a frame extracted in a single pass, used for
+/// filtering, flow keys and statistics without building control fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketSummary {
+    pub src_mac: MacAddress,
+    pub dst_mac: MacAddress,
+    /// EtherType after any VLAN tags
+    pub ether_type: u16,
+    pub vlan: Option<u16>,
+    pub src_ip: Option<IpAddr>,
+    pub dst_ip: Option<IpAddr>,
+    pub ip_proto: Option<u8>,
+    pub ttl: Option<u8>,
+    pub src_port: Option<u16>,
+    pub dst_port: Option<u16>,
+    pub tcp_flags: Option<u8>,
+    /// Offset of the TCP/UDP header when one was parsed
+    pub transport_offset: Option<usize>,
+    /// Offset of the innermost payload we understood (L4 payload when known)
+    pub payload_offset: usize,
+    /// Offset where the IP packet ends according to its length field, which
+    /// lies past the buffer when the capture cut the packet short
+    pub ip_end: Option<usize>,
+    pub length: usize,
+}
+
+impl PacketSummary {
+    /// Summarizes an Ethernet frame, returning `None` for frames too short to parse
+    pub fn from_ethernet(data: &[u8]) -> Option<Self> {
+        let eth = EthernetFrame::parse(data).ok()?;
+        let mut ether_type = eth.ether_type().value();
+        let mut offset = 14;
+        let mut vlan = None;
+
+        // 802.1Q / 802.1ad tags
+        while matches!(ether_type, 0x8100 | 0x88A8) && data.len() >= offset + 4 {
+            vlan.get_or_insert(u16::from_be_bytes([data[offset] & 0x0F, data[offset + 1]]));
+            ether_type = u16::from_be_bytes([data[offset + 2], data[offset + 3]]);
+            offset += 4;
+        }
+
+        let mut summary = PacketSummary {
+            src_mac: eth.src_mac(),
+            dst_mac: eth.dest_mac(),
+            ether_type,
+            vlan,
+            src_ip: None,
+            dst_ip: None,
+            ip_proto: None,
+            ttl: None,
+            src_port: None,
+            dst_port: None,
+            tcp_flags: None,
+            transport_offset: None,
+            payload_offset: offset,
+            ip_end: None,
+            length: data.len(),
+        };
+        summary.fill_network(data, offset);
+        Some(summary)
+    }
+
+    /// Summarizes a frame of the given datalink type (DLT_*): Ethernet or a
+    /// Linux cooked capture from the "any" device
+    pub fn from_link(datalink: i32, data: &[u8]) -> Option<Self> {
+        match datalink {
+            1 => Self::from_ethernet(data),
+            DLT_LINUX_SLL | DLT_LINUX_SLL2 => Self::from_sll(datalink, data),
+            DLT_NULL | DLT_LOOP => Self::from_null(datalink, data),
+            _ => None,
+        }
+    }
+
+    /// Summarizes a Linux cooked capture; the source MAC comes from the
+    /// header's link-layer address and the destination MAC is unknown
+    pub fn from_sll(datalink: i32, data: &[u8]) -> Option<Self> {
+        let sll = SllHeader::parse(datalink, data).ok()?;
+        let mut src_mac = [0u8; 6];
+        if let Ok(address) = <[u8; 6]>::try_from(sll.address()) {
+            src_mac = address;
+        }
+        Some(Self::from_pseudo_header(MacAddress::new(src_mac), sll.protocol(), data, sll.header_len()))
+    }
+
+    /// Summarizes a BSD loopback or utun packet, which has no MAC addresses
+    pub fn from_null(datalink: i32, data: &[u8]) -> Option<Self> {
+        let null = NullHeader::parse(datalink, data).ok()?;
+        Some(Self::from_pseudo_header(MacAddress::new([0; 6]), null.ether_type(), data, 4))
+    }
+
+    /// Summary of a network packet behind a link-layer pseudo-header of `offset` bytes
+    fn from_pseudo_header(src_mac: MacAddress, ether_type: u16, data: &[u8], offset: usize) -> Self {
+        let mut summary = PacketSummary {
+            src_mac,
+            dst_mac: MacAddress::new([0; 6]),
+            ether_type,
+            vlan: None,
+            src_ip: None,
+            dst_ip: None,
+            ip_proto: None,
+            ttl: None,
+            src_port: None,
+            dst_port: None,
+            tcp_flags: None,
+            transport_offset: None,
+            payload_offset: offset,
+            ip_end: None,
+            length: data.len(),
+        };
+        summary.fill_network(data, offset);
+        summary
+    }
+
+    /// Summarizes a bare IPv4/IPv6 packet, such as one decapsulated from a tunnel
+    pub fn from_ip(data: &[u8]) -> Option<Self> {
+        let ether_type = match data.first()? >> 4 {
+            4 => 0x0800,
+            6 => 0x86DD,
+            _ => return None,
+        };
+        let mut summary = PacketSummary {
+            src_mac: MacAddress::new([0; 6]),
+            dst_mac: MacAddress::new([0; 6]),
+            ether_type,
+            vlan: None,
+            src_ip: None,
+            dst_ip: None,
+            ip_proto: None,
+            ttl: None,
+            src_port: None,
+            dst_port: None,
+            tcp_flags: None,
+            transport_offset: None,
+            payload_offset: 0,
+            ip_end: None,
+            length: data.len(),
+        };
+        summary.fill_network(data, 0);
+        summary.src_ip.is_some().then_some(summary)
+    }
+
+    fn fill_network(&mut self, data: &[u8], offset: usize) {
+        let ip = &data[offset.min(data.len())..];
+        let l4_offset = match self.ether_type {
+            0x0800 if ip.len() >= 20 && ip[0] >> 4 == 4 => {
+                let ihl = ((ip[0] & 0x0F) as usize) * 4;
+                self.src_ip = Some(IpAddr::V4(Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15])));
+                self.dst_ip = Some(IpAddr::V4(Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19])));
+                self.ip_proto = Some(ip[9]);
+                self.ttl = Some(ip[8]);
+                self.ip_end = Some(offset + u16::from_be_bytes([ip[2], ip[3]]) as usize);
+                // Only the first fragment carries the transport header
+                let fragment_offset = u16::from_be_bytes([ip[6] & 0x1F, ip[7]]);
+                if fragment_offset != 0 || ihl < 20 {
+                    self.payload_offset = offset + ihl.max(20);
+                    return;
+                }
+                offset + ihl
+            }
+            0x86DD if ip.len() >= 40 && ip[0] >> 4 == 6 => {
+                let mut src = [0u8; 16];
+                let mut dst = [0u8; 16];
+                src.copy_from_slice(&ip[8..24]);
+                dst.copy_from_slice(&ip[24..40]);
+                self.src_ip = Some(IpAddr::V6(Ipv6Addr::from(src)));
+                self.dst_ip = Some(IpAddr::V6(Ipv6Addr::from(dst)));
+                self.ttl = Some(ip[7]);
+                self.ip_end = Some(offset + 40 + u16::from_be_bytes([ip[4], ip[5]]) as usize);
+                let (next_header, header_len) = skip_ipv6_extensions(ip);
+                self.ip_proto = Some(next_header);
+                offset + header_len
+            }
+            _ => return,
+        };
+        self.payload_offset = l4_offset.min(data.len());
+
+        let l4 = &data[self.payload_offset..];
+        match self.ip_proto {
+            Some(6) => {
+                // Options cut off by the snaplen still leave the ports and flags
+                let Ok(tcp) = TcpSegment::parse_truncated(l4) else { return };
+                self.transport_offset = Some(self.payload_offset);
+                self.src_port = Some(tcp.source_port());
+                self.dst_port = Some(tcp.destination_port());
+                self.tcp_flags = Some(tcp.flags() as u8);
+                self.payload_offset += tcp.header_length().min(l4.len());
+            }
+            Some(17) if l4.len() >= 8 => {
+                self.transport_offset = Some(self.payload_offset);
+                self.src_port = Some(u16::from_be_bytes([l4[0], l4[1]]));
+                self.dst_port = Some(u16::from_be_bytes([l4[2], l4[3]]));
+                self.payload_offset += 8;
+            }
+            // SCTP common header; the payload is the chunk list
+            Some(132) if l4.len() >= 12 => {
+                self.transport_offset = Some(self.payload_offset);
+                self.src_port = Some(u16::from_be_bytes([l4[0], l4[1]]));
+                self.dst_port = Some(u16::from_be_bytes([l4[2], l4[3]]));
+                self.payload_offset += 12;
+            }
+            _ => {}
+        }
+    }
+
+    /// Application payload (after the transport header when one was parsed)
+    pub fn payload<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        &data[self.payload_offset.min(data.len())..]
+    }
+
+    /// Bytes of the IP packet past the end of `data`, lost to the snaplen
+    pub fn missing_bytes(&self, data: &[u8]) -> usize {
+        self.ip_end.map_or(0, |end| end.saturating_sub(data.len()))
+    }
+
+    /// TCP sequence number
+    pub fn tcp_seq(&self, data: &[u8]) -> Option<u32> {
+        let offset = self.transport_offset.filter(|_| self.is_tcp())?;
+        TcpSegment::parse_truncated(data.get(offset..)?).ok().map(|tcp| tcp.sequence_number())
+    }
+
+    pub fn is_tcp(&self) -> bool {
+        self.ip_proto == Some(6)
+    }
+
+    pub fn is_udp(&self) -> bool {
+        self.ip_proto == Some(17)
+    }
+
+    pub fn is_sctp(&self) -> bool {
+        self.ip_proto == Some(132)
+    }
+
+    /// True when either port matches
+    pub fn has_port(&self, port: u16) -> bool {
+        self.src_port == Some(port) || self.dst_port == Some(port)
+    }
+
+    pub fn protocol_name(&self) -> &'static str {
+        match (self.ether_type, self.ip_proto) {
+            (_, Some(6)) => "TCP",
+            (_, Some(17)) => "UDP",
+            (_, Some(132)) => "SCTP",
+            (_, Some(1)) => "ICMP",
+            (_, Some(58)) => "ICMPv6",
+            (0x0800, _) => "IPv4",
+            (0x86DD, _) => "IPv6",
+            (0x0806, _) => "ARP",
+            _ => "Ethernet",
+        }
+    }
+}
+
+/// Walks IPv6 extension headers, returning the upper-layer protocol and the
+/// total header length
+pub(crate) fn skip_ipv6_extensions(ip: &[u8]) -> (u8, usize) {
+    let mut next_header = ip[6];
+    let mut offset = 40;
+    loop {
+        match next_header {
+            0 | 43 | 60 if ip.len() >= offset + 8 => {
+                next_header = ip[offset];
+                offset += (ip[offset + 1] as usize + 1) * 8;
+            }
+            44 if ip.len() >= offset + 8 => {
+                let fragment_offset = u16::from_be_bytes([ip[offset + 2], ip[offset + 3]]) >> 3;
+                next_header = ip[offset];
+                offset += 8;
+                if fragment_offset != 0 {
+                    // Non-first fragment: no transport header here
+                    return (next_header, ip.len());
+                }
+            }
+            _ => return (next_header, offset.min(ip.len())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn summarizes_tcp_and_udp() {
+        let frame = testutil::ipv4_tcp_frame(40000, 443, testutil::TCP_SYN, b"abc");
+        let summary = PacketSummary::from_ethernet(&frame).unwrap();
+        assert_eq!(summary.src_ip, Some(IpAddr::V4(testutil::client_v4())));
+        assert_eq!(summary.dst_port, Some(443));
+        assert_eq!(summary.tcp_flags, Some(testutil::TCP_SYN));
+        assert_eq!(summary.payload(&frame), b"abc");
+        assert_eq!(summary.protocol_name(), "TCP");
+        assert_eq!(summary.transport_offset, Some(34));
+        assert!(summary.tcp_seq(&frame).is_some());
+        assert_eq!(summary.missing_bytes(&frame), 0);
+        assert_eq!(summary.missing_bytes(&frame[..frame.len() - 2]), 2);
+
+        let frame = testutil::ipv6_udp_frame(5353, 53, b"q");
+        let summary = PacketSummary::from_ethernet(&frame).unwrap();
+        assert!(summary.is_udp());
+        assert!(summary.has_port(53));
+        assert_eq!(summary.payload(&frame), b"q");
+    }
+
+    #[test]
+    fn skips_vlan_tags() {
+        let inner = testutil::ipv4_udp_frame(1, 2, b"");
+        let mut tagged = inner[..12].to_vec();
+        tagged.extend_from_slice(&[0x81, 0x00, 0x00, 0x64]);
+        tagged.extend_from_slice(&inner[12..]);
+        let summary = PacketSummary::from_ethernet(&tagged).unwrap();
+        assert_eq!(summary.vlan, Some(100));
+        assert_eq!(summary.ether_type, 0x0800);
+        assert_eq!(summary.dst_port, Some(2));
+    }
+
+    #[test]
+    fn summarizes_cooked_captures() {
+        let frame = testutil::ipv4_udp_frame(5000, 53, b"q");
+        let mut sll2 = vec![0x08, 0x00, 0, 0, 0, 0, 0, 2, 0, 1, 0, 6];
+        sll2.extend_from_slice(&frame[6..12]);
+        sll2.extend_from_slice(&[0, 0]);
+        sll2.extend_from_slice(&frame[14..]);
+        let summary = PacketSummary::from_link(DLT_LINUX_SLL2, &sll2).unwrap();
+        assert_eq!(summary.src_mac, MacAddress::new(testutil::SRC_MAC));
+        assert_eq!(summary.dst_port, Some(53));
+        assert_eq!(summary.payload(&sll2), b"q");
+        assert!(PacketSummary::from_link(105, &sll2).is_none());
+
+        let mut utun = vec![30, 0, 0, 0];
+        utun.extend_from_slice(&testutil::ipv6_udp_frame(5353, 53, b"q")[14..]);
+        let summary = PacketSummary::from_link(DLT_NULL, &utun).unwrap();
+        assert_eq!((summary.protocol_name(), summary.dst_port), ("UDP", Some(53)));
+    }
+
+    #[test]
+    fn handles_arp() {
+        let frame = testutil::arp_request(testutil::client_v4(), testutil::server_v4());
+        let summary = PacketSummary::from_ethernet(&frame).unwrap();
+        assert_eq!(summary.protocol_name(), "ARP");
+        assert_eq!(summary.src_ip, None);
+    }
+}
@@ -0,0 +1,219 @@
+use super::frame_control::ControlField;
+use super::summary::PacketSummary;
+use std::fmt;
+
+pub const TACACS_PORT: u16 = 49;
+
+pub const TYPE_AUTHEN: u8 = 0x01;
+pub const TYPE_AUTHOR: u8 = 0x02;
+pub const TYPE_ACCT: u8 = 0x03;
+
+/// Body is sent in cleartext instead of being obfuscated with the shared key
+pub const FLAG_UNENCRYPTED: u8 = 0x01;
+pub const FLAG_SINGLE_CONNECT: u8 = 0x04;
+
+/// TACACS+ packet parser (RFC 8907)
+pub struct TacacsPacket<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum TacacsError {
+    TooShort,
+    UnsupportedVersion(u8),
+    UnknownType(u8),
+}
+
+impl fmt::Display for TacacsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TacacsError::TooShort => write!(f, "Packet too short for TACACS+ header"),
+            TacacsError::UnsupportedVersion(v) => write!(f, "Unsupported TACACS+ version 0x{:02x}", v),
+            TacacsError::UnknownType(t) => write!(f, "Unknown TACACS+ packet type {}", t),
+        }
+    }
+}
+
+impl<'a> TacacsPacket<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, TacacsError> {
+        if data.len() < 12 {
+            return Err(TacacsError::TooShort);
+        }
+        // Major version 0xc, minor 0 or 1
+        if !matches!(data[0], 0xC0 | 0xC1) {
+            return Err(TacacsError::UnsupportedVersion(data[0]));
+        }
+        if !matches!(data[1], TYPE_AUTHEN | TYPE_AUTHOR | TYPE_ACCT) {
+            return Err(TacacsError::UnknownType(data[1]));
+        }
+        Ok(TacacsPacket { data })
+    }
+
+    pub fn version(&self) -> u8 {
+        self.data[0]
+    }
+
+    pub fn packet_type(&self) -> u8 {
+        self.data[1]
+    }
+
+    pub fn sequence(&self) -> u8 {
+        self.data[2]
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.data[3]
+    }
+
+    pub fn session_id(&self) -> u32 {
+        u32::from_be_bytes([self.data[4], self.data[5], self.data[6], self.data[7]])
+    }
+
+    pub fn body_length(&self) -> u32 {
+        u32::from_be_bytes([self.data[8], self.data[9], self.data[10], self.data[11]])
+    }
+
+    pub fn is_unencrypted(&self) -> bool {
+        self.flags() & FLAG_UNENCRYPTED != 0
+    }
+
+    /// Requests have odd sequence numbers, replies even ones
+    pub fn is_request(&self) -> bool {
+        self.sequence() % 2 == 1
+    }
+
+    pub fn body(&self) -> &'a [u8] {
+        let end = (12 + self.body_length() as usize).min(self.data.len());
+        &self.data[12..end]
+    }
+
+    pub fn get_type_name(&self) -> String {
+        match self.packet_type() {
+            TYPE_AUTHEN => "Authentication".to_string(),
+            TYPE_AUTHOR => "Authorization".to_string(),
+            TYPE_ACCT => "Accounting".to_string(),
+            t => format!("Unknown ({})", t),
+        }
+    }
+
+    /// User name of an authentication START, authorization REQUEST or
+    /// accounting REQUEST, readable only when the body is unencrypted
+    pub fn user(&self) -> Option<String> {
+        if !self.is_unencrypted() || self.sequence() != 1 {
+            return None;
+        }
+        let body = self.body();
+        let (user_len, start) = match self.packet_type() {
+            // action, priv_lvl, authen_type, service, user_len, port_len, rem_addr_len, data_len
+            TYPE_AUTHEN => (*body.get(4)? as usize, 8),
+            // authen_method, priv_lvl, authen_type, service, user_len, port_len, rem_addr_len, arg_cnt, arg lengths
+            TYPE_AUTHOR => (*body.get(4)? as usize, 8 + *body.get(7)? as usize),
+            // flags, then the authorization layout
+            _ => (*body.get(5)? as usize, 9 + *body.get(8)? as usize),
+        };
+        body.get(start..start + user_len).map(|user| String::from_utf8_lossy(user).into_owned())
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut flags = Vec::new();
+        if self.is_unencrypted() {
+            flags.push("UNENCRYPTED");
+        }
+        if self.flags() & FLAG_SINGLE_CONNECT != 0 {
+            flags.push("SINGLE_CONNECT");
+        }
+        let mut fields = vec![
+            ControlField {
+                name: "TACACS+ Type".to_string(),
+                value: self.get_type_name(),
+                description: format!(
+                    "Version {}.{}, {} #{}",
+                    self.version() >> 4,
+                    self.version() & 0x0F,
+                    if self.is_request() { "request" } else { "reply" },
+                    self.sequence()
+                ),
+            },
+            ControlField {
+                name: "TACACS+ Session".to_string(),
+                value: format!("0x{:08x}", self.session_id()),
+                description: format!("{} byte body", self.body_length()),
+            },
+            ControlField {
+                name: "TACACS+ Flags".to_string(),
+                value: format!("0x{:02x}", self.flags()),
+                description: if flags.is_empty() { "Body obfuscated".to_string() } else { flags.join(", ") },
+            },
+        ];
+        if let Some(user) = self.user() {
+            fields.push(ControlField {
+                name: "TACACS+ User".to_string(),
+                value: user,
+                description: "Sent in cleartext".to_string(),
+            });
+        }
+        fields
+    }
+}
+
+/// Alert text for the first packet of a TACACS+ session whose body is not
+/// obfuscated, exposing user names, passwords and commands
+pub fn unencrypted_session_alert(summary: &PacketSummary, data: &[u8]) -> Option<String> {
+    if !summary.is_tcp() || !summary.has_port(TACACS_PORT) {
+        return None;
+    }
+    let packet = TacacsPacket::parse(summary.payload(data)).ok()?;
+    if !packet.is_unencrypted() || packet.sequence() != 1 {
+        return None;
+    }
+    Some(format!(
+        "Unencrypted TACACS+ {} session 0x{:08x}: {} -> {}{}",
+        packet.get_type_name().to_lowercase(),
+        packet.session_id(),
+        summary.src_ip?,
+        summary.dst_ip?,
+        packet.user().map(|user| format!(" user '{}'", user)).unwrap_or_default()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    fn authen_start(flags: u8, user: &str) -> Vec<u8> {
+        let mut body = vec![0x01, 0x01, 0x01, 0x01, user.len() as u8, 0, 0, 0];
+        body.extend_from_slice(user.as_bytes());
+        let mut packet = vec![0xC1, TYPE_AUTHEN, 1, flags, 0xde, 0xad, 0xbe, 0xef];
+        packet.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        packet.extend_from_slice(&body);
+        packet
+    }
+
+    #[test]
+    fn decodes_header_and_cleartext_user() {
+        let data = authen_start(FLAG_UNENCRYPTED, "netadmin");
+        let packet = TacacsPacket::parse(&data).unwrap();
+        assert_eq!(packet.get_type_name(), "Authentication");
+        assert_eq!(packet.session_id(), 0xdeadbeef);
+        assert!(packet.is_request());
+        assert_eq!(packet.user().as_deref(), Some("netadmin"));
+
+        let obfuscated = authen_start(0, "netadmin");
+        assert_eq!(TacacsPacket::parse(&obfuscated).unwrap().user(), None);
+        assert!(matches!(TacacsPacket::parse(&[0x16; 12]), Err(TacacsError::UnsupportedVersion(0x16))));
+    }
+
+    #[test]
+    fn alerts_on_unencrypted_sessions() {
+        let flags = testutil::TCP_PSH | testutil::TCP_ACK;
+        let frame = testutil::ipv4_tcp_frame(50000, TACACS_PORT, flags, &authen_start(FLAG_UNENCRYPTED, "netadmin"));
+        let summary = PacketSummary::from_ethernet(&frame).unwrap();
+        let alert = unencrypted_session_alert(&summary, &frame).unwrap();
+        assert!(alert.starts_with("Unencrypted TACACS+ authentication session 0xdeadbeef"));
+        assert!(alert.ends_with("user 'netadmin'"));
+
+        let frame = testutil::ipv4_tcp_frame(50000, TACACS_PORT, flags, &authen_start(0, "netadmin"));
+        assert!(unencrypted_session_alert(&PacketSummary::from_ethernet(&frame).unwrap(), &frame).is_none());
+    }
+}
@@ -0,0 +1,302 @@
+use super::frame_control::ControlField;
+use std::fmt;
+
+/// IP protocol number of TCP
+pub const IP_PROTO_TCP: u8 = 6;
+
+pub const FLAG_FIN: u16 = 0x001;
+pub const FLAG_SYN: u16 = 0x002;
+pub const FLAG_RST: u16 = 0x004;
+pub const FLAG_PSH: u16 = 0x008;
+pub const FLAG_ACK: u16 = 0x010;
+pub const FLAG_URG: u16 = 0x020;
+pub const FLAG_ECE: u16 = 0x040;
+pub const FLAG_CWR: u16 = 0x080;
+/// ECN nonce (RFC 3540, historic)
+pub const FLAG_NS: u16 = 0x100;
+
+const FLAG_NAMES: [(u16, &str); 9] = [
+    (FLAG_NS, "NS"),
+    (FLAG_CWR, "CWR"),
+    (FLAG_ECE, "ECE"),
+    (FLAG_URG, "URG"),
+    (FLAG_ACK, "ACK"),
+    (FLAG_PSH, "PSH"),
+    (FLAG_RST, "RST"),
+    (FLAG_SYN, "SYN"),
+    (FLAG_FIN, "FIN"),
+];
+
+/// TCP segment parser
+pub struct TcpSegment<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum TcpError {
+    TooShort,
+    InvalidDataOffset,
+}
+
+impl fmt::Display for TcpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TcpError::TooShort => write!(f, "Packet too short for TCP header"),
+            TcpError::InvalidDataOffset => write!(f, "Invalid TCP data offset"),
+        }
+    }
+}
+
+/// An option from the TCP header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TcpOption {
+    /// Maximum segment size
+    Mss(u16),
+    /// Shift count of the window (RFC 7323)
+    WindowScale(u8),
+    SackPermitted,
+    /// Blocks received out of order, as left and right edges
+    Sack(Vec<(u32, u32)>),
+    Timestamps { value: u32, echo_reply: u32 },
+    Unknown { kind: u8, length: u8 },
+}
+
+impl fmt::Display for TcpOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TcpOption::Mss(mss) => write!(f, "MSS={}", mss),
+            TcpOption::WindowScale(shift) => write!(f, "WS={}", shift),
+            TcpOption::SackPermitted => write!(f, "SACK_PERM"),
+            TcpOption::Sack(blocks) => {
+                let blocks: Vec<String> = blocks.iter().map(|(left, right)| format!("{}-{}", left, right)).collect();
+                write!(f, "SACK={}", blocks.join(","))
+            }
+            TcpOption::Timestamps { value, echo_reply } => write!(f, "TSval={} TSecr={}", value, echo_reply),
+            TcpOption::Unknown { kind, length } => write!(f, "kind {} ({} bytes)", kind, length),
+        }
+    }
+}
+
+impl<'a> TcpSegment<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, TcpError> {
+        if data.len() < 20 {
+            return Err(TcpError::TooShort);
+        }
+        let header_length = (data[12] >> 4) as usize * 4;
+        if header_length < 20 {
+            return Err(TcpError::InvalidDataOffset);
+        }
+        if header_length > data.len() {
+            return Err(TcpError::TooShort);
+        }
+        Ok(TcpSegment { data })
+    }
+
+    /// Like `parse`, but accepts options cut off by the snaplen; the options
+    /// and payload are then whatever was captured
+    pub fn parse_truncated(data: &'a [u8]) -> Result<Self, TcpError> {
+        match Self::parse(data) {
+            Err(TcpError::TooShort) if data.len() >= 20 => Ok(TcpSegment { data }),
+            result => result,
+        }
+    }
+
+    pub fn source_port(&self) -> u16 {
+        u16::from_be_bytes([self.data[0], self.data[1]])
+    }
+
+    pub fn destination_port(&self) -> u16 {
+        u16::from_be_bytes([self.data[2], self.data[3]])
+    }
+
+    pub fn sequence_number(&self) -> u32 {
+        u32::from_be_bytes([self.data[4], self.data[5], self.data[6], self.data[7]])
+    }
+
+    pub fn acknowledgment_number(&self) -> u32 {
+        u32::from_be_bytes([self.data[8], self.data[9], self.data[10], self.data[11]])
+    }
+
+    /// Header length in bytes, options included
+    pub fn header_length(&self) -> usize {
+        (self.data[12] >> 4) as usize * 4
+    }
+
+    /// The nine flag bits, NS highest
+    pub fn flags(&self) -> u16 {
+        (((self.data[12] & 0x01) as u16) << 8) | self.data[13] as u16
+    }
+
+    pub fn has_flag(&self, flag: u16) -> bool {
+        self.flags() & flag != 0
+    }
+
+    /// Receive window, before any window scaling
+    pub fn window(&self) -> u16 {
+        u16::from_be_bytes([self.data[14], self.data[15]])
+    }
+
+    pub fn checksum(&self) -> u16 {
+        u16::from_be_bytes([self.data[16], self.data[17]])
+    }
+
+    pub fn urgent_pointer(&self) -> u16 {
+        u16::from_be_bytes([self.data[18], self.data[19]])
+    }
+
+    /// Options in header order; parsing stops at End of Option List or a malformed option
+    pub fn options(&self) -> Vec<TcpOption> {
+        let mut options = Vec::new();
+        let mut rest = &self.data[20..self.header_end()];
+        while let Some(&kind) = rest.first() {
+            match kind {
+                0 => break,
+                1 => {
+                    rest = &rest[1..];
+                    continue;
+                }
+                _ => {}
+            }
+            let Some(&length) = rest.get(1) else { break };
+            if length < 2 || length as usize > rest.len() {
+                break;
+            }
+            let value = &rest[2..length as usize];
+            let u32_at = |i: usize| u32::from_be_bytes([value[i], value[i + 1], value[i + 2], value[i + 3]]);
+            options.push(match (kind, value.len()) {
+                (2, 2) => TcpOption::Mss(u16::from_be_bytes([value[0], value[1]])),
+                (3, 1) => TcpOption::WindowScale(value[0]),
+                (4, 0) => TcpOption::SackPermitted,
+                (5, len) if len % 8 == 0 => TcpOption::Sack((0..len / 8).map(|i| (u32_at(i * 8), u32_at(i * 8 + 4))).collect()),
+                (8, 8) => TcpOption::Timestamps { value: u32_at(0), echo_reply: u32_at(4) },
+                _ => TcpOption::Unknown { kind, length },
+            });
+            rest = &rest[length as usize..];
+        }
+        options
+    }
+
+    /// Get payload data following the header
+    pub fn payload(&self) -> &[u8] {
+        &self.data[self.header_end()..]
+    }
+
+    /// End of the captured header, short of `header_length` when truncated
+    fn header_end(&self) -> usize {
+        self.header_length().min(self.data.len())
+    }
+
+    /// Names of the flags set, e.g. `SYN, ACK`
+    pub fn get_flags_description(&self) -> String {
+        let names: Vec<&str> = FLAG_NAMES.iter().filter(|(flag, _)| self.has_flag(*flag)).map(|(_, name)| *name).collect();
+        if names.is_empty() {
+            "None".to_string()
+        } else {
+            names.join(", ")
+        }
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![
+            ControlField {
+                name: "Source Port".to_string(),
+                value: self.source_port().to_string(),
+                description: "TCP source port".to_string(),
+            },
+            ControlField {
+                name: "Destination Port".to_string(),
+                value: self.destination_port().to_string(),
+                description: "TCP destination port".to_string(),
+            },
+            ControlField {
+                name: "Sequence Number".to_string(),
+                value: self.sequence_number().to_string(),
+                description: "Position of the first payload byte in the sender's stream".to_string(),
+            },
+            ControlField {
+                name: "Acknowledgment Number".to_string(),
+                value: self.acknowledgment_number().to_string(),
+                description: if self.has_flag(FLAG_ACK) { "Next byte expected from the peer" } else { "Not set (no ACK flag)" }.to_string(),
+            },
+            ControlField {
+                name: "TCP Header Length".to_string(),
+                value: self.header_length().to_string(),
+                description: "TCP header length in bytes, options included".to_string(),
+            },
+            ControlField {
+                name: "TCP Flags".to_string(),
+                value: format!("0x{:03x}", self.flags()),
+                description: self.get_flags_description(),
+            },
+            ControlField {
+                name: "Window".to_string(),
+                value: self.window().to_string(),
+                description: "Receive window, before window scaling".to_string(),
+            },
+            ControlField {
+                name: "TCP Checksum".to_string(),
+                value: format!("0x{:04x}", self.checksum()),
+                description: "Checksum over the pseudo-header, header and payload".to_string(),
+            },
+        ];
+        if self.has_flag(FLAG_URG) {
+            fields.push(ControlField {
+                name: "Urgent Pointer".to_string(),
+                value: self.urgent_pointer().to_string(),
+                description: "Offset of the last urgent byte".to_string(),
+            });
+        }
+        for option in self.options() {
+            let (name, description) = match option {
+                TcpOption::Mss(_) => ("TCP MSS", "Largest segment the sender accepts"),
+                TcpOption::WindowScale(_) => ("TCP Window Scale", "Shift applied to the peer's window"),
+                TcpOption::SackPermitted => ("TCP SACK Permitted", "Selective acknowledgments may be used"),
+                TcpOption::Sack(_) => ("TCP SACK", "Blocks received beyond the acknowledgment number"),
+                TcpOption::Timestamps { .. } => ("TCP Timestamps", "For RTT measurement and protection against wrapped sequence numbers"),
+                TcpOption::Unknown { .. } => ("TCP Option", "Unrecognized option"),
+            };
+            fields.push(ControlField { name: name.to_string(), value: option.to_string(), description: description.to_string() });
+        }
+        fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn parses_header_and_options() {
+        let mut header = testutil::tcp(40000, 443, 1000, 0, testutil::TCP_SYN, &[]);
+        // MSS 1460, SACK permitted, timestamps, NOP, window scale 7
+        let options = [2, 4, 0x05, 0xb4, 4, 2, 8, 10, 0, 0, 0, 1, 0, 0, 0, 0, 1, 3, 3, 7];
+        header[12] = (((20 + options.len()) / 4) << 4) as u8;
+        header.splice(20..20, options);
+        header.extend_from_slice(b"hi");
+
+        let segment = TcpSegment::parse(&header).unwrap();
+        assert_eq!((segment.source_port(), segment.destination_port()), (40000, 443));
+        assert_eq!(segment.header_length(), 40);
+        assert_eq!(segment.get_flags_description(), "SYN");
+        assert_eq!(
+            segment.options(),
+            [TcpOption::Mss(1460), TcpOption::SackPermitted, TcpOption::Timestamps { value: 1, echo_reply: 0 }, TcpOption::WindowScale(7)]
+        );
+        assert_eq!(segment.payload(), b"hi");
+        let fields = segment.get_control_fields();
+        assert!(fields.iter().any(|f| f.name == "TCP Timestamps" && f.value == "TSval=1 TSecr=0"));
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let mut header = testutil::tcp(1, 2, 1000, 1, testutil::TCP_ACK, &[]);
+        assert!(matches!(TcpSegment::parse(&header[..19]), Err(TcpError::TooShort)));
+        header[12] = 0x40;
+        assert!(matches!(TcpSegment::parse(&header), Err(TcpError::InvalidDataOffset)));
+        header[12] = 0x60;
+        assert!(matches!(TcpSegment::parse(&header), Err(TcpError::TooShort)));
+        let truncated = TcpSegment::parse_truncated(&header).unwrap();
+        assert_eq!((truncated.sequence_number(), truncated.options(), truncated.payload()), (1000, Vec::new(), &[][..]));
+    }
+}
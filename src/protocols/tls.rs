@@ -0,0 +1,427 @@
+use super::frame_control::ControlField;
+use std::fmt;
+
+pub const TLS_PORT: u16 = 443;
+
+pub const CONTENT_CHANGE_CIPHER_SPEC: u8 = 20;
+pub const CONTENT_ALERT: u8 = 21;
+pub const CONTENT_HANDSHAKE: u8 = 22;
+pub const CONTENT_APPLICATION_DATA: u8 = 23;
+
+pub const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+pub const HANDSHAKE_SERVER_HELLO: u8 = 2;
+
+const EXT_SERVER_NAME: u16 = 0;
+const EXT_SUPPORTED_GROUPS: u16 = 10;
+const EXT_EC_POINT_FORMATS: u16 = 11;
+const EXT_ALPN: u16 = 16;
+const EXT_SUPPORTED_VERSIONS: u16 = 43;
+
+/// Largest record payload allowed, with room for expansion by the cipher
+const MAX_RECORD_LENGTH: usize = 16384 + 2048;
+
+/// Records reported per segment; bulk transfers pack many application data records
+const MAX_RECORDS: usize = 8;
+
+/// TLS record parser. The record may be cut short by the end of the segment;
+/// the fragment then holds what was captured.
+pub struct TlsRecord<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum TlsError {
+    TooShort,
+    NotTls,
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsError::TooShort => write!(f, "Data too short for TLS record header"),
+            TlsError::NotTls => write!(f, "Not a TLS record header"),
+        }
+    }
+}
+
+impl<'a> TlsRecord<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, TlsError> {
+        if data.len() < 5 {
+            return Err(TlsError::TooShort);
+        }
+        let length = u16::from_be_bytes([data[3], data[4]]) as usize;
+        if !(CONTENT_CHANGE_CIPHER_SPEC..=24).contains(&data[0]) || data[1] != 3 || data[2] > 4 || length > MAX_RECORD_LENGTH {
+            return Err(TlsError::NotTls);
+        }
+        Ok(TlsRecord { data: &data[..(5 + length).min(data.len())] })
+    }
+
+    pub fn content_type(&self) -> u8 {
+        self.data[0]
+    }
+
+    /// Record layer version; TLS 1.3 keeps 0x0303 here
+    pub fn version(&self) -> u16 {
+        u16::from_be_bytes([self.data[1], self.data[2]])
+    }
+
+    /// Length of the fragment as announced in the header
+    pub fn length(&self) -> usize {
+        u16::from_be_bytes([self.data[3], self.data[4]]) as usize
+    }
+
+    /// The captured part of the fragment
+    pub fn fragment(&self) -> &'a [u8] {
+        &self.data[5..]
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.fragment().len() < self.length()
+    }
+
+    pub fn get_content_type_name(&self) -> String {
+        match self.content_type() {
+            CONTENT_CHANGE_CIPHER_SPEC => "ChangeCipherSpec".to_string(),
+            CONTENT_ALERT => "Alert".to_string(),
+            CONTENT_HANDSHAKE => "Handshake".to_string(),
+            CONTENT_APPLICATION_DATA => "Application Data".to_string(),
+            24 => "Heartbeat".to_string(),
+            other => format!("Unknown ({})", other),
+        }
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![ControlField {
+            name: "TLS Record".to_string(),
+            value: self.get_content_type_name(),
+            description: format!(
+                "{} record, {} bytes{}",
+                version_name(self.version()),
+                self.length(),
+                if self.is_truncated() { " (continues in the next segment)" } else { "" }
+            ),
+        }];
+        if self.content_type() == CONTENT_HANDSHAKE {
+            fields.extend(handshake_fields(self.fragment()));
+        }
+        fields
+    }
+}
+
+/// The records at the start of a segment, up to the first that isn't one
+pub fn records(payload: &[u8]) -> Vec<TlsRecord<'_>> {
+    let mut out = Vec::new();
+    let mut rest = payload;
+    while let Ok(record) = TlsRecord::parse(rest) {
+        rest = &rest[record.data.len()..];
+        out.push(record);
+    }
+    out
+}
+
+/// Control fields for the TLS records in one TCP payload
+pub fn control_fields(payload: &[u8]) -> Vec<ControlField> {
+    records(payload).iter().take(MAX_RECORDS).flat_map(|record| record.get_control_fields()).collect()
+}
+
+/// What a client offers in its ClientHello
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientHello {
+    /// Legacy version field, 0x0303 for TLS 1.2 and 1.3
+    pub version: u16,
+    pub cipher_suites: Vec<u16>,
+    pub server_name: Option<String>,
+    pub alpn: Vec<String>,
+    /// From the supported_versions extension, which TLS 1.3 clients send
+    pub supported_versions: Vec<u16>,
+    /// Extension types in the order sent, GREASE included
+    pub extensions: Vec<u16>,
+    /// Named groups (curves) from the supported_groups extension
+    pub supported_groups: Vec<u16>,
+    pub ec_point_formats: Vec<u8>,
+}
+
+impl ClientHello {
+    /// Parses a ClientHello handshake message, header included. Extensions
+    /// cut off by the end of the data are left out.
+    pub fn parse(handshake: &[u8]) -> Option<Self> {
+        if handshake.first() != Some(&HANDSHAKE_CLIENT_HELLO) {
+            return None;
+        }
+        let body = handshake.get(4..)?;
+        let mut hello = ClientHello { version: u16::from_be_bytes([*body.first()?, *body.get(1)?]), ..ClientHello::default() };
+        let mut pos = 2 + 32; // version, random
+        pos += 1 + *body.get(pos)? as usize; // session id
+        let suites_len = u16::from_be_bytes([*body.get(pos)?, *body.get(pos + 1)?]) as usize;
+        let suites = body.get(pos + 2..pos + 2 + suites_len)?;
+        hello.cipher_suites = suites.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).filter(|&s| !is_grease(s)).collect();
+        pos += 2 + suites_len;
+        pos += 1 + *body.get(pos)? as usize; // compression methods
+
+        for (ext_type, data) in extensions(body.get(pos..).unwrap_or_default()) {
+            hello.extensions.push(ext_type);
+            match ext_type {
+                EXT_SERVER_NAME if data.len() >= 5 && data[2] == 0 => {
+                    let name_len = u16::from_be_bytes([data[3], data[4]]) as usize;
+                    hello.server_name = data.get(5..5 + name_len).map(|n| String::from_utf8_lossy(n).into_owned());
+                }
+                EXT_SUPPORTED_GROUPS if data.len() >= 2 => {
+                    hello.supported_groups = data[2..].chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+                }
+                EXT_EC_POINT_FORMATS if !data.is_empty() => hello.ec_point_formats = data[1..].to_vec(),
+                EXT_ALPN if data.len() >= 2 => hello.alpn = protocol_names(&data[2..]),
+                EXT_SUPPORTED_VERSIONS if !data.is_empty() => {
+                    let list = data.get(1..1 + data[0] as usize).unwrap_or_default();
+                    hello.supported_versions =
+                        list.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).filter(|&v| !is_grease(v)).collect();
+                }
+                _ => {}
+            }
+        }
+        Some(hello)
+    }
+
+    /// The highest version offered
+    pub fn max_version(&self) -> u16 {
+        self.supported_versions.iter().copied().max().unwrap_or(self.version)
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let offered: Vec<String> = self.supported_versions.iter().map(|&v| version_name(v)).collect();
+        let mut fields = vec![
+            ControlField {
+                name: "TLS Version".to_string(),
+                value: version_name(self.max_version()),
+                description: if offered.is_empty() { "Highest version offered".to_string() } else { format!("Offered: {}", offered.join(", ")) },
+            },
+            ControlField {
+                name: "TLS Cipher Suites".to_string(),
+                value: format!("{} offered", self.cipher_suites.len()),
+                description: self.cipher_suites.iter().map(|&s| cipher_suite_name(s)).collect::<Vec<_>>().join(", "),
+            },
+        ];
+        if let Some(name) = &self.server_name {
+            fields.push(ControlField {
+                name: "TLS SNI".to_string(),
+                value: name.clone(),
+                description: "Server name the client asked for".to_string(),
+            });
+        }
+        if !self.alpn.is_empty() {
+            fields.push(ControlField {
+                name: "TLS ALPN".to_string(),
+                value: self.alpn.join(","),
+                description: "Offered application protocols".to_string(),
+            });
+        }
+        fields
+    }
+}
+
+/// What the server picked in its ServerHello
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerHello {
+    pub version: u16,
+    pub cipher_suite: u16,
+    pub alpn: Option<String>,
+    /// From the supported_versions extension of a TLS 1.3 server
+    pub selected_version: Option<u16>,
+}
+
+impl ServerHello {
+    /// Parses a ServerHello handshake message, header included
+    pub fn parse(handshake: &[u8]) -> Option<Self> {
+        if handshake.first() != Some(&HANDSHAKE_SERVER_HELLO) {
+            return None;
+        }
+        let body = handshake.get(4..)?;
+        let mut pos = 2 + 32; // version, random
+        pos += 1 + *body.get(pos)? as usize; // session id
+        let mut hello = ServerHello {
+            version: u16::from_be_bytes([*body.first()?, *body.get(1)?]),
+            cipher_suite: u16::from_be_bytes([*body.get(pos)?, *body.get(pos + 1)?]),
+            ..ServerHello::default()
+        };
+        pos += 2 + 1; // cipher suite, compression method
+
+        for (ext_type, data) in extensions(body.get(pos..).unwrap_or_default()) {
+            match ext_type {
+                EXT_ALPN if data.len() >= 2 => hello.alpn = protocol_names(&data[2..]).into_iter().next(),
+                EXT_SUPPORTED_VERSIONS if data.len() == 2 => hello.selected_version = Some(u16::from_be_bytes([data[0], data[1]])),
+                _ => {}
+            }
+        }
+        Some(hello)
+    }
+
+    pub fn negotiated_version(&self) -> u16 {
+        self.selected_version.unwrap_or(self.version)
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![
+            ControlField {
+                name: "TLS Version".to_string(),
+                value: version_name(self.negotiated_version()),
+                description: "Negotiated version".to_string(),
+            },
+            ControlField {
+                name: "TLS Cipher Suite".to_string(),
+                value: cipher_suite_name(self.cipher_suite),
+                description: format!("Selected cipher suite 0x{:04x}", self.cipher_suite),
+            },
+        ];
+        if let Some(alpn) = &self.alpn {
+            fields.push(ControlField {
+                name: "TLS ALPN".to_string(),
+                value: alpn.clone(),
+                description: "Selected application protocol".to_string(),
+            });
+        }
+        fields
+    }
+}
+
+/// Fields of the handshake messages in a record, stopping at the first one
+/// that can't be a handshake header (as in encrypted Finished messages)
+fn handshake_fields(mut fragment: &[u8]) -> Vec<ControlField> {
+    let mut fields = Vec::new();
+    while fragment.len() >= 4 {
+        let length = u32::from_be_bytes([0, fragment[1], fragment[2], fragment[3]]) as usize;
+        let name = match fragment[0] {
+            0 => "HelloRequest",
+            HANDSHAKE_CLIENT_HELLO => "ClientHello",
+            HANDSHAKE_SERVER_HELLO => "ServerHello",
+            4 => "NewSessionTicket",
+            8 => "EncryptedExtensions",
+            11 => "Certificate",
+            12 => "ServerKeyExchange",
+            13 => "CertificateRequest",
+            14 => "ServerHelloDone",
+            15 => "CertificateVerify",
+            16 => "ClientKeyExchange",
+            20 => "Finished",
+            _ => break,
+        };
+        fields.push(ControlField { name: "TLS Handshake".to_string(), value: name.to_string(), description: format!("{} bytes", length) });
+        match fragment[0] {
+            HANDSHAKE_CLIENT_HELLO => fields.extend(ClientHello::parse(fragment).map(|h| h.get_control_fields()).unwrap_or_default()),
+            HANDSHAKE_SERVER_HELLO => fields.extend(ServerHello::parse(fragment).map(|h| h.get_control_fields()).unwrap_or_default()),
+            _ => {}
+        }
+        let Some(rest) = fragment.get(4 + length..) else { break };
+        fragment = rest;
+    }
+    fields
+}
+
+/// Extensions of a hello message, from its two-byte length prefix; a
+/// truncated extension ends the list
+fn extensions(data: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    let total = data.get(..2).map_or(0, |len| u16::from_be_bytes([len[0], len[1]]) as usize);
+    let mut rest = data.get(2..(2 + total).min(data.len())).unwrap_or_default();
+    std::iter::from_fn(move || {
+        let ext_type = u16::from_be_bytes([*rest.first()?, *rest.get(1)?]);
+        let ext_len = u16::from_be_bytes([*rest.get(2)?, *rest.get(3)?]) as usize;
+        let data = rest.get(4..4 + ext_len)?;
+        rest = &rest[4 + ext_len..];
+        Some((ext_type, data))
+    })
+}
+
+/// The length-prefixed names of an ALPN protocol list
+fn protocol_names(mut list: &[u8]) -> Vec<String> {
+    let mut names = Vec::new();
+    while let Some((&len, rest)) = list.split_first() {
+        let Some(name) = rest.get(..len as usize) else { break };
+        names.push(String::from_utf8_lossy(name).into_owned());
+        list = &rest[len as usize..];
+    }
+    names
+}
+
+/// GREASE values (RFC 8701), which clients send to keep servers tolerant of unknown values
+pub fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+pub fn version_name(version: u16) -> String {
+    match version {
+        0x0300 => "SSL 3.0".to_string(),
+        0x0301 => "TLS 1.0".to_string(),
+        0x0302 => "TLS 1.1".to_string(),
+        0x0303 => "TLS 1.2".to_string(),
+        0x0304 => "TLS 1.3".to_string(),
+        other => format!("0x{:04x}", other),
+    }
+}
+
+/// IANA name of common cipher suites, hex for the rest
+pub fn cipher_suite_name(suite: u16) -> String {
+    let name = match suite {
+        0x002f => "TLS_RSA_WITH_AES_128_CBC_SHA",
+        0x0035 => "TLS_RSA_WITH_AES_256_CBC_SHA",
+        0x009c => "TLS_RSA_WITH_AES_128_GCM_SHA256",
+        0x009d => "TLS_RSA_WITH_AES_256_GCM_SHA384",
+        0x00ff => "TLS_EMPTY_RENEGOTIATION_INFO_SCSV",
+        0x1301 => "TLS_AES_128_GCM_SHA256",
+        0x1302 => "TLS_AES_256_GCM_SHA384",
+        0x1303 => "TLS_CHACHA20_POLY1305_SHA256",
+        0xc009 => "TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA",
+        0xc00a => "TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA",
+        0xc013 => "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA",
+        0xc014 => "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA",
+        0xc02b => "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256",
+        0xc02c => "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384",
+        0xc02f => "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
+        0xc030 => "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
+        0xcca8 => "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256",
+        0xcca9 => "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256",
+        other => return format!("0x{:04x}", other),
+    };
+    name.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn parses_client_hello_record() {
+        let mut record = vec![CONTENT_HANDSHAKE, 3, 1, 0, 0];
+        record.extend(testutil::client_hello("api.example.org", &["h2", "http/1.1"]));
+        let length = (record.len() - 5) as u16;
+        record[3..5].copy_from_slice(&length.to_be_bytes());
+
+        let hello = ClientHello::parse(TlsRecord::parse(&record).unwrap().fragment()).unwrap();
+        assert_eq!(hello.server_name.as_deref(), Some("api.example.org"));
+        assert_eq!(hello.alpn, ["h2", "http/1.1"]);
+        assert_eq!((hello.cipher_suites.as_slice(), hello.max_version()), (&[0x1301][..], 0x0303));
+
+        let fields = control_fields(&record);
+        assert!(fields.iter().any(|f| f.name == "TLS Handshake" && f.value == "ClientHello"));
+        assert!(fields.iter().any(|f| f.name == "TLS SNI" && f.value == "api.example.org"));
+        assert!(fields.iter().any(|f| f.name == "TLS Cipher Suites" && f.description == "TLS_AES_128_GCM_SHA256"));
+        // The SNI comes before the cut when the hello spans two segments
+        let cut = control_fields(&record[..record.len() - 4]);
+        assert!(cut.iter().any(|f| f.name == "TLS Record" && f.description.ends_with("(continues in the next segment)")));
+        assert!(cut.iter().any(|f| f.name == "TLS SNI"));
+    }
+
+    #[test]
+    fn parses_server_hello_and_other_records() {
+        let mut payload = testutil::tls_server_hello(0xc02f);
+        payload.extend_from_slice(&[CONTENT_CHANGE_CIPHER_SPEC, 3, 3, 0, 1, 1]);
+        let records = records(&payload);
+        assert_eq!(records.len(), 2);
+        let hello = ServerHello::parse(records[0].fragment()).unwrap();
+        assert_eq!((hello.negotiated_version(), hello.cipher_suite), (0x0303, 0xc02f));
+
+        let fields = control_fields(&payload);
+        assert!(fields.iter().any(|f| f.name == "TLS Cipher Suite" && f.value == "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256"));
+        assert!(fields.iter().any(|f| f.name == "TLS Record" && f.value == "ChangeCipherSpec"));
+        assert!(matches!(TlsRecord::parse(b"GET / HTTP/1.1\r\n"), Err(TlsError::NotTls)));
+        assert!(is_grease(0x1a1a) && !is_grease(0x1301));
+    }
+}
@@ -0,0 +1,173 @@
+use super::frame_control::ControlField;
+use super::ipv6::IPv6Packet;
+use super::summary::PacketSummary;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+pub const TEREDO_PORT: u16 = 3544;
+
+/// 6to4 relay anycast prefix (RFC 3068)
+const RELAY_ANYCAST: [u8; 3] = [192, 88, 99];
+
+/// IPv6 transition mechanisms that carry IPv6 inside IPv4
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TunnelKind {
+    /// Protocol 41 with 2002::/16 addresses or to the relay anycast prefix
+    SixToFour,
+    /// Other protocol 41 (configured tunnels, tunnel brokers)
+    SixInFour,
+    /// IPv6 in UDP port 3544
+    Teredo,
+}
+
+impl TunnelKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TunnelKind::SixToFour => "6to4",
+            TunnelKind::SixInFour => "6in4",
+            TunnelKind::Teredo => "Teredo",
+        }
+    }
+}
+
+/// Finds an IPv6 packet tunnelled in an Ethernet frame, returning the
+/// mechanism and the inner packet
+pub fn detect<'a>(summary: &PacketSummary, data: &'a [u8]) -> Option<(TunnelKind, &'a [u8])> {
+    let (Some(IpAddr::V4(src)), Some(IpAddr::V4(dst))) = (summary.src_ip, summary.dst_ip) else {
+        return None;
+    };
+    let payload = summary.payload(data);
+    let inner = match summary.ip_proto? {
+        41 => payload,
+        17 if summary.has_port(TEREDO_PORT) => teredo_inner(payload)?,
+        _ => return None,
+    };
+    let ipv6 = IPv6Packet::parse(inner).ok()?;
+    let kind = match summary.ip_proto? {
+        17 => TunnelKind::Teredo,
+        _ if [src, dst].iter().any(|a| a.octets()[..3] == RELAY_ANYCAST)
+            || [ipv6.source_ip(), ipv6.destination_ip()].iter().any(|a| a.segments()[0] == 0x2002) =>
+        {
+            TunnelKind::SixToFour
+        }
+        _ => TunnelKind::SixInFour,
+    };
+    Some((kind, inner))
+}
+
+/// IPv6 packet in a Teredo datagram, after any authentication and origin
+/// indication headers (RFC 4380 section 5.1.1)
+pub fn teredo_inner(payload: &[u8]) -> Option<&[u8]> {
+    let mut rest = payload;
+    loop {
+        match rest {
+            [0x00, 0x01, id_len, auth_len, ..] => {
+                // Client identifier, authentication value, nonce (8) and confirmation (1)
+                let len = 4 + *id_len as usize + *auth_len as usize + 9;
+                rest = rest.get(len..)?;
+            }
+            [0x00, 0x00, ..] => rest = rest.get(8..)?,
+            _ => break,
+        }
+    }
+    (rest.len() >= 40 && rest[0] >> 4 == 6).then_some(rest)
+}
+
+/// Teredo server, client's public address and port encoded in a 2001::/32 address
+pub fn teredo_mapping(addr: Ipv6Addr) -> Option<(Ipv4Addr, Ipv4Addr, u16)> {
+    let s = addr.segments();
+    if s[0] != 0x2001 || s[1] != 0 {
+        return None;
+    }
+    let server = Ipv4Addr::from(((s[2] as u32) << 16) | s[3] as u32);
+    let port = s[5] ^ 0xffff;
+    let client = Ipv4Addr::from((((s[6] as u32) << 16) | s[7] as u32) ^ 0xffff_ffff);
+    Some((server, client, port))
+}
+
+/// Tunnel type, Teredo mapping and the inner IPv6 header fields
+pub fn control_fields(summary: &PacketSummary, data: &[u8]) -> Vec<ControlField> {
+    let Some((kind, inner)) = detect(summary, data) else { return Vec::new() };
+    let Ok(ipv6) = IPv6Packet::parse(inner) else { return Vec::new() };
+    let mut fields = vec![ControlField {
+        name: "Tunnel".to_string(),
+        value: kind.name().to_string(),
+        description: "IPv6 carried over IPv4".to_string(),
+    }];
+    let teredo = [ipv6.source_ip(), ipv6.destination_ip()].into_iter().find_map(teredo_mapping);
+    if let (TunnelKind::Teredo, Some((server, client, port))) = (kind, teredo) {
+        fields.push(ControlField {
+            name: "Teredo Mapping".to_string(),
+            value: format!("{}:{}", client, port),
+            description: format!("Client public address, via server {}", server),
+        });
+    }
+    fields.extend(ipv6.get_control_fields().into_iter().map(|field| ControlField { name: format!("Inner {}", field.name), ..field }));
+    fields
+}
+
+/// Alert text for a tunnelled packet whose mechanism is not in `allowed`
+pub fn policy_alert(summary: &PacketSummary, data: &[u8], allowed: &[TunnelKind]) -> Option<String> {
+    let (kind, inner) = detect(summary, data)?;
+    if allowed.contains(&kind) {
+        return None;
+    }
+    let ipv6 = IPv6Packet::parse(inner).ok()?;
+    Some(format!(
+        "Policy alert: {} tunnel {} -> {} carrying {} -> {}",
+        kind.name(),
+        summary.src_ip?,
+        summary.dst_ip?,
+        ipv6.source_ip(),
+        ipv6.destination_ip()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    fn inner(src: Ipv6Addr) -> Vec<u8> {
+        testutil::ipv6(src, testutil::server_v6(), 17, &testutil::udp(1, 2, b""))
+    }
+
+    fn frame(src: Ipv4Addr, dst: Ipv4Addr, protocol: u8, payload: &[u8]) -> Vec<u8> {
+        let ip = testutil::ipv4(src, dst, protocol, payload);
+        testutil::ethernet(testutil::SRC_MAC, testutil::DST_MAC, testutil::ETHERTYPE_IPV4, &ip)
+    }
+
+    #[test]
+    fn classifies_protocol_41() {
+        let six_to_four = frame(testutil::client_v4(), Ipv4Addr::new(192, 88, 99, 1), 41, &inner(testutil::client_v6()));
+        let summary = PacketSummary::from_ethernet(&six_to_four).unwrap();
+        assert_eq!(detect(&summary, &six_to_four).unwrap().0, TunnelKind::SixToFour);
+
+        let configured = frame(testutil::client_v4(), testutil::server_v4(), 41, &inner(testutil::client_v6()));
+        let summary = PacketSummary::from_ethernet(&configured).unwrap();
+        assert_eq!(detect(&summary, &configured).unwrap().0, TunnelKind::SixInFour);
+        let fields = control_fields(&summary, &configured);
+        assert!(fields.iter().any(|f| f.name == "Inner Next Header" && f.description == "UDP"));
+
+        assert!(policy_alert(&summary, &configured, &[TunnelKind::SixInFour]).is_none());
+        let alert = policy_alert(&summary, &configured, &[TunnelKind::Teredo]).unwrap();
+        assert!(alert.starts_with("Policy alert: 6in4 tunnel"));
+    }
+
+    #[test]
+    fn decodes_teredo_with_origin_indication() {
+        // Server 65.54.227.120, client 192.0.2.45:40000 (obfuscated)
+        let teredo: Ipv6Addr = "2001:0:4136:e378:8000:63bf:3fff:fdd2".parse().unwrap();
+        assert_eq!(teredo_mapping(teredo), Some((Ipv4Addr::new(65, 54, 227, 120), Ipv4Addr::new(192, 0, 2, 45), 40000)));
+
+        let mut payload = vec![0x00, 0x00, 0x63, 0xbf, 0x3f, 0xff, 0xfd, 0xd2];
+        payload.extend(inner(teredo));
+        let udp = testutil::udp(TEREDO_PORT, 51000, &payload);
+        let data = frame(Ipv4Addr::new(65, 54, 227, 120), testutil::client_v4(), 17, &udp);
+        let summary = PacketSummary::from_ethernet(&data).unwrap();
+        assert_eq!(detect(&summary, &data).unwrap().0, TunnelKind::Teredo);
+        let fields = control_fields(&summary, &data);
+        assert!(fields.iter().any(|f| f.name == "Teredo Mapping" && f.value == "192.0.2.45:40000"));
+
+        assert!(teredo_inner(b"\x00\x01\x00\x00short").is_none());
+    }
+}
@@ -0,0 +1,285 @@
+use super::frame_control::{ControlField, FrameControlInfo, ProtocolType};
+use std::fmt;
+
+/// Linux usbmon header, 48 bytes (DLT_USB_LINUX)
+pub const DLT_USB_LINUX: i32 = 189;
+/// Linux usbmon header with the isochronous fields, 64 bytes (DLT_USB_LINUX_MMAPPED)
+pub const DLT_USB_LINUX_MMAPPED: i32 = 220;
+
+pub const TRANSFER_ISOCHRONOUS: u8 = 0;
+pub const TRANSFER_INTERRUPT: u8 = 1;
+pub const TRANSFER_CONTROL: u8 = 2;
+pub const TRANSFER_BULK: u8 = 3;
+
+/// USB request block as captured by usbmon. Fields are in host byte order,
+/// which is little-endian on the platforms usbmon captures come from.
+pub struct UsbUrb<'a> {
+    data: &'a [u8],
+    header_len: usize,
+}
+
+#[derive(Debug)]
+pub enum UsbError {
+    TooShort,
+}
+
+impl fmt::Display for UsbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UsbError::TooShort => write!(f, "Packet too short for usbmon header"),
+        }
+    }
+}
+
+/// Standard control request in the 8-byte setup stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetupPacket {
+    pub request_type: u8,
+    pub request: u8,
+    pub value: u16,
+    pub index: u16,
+    pub length: u16,
+}
+
+impl SetupPacket {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let d = data.get(..8)?;
+        Some(SetupPacket {
+            request_type: d[0],
+            request: d[1],
+            value: u16::from_le_bytes([d[2], d[3]]),
+            index: u16::from_le_bytes([d[4], d[5]]),
+            length: u16::from_le_bytes([d[6], d[7]]),
+        })
+    }
+
+    /// Standard, class or vendor request
+    pub fn kind(&self) -> &'static str {
+        match (self.request_type >> 5) & 0x03 {
+            0 => "Standard",
+            1 => "Class",
+            2 => "Vendor",
+            _ => "Reserved",
+        }
+    }
+
+    pub fn get_request_name(&self) -> String {
+        if self.kind() != "Standard" {
+            return format!("{} request 0x{:02x}", self.kind(), self.request);
+        }
+        match self.request {
+            0 => "GET_STATUS".to_string(),
+            1 => "CLEAR_FEATURE".to_string(),
+            3 => "SET_FEATURE".to_string(),
+            5 => "SET_ADDRESS".to_string(),
+            6 => format!("GET_DESCRIPTOR ({})", descriptor_name((self.value >> 8) as u8)),
+            7 => "SET_DESCRIPTOR".to_string(),
+            8 => "GET_CONFIGURATION".to_string(),
+            9 => "SET_CONFIGURATION".to_string(),
+            10 => "GET_INTERFACE".to_string(),
+            11 => "SET_INTERFACE".to_string(),
+            12 => "SYNCH_FRAME".to_string(),
+            r => format!("Unknown ({})", r),
+        }
+    }
+}
+
+impl<'a> UsbUrb<'a> {
+    pub fn parse(data: &'a [u8], mmapped: bool) -> Result<Self, UsbError> {
+        let header_len = if mmapped { 64 } else { 48 };
+        if data.len() < header_len {
+            return Err(UsbError::TooShort);
+        }
+        Ok(UsbUrb { data, header_len })
+    }
+
+    pub fn urb_id(&self) -> u64 {
+        u64::from_le_bytes(self.data[..8].try_into().unwrap_or_default())
+    }
+
+    /// `S` submission, `C` completion or `E` error
+    pub fn event_type(&self) -> char {
+        self.data[8] as char
+    }
+
+    pub fn transfer_type(&self) -> u8 {
+        self.data[9]
+    }
+
+    /// Endpoint number with the direction in the high bit
+    pub fn endpoint(&self) -> u8 {
+        self.data[10]
+    }
+
+    pub fn is_in(&self) -> bool {
+        self.endpoint() & 0x80 != 0
+    }
+
+    pub fn device(&self) -> u8 {
+        self.data[11]
+    }
+
+    pub fn bus(&self) -> u16 {
+        u16::from_le_bytes([self.data[12], self.data[13]])
+    }
+
+    pub fn status(&self) -> i32 {
+        i32::from_le_bytes([self.data[28], self.data[29], self.data[30], self.data[31]])
+    }
+
+    /// Requested (submission) or transferred (completion) length
+    pub fn urb_length(&self) -> u32 {
+        u32::from_le_bytes([self.data[32], self.data[33], self.data[34], self.data[35]])
+    }
+
+    /// Setup stage of a control transfer submission; a zero flag means present
+    pub fn setup(&self) -> Option<SetupPacket> {
+        if self.data[14] != 0 || self.transfer_type() != TRANSFER_CONTROL {
+            return None;
+        }
+        SetupPacket::parse(&self.data[40..48])
+    }
+
+    /// Captured transfer data
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[self.header_len..]
+    }
+
+    pub fn get_event_name(&self) -> String {
+        match self.event_type() {
+            'S' => "Submit".to_string(),
+            'C' => "Complete".to_string(),
+            'E' => "Error".to_string(),
+            e => format!("Unknown ({})", e),
+        }
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![
+            ControlField {
+                name: "URB".to_string(),
+                value: self.get_event_name(),
+                description: format!("URB 0x{:016x}", self.urb_id()),
+            },
+            ControlField {
+                name: "USB Transfer".to_string(),
+                value: transfer_type_name(self.transfer_type()),
+                description: format!("{} bytes", self.urb_length()),
+            },
+            ControlField {
+                name: "USB Endpoint".to_string(),
+                value: format!("0x{:02x}", self.endpoint()),
+                description: format!(
+                    "Bus {} device {} endpoint {} {}",
+                    self.bus(),
+                    self.device(),
+                    self.endpoint() & 0x7F,
+                    if self.is_in() { "IN" } else { "OUT" }
+                ),
+            },
+        ];
+        if self.event_type() != 'S' && self.status() != 0 {
+            fields.push(ControlField {
+                name: "URB Status".to_string(),
+                value: self.status().to_string(),
+                description: "Negative errno".to_string(),
+            });
+        }
+        if let Some(setup) = self.setup() {
+            fields.push(ControlField {
+                name: "USB Setup".to_string(),
+                value: setup.get_request_name(),
+                description: format!(
+                    "bmRequestType 0x{:02x}, wValue 0x{:04x}, wIndex 0x{:04x}, wLength {}",
+                    setup.request_type, setup.value, setup.index, setup.length
+                ),
+            });
+        }
+        if !self.payload().is_empty() {
+            fields.push(ControlField {
+                name: "USB Data".to_string(),
+                value: format!("{} bytes", self.payload().len()),
+                description: "Captured transfer data".to_string(),
+            });
+        }
+        fields
+    }
+
+    pub fn get_frame_control(&self) -> FrameControlInfo {
+        FrameControlInfo {
+            protocol_type: ProtocolType::Other("USB".to_string()),
+            control_fields: self.get_control_fields(),
+        }
+    }
+}
+
+pub fn transfer_type_name(transfer_type: u8) -> String {
+    match transfer_type {
+        TRANSFER_ISOCHRONOUS => "Isochronous".to_string(),
+        TRANSFER_INTERRUPT => "Interrupt".to_string(),
+        TRANSFER_CONTROL => "Control".to_string(),
+        TRANSFER_BULK => "Bulk".to_string(),
+        t => format!("Unknown ({})", t),
+    }
+}
+
+pub fn descriptor_name(descriptor_type: u8) -> String {
+    match descriptor_type {
+        1 => "Device".to_string(),
+        2 => "Configuration".to_string(),
+        3 => "String".to_string(),
+        4 => "Interface".to_string(),
+        5 => "Endpoint".to_string(),
+        6 => "Device Qualifier".to_string(),
+        0x0F => "BOS".to_string(),
+        0x21 => "HID".to_string(),
+        0x22 => "HID Report".to_string(),
+        d => format!("Unknown (0x{:02x})", d),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urb(event: u8, transfer_type: u8, endpoint: u8, setup: Option<[u8; 8]>, data: &[u8]) -> Vec<u8> {
+        let mut urb = vec![0; 64];
+        urb[..8].copy_from_slice(&0xffff_8880_1234_5600u64.to_le_bytes());
+        urb[8] = event;
+        urb[9] = transfer_type;
+        urb[10] = endpoint;
+        urb[11] = 3;
+        urb[12] = 1;
+        urb[14] = if setup.is_some() { 0 } else { b'-' };
+        urb[32..36].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        urb[36..40].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        if let Some(setup) = setup {
+            urb[40..48].copy_from_slice(&setup);
+        }
+        urb.extend_from_slice(data);
+        urb
+    }
+
+    #[test]
+    fn decodes_control_setup() {
+        let data = urb(b'S', TRANSFER_CONTROL, 0x80, Some([0x80, 6, 0, 1, 0, 0, 18, 0]), &[]);
+        let urb = UsbUrb::parse(&data, true).unwrap();
+        assert_eq!(urb.get_event_name(), "Submit");
+        assert!(urb.is_in());
+        let setup = urb.setup().unwrap();
+        assert_eq!(setup.get_request_name(), "GET_DESCRIPTOR (Device)");
+        assert_eq!(setup.length, 18);
+        assert!(UsbUrb::parse(&data[..60], true).is_err());
+    }
+
+    #[test]
+    fn decodes_bulk_completion() {
+        let data = urb(b'C', TRANSFER_BULK, 0x02, None, &[0x55, 0x53, 0x42, 0x43]);
+        let urb = UsbUrb::parse(&data, true).unwrap();
+        assert!(urb.setup().is_none());
+        assert_eq!(urb.payload(), b"USBC");
+        let fields = urb.get_control_fields();
+        assert!(fields.iter().any(|f| f.name == "USB Endpoint" && f.description == "Bus 1 device 3 endpoint 2 OUT"));
+        assert!(fields.iter().any(|f| f.name == "USB Data" && f.value == "4 bytes"));
+    }
+}
@@ -0,0 +1,119 @@
+use super::frame_control::ControlField;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+pub const IP_PROTO_VRRP: u8 = 112;
+
+/// VRRP advertisement parser (version 2, RFC 3768, and version 3, RFC 5798)
+pub struct VrrpPacket<'a> {
+    data: &'a [u8],
+    ipv6: bool,
+}
+
+#[derive(Debug)]
+pub enum VrrpError {
+    TooShort,
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for VrrpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VrrpError::TooShort => write!(f, "Packet too short for VRRP advertisement"),
+            VrrpError::UnsupportedVersion(v) => write!(f, "Unsupported VRRP version {}", v),
+        }
+    }
+}
+
+impl<'a> VrrpPacket<'a> {
+    /// Parses an advertisement; `ipv6` tells whether it came in an IPv6 packet,
+    /// which decides the size of the virtual addresses in version 3
+    pub fn parse(data: &'a [u8], ipv6: bool) -> Result<Self, VrrpError> {
+        if data.len() < 8 {
+            return Err(VrrpError::TooShort);
+        }
+        match data[0] >> 4 {
+            2 | 3 => Ok(VrrpPacket { data, ipv6 }),
+            v => Err(VrrpError::UnsupportedVersion(v)),
+        }
+    }
+
+    pub fn version(&self) -> u8 {
+        self.data[0] >> 4
+    }
+
+    /// Virtual router ID
+    pub fn vrid(&self) -> u8 {
+        self.data[1]
+    }
+
+    /// 255 means the router owns the virtual addresses; 0 means the master is leaving
+    pub fn priority(&self) -> u8 {
+        self.data[2]
+    }
+
+    /// Advertisement interval in centiseconds
+    pub fn interval(&self) -> u16 {
+        match self.version() {
+            2 => self.data[5] as u16 * 100,
+            _ => u16::from_be_bytes([self.data[4] & 0x0F, self.data[5]]),
+        }
+    }
+
+    pub fn virtual_ips(&self) -> Vec<IpAddr> {
+        let count = self.data[3] as usize;
+        let addresses = &self.data[8..];
+        if self.ipv6 && self.version() == 3 {
+            addresses
+                .chunks_exact(16)
+                .take(count)
+                .map(|a| IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(a).unwrap_or_default())))
+                .collect()
+        } else {
+            addresses.chunks_exact(4).take(count).map(|a| IpAddr::V4(Ipv4Addr::new(a[0], a[1], a[2], a[3]))).collect()
+        }
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let priority = match self.priority() {
+            255 => "Address owner".to_string(),
+            0 => "Master stopping".to_string(),
+            _ => "Backup priority".to_string(),
+        };
+        let ips: Vec<String> = self.virtual_ips().iter().map(|ip| ip.to_string()).collect();
+        vec![
+            ControlField {
+                name: "VRRP Router ID".to_string(),
+                value: self.vrid().to_string(),
+                description: format!("VRRPv{}, advertised every {} cs", self.version(), self.interval()),
+            },
+            ControlField { name: "VRRP Priority".to_string(), value: self.priority().to_string(), description: priority },
+            ControlField {
+                name: "VRRP Virtual IPs".to_string(),
+                value: ips.len().to_string(),
+                description: ips.join(", "),
+            },
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_v2_and_v3_advertisements() {
+        let v2 = [0x21, 10, 100, 1, 0, 1, 0, 0, 192, 168, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0];
+        let packet = VrrpPacket::parse(&v2, false).unwrap();
+        assert_eq!((packet.vrid(), packet.priority(), packet.interval()), (10, 100, 100));
+        assert_eq!(packet.virtual_ips(), vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))]);
+
+        let mut v3 = vec![0x31, 7, 255, 1, 0x00, 0x64, 0, 0];
+        v3.extend_from_slice(&"fe80::1".parse::<Ipv6Addr>().unwrap().octets());
+        let packet = VrrpPacket::parse(&v3, true).unwrap();
+        assert_eq!(packet.interval(), 100);
+        assert_eq!(packet.virtual_ips()[0].to_string(), "fe80::1");
+        assert_eq!(packet.get_control_fields()[1].description, "Address owner");
+        assert!(matches!(VrrpPacket::parse(&[0x11; 8], false), Err(VrrpError::UnsupportedVersion(1))));
+    }
+}
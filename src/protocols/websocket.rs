@@ -0,0 +1,273 @@
+use super::frame_control::ControlField;
+use std::fmt;
+
+pub const OPCODE_CONTINUATION: u8 = 0x0;
+pub const OPCODE_TEXT: u8 = 0x1;
+pub const OPCODE_BINARY: u8 = 0x2;
+pub const OPCODE_CLOSE: u8 = 0x8;
+pub const OPCODE_PING: u8 = 0x9;
+pub const OPCODE_PONG: u8 = 0xA;
+
+/// WebSocket frame parser (RFC 6455)
+pub struct WebSocketFrame<'a> {
+    data: &'a [u8],
+    header_len: usize,
+    payload_len: usize,
+}
+
+#[derive(Debug)]
+pub enum WebSocketError {
+    TooShort,
+    Truncated,
+}
+
+impl fmt::Display for WebSocketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebSocketError::TooShort => write!(f, "Data too short for WebSocket frame header"),
+            WebSocketError::Truncated => write!(f, "WebSocket frame payload truncated"),
+        }
+    }
+}
+
+impl<'a> WebSocketFrame<'a> {
+    /// Parses the frame at the start of `data`, which must hold the whole frame
+    pub fn parse(data: &'a [u8]) -> Result<Self, WebSocketError> {
+        if data.len() < 2 {
+            return Err(WebSocketError::TooShort);
+        }
+        let (payload_len, mut header_len): (usize, usize) = match data[1] & 0x7f {
+            126 => {
+                let len = data.get(2..4).ok_or(WebSocketError::TooShort)?;
+                (u16::from_be_bytes([len[0], len[1]]) as usize, 4)
+            }
+            127 => {
+                let len = data.get(2..10).ok_or(WebSocketError::TooShort)?;
+                let len = u64::from_be_bytes(len.try_into().expect("8 bytes"));
+                (usize::try_from(len).map_err(|_| WebSocketError::Truncated)?, 10)
+            }
+            len => (len as usize, 2),
+        };
+        if data[1] & 0x80 != 0 {
+            header_len += 4;
+        }
+        let end = header_len.checked_add(payload_len).ok_or(WebSocketError::Truncated)?;
+        if data.len() < header_len {
+            return Err(WebSocketError::TooShort);
+        }
+        let data = data.get(..end).ok_or(WebSocketError::Truncated)?;
+        Ok(WebSocketFrame { data, header_len, payload_len })
+    }
+
+    /// Header plus payload length
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn fin(&self) -> bool {
+        self.data[0] & 0x80 != 0
+    }
+
+    /// RSV1-3 bits; RSV1 marks per-message compression
+    pub fn rsv(&self) -> u8 {
+        (self.data[0] >> 4) & 0x07
+    }
+
+    pub fn opcode(&self) -> u8 {
+        self.data[0] & 0x0f
+    }
+
+    pub fn masked(&self) -> bool {
+        self.data[1] & 0x80 != 0
+    }
+
+    pub fn mask_key(&self) -> Option<[u8; 4]> {
+        self.masked()
+            .then(|| self.data[self.header_len - 4..self.header_len].try_into().expect("4 bytes"))
+    }
+
+    pub fn payload_len(&self) -> usize {
+        self.payload_len
+    }
+
+    pub fn is_control(&self) -> bool {
+        self.opcode() & 0x08 != 0
+    }
+
+    /// Payload with the client mask removed
+    pub fn payload(&self) -> Vec<u8> {
+        let payload = &self.data[self.header_len..];
+        match self.mask_key() {
+            Some(key) => payload.iter().enumerate().map(|(i, b)| b ^ key[i % 4]).collect(),
+            None => payload.to_vec(),
+        }
+    }
+
+    pub fn get_opcode_name(&self) -> String {
+        opcode_name(self.opcode())
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![
+            ControlField {
+                name: "WebSocket Opcode".to_string(),
+                value: self.opcode().to_string(),
+                description: self.get_opcode_name(),
+            },
+            ControlField {
+                name: "WebSocket FIN".to_string(),
+                value: (self.fin() as u8).to_string(),
+                description: if self.fin() { "Final fragment" } else { "More fragments follow" }.to_string(),
+            },
+            ControlField {
+                name: "WebSocket Payload Length".to_string(),
+                value: self.payload_len.to_string(),
+                description: "Bytes".to_string(),
+            },
+        ];
+        if let Some(key) = self.mask_key() {
+            fields.push(ControlField {
+                name: "WebSocket Mask".to_string(),
+                value: key.iter().map(|b| format!("{:02x}", b)).collect(),
+                description: "Client masking key".to_string(),
+            });
+        }
+        fields
+    }
+}
+
+pub fn opcode_name(opcode: u8) -> String {
+    match opcode {
+        OPCODE_CONTINUATION => "Continuation".to_string(),
+        OPCODE_TEXT => "Text".to_string(),
+        OPCODE_BINARY => "Binary".to_string(),
+        OPCODE_CLOSE => "Close".to_string(),
+        OPCODE_PING => "Ping".to_string(),
+        OPCODE_PONG => "Pong".to_string(),
+        op => format!("Reserved (0x{:x})", op),
+    }
+}
+
+/// Splits one direction of an upgraded connection into frames, stopping at
+/// a truncated frame
+pub fn frames(stream: &[u8]) -> Vec<WebSocketFrame<'_>> {
+    let mut rest = stream;
+    let mut out = Vec::new();
+    while let Ok(frame) = WebSocketFrame::parse(rest) {
+        rest = &rest[frame.len()..];
+        out.push(frame);
+    }
+    out
+}
+
+/// A complete message, reassembled from its fragments
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebSocketMessage {
+    pub opcode: u8,
+    pub data: Vec<u8>,
+    pub fragments: usize,
+    /// RSV1 set on the first frame: the payload is deflate-compressed
+    pub compressed: bool,
+}
+
+impl WebSocketMessage {
+    /// Printable preview of a text message, `None` for binary or control frames
+    pub fn text_preview(&self, max_chars: usize) -> Option<String> {
+        if self.opcode != OPCODE_TEXT || self.compressed {
+            return None;
+        }
+        let text = String::from_utf8_lossy(&self.data);
+        let mut preview: String = text.chars().take(max_chars).map(|c| if c.is_control() { ' ' } else { c }).collect();
+        if text.chars().count() > max_chars {
+            preview.push_str("...");
+        }
+        Some(preview)
+    }
+}
+
+/// Reassembles fragmented messages from one direction's frames, fed in
+/// order. Control frames may be interleaved with fragments and come out on their own.
+#[derive(Debug, Default)]
+pub struct MessageAssembler {
+    partial: Option<WebSocketMessage>,
+}
+
+impl MessageAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, frame: &WebSocketFrame) -> Option<WebSocketMessage> {
+        if frame.is_control() {
+            return Some(WebSocketMessage { opcode: frame.opcode(), data: frame.payload(), fragments: 1, compressed: false });
+        }
+        match (frame.opcode(), self.partial.as_mut()) {
+            (OPCODE_CONTINUATION, Some(message)) => {
+                message.data.extend(frame.payload());
+                message.fragments += 1;
+            }
+            // Continuation without a start: the beginning wasn't captured
+            (OPCODE_CONTINUATION, None) => return None,
+            (opcode, _) => {
+                self.partial = Some(WebSocketMessage {
+                    opcode,
+                    data: frame.payload(),
+                    fragments: 1,
+                    compressed: frame.rsv() & 0x04 != 0,
+                });
+            }
+        }
+        if frame.fin() { self.partial.take() } else { None }
+    }
+}
+
+/// Reassembles all messages in a sequence of frames
+pub fn messages(frames: &[WebSocketFrame]) -> Vec<WebSocketMessage> {
+    let mut assembler = MessageAssembler::new();
+    frames.iter().filter_map(|frame| assembler.push(frame)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::websocket_frame;
+
+    #[test]
+    fn parses_masked_and_extended_frames() {
+        let frame = websocket_frame(true, OPCODE_TEXT, Some([1, 2, 3, 4]), b"hello");
+        let parsed = WebSocketFrame::parse(&frame).unwrap();
+        assert!(parsed.fin() && parsed.masked());
+        assert_eq!(parsed.get_opcode_name(), "Text");
+        assert_eq!(parsed.payload(), b"hello");
+        assert_eq!(parsed.get_control_fields().len(), 4);
+
+        let big = websocket_frame(true, OPCODE_BINARY, None, &[7; 300]);
+        let parsed = WebSocketFrame::parse(&big).unwrap();
+        assert_eq!(parsed.payload_len(), 300);
+        assert_eq!(parsed.len(), 304);
+        assert!(matches!(WebSocketFrame::parse(&big[..100]), Err(WebSocketError::Truncated)));
+        assert!(matches!(WebSocketFrame::parse(&[0x81]), Err(WebSocketError::TooShort)));
+    }
+
+    #[test]
+    fn reassembles_fragments_around_control_frames() {
+        let mut stream = websocket_frame(false, OPCODE_TEXT, None, b"Hello, ");
+        stream.extend(websocket_frame(true, OPCODE_PING, None, b"?"));
+        stream.extend(websocket_frame(true, OPCODE_CONTINUATION, None, b"world"));
+        stream.extend(websocket_frame(true, OPCODE_CLOSE, None, &[0x03, 0xe8]));
+
+        let frames = frames(&stream);
+        assert_eq!(frames.len(), 4);
+        let messages = messages(&frames);
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].opcode, OPCODE_PING);
+        assert_eq!(messages[1].data, b"Hello, world");
+        assert_eq!(messages[1].fragments, 2);
+        assert_eq!(messages[1].text_preview(5).as_deref(), Some("Hello..."));
+        assert_eq!(messages[2].opcode, OPCODE_CLOSE);
+    }
+}
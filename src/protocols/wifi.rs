@@ -1,4 +1,367 @@
-use super::frame_control::{FrameControlInfo, ProtocolType, ControlField};
+use super::eapol::{EapolPacket, ETHERTYPE_EAPOL};
+use super::ethernet::MacAddress;
+use super::frame_control::{ControlField, FrameControlInfo, ProtocolType};
+use super::radiotap::RadiotapHeader;
+use std::fmt;
 
-// This is a placeholder for WiFi frame parsing
-// In a full implementation, you would add code to parse 802.11 frames
\ No newline at end of file
+/// IEEE 802.11 MAC frame parser
+pub struct WifiFrame<'a> {
+    data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum WifiError {
+    TooShort,
+    InvalidVersion,
+}
+
+impl fmt::Display for WifiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WifiError::TooShort => write!(f, "Packet too short for 802.11 frame"),
+            WifiError::InvalidVersion => write!(f, "Invalid 802.11 protocol version"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WifiFrameType {
+    Management,
+    Control,
+    Data,
+    Extension,
+}
+
+impl<'a> WifiFrame<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, WifiError> {
+        // Frame control + duration + addr1 is the shortest frame (ACK/CTS)
+        if data.len() < 10 {
+            return Err(WifiError::TooShort);
+        }
+        if data[0] & 0x03 != 0 {
+            return Err(WifiError::InvalidVersion);
+        }
+        Ok(WifiFrame { data })
+    }
+
+    pub fn frame_type(&self) -> WifiFrameType {
+        match (self.data[0] >> 2) & 0x03 {
+            0 => WifiFrameType::Management,
+            1 => WifiFrameType::Control,
+            2 => WifiFrameType::Data,
+            _ => WifiFrameType::Extension,
+        }
+    }
+
+    pub fn subtype(&self) -> u8 {
+        self.data[0] >> 4
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.data[1]
+    }
+
+    pub fn to_ds(&self) -> bool {
+        self.flags() & 0x01 != 0
+    }
+
+    pub fn from_ds(&self) -> bool {
+        self.flags() & 0x02 != 0
+    }
+
+    pub fn is_retry(&self) -> bool {
+        self.flags() & 0x08 != 0
+    }
+
+    pub fn is_protected(&self) -> bool {
+        self.flags() & 0x40 != 0
+    }
+
+    pub fn duration(&self) -> u16 {
+        u16::from_le_bytes([self.data[2], self.data[3]])
+    }
+
+    fn address(&self, index: usize) -> Option<MacAddress> {
+        let start = 4 + index * 6;
+        if self.data.len() < start + 6 {
+            return None;
+        }
+        let mut mac = [0u8; 6];
+        mac.copy_from_slice(&self.data[start..start + 6]);
+        Some(MacAddress::new(mac))
+    }
+
+    /// Receiver address
+    pub fn addr1(&self) -> Option<MacAddress> {
+        self.address(0)
+    }
+
+    /// Transmitter address
+    pub fn addr2(&self) -> Option<MacAddress> {
+        self.address(1)
+    }
+
+    pub fn addr3(&self) -> Option<MacAddress> {
+        self.address(2)
+    }
+
+    /// BSSID, whose position depends on the To/From DS bits
+    pub fn bssid(&self) -> Option<MacAddress> {
+        match (self.frame_type(), self.to_ds(), self.from_ds()) {
+            (WifiFrameType::Control, _, _) => None,
+            (_, false, false) => self.addr3(),
+            (_, false, true) => self.addr2(),
+            (_, true, false) => self.addr1(),
+            (_, true, true) => None,
+        }
+    }
+
+    pub fn sequence_number(&self) -> Option<u16> {
+        if self.frame_type() == WifiFrameType::Control || self.data.len() < 24 {
+            return None;
+        }
+        Some(u16::from_le_bytes([self.data[22], self.data[23]]) >> 4)
+    }
+
+    /// Length of the MAC header, including QoS control and the 4th address
+    pub fn header_length(&self) -> usize {
+        match self.frame_type() {
+            WifiFrameType::Control => 10.max(self.data.len().min(16)),
+            WifiFrameType::Data => {
+                let mut len = 24;
+                if self.to_ds() && self.from_ds() {
+                    len += 6;
+                }
+                if self.subtype() & 0x08 != 0 {
+                    len += 2; // QoS control
+                }
+                len
+            }
+            _ => 24,
+        }
+    }
+
+    /// Frame body following the MAC header
+    pub fn body(&self) -> &'a [u8] {
+        &self.data[self.header_length().min(self.data.len())..]
+    }
+
+    /// EtherType and payload of an unprotected data frame with an LLC/SNAP header
+    pub fn llc_payload(&self) -> Option<(u16, &'a [u8])> {
+        if self.frame_type() != WifiFrameType::Data || self.is_protected() {
+            return None;
+        }
+        let body = self.body();
+        if body.get(..6)? != [0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00] {
+            return None;
+        }
+        Some((u16::from_be_bytes([*body.get(6)?, *body.get(7)?]), &body[8..]))
+    }
+
+    /// EAPOL frame (802.1X authentication or WPA key exchange) carried in a data frame
+    pub fn eapol(&self) -> Option<EapolPacket<'a>> {
+        match self.llc_payload()? {
+            (ETHERTYPE_EAPOL, payload) => EapolPacket::parse(payload).ok(),
+            _ => None,
+        }
+    }
+
+    /// SSID from beacons, probe requests/responses and (re)association requests
+    pub fn ssid(&self) -> Option<String> {
+        if self.frame_type() != WifiFrameType::Management {
+            return None;
+        }
+        let fixed = match self.subtype() {
+            8 | 5 => 12, // beacon, probe response: timestamp, interval, capabilities
+            4 => 0,      // probe request
+            0 => 4,      // association request: capabilities, listen interval
+            2 => 10,     // reassociation request: + current AP
+            _ => return None,
+        };
+        let mut tags = self.body().get(fixed..)?;
+        while tags.len() >= 2 {
+            let (id, len) = (tags[0], tags[1] as usize);
+            let value = tags.get(2..2 + len)?;
+            if id == 0 {
+                return Some(String::from_utf8_lossy(value).into_owned());
+            }
+            tags = &tags[2 + len..];
+        }
+        None
+    }
+
+    pub fn get_type_name(&self) -> String {
+        let name = match (self.frame_type(), self.subtype()) {
+            (WifiFrameType::Management, 0) => "Association Request",
+            (WifiFrameType::Management, 1) => "Association Response",
+            (WifiFrameType::Management, 2) => "Reassociation Request",
+            (WifiFrameType::Management, 3) => "Reassociation Response",
+            (WifiFrameType::Management, 4) => "Probe Request",
+            (WifiFrameType::Management, 5) => "Probe Response",
+            (WifiFrameType::Management, 8) => "Beacon",
+            (WifiFrameType::Management, 10) => "Disassociation",
+            (WifiFrameType::Management, 11) => "Authentication",
+            (WifiFrameType::Management, 12) => "Deauthentication",
+            (WifiFrameType::Management, 13) => "Action",
+            (WifiFrameType::Control, 8) => "Block Ack Request",
+            (WifiFrameType::Control, 9) => "Block Ack",
+            (WifiFrameType::Control, 11) => "RTS",
+            (WifiFrameType::Control, 12) => "CTS",
+            (WifiFrameType::Control, 13) => "ACK",
+            (WifiFrameType::Data, 0) => "Data",
+            (WifiFrameType::Data, 4) => "Null",
+            (WifiFrameType::Data, 8) => "QoS Data",
+            (WifiFrameType::Data, 12) => "QoS Null",
+            (frame_type, subtype) => return format!("{:?} (subtype {})", frame_type, subtype),
+        };
+        name.to_string()
+    }
+
+    pub fn get_control_fields(&self) -> Vec<ControlField> {
+        let mut fields = vec![
+            ControlField {
+                name: "Frame Type".to_string(),
+                value: format!("{:?}/{}", self.frame_type(), self.subtype()),
+                description: self.get_type_name(),
+            },
+            ControlField {
+                name: "Flags".to_string(),
+                value: format!("0x{:02x}", self.flags()),
+                description: self.get_flags_description(),
+            },
+            ControlField {
+                name: "Duration".to_string(),
+                value: self.duration().to_string(),
+                description: "Duration/ID in microseconds".to_string(),
+            },
+        ];
+        let addresses = [
+            ("Receiver", self.addr1()),
+            ("Transmitter", self.addr2()),
+            ("BSSID", self.bssid()),
+        ];
+        for (name, addr) in addresses {
+            if let Some(addr) = addr {
+                fields.push(ControlField {
+                    name: name.to_string(),
+                    value: addr.to_string(),
+                    description: format!("{} address", name),
+                });
+            }
+        }
+        if let Some(seq) = self.sequence_number() {
+            fields.push(ControlField {
+                name: "Sequence".to_string(),
+                value: seq.to_string(),
+                description: "Sequence number".to_string(),
+            });
+        }
+        if let Some(ssid) = self.ssid() {
+            fields.push(ControlField {
+                name: "SSID".to_string(),
+                value: ssid,
+                description: "Network name".to_string(),
+            });
+        }
+        if let Some(eapol) = self.eapol() {
+            fields.extend(eapol.get_control_fields());
+        }
+        fields
+    }
+
+    pub fn get_flags_description(&self) -> String {
+        let mut desc = Vec::new();
+        if self.to_ds() { desc.push("To DS"); }
+        if self.from_ds() { desc.push("From DS"); }
+        if self.flags() & 0x04 != 0 { desc.push("More Fragments"); }
+        if self.is_retry() { desc.push("Retry"); }
+        if self.flags() & 0x10 != 0 { desc.push("Power Management"); }
+        if self.is_protected() { desc.push("Protected"); }
+
+        if desc.is_empty() {
+            "None".to_string()
+        } else {
+            desc.join(", ")
+        }
+    }
+
+    pub fn get_frame_control(&self) -> FrameControlInfo {
+        FrameControlInfo {
+            protocol_type: ProtocolType::WiFi,
+            control_fields: self.get_control_fields(),
+        }
+    }
+}
+
+/// Parses a radiotap-prefixed 802.11 frame (DLT_IEEE802_11_RADIO)
+pub fn radiotap_frame_control(data: &[u8]) -> Option<FrameControlInfo> {
+    let radiotap = RadiotapHeader::parse(data).ok()?;
+    let mut info = WifiFrame::parse(radiotap.payload()).ok()?.get_frame_control();
+    let mut fields = radiotap.get_control_fields();
+    fields.append(&mut info.control_fields);
+    info.control_fields = fields;
+    Some(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn beacon(ssid: &str) -> Vec<u8> {
+        let mut frame = vec![0x80, 0x00, 0x00, 0x00];
+        frame.extend_from_slice(&[0xff; 6]);
+        frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x01]);
+        frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x01]);
+        frame.extend_from_slice(&[0x10, 0x00]); // sequence 1
+        frame.extend_from_slice(&[0; 12]);
+        frame.push(0);
+        frame.push(ssid.len() as u8);
+        frame.extend_from_slice(ssid.as_bytes());
+        frame
+    }
+
+    #[test]
+    fn parses_beacon() {
+        let data = beacon("CoffeeShop");
+        let frame = WifiFrame::parse(&data).unwrap();
+        assert_eq!(frame.frame_type(), WifiFrameType::Management);
+        assert_eq!(frame.get_type_name(), "Beacon");
+        assert_eq!(frame.ssid().as_deref(), Some("CoffeeShop"));
+        assert_eq!(frame.bssid().unwrap().to_string(), "02:00:00:00:00:01");
+        assert_eq!(frame.sequence_number(), Some(1));
+    }
+
+    #[test]
+    fn parses_qos_data_header() {
+        let mut data = vec![0x88, 0x41, 0x00, 0x00]; // QoS data, To DS + Protected
+        data.extend_from_slice(&[0u8; 22]);
+        data.extend_from_slice(&[0xaa, 0xbb]);
+        let frame = WifiFrame::parse(&data).unwrap();
+        assert_eq!(frame.header_length(), 26);
+        assert_eq!(frame.body(), &[0xaa, 0xbb]);
+        assert!(frame.is_protected());
+        assert_eq!(frame.get_flags_description(), "To DS, Protected");
+    }
+
+    #[test]
+    fn decodes_eapol_in_data_frames() {
+        let mut data = vec![0x08, 0x02, 0x00, 0x00]; // Data, From DS
+        data.extend_from_slice(&[0u8; 20]);
+        data.extend_from_slice(&[0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00, 0x88, 0x8e]);
+        data.extend(crate::testutil::eapol_key(0x008a, 1, &[7; 32], &[0; 16], &[]));
+        let frame = WifiFrame::parse(&data).unwrap();
+        assert_eq!(frame.llc_payload().unwrap().0, ETHERTYPE_EAPOL);
+        assert_eq!(frame.eapol().unwrap().key().unwrap().handshake_message(), Some(1));
+        assert!(frame.get_control_fields().iter().any(|f| f.name == "Key Info"));
+    }
+
+    #[test]
+    fn decodes_radiotap_prefixed_frames() {
+        let mut data = vec![0, 0, 8, 0, 0, 0, 0, 0];
+        data.extend_from_slice(&beacon("lab"));
+        let info = radiotap_frame_control(&data).unwrap();
+        assert!(matches!(info.protocol_type, ProtocolType::WiFi));
+        assert!(info.control_fields.iter().any(|f| f.name == "SSID" && f.value == "lab"));
+        assert!(WifiFrame::parse(&[0u8; 4]).is_err());
+    }
+}
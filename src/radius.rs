@@ -0,0 +1,144 @@
+use crate::capfile::CaptureReader;
+use crate::error::CaptureError;
+use crate::protocols::radius::{
+    RadiusPacket, ACCESS_ACCEPT, ACCESS_CHALLENGE, ACCESS_REJECT, ACCESS_REQUEST, RADIUS_AUTH_PORT,
+};
+use crate::protocols::summary::PacketSummary;
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Authentication outcomes seen for one NAS (the RADIUS client)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NasStats {
+    /// NAS-Identifier, when the NAS sends one
+    pub identifier: Option<String>,
+    pub requests: u64,
+    pub accepts: u64,
+    pub rejects: u64,
+    pub challenges: u64,
+    /// Rejections per User-Name
+    pub failed_users: BTreeMap<String, u64>,
+}
+
+impl NasStats {
+    /// Share of answered requests that were rejected
+    pub fn failure_rate(&self) -> Option<f64> {
+        let answered = self.accepts + self.rejects;
+        (answered > 0).then(|| self.rejects as f64 / answered as f64)
+    }
+}
+
+/// A request waiting for its Accept, Reject or Challenge
+#[derive(Debug)]
+struct Pending {
+    nas: IpAddr,
+    user: Option<String>,
+}
+
+/// Per-NAS authentication statistics built from RADIUS traffic on port 1812.
+/// Responses are matched to requests by the NAS socket and packet identifier.
+#[derive(Debug, Default)]
+pub struct RadiusStats {
+    pub nas: BTreeMap<IpAddr, NasStats>,
+    pending: HashMap<(IpAddr, u16, u8), Pending>,
+}
+
+impl RadiusStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds one packet of the given datalink
+    pub fn push(&mut self, datalink: i32, data: &[u8]) {
+        let Some(summary) = PacketSummary::from_link(datalink, data) else { return };
+        let (Some(src), Some(dst), Some(sport), Some(dport)) =
+            (summary.src_ip, summary.dst_ip, summary.src_port, summary.dst_port)
+        else {
+            return;
+        };
+        if !summary.is_udp() || !summary.has_port(RADIUS_AUTH_PORT) {
+            return;
+        }
+        let Ok(packet) = RadiusPacket::parse(summary.payload(data)) else { return };
+        match packet.code() {
+            ACCESS_REQUEST if dport == RADIUS_AUTH_PORT => {
+                // NAS-IP-Address names the NAS even when a proxy relays the request
+                let nas = packet.nas_ip().map(IpAddr::V4).unwrap_or(src);
+                let stats = self.nas.entry(nas).or_default();
+                stats.requests += 1;
+                if let Some(identifier) = packet.nas_identifier() {
+                    stats.identifier = Some(identifier);
+                }
+                self.pending.insert((src, sport, packet.identifier()), Pending { nas, user: packet.user_name() });
+            }
+            code @ (ACCESS_ACCEPT | ACCESS_REJECT | ACCESS_CHALLENGE) if sport == RADIUS_AUTH_PORT => {
+                let Some(pending) = self.pending.remove(&(dst, dport, packet.identifier())) else { return };
+                let stats = self.nas.entry(pending.nas).or_default();
+                match code {
+                    ACCESS_ACCEPT => stats.accepts += 1,
+                    ACCESS_REJECT => {
+                        stats.rejects += 1;
+                        let user = pending.user.unwrap_or_else(|| "-".to_string());
+                        *stats.failed_users.entry(user).or_default() += 1;
+                    }
+                    _ => stats.challenges += 1,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Requests that never got an answer
+    pub fn unanswered(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// Reads a capture file and collects RADIUS authentication statistics
+pub fn analyze_radius(input: &Path) -> Result<RadiusStats, CaptureError> {
+    let mut stats = RadiusStats::new();
+    let reader = CaptureReader::open(input)?;
+    let datalink = reader.linktype().0;
+    for packet in reader {
+        stats.push(datalink, &packet.data);
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::radius::{ATTR_NAS_IP_ADDRESS, ATTR_USER_NAME};
+    use crate::testutil;
+    use std::net::Ipv4Addr;
+
+    fn exchange(stats: &mut RadiusStats, id: u8, user: &str, answer: u8) {
+        let nas = testutil::client_v4();
+        let server = testutil::server_v4();
+        let request = testutil::radius_packet(
+            ACCESS_REQUEST,
+            id,
+            &[(ATTR_USER_NAME, user.as_bytes()), (ATTR_NAS_IP_ADDRESS, &[10, 0, 0, 1])],
+        );
+        stats.push(1, &testutil::frame().ipv4(nas, server).udp(40000, RADIUS_AUTH_PORT).payload(&request).build());
+        let response = testutil::radius_packet(answer, id, &[]);
+        stats.push(1, &testutil::frame().ipv4(server, nas).udp(RADIUS_AUTH_PORT, 40000).payload(&response).build());
+    }
+
+    #[test]
+    fn tracks_failure_rate_per_nas() {
+        let mut stats = RadiusStats::new();
+        exchange(&mut stats, 1, "alice", ACCESS_ACCEPT);
+        exchange(&mut stats, 2, "bob", ACCESS_REJECT);
+        exchange(&mut stats, 3, "bob", ACCESS_REJECT);
+        exchange(&mut stats, 4, "carol", ACCESS_CHALLENGE);
+
+        let nas = &stats.nas[&IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))];
+        assert_eq!((nas.requests, nas.accepts, nas.rejects, nas.challenges), (4, 1, 2, 1));
+        assert_eq!(nas.failed_users["bob"], 2);
+        assert!((nas.failure_rate().unwrap() - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stats.unanswered(), 0);
+        assert_eq!(NasStats::default().failure_rate(), None);
+    }
+}
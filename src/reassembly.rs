@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Which peer sent a piece of a conversation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// The peer that opened the connection (or was seen sending first)
+    Client,
+    Server,
+}
+
+/// In-order payload delivered by the reassembler
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamChunk {
+    pub side: Side,
+    pub ts: Duration,
+    pub data: Vec<u8>,
+}
+
+/// One direction of a TCP connection
+#[derive(Debug, Default)]
+struct HalfStream {
+    /// Sequence number of the first payload byte
+    isn: Option<u32>,
+    /// Offset (from `isn`) of the next byte to deliver
+    next: u64,
+    /// Out-of-order segments keyed by offset
+    pending: BTreeMap<u64, Vec<u8>>,
+    /// Bytes dropped because a gap never filled
+    missing: u64,
+}
+
+/// Once this many segments are buffered behind a gap, the gap is treated as a
+/// lost segment instead of waiting for it forever
+const MAX_PENDING: usize = 256;
+
+impl HalfStream {
+    /// Accepts a segment and returns newly contiguous data
+    fn push(&mut self, seq: u32, syn: bool, payload: &[u8]) -> Vec<u8> {
+        let data_seq = if syn { seq.wrapping_add(1) } else { seq };
+        let isn = *self.isn.get_or_insert(data_seq);
+        if payload.is_empty() {
+            return Vec::new();
+        }
+
+        // Relative offset, allowing for segments that start before the ISN
+        let relative = data_seq.wrapping_sub(isn) as i32 as i64;
+        let (offset, payload) = if relative < 0 {
+            let skip = (-relative) as usize;
+            if skip >= payload.len() {
+                return Vec::new();
+            }
+            (0u64, &payload[skip..])
+        } else {
+            (relative as u64, payload)
+        };
+        if offset + payload.len() as u64 <= self.next {
+            return Vec::new(); // retransmission of delivered data
+        }
+        let existing = self.pending.get(&offset).map_or(0, Vec::len);
+        if payload.len() > existing {
+            self.pending.insert(offset, payload.to_vec());
+        }
+
+        let mut delivered = Vec::new();
+        while let Some((&start, _)) = self.pending.first_key_value() {
+            if start > self.next {
+                if self.pending.len() <= MAX_PENDING {
+                    break;
+                }
+                // Give up on the gap and resume at the earliest buffered segment
+                self.missing += start - self.next;
+                self.next = start;
+            }
+            let (start, segment) = self.pending.pop_first().unwrap_or_default();
+            let skip = (self.next - start) as usize;
+            if skip < segment.len() {
+                delivered.extend_from_slice(&segment[skip..]);
+                self.next = start + segment.len() as u64;
+            }
+        }
+        delivered
+    }
+}
+
+/// Reassembles both directions of one TCP connection into ordered chunks,
+/// handling retransmissions, overlaps and out-of-order delivery.
+#[derive(Debug, Default)]
+pub struct TcpReassembler {
+    client: Option<(IpAddr, u16)>,
+    server: Option<(IpAddr, u16)>,
+    halves: [HalfStream; 2],
+    chunks: Vec<StreamChunk>,
+}
+
+impl TcpReassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds one segment of the connection. The client is the sender of the
+    /// first SYN, the peer of the first SYN-ACK, or failing both the first sender seen.
+    pub fn push(&mut self, src: (IpAddr, u16), seq: u32, flags: u8, payload: &[u8], ts: Duration) {
+        let syn = flags & 0x02 != 0;
+        let ack = flags & 0x10 != 0;
+        let side = match (self.client, self.server) {
+            (Some(client), _) if client == src => Side::Client,
+            (_, Some(server)) if server == src => Side::Server,
+            (Some(_), _) => {
+                self.server = Some(src);
+                Side::Server
+            }
+            (None, Some(_)) => {
+                self.client = Some(src);
+                Side::Client
+            }
+            (None, None) if syn && ack => {
+                self.server = Some(src);
+                Side::Server
+            }
+            (None, None) => {
+                self.client = Some(src);
+                Side::Client
+            }
+        };
+        self.push_side(side, seq, syn, payload, ts);
+    }
+
+    fn push_side(&mut self, side: Side, seq: u32, syn: bool, payload: &[u8], ts: Duration) {
+        let half = &mut self.halves[side as usize];
+        let data = half.push(seq, syn, payload);
+        if data.is_empty() {
+            return;
+        }
+        // Merge consecutive data from the same side into one chunk
+        match self.chunks.last_mut() {
+            Some(last) if last.side == side => last.data.extend_from_slice(&data),
+            _ => self.chunks.push(StreamChunk { side, ts, data }),
+        }
+    }
+
+    pub fn client(&self) -> Option<(IpAddr, u16)> {
+        self.client
+    }
+
+    /// Bytes skipped in each direction (client, server) because segments were missing
+    pub fn missing_bytes(&self) -> (u64, u64) {
+        (self.halves[0].missing, self.halves[1].missing)
+    }
+
+    /// Everything one side sent, in order
+    pub fn stream(&self, side: Side) -> Vec<u8> {
+        self.chunks.iter().filter(|c| c.side == side).flat_map(|c| c.data.iter().copied()).collect()
+    }
+
+    pub fn chunks(&self) -> &[StreamChunk] {
+        &self.chunks
+    }
+
+    pub fn into_chunks(self) -> Vec<StreamChunk> {
+        self.chunks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const SYN: u8 = 0x02;
+    const ACK: u8 = 0x10;
+    const SYN_ACK: u8 = 0x12;
+
+    fn client() -> (IpAddr, u16) {
+        (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 40000)
+    }
+
+    fn server() -> (IpAddr, u16) {
+        (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 80)
+    }
+
+    fn text(chunks: &[StreamChunk]) -> Vec<(Side, String)> {
+        chunks.iter().map(|c| (c.side, String::from_utf8_lossy(&c.data).into_owned())).collect()
+    }
+
+    #[test]
+    fn orders_a_conversation() {
+        let t = Duration::ZERO;
+        let mut r = TcpReassembler::new();
+        r.push(client(), 1000, SYN, b"", t);
+        r.push(server(), 5000, SYN_ACK, b"", t);
+        r.push(client(), 1001, ACK, b"GET / ", t);
+        r.push(client(), 1007, ACK, b"HTTP/1.1\r\n", t);
+        r.push(server(), 5001, ACK, b"HTTP/1.1 200 OK\r\n", t);
+        assert_eq!(
+            text(r.chunks()),
+            vec![
+                (Side::Client, "GET / HTTP/1.1\r\n".to_string()),
+                (Side::Server, "HTTP/1.1 200 OK\r\n".to_string()),
+            ]
+        );
+        assert_eq!(r.client(), Some(client()));
+        assert_eq!(r.stream(Side::Server), b"HTTP/1.1 200 OK\r\n");
+    }
+
+    #[test]
+    fn handles_reordering_and_retransmissions() {
+        let t = Duration::ZERO;
+        let mut r = TcpReassembler::new();
+        r.push(client(), 100, SYN, b"", t);
+        r.push(client(), 107, ACK, b"world", t); // arrives early
+        r.push(client(), 101, ACK, b"hello ", t);
+        r.push(client(), 101, ACK, b"hello ", t); // retransmission
+        r.push(client(), 104, ACK, b"lo world!", t); // overlapping
+        assert_eq!(text(r.chunks()), vec![(Side::Client, "hello world!".to_string())]);
+    }
+
+    #[test]
+    fn picks_up_mid_stream_and_after_syn_ack() {
+        let t = Duration::ZERO;
+        let mut r = TcpReassembler::new();
+        r.push(server(), 9000, SYN_ACK, b"", t);
+        r.push(client(), 300, ACK, b"hi", t);
+        assert_eq!(text(r.chunks()), vec![(Side::Client, "hi".to_string())]);
+        assert_eq!(r.missing_bytes(), (0, 0));
+    }
+}
@@ -0,0 +1,318 @@
+use crate::builder::{internet_checksum, transport_checksum};
+use crate::error::CaptureError;
+use crate::protocols::ethernet::MacAddress;
+use crate::protocols::summary::skip_ipv6_extensions;
+use log::{info, warn};
+use pcap::{Capture, Device};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How fast packets are put back on the wire
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayTiming {
+    /// Reproduce the inter-packet gaps recorded in the file
+    Original,
+    /// Send at a fixed number of packets per second
+    FixedRate(u32),
+    /// Send as fast as the interface accepts
+    MaxSpeed,
+}
+
+/// Optional header rewriting applied to every replayed frame
+#[derive(Debug, Default, Clone)]
+pub struct RewriteRules {
+    pub src_mac: Option<MacAddress>,
+    pub dst_mac: Option<MacAddress>,
+    pub ip_map: HashMap<IpAddr, IpAddr>,
+}
+
+impl RewriteRules {
+    pub fn is_empty(&self) -> bool {
+        self.src_mac.is_none() && self.dst_mac.is_none() && self.ip_map.is_empty()
+    }
+
+    /// Adds an `OLD=NEW` address mapping, both sides of the same family
+    pub fn add_ip_mapping(&mut self, spec: &str) -> Result<(), CaptureError> {
+        let (old, new) = spec
+            .split_once('=')
+            .ok_or_else(|| CaptureError::InputError(format!("Expected OLD=NEW, got '{}'", spec)))?;
+        let old: IpAddr = old.trim().parse().map_err(|_| CaptureError::InputError(format!("Invalid IP '{}'", old)))?;
+        let new: IpAddr = new.trim().parse().map_err(|_| CaptureError::InputError(format!("Invalid IP '{}'", new)))?;
+        if old.is_ipv4() != new.is_ipv4() {
+            return Err(CaptureError::InputError(format!("Address families differ in '{}'", spec)));
+        }
+        self.ip_map.insert(old, new);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub sent: u64,
+    pub failed: u64,
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+/// Reads a pcap file and retransmits its packets on `interface_name`
+pub fn replay_file(
+    path: &str,
+    interface_name: &str,
+    timing: ReplayTiming,
+    rules: &RewriteRules,
+) -> Result<ReplayReport, CaptureError> {
+    let iface = Device::list()
+        .map_err(|e| CaptureError::PcapError(e.to_string()))?
+        .into_iter()
+        .find(|d| d.name == interface_name)
+        .ok_or_else(|| CaptureError::InterfaceNotFound(interface_name.to_string()))?;
+
+    let mut output = Capture::from_device(iface)
+        .map_err(|e| CaptureError::PcapError(e.to_string()))?
+        .open()
+        .map_err(|e| CaptureError::PcapError(e.to_string()))?;
+    let mut input = Capture::from_file(path).map_err(|e| CaptureError::PcapError(e.to_string()))?;
+
+    info!("Replaying '{}' on '{}' ({:?})", path, interface_name, timing);
+    let mut report = ReplayReport::default();
+    let start = Instant::now();
+    let mut first_ts: Option<Duration> = None;
+
+    loop {
+        let packet = match input.next_packet() {
+            Ok(packet) => packet,
+            Err(pcap::Error::NoMorePackets) => break,
+            Err(e) => return Err(CaptureError::PcapError(e.to_string())),
+        };
+
+        let target = match timing {
+            ReplayTiming::Original => {
+                let ts = Duration::new(packet.header.ts.tv_sec as u64, packet.header.ts.tv_usec as u32 * 1000);
+                let first = *first_ts.get_or_insert(ts);
+                Some(ts.saturating_sub(first))
+            }
+            ReplayTiming::FixedRate(pps) => Some(Duration::from_secs_f64(report.sent as f64 / pps.max(1) as f64)),
+            ReplayTiming::MaxSpeed => None,
+        };
+        if let Some(target) = target {
+            let now = start.elapsed();
+            if target > now {
+                thread::sleep(target - now);
+            }
+        }
+
+        let mut frame = packet.data.to_vec();
+        if !rules.is_empty() {
+            rewrite_frame(&mut frame, rules);
+        }
+        match output.sendpacket(frame.as_slice()) {
+            Ok(()) => {
+                report.sent += 1;
+                report.bytes += frame.len() as u64;
+            }
+            Err(e) => {
+                warn!("Failed to send packet: {}", e);
+                report.failed += 1;
+            }
+        }
+    }
+
+    report.elapsed = start.elapsed();
+    info!(
+        "Replay completed. Sent: {}, failed: {}, bytes: {}, elapsed: {:.3}s",
+        report.sent,
+        report.failed,
+        report.bytes,
+        report.elapsed.as_secs_f64()
+    );
+    Ok(report)
+}
+
+/// Applies MAC/IP rewriting to an Ethernet frame in place, fixing up the
+/// IPv4 header checksum and TCP/UDP checksums when addresses change.
+pub fn rewrite_frame(frame: &mut [u8], rules: &RewriteRules) {
+    if frame.len() < 14 {
+        return;
+    }
+    if let Some(mac) = rules.dst_mac {
+        frame[0..6].copy_from_slice(&mac.octets());
+    }
+    if let Some(mac) = rules.src_mac {
+        frame[6..12].copy_from_slice(&mac.octets());
+    }
+    if rules.ip_map.is_empty() {
+        return;
+    }
+    rewrite_ip_addresses(frame, |addr| map_ip(rules, addr), false);
+}
+
+/// Maps the IPv4/IPv6 source and destination addresses of an (optionally VLAN
+/// tagged) Ethernet frame in place, recomputing the IPv4 header and transport checksums when an address
+/// changes, or always with `force_checksums` (for callers that edited the payload).
+pub(crate) fn rewrite_ip_addresses(frame: &mut [u8], map: impl FnMut(IpAddr) -> IpAddr, force_checksums: bool) {
+    if let Some((ether_type, offset)) = ethernet_payload(frame) {
+        rewrite_ip_packet(ether_type, &mut frame[offset..], map, force_checksums);
+    }
+}
+
+/// EtherType and offset of the network header of an Ethernet frame, past any VLAN tags
+pub(crate) fn ethernet_payload(frame: &[u8]) -> Option<(u16, usize)> {
+    if frame.len() < 14 {
+        return None;
+    }
+    let mut ether_type = u16::from_be_bytes([frame[12], frame[13]]);
+    let mut offset = 14;
+    // Step over 802.1Q / 802.1ad tags
+    while matches!(ether_type, 0x8100 | 0x88A8) && frame.len() >= offset + 4 {
+        ether_type = u16::from_be_bytes([frame[offset + 2], frame[offset + 3]]);
+        offset += 4;
+    }
+    Some((ether_type, offset))
+}
+
+/// `rewrite_ip_addresses` for a bare IPv4/IPv6 packet of the given EtherType
+pub(crate) fn rewrite_ip_packet(ether_type: u16, ip: &mut [u8], mut map: impl FnMut(IpAddr) -> IpAddr, force_checksums: bool) {
+    match ether_type {
+        0x0800 if ip.len() >= 20 => {
+            let ihl = ((ip[0] & 0x0F) as usize) * 4;
+            if ihl < 20 || ip.len() < ihl {
+                return;
+            }
+            let src = IpAddr::V4(Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]));
+            let dst = IpAddr::V4(Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19]));
+            let (new_src, new_dst) = (map(src), map(dst));
+            if new_src == src && new_dst == dst && !force_checksums {
+                return;
+            }
+            if let (IpAddr::V4(s), IpAddr::V4(d)) = (new_src, new_dst) {
+                ip[12..16].copy_from_slice(&s.octets());
+                ip[16..20].copy_from_slice(&d.octets());
+            }
+            ip[10..12].copy_from_slice(&[0, 0]);
+            let checksum = internet_checksum(&ip[..ihl]);
+            ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+            let protocol = ip[9];
+            let fragment_offset = u16::from_be_bytes([ip[6] & 0x1F, ip[7]]);
+            // Ethernet pads short frames; the trailer isn't part of the segment
+            let end = (u16::from_be_bytes([ip[2], ip[3]]) as usize).clamp(ihl, ip.len());
+            if fragment_offset == 0 {
+                fix_transport_checksum(&mut ip[ihl..end], protocol, new_src, new_dst);
+            }
+        }
+        0x86DD if ip.len() >= 40 => {
+            let mut src = [0u8; 16];
+            let mut dst = [0u8; 16];
+            src.copy_from_slice(&ip[8..24]);
+            dst.copy_from_slice(&ip[24..40]);
+            let (src, dst) = (IpAddr::V6(Ipv6Addr::from(src)), IpAddr::V6(Ipv6Addr::from(dst)));
+            let (new_src, new_dst) = (map(src), map(dst));
+            if new_src == src && new_dst == dst && !force_checksums {
+                return;
+            }
+            if let (IpAddr::V6(s), IpAddr::V6(d)) = (new_src, new_dst) {
+                ip[8..24].copy_from_slice(&s.octets());
+                ip[24..40].copy_from_slice(&d.octets());
+            }
+            let end = (40 + u16::from_be_bytes([ip[4], ip[5]]) as usize).min(ip.len());
+            let (next_header, header_len) = skip_ipv6_extensions(&ip[..end]);
+            fix_transport_checksum(&mut ip[header_len..end], next_header, new_src, new_dst);
+        }
+        _ => {}
+    }
+}
+
+fn map_ip(rules: &RewriteRules, addr: IpAddr) -> IpAddr {
+    rules.ip_map.get(&addr).copied().unwrap_or(addr)
+}
+
+fn fix_transport_checksum(segment: &mut [u8], protocol: u8, src: IpAddr, dst: IpAddr) {
+    let offset = match protocol {
+        6 if segment.len() >= 20 => 16,
+        17 if segment.len() >= 8 => 6,
+        58 if segment.len() >= 4 => 2,
+        _ => return,
+    };
+    // UDP over IPv4 may legitimately carry no checksum
+    if protocol == 17 && src.is_ipv4() && segment[6..8] == [0, 0] {
+        return;
+    }
+    segment[offset..offset + 2].copy_from_slice(&[0, 0]);
+    let checksum = transport_checksum(src, dst, protocol, segment);
+    segment[offset..offset + 2].copy_from_slice(&checksum.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::PacketBuilder;
+    use crate::testutil;
+
+    #[test]
+    fn rewrites_addresses_and_checksums() {
+        let mut rules = RewriteRules {
+            src_mac: Some("02:00:00:00:00:01".parse().unwrap()),
+            ..Default::default()
+        };
+        rules.add_ip_mapping("192.168.1.10=10.1.1.1").unwrap();
+
+        let mut frame = testutil::ipv4_tcp_frame(40000, 80, testutil::TCP_SYN, b"GET /");
+        rewrite_frame(&mut frame, &rules);
+
+        let expected = testutil::frame()
+            .src_mac([0x02, 0, 0, 0, 0, 1])
+            .ipv4(Ipv4Addr::new(10, 1, 1, 1), testutil::server_v4())
+            .tcp(40000, 80)
+            .seq(1)
+            .tcp_flags(testutil::TCP_SYN)
+            .payload(b"GET /")
+            .build();
+        assert_eq!(frame, expected);
+    }
+
+    #[test]
+    fn rewrites_ipv6_udp() {
+        let mut rules = RewriteRules::default();
+        rules.add_ip_mapping("2001:db8::2=2001:db8::99").unwrap();
+
+        let mut frame = testutil::ipv6_udp_frame(1000, 53, b"x");
+        rewrite_frame(&mut frame, &rules);
+
+        let expected = PacketBuilder::ethernet()
+            .src_mac(testutil::SRC_MAC)
+            .dst_mac(testutil::DST_MAC)
+            .ipv6(testutil::client_v6(), "2001:db8::99".parse().unwrap())
+            .udp(1000, 53)
+            .payload(b"x")
+            .build();
+        assert_eq!(frame, expected);
+    }
+
+    #[test]
+    fn leaves_ethernet_padding_out_of_the_checksum() {
+        let mut rules = RewriteRules::default();
+        rules.add_ip_mapping("192.168.1.10=10.1.1.1").unwrap();
+
+        // Bare ACK padded to the 60-byte Ethernet minimum
+        let mut frame = testutil::ipv4_tcp_frame(40000, 80, testutil::TCP_ACK, b"");
+        frame.resize(60, 0);
+        rewrite_frame(&mut frame, &rules);
+
+        let mut expected = testutil::frame()
+            .ipv4(Ipv4Addr::new(10, 1, 1, 1), testutil::server_v4())
+            .tcp(40000, 80)
+            .seq(1)
+            .tcp_flags(testutil::TCP_ACK)
+            .build();
+        expected.resize(60, 0);
+        assert_eq!(frame, expected);
+    }
+
+    #[test]
+    fn rejects_bad_mappings() {
+        let mut rules = RewriteRules::default();
+        assert!(rules.add_ip_mapping("10.0.0.1").is_err());
+        assert!(rules.add_ip_mapping("10.0.0.1=::1").is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use crate::protocols::ethernet::MacAddress;
+use crate::protocols::hsrp::{HsrpMessage, HSRP_IPV6_PORT, HSRP_PORT};
+use crate::protocols::ospf::{OspfPacket, IP_PROTO_OSPF, OSPF_HELLO};
+use crate::protocols::summary::PacketSummary;
+use crate::protocols::vrrp::{VrrpPacket, IP_PROTO_VRRP};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+
+/// Where an OSPF router was first heard from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OspfSpeaker {
+    pub mac: MacAddress,
+    pub ip: IpAddr,
+    pub area: Ipv4Addr,
+}
+
+/// Watches OSPF Hellos for routers joining the segment. With an expected
+/// router list, anything else is flagged; without one, each router is
+/// reported the first time it speaks. A known router ID reappearing from
+/// another host is always flagged.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct OspfMonitor {
+    /// From the configuration, so not part of saved state
+    #[serde(skip)]
+    expected: Vec<Ipv4Addr>,
+    speakers: HashMap<Ipv4Addr, OspfSpeaker>,
+}
+
+impl OspfMonitor {
+    pub fn new(expected: Vec<Ipv4Addr>) -> Self {
+        OspfMonitor { expected, speakers: HashMap::new() }
+    }
+
+    /// Replaces the expected router list, keeping the routers already heard
+    pub fn set_expected(&mut self, expected: Vec<Ipv4Addr>) {
+        self.expected = expected;
+    }
+
+    /// Takes over the routers a previous run had heard, keeping the expected list
+    pub fn restore(&mut self, saved: OspfMonitor) {
+        self.speakers = saved.speakers;
+    }
+
+    pub fn speakers(&self) -> &HashMap<Ipv4Addr, OspfSpeaker> {
+        &self.speakers
+    }
+
+    /// Feeds one captured packet, returning alert messages
+    pub fn push(&mut self, summary: &PacketSummary, data: &[u8]) -> Vec<String> {
+        let (Some(IP_PROTO_OSPF), Some(ip)) = (summary.ip_proto, summary.src_ip) else { return Vec::new() };
+        let Ok(packet) = OspfPacket::parse(summary.payload(data)) else { return Vec::new() };
+        if packet.packet_type() != OSPF_HELLO {
+            return Vec::new();
+        }
+        let router = packet.router_id();
+        let speaker = OspfSpeaker { mac: summary.src_mac, ip, area: packet.area_id() };
+        match self.speakers.insert(router, speaker) {
+            None if self.expected.is_empty() => vec![format!(
+                "New OSPF speaker: router {} in area {} from {} ({})",
+                router, speaker.area, ip, speaker.mac
+            )],
+            None if !self.expected.contains(&router) => vec![format!(
+                "Unexpected OSPF speaker: router {} in area {} from {} ({})",
+                router, speaker.area, ip, speaker.mac
+            )],
+            Some(previous) if previous.mac != speaker.mac || previous.ip != speaker.ip => vec![format!(
+                "OSPF router ID {} now advertised by {} ({}), previously {} ({})",
+                router, ip, speaker.mac, previous.ip, previous.mac
+            )],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// First-hop redundancy protocol a gateway group runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RedundancyProtocol {
+    Vrrp,
+    Hsrp,
+}
+
+impl RedundancyProtocol {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RedundancyProtocol::Vrrp => "VRRP",
+            RedundancyProtocol::Hsrp => "HSRP",
+        }
+    }
+}
+
+/// The router currently forwarding for a virtual gateway
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GatewayMaster {
+    pub mac: MacAddress,
+    pub ip: IpAddr,
+    pub priority: u32,
+}
+
+/// Tracks the master of each VRRP and HSRP group and reports takeovers.
+/// A change of master and an advertiser outbidding the current master are
+/// what a gateway hijack looks like on the wire.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GatewayMonitor {
+    masters: HashMap<(RedundancyProtocol, u16), GatewayMaster>,
+}
+
+impl GatewayMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn masters(&self) -> &HashMap<(RedundancyProtocol, u16), GatewayMaster> {
+        &self.masters
+    }
+
+    /// Feeds one captured packet, returning alert messages
+    pub fn push(&mut self, summary: &PacketSummary, data: &[u8]) -> Vec<String> {
+        let Some(ip) = summary.src_ip else { return Vec::new() };
+        let payload = summary.payload(data);
+        let (protocol, group, priority, active) = if summary.ip_proto == Some(IP_PROTO_VRRP) {
+            let Ok(vrrp) = VrrpPacket::parse(payload, ip.is_ipv6()) else { return Vec::new() };
+            // Only the master advertises; priority 0 announces it is stepping down
+            (RedundancyProtocol::Vrrp, vrrp.vrid() as u16, vrrp.priority() as u32, vrrp.priority() != 0)
+        } else if summary.is_udp() && (summary.has_port(HSRP_PORT) || summary.has_port(HSRP_IPV6_PORT)) {
+            let Ok(hsrp) = HsrpMessage::parse(payload) else { return Vec::new() };
+            (RedundancyProtocol::Hsrp, hsrp.group, hsrp.priority, hsrp.is_active())
+        } else {
+            return Vec::new();
+        };
+
+        let advertiser = GatewayMaster { mac: summary.src_mac, ip, priority };
+        let key = (protocol, group);
+        let Some(master) = self.masters.get(&key).copied() else {
+            if active {
+                self.masters.insert(key, advertiser);
+            }
+            return Vec::new();
+        };
+        if master.mac == advertiser.mac && master.ip == advertiser.ip {
+            if active {
+                self.masters.insert(key, advertiser);
+            }
+            return Vec::new();
+        }
+
+        let mut alerts = Vec::new();
+        if advertiser.priority > master.priority {
+            alerts.push(format!(
+                "{} group {}: {} ({}) advertises priority {}, above master {} ({}) at {}",
+                protocol.name(),
+                group,
+                advertiser.ip,
+                advertiser.mac,
+                advertiser.priority,
+                master.ip,
+                master.mac,
+                master.priority
+            ));
+        }
+        if active {
+            alerts.push(format!(
+                "{} group {}: master changed from {} ({}) to {} ({}), priority {} -> {}",
+                protocol.name(),
+                group,
+                master.ip,
+                master.mac,
+                advertiser.ip,
+                advertiser.mac,
+                master.priority,
+                advertiser.priority
+            ));
+            self.masters.insert(key, advertiser);
+        }
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::hsrp::{STATE_ACTIVE, STATE_STANDBY};
+    use crate::testutil;
+
+    fn summary(frame: &[u8]) -> PacketSummary {
+        PacketSummary::from_ethernet(frame).unwrap()
+    }
+
+    fn hello(mac: [u8; 6], src: Ipv4Addr, router: Ipv4Addr) -> Vec<u8> {
+        let payload = testutil::ospf_hello(router, Ipv4Addr::UNSPECIFIED, &[]);
+        let ip = testutil::ipv4(src, Ipv4Addr::new(224, 0, 0, 5), IP_PROTO_OSPF, &payload);
+        testutil::ethernet(mac, [0x01, 0x00, 0x5e, 0x00, 0x00, 0x05], testutil::ETHERTYPE_IPV4, &ip)
+    }
+
+    #[test]
+    fn flags_unexpected_and_moved_routers() {
+        let core = Ipv4Addr::new(10, 255, 0, 1);
+        let mut monitor = OspfMonitor::new(vec![core]);
+        let core_hello = hello(testutil::SRC_MAC, Ipv4Addr::new(10, 0, 0, 1), core);
+        assert!(monitor.push(&summary(&core_hello), &core_hello).is_empty());
+        assert!(monitor.push(&summary(&core_hello), &core_hello).is_empty());
+
+        let rogue = hello(testutil::DST_MAC, Ipv4Addr::new(10, 0, 0, 66), Ipv4Addr::new(6, 6, 6, 6));
+        let alerts = monitor.push(&summary(&rogue), &rogue);
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].starts_with("Unexpected OSPF speaker: router 6.6.6.6 in area 0.0.0.0 from 10.0.0.66"));
+        assert!(monitor.push(&summary(&rogue), &rogue).is_empty());
+
+        let spoofed = hello(testutil::DST_MAC, Ipv4Addr::new(10, 0, 0, 66), core);
+        let spoofed = monitor.push(&summary(&spoofed), &spoofed);
+        assert!(spoofed[0].contains("previously 10.0.0.1"));
+    }
+
+    fn vrrp(mac: [u8; 6], src: Ipv4Addr, priority: u8) -> Vec<u8> {
+        let payload = [0x21, 10, priority, 1, 0, 1, 0, 0, 192, 168, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0];
+        let ip = testutil::ipv4(src, Ipv4Addr::new(224, 0, 0, 18), IP_PROTO_VRRP, &payload);
+        testutil::ethernet(mac, [0x00, 0x00, 0x5e, 0x00, 0x01, 10], testutil::ETHERTYPE_IPV4, &ip)
+    }
+
+    #[test]
+    fn reports_gateway_takeovers() {
+        let mut monitor = GatewayMonitor::new();
+        let master = vrrp(testutil::SRC_MAC, Ipv4Addr::new(192, 168, 1, 2), 100);
+        assert!(monitor.push(&summary(&master), &master).is_empty());
+        assert!(monitor.push(&summary(&master), &master).is_empty());
+
+        let rogue = vrrp(testutil::DST_MAC, Ipv4Addr::new(192, 168, 1, 66), 254);
+        let alerts = monitor.push(&summary(&rogue), &rogue);
+        assert_eq!(alerts.len(), 2);
+        assert!(alerts[0].contains("advertises priority 254, above master 192.168.1.2"));
+        assert!(alerts[1].starts_with("VRRP group 10: master changed from 192.168.1.2"));
+        assert_eq!(monitor.masters()[&(RedundancyProtocol::Vrrp, 10)].priority, 254);
+    }
+
+    #[test]
+    fn ignores_standby_routers_until_they_take_over() {
+        let mut monitor = GatewayMonitor::new();
+        let hsrp = |state: u8, priority: u8, mac: [u8; 6], last: u8| {
+            let payload = [0, 0, state, 3, 10, priority, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 192, 168, 1, 254];
+            testutil::frame()
+                .src_mac(mac)
+                .ipv4(Ipv4Addr::new(192, 168, 1, last), Ipv4Addr::new(224, 0, 0, 2))
+                .udp(HSRP_PORT, HSRP_PORT)
+                .payload(&payload)
+                .build()
+        };
+        let (active, standby) = (hsrp(STATE_ACTIVE, 110, testutil::SRC_MAC, 2), hsrp(STATE_STANDBY, 100, testutil::DST_MAC, 3));
+        assert!(monitor.push(&summary(&active), &active).is_empty());
+        assert!(monitor.push(&summary(&standby), &standby).is_empty());
+
+        let takeover = hsrp(STATE_ACTIVE, 100, testutil::DST_MAC, 3);
+        let alerts = monitor.push(&summary(&takeover), &takeover);
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].starts_with("HSRP group 1: master changed from 192.168.1.2"));
+    }
+
+    #[test]
+    fn reports_each_new_router_without_a_list() {
+        let mut monitor = OspfMonitor::default();
+        let frame = hello(testutil::SRC_MAC, Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(1, 1, 1, 1));
+        assert_eq!(monitor.push(&summary(&frame), &frame).len(), 1);
+        assert!(monitor.push(&summary(&frame), &frame).is_empty());
+        assert_eq!(monitor.speakers().len(), 1);
+    }
+}
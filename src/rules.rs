@@ -0,0 +1,379 @@
+use crate::alert::Severity;
+use crate::attack;
+use crate::error::CaptureError;
+use crate::flow::{FlowKey, FlowRecord, FlowTracker};
+use crate::meta::Direction;
+use crate::protocols::summary::PacketSummary;
+use log::warn;
+use maxminddb::geoip2;
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+use serde::Deserialize;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Flows are forgotten after this long without packets
+const RULE_FLOW_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Work a single rule may do per packet before it is stopped
+const MAX_OPERATIONS: u64 = 10_000;
+
+/// An alert rule from the configuration file (`[[alerts.rules]]`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct RuleConfig {
+    pub name: String,
+    /// Rhai expression over `packet`, `flow`, `src` and `dst`, e.g.
+    /// `flow.bytes_out > 10MB && dst.country != "US"`
+    pub condition: String,
+    #[serde(default)]
+    pub severity: Severity,
+    /// Seconds between two alerts from the rule; matches in between are counted
+    #[serde(default = "default_throttle")]
+    pub throttle: u64,
+    /// ATT&CK technique IDs the rule detects, such as `T1071.001`
+    #[serde(default)]
+    pub techniques: Vec<String>,
+}
+
+fn default_throttle() -> u64 {
+    60
+}
+
+struct Rule {
+    name: String,
+    severity: Severity,
+    throttle: f64,
+    techniques: Vec<String>,
+    ast: AST,
+}
+
+/// Lookups in a MaxMind GeoLite2/GeoIP2 database: countries from a Country
+/// or City database, autonomous systems from an ASN one
+pub struct GeoIp(maxminddb::Reader<Vec<u8>>);
+
+impl GeoIp {
+    pub fn open(path: &Path) -> Result<Self, CaptureError> {
+        maxminddb::Reader::open_readfile(path)
+            .map(GeoIp)
+            .map_err(|e| CaptureError::InputError(format!("Cannot open GeoIP database {}: {}", path.display(), e)))
+    }
+
+    /// ISO country code, empty when the address isn't in the database
+    pub fn country(&self, ip: IpAddr) -> String {
+        self.0
+            .lookup::<geoip2::Country>(ip)
+            .ok()
+            .and_then(|record| record.country?.iso_code)
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// AS number and organization, `None` when the address isn't in the database
+    pub fn asn(&self, ip: IpAddr) -> Option<(u32, String)> {
+        let record = self.0.lookup::<geoip2::Asn>(ip).ok()?;
+        Some((record.autonomous_system_number?, record.autonomous_system_organization.unwrap_or_default().to_string()))
+    }
+}
+
+/// Compiled alert rules, shared by every capture thread and replaced on reload
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    geoip: Option<GeoIp>,
+}
+
+impl RuleSet {
+    /// Compiles every rule, failing on the first one that doesn't parse
+    pub fn compile(configs: &[RuleConfig], geoip: Option<GeoIp>) -> Result<Self, CaptureError> {
+        let engine = rule_engine();
+        let rules = configs
+            .iter()
+            .map(|config| {
+                let ast = engine
+                    .compile_expression(expand_units(&config.condition))
+                    .map_err(|e| CaptureError::InputError(format!("Rule '{}': {}", config.name, e)))?;
+                attack::validate(&format!("Rule '{}'", config.name), &config.techniques)?;
+                Ok(Rule {
+                    name: config.name.clone(),
+                    severity: config.severity,
+                    throttle: config.throttle as f64,
+                    techniques: config.techniques.clone(),
+                    ast,
+                })
+            })
+            .collect::<Result<_, CaptureError>>()?;
+        Ok(RuleSet { rules, geoip })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+fn rule_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_expr_depths(32, 32);
+    engine
+}
+
+/// Rewrites size literals such as `10MB` to `(10 * 1048576)`; KB, MB, GB and
+/// TB are powers of 1024. String literals are left alone.
+pub fn expand_units(condition: &str) -> String {
+    const UNITS: [(&str, u64); 4] = [("KB", 1 << 10), ("MB", 1 << 20), ("GB", 1 << 30), ("TB", 1 << 40)];
+    let chars: Vec<char> = condition.chars().collect();
+    let mut out = String::with_capacity(condition.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '"' || c == '\'' || c == '`' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i = (i + 1).min(chars.len());
+            out.extend(&chars[start..i]);
+            continue;
+        }
+        let follows_word = i > 0 && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '_');
+        if c.is_ascii_digit() && !follows_word {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.' || chars[i] == '_') {
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().collect();
+            let unit = UNITS.iter().find(|(suffix, _)| {
+                let end = i + suffix.len();
+                end <= chars.len()
+                    && chars[i..end].iter().copied().eq(suffix.chars())
+                    && !chars.get(end).is_some_and(|&next| next.is_alphanumeric() || next == '_')
+            });
+            match unit {
+                Some((suffix, factor)) => {
+                    out.push_str(&format!("({} * {})", number, factor));
+                    i += suffix.len();
+                }
+                None => out.push_str(&number),
+            }
+            continue;
+        }
+        out.push(c);
+        i += 1;
+    }
+    out
+}
+
+/// A rule that matched, with the number of matches its throttle swallowed since the last alert
+#[derive(Debug, Clone)]
+pub struct RuleAlert {
+    pub rule: String,
+    pub severity: Severity,
+    pub flow: Option<FlowKey>,
+    pub suppressed: u64,
+    pub techniques: Vec<String>,
+}
+
+impl RuleAlert {
+    /// The external endpoints of the matching flow
+    pub fn hosts(&self) -> Vec<IpAddr> {
+        let Some(flow) = &self.flow else { return Vec::new() };
+        [flow.a.0, flow.b.0].into_iter().filter(|&ip| !is_internal(ip)).collect()
+    }
+}
+
+impl fmt::Display for RuleAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] Rule '{}' matched", self.severity, self.rule)?;
+        if let Some(flow) = &self.flow {
+            write!(f, " on {}", flow)?;
+        }
+        if self.suppressed > 0 {
+            write!(f, " ({} more matches throttled)", self.suppressed)?;
+        }
+        Ok(())
+    }
+}
+
+/// Evaluates a rule set on every packet with the flow it belongs to
+pub struct RuleEngine {
+    engine: Engine,
+    set: Arc<RuleSet>,
+    /// Per rule: capture time of the last alert and matches throttled since
+    fired: Vec<(Option<f64>, u64)>,
+    /// Rules whose evaluation error was already logged
+    failed: Vec<bool>,
+    flows: FlowTracker,
+}
+
+impl RuleEngine {
+    pub fn new(set: Arc<RuleSet>) -> Self {
+        let count = set.rules.len();
+        RuleEngine {
+            engine: rule_engine(),
+            set,
+            fired: vec![(None, 0); count],
+            failed: vec![false; count],
+            flows: FlowTracker::new(RULE_FLOW_TIMEOUT),
+        }
+    }
+
+    /// Switches to reloaded rules; flow totals are kept, throttling starts over
+    pub fn replace(&mut self, set: Arc<RuleSet>) {
+        self.fired = vec![(None, 0); set.rules.len()];
+        self.failed = vec![false; set.rules.len()];
+        self.set = set;
+    }
+
+    /// Alerts from the rules matching a packet seen at `time` (seconds)
+    pub fn push(&mut self, summary: &PacketSummary, time: f64, direction: Direction) -> Vec<RuleAlert> {
+        let ended = self.flows.push(summary, time);
+        let key = FlowKey::from_summary(summary);
+        let flow = key.and_then(|key| self.flows.flow(&key).or_else(|| ended.iter().find(|r| r.key == key)));
+
+        let mut scope = Scope::new();
+        scope.push_constant("packet", packet_map(summary, time, direction));
+        scope.push_constant("flow", flow_map(flow));
+        scope.push_constant("src", endpoint_map(summary.src_ip, summary.src_port, self.set.geoip.as_ref()));
+        scope.push_constant("dst", endpoint_map(summary.dst_ip, summary.dst_port, self.set.geoip.as_ref()));
+
+        let mut alerts = Vec::new();
+        for (index, rule) in self.set.rules.iter().enumerate() {
+            match self.engine.eval_ast_with_scope::<bool>(&mut scope, &rule.ast) {
+                Ok(false) => {}
+                Ok(true) => {
+                    let (last, suppressed) = &mut self.fired[index];
+                    if last.is_some_and(|last| time - last < rule.throttle) {
+                        *suppressed += 1;
+                        continue;
+                    }
+                    alerts.push(RuleAlert {
+                        rule: rule.name.clone(),
+                        severity: rule.severity,
+                        flow: key,
+                        suppressed: *suppressed,
+                        techniques: rule.techniques.clone(),
+                    });
+                    *last = Some(time);
+                    *suppressed = 0;
+                }
+                // Usually a type mismatch on packets the rule wasn't written for
+                Err(e) if !self.failed[index] => {
+                    self.failed[index] = true;
+                    warn!("Rule '{}' failed: {} (further errors not shown)", rule.name, e);
+                }
+                Err(_) => {}
+            }
+        }
+        alerts
+    }
+}
+
+fn int(value: impl TryInto<i64>) -> Dynamic {
+    Dynamic::from_int(value.try_into().unwrap_or(i64::MAX))
+}
+
+fn packet_map(summary: &PacketSummary, time: f64, direction: Direction) -> Map {
+    let mut map = Map::new();
+    map.insert("time".into(), Dynamic::from_float(time));
+    map.insert("direction".into(), direction.to_string().into());
+    map.insert("length".into(), int(summary.length));
+    map.insert("payload_len".into(), int(summary.length.saturating_sub(summary.payload_offset)));
+    map.insert("protocol".into(), summary.protocol_name().into());
+    map.insert("ether_type".into(), int(summary.ether_type));
+    map.insert("vlan".into(), int(summary.vlan.unwrap_or(0)));
+    map.insert("ip_proto".into(), int(summary.ip_proto.unwrap_or(0)));
+    map.insert("ttl".into(), int(summary.ttl.unwrap_or(0)));
+    map.insert("tcp_flags".into(), int(summary.tcp_flags.unwrap_or(0)));
+    map
+}
+
+fn flow_map(flow: Option<&FlowRecord>) -> Map {
+    let (packets, bytes, packets_out, bytes_out, duration) =
+        flow.map_or((0, 0, 0, 0, 0.0), |f| (f.packets, f.bytes, f.packets_out, f.bytes_out, f.last - f.first));
+    let mut map = Map::new();
+    map.insert("packets".into(), int(packets));
+    map.insert("bytes".into(), int(bytes));
+    map.insert("packets_out".into(), int(packets_out));
+    map.insert("bytes_out".into(), int(bytes_out));
+    map.insert("packets_in".into(), int(packets - packets_out));
+    map.insert("bytes_in".into(), int(bytes - bytes_out));
+    map.insert("duration".into(), Dynamic::from_float(duration));
+    map
+}
+
+/// Private, loopback and link-local addresses
+pub fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_private() || ip.is_loopback() || ip.is_link_local(),
+        IpAddr::V6(ip) => ip.is_loopback() || (ip.segments()[0] & 0xfe00) == 0xfc00 || (ip.segments()[0] & 0xffc0) == 0xfe80,
+    }
+}
+
+fn endpoint_map(ip: Option<IpAddr>, port: Option<u16>, geoip: Option<&GeoIp>) -> Map {
+    let internal = ip.is_some_and(is_internal);
+    let mut map = Map::new();
+    map.insert("ip".into(), ip.map(|ip| ip.to_string()).unwrap_or_default().into());
+    map.insert("port".into(), int(port.unwrap_or(0)));
+    map.insert("internal".into(), internal.into());
+    map.insert("country".into(), ip.zip(geoip).map(|(ip, geoip)| geoip.country(ip)).unwrap_or_default().into());
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::testutil;
+
+    #[test]
+    fn expands_size_units_outside_strings() {
+        assert_eq!(expand_units("flow.bytes_out > 10MB"), "flow.bytes_out > (10 * 1048576)");
+        assert_eq!(expand_units("x > 1.5KB && y == \"2MB\""), "x > (1.5 * 1024) && y == \"2MB\"");
+        assert_eq!(expand_units("v6MB > 2MBit"), "v6MB > 2MBit");
+    }
+
+    #[test]
+    fn alerts_on_flow_totals_with_throttling() {
+        let config = Config::parse(
+            r#"
+            [[alerts.rules]]
+            name = "upload"
+            condition = 'flow.bytes_out > 1KB && dst.port == 443 && !dst.internal && dst.country != "US" && packet.direction == "outbound"'
+            severity = "high"
+            throttle = 10
+            techniques = ["T1048"]
+            "#,
+        )
+        .unwrap();
+        let set = RuleSet::compile(&config.alerts.rules, None).unwrap();
+        let mut engine = RuleEngine::new(Arc::new(set));
+        let frame = testutil::ipv4_tcp_frame(40000, 443, testutil::TCP_ACK, &[0; 600]);
+        let summary = PacketSummary::from_ethernet(&frame).unwrap();
+
+        assert!(engine.push(&summary, 0.0, Direction::Outbound).is_empty());
+        let alerts = engine.push(&summary, 1.0, Direction::Outbound);
+        assert_eq!(alerts[0].to_string(), "[HIGH] Rule 'upload' matched on TCP 93.184.216.34:443 <-> 192.168.1.10:40000");
+        assert!(engine.push(&summary, 2.0, Direction::Outbound).is_empty());
+        assert!(engine.push(&summary, 3.0, Direction::Outbound).is_empty());
+        let alert = &engine.push(&summary, 11.0, Direction::Outbound)[0];
+        assert_eq!((alert.suppressed, alert.techniques.as_slice(), alert.hosts()), (2, &["T1048".to_string()][..], vec![testutil::server_v4().into()]));
+        assert!(engine.push(&summary, 30.0, Direction::Inbound).is_empty());
+    }
+
+    #[test]
+    fn rejects_rules_that_do_not_compile() {
+        let rule = RuleConfig {
+            name: "broken".to_string(),
+            condition: "flow.bytes >".to_string(),
+            severity: Severity::Low,
+            throttle: 0,
+            techniques: Vec::new(),
+        };
+        let error = RuleSet::compile(std::slice::from_ref(&rule), None).err().unwrap();
+        assert!(error.to_string().contains("Rule 'broken'"));
+        let rule = RuleConfig { condition: "true".to_string(), techniques: vec!["exfil".to_string()], ..rule };
+        assert!(RuleSet::compile(&[rule], None).err().unwrap().to_string().contains("not an ATT&CK technique ID"));
+    }
+}
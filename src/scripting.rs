@@ -0,0 +1,256 @@
+use crate::error::CaptureError;
+use std::path::Path;
+
+/// Flows without packets for this long are reported to `on_flow_end` as idle
+pub const FLOW_IDLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
+/// A Lua script read before the capture starts, so it can be loaded after
+/// privileges are dropped and under `--sandbox`
+#[derive(Debug, Clone)]
+pub struct Script {
+    pub name: String,
+    pub source: String,
+}
+
+impl Script {
+    pub fn read(path: &Path) -> Result<Self, CaptureError> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| CaptureError::InputError(format!("Cannot read script {}: {}", path.display(), e)))?;
+        let name = path.file_name().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        Ok(Script { name, source })
+    }
+}
+
+#[cfg(feature = "lua")]
+pub use lua::ScriptHooks;
+
+#[cfg(feature = "lua")]
+mod lua {
+    use super::{Script, FLOW_IDLE_TIMEOUT};
+    use crate::error::CaptureError;
+    use crate::flow::{FlowRecord, FlowTracker};
+    use crate::protocols::analyze_link_layer;
+    use crate::protocols::summary::PacketSummary;
+    use log::{info, warn};
+    use mlua::{Function, IntoLuaMulti, Lua, Table, Value};
+    use std::cell::Cell;
+
+    /// Hook errors logged before the rest are counted silently
+    const MAX_REPORTED_ERRORS: u32 = 10;
+
+    /// A user script with optional `on_packet(packet)`, `on_flow_end(flow)`
+    /// and `on_alert(message, source)` functions. `on_packet` gets the packet's
+    /// summary and decoded fields; a string it returns is logged as a tag for
+    /// the packet. Errors in a hook are logged and the capture carries on.
+    pub struct ScriptHooks {
+        lua: Lua,
+        name: String,
+        /// Only tracked when the script defines `on_flow_end`
+        flows: Option<FlowTracker>,
+        errors: Cell<u32>,
+    }
+
+    impl ScriptHooks {
+        /// Runs the script's top-level code, which defines the hooks and any state they share
+        pub fn new(script: &Script) -> Result<Self, CaptureError> {
+            let lua = Lua::new();
+            let error = |e: mlua::Error| CaptureError::InputError(format!("Script {}: {}", script.name, e));
+            // `sniffer.log(message)` writes to the sniffer's log
+            let sniffer = lua.create_table().map_err(error)?;
+            let log = lua
+                .create_function(|_, message: String| {
+                    info!("{}", message);
+                    Ok(())
+                })
+                .map_err(error)?;
+            sniffer.set("log", log).and_then(|()| lua.globals().set("sniffer", sniffer)).map_err(error)?;
+            lua.load(script.source.as_str()).set_name(script.name.as_str()).exec().map_err(error)?;
+
+            let mut hooks = ScriptHooks { lua, name: script.name.clone(), flows: None, errors: Cell::new(0) };
+            if hooks.hook("on_flow_end").is_some() {
+                hooks.flows = Some(FlowTracker::new(FLOW_IDLE_TIMEOUT));
+            }
+            info!("Loaded script {}", hooks.name);
+            Ok(hooks)
+        }
+
+        fn hook(&self, name: &str) -> Option<Function<'_>> {
+            self.lua.globals().get::<_, Option<Function>>(name).ok().flatten()
+        }
+
+        fn call<'lua>(&'lua self, name: &str, args: impl IntoLuaMulti<'lua>) -> Option<Value<'lua>> {
+            let result = self.hook(name)?.call::<_, Value>(args);
+            result.map_err(|e| self.hook_error(name, e)).ok()
+        }
+
+        fn hook_error(&self, hook: &str, e: mlua::Error) {
+            let errors = self.errors.get() + 1;
+            self.errors.set(errors);
+            if errors <= MAX_REPORTED_ERRORS {
+                warn!("Script {}: {} failed: {}", self.name, hook, e);
+            }
+            if errors == MAX_REPORTED_ERRORS {
+                warn!("Script {}: not reporting further errors", self.name);
+            }
+        }
+
+        /// Calls `on_packet` and feeds flow tracking; returns the packet's tag, if any
+        pub fn on_packet(&mut self, time: f64, datalink: i32, data: &[u8], source: Option<&str>) -> Option<String> {
+            let summary = PacketSummary::from_link(datalink, data);
+            if let (Some(flows), Some(summary)) = (&mut self.flows, &summary) {
+                for flow in flows.push(summary, time) {
+                    self.on_flow_end(&flow);
+                }
+            }
+            // Building the packet table is the costly part; skip it for flow-only scripts
+            self.hook("on_packet")?;
+            let packet = match packet_table(&self.lua, time, datalink, data, source, summary.as_ref()) {
+                Ok(packet) => packet,
+                Err(e) => {
+                    self.hook_error("on_packet", e);
+                    return None;
+                }
+            };
+            match self.call("on_packet", packet)? {
+                Value::String(tag) => Some(tag.to_string_lossy().into_owned()),
+                _ => None,
+            }
+        }
+
+        pub fn on_alert(&self, alert: &str, source: Option<&str>) {
+            self.call("on_alert", (alert, source));
+        }
+
+        /// Reports the flows still open when the capture stops
+        pub fn finish(&mut self) {
+            let Some(flows) = &mut self.flows else { return };
+            for flow in flows.finish() {
+                self.on_flow_end(&flow);
+            }
+        }
+
+        fn on_flow_end(&self, flow: &FlowRecord) {
+            match flow_table(&self.lua, flow) {
+                Ok(table) => {
+                    self.call("on_flow_end", table);
+                }
+                Err(e) => self.hook_error("on_flow_end", e),
+            }
+        }
+    }
+
+    fn packet_table<'lua>(
+        lua: &'lua Lua,
+        time: f64,
+        datalink: i32,
+        data: &[u8],
+        source: Option<&str>,
+        summary: Option<&PacketSummary>,
+    ) -> mlua::Result<Table<'lua>> {
+        let packet = lua.create_table()?;
+        packet.set("time", time)?;
+        packet.set("length", data.len())?;
+        packet.set("datalink", datalink)?;
+        packet.set("source", source)?;
+        if let Some(s) = summary {
+            packet.set("src_mac", s.src_mac.to_string())?;
+            packet.set("dst_mac", s.dst_mac.to_string())?;
+            packet.set("ether_type", s.ether_type)?;
+            packet.set("vlan", s.vlan)?;
+            packet.set("src_ip", s.src_ip.map(|ip| ip.to_string()))?;
+            packet.set("dst_ip", s.dst_ip.map(|ip| ip.to_string()))?;
+            packet.set("protocol", s.protocol_name())?;
+            packet.set("ip_proto", s.ip_proto)?;
+            packet.set("ttl", s.ttl)?;
+            packet.set("src_port", s.src_port)?;
+            packet.set("dst_port", s.dst_port)?;
+            packet.set("tcp_flags", s.tcp_flags)?;
+            packet.set("payload", lua.create_string(s.payload(data))?)?;
+        }
+        let fields = lua.create_table()?;
+        for field in analyze_link_layer(datalink, data).map(|info| info.control_fields).unwrap_or_default() {
+            let entry = lua.create_table()?;
+            entry.set("name", field.name)?;
+            entry.set("value", field.value)?;
+            entry.set("description", field.description)?;
+            fields.push(entry)?;
+        }
+        packet.set("fields", fields)?;
+        Ok(packet)
+    }
+
+    fn flow_table<'lua>(lua: &'lua Lua, flow: &FlowRecord) -> mlua::Result<Table<'lua>> {
+        let table = lua.create_table()?;
+        table.set("key", flow.key.to_string())?;
+        table.set("protocol", flow.key.protocol_name())?;
+        table.set("a_ip", flow.key.a.0.to_string())?;
+        table.set("a_port", flow.key.a.1)?;
+        table.set("b_ip", flow.key.b.0.to_string())?;
+        table.set("b_port", flow.key.b.1)?;
+        table.set("packets", flow.packets)?;
+        table.set("bytes", flow.bytes)?;
+        table.set("first", flow.first)?;
+        table.set("last", flow.last)?;
+        table.set("reason", flow.end.name())?;
+        Ok(table)
+    }
+}
+
+/// Stand-in when the sniffer is built without the `lua` feature
+#[cfg(not(feature = "lua"))]
+pub struct ScriptHooks;
+
+#[cfg(not(feature = "lua"))]
+impl ScriptHooks {
+    pub fn new(_script: &Script) -> Result<Self, CaptureError> {
+        Err(CaptureError::InputError("Lua scripts need a build with `--features lua`".to_string()))
+    }
+
+    pub fn on_packet(&mut self, _time: f64, _datalink: i32, _data: &[u8], _source: Option<&str>) -> Option<String> {
+        None
+    }
+
+    pub fn on_alert(&self, _alert: &str, _source: Option<&str>) {}
+
+    pub fn finish(&mut self) {}
+}
+
+#[cfg(all(test, feature = "lua"))]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn runs_hooks_with_shared_state() {
+        let script = Script {
+            name: "count.lua".to_string(),
+            source: r#"
+                count, seen = 0, ""
+                function on_packet(p)
+                    count = count + 1
+                    if p.dst_port == 53 then return "dns " .. #p.payload end
+                    return count .. seen
+                end
+                function on_flow_end(f) seen = seen .. " " .. f.reason .. ":" .. f.packets end
+                function on_alert(message, source) seen = seen .. " " .. source .. ":" .. message end
+            "#
+            .to_string(),
+        };
+        let mut hooks = ScriptHooks::new(&script).unwrap();
+        let query = testutil::dns_query_frame(1, "example.com");
+        assert_eq!(hooks.on_packet(1.0, 1, &query, None).as_deref(), Some("dns 29"));
+        hooks.on_alert("ARP spoofing", Some("web"));
+        let syn = testutil::ipv4_tcp_frame(40000, 443, testutil::TCP_SYN, b"");
+        assert_eq!(hooks.on_packet(2.0, 1, &syn, None).as_deref(), Some("2 web:ARP spoofing"));
+        hooks.finish();
+        assert_eq!(hooks.on_packet(3.0, 1, &syn, None).as_deref(), Some("3 web:ARP spoofing end:1 end:1"));
+    }
+
+    #[test]
+    fn script_errors_do_not_stop_the_capture() {
+        let script = Script { name: "bad.lua".to_string(), source: "function on_packet(p) error('boom') end".to_string() };
+        let mut hooks = ScriptHooks::new(&script).unwrap();
+        assert_eq!(hooks.on_packet(0.0, 1, &testutil::dns_query_frame(1, "a.b"), None), None);
+        assert!(ScriptHooks::new(&Script { name: "syntax.lua".to_string(), source: "function (".to_string() }).is_err());
+    }
+}
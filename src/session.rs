@@ -0,0 +1,262 @@
+use crate::ai_analyzer::SecurityAnalysis;
+use crate::alert::Alert;
+use crate::cluster::Cluster;
+use crate::error::CaptureError;
+use crate::flow_review::ReviewedFlow;
+use crate::meta::{Direction, DirectionStats};
+use crate::protocols::summary::PacketSummary;
+use crate::timestamp::format_rfc3339;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Hosts listed under `top_talkers`
+const TOP_TALKERS: usize = 10;
+
+/// Alerts kept in the report; later ones are only counted
+const MAX_ALERTS: usize = 1000;
+
+/// How the capture was set up
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureParameters {
+    /// Interface, or the file read with `-r`
+    pub source: String,
+    /// `live`, `ai` or `file`
+    pub mode: &'static str,
+    pub bpf: Option<String>,
+    pub display_filter: Option<String>,
+    pub snaplen: i32,
+    pub promisc: bool,
+    pub monitor: bool,
+}
+
+/// libpcap's counters at the end of a live capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PcapStats {
+    pub received: u32,
+    pub dropped: u32,
+    pub if_dropped: u32,
+}
+
+/// Traffic sent and received by one address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Talker {
+    pub ip: IpAddr,
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+/// Everything the report file holds
+#[derive(Debug, Serialize)]
+pub struct SessionReport<'a> {
+    pub version: &'static str,
+    pub parameters: &'a CaptureParameters,
+    /// Wall-clock start and end of the session
+    pub started: String,
+    pub ended: String,
+    pub duration: f64,
+    /// Capture time of the first and last packet
+    pub first_packet: Option<String>,
+    pub last_packet: Option<String>,
+    pub packets: u64,
+    pub bytes: u64,
+    pub pcap_stats: Option<PcapStats>,
+    /// Packets and bytes inbound, outbound, local and in transit
+    pub directions: &'a DirectionStats,
+    pub protocols: &'a BTreeMap<&'static str, u64>,
+    pub top_talkers: Vec<Talker>,
+    pub alerts: &'a [Alert],
+    /// Alerts beyond the ones listed
+    pub alerts_omitted: u64,
+    pub ai_analyses: &'a [SecurityAnalysis],
+    /// Flows the AI reviewed, each with its verdict
+    pub ai_flows: &'a [ReviewedFlow],
+    /// Kinds of flows seen, with `--cluster-flows`
+    pub flow_clusters: &'a [Cluster],
+}
+
+/// Gathers a machine-readable summary of a capture session and writes it as
+/// JSON when the capture stops (`--report`), to archive next to the pcap
+pub struct SessionRecorder {
+    path: PathBuf,
+    /// Opened up front, so the report can still be written under `--sandbox`
+    file: File,
+    parameters: CaptureParameters,
+    started: f64,
+    first_packet: Option<f64>,
+    last_packet: Option<f64>,
+    packets: u64,
+    bytes: u64,
+    pcap_stats: Option<PcapStats>,
+    directions: DirectionStats,
+    protocols: BTreeMap<&'static str, u64>,
+    talkers: HashMap<IpAddr, (u64, u64)>,
+    alerts: Vec<Alert>,
+    alerts_omitted: u64,
+    analyses: Vec<SecurityAnalysis>,
+    reviewed_flows: Vec<ReviewedFlow>,
+    clusters: Vec<Cluster>,
+}
+
+impl SessionRecorder {
+    pub fn create(path: &Path, parameters: CaptureParameters) -> Result<Self, CaptureError> {
+        let file = File::create(path).map_err(|e| CaptureError::Other(format!("Cannot create {}: {}", path.display(), e)))?;
+        Ok(SessionRecorder {
+            path: path.to_path_buf(),
+            file,
+            parameters,
+            started: now(),
+            first_packet: None,
+            last_packet: None,
+            packets: 0,
+            bytes: 0,
+            pcap_stats: None,
+            directions: DirectionStats::default(),
+            protocols: BTreeMap::new(),
+            talkers: HashMap::new(),
+            alerts: Vec::new(),
+            alerts_omitted: 0,
+            analyses: Vec::new(),
+            reviewed_flows: Vec::new(),
+            clusters: Vec::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Counts a packet of `length` bytes on the wire; `summary` is `None` for
+    /// frames the parser doesn't understand
+    pub fn push(&mut self, summary: Option<&PacketSummary>, length: u64, time: f64, direction: Direction) {
+        self.packets += 1;
+        self.bytes += length;
+        self.directions.push(direction, length);
+        self.first_packet.get_or_insert(time);
+        self.last_packet = Some(time);
+        *self.protocols.entry(summary.map_or("Other", PacketSummary::protocol_name)).or_default() += 1;
+        for ip in summary.into_iter().flat_map(|s| [s.src_ip, s.dst_ip]).flatten() {
+            let talker = self.talkers.entry(ip).or_default();
+            talker.0 += 1;
+            talker.1 += length;
+        }
+    }
+
+    pub fn push_alert(&mut self, alert: &Alert) {
+        if self.alerts.len() < MAX_ALERTS {
+            self.alerts.push(alert.clone());
+        } else {
+            self.alerts_omitted += 1;
+        }
+    }
+
+    pub fn push_analysis(&mut self, analysis: SecurityAnalysis) {
+        self.analyses.push(analysis);
+    }
+
+    pub fn push_reviewed_flow(&mut self, flow: ReviewedFlow) {
+        self.reviewed_flows.push(flow);
+    }
+
+    pub fn set_flow_clusters(&mut self, clusters: Vec<Cluster>) {
+        self.clusters = clusters;
+    }
+
+    pub fn set_pcap_stats(&mut self, stats: PcapStats) {
+        self.pcap_stats = Some(stats);
+    }
+
+    /// The report as of now
+    pub fn report(&self) -> SessionReport<'_> {
+        let mut top_talkers: Vec<Talker> =
+            self.talkers.iter().map(|(&ip, &(packets, bytes))| Talker { ip, packets, bytes }).collect();
+        top_talkers.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.ip.cmp(&b.ip)));
+        top_talkers.truncate(TOP_TALKERS);
+        let ended = now();
+        SessionReport {
+            version: env!("CARGO_PKG_VERSION"),
+            parameters: &self.parameters,
+            started: format_rfc3339(self.started),
+            ended: format_rfc3339(ended),
+            duration: ended - self.started,
+            first_packet: self.first_packet.map(format_rfc3339),
+            last_packet: self.last_packet.map(format_rfc3339),
+            packets: self.packets,
+            bytes: self.bytes,
+            pcap_stats: self.pcap_stats,
+            directions: &self.directions,
+            protocols: &self.protocols,
+            top_talkers,
+            alerts: &self.alerts,
+            alerts_omitted: self.alerts_omitted,
+            ai_analyses: &self.analyses,
+            ai_flows: &self.reviewed_flows,
+            flow_clusters: &self.clusters,
+        }
+    }
+
+    /// Writes the report to the file given at creation
+    pub fn write(&mut self) -> Result<(), CaptureError> {
+        let text = serde_json::to_string_pretty(&self.report()).map_err(|e| CaptureError::Other(e.to_string()))?;
+        let failed = |e: std::io::Error| CaptureError::Other(format!("Cannot write {}: {}", self.path.display(), e));
+        self.file.write_all(text.as_bytes()).map_err(failed)?;
+        self.file.write_all(b"\n").map_err(failed)
+    }
+}
+
+fn now() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::Severity;
+    use crate::testutil;
+
+    #[test]
+    fn summarizes_a_session() {
+        let path = std::env::temp_dir().join(format!("session-{}.json", std::process::id()));
+        let parameters = CaptureParameters {
+            source: "eth0".to_string(),
+            mode: "live",
+            bpf: Some("tcp".to_string()),
+            display_filter: None,
+            snaplen: 65535,
+            promisc: true,
+            monitor: false,
+        };
+        let mut recorder = SessionRecorder::create(&path, parameters).unwrap();
+        for (time, frame) in [(1.0, testutil::ipv4_tcp_frame(40000, 443, testutil::TCP_SYN, b"")), (2.5, testutil::ipv4_udp_frame(53000, 53, b"x"))] {
+            let summary = PacketSummary::from_ethernet(&frame);
+            recorder.push(summary.as_ref(), frame.len() as u64, time, Direction::Outbound);
+        }
+        recorder.push(None, 60, 3.0, Direction::Unknown);
+        recorder.push_alert(&Alert {
+            time: 2.0,
+            severity: Severity::High,
+            kind: "rule",
+            message: "Rule 'dns' matched".to_string(),
+            source: None,
+            hosts: Vec::new(),
+            techniques: vec!["T1071.004".to_string()],
+        });
+        recorder.set_pcap_stats(PcapStats { received: 4, dropped: 1, if_dropped: 0 });
+        recorder.write().unwrap();
+
+        let report: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(report["parameters"]["bpf"], "tcp");
+        assert_eq!((report["packets"].as_u64(), report["bytes"].as_u64()), (Some(3), Some(54 + 43 + 60)));
+        assert_eq!(report["protocols"], serde_json::json!({ "Other": 1, "TCP": 1, "UDP": 1 }));
+        assert_eq!(report["top_talkers"][0], serde_json::json!({ "ip": "93.184.216.34", "packets": 2, "bytes": 97 }));
+        assert_eq!(report["last_packet"], "1970-01-01T00:00:03.000000Z");
+        assert_eq!(report["pcap_stats"]["dropped"], 1);
+        assert_eq!(report["directions"]["outbound"], serde_json::json!({ "packets": 2, "bytes": 97 }));
+        assert_eq!(report["alerts"][0]["techniques"][0], "T1071.004");
+    }
+}
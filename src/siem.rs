@@ -0,0 +1,230 @@
+use crate::alert::{Alert, Severity};
+use crate::flow::FlowRecord;
+use crate::timestamp::format_rfc3339;
+use serde::Deserialize;
+use std::net::IpAddr;
+
+const VENDOR: &str = "rust-sniffer";
+const PRODUCT: &str = "rust-sniffer";
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Java date format of `devTime` in LEEF events
+const LEEF_TIME_FORMAT: &str = "yyyy-MM-dd'T'HH:mm:ss.SSSX";
+
+/// Event string format for SIEM ingestion
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventFormat {
+    /// Plain RFC 5424 message with structured data
+    #[default]
+    Rfc5424,
+    /// ArcSight Common Event Format
+    Cef,
+    /// QRadar Log Event Extended Format
+    Leef,
+}
+
+/// CEF severity, 0-10
+fn cef_severity(severity: Severity) -> u8 {
+    match severity {
+        Severity::Low => 3,
+        Severity::Medium => 5,
+        Severity::High => 8,
+        Severity::Critical => 10,
+    }
+}
+
+/// Fields common to alerts and flows, in the order they are written
+struct Event<'a> {
+    id: &'a str,
+    name: String,
+    severity: u8,
+    fields: Vec<(&'a str, String)>,
+}
+
+impl Event<'_> {
+    fn alert(alert: &Alert) -> Event<'_> {
+        let mut fields = vec![("cat", alert.kind.to_string()), ("msg", alert.message.clone())];
+        if !alert.techniques.is_empty() {
+            fields.push(("techniques", alert.techniques.join(",")));
+        }
+        if let Some(source) = &alert.source {
+            fields.push(("namespace", source.clone()));
+        }
+        Event { id: alert.kind, name: alert.message.clone(), severity: cef_severity(alert.severity), fields }
+    }
+
+    fn flow(flow: &FlowRecord) -> Event<'static> {
+        let responder = if flow.initiator == flow.key.a { flow.key.b } else { flow.key.a };
+        let fields = vec![
+            ("proto", flow.key.protocol_name().to_uppercase()),
+            ("src", flow.initiator.0.to_string()),
+            ("spt", flow.initiator.1.to_string()),
+            ("dst", responder.0.to_string()),
+            ("dpt", responder.1.to_string()),
+            ("out", flow.bytes_out.to_string()),
+            ("in", (flow.bytes - flow.bytes_out).to_string()),
+            ("cnt", flow.packets.to_string()),
+            ("reason", flow.end.name().to_string()),
+        ];
+        Event { id: "flow", name: format!("Flow ended ({})", flow.end.name()), severity: 1, fields }
+    }
+}
+
+/// `CEF:0|vendor|product|version|id|name|severity|extension` for an alert
+pub fn cef_alert(alert: &Alert) -> String {
+    let mut event = Event::alert(alert);
+    // CEF has no keys for these; custom strings carry a label
+    event.fields = event
+        .fields
+        .into_iter()
+        .flat_map(|(key, value)| match key {
+            "techniques" => vec![("cs2", value), ("cs2Label", "ATT&CK Techniques".to_string())],
+            "namespace" => vec![("cs1", value), ("cs1Label", "Namespace".to_string())],
+            _ => vec![(key, value)],
+        })
+        .collect();
+    cef(&event, alert.time, None)
+}
+
+/// The CEF event for a finished flow, with `start` and `end` in milliseconds
+pub fn cef_flow(flow: &FlowRecord) -> String {
+    let mut event = Event::flow(flow);
+    // CEF keeps IPv6 addresses in their own keys
+    for (key, value) in &mut event.fields {
+        if value.parse::<IpAddr>().is_ok_and(|ip| ip.is_ipv6()) {
+            *key = if *key == "src" { "c6a2" } else { "c6a3" };
+        }
+    }
+    cef(&event, flow.last, Some(flow.first))
+}
+
+fn cef(event: &Event<'_>, time: f64, start: Option<f64>) -> String {
+    let millis = |secs: f64| ((secs * 1000.0).round() as i64).to_string();
+    let mut extension = vec![format!("rt={}", millis(time))];
+    if let Some(start) = start {
+        extension.push(format!("start={} end={}", millis(start), millis(time)));
+    }
+    extension.extend(event.fields.iter().map(|(key, value)| format!("{}={}", key, escape_cef_value(value))));
+    format!(
+        "CEF:0|{}|{}|{}|{}|{}|{}|{}",
+        VENDOR,
+        PRODUCT,
+        VERSION,
+        escape_cef_header(event.id),
+        escape_cef_header(&event.name),
+        event.severity,
+        extension.join(" ")
+    )
+}
+
+/// `LEEF:1.0|vendor|product|version|id|` followed by tab-separated attributes
+pub fn leef_alert(alert: &Alert) -> String {
+    let mut event = Event::alert(alert);
+    event.fields.insert(0, ("sev", cef_severity(alert.severity).to_string()));
+    leef(&event, alert.time)
+}
+
+pub fn leef_flow(flow: &FlowRecord) -> String {
+    let mut event = Event::flow(flow);
+    // LEEF's own names for the CEF keys
+    for (key, _) in &mut event.fields {
+        *key = match *key {
+            "spt" => "srcPort",
+            "dpt" => "dstPort",
+            "out" => "srcBytes",
+            "in" => "dstBytes",
+            "cnt" => "totalPackets",
+            other => other,
+        };
+    }
+    event.fields.insert(0, ("sev", event.severity.to_string()));
+    event.fields.push(("duration", format!("{:.3}", flow.last - flow.first)));
+    leef(&event, flow.last)
+}
+
+fn leef(event: &Event<'_>, time: f64) -> String {
+    // Millisecond precision for the Java date format
+    let rfc3339 = format_rfc3339(time);
+    let dev_time = format!("{}Z", &rfc3339[..rfc3339.len() - 4]);
+    let mut attributes = vec![format!("devTime={}", dev_time), format!("devTimeFormat={}", LEEF_TIME_FORMAT)];
+    attributes.extend(event.fields.iter().map(|(key, value)| format!("{}={}", key, escape_leef_value(value))));
+    format!("LEEF:1.0|{}|{}|{}|{}|{}", VENDOR, PRODUCT, VERSION, event.id.replace('|', "_"), attributes.join("\t"))
+}
+
+fn escape_cef_header(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+fn escape_cef_value(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('=', "\\=").replace("\r\n", "\\n").replace(['\r', '\n'], "\\n")
+}
+
+/// LEEF has no escaping; tabs and line breaks would end the attribute
+fn escape_leef_value(text: &str) -> String {
+    text.replace(['\t', '\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::{FlowEnd, FlowKey};
+    use std::net::Ipv6Addr;
+
+    fn alert() -> Alert {
+        Alert {
+            time: 1_700_000_000.25,
+            severity: Severity::High,
+            kind: "rule",
+            message: "[HIGH] Rule 'a|b' matched x=1\nnext".to_string(),
+            source: Some("web".to_string()),
+            hosts: Vec::new(),
+            techniques: vec!["T1071.001".to_string(), "T1573".to_string()],
+        }
+    }
+
+    #[test]
+    fn formats_alerts() {
+        assert_eq!(
+            cef_alert(&alert()),
+            format!(
+                "CEF:0|rust-sniffer|rust-sniffer|{}|rule|[HIGH] Rule 'a\\|b' matched x=1 next|8|rt=1700000000250 cat=rule \
+                 msg=[HIGH] Rule 'a|b' matched x\\=1\\nnext cs2=T1071.001,T1573 cs2Label=ATT&CK Techniques cs1=web cs1Label=Namespace",
+                VERSION
+            )
+        );
+        assert_eq!(
+            leef_alert(&alert()),
+            format!(
+                "LEEF:1.0|rust-sniffer|rust-sniffer|{}|rule|devTime=2023-11-14T22:13:20.250Z\tdevTimeFormat={}\tsev=8\tcat=rule\t\
+                 msg=[HIGH] Rule 'a|b' matched x=1 next\ttechniques=T1071.001,T1573\tnamespace=web",
+                VERSION, LEEF_TIME_FORMAT
+            )
+        );
+    }
+
+    #[test]
+    fn formats_flows() {
+        let client = (IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)), 50000);
+        let server = (IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2)), 53);
+        let flow = FlowRecord {
+            key: FlowKey::new(17, client, server),
+            initiator: client,
+            packets: 2,
+            bytes: 180,
+            packets_out: 1,
+            bytes_out: 70,
+            first: 10.0,
+            last: 10.5,
+            end: FlowEnd::Idle,
+        };
+        assert!(cef_flow(&flow).ends_with(
+            "|flow|Flow ended (idle)|1|rt=10500 start=10000 end=10500 proto=UDP c6a2=2001:db8::1 spt=50000 c6a3=2001:db8::2 dpt=53 \
+             out=70 in=110 cnt=2 reason=idle"
+        ));
+        assert!(leef_flow(&flow).ends_with(
+            "|flow|devTime=1970-01-01T00:00:10.500Z\tdevTimeFormat=yyyy-MM-dd'T'HH:mm:ss.SSSX\tsev=1\tproto=UDP\tsrc=2001:db8::1\t\
+             srcPort=50000\tdst=2001:db8::2\tdstPort=53\tsrcBytes=70\tdstBytes=110\ttotalPackets=2\treason=idle\tduration=0.500"
+        ));
+    }
+}
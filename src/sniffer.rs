@@ -0,0 +1,356 @@
+use crate::capfile::StoredPacket;
+use crate::capture::CaptureConfig;
+use crate::error::CaptureError;
+use crate::filter::CaptureFilter;
+use crate::flow::{FlowKey, FlowRecord, FlowTracker};
+use crate::protocols::summary::PacketSummary;
+use crate::stream::PacketStream;
+use futures_core::Stream;
+use log::warn;
+use pcap::{Active, Capture, Offline};
+use std::collections::VecDeque;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Longest wait for a packet before [`Sniffer::run`] checks its stop token again
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A packet with the header fields the parser understood
+#[derive(Debug, Clone)]
+pub struct ParsedPacket {
+    pub datalink: i32,
+    pub packet: StoredPacket,
+    /// Addresses, ports and protocol; `None` for frames the parser doesn't understand
+    pub summary: Option<PacketSummary>,
+}
+
+impl ParsedPacket {
+    pub fn new(datalink: i32, packet: StoredPacket) -> Self {
+        let summary = PacketSummary::from_link(datalink, &packet.data);
+        ParsedPacket { datalink, packet, summary }
+    }
+
+    /// Capture time in seconds since the epoch
+    pub fn time(&self) -> f64 {
+        self.packet.timestamp().as_secs_f64()
+    }
+
+    /// The TCP/UDP conversation the packet belongs to
+    pub fn flow(&self) -> Option<FlowKey> {
+        self.summary.as_ref().and_then(FlowKey::from_summary)
+    }
+}
+
+/// A conversation starting or ending
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlowEvent {
+    Started { key: FlowKey, time: f64 },
+    /// Closed, idle past the timeout, or still open when the capture stopped
+    Ended(FlowRecord),
+}
+
+/// Turns parsed packets into flow events
+pub struct FlowEvents {
+    tracker: FlowTracker,
+}
+
+impl FlowEvents {
+    pub fn new(idle_timeout: Duration) -> Self {
+        FlowEvents { tracker: FlowTracker::new(idle_timeout) }
+    }
+
+    /// The events a packet causes: flows that timed out or closed, then the packet's own flow if it is new
+    pub fn push(&mut self, packet: &ParsedPacket) -> Vec<FlowEvent> {
+        let Some(summary) = &packet.summary else { return Vec::new() };
+        let time = packet.time();
+        let mut events: Vec<FlowEvent> = self.tracker.push(summary, time).into_iter().map(FlowEvent::Ended).collect();
+        if let Some(key) = FlowKey::from_summary(summary)
+            && self.tracker.flow(&key).is_some_and(|flow| flow.packets == 1)
+        {
+            events.push(FlowEvent::Started { key, time });
+        }
+        events
+    }
+
+    /// Ends the flows still open
+    pub fn finish(&mut self) -> Vec<FlowEvent> {
+        self.tracker.finish().into_iter().map(FlowEvent::Ended).collect()
+    }
+}
+
+/// Stops a running [`Sniffer::run`] from another thread or a signal handler
+#[derive(Debug, Clone, Default)]
+pub struct StopToken(Arc<AtomicBool>);
+
+impl StopToken {
+    pub fn new() -> Self {
+        StopToken::default()
+    }
+
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+enum Source {
+    Live(Capture<Active>),
+    File(Capture<Offline>),
+}
+
+/// What one read from the handle produced
+enum Read {
+    Packet(ParsedPacket),
+    /// Nothing yet, or a packet the display filter rejected
+    Idle,
+    /// The end of a capture file
+    End,
+    Failed(CaptureError),
+}
+
+/// A capture for programs using rust-sniffer as a library: packets come out
+/// parsed, as an async stream of packets or of flow events, through a
+/// callback, or from a plain iterator
+pub struct Sniffer {
+    source: Source,
+    datalink: i32,
+    filter: Option<CaptureFilter>,
+}
+
+impl Sniffer {
+    /// Opens `interface` with the defaults of [`CaptureConfig::new`]
+    pub fn open(interface: &str) -> Result<Self, CaptureError> {
+        Sniffer::with_config(&CaptureConfig::new(interface))
+    }
+
+    /// Opens a live capture with other settings, such as a snaplen or a BPF
+    /// filter installed before the first packet
+    pub fn with_config(config: &CaptureConfig) -> Result<Self, CaptureError> {
+        config.open().map(Sniffer::from_capture)
+    }
+
+    /// Wraps a handle opened elsewhere; the async API needs it non-blocking (`setnonblock()`)
+    pub fn from_capture(cap: Capture<Active>) -> Self {
+        let datalink = cap.get_datalink().0;
+        Sniffer { source: Source::Live(cap), datalink, filter: None }
+    }
+
+    /// Reads a pcap or pcapng file instead of an interface, for [`Sniffer::iter`] and [`Sniffer::run`]
+    pub fn read_file(path: &Path) -> Result<Self, CaptureError> {
+        let cap = Capture::from_file(path).map_err(|e| CaptureError::PcapError(format!("Cannot read {}: {}", path.display(), e)))?;
+        let datalink = cap.get_datalink().0;
+        Ok(Sniffer { source: Source::File(cap), datalink, filter: None })
+    }
+
+    pub fn datalink(&self) -> i32 {
+        self.datalink
+    }
+
+    /// Installs the BPF part of `filter` on the handle and checks the display part on every packet
+    pub fn set_filter(&mut self, filter: CaptureFilter) -> Result<(), CaptureError> {
+        let bpf = filter.bpf.as_deref().unwrap_or("");
+        match &mut self.source {
+            Source::Live(cap) => cap.filter(bpf, true),
+            Source::File(cap) => cap.filter(bpf, true),
+        }
+        .map_err(|e| CaptureError::PcapError(format!("Invalid BPF: {}", e)))?;
+        self.filter = Some(filter);
+        Ok(())
+    }
+
+    /// Hands every packet that passes the filter to `handler` until `token`
+    /// is stopped or a capture file ends, then returns how many it handled.
+    /// The token is checked at least every 100 ms, so stopping takes effect
+    /// even on a quiet interface.
+    pub fn run(&mut self, mut handler: impl FnMut(ParsedPacket), token: &StopToken) -> Result<u64, CaptureError> {
+        let mut handled = 0;
+        while !token.is_stopped() {
+            match self.read(STOP_CHECK_INTERVAL) {
+                Read::Packet(packet) => {
+                    handler(packet);
+                    handled += 1;
+                }
+                Read::Idle => {}
+                Read::End => break,
+                Read::Failed(e) => return Err(e),
+            }
+        }
+        Ok(handled)
+    }
+
+    /// The packets that pass the filter, read on the calling thread. A
+    /// capture file ends the iteration at its last packet, a live capture
+    /// only after an error, which is yielded last.
+    pub fn iter(&mut self) -> impl Iterator<Item = Result<ParsedPacket, CaptureError>> + '_ {
+        let mut failed = false;
+        std::iter::from_fn(move || {
+            while !failed {
+                match self.read(STOP_CHECK_INTERVAL) {
+                    Read::Packet(packet) => return Some(Ok(packet)),
+                    Read::Idle => {}
+                    Read::End => return None,
+                    Read::Failed(e) => {
+                        failed = true;
+                        return Some(Err(e));
+                    }
+                }
+            }
+            None
+        })
+    }
+
+    /// Reads a packet, waiting up to `wait` for one on a quiet live capture
+    fn read(&mut self, wait: Duration) -> Read {
+        let fd = match &self.source {
+            Source::Live(cap) => Some(cap.as_raw_fd()),
+            Source::File(_) => None,
+        };
+        let next = match &mut self.source {
+            Source::Live(cap) => cap.next_packet(),
+            Source::File(cap) => cap.next_packet(),
+        };
+        match next {
+            Ok(packet) if self.filter.as_ref().is_some_and(|f| !f.matches(self.datalink, packet.data)) => Read::Idle,
+            Ok(packet) => Read::Packet(ParsedPacket::new(self.datalink, packet.into())),
+            Err(pcap::Error::NoMorePackets) => Read::End,
+            Err(pcap::Error::TimeoutExpired) => {
+                fd.into_iter().for_each(|fd| wait_readable(fd, wait));
+                Read::Idle
+            }
+            Err(pcap::Error::PcapError(e)) if e.contains("Packets are not available") => {
+                fd.into_iter().for_each(|fd| wait_readable(fd, wait));
+                Read::Idle
+            }
+            Err(e) => Read::Failed(CaptureError::PcapError(e.to_string())),
+        }
+    }
+
+    /// Parsed packets as they arrive. The stream ends at the first capture
+    /// error, which is logged. Must be called from within a tokio runtime.
+    pub fn stream(self) -> Result<impl Stream<Item = ParsedPacket>, CaptureError> {
+        let Source::Live(cap) = self.source else {
+            return Err(CaptureError::InputError("Streams need a live capture; read files with iter()".to_string()));
+        };
+        Ok(ParsedPackets { packets: Some(PacketStream::new(cap)?), datalink: self.datalink, filter: self.filter })
+    }
+
+    /// Flows starting and ending, ended after `idle_timeout` without traffic.
+    /// Flows still open when the capture fails are reported as ended.
+    pub fn flow_stream(self, idle_timeout: Duration) -> Result<impl Stream<Item = FlowEvent>, CaptureError> {
+        Ok(FlowStream { packets: self.stream()?, flows: FlowEvents::new(idle_timeout), pending: VecDeque::new(), finished: false })
+    }
+}
+
+/// Sleeps until a non-blocking handle has packets to read, or `timeout` passes
+fn wait_readable(fd: RawFd, timeout: Duration) {
+    let mut poll = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+    // An error or signal only cuts the wait short
+    unsafe { libc::poll(&mut poll, 1, timeout.as_millis() as libc::c_int) };
+}
+
+struct ParsedPackets {
+    /// `None` once the capture failed
+    packets: Option<PacketStream>,
+    datalink: i32,
+    filter: Option<CaptureFilter>,
+}
+
+impl Stream for ParsedPackets {
+    type Item = ParsedPacket;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ParsedPacket>> {
+        let this = self.get_mut();
+        loop {
+            let Some(packets) = &mut this.packets else { return Poll::Ready(None) };
+            match packets.poll_packet(cx) {
+                Poll::Ready(Ok(packet)) if this.filter.as_ref().is_some_and(|f| !f.matches(this.datalink, &packet.data)) => {}
+                Poll::Ready(Ok(packet)) => return Poll::Ready(Some(ParsedPacket::new(this.datalink, packet))),
+                Poll::Ready(Err(e)) => {
+                    warn!("Capture stopped: {}", e);
+                    this.packets = None;
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+struct FlowStream<S> {
+    packets: S,
+    flows: FlowEvents,
+    pending: VecDeque<FlowEvent>,
+    finished: bool,
+}
+
+impl<S: Stream<Item = ParsedPacket> + Unpin> Stream for FlowStream<S> {
+    type Item = FlowEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<FlowEvent>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.pending.pop_front() {
+                return Poll::Ready(Some(event));
+            }
+            if this.finished {
+                return Poll::Ready(None);
+            }
+            match Pin::new(&mut this.packets).poll_next(cx) {
+                Poll::Ready(Some(packet)) => this.pending.extend(this.flows.push(&packet)),
+                Poll::Ready(None) => {
+                    this.finished = true;
+                    this.pending.extend(this.flows.finish());
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    fn parsed(frame: Vec<u8>, secs: i64) -> ParsedPacket {
+        let header = pcap::PacketHeader { ts: libc::timeval { tv_sec: secs, tv_usec: 0 }, caplen: frame.len() as u32, len: frame.len() as u32 };
+        ParsedPacket::new(1, StoredPacket::new(header, frame))
+    }
+
+    #[test]
+    fn reports_flows_starting_and_ending() {
+        let mut flows = FlowEvents::new(Duration::from_secs(30));
+        let syn = parsed(testutil::ipv4_tcp_frame(40000, 443, testutil::TCP_SYN, b""), 10);
+        assert_eq!(syn.flow().map(|key| key.to_string()).as_deref(), Some("TCP 93.184.216.34:443 <-> 192.168.1.10:40000"));
+        let events = flows.push(&syn);
+        assert!(matches!(events[..], [FlowEvent::Started { time: 10.0, .. }]));
+        assert!(flows.push(&parsed(testutil::ipv4_tcp_frame(40000, 443, testutil::TCP_ACK, b"x"), 11)).is_empty());
+
+        // A packet after the idle timeout ends the first flow and starts another
+        let events = flows.push(&parsed(testutil::ipv4_udp_frame(53000, 53, b"q"), 100));
+        match &events[..] {
+            [FlowEvent::Ended(record), FlowEvent::Started { key, .. }] => {
+                assert_eq!((record.packets, record.key.protocol_name()), (2, "tcp".to_string()));
+                assert_eq!(key.protocol_name(), "udp");
+            }
+            other => panic!("unexpected events {:?}", other),
+        }
+        assert_eq!(flows.finish().len(), 1);
+    }
+
+    #[test]
+    fn stop_token_is_shared_by_clones() {
+        let token = StopToken::new();
+        let remote = token.clone();
+        assert!(!token.is_stopped());
+        std::thread::spawn(move || remote.stop()).join().unwrap();
+        assert!(token.is_stopped());
+    }
+}
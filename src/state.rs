@@ -0,0 +1,143 @@
+use crate::cluster::FlowClusters;
+use crate::error::CaptureError;
+use crate::exfil::UploadVolumes;
+use crate::flow::FlowTracker;
+use crate::mtu::FrameSizes;
+use crate::poisoning::PoisoningDetector;
+use crate::routing::{GatewayMonitor, OspfMonitor};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Start of every state file, followed by the format version
+const MAGIC: &[u8; 4] = b"RSST";
+
+/// Bumped whenever a saved structure changes; files of another version are refused
+const FORMAT_VERSION: u8 = 3;
+
+/// What a capture has learned so far, borrowed from the running monitors for
+/// a checkpoint. The fields must stay in the order of [`SavedState`]: bincode
+/// stores no field names.
+#[derive(Serialize)]
+pub struct Snapshot<'a> {
+    /// Conversations still open, for sinks that take flow summaries
+    pub flows: Option<&'a FlowTracker>,
+    /// OSPF routers heard so far
+    pub ospf: &'a OspfMonitor,
+    /// Current VRRP/HSRP masters
+    pub gateways: &'a GatewayMonitor,
+    /// Which hosts answer for which local names
+    pub names: &'a PoisoningDetector,
+    /// Frame size baseline per interface
+    pub frame_sizes: &'a FrameSizes,
+    /// Kinds of flows seen so far (`--cluster-flows`)
+    pub clusters: Option<&'a FlowClusters>,
+    /// Daily upload volumes per host (`[alerts.exfil]`)
+    pub uploads: Option<&'a UploadVolumes>,
+}
+
+/// A checkpoint read back at startup
+#[derive(Deserialize)]
+pub struct SavedState {
+    pub flows: Option<FlowTracker>,
+    pub ospf: OspfMonitor,
+    pub gateways: GatewayMonitor,
+    pub names: PoisoningDetector,
+    pub frame_sizes: FrameSizes,
+    pub clusters: Option<FlowClusters>,
+    pub uploads: Option<UploadVolumes>,
+}
+
+/// Checkpoints monitor state to a file (`--state`) so a restarted capture
+/// picks up where the last one stopped
+pub struct StateFile {
+    path: PathBuf,
+    interval: Duration,
+    last_saved: Instant,
+}
+
+impl StateFile {
+    pub fn new(path: &Path, interval: Duration) -> Self {
+        StateFile { path: path.to_path_buf(), interval, last_saved: Instant::now() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The last checkpoint, or `None` when there is none yet. A file from
+    /// another version of the format is an error, so it isn't silently overwritten.
+    pub fn load(&self) -> Result<Option<SavedState>, CaptureError> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(CaptureError::Other(format!("Cannot read {}: {}", self.path.display(), e))),
+        };
+        let invalid = |why: String| CaptureError::InputError(format!("{} is not a usable state file: {}", self.path.display(), why));
+        match data.get(..5) {
+            Some([m0, m1, m2, m3, version]) if [*m0, *m1, *m2, *m3] == *MAGIC => {
+                if *version != FORMAT_VERSION {
+                    return Err(invalid(format!("format version {}, expected {}", version, FORMAT_VERSION)));
+                }
+            }
+            _ => return Err(invalid("unknown format".to_string())),
+        }
+        let state: SavedState = bincode::deserialize(&data[5..]).map_err(|e| invalid(e.to_string()))?;
+        info!("Restored state from {}", self.path.display());
+        Ok(Some(state))
+    }
+
+    /// Whether the checkpoint interval has passed since the last save
+    pub fn due(&self) -> bool {
+        self.last_saved.elapsed() >= self.interval
+    }
+
+    /// Writes a checkpoint next to the file and renames it into place, so a
+    /// crash mid-write leaves the previous one intact
+    pub fn save(&mut self, snapshot: &Snapshot<'_>) -> Result<(), CaptureError> {
+        self.last_saved = Instant::now();
+        let mut data = MAGIC.to_vec();
+        data.push(FORMAT_VERSION);
+        bincode::serialize_into(&mut data, snapshot).map_err(|e| CaptureError::Other(format!("Cannot encode state: {}", e)))?;
+        let temp = self.path.with_extension("tmp");
+        let failed = |e: std::io::Error| CaptureError::Other(format!("Cannot write {}: {}", self.path.display(), e));
+        fs::write(&temp, &data).map_err(failed)?;
+        fs::rename(&temp, &self.path).map_err(failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::summary::PacketSummary;
+    use crate::testutil;
+
+    #[test]
+    fn restores_a_checkpoint() {
+        let path = std::env::temp_dir().join(format!("state-{}.bin", std::process::id()));
+        let mut file = StateFile::new(&path, Duration::from_secs(60));
+        assert!(file.load().unwrap().is_none());
+
+        let mut flows = FlowTracker::new(Duration::from_secs(120));
+        let frame = testutil::ipv4_tcp_frame(40000, 443, testutil::TCP_SYN, b"");
+        flows.push(&PacketSummary::from_ethernet(&frame).unwrap(), 10.0);
+        let mut frame_sizes = FrameSizes::default();
+        frame_sizes.push("eth0", 1514);
+        let (ospf, gateways, names) = (OspfMonitor::default(), GatewayMonitor::default(), PoisoningDetector::default());
+        let snapshot = Snapshot { flows: Some(&flows), ospf: &ospf, gateways: &gateways, names: &names, frame_sizes: &frame_sizes, clusters: None, uploads: None };
+        file.save(&snapshot).unwrap();
+
+        let mut restored = file.load().unwrap().unwrap();
+        let sizes: Vec<_> = restored.frame_sizes.interfaces().iter().map(|(name, sizes)| (name.to_string(), sizes.total())).collect();
+        assert_eq!(sizes, [("eth0".to_string(), 1)]);
+        let open = restored.flows.as_mut().unwrap().finish();
+        assert_eq!((open.len(), open[0].first, open[0].initiator.1), (1, 10.0, 40000));
+
+        fs::write(&path, b"RSST\x00").unwrap();
+        assert!(file.load().is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+use crate::session::PcapStats;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Time between samples when none is configured; load shedding reacts to
+/// new drops at this pace too
+pub const DEFAULT_STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Samples libpcap's counters on a timer, away from the per-packet path, and
+/// turns the change since the last sample into rates
+pub struct StatsReporter {
+    interval: Duration,
+    next: Instant,
+    last: Option<Sample>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: Instant,
+    stats: PcapStats,
+    processed: u64,
+}
+
+/// Counters at one sample, with their rates over the interval before it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatsReport {
+    pub stats: PcapStats,
+    /// Packets that passed the filters and were handled
+    pub processed: u64,
+    pub received_per_sec: f64,
+    pub processed_per_sec: f64,
+    pub dropped_per_sec: f64,
+    /// Share of the packets that reached libpcap in the interval that it dropped
+    pub drop_ratio: f64,
+}
+
+impl StatsReporter {
+    /// The first sample is due right away, as a baseline for the rates
+    pub fn new(interval: Duration, now: Instant) -> Self {
+        StatsReporter { interval, next: now, last: None }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn due(&self, now: Instant) -> bool {
+        now >= self.next
+    }
+
+    /// Records a sample; `None` for the first, which only sets the baseline.
+    /// libpcap's counters are 32 bits and wrap on long captures.
+    pub fn sample(&mut self, now: Instant, stats: PcapStats, processed: u64) -> Option<StatsReport> {
+        self.next = now + self.interval;
+        let last = self.last.replace(Sample { at: now, stats, processed })?;
+        let elapsed = now.duration_since(last.at).as_secs_f64().max(f64::EPSILON);
+        let received = stats.received.wrapping_sub(last.stats.received) as f64;
+        let dropped = stats.dropped.wrapping_sub(last.stats.dropped) as f64;
+        let offered = received + dropped;
+        Some(StatsReport {
+            stats,
+            processed,
+            received_per_sec: received / elapsed,
+            processed_per_sec: processed.saturating_sub(last.processed) as f64 / elapsed,
+            dropped_per_sec: dropped / elapsed,
+            drop_ratio: if offered > 0.0 { dropped / offered } else { 0.0 },
+        })
+    }
+}
+
+impl StatsReport {
+    /// Nothing received or dropped since the sample before
+    pub fn is_idle(&self) -> bool {
+        self.received_per_sec == 0.0 && self.dropped_per_sec == 0.0
+    }
+}
+
+impl fmt::Display for StatsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "received {} ({:.1}/s), processed {} ({:.1}/s), dropped {} ({:.1}/s, {:.2}%), interface drops {}",
+            self.stats.received,
+            self.received_per_sec,
+            self.processed,
+            self.processed_per_sec,
+            self.stats.dropped,
+            self.dropped_per_sec,
+            self.drop_ratio * 100.0,
+            self.stats.if_dropped
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(received: u32, dropped: u32) -> PcapStats {
+        PcapStats { received, dropped, if_dropped: 0 }
+    }
+
+    #[test]
+    fn reports_rates_between_samples() {
+        let start = Instant::now();
+        let mut reporter = StatsReporter::new(Duration::from_secs(5), start);
+        assert!(reporter.due(start));
+        assert_eq!(reporter.sample(start, stats(100, 0), 100), None);
+        assert!(!reporter.due(start + Duration::from_secs(4)));
+
+        let later = start + Duration::from_secs(5);
+        let report = reporter.sample(later, stats(1100, 100), 1000).unwrap();
+        assert_eq!((report.received_per_sec, report.processed_per_sec, report.dropped_per_sec), (200.0, 180.0, 20.0));
+        assert!((report.drop_ratio - 100.0 / 1100.0).abs() < 1e-9);
+        assert_eq!(report.to_string(), "received 1100 (200.0/s), processed 1000 (180.0/s), dropped 100 (20.0/s, 9.09%), interface drops 0");
+    }
+
+    #[test]
+    fn survives_counter_wraparound() {
+        let start = Instant::now();
+        let mut reporter = StatsReporter::new(Duration::from_secs(1), start);
+        reporter.sample(start, stats(u32::MAX - 9, 0), 0);
+        let report = reporter.sample(start + Duration::from_secs(1), stats(10, 0), 20).unwrap();
+        assert_eq!(report.received_per_sec, 20.0);
+        assert!(!report.is_idle());
+        assert!(reporter.sample(start + Duration::from_secs(2), stats(10, 0), 20).unwrap().is_idle());
+    }
+}
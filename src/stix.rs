@@ -0,0 +1,310 @@
+use crate::alert::{Alert, AlertSink, Severity};
+use crate::anonymize::random_key;
+use crate::attack::technique_url;
+use crate::error::CaptureError;
+use crate::timestamp::format_rfc3339;
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const TAXII_MEDIA_TYPE: &str = "application/taxii+json;version=2.1";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Alert messages kept in an indicator's description
+const MAX_DESCRIBED: usize = 10;
+
+/// STIX export from the configuration file (`[alerts.stix]`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StixConfig {
+    /// File the bundle is written to when the capture stops
+    pub path: Option<PathBuf>,
+    /// Alerts below this severity don't produce indicators
+    #[serde(default = "default_min_severity")]
+    pub min_severity: Severity,
+    pub taxii: Option<TaxiiConfig>,
+}
+
+/// A TAXII 2.1 collection the indicators are added to
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaxiiConfig {
+    /// Collection URL, such as `https://taxii.example.com/api1/collections/<id>/`
+    pub collection: String,
+    pub username: Option<String>,
+    /// Environment variable holding the password
+    pub password_env: Option<String>,
+}
+
+fn default_min_severity() -> Severity {
+    Severity::Medium
+}
+
+/// What the alerts said about one host
+#[derive(Debug)]
+struct Sightings {
+    id: String,
+    sighting_id: String,
+    first: f64,
+    last: f64,
+    count: u64,
+    severity: Severity,
+    kinds: BTreeSet<&'static str>,
+    techniques: BTreeSet<String>,
+    messages: Vec<String>,
+}
+
+/// Turns the hosts alerts point at into STIX 2.1 indicators with sightings,
+/// written as a bundle and optionally added to a TAXII collection when the
+/// capture stops
+pub struct StixSink {
+    path: Option<PathBuf>,
+    taxii: Option<(String, Option<(String, String)>)>,
+    min_severity: Severity,
+    identity: String,
+    /// Creation time of the objects, fixed so re-exports replace them instead of adding versions
+    created: String,
+    hosts: BTreeMap<IpAddr, Sightings>,
+}
+
+impl StixSink {
+    pub fn new(config: &StixConfig) -> Result<Self, CaptureError> {
+        if config.path.is_none() && config.taxii.is_none() {
+            return Err(CaptureError::InputError("STIX export needs a path, a TAXII collection or both".to_string()));
+        }
+        let taxii = match &config.taxii {
+            None => None,
+            Some(taxii) => {
+                if !taxii.collection.starts_with("https://") && !taxii.collection.starts_with("http://") {
+                    return Err(CaptureError::InputError(format!("Invalid TAXII collection URL '{}'", taxii.collection)));
+                }
+                let password = match &taxii.password_env {
+                    Some(var) => std::env::var(var)
+                        .map_err(|_| CaptureError::InputError(format!("Environment variable {} with the TAXII password is not set", var)))?,
+                    None => String::new(),
+                };
+                let url = format!("{}/objects/", taxii.collection.trim_end_matches('/'));
+                Some((url, taxii.username.clone().map(|username| (username, password))))
+            }
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64();
+        Ok(StixSink {
+            path: config.path.clone(),
+            taxii,
+            min_severity: config.min_severity,
+            identity: stix_id("identity")?,
+            created: format_rfc3339(now),
+            hosts: BTreeMap::new(),
+        })
+    }
+
+    /// The bundle of everything seen so far: the sniffer's identity, then an
+    /// indicator and a sighting per host
+    pub fn bundle(&self) -> Result<Value, CaptureError> {
+        let mut objects = vec![json!({
+            "type": "identity",
+            "spec_version": "2.1",
+            "id": self.identity,
+            "created": self.created,
+            "modified": self.created,
+            "name": "rust-sniffer",
+            "identity_class": "system",
+        })];
+        for (ip, host) in &self.hosts {
+            let address_type = if ip.is_ipv4() { "ipv4-addr" } else { "ipv6-addr" };
+            let indicator_type = if host.severity >= Severity::High { "malicious-activity" } else { "anomalous-activity" };
+            let mut description = host.messages.join("\n");
+            if host.count as usize > host.messages.len() {
+                description.push_str(&format!("\n... {} alerts in total", host.count));
+            }
+            let references: Vec<Value> = host
+                .techniques
+                .iter()
+                .map(|id| json!({ "source_name": "mitre-attack", "external_id": id, "url": technique_url(id) }))
+                .collect();
+            let mut indicator = json!({
+                "type": "indicator",
+                "spec_version": "2.1",
+                "id": host.id,
+                "created_by_ref": self.identity,
+                "created": self.created,
+                "modified": self.created,
+                "name": format!("{} host {}", if host.severity >= Severity::High { "Malicious" } else { "Suspicious" }, ip),
+                "description": description,
+                "indicator_types": [indicator_type],
+                "pattern": format!("[{}:value = '{}']", address_type, ip),
+                "pattern_type": "stix",
+                "valid_from": format_rfc3339(host.first),
+                "confidence": confidence(host.severity),
+                "labels": host.kinds,
+            });
+            // STIX lists may not be empty
+            if !references.is_empty() {
+                indicator["external_references"] = references.into();
+            }
+            objects.push(indicator);
+            objects.push(json!({
+                "type": "sighting",
+                "spec_version": "2.1",
+                "id": host.sighting_id,
+                "created_by_ref": self.identity,
+                "created": self.created,
+                "modified": self.created,
+                "sighting_of_ref": host.id,
+                "first_seen": format_rfc3339(host.first),
+                "last_seen": format_rfc3339(host.last),
+                "count": host.count,
+                "where_sighted_refs": [self.identity],
+            }));
+        }
+        Ok(json!({ "type": "bundle", "id": stix_id("bundle")?, "objects": objects }))
+    }
+
+    fn export(&self) -> Result<(), String> {
+        let bundle = self.bundle().map_err(|e| e.to_string())?;
+        if let Some(path) = &self.path {
+            let text = serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string())?;
+            std::fs::write(path, text).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+            info!("Wrote {} STIX indicator(s) to {}", self.hosts.len(), path.display());
+        }
+        if let Some((url, credentials)) = self.taxii.clone() {
+            let envelope = json!({ "objects": bundle["objects"] });
+            // The blocking client gets a thread of its own, away from the async runtime
+            thread::spawn(move || publish(&url, credentials, &envelope)).join().map_err(|_| "TAXII upload panicked".to_string())??;
+            info!("Added {} STIX indicator(s) to the TAXII collection", self.hosts.len());
+        }
+        Ok(())
+    }
+}
+
+impl AlertSink for StixSink {
+    fn name(&self) -> &str {
+        "stix"
+    }
+
+    fn send(&mut self, alert: &Alert) {
+        if alert.severity < self.min_severity {
+            return;
+        }
+        for &ip in &alert.hosts {
+            let host = match self.hosts.get_mut(&ip) {
+                Some(host) => host,
+                None => {
+                    let (Ok(id), Ok(sighting_id)) = (stix_id("indicator"), stix_id("sighting")) else { return };
+                    self.hosts.entry(ip).or_insert(Sightings {
+                        id,
+                        sighting_id,
+                        first: alert.time,
+                        last: alert.time,
+                        count: 0,
+                        severity: alert.severity,
+                        kinds: BTreeSet::new(),
+                        techniques: BTreeSet::new(),
+                        messages: Vec::new(),
+                    })
+                }
+            };
+            host.first = host.first.min(alert.time);
+            host.last = host.last.max(alert.time);
+            host.count += 1;
+            host.severity = host.severity.max(alert.severity);
+            host.kinds.insert(alert.kind);
+            host.techniques.extend(alert.techniques.iter().cloned());
+            if host.messages.len() < MAX_DESCRIBED && !host.messages.contains(&alert.message) {
+                host.messages.push(alert.message.clone());
+            }
+        }
+    }
+
+    /// Writes and uploads the indicators gathered so far
+    fn flush(&mut self) {
+        if self.hosts.is_empty() {
+            return;
+        }
+        if let Err(e) = self.export() {
+            warn!("STIX export failed: {}", e);
+        }
+    }
+}
+
+fn publish(url: &str, credentials: Option<(String, String)>, envelope: &Value) -> Result<(), String> {
+    let client = reqwest::blocking::Client::builder().timeout(REQUEST_TIMEOUT).build().map_err(|e| e.to_string())?;
+    let mut request = client
+        .post(url)
+        .header(reqwest::header::ACCEPT, TAXII_MEDIA_TYPE)
+        .header(reqwest::header::CONTENT_TYPE, TAXII_MEDIA_TYPE)
+        .body(envelope.to_string());
+    if let Some((username, password)) = credentials {
+        request = request.basic_auth(username, Some(password));
+    }
+    let response = request.send().map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("TAXII server answered HTTP {}", response.status()));
+    }
+    Ok(())
+}
+
+/// `<type>--<random UUID>`
+fn stix_id(object_type: &str) -> Result<String, CaptureError> {
+    let mut bytes: [u8; 16] = random_key()?[..16].try_into().expect("16 bytes");
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!("{}--{}-{}-{}-{}-{}", object_type, &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..]))
+}
+
+/// STIX confidence, 0-100
+fn confidence(severity: Severity) -> u8 {
+    match severity {
+        Severity::Low => 15,
+        Severity::Medium => 50,
+        Severity::High => 85,
+        Severity::Critical => 95,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_indicators_from_alert_hosts() {
+        let path = std::env::temp_dir().join(format!("stix-{}.json", std::process::id()));
+        let config = StixConfig { path: Some(path.clone()), min_severity: Severity::Medium, taxii: None };
+        let mut sink = StixSink::new(&config).unwrap();
+        let host: IpAddr = "203.0.113.9".parse().unwrap();
+        let alert = |time, severity, kind, message: &str| Alert {
+            time,
+            severity,
+            kind,
+            message: message.to_string(),
+            source: None,
+            hosts: vec![host],
+            techniques: vec!["T1071".to_string()],
+        };
+        sink.send(&alert(100.0, Severity::Medium, "threshold", "Threshold 'scan' exceeded"));
+        sink.send(&alert(160.0, Severity::High, "rule", "Rule 'c2' matched"));
+        sink.send(&alert(170.0, Severity::Low, "rule", "ignored"));
+        sink.flush();
+
+        let bundle: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let objects = bundle["objects"].as_array().unwrap();
+        assert_eq!(objects.len(), 3);
+        let indicator = &objects[1];
+        assert!(indicator["id"].as_str().unwrap().starts_with("indicator--"));
+        assert_eq!(indicator["pattern"], "[ipv4-addr:value = '203.0.113.9']");
+        assert_eq!(indicator["name"], "Malicious host 203.0.113.9");
+        assert_eq!(indicator["labels"], json!(["rule", "threshold"]));
+        assert_eq!(indicator["external_references"][0]["url"], "https://attack.mitre.org/techniques/T1071/");
+        assert_eq!(indicator["description"], "Threshold 'scan' exceeded\nRule 'c2' matched");
+        assert_eq!(objects[2]["sighting_of_ref"], indicator["id"]);
+        assert_eq!((objects[2]["count"].as_u64(), objects[2]["last_seen"].as_str()), (Some(2), Some("1970-01-01T00:02:40.000000Z")));
+    }
+}
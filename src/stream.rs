@@ -0,0 +1,71 @@
+use crate::capfile::StoredPacket;
+use crate::error::CaptureError;
+use futures_core::Stream;
+use pcap::{Active, Capture};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::Interest;
+use tokio::io::unix::AsyncFd;
+
+/// The descriptor of a capture handle, borrowed for readiness events
+struct Selectable(RawFd);
+
+impl AsRawFd for Selectable {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+/// A live capture read from tokio: waiting for packets parks the task on the
+/// handle's descriptor instead of polling it, so timers, AI requests and
+/// user input run while the capture is idle
+pub struct PacketStream {
+    fd: AsyncFd<Selectable>,
+    cap: Capture<Active>,
+}
+
+impl PacketStream {
+    /// Takes a handle opened with `setnonblock()`; must be called from within a tokio runtime
+    pub fn new(cap: Capture<Active>) -> Result<Self, CaptureError> {
+        let fd = AsyncFd::with_interest(Selectable(cap.as_raw_fd()), Interest::READABLE)
+            .map_err(|e| CaptureError::PcapError(format!("Cannot watch the capture handle: {}", e)))?;
+        Ok(PacketStream { fd, cap })
+    }
+
+    /// The handle, for statistics and filter changes
+    pub fn capture(&mut self) -> &mut Capture<Active> {
+        &mut self.cap
+    }
+
+    /// The next packet, copied out of libpcap's buffer. Dropping the future
+    /// before it completes loses no packet, so it can be raced in `select!`.
+    pub async fn next_packet(&mut self) -> Result<StoredPacket, pcap::Error> {
+        std::future::poll_fn(|cx| self.poll_packet(cx)).await
+    }
+
+    /// Reads a packet if one is waiting, or registers `cx` to be woken when the handle becomes readable
+    pub fn poll_packet(&mut self, cx: &mut Context<'_>) -> Poll<Result<StoredPacket, pcap::Error>> {
+        loop {
+            match self.cap.next_packet() {
+                Ok(packet) => return Poll::Ready(Ok(packet.into())),
+                Err(pcap::Error::TimeoutExpired) => {}
+                Err(pcap::Error::PcapError(e)) if e.contains("Packets are not available") => {}
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+            match self.fd.poll_read_ready(cx) {
+                Poll::Ready(Ok(mut ready)) => ready.clear_ready(),
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(pcap::Error::IoError(e.kind()))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl Stream for PacketStream {
+    type Item = Result<StoredPacket, pcap::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_packet(cx).map(Some)
+    }
+}
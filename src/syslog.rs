@@ -0,0 +1,406 @@
+use crate::alert::{Alert, AlertSink, Severity};
+use crate::error::CaptureError;
+use crate::flow::FlowRecord;
+use crate::siem::{cef_alert, cef_flow, leef_alert, leef_flow, EventFormat};
+use crate::timestamp::format_rfc3339;
+use log::warn;
+use native_tls::{TlsConnector, TlsStream};
+use serde::Deserialize;
+use std::io::Write;
+use std::net::{TcpStream, UdpSocket};
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Private enterprise number reserved for documentation (RFC 5612), used to
+/// name the structured data elements
+const ENTERPRISE_ID: u32 = 32473;
+
+/// Delivery errors logged before the rest are counted silently
+const MAX_REPORTED_ERRORS: u32 = 10;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Syslog output from the configuration file (`[alerts.syslog]`) or `--syslog`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyslogConfig {
+    /// `udp://host:514`, `tcp://host:514`, `tls://host:6514` or a Unix socket such as `/dev/log`
+    pub address: String,
+    /// `user`, `daemon`, `auth`, `authpriv` or `local0` to `local7`
+    #[serde(default = "default_facility")]
+    pub facility: String,
+    #[serde(default = "default_app_name")]
+    pub app_name: String,
+    /// Also send a summary of every TCP/UDP flow when it ends
+    #[serde(default)]
+    pub flows: bool,
+    /// Alerts below this severity are not sent
+    #[serde(default = "default_min_severity")]
+    pub min_severity: Severity,
+    /// Accept a TLS collector whose certificate doesn't verify
+    #[serde(default)]
+    pub insecure: bool,
+    /// `rfc5424` structured data, or `cef`/`leef` event strings as the message
+    #[serde(default)]
+    pub format: EventFormat,
+}
+
+fn default_facility() -> String {
+    "user".to_string()
+}
+
+fn default_app_name() -> String {
+    "rust-sniffer".to_string()
+}
+
+fn default_min_severity() -> Severity {
+    Severity::Low
+}
+
+impl SyslogConfig {
+    /// Defaults for everything but the address
+    pub fn new(address: &str) -> Self {
+        SyslogConfig {
+            address: address.to_string(),
+            facility: default_facility(),
+            app_name: default_app_name(),
+            flows: false,
+            min_severity: default_min_severity(),
+            insecure: false,
+            format: EventFormat::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    Udp(String),
+    Tcp(String),
+    Tls(String),
+    Unix(PathBuf),
+}
+
+impl Target {
+    fn parse(address: &str) -> Result<Self, CaptureError> {
+        let with_port = |rest: &str, port: u16| if rest.contains(':') { rest.to_string() } else { format!("{}:{}", rest, port) };
+        let target = if let Some(rest) = address.strip_prefix("udp://") {
+            Target::Udp(with_port(rest, 514))
+        } else if let Some(rest) = address.strip_prefix("tcp://") {
+            Target::Tcp(with_port(rest, 514))
+        } else if let Some(rest) = address.strip_prefix("tls://") {
+            Target::Tls(with_port(rest, 6514))
+        } else if let Some(path) = address.strip_prefix("unix://") {
+            Target::Unix(PathBuf::from(path))
+        } else if address.starts_with('/') {
+            Target::Unix(PathBuf::from(address))
+        } else {
+            return Err(CaptureError::InputError(format!(
+                "Invalid syslog address '{}': use udp://, tcp://, tls:// or a socket path",
+                address
+            )));
+        };
+        Ok(target)
+    }
+
+    /// Stream transports need octet-counting framing (RFC 6587, RFC 5425)
+    fn is_stream(&self) -> bool {
+        matches!(self, Target::Tcp(_) | Target::Tls(_))
+    }
+}
+
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+    Unix(UnixDatagram),
+}
+
+/// Sends alerts, and optionally flow summaries, as RFC 5424 syslog messages.
+/// A stream connection that fails is reopened for the next message.
+pub struct SyslogSink {
+    target: Target,
+    insecure: bool,
+    connection: Option<Connection>,
+    facility: u8,
+    app_name: String,
+    hostname: String,
+    flows: bool,
+    min_severity: Severity,
+    format: EventFormat,
+    errors: u32,
+}
+
+impl SyslogSink {
+    /// Connects right away so a wrong address fails before the capture starts
+    pub fn connect(config: &SyslogConfig) -> Result<Self, CaptureError> {
+        let facility = facility_code(&config.facility)
+            .ok_or_else(|| CaptureError::InputError(format!("Unknown syslog facility '{}'", config.facility)))?;
+        let mut sink = SyslogSink {
+            target: Target::parse(&config.address)?,
+            insecure: config.insecure,
+            connection: None,
+            facility,
+            app_name: config.app_name.clone(),
+            hostname: hostname(),
+            flows: config.flows,
+            min_severity: config.min_severity,
+            format: config.format,
+            errors: 0,
+        };
+        sink.connection = Some(sink.open().map_err(|e| CaptureError::NetworkError(format!("Cannot reach syslog at {}: {}", config.address, e)))?);
+        Ok(sink)
+    }
+
+    fn open(&self) -> Result<Connection, String> {
+        let tcp = |address: &str| -> Result<TcpStream, String> {
+            let addr = std::net::ToSocketAddrs::to_socket_addrs(address).map_err(|e| e.to_string())?.next().ok_or("no address")?;
+            TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).map_err(|e| e.to_string())
+        };
+        match &self.target {
+            Target::Udp(address) => {
+                let socket = UdpSocket::bind(if address.starts_with('[') { "[::]:0" } else { "0.0.0.0:0" }).map_err(|e| e.to_string())?;
+                socket.connect(address).map_err(|e| e.to_string())?;
+                Ok(Connection::Udp(socket))
+            }
+            Target::Tcp(address) => tcp(address).map(Connection::Tcp),
+            Target::Tls(address) => {
+                let domain = address.rsplit_once(':').map_or(address.as_str(), |(host, _)| host).trim_matches(['[', ']']);
+                let connector = TlsConnector::builder()
+                    .danger_accept_invalid_certs(self.insecure)
+                    .build()
+                    .map_err(|e| e.to_string())?;
+                let stream = connector.connect(domain, tcp(address)?).map_err(|e| e.to_string())?;
+                Ok(Connection::Tls(Box::new(stream)))
+            }
+            Target::Unix(path) => {
+                let socket = UnixDatagram::unbound().map_err(|e| e.to_string())?;
+                socket.connect(path).map_err(|e| e.to_string())?;
+                Ok(Connection::Unix(socket))
+            }
+        }
+    }
+
+    /// The RFC 5424 message for an alert
+    pub fn format_alert(&self, alert: &Alert) -> String {
+        let severity = syslog_severity(alert.severity);
+        match self.format {
+            EventFormat::Cef => return self.format(alert.time, severity, "alert", None, &cef_alert(alert)),
+            EventFormat::Leef => return self.format(alert.time, severity, "alert", None, &leef_alert(alert)),
+            EventFormat::Rfc5424 => {}
+        }
+        let mut params = vec![("severity", alert.severity.to_string()), ("kind", alert.kind.to_string())];
+        if !alert.techniques.is_empty() {
+            params.push(("techniques", alert.techniques.join(",")));
+        }
+        if let Some(source) = &alert.source {
+            params.push(("namespace", source.clone()));
+        }
+        self.format(alert.time, severity, "alert", Some(("alert", &params)), &alert.message)
+    }
+
+    /// The RFC 5424 message for a finished flow, sent at informational level
+    pub fn format_flow(&self, flow: &FlowRecord) -> String {
+        match self.format {
+            EventFormat::Cef => return self.format(flow.last, 6, "flow", None, &cef_flow(flow)),
+            EventFormat::Leef => return self.format(flow.last, 6, "flow", None, &leef_flow(flow)),
+            EventFormat::Rfc5424 => {}
+        }
+        let responder = if flow.initiator == flow.key.a { flow.key.b } else { flow.key.a };
+        let params = [
+            ("protocol", flow.key.protocol_name()),
+            ("src", flow.initiator.0.to_string()),
+            ("sport", flow.initiator.1.to_string()),
+            ("dst", responder.0.to_string()),
+            ("dport", responder.1.to_string()),
+            ("packets", flow.packets.to_string()),
+            ("bytes", flow.bytes.to_string()),
+            ("bytes_out", flow.bytes_out.to_string()),
+            ("duration", format!("{:.3}", flow.last - flow.first)),
+            ("end", flow.end.name().to_string()),
+        ];
+        let message = format!("Flow {} ended ({}): {} packets, {} bytes", flow.key, flow.end.name(), flow.packets, flow.bytes);
+        self.format(flow.last, 6, "flow", Some(("flow", &params)), &message)
+    }
+
+    /// Header, structured data element (`-` without one) and message
+    fn format(&self, time: f64, severity: u8, msgid: &str, data: Option<(&str, &[(&str, String)])>, message: &str) -> String {
+        let data = match data {
+            Some((element, params)) => {
+                let params: String = params.iter().map(|(name, value)| format!(" {}=\"{}\"", name, escape_param(value))).collect();
+                format!("[{}@{}{}]", element, ENTERPRISE_ID, params)
+            }
+            None => "-".to_string(),
+        };
+        format!(
+            "<{}>1 {} {} {} {} {} {} {}",
+            self.facility as u32 * 8 + severity as u32,
+            format_rfc3339(time),
+            header_field(&self.hostname),
+            header_field(&self.app_name),
+            std::process::id(),
+            msgid,
+            data,
+            message
+        )
+    }
+
+    fn deliver(&mut self, message: String) {
+        let framed = if self.target.is_stream() { format!("{} {}", message.len(), message) } else { message };
+        let result = match self.connection.take().map_or_else(|| self.open(), Ok) {
+            Ok(mut connection) => {
+                let sent = match &mut connection {
+                    Connection::Udp(socket) => socket.send(framed.as_bytes()).map(|_| ()),
+                    Connection::Tcp(stream) => stream.write_all(framed.as_bytes()),
+                    Connection::Tls(stream) => stream.write_all(framed.as_bytes()),
+                    Connection::Unix(socket) => socket.send(framed.as_bytes()).map(|_| ()),
+                };
+                // Datagram sockets stay usable after a failed send; streams are reopened
+                if sent.is_ok() || !self.target.is_stream() {
+                    self.connection = Some(connection);
+                }
+                sent.map_err(|e| e.to_string())
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            self.errors += 1;
+            if self.errors <= MAX_REPORTED_ERRORS {
+                warn!("Syslog delivery failed: {}", e);
+            }
+        }
+    }
+}
+
+impl AlertSink for SyslogSink {
+    fn name(&self) -> &str {
+        "syslog"
+    }
+
+    fn send(&mut self, alert: &Alert) {
+        if alert.severity >= self.min_severity {
+            let message = self.format_alert(alert);
+            self.deliver(message);
+        }
+    }
+
+    fn wants_flows(&self) -> bool {
+        self.flows
+    }
+
+    fn send_flow(&mut self, flow: &FlowRecord) {
+        let message = self.format_flow(flow);
+        self.deliver(message);
+    }
+}
+
+fn facility_code(name: &str) -> Option<u8> {
+    Some(match name {
+        "kern" => 0,
+        "user" => 1,
+        "daemon" => 3,
+        "auth" | "security" => 4,
+        "syslog" => 5,
+        "authpriv" => 10,
+        "local0" => 16,
+        "local1" => 17,
+        "local2" => 18,
+        "local3" => 19,
+        "local4" => 20,
+        "local5" => 21,
+        "local6" => 22,
+        "local7" => 23,
+        _ => return None,
+    })
+}
+
+/// Syslog severity: critical, error, warning or notice
+fn syslog_severity(severity: Severity) -> u8 {
+    match severity {
+        Severity::Critical => 2,
+        Severity::High => 3,
+        Severity::Medium => 4,
+        Severity::Low => 5,
+    }
+}
+
+/// Header fields are printable ASCII without spaces, `-` when empty
+fn header_field(value: &str) -> String {
+    let field: String = value.chars().filter(|c| c.is_ascii_graphic()).take(48).collect();
+    if field.is_empty() { "-".to_string() } else { field }
+}
+
+fn escape_param(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace(']', "\\]")
+}
+
+fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for its length; the name may not be NUL-terminated when truncated
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return String::new();
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::{FlowEnd, FlowKey};
+    use crate::testutil;
+
+    #[test]
+    fn formats_rfc5424_messages_and_sends_them_over_udp() {
+        let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let config = SyslogConfig { facility: "local3".to_string(), flows: true, ..SyslogConfig::new(&format!("udp://{}", collector.local_addr().unwrap())) };
+        let mut sink = SyslogSink::connect(&config).unwrap();
+        sink.hostname = "sensor".to_string();
+        let alert = Alert {
+            time: 1_700_000_000.25,
+            severity: Severity::High,
+            kind: "rule",
+            message: "[HIGH] Rule 'x' matched".to_string(),
+            source: Some("web \"1\"".to_string()),
+            hosts: Vec::new(),
+            techniques: Vec::new(),
+        };
+        let expected = format!(
+            "<155>1 2023-11-14T22:13:20.250000Z sensor rust-sniffer {} alert [alert@32473 severity=\"HIGH\" kind=\"rule\" namespace=\"web \\\"1\\\"\"] [HIGH] Rule 'x' matched",
+            std::process::id()
+        );
+        assert_eq!(sink.format_alert(&alert), expected);
+        sink.send(&alert);
+        let mut buf = [0u8; 512];
+        let len = collector.recv(&mut buf).unwrap();
+        assert_eq!(std::str::from_utf8(&buf[..len]).unwrap(), expected);
+
+        let (client, server) = ((testutil::client_v4().into(), 40000), (testutil::server_v4().into(), 443));
+        let flow = FlowRecord {
+            key: FlowKey::new(6, client, server),
+            initiator: client,
+            packets: 10,
+            bytes: 4000,
+            packets_out: 4,
+            bytes_out: 600,
+            first: 1.0,
+            last: 3.5,
+            end: FlowEnd::Fin,
+        };
+        let message = sink.format_flow(&flow);
+        assert!(message.starts_with("<158>1 1970-01-01T00:00:03.500000Z sensor"));
+        assert!(message.contains("src=\"192.168.1.10\" sport=\"40000\" dst=\"93.184.216.34\" dport=\"443\""));
+        assert!(message.contains("duration=\"2.500\" end=\"fin\""));
+        sink.format = EventFormat::Cef;
+        assert!(sink.format_flow(&flow).contains(" flow - CEF:0|rust-sniffer|"));
+    }
+
+    #[test]
+    fn parses_addresses() {
+        assert_eq!(Target::parse("udp://logs").unwrap(), Target::Udp("logs:514".to_string()));
+        assert_eq!(Target::parse("tls://logs.example.com").unwrap(), Target::Tls("logs.example.com:6514".to_string()));
+        assert_eq!(Target::parse("/dev/log").unwrap(), Target::Unix(PathBuf::from("/dev/log")));
+        assert!(Target::parse("logs:514").is_err());
+        assert!(SyslogSink::connect(&SyslogConfig { facility: "mail2".to_string(), ..SyslogConfig::new("udp://127.0.0.1") }).is_err());
+    }
+}
@@ -0,0 +1,451 @@
+//! Synthetic packet generation for tests and benchmarks.
+//!
+//! Every helper returns plain wire-format bytes so parsers can be exercised
+//! without a live capture. Layers are built inside-out: build the payload,
+//! wrap it in a transport header, then an IP header, then an Ethernet frame.
+
+use crate::builder::PacketBuilder;
+use crate::protocols::arp::{build_arp, ARP_REQUEST};
+use crate::protocols::quic::{encode_varint, InitialKeys};
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes128Gcm, KeyInit};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+pub use crate::builder::{internet_checksum, TCP_ACK, TCP_FIN, TCP_PSH, TCP_RST, TCP_SYN};
+
+pub const SRC_MAC: [u8; 6] = [0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb];
+pub const DST_MAC: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+
+pub const ETHERTYPE_IPV4: u16 = 0x0800;
+pub const ETHERTYPE_ARP: u16 = 0x0806;
+pub const ETHERTYPE_IPV6: u16 = 0x86DD;
+
+pub const PROTO_TCP: u8 = 6;
+pub const PROTO_UDP: u8 = 17;
+
+/// Wraps a payload in an Ethernet II header
+pub fn ethernet(src: [u8; 6], dst: [u8; 6], ether_type: u16, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(14 + payload.len());
+    frame.extend_from_slice(&dst);
+    frame.extend_from_slice(&src);
+    frame.extend_from_slice(&ether_type.to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Wraps a payload in a 20-byte IPv4 header with a valid header checksum
+pub fn ipv4(src: Ipv4Addr, dst: Ipv4Addr, protocol: u8, payload: &[u8]) -> Vec<u8> {
+    let total_len = (20 + payload.len()) as u16;
+    let mut packet = Vec::with_capacity(total_len as usize);
+    packet.extend_from_slice(&[0x45, 0x00]);
+    packet.extend_from_slice(&total_len.to_be_bytes());
+    packet.extend_from_slice(&[0x1c, 0x46, 0x40, 0x00]); // id, DF
+    packet.extend_from_slice(&[64, protocol, 0x00, 0x00]); // ttl, proto, checksum
+    packet.extend_from_slice(&src.octets());
+    packet.extend_from_slice(&dst.octets());
+    let checksum = internet_checksum(&packet);
+    packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// Wraps a payload in a fixed 40-byte IPv6 header
+pub fn ipv6(src: Ipv6Addr, dst: Ipv6Addr, next_header: u8, payload: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(40 + payload.len());
+    packet.extend_from_slice(&[0x60, 0x00, 0x00, 0x00]);
+    packet.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    packet.extend_from_slice(&[next_header, 64]);
+    packet.extend_from_slice(&src.octets());
+    packet.extend_from_slice(&dst.octets());
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// Builds a 20-byte TCP header (no options) followed by the payload.
+/// The checksum is left at zero.
+pub fn tcp(src_port: u16, dst_port: u16, seq: u32, ack: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+    let mut segment = Vec::with_capacity(20 + payload.len());
+    segment.extend_from_slice(&src_port.to_be_bytes());
+    segment.extend_from_slice(&dst_port.to_be_bytes());
+    segment.extend_from_slice(&seq.to_be_bytes());
+    segment.extend_from_slice(&ack.to_be_bytes());
+    segment.extend_from_slice(&[0x50, flags, 0xfa, 0xf0, 0x00, 0x00, 0x00, 0x00]);
+    segment.extend_from_slice(payload);
+    segment
+}
+
+/// Builds a UDP header followed by the payload. The checksum is left at zero,
+/// which IPv4 treats as "not computed".
+pub fn udp(src_port: u16, dst_port: u16, payload: &[u8]) -> Vec<u8> {
+    let len = (8 + payload.len()) as u16;
+    let mut datagram = Vec::with_capacity(len as usize);
+    datagram.extend_from_slice(&src_port.to_be_bytes());
+    datagram.extend_from_slice(&dst_port.to_be_bytes());
+    datagram.extend_from_slice(&len.to_be_bytes());
+    datagram.extend_from_slice(&[0x00, 0x00]);
+    datagram.extend_from_slice(payload);
+    datagram
+}
+
+/// Encodes a dotted domain name as DNS labels (no compression)
+pub fn dns_name(name: &str) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    for label in name.split('.').filter(|l| !l.is_empty()) {
+        encoded.push(label.len() as u8);
+        encoded.extend_from_slice(label.as_bytes());
+    }
+    encoded.push(0);
+    encoded
+}
+
+/// Builds a recursive DNS query for a single name and record type
+pub fn dns_query(id: u16, name: &str, qtype: u16) -> Vec<u8> {
+    let mut message = Vec::new();
+    message.extend_from_slice(&id.to_be_bytes());
+    message.extend_from_slice(&[0x01, 0x00]); // RD
+    message.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]); // 1 question
+    message.extend_from_slice(&dns_name(name));
+    message.extend_from_slice(&qtype.to_be_bytes());
+    message.extend_from_slice(&[0x00, 0x01]); // IN
+    message
+}
+
+/// Builds a DNS response answering an A query with the given addresses.
+/// Answers point back at the question name through a compression pointer.
+pub fn dns_response(id: u16, name: &str, answers: &[Ipv4Addr]) -> Vec<u8> {
+    let mut message = Vec::new();
+    message.extend_from_slice(&id.to_be_bytes());
+    message.extend_from_slice(&[0x81, 0x80]); // QR, RD, RA
+    message.extend_from_slice(&[0, 1]);
+    message.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+    message.extend_from_slice(&[0, 0, 0, 0]);
+    message.extend_from_slice(&dns_name(name));
+    message.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+    for addr in answers {
+        message.extend_from_slice(&[0xc0, 0x0c]); // pointer to offset 12
+        message.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+        message.extend_from_slice(&300u32.to_be_bytes());
+        message.extend_from_slice(&[0x00, 0x04]);
+        message.extend_from_slice(&addr.octets());
+    }
+    message
+}
+
+/// First-level encodes a NetBIOS name with the workstation (0x00) suffix
+pub fn netbios_name(name: &str) -> Vec<u8> {
+    let mut padded = [b' '; 16];
+    padded[..name.len()].copy_from_slice(name.as_bytes());
+    padded[15] = 0x00;
+    let mut encoded = vec![32];
+    for byte in padded {
+        encoded.extend_from_slice(&[b'A' + (byte >> 4), b'A' + (byte & 0x0F)]);
+    }
+    encoded.push(0);
+    encoded
+}
+
+/// Builds a positive NetBIOS name query response carrying one unique name
+pub fn nbns_response(id: u16, name: &str, addr: Ipv4Addr) -> Vec<u8> {
+    let mut message = Vec::new();
+    message.extend_from_slice(&id.to_be_bytes());
+    message.extend_from_slice(&[0x85, 0x00]); // response, AA, RD
+    message.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 0]); // answer only, as Windows sends it
+    message.extend_from_slice(&netbios_name(name));
+    message.extend_from_slice(&[0x00, 0x20, 0x00, 0x01]); // NB, IN
+    message.extend_from_slice(&300u32.to_be_bytes());
+    message.extend_from_slice(&[0x00, 0x06, 0x00, 0x00]); // unique B-node entry
+    message.extend_from_slice(&addr.octets());
+    message
+}
+
+/// Builds an LDAPv3 BindRequest with simple authentication (short-form BER lengths)
+pub fn ldap_simple_bind(message_id: u8, dn: &str, password: &str) -> Vec<u8> {
+    let mut bind = vec![0x02, 0x01, 0x03, 0x04, dn.len() as u8];
+    bind.extend_from_slice(dn.as_bytes());
+    bind.extend_from_slice(&[0x80, password.len() as u8]);
+    bind.extend_from_slice(password.as_bytes());
+    let mut message = vec![0x02, 0x01, message_id, 0x60, bind.len() as u8];
+    message.extend_from_slice(&bind);
+    let mut encoded = vec![0x30, message.len() as u8];
+    encoded.extend_from_slice(&message);
+    encoded
+}
+
+/// Builds a RADIUS packet with a zero authenticator and the given attributes
+pub fn radius_packet(code: u8, identifier: u8, attributes: &[(u8, &[u8])]) -> Vec<u8> {
+    let mut packet = vec![code, identifier, 0, 0];
+    packet.extend_from_slice(&[0; 16]);
+    for (attr_type, value) in attributes {
+        packet.extend_from_slice(&[*attr_type, value.len() as u8 + 2]);
+        packet.extend_from_slice(value);
+    }
+    let length = packet.len() as u16;
+    packet[2..4].copy_from_slice(&length.to_be_bytes());
+    packet
+}
+
+/// Builds an OSPFv2 Hello (priority 1, no authentication) listing the given neighbors
+pub fn ospf_hello(router_id: Ipv4Addr, area_id: Ipv4Addr, neighbors: &[Ipv4Addr]) -> Vec<u8> {
+    let mut packet = vec![2, 1, 0, 0];
+    packet.extend_from_slice(&router_id.octets());
+    packet.extend_from_slice(&area_id.octets());
+    packet.extend_from_slice(&[0; 12]); // checksum, autype, authentication
+    packet.extend_from_slice(&[255, 255, 255, 0, 0, 10, 0x02, 1, 0, 0, 0, 40]);
+    packet.extend_from_slice(&[0; 8]); // DR, BDR
+    for neighbor in neighbors {
+        packet.extend_from_slice(&neighbor.octets());
+    }
+    let length = packet.len() as u16;
+    packet[2..4].copy_from_slice(&length.to_be_bytes());
+    packet
+}
+
+/// Builds a GTP-U G-PDU header with no optional fields around `inner`
+pub fn gtp_u(teid: u32, inner: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0x30, 255];
+    packet.extend_from_slice(&(inner.len() as u16).to_be_bytes());
+    packet.extend_from_slice(&teid.to_be_bytes());
+    packet.extend_from_slice(inner);
+    packet
+}
+
+/// Builds a Diameter message whose AVPs have the M flag and no vendor ID
+pub fn diameter_message(flags: u8, command: u32, application: u32, avps: &[(u32, &[u8])]) -> Vec<u8> {
+    let mut message = vec![1, 0, 0, 0, flags];
+    message.extend_from_slice(&command.to_be_bytes()[1..]);
+    message.extend_from_slice(&application.to_be_bytes());
+    message.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 1]);
+    for (code, value) in avps {
+        message.extend_from_slice(&code.to_be_bytes());
+        message.push(0x40);
+        message.extend_from_slice(&(value.len() as u32 + 8).to_be_bytes()[1..]);
+        message.extend_from_slice(value);
+        message.resize(message.len().next_multiple_of(4), 0);
+    }
+    let length = message.len() as u32;
+    message[1..4].copy_from_slice(&length.to_be_bytes()[1..]);
+    message
+}
+
+/// SCTP common header (zero checksum) followed by one DATA chunk
+pub fn sctp_data(src_port: u16, dst_port: u16, ppid: u32, data: &[u8]) -> Vec<u8> {
+    let mut packet = src_port.to_be_bytes().to_vec();
+    packet.extend_from_slice(&dst_port.to_be_bytes());
+    packet.extend_from_slice(&[0; 8]);
+    packet.extend_from_slice(&[0, 3]);
+    packet.extend_from_slice(&(data.len() as u16 + 16).to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 0]);
+    packet.extend_from_slice(&ppid.to_be_bytes());
+    packet.extend_from_slice(data);
+    packet.resize(packet.len().next_multiple_of(4), 0);
+    packet
+}
+
+/// Ethernet + IPv4 + TCP frame between two fixed hosts
+pub fn ipv4_tcp_frame(src_port: u16, dst_port: u16, flags: u8, payload: &[u8]) -> Vec<u8> {
+    frame().ipv4(client_v4(), server_v4()).tcp(src_port, dst_port).seq(1).tcp_flags(flags).payload(payload).build()
+}
+
+/// Ethernet + IPv4 + UDP frame between two fixed hosts
+pub fn ipv4_udp_frame(src_port: u16, dst_port: u16, payload: &[u8]) -> Vec<u8> {
+    frame().ipv4(client_v4(), server_v4()).udp(src_port, dst_port).payload(payload).build()
+}
+
+/// Ethernet + IPv6 + TCP frame between two fixed hosts
+pub fn ipv6_tcp_frame(src_port: u16, dst_port: u16, flags: u8, payload: &[u8]) -> Vec<u8> {
+    frame().ipv6(client_v6(), server_v6()).tcp(src_port, dst_port).seq(1).tcp_flags(flags).payload(payload).build()
+}
+
+/// Ethernet + IPv6 + UDP frame between two fixed hosts
+pub fn ipv6_udp_frame(src_port: u16, dst_port: u16, payload: &[u8]) -> Vec<u8> {
+    frame().ipv6(client_v6(), server_v6()).udp(src_port, dst_port).payload(payload).build()
+}
+
+/// Ethernet + IPv4 + UDP frame carrying a DNS A query to port 53
+pub fn dns_query_frame(id: u16, name: &str) -> Vec<u8> {
+    ipv4_udp_frame(53000, 53, &dns_query(id, name, 1))
+}
+
+/// ClientHello handshake message with server_name and, when given, ALPN extensions
+pub fn client_hello(server_name: &str, alpn: &[&str]) -> Vec<u8> {
+    let name = server_name.as_bytes();
+    let mut sni = Vec::new();
+    sni.extend_from_slice(&((name.len() + 3) as u16).to_be_bytes()); // server_name_list
+    sni.push(0); // host_name
+    sni.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    sni.extend_from_slice(name);
+
+    let mut extensions = vec![0x00, 0x00]; // server_name
+    extensions.extend_from_slice(&(sni.len() as u16).to_be_bytes());
+    extensions.extend_from_slice(&sni);
+
+    if !alpn.is_empty() {
+        let mut list = Vec::new();
+        for protocol in alpn {
+            list.push(protocol.len() as u8);
+            list.extend_from_slice(protocol.as_bytes());
+        }
+        extensions.extend_from_slice(&[0x00, 0x10]); // application_layer_protocol_negotiation
+        extensions.extend_from_slice(&((list.len() + 2) as u16).to_be_bytes());
+        extensions.extend_from_slice(&(list.len() as u16).to_be_bytes());
+        extensions.extend_from_slice(&list);
+    }
+
+    let mut hello = vec![0x03, 0x03];
+    hello.extend_from_slice(&[0x42; 32]); // random
+    hello.push(0); // session id
+    hello.extend_from_slice(&[0x00, 0x02, 0x13, 0x01]); // TLS_AES_128_GCM_SHA256
+    hello.extend_from_slice(&[0x01, 0x00]); // null compression
+    hello.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+    hello.extend_from_slice(&extensions);
+
+    let mut handshake = vec![0x01];
+    handshake.extend_from_slice(&(hello.len() as u32).to_be_bytes()[1..]);
+    handshake.extend_from_slice(&hello);
+    handshake
+}
+
+/// Minimal TLS 1.2 ClientHello record carrying a server_name extension
+pub fn tls_client_hello(server_name: &str) -> Vec<u8> {
+    let handshake = client_hello(server_name, &[]);
+    let mut record = vec![0x16, 0x03, 0x01];
+    record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+    record.extend_from_slice(&handshake);
+    record
+}
+
+/// TLS ServerHello record selecting `cipher_suite`
+pub fn tls_server_hello(cipher_suite: u16) -> Vec<u8> {
+    let mut hello = vec![0x03, 0x03];
+    hello.extend_from_slice(&[0x24; 32]); // random
+    hello.push(0); // session id
+    hello.extend_from_slice(&cipher_suite.to_be_bytes());
+    hello.push(0); // null compression
+    hello.extend_from_slice(&[0x00, 0x00]); // no extensions
+
+    let mut record = vec![0x16, 0x03, 0x03];
+    record.extend_from_slice(&((hello.len() + 4) as u16).to_be_bytes());
+    record.push(0x02);
+    record.extend_from_slice(&(hello.len() as u32).to_be_bytes()[1..]);
+    record.extend_from_slice(&hello);
+    record
+}
+
+/// Protected QUIC v1 client Initial carrying `crypto` in a CRYPTO frame,
+/// padded to the 1200-byte minimum like a real first flight
+pub fn quic_initial(dcid: &[u8], scid: &[u8], packet_number: u32, crypto: &[u8]) -> Vec<u8> {
+    let mut frames = vec![0x06, 0x00];
+    frames.extend_from_slice(&encode_varint(crypto.len() as u64));
+    frames.extend_from_slice(crypto);
+
+    let mut header = vec![0xc3, 0x00, 0x00, 0x00, 0x01, dcid.len() as u8];
+    header.extend_from_slice(dcid);
+    header.push(scid.len() as u8);
+    header.extend_from_slice(scid);
+    header.push(0); // no token
+    let overhead = header.len() + 2 + 4 + 16;
+    frames.resize(frames.len().max(1200 - overhead), 0); // PADDING
+    header.extend_from_slice(&((4 + frames.len() + 16) as u16 | 0x4000).to_be_bytes());
+    let pn_offset = header.len();
+    header.extend_from_slice(&packet_number.to_be_bytes());
+
+    let keys = InitialKeys::client(dcid);
+    let sealed = Aes128Gcm::new_from_slice(&keys.key)
+        .unwrap()
+        .encrypt(&keys.nonce(packet_number as u64).into(), Payload { msg: &frames, aad: &header })
+        .unwrap();
+    let mut packet = header;
+    packet.extend_from_slice(&sealed);
+
+    let mask = keys.header_mask(&packet[pn_offset + 4..pn_offset + 20]);
+    packet[0] ^= mask[0] & 0x0f;
+    for i in 0..4 {
+        packet[pn_offset + i] ^= mask[1 + i];
+    }
+    packet
+}
+
+/// Ethernet + IPv4 + UDP frame carrying a QUIC client Initial to port 443
+pub fn quic_initial_frame(src_port: u16, dcid: &[u8], server_name: &str, alpn: &[&str]) -> Vec<u8> {
+    let initial = quic_initial(dcid, &[0xc1, 0x1e, 0x47, 0x00], 0, &client_hello(server_name, alpn));
+    ipv4_udp_frame(src_port, 443, &initial)
+}
+
+/// EAPOL-Key frame (RSN descriptor, 802.1X-2004) without the link-layer header
+pub fn eapol_key(key_info: u16, replay_counter: u64, nonce: &[u8; 32], mic: &[u8; 16], key_data: &[u8]) -> Vec<u8> {
+    let mut body = vec![2];
+    body.extend_from_slice(&key_info.to_be_bytes());
+    body.extend_from_slice(&16u16.to_be_bytes()); // key length
+    body.extend_from_slice(&replay_counter.to_be_bytes());
+    body.extend_from_slice(nonce);
+    body.extend_from_slice(&[0; 16 + 8 + 8]); // IV, RSC, reserved
+    body.extend_from_slice(mic);
+    body.extend_from_slice(&(key_data.len() as u16).to_be_bytes());
+    body.extend_from_slice(key_data);
+    let mut packet = vec![2, 3];
+    packet.extend_from_slice(&(body.len() as u16).to_be_bytes());
+    packet.extend(body);
+    packet
+}
+
+/// HTTP/2 frame with the given header fields
+pub fn http2_frame(frame_type: u8, flags: u8, stream_id: u32, payload: &[u8]) -> Vec<u8> {
+    let mut frame = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+    frame.push(frame_type);
+    frame.push(flags);
+    frame.extend_from_slice(&stream_id.to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// WebSocket frame, masked with `mask` when given (as clients must)
+pub fn websocket_frame(fin: bool, opcode: u8, mask: Option<[u8; 4]>, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![if fin { 0x80 } else { 0 } | opcode];
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        len @ 0..=125 => frame.push(mask_bit | len as u8),
+        len @ 126..=0xffff => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    match mask {
+        Some(key) => {
+            frame.extend_from_slice(&key);
+            frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ key[i % 4]));
+        }
+        None => frame.extend_from_slice(payload),
+    }
+    frame
+}
+
+/// Ethernet broadcast ARP who-has request
+pub fn arp_request(sender: Ipv4Addr, target: Ipv4Addr) -> Vec<u8> {
+    let arp = build_arp(ARP_REQUEST, SRC_MAC, sender, [0; 6], target);
+    ethernet(SRC_MAC, [0xff; 6], ETHERTYPE_ARP, &arp)
+}
+
+/// Ethernet builder pre-filled with the fixed test MAC addresses
+pub fn frame() -> PacketBuilder {
+    PacketBuilder::ethernet().src_mac(SRC_MAC).dst_mac(DST_MAC)
+}
+
+pub fn client_v4() -> Ipv4Addr {
+    Ipv4Addr::new(192, 168, 1, 10)
+}
+
+pub fn server_v4() -> Ipv4Addr {
+    Ipv4Addr::new(93, 184, 216, 34)
+}
+
+pub fn client_v6() -> Ipv6Addr {
+    "fe80::1".parse().unwrap()
+}
+
+pub fn server_v6() -> Ipv6Addr {
+    "2001:db8::2".parse().unwrap()
+}
@@ -0,0 +1,429 @@
+use crate::alert::Severity;
+use crate::attack;
+use crate::capfile::parse_size;
+use crate::error::CaptureError;
+use crate::filter::DisplayFilter;
+use crate::protocols::dns::DnsMessage;
+use crate::protocols::summary::PacketSummary;
+use crate::rules::is_internal;
+use crate::timestamp::parse_duration;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Each window is counted in this many slots, which slide out one at a time
+const SLOTS: usize = 12;
+
+/// What a threshold counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Packets,
+    /// Bytes on the wire, including those the snaplen cut off
+    Bytes,
+    /// TCP connection attempts (SYN without ACK)
+    Connections,
+    /// TCP resets
+    Resets,
+    DnsQueries,
+    /// DNS responses with rcode 3
+    Nxdomain,
+    /// ICMP/ICMPv6 destination unreachable messages
+    Unreachable,
+}
+
+impl Metric {
+    fn name(&self) -> &'static str {
+        match self {
+            Metric::Packets => "packets",
+            Metric::Bytes => "bytes",
+            Metric::Connections => "connections",
+            Metric::Resets => "resets",
+            Metric::DnsQueries => "DNS queries",
+            Metric::Nxdomain => "NXDOMAIN responses",
+            Metric::Unreachable => "unreachables",
+        }
+    }
+
+    /// ATT&CK technique a threshold on the metric usually catches
+    fn technique(&self, direction: Option<TrafficDirection>) -> Option<&'static str> {
+        match (self, direction) {
+            (Metric::Connections | Metric::Resets | Metric::Unreachable, _) => Some(attack::NETWORK_SERVICE_DISCOVERY),
+            (Metric::Nxdomain, _) => Some(attack::DOMAIN_GENERATION_ALGORITHMS),
+            (Metric::DnsQueries, _) => Some(attack::DNS),
+            (Metric::Bytes, Some(TrafficDirection::Outbound)) => Some(attack::EXFILTRATION_OVER_ALTERNATIVE_PROTOCOL),
+            _ => None,
+        }
+    }
+
+    /// How much a packet adds to the metric
+    fn amount(&self, summary: &PacketSummary, data: &[u8]) -> u64 {
+        let tcp_flags = summary.tcp_flags.unwrap_or(0);
+        let dns = |port: Option<u16>| {
+            if !summary.is_udp() || port != Some(53) {
+                return None;
+            }
+            let message = DnsMessage::parse(summary.payload(data)).ok()?;
+            Some((message.is_response(), message.rcode()))
+        };
+        let hit = match self {
+            Metric::Packets => true,
+            Metric::Bytes => return summary.length.max(summary.ip_end.unwrap_or(0)) as u64,
+            Metric::Connections => summary.is_tcp() && tcp_flags & 0x12 == 0x02,
+            Metric::Resets => summary.is_tcp() && tcp_flags & 0x04 != 0,
+            Metric::DnsQueries => dns(summary.dst_port).is_some_and(|(response, _)| !response),
+            Metric::Nxdomain => dns(summary.src_port) == Some((true, 3)),
+            Metric::Unreachable => match summary.ip_proto {
+                Some(1) => summary.payload(data).first() == Some(&3),
+                Some(58) => summary.payload(data).first() == Some(&1),
+                _ => false,
+            },
+        };
+        hit as u64
+    }
+}
+
+/// Which hosts share a counter
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    #[default]
+    Src,
+    Dst,
+    /// Source and destination together
+    Pair,
+    /// One counter for all traffic
+    Global,
+}
+
+/// Traffic a threshold is restricted to, by which side is on the internal network
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrafficDirection {
+    /// Internal source, external destination
+    Outbound,
+    /// External source, internal destination
+    Inbound,
+}
+
+/// A number or a string with a unit, such as `1GB` or `10m`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Quantity {
+    Number(u64),
+    Text(String),
+}
+
+/// A threshold from the configuration file (`[[alerts.thresholds]]`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThresholdConfig {
+    pub name: String,
+    pub metric: Metric,
+    /// Alert when the metric exceeds this within `window`; sizes take KB, MB, GB
+    pub threshold: Quantity,
+    /// Seconds, or a duration such as `1m` or `1h`
+    #[serde(default = "default_window")]
+    pub window: Quantity,
+    #[serde(default)]
+    pub per: GroupBy,
+    /// Display filter a packet must match to be counted
+    pub filter: Option<String>,
+    pub direction: Option<TrafficDirection>,
+    #[serde(default)]
+    pub severity: Severity,
+    /// Time before the same host can trigger the threshold again (defaults to the window)
+    pub cooldown: Option<Quantity>,
+    /// ATT&CK technique IDs for the alerts; defaults to the usual one for the metric
+    #[serde(default)]
+    pub techniques: Vec<String>,
+}
+
+fn default_window() -> Quantity {
+    Quantity::Number(60)
+}
+
+struct Threshold {
+    name: String,
+    metric: Metric,
+    limit: u64,
+    window: f64,
+    per: GroupBy,
+    filter: Option<DisplayFilter>,
+    direction: Option<TrafficDirection>,
+    severity: Severity,
+    cooldown: f64,
+    techniques: Vec<String>,
+}
+
+/// Compiled thresholds, shared by every capture thread and replaced on reload
+pub struct ThresholdSet {
+    thresholds: Vec<Threshold>,
+}
+
+impl ThresholdSet {
+    /// Validates every threshold, failing on the first bad one
+    pub fn compile(configs: &[ThresholdConfig]) -> Result<Self, CaptureError> {
+        let thresholds = configs
+            .iter()
+            .map(|config| {
+                let error = |e: CaptureError| CaptureError::InputError(format!("Threshold '{}': {}", config.name, e));
+                let seconds = |q: &Quantity| match q {
+                    Quantity::Number(n) => Ok(*n as f64),
+                    Quantity::Text(text) => parse_duration(text).map(|d| d.as_secs_f64()).map_err(error),
+                };
+                let limit = match &config.threshold {
+                    Quantity::Number(n) => *n,
+                    Quantity::Text(text) => parse_size(text.strip_suffix(['B', 'b']).unwrap_or(text)).map_err(error)?,
+                };
+                let window = seconds(&config.window)?;
+                if window <= 0.0 {
+                    return Err(error(CaptureError::InputError("window must be positive".to_string())));
+                }
+                Ok(Threshold {
+                    name: config.name.clone(),
+                    metric: config.metric,
+                    limit,
+                    window,
+                    per: config.per,
+                    filter: config.filter.as_deref().map(DisplayFilter::parse).transpose().map_err(error)?,
+                    direction: config.direction,
+                    severity: config.severity,
+                    cooldown: config.cooldown.as_ref().map_or(Ok(window), seconds)?,
+                    techniques: if config.techniques.is_empty() {
+                        config.metric.technique(config.direction).into_iter().map(str::to_string).collect()
+                    } else {
+                        attack::validate(&format!("Threshold '{}'", config.name), &config.techniques)?;
+                        config.techniques.clone()
+                    },
+                })
+            })
+            .collect::<Result<_, CaptureError>>()?;
+        Ok(ThresholdSet { thresholds })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.thresholds.is_empty()
+    }
+}
+
+/// A threshold that was exceeded
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThresholdAlert {
+    pub threshold: String,
+    pub severity: Severity,
+    /// The host, host pair or "all traffic" the count belongs to
+    pub group: String,
+    pub hosts: Vec<IpAddr>,
+    pub techniques: Vec<String>,
+    pub metric: Metric,
+    pub count: u64,
+    pub limit: u64,
+    pub window: f64,
+}
+
+impl fmt::Display for ThresholdAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] Threshold '{}' exceeded by {}: {} {} in {}s (limit {})",
+            self.severity,
+            self.threshold,
+            self.group,
+            self.count,
+            self.metric.name(),
+            self.window,
+            self.limit
+        )
+    }
+}
+
+/// A sliding window of `SLOTS` slots
+#[derive(Debug, Default)]
+struct Counter {
+    slots: [u64; SLOTS],
+    /// Absolute index of the newest slot
+    newest: i64,
+    last_alert: Option<f64>,
+}
+
+impl Counter {
+    /// Adds to the given slot and returns the window's total
+    fn add(&mut self, slot: i64, amount: u64) -> u64 {
+        if slot > self.newest {
+            let expired = (slot - self.newest).min(SLOTS as i64);
+            for i in 1..=expired {
+                self.slots[(self.newest + i).rem_euclid(SLOTS as i64) as usize] = 0;
+            }
+            self.newest = slot;
+        }
+        // Packets older than the window (out of order in merged captures) don't count
+        if slot > self.newest - SLOTS as i64 {
+            self.slots[slot.rem_euclid(SLOTS as i64) as usize] += amount;
+        }
+        self.slots.iter().sum()
+    }
+}
+
+type GroupKey = (Option<IpAddr>, Option<IpAddr>);
+
+/// Counts every threshold's metric per host and raises an alert when one is exceeded
+pub struct ThresholdEngine {
+    set: Arc<ThresholdSet>,
+    counters: Vec<HashMap<GroupKey, Counter>>,
+    /// Capture time of the last sweep of idle counters
+    swept: f64,
+}
+
+impl ThresholdEngine {
+    pub fn new(set: Arc<ThresholdSet>) -> Self {
+        let counters = set.thresholds.iter().map(|_| HashMap::new()).collect();
+        ThresholdEngine { set, counters, swept: 0.0 }
+    }
+
+    /// Switches to reloaded thresholds; counts start over
+    pub fn replace(&mut self, set: Arc<ThresholdSet>) {
+        *self = ThresholdEngine::new(set);
+    }
+
+    /// Alerts from the thresholds a packet seen at `time` (seconds) pushes over their limit
+    pub fn push(&mut self, summary: &PacketSummary, data: &[u8], time: f64) -> Vec<ThresholdAlert> {
+        self.sweep(time);
+        let mut alerts = Vec::new();
+        for (threshold, counters) in self.set.thresholds.iter().zip(&mut self.counters) {
+            let amount = threshold.metric.amount(summary, data);
+            if amount == 0 || !threshold.counts(summary) {
+                continue;
+            }
+            let key = match threshold.per {
+                GroupBy::Src => (summary.src_ip, None),
+                GroupBy::Dst => (None, summary.dst_ip),
+                GroupBy::Pair => (summary.src_ip, summary.dst_ip),
+                GroupBy::Global => (None, None),
+            };
+            let slot = (time * SLOTS as f64 / threshold.window).floor() as i64;
+            let counter = counters.entry(key).or_insert_with(|| Counter { newest: slot, ..Counter::default() });
+            let count = counter.add(slot, amount);
+            if count <= threshold.limit || counter.last_alert.is_some_and(|last| time - last < threshold.cooldown) {
+                continue;
+            }
+            counter.last_alert = Some(time);
+            alerts.push(ThresholdAlert {
+                threshold: threshold.name.clone(),
+                severity: threshold.severity,
+                group: group_name(key, threshold.per),
+                hosts: [key.0, key.1].into_iter().flatten().collect(),
+                techniques: threshold.techniques.clone(),
+                metric: threshold.metric,
+                count,
+                limit: threshold.limit,
+                window: threshold.window,
+            });
+        }
+        alerts
+    }
+
+    /// Forgets counters that have been idle for a whole window and are out of cooldown
+    fn sweep(&mut self, time: f64) {
+        if time - self.swept < 60.0 {
+            return;
+        }
+        self.swept = time;
+        for (threshold, counters) in self.set.thresholds.iter().zip(&mut self.counters) {
+            let slot = (time * SLOTS as f64 / threshold.window).floor() as i64;
+            counters.retain(|_, counter| {
+                slot - counter.newest < SLOTS as i64 || counter.last_alert.is_some_and(|last| time - last < threshold.cooldown)
+            });
+        }
+    }
+}
+
+impl Threshold {
+    fn counts(&self, summary: &PacketSummary) -> bool {
+        let internal = |ip: Option<IpAddr>| ip.map(is_internal);
+        let direction_ok = match self.direction {
+            None => true,
+            Some(TrafficDirection::Outbound) => internal(summary.src_ip) == Some(true) && internal(summary.dst_ip) == Some(false),
+            Some(TrafficDirection::Inbound) => internal(summary.src_ip) == Some(false) && internal(summary.dst_ip) == Some(true),
+        };
+        direction_ok && self.filter.as_ref().is_none_or(|f| f.matches(summary))
+    }
+}
+
+fn group_name(key: GroupKey, per: GroupBy) -> String {
+    let ip = |ip: Option<IpAddr>| ip.map_or("?".to_string(), |ip| ip.to_string());
+    match per {
+        GroupBy::Src => ip(key.0),
+        GroupBy::Dst => ip(key.1),
+        GroupBy::Pair => format!("{} -> {}", ip(key.0), ip(key.1)),
+        GroupBy::Global => "all traffic".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::testutil;
+
+    fn engine(toml: &str) -> ThresholdEngine {
+        let config = Config::parse(toml).unwrap();
+        ThresholdEngine::new(Arc::new(ThresholdSet::compile(&config.alerts.thresholds).unwrap()))
+    }
+
+    #[test]
+    fn alerts_once_per_cooldown_when_a_host_exceeds_a_rate() {
+        let mut engine = engine(
+            r#"
+            [[alerts.thresholds]]
+            name = "SYN flood"
+            metric = "connections"
+            threshold = 3
+            window = "1m"
+            cooldown = "5m"
+            severity = "high"
+            "#,
+        );
+        let syn = testutil::ipv4_tcp_frame(40000, 80, testutil::TCP_SYN, b"");
+        let summary = PacketSummary::from_ethernet(&syn).unwrap();
+        let counts: Vec<usize> = (0..6).map(|i| engine.push(&summary, &syn, 1000.0 + i as f64).len()).collect();
+        assert_eq!(counts, [0, 0, 0, 1, 0, 0]);
+        let alert = engine.push(&summary, &syn, 1400.0);
+        assert!(alert.is_empty(), "the window slid past the earlier SYNs");
+        let alert = engine.push(&summary, &syn, 1000.0 + 4.0);
+        assert!(alert.is_empty());
+
+        let syn_ack = testutil::ipv4_tcp_frame(40000, 80, testutil::TCP_SYN | testutil::TCP_ACK, b"");
+        assert_eq!(Metric::Connections.amount(&PacketSummary::from_ethernet(&syn_ack).unwrap(), &syn_ack), 0);
+    }
+
+    #[test]
+    fn counts_outbound_bytes_per_destination() {
+        let mut engine = engine(
+            r#"
+            [[alerts.thresholds]]
+            name = "upload"
+            metric = "bytes"
+            per = "dst"
+            direction = "outbound"
+            filter = "tcp"
+            threshold = "3KB"
+            window = "1h"
+            "#,
+        );
+        let frame = testutil::ipv4_tcp_frame(40000, 443, testutil::TCP_ACK, &[0; 1000]);
+        let summary = PacketSummary::from_ethernet(&frame).unwrap();
+        assert!(engine.push(&summary, &frame, 0.0).is_empty());
+        assert!(engine.push(&summary, &frame, 10.0).is_empty());
+        let alerts = engine.push(&summary, &frame, 20.0);
+        assert_eq!(
+            alerts[0].to_string(),
+            "[MEDIUM] Threshold 'upload' exceeded by 93.184.216.34: 3162 bytes in 3600s (limit 3072)"
+        );
+        assert_eq!(alerts[0].techniques, [attack::EXFILTRATION_OVER_ALTERNATIVE_PROTOCOL]);
+        let reply = testutil::frame().ipv4(testutil::server_v4(), testutil::client_v4()).tcp(443, 40000).payload(&[0; 4000]).build();
+        assert!(engine.push(&PacketSummary::from_ethernet(&reply).unwrap(), &reply, 30.0).is_empty());
+    }
+}
@@ -0,0 +1,345 @@
+use crate::alert::Severity;
+use crate::capfile::CaptureReader;
+use crate::error::CaptureError;
+use crate::flow::{FlowKey, FlowRecord, FlowTracker};
+use crate::meta::Direction;
+use crate::protocols::dns::DnsMessage;
+use crate::protocols::summary::PacketSummary;
+use crate::rules::{RuleEngine, RuleSet};
+use crate::thresholds::{ThresholdEngine, ThresholdSet};
+use crate::timestamp::format_rfc3339;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Idle time after which a conversation counts as finished
+const FLOW_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// What the timeline is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Focus {
+    Host { ip: IpAddr },
+    /// Both directions of one conversation
+    Flow { a: SocketAddr, b: SocketAddr },
+}
+
+impl Focus {
+    /// `10.0.0.5` for a host, `10.0.0.5:51000-203.0.113.7:443` (IPv6 in brackets) for a flow
+    pub fn parse(text: &str) -> Result<Self, CaptureError> {
+        let invalid = || CaptureError::InputError(format!("'{}' is neither an address nor a flow like 10.0.0.5:51000-203.0.113.7:443", text));
+        if let Ok(ip) = text.parse() {
+            return Ok(Focus::Host { ip });
+        }
+        let (a, b) = text.split_once('-').ok_or_else(invalid)?;
+        Ok(Focus::Flow { a: a.trim().parse().map_err(|_| invalid())?, b: b.trim().parse().map_err(|_| invalid())? })
+    }
+
+    fn matches(&self, summary: &PacketSummary) -> bool {
+        let (Some(src), Some(dst)) = (summary.src_ip, summary.dst_ip) else { return false };
+        match *self {
+            Focus::Host { ip } => src == ip || dst == ip,
+            Focus::Flow { a, b } => {
+                let (Some(sport), Some(dport)) = (summary.src_port, summary.dst_port) else { return false };
+                let (src, dst) = (SocketAddr::new(src, sport), SocketAddr::new(dst, dport));
+                (src, dst) == (a, b) || (src, dst) == (b, a)
+            }
+        }
+    }
+
+    /// The host whose point of view the narrative takes
+    fn subject(&self) -> IpAddr {
+        match self {
+            Focus::Host { ip } => *ip,
+            Focus::Flow { a, .. } => a.ip(),
+        }
+    }
+}
+
+impl fmt::Display for Focus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Focus::Host { ip } => write!(f, "host {}", ip),
+            Focus::Flow { a, b } => write!(f, "flow {} <-> {}", a, b),
+        }
+    }
+}
+
+/// Something that happened, from the point of view of the focus host
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    /// First packet exchanged with a peer
+    FirstContact { peer: IpAddr, outbound: bool },
+    DnsQuery { client: IpAddr, server: IpAddr, name: String },
+    DnsAnswer { server: IpAddr, name: String, addresses: Vec<IpAddr>, rcode: u8 },
+    /// First packet of a TCP connection or UDP conversation
+    Connection { protocol: String, client: SocketAddr, server: SocketAddr },
+    /// A conversation ended, with its totals
+    Transfer {
+        protocol: String,
+        client: SocketAddr,
+        server: SocketAddr,
+        bytes_sent: u64,
+        bytes_received: u64,
+        packets: u64,
+        duration: f64,
+        end: &'static str,
+    },
+    Alert { severity: Severity, message: String, techniques: Vec<String> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimelineEvent {
+    pub time: f64,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+impl fmt::Display for TimelineEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}  ", format_rfc3339(self.time))?;
+        match &self.kind {
+            EventKind::FirstContact { peer, outbound: true } => write!(f, "FIRST     first contact with {}", peer),
+            EventKind::FirstContact { peer, outbound: false } => write!(f, "FIRST     first contact from {}", peer),
+            EventKind::DnsQuery { client, server, name } => write!(f, "DNS       {} asked {} for {}", client, server, name),
+            EventKind::DnsAnswer { server, name, addresses, rcode: 0 } => {
+                let addresses: Vec<String> = addresses.iter().map(IpAddr::to_string).collect();
+                write!(f, "DNS       {} answered {} = {}", server, name, if addresses.is_empty() { "-".to_string() } else { addresses.join(", ") })
+            }
+            EventKind::DnsAnswer { server, name, rcode, .. } => {
+                write!(f, "DNS       {} answered {} with {}", server, name, if *rcode == 3 { "NXDOMAIN".to_string() } else { format!("rcode {}", rcode) })
+            }
+            EventKind::Connection { protocol, client, server } => write!(f, "CONNECT   {} {} -> {}", protocol, client, server),
+            EventKind::Transfer { protocol, client, server, bytes_sent, bytes_received, packets, duration, end } => write!(
+                f,
+                "TRANSFER  {} {} -> {}: {} bytes sent, {} received in {} packets over {:.1}s ({})",
+                protocol, client, server, bytes_sent, bytes_received, packets, duration, end
+            ),
+            EventKind::Alert { message, techniques, .. } if techniques.is_empty() => write!(f, "ALERT     {}", message),
+            EventKind::Alert { message, techniques, .. } => write!(f, "ALERT     {} [ATT&CK {}]", message, techniques.join(", ")),
+        }
+    }
+}
+
+/// The chronological story of a host or flow across one or more captures
+#[derive(Serialize)]
+pub struct Timeline {
+    pub focus: Focus,
+    pub packets: u64,
+    pub bytes: u64,
+    pub first_seen: Option<f64>,
+    pub last_seen: Option<f64>,
+    pub events: Vec<TimelineEvent>,
+    #[serde(skip)]
+    peers: HashSet<IpAddr>,
+    #[serde(skip)]
+    flows: FlowTracker,
+    #[serde(skip)]
+    rules: Option<RuleEngine>,
+    #[serde(skip)]
+    thresholds: Option<ThresholdEngine>,
+}
+
+impl Timeline {
+    /// A timeline that also evaluates the configured alert rules and thresholds
+    /// on the focus traffic
+    pub fn new(focus: Focus, rules: Option<Arc<RuleSet>>, thresholds: Option<Arc<ThresholdSet>>) -> Self {
+        Timeline {
+            focus,
+            packets: 0,
+            bytes: 0,
+            first_seen: None,
+            last_seen: None,
+            events: Vec::new(),
+            peers: HashSet::new(),
+            flows: FlowTracker::new(FLOW_TIMEOUT),
+            rules: rules.map(RuleEngine::new),
+            thresholds: thresholds.map(ThresholdEngine::new),
+        }
+    }
+
+    /// Feeds one packet of the given datalink captured at `time`
+    pub fn push(&mut self, datalink: i32, data: &[u8], time: f64) {
+        let Some(summary) = PacketSummary::from_link(datalink, data) else { return };
+        if !self.focus.matches(&summary) {
+            return;
+        }
+        let (Some(src), Some(dst)) = (summary.src_ip, summary.dst_ip) else { return };
+        self.packets += 1;
+        self.bytes += summary.length as u64;
+        self.first_seen.get_or_insert(time);
+        self.last_seen = Some(time);
+
+        let subject = self.focus.subject();
+        let outbound = src == subject;
+        let peer = if outbound { dst } else { src };
+        if self.peers.insert(peer) {
+            self.event(time, EventKind::FirstContact { peer, outbound });
+        }
+        if summary.is_udp() && summary.has_port(53) {
+            self.dns(&summary, data, time);
+        }
+        if let Some(key) = FlowKey::from_summary(&summary)
+            && (summary.is_tcp() || summary.is_udp())
+            && self.flows.flow(&key).is_none()
+            && let (Some(sport), Some(dport)) = (summary.src_port, summary.dst_port)
+        {
+            let (client, server) = (SocketAddr::new(src, sport), SocketAddr::new(dst, dport));
+            self.event(time, EventKind::Connection { protocol: key.protocol_name().to_uppercase(), client, server });
+        }
+        for flow in self.flows.push(&summary, time) {
+            self.transfer(&flow);
+        }
+        let mut alerts = Vec::new();
+        if let Some(rules) = &mut self.rules {
+            alerts.extend(rules.push(&summary, time, Direction::Unknown).into_iter().map(|a| (a.severity, a.to_string(), a.techniques)));
+        }
+        if let Some(thresholds) = &mut self.thresholds {
+            alerts.extend(thresholds.push(&summary, data, time).into_iter().map(|a| (a.severity, a.to_string(), a.techniques)));
+        }
+        for (severity, message, techniques) in alerts {
+            self.event(time, EventKind::Alert { severity, message, techniques });
+        }
+    }
+
+    fn dns(&mut self, summary: &PacketSummary, data: &[u8], time: f64) {
+        let Ok(message) = DnsMessage::parse(summary.payload(data)) else { return };
+        let (Some(src), Some(dst)) = (summary.src_ip, summary.dst_ip) else { return };
+        let Some(name) = message.questions().into_iter().next().map(|question| question.name) else { return };
+        let event = if message.is_response() {
+            let addresses = message.answers().iter().filter_map(|answer| answer.data.address()).collect();
+            EventKind::DnsAnswer { server: src, name, addresses, rcode: message.rcode() }
+        } else {
+            EventKind::DnsQuery { client: src, server: dst, name }
+        };
+        self.event(time, event);
+    }
+
+    fn transfer(&mut self, flow: &FlowRecord) {
+        let responder = if flow.initiator == flow.key.a { flow.key.b } else { flow.key.a };
+        self.event(
+            flow.last,
+            EventKind::Transfer {
+                protocol: flow.key.protocol_name().to_uppercase(),
+                client: SocketAddr::new(flow.initiator.0, flow.initiator.1),
+                server: SocketAddr::new(responder.0, responder.1),
+                bytes_sent: flow.bytes_out,
+                bytes_received: flow.bytes - flow.bytes_out,
+                packets: flow.packets,
+                duration: flow.last - flow.first,
+                end: flow.end.name(),
+            },
+        );
+    }
+
+    fn event(&mut self, time: f64, kind: EventKind) {
+        self.events.push(TimelineEvent { time, kind });
+    }
+
+    /// Closes the conversations still open and puts the events in time order
+    pub fn finish(&mut self) {
+        for flow in self.flows.finish() {
+            self.transfer(&flow);
+        }
+        // Stable, so events of one packet keep their order
+        self.events.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+
+    /// The narrative as text: a summary line, then one line per event
+    pub fn to_text(&self) -> String {
+        let mut text = match (self.first_seen, self.last_seen) {
+            (Some(first), Some(last)) => format!(
+                "Timeline of {}: {} packets, {} bytes, {} peer(s), from {} to {}\n",
+                self.focus,
+                self.packets,
+                self.bytes,
+                self.peers.len(),
+                format_rfc3339(first),
+                format_rfc3339(last)
+            ),
+            _ => return format!("No traffic for {}\n", self.focus),
+        };
+        for event in &self.events {
+            text.push_str(&event.to_string());
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Reads capture files in the order given and builds the timeline of `focus`
+pub fn build_timeline(
+    files: &[impl AsRef<Path>],
+    focus: Focus,
+    rules: Option<Arc<RuleSet>>,
+    thresholds: Option<Arc<ThresholdSet>>,
+) -> Result<Timeline, CaptureError> {
+    let mut timeline = Timeline::new(focus, rules, thresholds);
+    for file in files {
+        let reader = CaptureReader::open(file.as_ref())?;
+        let datalink = reader.linktype().0;
+        for packet in reader {
+            timeline.push(datalink, &packet.data, packet.timestamp().as_secs_f64());
+        }
+    }
+    timeline.finish();
+    Ok(timeline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn tells_what_a_host_did() {
+        let (client, server) = (testutil::client_v4(), testutil::server_v4());
+        let mut timeline = Timeline::new(Focus::parse("192.168.1.10").unwrap(), None, None);
+        timeline.push(1, &testutil::dns_query_frame(7, "example.com"), 10.0);
+        let answer = testutil::dns_response(7, "example.com", &[server]);
+        timeline.push(1, &testutil::frame().ipv4(server, client).udp(53, 53000).payload(&answer).build(), 10.1);
+        timeline.push(1, &testutil::ipv4_tcp_frame(40000, 443, testutil::TCP_SYN, b""), 10.2);
+        timeline.push(1, &testutil::frame().ipv4(server, client).tcp(443, 40000).payload(&[0; 100]).build(), 10.3);
+        timeline.push(1, &testutil::frame().ipv4(server, Ipv4Addr::new(10, 9, 9, 9)).tcp(443, 1).build(), 10.4);
+        timeline.finish();
+
+        let text = timeline.to_text();
+        assert_eq!(
+            text.lines().next(),
+            Some("Timeline of host 192.168.1.10: 4 packets, 366 bytes, 1 peer(s), from 1970-01-01T00:00:10.000000Z to 1970-01-01T00:00:10.300000Z")
+        );
+        let lines: Vec<&str> = text.lines().skip(1).map(|line| line.split_once("  ").unwrap().1).collect();
+        assert_eq!(
+            lines,
+            [
+                "FIRST     first contact with 93.184.216.34",
+                "DNS       192.168.1.10 asked 93.184.216.34 for example.com",
+                "CONNECT   UDP 192.168.1.10:53000 -> 93.184.216.34:53",
+                "DNS       93.184.216.34 answered example.com = 93.184.216.34",
+                "TRANSFER  UDP 192.168.1.10:53000 -> 93.184.216.34:53: 71 bytes sent, 87 received in 2 packets over 0.1s (end)",
+                "CONNECT   TCP 192.168.1.10:40000 -> 93.184.216.34:443",
+                "TRANSFER  TCP 192.168.1.10:40000 -> 93.184.216.34:443: 54 bytes sent, 154 received in 2 packets over 0.1s (end)",
+            ]
+        );
+        assert_eq!(timeline.packets, 4);
+        let json = serde_json::to_value(&timeline).unwrap();
+        assert_eq!(json["focus"], serde_json::json!({ "type": "host", "ip": "192.168.1.10" }));
+        assert_eq!(json["events"][3]["event"], "dns_answer");
+        assert_eq!(json["events"][3]["addresses"][0], "93.184.216.34");
+    }
+
+    #[test]
+    fn parses_focus() {
+        assert_eq!(
+            Focus::parse("[2001:db8::1]:443-10.0.0.5:51000").unwrap().to_string(),
+            "flow [2001:db8::1]:443 <-> 10.0.0.5:51000"
+        );
+        assert!(Focus::parse("example.com").is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use crate::error::CaptureError;
+use std::time::Duration;
+
+/// UTC calendar date (year, month, day) for a number of days since the Unix epoch
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Days since the Unix epoch for a UTC calendar date
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// `YYYY-MM-DD` for a Unix timestamp in seconds
+pub fn format_date(secs: i64) -> String {
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// RFC 3339 UTC date-time with microseconds for a Unix timestamp in seconds
+pub fn format_rfc3339(secs: f64) -> String {
+    let micros = (secs * 1e6).round() as i64;
+    let (whole, micros) = (micros.div_euclid(1_000_000), micros.rem_euclid(1_000_000));
+    let time = whole.rem_euclid(86_400);
+    format!("{}T{:02}:{:02}:{:02}.{:06}Z", format_date(whole), time / 3600, time / 60 % 60, time % 60, micros)
+}
+
+/// ISO 8601 date-time at a UTC offset in seconds, `Z` for UTC, with
+/// microseconds or nanoseconds
+pub fn format_iso8601(since_epoch: Duration, offset: i64, nanos: bool) -> String {
+    let whole = since_epoch.as_secs() as i64 + offset;
+    let time = whole.rem_euclid(86_400);
+    let fraction = if nanos { format!("{:09}", since_epoch.subsec_nanos()) } else { format!("{:06}", since_epoch.subsec_micros()) };
+    let zone = match offset {
+        0 => "Z".to_string(),
+        _ => format!("{}{:02}:{:02}", if offset < 0 { '-' } else { '+' }, offset.abs() / 3600, offset.abs() / 60 % 60),
+    };
+    format!("{}T{:02}:{:02}:{:02}.{}{}", format_date(whole), time / 3600, time / 60 % 60, time % 60, fraction, zone)
+}
+
+/// Offset of the system time zone from UTC at a Unix timestamp, in seconds
+pub fn local_offset(secs: i64) -> i64 {
+    let time = secs as libc::time_t;
+    // SAFETY: localtime_r only writes to the tm it is given
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+/// Parses a point in time as Unix seconds (`1700000000.25`) or as a UTC
+/// date-time (`2023-11-14T22:13:20Z`, `2023-11-14 22:13:20.5`, `2023-11-14`)
+pub fn parse_timestamp(text: &str) -> Result<Duration, CaptureError> {
+    let text = text.trim();
+    let invalid = || CaptureError::InputError(format!("Invalid timestamp '{}'", text));
+
+    if let Ok(secs) = text.parse::<f64>() {
+        return Duration::try_from_secs_f64(secs).map_err(|_| invalid());
+    }
+
+    let text_utc = text.strip_suffix('Z').unwrap_or(text);
+    let (date, time) = match text_utc.split_once(['T', ' ']) {
+        Some((date, time)) => (date, time),
+        None => (text_utc, "00:00:00"),
+    };
+    let date: Vec<&str> = date.split('-').collect();
+    let time: Vec<&str> = time.split(':').collect();
+    if date.len() != 3 || !(2..=3).contains(&time.len()) {
+        return Err(invalid());
+    }
+    let year: i64 = date[0].parse().map_err(|_| invalid())?;
+    let month: u32 = date[1].parse().map_err(|_| invalid())?;
+    let day: u32 = date[2].parse().map_err(|_| invalid())?;
+    let hour: u64 = time[0].parse().map_err(|_| invalid())?;
+    let minute: u64 = time[1].parse().map_err(|_| invalid())?;
+    let second: f64 = time.get(2).map_or(Ok(0.0), |s| s.parse()).map_err(|_| invalid())?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || !(0.0..61.0).contains(&second) {
+        return Err(invalid());
+    }
+
+    let days = days_from_civil(year, month, day);
+    if days < 0 {
+        return Err(invalid());
+    }
+    let whole = days as u64 * 86_400 + hour * 3600 + minute * 60;
+    Ok(Duration::from_secs(whole) + Duration::from_secs_f64(second))
+}
+
+/// Parses a length of time as seconds (`90`) or with a unit (`30s`, `5m`, `1h`, `7d`)
+pub fn parse_duration(text: &str) -> Result<Duration, CaptureError> {
+    let text = text.trim();
+    let (digits, unit) = match text.char_indices().last() {
+        Some((i, 's')) => (&text[..i], 1),
+        Some((i, 'm')) => (&text[..i], 60),
+        Some((i, 'h')) => (&text[..i], 3600),
+        Some((i, 'd')) => (&text[..i], 86_400),
+        _ => (text, 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .map(Duration::from_secs)
+        .ok_or_else(|| CaptureError::InputError(format!("Invalid duration '{}'", text)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_dates_both_ways() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+        assert_eq!(days_from_civil(2000, 2, 29), 11_016);
+        assert_eq!(civil_from_days(days_from_civil(2023, 11, 14)), (2023, 11, 14));
+        assert_eq!(format_rfc3339(1_700_000_000.25), "2023-11-14T22:13:20.250000Z");
+        let time = Duration::new(1_700_000_000, 250_000_001);
+        assert_eq!(format_iso8601(time, 0, false), "2023-11-14T22:13:20.250000Z");
+        assert_eq!(format_iso8601(time, -5 * 3600 - 1800, true), "2023-11-14T16:43:20.250000001-05:30");
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_timestamp("1700000000").unwrap(), Duration::from_secs(1_700_000_000));
+        assert_eq!(parse_timestamp("1700000000.5").unwrap(), Duration::from_millis(1_700_000_000_500));
+        assert_eq!(parse_timestamp("2023-11-14T22:13:20Z").unwrap(), Duration::from_secs(1_700_000_000));
+        assert_eq!(parse_timestamp("2023-11-14 22:13:20.25").unwrap(), Duration::from_millis(1_700_000_000_250));
+        assert_eq!(parse_timestamp("2023-11-14").unwrap(), Duration::from_secs(1_699_920_000));
+        assert!(parse_timestamp("2023-13-01").is_err());
+        assert!(parse_timestamp("yesterday").is_err());
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+        assert!(parse_duration("1w").is_err());
+    }
+}
@@ -0,0 +1,68 @@
+/// Packets cut short by the snapshot length (`caplen < len`) over a capture
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TruncationStats {
+    pub packets: u64,
+    pub truncated: u64,
+    /// Bytes seen on the wire but not captured
+    pub missing_bytes: u64,
+    /// Largest captured length of a truncated packet: the snaplen in effect
+    pub snaplen: u32,
+    /// Largest wire length of a truncated packet
+    pub largest: u32,
+}
+
+impl TruncationStats {
+    pub fn push(&mut self, caplen: u32, len: u32) {
+        self.packets += 1;
+        if caplen < len {
+            self.truncated += 1;
+            self.missing_bytes += (len - caplen) as u64;
+            self.snaplen = self.snaplen.max(caplen);
+            self.largest = self.largest.max(len);
+        }
+    }
+
+    /// Share of packets truncated, 0-100
+    pub fn percentage(&self) -> f64 {
+        if self.packets == 0 {
+            return 0.0;
+        }
+        self.truncated as f64 * 100.0 / self.packets as f64
+    }
+
+    /// A warning suggesting a snaplen that fits every packet, if any was truncated
+    pub fn warning(&self) -> Option<String> {
+        (self.truncated > 0).then(|| {
+            format!(
+                "{} of {} packets ({:.1}%) were truncated to {} bytes and {} bytes were not captured; \
+                 fields past the cut show as <truncated>. Capture with --snaplen {} or larger to keep whole packets",
+                self.truncated,
+                self.packets,
+                self.percentage(),
+                self.snaplen,
+                self.missing_bytes,
+                self.largest
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_truncated_packets() {
+        let mut stats = TruncationStats::default();
+        assert_eq!(stats.warning(), None);
+        stats.push(60, 60);
+        stats.push(96, 1514);
+        stats.push(96, 9014);
+        stats.push(40, 40);
+        assert_eq!((stats.truncated, stats.missing_bytes, stats.snaplen), (2, 1418 + 8918, 96));
+        assert_eq!(stats.percentage(), 50.0);
+        let warning = stats.warning().unwrap();
+        assert!(warning.starts_with("2 of 4 packets (50.0%) were truncated to 96 bytes"));
+        assert!(warning.ends_with("--snaplen 9014 or larger to keep whole packets"));
+    }
+}
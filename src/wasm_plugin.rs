@@ -0,0 +1,212 @@
+use crate::error::CaptureError;
+use crate::plugin::{Dissector, Registration};
+use crate::protocols::frame_control::ControlField;
+use log::warn;
+use std::path::Path;
+use std::sync::Mutex;
+use wasmtime::{Caller, Config, Engine, Extern, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+/// Host API module the guest imports from
+pub const HOST_MODULE: &str = "sniffer";
+
+/// Instructions a plugin may spend on one packet before it is stopped
+pub const FUEL_PER_PACKET: u64 = 10_000_000;
+/// Largest linear memory a plugin may grow to
+pub const MAX_MEMORY: usize = 64 * 1024 * 1024;
+
+/// What the host functions can see and fill in during a call
+struct HostState {
+    payload: Vec<u8>,
+    fields: Vec<ControlField>,
+    alerts: Vec<String>,
+    registrations: Vec<Registration>,
+    limits: StoreLimits,
+}
+
+/// A dissector compiled to WebAssembly and run in a wasmtime sandbox. The
+/// guest only reaches the host through the `sniffer` imports:
+///
+/// * `register(kind: i32, value: i32)`: 0 TCP port, 1 UDP port, 2 EtherType
+/// * `payload_len() -> i32` and `read_payload(ptr: i32, len: i32) -> i32`
+/// * `emit_field(name_ptr, name_len, value_ptr, value_len, description_ptr, description_len)`
+/// * `emit_alert(ptr: i32, len: i32)`
+///
+/// It exports `memory`, `init()`, which registers its ports, and `dissect()`,
+/// called once per matching packet. Each call runs on a fuel budget and a
+/// memory cap, so a buggy or hostile plugin can only fail its own packet.
+pub struct WasmDissector {
+    name: String,
+    registrations: Vec<Registration>,
+    runtime: Mutex<(Store<HostState>, TypedFunc<(), ()>)>,
+}
+
+impl WasmDissector {
+    /// Compiles and instantiates a `.wasm` (or `.wat`) plugin and runs its `init`
+    pub fn load(path: &Path) -> Result<Self, CaptureError> {
+        let error = |what: String| CaptureError::InputError(format!("WASM plugin {}: {}", path.display(), what));
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(|e| error(e.to_string()))?;
+        let module = Module::from_file(&engine, path).map_err(|e| error(e.to_string()))?;
+
+        let state = HostState {
+            payload: Vec::new(),
+            fields: Vec::new(),
+            alerts: Vec::new(),
+            registrations: Vec::new(),
+            limits: StoreLimitsBuilder::new().memory_size(MAX_MEMORY).instances(1).build(),
+        };
+        let mut store = Store::new(&engine, state);
+        store.limiter(|state| &mut state.limits);
+        let instance = host_api(&engine)
+            .and_then(|linker| linker.instantiate(&mut store, &module))
+            .map_err(|e| error(e.to_string()))?;
+
+        let init = instance.get_typed_func::<(), ()>(&mut store, "init").map_err(|e| error(e.to_string()))?;
+        store.set_fuel(FUEL_PER_PACKET).map_err(|e| error(e.to_string()))?;
+        init.call(&mut store, ()).map_err(|e| error(format!("init failed: {}", e)))?;
+        let dissect = dissect_function(&instance, &mut store).map_err(error)?;
+
+        let registrations = std::mem::take(&mut store.data_mut().registrations);
+        let name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        Ok(WasmDissector { name, registrations, runtime: Mutex::new((store, dissect)) })
+    }
+}
+
+fn dissect_function(instance: &Instance, store: &mut Store<HostState>) -> Result<TypedFunc<(), ()>, String> {
+    if instance.get_memory(&mut *store, "memory").is_none() {
+        return Err("does not export its memory".to_string());
+    }
+    instance.get_typed_func::<(), ()>(store, "dissect").map_err(|e| e.to_string())
+}
+
+/// Guest memory as a string, or `None` when the range is out of bounds
+fn guest_string(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> Option<String> {
+    let Some(Extern::Memory(memory)) = caller.get_export("memory") else { return None };
+    let bytes = memory.data(&caller).get(ptr as u32 as usize..(ptr as u32 as usize).checked_add(len as u32 as usize)?)?;
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn host_api(engine: &Engine) -> wasmtime::Result<Linker<HostState>> {
+    let mut linker = Linker::new(engine);
+    linker.func_wrap(HOST_MODULE, "register", |mut caller: Caller<'_, HostState>, kind: i32, value: i32| {
+        let value = value as u16;
+        let registration = match kind {
+            0 => Registration::TcpPort(value),
+            1 => Registration::UdpPort(value),
+            2 => Registration::EtherType(value),
+            _ => return,
+        };
+        caller.data_mut().registrations.push(registration);
+    })?;
+    linker.func_wrap(HOST_MODULE, "payload_len", |caller: Caller<'_, HostState>| caller.data().payload.len() as i32)?;
+    linker.func_wrap(HOST_MODULE, "read_payload", |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> i32 {
+        let Some(Extern::Memory(memory)) = caller.get_export("memory") else { return -1 };
+        let (memory, state) = memory.data_and_store_mut(&mut caller);
+        let n = (len.max(0) as usize).min(state.payload.len());
+        let start = ptr as u32 as usize;
+        match memory.get_mut(start..start + n) {
+            Some(dst) => {
+                dst.copy_from_slice(&state.payload[..n]);
+                n as i32
+            }
+            None => -1,
+        }
+    })?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "emit_field",
+        |mut caller: Caller<'_, HostState>, name: i32, name_len: i32, value: i32, value_len: i32, description: i32, description_len: i32| {
+            let field = (
+                guest_string(&mut caller, name, name_len),
+                guest_string(&mut caller, value, value_len),
+                guest_string(&mut caller, description, description_len),
+            );
+            if let (Some(name), Some(value), Some(description)) = field {
+                caller.data_mut().fields.push(ControlField { name, value, description });
+            }
+        },
+    )?;
+    linker.func_wrap(HOST_MODULE, "emit_alert", |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+        if let Some(alert) = guest_string(&mut caller, ptr, len) {
+            caller.data_mut().alerts.push(alert);
+        }
+    })?;
+    Ok(linker)
+}
+
+impl Dissector for WasmDissector {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn registrations(&self) -> Vec<Registration> {
+        self.registrations.clone()
+    }
+
+    /// Runs the guest on one payload; alerts are logged and shown as fields
+    fn dissect(&self, payload: &[u8]) -> Vec<ControlField> {
+        let Ok(mut runtime) = self.runtime.lock() else { return Vec::new() };
+        let (store, dissect) = &mut *runtime;
+        store.data_mut().payload = payload.to_vec();
+        let result = store.set_fuel(FUEL_PER_PACKET).and_then(|()| dissect.call(&mut *store, ()));
+        let state = store.data_mut();
+        let mut fields = std::mem::take(&mut state.fields);
+        for alert in std::mem::take(&mut state.alerts) {
+            warn!("[{}] {}", self.name, alert);
+            fields.push(ControlField { name: "Plugin Alert".to_string(), value: alert, description: self.name.clone() });
+        }
+        if let Err(e) = result {
+            fields.push(ControlField {
+                name: "Plugin Error".to_string(),
+                value: e.to_string(),
+                description: "The plugin was stopped on this packet".to_string(),
+            });
+        }
+        fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLUGIN: &str = r#"
+        (module
+          (import "sniffer" "register" (func $register (param i32 i32)))
+          (import "sniffer" "payload_len" (func $payload_len (result i32)))
+          (import "sniffer" "read_payload" (func $read_payload (param i32 i32) (result i32)))
+          (import "sniffer" "emit_field" (func $emit_field (param i32 i32 i32 i32 i32 i32)))
+          (import "sniffer" "emit_alert" (func $emit_alert (param i32 i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "Echo")
+          (data (i32.const 8) "payload")
+          (data (i32.const 16) "spin")
+          (func (export "init") (call $register (i32.const 1) (i32.const 7)))
+          (func (export "dissect") (local $n i32)
+            (local.set $n (call $read_payload (i32.const 64) (call $payload_len)))
+            (if (i32.eq (i32.load8_u (i32.const 64)) (i32.const 33))
+              (then
+                (call $emit_alert (i32.const 16) (i32.const 4))
+                (loop $forever (br $forever))))
+            (call $emit_field (i32.const 0) (i32.const 4) (i32.const 64) (local.get $n) (i32.const 8) (i32.const 7))))
+    "#;
+
+    #[test]
+    fn runs_guest_within_budget() {
+        let path = std::env::temp_dir().join(format!("rust-sniffer-echo-{}.wat", std::process::id()));
+        std::fs::write(&path, PLUGIN).unwrap();
+        let plugin = WasmDissector::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(plugin.registrations(), vec![Registration::UdpPort(7)]);
+
+        let fields = plugin.dissect(b"hello");
+        assert_eq!((fields[0].name.as_str(), fields[0].value.as_str()), ("Echo", "hello"));
+
+        // An endless loop runs out of fuel and only costs its own packet
+        let fields = plugin.dissect(b"!");
+        assert_eq!(fields[0].name, "Plugin Alert");
+        assert_eq!(fields[1].name, "Plugin Error");
+        assert_eq!(plugin.dissect(b"again")[0].value, "again");
+    }
+}
@@ -0,0 +1,220 @@
+use crate::error::CaptureError;
+use crate::follow::{tcp_streams, StreamInfo};
+use crate::protocols::websocket::{MessageAssembler, WebSocketFrame, WebSocketMessage};
+use crate::reassembly::{Side, StreamChunk};
+use std::path::Path;
+use std::time::Duration;
+
+/// The HTTP/1.1 Upgrade request that opened a WebSocket
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Handshake {
+    pub path: String,
+    pub host: Option<String>,
+    /// Sec-WebSocket-Protocol offered by the client
+    pub protocol: Option<String>,
+    /// Sec-WebSocket-Extensions accepted by the server (e.g. permessage-deflate)
+    pub extensions: Option<String>,
+}
+
+/// A message on an upgraded connection, in capture order
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionMessage {
+    pub side: Side,
+    pub ts: Duration,
+    pub message: WebSocketMessage,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebSocketSession {
+    pub stream: StreamInfo,
+    pub handshake: Handshake,
+    pub messages: Vec<SessionMessage>,
+}
+
+/// HTTP header fields with lowercased names
+type Headers = Vec<(String, String)>;
+
+/// Splits an HTTP head off the front of `data`, returning the start line,
+/// headers (names lowercased) and the bytes after the blank line
+fn http_head(data: &[u8]) -> Option<(String, Headers, &[u8])> {
+    let end = data.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let head = String::from_utf8_lossy(&data[..end]);
+    let mut lines = head.split("\r\n");
+    let start = lines.next()?.to_string();
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    Some((start, headers, &data[end + 4..]))
+}
+
+fn header<'h>(headers: &'h [(String, String)], name: &str) -> Option<&'h str> {
+    headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+}
+
+/// Parses a client Upgrade request, returning the handshake and the bytes after it
+pub fn parse_upgrade_request(data: &[u8]) -> Option<(Handshake, &[u8])> {
+    let (start, headers, rest) = http_head(data)?;
+    let mut parts = start.split_whitespace();
+    if parts.next() != Some("GET") {
+        return None;
+    }
+    let path = parts.next()?.to_string();
+    if !header(&headers, "upgrade").is_some_and(|u| u.eq_ignore_ascii_case("websocket")) {
+        return None;
+    }
+    let handshake = Handshake {
+        path,
+        host: header(&headers, "host").map(str::to_string),
+        protocol: header(&headers, "sec-websocket-protocol").map(str::to_string),
+        extensions: None,
+    };
+    Some((handshake, rest))
+}
+
+/// Parses a `101 Switching Protocols` response, returning the accepted
+/// extensions and the bytes after it
+pub fn parse_upgrade_response(data: &[u8]) -> Option<(Option<String>, &[u8])> {
+    let (start, headers, rest) = http_head(data)?;
+    let mut parts = start.split_whitespace();
+    if !parts.next()?.starts_with("HTTP/1.") || parts.next() != Some("101") {
+        return None;
+    }
+    Some((header(&headers, "sec-websocket-extensions").map(str::to_string), rest))
+}
+
+/// Per-direction frame decoding state
+#[derive(Default)]
+struct Direction {
+    buffer: Vec<u8>,
+    upgraded: bool,
+    assembler: MessageAssembler,
+}
+
+impl Direction {
+    /// Parses every complete frame in the buffer
+    fn drain(&mut self, side: Side, ts: Duration, out: &mut Vec<SessionMessage>) {
+        let mut consumed = 0;
+        while let Ok(frame) = WebSocketFrame::parse(&self.buffer[consumed..]) {
+            consumed += frame.len();
+            if let Some(message) = self.assembler.push(&frame) {
+                out.push(SessionMessage { side, ts, message });
+            }
+        }
+        self.buffer.drain(..consumed);
+    }
+}
+
+/// Finds the WebSocket handshake in a TCP conversation and decodes the
+/// messages that follow it in both directions
+pub fn dissect_session(stream: StreamInfo, chunks: &[StreamChunk]) -> Option<WebSocketSession> {
+    let mut client = Direction::default();
+    let mut server = Direction::default();
+    let mut handshake = None;
+    let mut messages = Vec::new();
+
+    for chunk in chunks {
+        let direction = match chunk.side {
+            Side::Client => &mut client,
+            Side::Server => &mut server,
+        };
+        direction.buffer.extend_from_slice(&chunk.data);
+        if !direction.upgraded {
+            let rest = match chunk.side {
+                Side::Client => parse_upgrade_request(&direction.buffer).map(|(h, rest)| {
+                    handshake = Some(h);
+                    rest.to_vec()
+                }),
+                Side::Server if handshake.is_some() => parse_upgrade_response(&direction.buffer).map(|(ext, rest)| {
+                    if let Some(h) = handshake.as_mut() {
+                        h.extensions = ext;
+                    }
+                    rest.to_vec()
+                }),
+                Side::Server => None,
+            };
+            match rest {
+                Some(rest) => {
+                    direction.buffer = rest;
+                    direction.upgraded = true;
+                }
+                // Give up on conversations that clearly aren't an upgrade
+                None if direction.buffer.len() > 16 * 1024 => return None,
+                None => continue,
+            }
+        }
+        direction.drain(chunk.side, chunk.ts, &mut messages);
+    }
+
+    // Client frames sent before the 101 arrived are only trusted once the server agreed
+    if !server.upgraded {
+        return None;
+    }
+    Some(WebSocketSession { stream, handshake: handshake?, messages })
+}
+
+/// Decodes every WebSocket session in a capture file
+pub fn websocket_sessions(input: &Path) -> Result<Vec<WebSocketSession>, CaptureError> {
+    Ok(tcp_streams(input)?
+        .into_iter()
+        .filter_map(|(info, reassembler)| dissect_session(info, reassembler.chunks()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::FlowKey;
+    use crate::protocols::websocket::{OPCODE_BINARY, OPCODE_CONTINUATION, OPCODE_TEXT};
+    use crate::testutil::websocket_frame;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn info() -> StreamInfo {
+        let a = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 40000);
+        let b = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 80);
+        StreamInfo { index: 0, key: FlowKey::new(6, a, b), packets: 0, bytes: 0 }
+    }
+
+    fn chunk(side: Side, ms: u64, data: Vec<u8>) -> StreamChunk {
+        StreamChunk { side, ts: Duration::from_millis(ms), data }
+    }
+
+    #[test]
+    fn decodes_messages_after_upgrade() {
+        let request = b"GET /chat HTTP/1.1\r\nHost: example.com\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Protocol: chat\r\n\r\n";
+        let response = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nSec-WebSocket-Extensions: permessage-deflate\r\n\r\n";
+        let mut first = request.to_vec();
+        first.extend(websocket_frame(false, OPCODE_TEXT, Some([9, 8, 7, 6]), b"hel"));
+        let mut reply = response.to_vec();
+        reply.extend(websocket_frame(true, OPCODE_BINARY, None, &[1, 2, 3]));
+        let split = websocket_frame(true, OPCODE_CONTINUATION, Some([1, 1, 1, 1]), b"lo");
+
+        let chunks = vec![
+            chunk(Side::Client, 0, first),
+            chunk(Side::Server, 5, reply),
+            chunk(Side::Client, 10, split[..3].to_vec()),
+            chunk(Side::Client, 11, split[3..].to_vec()),
+        ];
+        let session = dissect_session(info(), &chunks).unwrap();
+        assert_eq!(session.handshake.path, "/chat");
+        assert_eq!(session.handshake.host.as_deref(), Some("example.com"));
+        assert_eq!(session.handshake.protocol.as_deref(), Some("chat"));
+        assert_eq!(session.handshake.extensions.as_deref(), Some("permessage-deflate"));
+
+        assert_eq!(session.messages.len(), 2);
+        assert_eq!(session.messages[0].side, Side::Server);
+        assert_eq!(session.messages[1].message.text_preview(80).as_deref(), Some("hello"));
+        assert_eq!(session.messages[1].ts, Duration::from_millis(11));
+    }
+
+    #[test]
+    fn ignores_refused_upgrades_and_plain_http() {
+        let request = b"GET /chat HTTP/1.1\r\nUpgrade: websocket\r\n\r\n".to_vec();
+        let refused = b"HTTP/1.1 403 Forbidden\r\n\r\n".to_vec();
+        let chunks = vec![chunk(Side::Client, 0, request), chunk(Side::Server, 1, refused)];
+        assert!(dissect_session(info(), &chunks).is_none());
+
+        let plain = vec![chunk(Side::Client, 0, b"GET / HTTP/1.1\r\nHost: x\r\n\r\n".to_vec())];
+        assert!(dissect_session(info(), &plain).is_none());
+    }
+}
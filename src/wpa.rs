@@ -0,0 +1,297 @@
+use crate::error::CaptureError;
+use crate::protocols::ethernet::MacAddress;
+use crate::protocols::quic::hex;
+use crate::protocols::radiotap::RadiotapHeader;
+use crate::protocols::wifi::{WifiFrame, WifiFrameType};
+use log::info;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// DLT_IEEE802_11 and DLT_IEEE802_11_RADIO
+pub fn is_wifi_datalink(datalink: i32) -> bool {
+    matches!(datalink, 105 | 127)
+}
+
+/// Offset of the MIC inside an EAPOL-Key packet (4-byte header, then the descriptor)
+const EAPOL_MIC: usize = 81;
+
+/// An EAPOL-Key message kept for building hashes
+#[derive(Debug, Clone)]
+struct KeyMessage {
+    replay_counter: u64,
+    nonce: Vec<u8>,
+    mic: Vec<u8>,
+    /// Whole EAPOL packet with the MIC zeroed, as hashcat expects
+    eapol: Vec<u8>,
+}
+
+/// What was seen of the key exchange between one AP and one station
+#[derive(Debug, Default)]
+struct Exchange {
+    messages: [Option<KeyMessage>; 4],
+    pmkid: Option<Vec<u8>>,
+}
+
+impl Exchange {
+    fn is_complete(&self) -> bool {
+        self.messages.iter().all(Option::is_some)
+    }
+}
+
+/// Collects WPA 4-way handshakes and PMKIDs from 802.11 frames and turns
+/// them into hashcat mode 22000 lines once the network's SSID is known
+#[derive(Debug, Default)]
+pub struct HandshakeTracker {
+    ssids: HashMap<MacAddress, String>,
+    /// Keyed by (AP, station)
+    exchanges: HashMap<(MacAddress, MacAddress), Exchange>,
+    emitted: HashSet<String>,
+}
+
+impl HandshakeTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds one captured frame, returning the hash lines it made available
+    pub fn push(&mut self, datalink: i32, data: &[u8]) -> Vec<String> {
+        let data = match datalink {
+            105 => data,
+            127 => match RadiotapHeader::parse(data) {
+                Ok(radiotap) => radiotap.payload(),
+                Err(_) => return Vec::new(),
+            },
+            _ => return Vec::new(),
+        };
+        let Ok(frame) = WifiFrame::parse(data) else { return Vec::new() };
+        match frame.frame_type() {
+            WifiFrameType::Management => {
+                if let (Some(bssid), Some(ssid)) = (frame.bssid(), frame.ssid())
+                    && !ssid.is_empty()
+                    && self.ssids.insert(bssid, ssid.clone()).as_ref() != Some(&ssid)
+                {
+                    return self.ready(bssid);
+                }
+                Vec::new()
+            }
+            WifiFrameType::Data => {
+                let (Some(ap), Some(receiver), Some(transmitter)) = (frame.bssid(), frame.addr1(), frame.addr2()) else {
+                    return Vec::new();
+                };
+                let station = if transmitter == ap { receiver } else { transmitter };
+                let Some(eapol) = frame.eapol() else { return Vec::new() };
+                let Some(key) = eapol.key() else { return Vec::new() };
+                let Some(number) = key.handshake_message() else { return Vec::new() };
+
+                let mut bytes = eapol.bytes().to_vec();
+                bytes[EAPOL_MIC..EAPOL_MIC + 16].fill(0);
+                let exchange = self.exchanges.entry((ap, station)).or_default();
+                if number == 1 {
+                    exchange.pmkid = exchange.pmkid.take().or_else(|| pmkid(key.key_data()));
+                }
+                exchange.messages[number as usize - 1] = Some(KeyMessage {
+                    replay_counter: key.replay_counter(),
+                    nonce: key.nonce().to_vec(),
+                    mic: key.mic().to_vec(),
+                    eapol: bytes,
+                });
+                self.ready(ap)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Hash lines not emitted yet for the exchanges with `ap`
+    fn ready(&mut self, ap: MacAddress) -> Vec<String> {
+        let Some(ssid) = self.ssids.get(&ap) else { return Vec::new() };
+        let mut lines = Vec::new();
+        for ((exchange_ap, station), exchange) in &self.exchanges {
+            if *exchange_ap != ap {
+                continue;
+            }
+            if let Some(pmkid) = &exchange.pmkid {
+                lines.push(pmkid_line(pmkid, &ap, station, ssid));
+            }
+            if let Some(line) = eapol_line(exchange, &ap, station, ssid) {
+                lines.push(line);
+            }
+        }
+        lines.retain(|line| self.emitted.insert(line.clone()));
+        lines
+    }
+
+    /// Exchanges where all four handshake messages were captured
+    pub fn complete_handshakes(&self) -> usize {
+        self.exchanges.values().filter(|e| e.is_complete()).count()
+    }
+
+    pub fn pmkids(&self) -> usize {
+        self.exchanges.values().filter(|e| e.pmkid.is_some()).count()
+    }
+
+    pub fn ssid(&self, bssid: &MacAddress) -> Option<&str> {
+        self.ssids.get(bssid).map(String::as_str)
+    }
+}
+
+/// PMKID KDE (00-0f-ac type 4) in the key data of message 1
+fn pmkid(key_data: &[u8]) -> Option<Vec<u8>> {
+    let mut rest = key_data;
+    while rest.len() >= 2 {
+        let (id, len) = (rest[0], rest[1] as usize);
+        let element = rest.get(2..2 + len)?;
+        if id == 0xdd && element.len() == 20 && element[..4] == [0x00, 0x0f, 0xac, 0x04] {
+            let pmkid = &element[4..];
+            // Some APs send an all-zero placeholder
+            return pmkid.iter().any(|&b| b != 0).then(|| pmkid.to_vec());
+        }
+        rest = &rest[2 + len..];
+    }
+    None
+}
+
+fn mac_hex(mac: &MacAddress) -> String {
+    hex(&mac.octets())
+}
+
+/// `WPA*01*PMKID*MAC_AP*MAC_STA*ESSID***`
+pub fn pmkid_line(pmkid: &[u8], ap: &MacAddress, station: &MacAddress, ssid: &str) -> String {
+    format!("WPA*01*{}*{}*{}*{}***", hex(pmkid), mac_hex(ap), mac_hex(station), hex(ssid.as_bytes()))
+}
+
+/// `WPA*02*MIC*MAC_AP*MAC_STA*ESSID*ANONCE*EAPOL*MESSAGEPAIR` from message 2
+/// and the ANonce of a matching message 1 (pair 00) or message 3 (pair 02)
+fn eapol_line(exchange: &Exchange, ap: &MacAddress, station: &MacAddress, ssid: &str) -> Option<String> {
+    let m2 = exchange.messages[1].as_ref()?;
+    let (anonce, pair) = match (&exchange.messages[0], &exchange.messages[2]) {
+        (Some(m1), _) if m1.replay_counter == m2.replay_counter => (&m1.nonce, 0x00),
+        (_, Some(m3)) if m3.replay_counter == m2.replay_counter + 1 => (&m3.nonce, 0x02),
+        _ => return None,
+    };
+    Some(format!(
+        "WPA*02*{}*{}*{}*{}*{}*{}*{:02x}",
+        hex(&m2.mic),
+        mac_hex(ap),
+        mac_hex(station),
+        hex(ssid.as_bytes()),
+        hex(anonce),
+        hex(&m2.eapol),
+        pair
+    ))
+}
+
+/// Appends the hashes found in a capture to a hashcat 22000 file as soon
+/// as they are complete, so a live capture can be stopped at any time
+pub struct WpaExporter {
+    tracker: HandshakeTracker,
+    file: File,
+    pub written: u64,
+}
+
+impl WpaExporter {
+    pub fn create(path: &Path) -> Result<Self, CaptureError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| CaptureError::Other(format!("Cannot open {}: {}", path.display(), e)))?;
+        Ok(WpaExporter { tracker: HandshakeTracker::new(), file, written: 0 })
+    }
+
+    pub fn push(&mut self, datalink: i32, data: &[u8]) -> Result<(), CaptureError> {
+        for line in self.tracker.push(datalink, data) {
+            writeln!(self.file, "{}", line).map_err(|e| CaptureError::Other(e.to_string()))?;
+            self.written += 1;
+            let kind = if line.starts_with("WPA*01") { "PMKID" } else { "4-way handshake" };
+            info!("Exported {} hash ({} total)", kind, self.written);
+        }
+        Ok(())
+    }
+
+    pub fn tracker(&self) -> &HandshakeTracker {
+        &self.tracker
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::eapol::{KEY_INFO_ACK, KEY_INFO_INSTALL, KEY_INFO_MIC, KEY_INFO_PAIRWISE, KEY_INFO_SECURE};
+    use crate::testutil::eapol_key;
+
+    const AP: [u8; 6] = [0x02, 0, 0, 0, 0, 0x01];
+    const STA: [u8; 6] = [0x02, 0, 0, 0, 0, 0x02];
+
+    fn beacon(ssid: &str) -> Vec<u8> {
+        let mut frame = vec![0x80, 0x00, 0x00, 0x00];
+        frame.extend_from_slice(&[0xff; 6]);
+        frame.extend_from_slice(&AP);
+        frame.extend_from_slice(&AP);
+        frame.extend_from_slice(&[0; 2 + 12]);
+        frame.extend_from_slice(&[0, ssid.len() as u8]);
+        frame.extend_from_slice(ssid.as_bytes());
+        frame
+    }
+
+    fn data(from_ap: bool, eapol: &[u8]) -> Vec<u8> {
+        let (flags, addr1, addr2) = if from_ap { (0x02, STA, AP) } else { (0x01, AP, STA) };
+        let mut frame = vec![0x08, flags, 0x00, 0x00];
+        frame.extend_from_slice(&addr1);
+        frame.extend_from_slice(&addr2);
+        frame.extend_from_slice(&AP);
+        frame.extend_from_slice(&[0; 2]);
+        frame.extend_from_slice(&[0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00, 0x88, 0x8e]);
+        frame.extend_from_slice(eapol);
+        frame
+    }
+
+    #[test]
+    fn exports_handshake_and_pmkid_once_ssid_is_known() {
+        let mut pmkid_kde = vec![0xdd, 20, 0x00, 0x0f, 0xac, 0x04];
+        pmkid_kde.extend_from_slice(&[0xab; 16]);
+        let m1 = eapol_key(KEY_INFO_PAIRWISE | KEY_INFO_ACK | 2, 1, &[0xa1; 32], &[0; 16], &pmkid_kde);
+        let m2 = eapol_key(KEY_INFO_PAIRWISE | KEY_INFO_MIC | 2, 1, &[0x5e; 32], &[0x33; 16], &[0x30; 20]);
+        let m3 = eapol_key(KEY_INFO_PAIRWISE | KEY_INFO_ACK | KEY_INFO_MIC | KEY_INFO_INSTALL | KEY_INFO_SECURE | 2, 2, &[0xa1; 32], &[0x44; 16], &[0; 56]);
+        let m4 = eapol_key(KEY_INFO_PAIRWISE | KEY_INFO_MIC | KEY_INFO_SECURE | 2, 2, &[0; 32], &[0x55; 16], &[]);
+
+        let mut tracker = HandshakeTracker::new();
+        assert!(tracker.push(105, &data(true, &m1)).is_empty());
+        assert!(tracker.push(105, &data(false, &m2)).is_empty());
+        // Hashes wait for the SSID
+        let lines = tracker.push(105, &beacon("lab"));
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], format!("WPA*01*{}*020000000001*020000000002*6c6162***", "ab".repeat(16)));
+
+        let fields: Vec<&str> = lines[1].split('*').collect();
+        assert_eq!(fields[..5], ["WPA", "02", &"33".repeat(16), "020000000001", "020000000002"]);
+        assert_eq!(fields[6], "a1".repeat(32));
+        assert!(fields[7].starts_with("0203") && !fields[7].contains(&"33".repeat(16)));
+        assert_eq!(fields[8], "00");
+
+        assert!(tracker.push(105, &data(true, &m3)).is_empty());
+        assert!(tracker.push(105, &data(false, &m4)).is_empty());
+        assert_eq!(tracker.complete_handshakes(), 1);
+        assert_eq!(tracker.pmkids(), 1);
+        assert!(tracker.push(105, &beacon("lab")).is_empty());
+        assert!(tracker.push(1, &beacon("lab")).is_empty());
+        assert_eq!(tracker.ssid(&MacAddress::new(AP)), Some("lab"));
+    }
+
+    #[test]
+    fn pairs_message_two_with_message_three() {
+        let m2 = eapol_key(KEY_INFO_PAIRWISE | KEY_INFO_MIC | 2, 5, &[0x5e; 32], &[0x33; 16], &[0x30; 20]);
+        let m3 = eapol_key(KEY_INFO_PAIRWISE | KEY_INFO_ACK | KEY_INFO_MIC | KEY_INFO_INSTALL | 2, 6, &[0xa3; 32], &[0x44; 16], &[]);
+        let mut radiotap = vec![0, 0, 8, 0, 0, 0, 0, 0];
+        radiotap.extend(beacon("lab"));
+
+        let mut tracker = HandshakeTracker::new();
+        assert!(tracker.push(127, &radiotap).is_empty());
+        assert!(tracker.push(105, &data(false, &m2)).is_empty());
+        let lines = tracker.push(105, &data(true, &m3));
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].ends_with("*02"));
+        assert!(lines[0].contains(&"a3".repeat(32)));
+    }
+}